@@ -0,0 +1,166 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Stall file loading benchmarks.
+//!
+//! Measures the time, peak heap usage, and retained heap usage of loading
+//! large stall files in each format. Run with `cargo bench --bench load`,
+//! optionally followed by `-- <ENTRIES>` to set the number of entries.
+////////////////////////////////////////////////////////////////////////////////
+
+// External library imports.
+use stall::Stall;
+use stall::StallFormat;
+use temp_dir::TempDir;
+
+// Standard library imports.
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::io::BufWriter;
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CountingAlloc
+////////////////////////////////////////////////////////////////////////////////
+/// An allocator which tracks the current and peak number of allocated bytes.
+struct CountingAlloc {
+	/// The number of bytes currently allocated.
+	current: AtomicUsize,
+	/// The largest number of bytes allocated since the last reset.
+	peak: AtomicUsize,
+}
+
+impl CountingAlloc {
+	/// Resets the peak to the current number of allocated bytes, and returns
+	/// that number.
+	fn reset(&self) -> usize {
+		let current = self.current.load(Ordering::SeqCst);
+		self.peak.store(current, Ordering::SeqCst);
+		current
+	}
+
+	/// Records an allocation of the given number of bytes.
+	fn grow(&self, size: usize) {
+		let current = self.current.fetch_add(size, Ordering::SeqCst) + size;
+		let _ = self.peak.fetch_max(current, Ordering::SeqCst);
+	}
+
+	/// Returns the current and peak number of allocated bytes.
+	fn stats(&self) -> (usize, usize) {
+		(self.current.load(Ordering::SeqCst), self.peak.load(Ordering::SeqCst))
+	}
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		// SAFETY: The layout is forwarded unchanged.
+		let ptr = unsafe { System.alloc(layout) };
+		if !ptr.is_null() { self.grow(layout.size()); }
+		ptr
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		// SAFETY: The pointer and layout are forwarded unchanged.
+		unsafe { System.dealloc(ptr, layout) };
+		let _ = self.current.fetch_sub(layout.size(), Ordering::SeqCst);
+	}
+
+	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize)
+		-> *mut u8
+	{
+		// SAFETY: The arguments are forwarded unchanged.
+		let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+		if !new_ptr.is_null() {
+			let _ = self.current.fetch_sub(layout.size(), Ordering::SeqCst);
+			self.grow(new_size);
+		}
+		new_ptr
+	}
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc {
+	current: AtomicUsize::new(0),
+	peak: AtomicUsize::new(0),
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Benchmarks
+////////////////////////////////////////////////////////////////////////////////
+/// The default number of entries in the generated stall files.
+const DEFAULT_ENTRIES: usize = 50_000;
+
+fn main() {
+	let entries = std::env::args()
+		.skip(1)
+		.find_map(|arg| arg.parse().ok())
+		.unwrap_or(DEFAULT_ENTRIES);
+	let temp_dir = TempDir::new().expect("create temp dir");
+
+	let ron_path = temp_dir.path().join("stall.ron");
+	write_stall(&ron_path, entries, StallFormat::Ron);
+	bench_load("ron", &ron_path, entries);
+
+	let list_path = temp_dir.path().join(".stall");
+	write_stall(&list_path, entries, StallFormat::List);
+	bench_load("list", &list_path, entries);
+}
+
+/// Writes a stall file with the given number of entries in the given format.
+fn write_stall(path: &Path, entries: usize, format: StallFormat) {
+	let file = std::fs::File::create(path).expect("create stall file");
+	let mut out = BufWriter::new(file);
+	if format == StallFormat::Ron {
+		writeln!(out, "(version: 1, entries: {{").unwrap();
+	}
+	for i in 0..entries {
+		let remote = format!("/home/user/.config/app_{}/settings/config_{i}.toml",
+			i % 100);
+		let local = format!("app_{}/config_{i}.toml", i % 100);
+		match format {
+			StallFormat::Ron  => writeln!(out, "    {local:?}: {remote:?},"),
+			StallFormat::List => writeln!(out, "{local} -> {remote}"),
+		}.unwrap();
+	}
+	if format == StallFormat::Ron {
+		// Every entry is a member of its app's group.
+		writeln!(out, "}}, groups: {{").unwrap();
+		for app in 0..entries.min(100) {
+			write!(out, "    \"app_{app}\": [").unwrap();
+			for i in (app..entries).step_by(100) {
+				write!(out, "\"app_{app}/config_{i}.toml\", ").unwrap();
+			}
+			writeln!(out, "],").unwrap();
+		}
+		writeln!(out, "}})").unwrap();
+	}
+	out.flush().unwrap();
+}
+
+/// Loads the stall file at the given path and prints the time taken and the
+/// heap usage.
+fn bench_load(name: &str, path: &Path, entries: usize) {
+	let len = std::fs::metadata(path).expect("read metadata").len();
+	let base = ALLOC.reset();
+	let start = Instant::now();
+	let stall = Stall::read_from_path(path).expect("load stall");
+	let elapsed = start.elapsed();
+	let (current, peak) = ALLOC.stats();
+	assert_eq!(stall.entries().count(), entries);
+
+	println!("{name}: {entries} entries, {len} byte file");
+	println!("    time:     {elapsed:?}");
+	println!("    peak:     {} bytes", peak - base);
+	println!("    retained: {} bytes", current - base);
+	drop(stall);
+}
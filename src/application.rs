@@ -10,13 +10,17 @@
 
 // Internal modules.
 mod config;
+mod discovery;
 mod load_status;
+mod paths;
 mod trace;
 mod prefs;
 
 
 // Exports.
 pub use config::*;
+pub use discovery::*;
 pub use load_status::*;
+pub use paths::*;
 pub use trace::*;
 pub use prefs::*;
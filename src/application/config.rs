@@ -9,17 +9,22 @@
 
 
 // Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::suggest;
+use crate::application::config_dir;
+use crate::application::default_prefs_path;
+use crate::application::user_config_path;
 use crate::application::LoadStatus;
 use crate::application::TraceConfig;
+use crate::command::ColorOption;
+use crate::manifest::HashAlgorithm;
 
 // External library imports.
-use anyhow::Context as _;
-use anyhow::Error;
 use serde::Deserialize;
 use serde::Serialize;
 
 // Standard library imports.
-use std::convert::TryInto as _;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::BufWriter;
@@ -33,8 +38,17 @@ use std::path::PathBuf;
 ////////////////////////////////////////////////////////////////////////////////
 // Config
 ////////////////////////////////////////////////////////////////////////////////
-/// Application configuration data. Configures the logger, window, renderer,
-/// application limits, and behaviors.
+/// Application configuration data. Configures the logger, the prefs file
+/// location, and the default command options.
+///
+/// Configuration is read from the system, user, and stall directory levels
+/// (see [`ConfigLevel`].) The trace configuration and prefs path are taken
+/// from the user config, or the system config if there is no user config,
+/// while the [`Defaults`] of each level are layered, with later levels taking
+/// precedence.
+///
+/// [`ConfigLevel`]: ConfigLevel
+/// [`Defaults`]: Defaults
 #[derive(Debug, Clone)]
 #[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -47,11 +61,14 @@ pub struct Config {
 	#[serde(default = "Config::default_trace_config")]
 	pub trace_config: TraceConfig,
 	
-	/// The path for the prefs file.
+	/// The path for the prefs file. Relative paths are resolved against the
+	/// config directory.
 	#[serde(default = "Config::default_prefs_path")]
 	pub prefs_path: PathBuf,
 
-	// TODO: Stall path
+	/// The default command options.
+	#[serde(default)]
+	pub defaults: Defaults,
 }
 
 
@@ -67,17 +84,21 @@ impl Config {
 	/// [`Config`]: crate::application::Config
 	pub const DEFAULT_CONFIG_PATH: &'static str = ".stall-config";
 
-	/// The default path to look for the [`Prefs`] file, relative to the app
-	/// root.
+	/// The default path to look for the [`Prefs`] file, relative to the config
+	/// directory.
 	///
 	/// [`Prefs`]: crate::application::Prefs
-	pub const DEFAULT_PREFS_PATH: &'static str = ".stall-preferences";
+	pub const DEFAULT_PREFS_PATH: &'static str = "prefs";
 
 	/// The default path to look for the stall file.
 	///
 	/// [`Stall`]: crate::application::Stall
 	pub const DEFAULT_STALL_PATH: &'static str = ".stall";
 
+	/// The file names to look for stall files with in a stall directory, in
+	/// order of preference.
+	pub const STALL_FILE_NAMES: [&'static str; 2] = [".stall", "stall.ron"];
+
 	/// Constructs a new `Config` with the default options.
 	#[must_use]
 	pub fn new() -> Self {
@@ -85,6 +106,20 @@ impl Config {
 			load_status: LoadStatus::default(),
 			trace_config: Self::default_trace_config(),
 			prefs_path: Self::default_prefs_path(),
+			defaults: Defaults::default(),
+		}
+	}
+
+	/// Returns the path of the prefs file, if it can be determined. Relative
+	/// prefs paths are resolved against the config directory.
+	#[must_use]
+	pub fn resolved_prefs_path(&self) -> Option<PathBuf> {
+		if self.prefs_path == Path::new(Self::DEFAULT_PREFS_PATH) {
+			default_prefs_path()
+		} else if self.prefs_path.is_absolute() {
+			Some(self.prefs_path.clone())
+		} else {
+			config_dir().map(|dir| dir.join(&self.prefs_path))
 		}
 	}
 
@@ -126,39 +161,53 @@ impl Config {
 	}
 
 	/// Constructs a new `Config` with options read from the given file path.
-	pub fn read_from_path<P>(path: P) -> Result<Self, Error> 
+	pub fn read_from_path<P>(path: P) -> Result<Self, StallError> 
 		where P: AsRef<Path>
 	{
 		let path = path.as_ref();
 		let file = File::open(path)
-			.with_context(|| format!(
-				"Failed to open config file for reading: {}",
-				path.display()))?;
-		let mut config = Self::read_from_file(file)?;
+			.with_path("Failed to open config file for reading", path)?;
+		let mut config = Self::read_from_file(file)
+			.map_err(|e| e.in_file(path))?;
 		config.set_load_path(path);
 		Ok(config)
 	}
 
+	/// Constructs a new `Config` with options read from the given file path,
+	/// or returns `None` if the file does not exist.
+	pub fn read_if_exists<P>(path: P) -> Result<Option<Self>, StallError>
+		where P: AsRef<Path>
+	{
+		let path = path.as_ref();
+		if path.exists() {
+			Self::read_from_path(path).map(Some)
+		} else {
+			Ok(None)
+		}
+	}
+
 	/// Open a file at the given path and write the `Config` into it.
-	pub fn write_to_path<P>(&self, path: P) -> Result<(), Error>
+	pub fn write_to_path<P>(&self, path: P) -> Result<(), StallError>
 		where P: AsRef<Path>
 	{
 		let path = path.as_ref();
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)
+				.with_path("Failed to create config directory", parent)?;
+		}
 		let file = OpenOptions::new()
 			.write(true)
 			.truncate(true)
 			.create(true)
 			.open(path)
-			.with_context(|| format!(
-				"Failed to create/open config file for writing: {}",
-				path.display()))?;
+			.with_path("Failed to create/open config file for writing", path)?;
 		self.write_to_file(file)
-			.context("Failed to write config file")?;
+			.map_err(|e| e.in_file(path))?;
 		Ok(())
 	}
 	
 	/// Create a new file at the given path and write the `Config` into it.
-	pub fn write_to_path_if_new<P>(&self, path: P) -> Result<(), Error>
+	pub fn write_to_path_if_new<P>(&self, path: P) -> Result<(), StallError>
 		where P: AsRef<Path>
 	{
 		let path = path.as_ref();
@@ -167,17 +216,15 @@ impl Config {
 			.truncate(true)
 			.create_new(true)
 			.open(path)
-			.with_context(|| format!(
-				"Failed to create config file: {}",
-				path.display()))?;
+			.with_path("Failed to create config file", path)?;
 		self.write_to_file(file)
-			.context("Failed to write config file")?;
+			.map_err(|e| e.in_file(path))?;
 		Ok(())
 	}
 
 	/// Write the `Config` into the file is was loaded from. Returns true if the
 	/// data was written.
-	pub fn write_to_load_path(&self) -> Result<bool, Error> {
+	pub fn write_to_load_path(&self) -> Result<bool, StallError> {
 		match self.load_status.load_path() {
 			Some(path) => {
 				self.write_to_path(path)?;
@@ -189,7 +236,7 @@ impl Config {
 
 	/// Write the `Config` into a new file using the load path. Returns true
 	/// if the data was written.
-	pub fn write_to_load_path_if_new(&self) -> Result<bool, Error> {
+	pub fn write_to_load_path_if_new(&self) -> Result<bool, StallError> {
 		match self.load_status.load_path() {
 			Some(path) => {
 				self.write_to_path_if_new(path)?;
@@ -200,42 +247,42 @@ impl Config {
 	}
 
 	/// Constructs a new `Config` with options parsed from the given file.
-	pub fn read_from_file(mut file: File) -> Result<Self, Error>  {
+	pub fn read_from_file(mut file: File) -> Result<Self, StallError>  {
 		Self::parse_ron_from_file(&mut file)
 	}
 
 	/// Parses a `Config` from a file using the RON format.
-	fn parse_ron_from_file(file: &mut File) -> Result<Self, Error> {
+	fn parse_ron_from_file(file: &mut File) -> Result<Self, StallError> {
 		let len = file.metadata()
-			.context("Failed to recover file metadata.")?
+			.with_io_context("Failed to recover file metadata.")?
 			.len();
-		let mut buf = Vec::with_capacity(len.try_into()?);
+		let mut buf = Vec::with_capacity(usize::try_from(len).unwrap_or_default());
 		let _ = file.read_to_end(&mut buf)
-			.context("Failed to read config file")?;
+			.with_io_context("Failed to read config file")?;
 
 		Self::parse_ron_from_bytes(&buf[..])
 	}
 
 	/// Parses a `Config` from a buffer using the RON format.
-	fn parse_ron_from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+	fn parse_ron_from_bytes(bytes: &[u8]) -> Result<Self, StallError> {
 		use ron::de::Deserializer;
 		let mut d = Deserializer::from_bytes(bytes)
-			.context("Failed deserializing RON file")?;
+			.map_err(|e| StallError::ron_parse("Failed deserializing RON file", None, &e))?;
 		let config = Self::deserialize(&mut d)
-			.context("Failed parsing RON file")?;
+			.map_err(|e| StallError::ron_parse("Failed parsing RON file", None, &e))?;
 		d.end()
-			.context("Failed parsing RON file")?;
+			.map_err(|e| StallError::ron_parse("Failed parsing RON file", None, &e))?;
 
 		Ok(config) 
 	}
 
 	/// Write the `Config` into the given file.
-	pub fn write_to_file(&self, mut file: File) -> Result<(), Error> {
+	pub fn write_to_file(&self, mut file: File) -> Result<(), StallError> {
 		self.generate_ron_into_file(&mut file)
 	}
 
 	/// Parses a `Config` from a file using the RON format.
-	fn generate_ron_into_file(&self, file: &mut File) -> Result<(), Error> {
+	fn generate_ron_into_file(&self, file: &mut File) -> Result<(), StallError> {
 		tracing::debug!("Serializing & writing Config file.");
 		let pretty = ron::ser::PrettyConfig::new()
 			.depth_limit(2)
@@ -243,12 +290,12 @@ impl Config {
 			.enumerate_arrays(true)
 			.extensions(ron::extensions::Extensions::IMPLICIT_SOME);
 		let s = ron::ser::to_string_pretty(&self, pretty)
-			.context("Failed to serialize RON file")?;
+			.map_err(|e| StallError::ron_serialize("Failed to serialize RON file", &e))?;
 		let mut writer = BufWriter::new(file);
 		writer.write_all(s.as_bytes())
-			.context("Failed to write RON file")?;
+			.with_io_context("Failed to write RON file")?;
 		writer.flush()
-			.context("Failed to flush file buffer")
+			.with_io_context("Failed to flush file buffer")
 	}
 
 	////////////////////////////////////////////////////////////////////////////
@@ -284,7 +331,238 @@ impl std::fmt::Display for Config {
 		}
 		writeln!(fmt, "\tprefs_path: {:?}", 
 			self.prefs_path)?;
+		writeln!(fmt, "\tdefaults:")?;
+		for key in Defaults::KEYS {
+			if let Ok(Some(value)) = self.defaults.get(key) {
+				writeln!(fmt, "\t\t{key}: {value}")?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ConfigLevel
+////////////////////////////////////////////////////////////////////////////////
+/// A level at which a [`Config`] file may be placed. Defaults set at later
+/// levels take precedence over earlier levels.
+///
+/// [`Config`]: Config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(clap::ArgEnum)]
+pub enum ConfigLevel {
+	/// The system config file. This is read from the path given by the
+	/// `STALL_SYSTEM_CONFIG` environment variable if it is set, and is
+	/// otherwise `/etc/stall/config` on unix systems, or
+	/// `%ProgramData%\stall\config` on windows.
+	System,
+	/// The user config file. This is `config` in the user's config
+	/// directory, such as `~/.config/stall/config`.
+	User,
+	/// The stall config file, placed in the stall directory.
+	Stall,
+}
+
+impl ConfigLevel {
+	/// The environment variable used to override the system config path.
+	pub const SYSTEM_CONFIG_VAR: &'static str = "STALL_SYSTEM_CONFIG";
+
+	/// Returns the config file path for the level, if it can be determined.
+	/// The stall level requires a stall directory.
+	#[must_use]
+	pub fn path(self, stall_dir: Option<&Path>) -> Option<PathBuf> {
+		match self {
+			Self::System => std::env::var_os(Self::SYSTEM_CONFIG_VAR)
+				.map(PathBuf::from)
+				.or_else(system_config_path),
+			Self::User => user_config_path(),
+			Self::Stall => stall_dir
+				.map(|dir| dir.join(Config::DEFAULT_CONFIG_PATH)),
+		}
+	}
+}
+
+impl std::fmt::Display for ConfigLevel {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::System => write!(f, "system"),
+			Self::User   => write!(f, "user"),
+			Self::Stall  => write!(f, "stall"),
+		}
+	}
+}
+
+/// Returns the default system config path.
+#[cfg(windows)]
+fn system_config_path() -> Option<PathBuf> {
+	std::env::var_os("ProgramData")
+		.map(|dir| PathBuf::from(dir).join("stall").join("config"))
+}
+
+/// Returns the default system config path.
+#[cfg(not(windows))]
+#[allow(clippy::unnecessary_wraps)]
+fn system_config_path() -> Option<PathBuf> {
+	Some(PathBuf::from("/etc/stall/config"))
+}
 
+
+////////////////////////////////////////////////////////////////////////////////
+// Defaults
+////////////////////////////////////////////////////////////////////////////////
+/// Default command options. Unset options fall back to the next lower
+/// [`ConfigLevel`], and then to the built-in defaults. Options given on the
+/// command line take precedence over all levels.
+///
+/// [`ConfigLevel`]: ConfigLevel
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+	/// When to color output.
+	#[serde(default)]
+	pub color: Option<ColorOption>,
+
+	/// Whether to shorten filenames by omitting path prefixes.
+	#[serde(default)]
+	pub short_names: Option<bool>,
+
+	/// The directory to keep deleted and overwritten files in, relative to the
+	/// stall directory.
+	#[serde(default)]
+	pub backup_dir: Option<PathBuf>,
+
+	/// The algorithm to hash manifest files with.
+	#[serde(default)]
+	pub hash_algorithm: Option<HashAlgorithm>,
+
+	/// The maximum number of commands for `foreach` to run in parallel.
+	#[serde(default)]
+	pub jobs: Option<usize>,
+}
+
+impl Defaults {
+	/// The names of the configurable options.
+	pub const KEYS: [&'static str; 5] = [
+		"color",
+		"short_names",
+		"backup_dir",
+		"hash_algorithm",
+		"jobs",
+	];
+
+	/// Returns the built-in defaults, which have every option set.
+	#[must_use]
+	pub fn builtin() -> Self {
+		Self {
+			color: Some(ColorOption::Auto),
+			short_names: Some(false),
+			backup_dir: Some(PathBuf::from(crate::trash::Trash::DEFAULT_TRASH_PATH)),
+			hash_algorithm: Some(HashAlgorithm::default()),
+			jobs: Some(1),
+		}
+	}
+
+	/// Returns the `Defaults` with any options set in `other` replacing those
+	/// of `self`.
+	#[must_use]
+	pub fn layered(self, other: &Self) -> Self {
+		Self {
+			color: other.color.or(self.color),
+			short_names: other.short_names.or(self.short_names),
+			backup_dir: other.backup_dir.clone().or(self.backup_dir),
+			hash_algorithm: other.hash_algorithm.or(self.hash_algorithm),
+			jobs: other.jobs.or(self.jobs),
+		}
+	}
+
+	/// Returns the value of the option with the given key, or `None` if it is
+	/// not set.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the key is not recognized.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn get(&self, key: &str) -> Result<Option<String>, StallError> {
+		Ok(match key {
+			"color"          => self.color.map(|c| c.to_string()),
+			"short_names"    => self.short_names.map(|b| b.to_string()),
+			"backup_dir"     => self.backup_dir
+				.as_ref()
+				.map(|p| p.display().to_string()),
+			"hash_algorithm" => self.hash_algorithm.map(|h| h.to_string()),
+			"jobs"           => self.jobs.map(|n| n.to_string()),
+			_ => return Err(unknown_key(key)),
+		})
+	}
+
+	/// Sets the option with the given key, or unsets it if `value` is `None`.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the key is not recognized or the value is
+	/// invalid for the option.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn set(&mut self, key: &str, value: Option<&str>)
+		-> Result<(), StallError>
+	{
+		let invalid = |expected: &str| StallError::InvalidInput {
+			message: format!("invalid value for {key}: {:?} (expected {})",
+				value.unwrap_or_default(),
+				expected),
+		};
+		match key {
+			"color" => self.color = value
+				.map(|v| v.parse()
+					.ok()
+					.ok_or_else(|| suggest::invalid_choice(
+						key,
+						v,
+						&["auto", "always", "never"])))
+				.transpose()?,
+			"short_names" => self.short_names = value
+				.map(|v| v.parse()
+					.ok()
+					.ok_or_else(|| invalid("true or false")))
+				.transpose()?,
+			"backup_dir" => self.backup_dir = value
+				.map(|v| if v.is_empty() || Path::new(v).is_absolute() {
+					Err(invalid("a relative path"))
+				} else {
+					Ok(PathBuf::from(v))
+				})
+				.transpose()?,
+			"hash_algorithm" => self.hash_algorithm = value
+				.map(|v| <HashAlgorithm as clap::ArgEnum>::from_str(v, true)
+					.ok()
+					.ok_or_else(|| suggest::invalid_choice(
+						key,
+						v,
+						&["sha256", "sha512"])))
+				.transpose()?,
+			"jobs" => self.jobs = value
+				.map(|v| v.parse()
+					.ok()
+					.filter(|&n| n > 0)
+					.ok_or_else(|| invalid("a positive integer")))
+				.transpose()?,
+			_ => return Err(unknown_key(key)),
+		}
 		Ok(())
 	}
 }
+
+/// Returns an error for an unrecognized [`Defaults`] key.
+///
+/// [`Defaults`]: Defaults
+fn unknown_key(key: &str) -> StallError {
+	StallError::NotFound {
+		kind: "config key",
+		name: key.to_owned(),
+		suggestions: suggest::similar(key, Defaults::KEYS),
+	}
+}
@@ -0,0 +1,267 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Stall file discovery.
+////////////////////////////////////////////////////////////////////////////////
+
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::application::Config;
+use crate::application::Prefs;
+
+// External library imports.
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::ffi::OsStr;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The environment variable specifying the path of the stall file to use.
+pub const STALL_FILE_ENV: &str = "STALL_FILE";
+
+/// The environment variable specifying the stall directory to use.
+pub const STALL_DIR_ENV: &str = "STALL_DIR";
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StallLocation
+////////////////////////////////////////////////////////////////////////////////
+/// The resolved location of a stall.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StallLocation {
+	/// The stall directory containing the local files.
+	pub dir: PathBuf,
+	/// The path of the stall file.
+	pub file: PathBuf,
+}
+
+impl StallLocation {
+	/// Constructs a new `StallLocation` for the stall file in the given
+	/// directory.
+	#[must_use]
+	pub fn from_dir<P>(dir: P) -> Self
+		where P: AsRef<Path>
+	{
+		let dir = dir.as_ref();
+		Self {
+			dir: dir.to_path_buf(),
+			file: dir.join(Config::DEFAULT_STALL_PATH),
+		}
+	}
+
+	/// Constructs a new `StallLocation` for the given stall file.
+	pub fn from_file<P>(file: P) -> Result<Self, StallError>
+		where P: AsRef<Path>
+	{
+		let file = file.as_ref();
+		let dir = file.parent()
+			.ok_or_else(|| StallError::InvalidPath {
+				path: file.to_path_buf(),
+				reason: "unable to determine stall parent directory".to_owned(),
+			})?;
+		Ok(Self {
+			dir: dir.to_path_buf(),
+			file: file.to_path_buf(),
+		})
+	}
+
+	/// Constructs a new `StallLocation` for the stall file in the given
+	/// directory, using the first of the given stall file names which exists
+	/// in the directory, or the first name if none exist.
+	#[must_use]
+	pub fn in_dir<P>(dir: P, names: &[&str]) -> Self
+		where P: AsRef<Path>
+	{
+		let dir = dir.as_ref();
+		let name = names
+			.iter()
+			.find(|name| dir.join(name).is_file())
+			.or_else(|| names.first())
+			.copied()
+			.unwrap_or(Config::DEFAULT_STALL_PATH);
+		Self {
+			dir: dir.to_path_buf(),
+			file: dir.join(name),
+		}
+	}
+
+	/// Constructs a new `StallLocation` for the given stall file or directory.
+	///
+	/// An existing directory is searched for a stall file using the given
+	/// stall file names. A path which doesn't exist is a stall file if its
+	/// file name is one of the given names or has an extension, and is a
+	/// stall directory otherwise.
+	pub fn from_path<P>(path: P, names: &[&str]) -> Result<Self, StallError>
+		where P: AsRef<Path>
+	{
+		let path = path.as_ref();
+		if path.is_dir() { return Ok(Self::in_dir(path, names)); }
+		if path.is_file() { return Self::from_file(path); }
+
+		let is_file = path
+			.file_name()
+			.is_some_and(|name| names.iter().any(|n| OsStr::new(n) == name)
+				|| Path::new(name).extension().is_some());
+		if is_file {
+			Self::from_file(path)
+		} else {
+			Ok(Self::in_dir(path, names))
+		}
+	}
+
+	/// Resolves the stall location to use for a command.
+	///
+	/// The stall is located using the first of the following which is
+	/// available:
+	///
+	/// 1. The `stall` path, if given. This may be either a stall file, a
+	///    stall directory, or the name of a stall registered in the [`Prefs`].
+	///    Existing paths take precedence over registered names.
+	/// 2. The stall file given by the `STALL_FILE` environment variable.
+	/// 3. The stall directory given by the `STALL_DIR` environment variable.
+	/// 4. The nearest stall file found by searching upward from `cur_dir`.
+	/// 5. The stall file in `cur_dir`.
+	///
+	/// Stall directories are searched for a stall file using the stall file
+	/// names given by [`Prefs::stall_file_names`], and paths are resolved with
+	/// [`StallLocation::from_path`]. If `init` is true, a new stall is being
+	/// created, so only the `stall` path and `cur_dir` are considered.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if `init` is true and the stall file already
+	/// exists, or if the parent directory of a stall file cannot be
+	/// determined.
+	///
+	/// [`Prefs`]: crate::application::Prefs
+	/// [`Prefs::stall_file_names`]: crate::application::Prefs::stall_file_names
+	/// [`StallLocation::from_path`]: #method.from_path
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn resolve(
+		stall: Option<&Path>,
+		init: bool,
+		cur_dir: &Path,
+		prefs: &Prefs)
+		-> Result<Self, StallError>
+	{
+		let file_names = prefs.stall_file_names();
+		let stall = stall.map(|path| match path.to_str()
+			.and_then(|name| prefs.named_stall(name))
+		{
+			Some(named) if !path.exists() => {
+				event!(Level::DEBUG, "Using named stall {:?}: {:?}",
+					path, named);
+				named
+			},
+			_ => path,
+		});
+
+		match stall {
+			Some(path) if path.is_file() && init => {
+				return Err(StallError::Conflict {
+					reason: "file already exists".to_owned(),
+					subject: path.display().to_string(),
+					hint: None,
+				});
+			},
+			Some(path)   => return Self::from_path(path, &file_names),
+			None if init => return Ok(Self::in_dir(cur_dir, &file_names)),
+			None         => (),
+		}
+
+		if let Some(file) = env_path(STALL_FILE_ENV) {
+			event!(Level::DEBUG, "Using stall file from {}: {:?}",
+				STALL_FILE_ENV, file);
+			return Self::from_file(file);
+		}
+
+		if let Some(dir) = env_path(STALL_DIR_ENV) {
+			event!(Level::DEBUG, "Using stall directory from {}: {:?}",
+				STALL_DIR_ENV, dir);
+			return Ok(Self::in_dir(dir, &file_names));
+		}
+
+		for dir in cur_dir.ancestors() {
+			let location = Self::in_dir(dir, &file_names);
+			if location.file.is_file() {
+				event!(Level::DEBUG, "Found stall file: {:?}", location.file);
+				return Ok(location);
+			}
+		}
+
+		Ok(Self::in_dir(cur_dir, &file_names))
+	}
+
+	/// Returns the locations of the stalls nested in subdirectories of the
+	/// stall directory, found by searching for stall files with the same file
+	/// name as this stall's file. Hidden directories and symbolic links are
+	/// not searched. The locations are returned in sorted order.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if a directory cannot be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn nested(&self) -> Result<Vec<Self>, StallError> {
+		let file_name = self.file
+			.file_name()
+			.unwrap_or_else(|| OsStr::new(Config::DEFAULT_STALL_PATH));
+		let mut nested = Vec::new();
+		let mut dirs = vec![self.dir.clone()];
+		while let Some(dir) = dirs.pop() {
+			let read_dir = std::fs::read_dir(&dir)
+				.with_path("failed to read stall directory", &dir)?;
+			for entry in read_dir {
+				let entry = entry
+					.with_path("failed to read stall directory", &dir)?;
+				let hidden = entry.file_name()
+					.to_string_lossy()
+					.starts_with('.');
+				let is_dir = entry.file_type()
+					.with_path("failed to read stall directory", &dir)?
+					.is_dir();
+				if hidden || !is_dir { continue; }
+
+				let location = Self::from_dir(entry.path())
+					.with_file_name(file_name);
+				if location.file.is_file() {
+					event!(Level::DEBUG, "Found nested stall file: {:?}",
+						location.file);
+					nested.push(location);
+				}
+				dirs.push(entry.path());
+			}
+		}
+		nested.sort_by(|a, b| a.dir.cmp(&b.dir));
+		Ok(nested)
+	}
+
+	/// Returns the `StallLocation` with the stall file in the stall directory
+	/// given by `file_name`.
+	fn with_file_name(mut self, file_name: &OsStr) -> Self {
+		self.file = self.dir.join(file_name);
+		self
+	}
+}
+
+
+/// Returns the path stored in the given environment variable, if it is set and
+/// non-empty.
+fn env_path(var: &str) -> Option<PathBuf> {
+	std::env::var_os(var)
+		.filter(|value| !value.is_empty())
+		.map(PathBuf::from)
+}
@@ -9,6 +9,8 @@
 
 
 // Standard library imports.
+use std::hash::Hash as _;
+use std::hash::Hasher as _;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -24,6 +26,8 @@ pub struct LoadStatus {
 	load_path: Option<PathBuf>,
 	/// Whether the data has been modified since last save.
 	modified: bool,
+	/// The fingerprint of the file when the data was loaded, if it existed.
+	fingerprint: Option<Fingerprint>,
 }
 
 impl Default for LoadStatus {
@@ -39,6 +43,7 @@ impl LoadStatus {
 		Self {
 			load_path: None,
 			modified: false,
+			fingerprint: None,
 		}
 	}
 
@@ -87,4 +92,69 @@ impl LoadStatus {
 	pub fn set_modified(&mut self, modified: bool) {
 		self.modified = modified;
 	}
+
+	/// Returns the fingerprint of the file when the data was loaded.
+	#[must_use]
+	pub const fn fingerprint(&self) -> Option<Fingerprint> {
+		self.fingerprint
+	}
+
+	/// Sets the fingerprint of the file the data was loaded from.
+	pub fn set_fingerprint(&mut self, fingerprint: Option<Fingerprint>) {
+		self.fingerprint = fingerprint;
+	}
+
+	/// Returns `true` if the file at the load path has been changed by
+	/// another program since the data was loaded. Returns `false` if there is
+	/// no load path or fingerprint.
+	///
+	/// ### Errors
+	///
+	/// Returns an IO error if the file cannot be read.
+	pub fn changed_on_disk(&self) -> std::io::Result<bool> {
+		match (&self.load_path, self.fingerprint) {
+			(Some(path), Some(fingerprint)) => {
+				Ok(Fingerprint::read(path)? != Some(fingerprint))
+			},
+			_ => Ok(false),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Fingerprint
+////////////////////////////////////////////////////////////////////////////////
+/// The size and content hash of a file, used to detect changes made to the
+/// file by other programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+	/// The size of the file in bytes.
+	len: u64,
+	/// A hash of the file's contents.
+	hash: u64,
+}
+
+impl Fingerprint {
+	/// Returns the `Fingerprint` of the file at the given path, or `None` if
+	/// the file does not exist.
+	///
+	/// ### Errors
+	///
+	/// Returns an IO error if the file exists but cannot be read.
+	pub fn read(path: &Path) -> std::io::Result<Option<Self>> {
+		let bytes = match std::fs::read(path) {
+			Ok(bytes) => bytes,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+				return Ok(None);
+			},
+			Err(e) => return Err(e),
+		};
+		let mut hasher = std::collections::hash_map::DefaultHasher::new();
+		bytes.hash(&mut hasher);
+		Ok(Some(Self {
+			len: u64::try_from(bytes.len()).unwrap_or(u64::MAX),
+			hash: hasher.finish(),
+		}))
+	}
 }
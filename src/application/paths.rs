@@ -0,0 +1,231 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Application directories.
+//!
+//! The user config and prefs files are kept in the config directory, the
+//! trace logs in the state directory, and regenerable data in the cache
+//! directory. On unix systems these follow the XDG base directory
+//! specification:
+//!
+//! + config: `$XDG_CONFIG_HOME/stall`, or `~/.config/stall`. On macOS,
+//!   `~/Library/Application Support/stall`, and on windows,
+//!   `%APPDATA%\stall`.
+//! + state: `$XDG_STATE_HOME/stall`, or `~/.local/state/stall`. On macOS,
+//!   `~/Library/Application Support/stall/state`, and on windows,
+//!   `%LOCALAPPDATA%\stall`.
+//! + cache: `$XDG_CACHE_HOME/stall`, or `~/.cache/stall`. On macOS,
+//!   `~/Library/Caches/stall`, and on windows, `%LOCALAPPDATA%\stall\cache`.
+//!
+//! The XDG environment variables are also respected on macOS when they are
+//! set. Each stall's journal is kept in a directory for the stall within the
+//! state directory, and its state file within the cache directory, so that
+//! the stall directory only holds files worth keeping under version control.
+//! Journal and state files written into the stall directory by older versions
+//! are moved when they are next written.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::application::Config;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::platform::home_dir;
+
+// External library imports.
+use sha2::Digest as _;
+use sha2::Sha256;
+
+// Standard library imports.
+use std::fmt::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The name of the application directory within each base directory.
+const APP_DIR: &str = "stall";
+
+/// The name of the user config file within the config directory.
+const USER_CONFIG_FILE: &str = "config";
+
+/// The name of the directory holding the per-stall directories within the
+/// state and cache directories.
+const STALLS_DIR: &str = "stalls";
+
+/// The user config path used before the config directory, relative to the
+/// home directory. It is read if it exists and the new path does not.
+const LEGACY_CONFIG_PATH: &str = ".stall-config";
+
+/// The prefs path used before the config directory, relative to the home
+/// directory. It is read if it exists and the new path does not.
+const LEGACY_PREFS_PATH: &str = ".stall-preferences";
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Base directories
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the user's config directory for stall, if it can be determined.
+#[must_use]
+pub fn config_dir() -> Option<PathBuf> {
+	base_dir(
+		"XDG_CONFIG_HOME",
+		".config",
+		"Library/Application Support",
+		"APPDATA")
+}
+
+/// Returns the user's state directory for stall, if it can be determined.
+#[must_use]
+pub fn state_dir() -> Option<PathBuf> {
+	// macOS has no state directory, so state is kept in the config
+	// directory.
+	if cfg!(target_os = "macos") && env_dir("XDG_STATE_HOME").is_none() {
+		return config_dir().map(|dir| dir.join("state"));
+	}
+	base_dir("XDG_STATE_HOME", ".local/state", "", "LOCALAPPDATA")
+}
+
+/// Returns the user's cache directory for stall, if it can be determined.
+#[must_use]
+pub fn cache_dir() -> Option<PathBuf> {
+	if cfg!(windows) {
+		return env_dir("LOCALAPPDATA")
+			.map(|dir| dir.join(APP_DIR).join("cache"));
+	}
+	base_dir(
+		"XDG_CACHE_HOME",
+		".cache",
+		"Library/Caches",
+		"LOCALAPPDATA")
+}
+
+/// Returns the directory for the stall in the given directory within the
+/// state directory, if it can be determined.
+#[must_use]
+pub fn stall_state_dir(stall_dir: &Path) -> Option<PathBuf> {
+	state_dir().map(|dir| dir.join(STALLS_DIR).join(stall_key(stall_dir)))
+}
+
+/// Returns the directory for the stall in the given directory within the
+/// cache directory, if it can be determined.
+#[must_use]
+pub fn stall_cache_dir(stall_dir: &Path) -> Option<PathBuf> {
+	cache_dir().map(|dir| dir.join(STALLS_DIR).join(stall_key(stall_dir)))
+}
+
+/// Returns the name of the per-stall directory of the stall in the given
+/// directory. The name is the stall directory's name followed by a hash of
+/// its canonical path, so that stalls with the same name don't share files.
+fn stall_key(stall_dir: &Path) -> String {
+	let stall_dir = stall_dir
+		.canonicalize()
+		.unwrap_or_else(|_| stall_dir.to_path_buf());
+	let digest = Sha256::digest(stall_dir.as_os_str().to_string_lossy()
+		.as_bytes());
+	let mut key = stall_dir
+		.file_name()
+		.map(|name| name.to_string_lossy().into_owned())
+		.unwrap_or_default();
+	key.push('-');
+	for byte in &digest[..8] {
+		let _ = write!(key, "{byte:02x}");
+	}
+	key
+}
+
+/// Returns the application directory within a base directory. The XDG
+/// environment variable is used on unix systems if it is set, falling back to
+/// the given home-relative directories. Windows uses the given environment
+/// variable instead.
+fn base_dir(xdg_var: &str, unix: &str, macos: &str, windows_var: &str)
+	-> Option<PathBuf>
+{
+	let base = if cfg!(windows) {
+		env_dir(windows_var)
+	} else {
+		let home_base = if cfg!(target_os = "macos") { macos } else { unix };
+		env_dir(xdg_var).or_else(|| home_dir().map(|home| home.join(home_base)))
+	};
+	base.map(|dir| dir.join(APP_DIR))
+}
+
+/// Returns the directory in the given environment variable, if it is set to
+/// an absolute path. Relative paths are ignored, as required by the XDG base
+/// directory specification.
+fn env_dir(var: &str) -> Option<PathBuf> {
+	std::env::var_os(var)
+		.map(PathBuf::from)
+		.filter(|dir| dir.is_absolute())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Files
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the path of the user config file, if it can be determined.
+///
+/// This is `config` in the config directory, unless only the legacy
+/// `~/.stall-config` file exists.
+#[must_use]
+pub fn user_config_path() -> Option<PathBuf> {
+	with_legacy(
+		config_dir().map(|dir| dir.join(USER_CONFIG_FILE)),
+		LEGACY_CONFIG_PATH)
+}
+
+/// Returns the default path of the prefs file, if it can be determined.
+///
+/// This is `prefs` in the config directory, unless only the legacy
+/// `~/.stall-preferences` file exists.
+#[must_use]
+pub fn default_prefs_path() -> Option<PathBuf> {
+	with_legacy(
+		config_dir().map(|dir| dir.join(Config::DEFAULT_PREFS_PATH)),
+		LEGACY_PREFS_PATH)
+}
+
+/// Moves a per-stall file from its legacy path in the stall directory to the
+/// given path, if only the legacy file exists. The parent directories of the
+/// path are created if they don't exist.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the directories can't be created or the file
+/// can't be moved.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn migrate_stall_file(path: &Path, legacy: &Path) -> Result<(), StallError> {
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent)
+			.with_path("failed to create directory", parent)?;
+	}
+	if path == legacy || path.exists() || !legacy.exists() { return Ok(()); }
+
+	// The state and cache directories may be on another file system, in
+	// which case the file is copied instead.
+	if std::fs::rename(legacy, path).is_err() {
+		let _ = std::fs::copy(legacy, path)
+			.with_path("failed to move file", legacy)?;
+		std::fs::remove_file(legacy)
+			.with_path("failed to remove file", legacy)?;
+	}
+	Ok(())
+}
+
+/// Returns the given path, or the legacy path in the home directory if only
+/// the legacy file exists.
+fn with_legacy(path: Option<PathBuf>, legacy: &str) -> Option<PathBuf> {
+	let legacy = home_dir()
+		.map(|home| home.join(legacy))
+		.filter(|legacy| legacy.exists());
+	match (path, legacy) {
+		(Some(path), Some(legacy)) if !path.exists() => Some(legacy),
+		(None, legacy) => legacy,
+		(path, _) => path,
+	}
+}
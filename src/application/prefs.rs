@@ -9,22 +9,34 @@
 
 
 // Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::suggest;
+use crate::application::Config;
 use crate::application::LoadStatus;
+use crate::application::LogLocation;
+use crate::entry::BufferSize;
+use crate::entry::ConflictPolicy;
+use crate::entry::Retry;
+use crate::entry::Throttle;
+use crate::mergetool::MergeTool;
+use crate::notify::Notifier;
+use crate::privilege::Escalation;
+use crate::versions::Retention;
 
 // External library imports.
-use anyhow::Context as _;
-use anyhow::Error;
 use serde::Deserialize;
 use serde::Serialize;
 
 // Standard library imports.
-use std::convert::TryInto as _;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::BufWriter;
-use std::io::Read as _;
+use std::io::Read;
 use std::io::Write as _;
 use std::path::Path;
+use std::path::PathBuf;
 
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -38,6 +50,65 @@ pub struct Prefs {
 	/// The Prefs file's load status.
 	#[serde(skip)]
 	load_status: LoadStatus,
+
+	/// Registered stall paths, by name.
+	#[serde(default)]
+	stalls: BTreeMap<String, PathBuf>,
+
+	/// The file names to look for stall files with, if not the default names.
+	#[serde(default)]
+	stall_files: Vec<String>,
+
+	/// Whether `distribute` creates missing remote directories by default.
+	#[serde(default)]
+	create_dirs: bool,
+
+	/// The default copy rate limit for `collect` and `distribute`, in
+	/// megabytes per second.
+	#[serde(default)]
+	throttle: Option<f64>,
+
+	/// The size of the buffer for copying file contents in-process, if not
+	/// the default.
+	#[serde(default)]
+	copy_buffer: Option<BufferSize>,
+
+	/// The policy for retrying copies which fail with transient errors, if
+	/// not the default.
+	#[serde(default)]
+	retry: Option<Retry>,
+
+	/// Where to write the trace log, if anywhere.
+	#[serde(default)]
+	trace_log: Option<LogLocation>,
+
+	/// The policy for resolving conflicts of entries without a policy.
+	#[serde(default)]
+	conflict_policy: ConflictPolicy,
+
+	/// The merge tool for conflicts with the `merge` policy: the name of a
+	/// known tool, or a command template.
+	#[serde(default)]
+	merge_tool: Option<String>,
+
+	/// The policy for keeping previous versions of stalled files overwritten
+	/// by `collect`, if any.
+	#[serde(default)]
+	version_retention: Option<Retention>,
+
+	/// The command used to run privileged copies, if not `sudo`.
+	#[serde(default)]
+	escalation: Option<String>,
+
+	/// How commands run with `--notify` report failures, if not with a
+	/// desktop notification.
+	#[serde(default)]
+	notify: Option<String>,
+
+	/// Glob patterns for additional files to check for with
+	/// `status --remote-missing`.
+	#[serde(default)]
+	scan_paths: Vec<String>,
 }
 
 impl Default for Prefs {
@@ -47,14 +118,518 @@ impl Default for Prefs {
 }
 
 impl Prefs {
+	/// The keys of the preferences which may be set, other than registered
+	/// stall names.
+	pub const KEYS: [&'static str; 12] = [
+		"stall_files",
+		"create_dirs",
+		"throttle",
+		"copy_buffer",
+		"retry",
+		"trace_log",
+		"conflict_policy",
+		"merge_tool",
+		"version_retention",
+		"escalation",
+		"notify",
+		"scan_paths",
+	];
+
+	/// The contents of a new prefs file, with comments describing each
+	/// preference.
+	pub const TEMPLATE: &'static str = "\
+// Stall preferences.
+//
+// This file is written in RON format. See https://github.com/ron-rs/ron for
+// details. Preferences may also be set with `stall prefs set KEY VALUE`.
+(
+	// Registered stall paths, by name. A registered stall can be selected with
+	// `--stall NAME`, or registered with `stall stalls add NAME PATH`.
+	stalls: {
+		// \"dotfiles\": \"/home/user/dotfiles\",
+	},
+
+	// The file names to look for stall files with in stall directories, in
+	// order of preference, e.g. `[\"stall.ron\", \".stall\"]`. `init` creates
+	// stall files with the first name. Defaults to `.stall` and `stall.ron`.
+	stall_files: [],
+
+	// Whether `distribute` creates missing remote directories by default.
+	create_dirs: false,
+
+	// The default copy rate limit for `collect` and `distribute`, in megabytes
+	// per second, e.g. `Some(20.0)`.
+	throttle: None,
+
+	// The size of the buffer for copying file contents when they can't be
+	// copied by the kernel, or when copies are throttled, e.g. `Some(\"4M\")`.
+	// Defaults to 1M.
+	copy_buffer: None,
+
+	// How many times copies which fail with transient errors, such as network
+	// file system hiccups, are retried, optionally followed by `x` and the
+	// delay before the first retry, which doubles for each further retry,
+	// e.g. `Some(\"5x2s\")`. Defaults to 3x1s.
+	retry: None,
+
+	// Where to write a log of stall operations, if anywhere: `Some(stall)` for
+	// the stall directory, or `Some(state)` for the user's state directory.
+	trace_log: None,
+
+	// How `collect` and `distribute` copy an entry's files when both have been
+	// modified since they were last copied: \"prefer-local\", \"prefer-remote\",
+	// \"newest-wins\", \"prompt\", \"merge\", or \"fail\".
+	conflict_policy: \"newest-wins\",
+
+	// The merge tool for the \"merge\" conflict policy, e.g. `Some(\"meld\")`:
+	// \"meld\", \"vimdiff\", \"kdiff3\", or a command using the `{local}`,
+	// `{remote}`, `{base}`, and `{merged}` placeholders.
+	merge_tool: None,
+
+	// Which previous versions of stalled files overwritten by `collect` are
+	// kept under `.stall-versions` in the stall directory: a number of
+	// versions, e.g. `Some(\"10\")`, or a number of days, e.g. `Some(\"30d\")`.
+	version_retention: None,
+
+	// The command used by `distribute` to copy the files of privileged entries
+	// with elevated privileges, e.g. `Some(\"doas\")`. Defaults to `sudo`.
+	escalation: None,
+
+	// How commands run with `--notify`, such as scheduled commands, report
+	// errors and conflicts: \"desktop\" for a desktop notification, or a
+	// command using the `{title}` and `{message}` placeholders, e.g.
+	// `Some(\"mail -s {title} me@example.com\")`. Defaults to \"desktop\".
+	notify: None,
+
+	// Glob patterns for files to check for with `stall status --remote-missing`
+	// in addition to the common config file locations, e.g. `[\"~/.emacs.d\"]`.
+	// Relative patterns are relative to the home directory.
+	scan_paths: [],
+)
+";
+
 	/// Constructs a new `Prefs` with the default options.
 	#[must_use]
 	pub fn new() -> Self {
 		Self {
 			load_status: LoadStatus::default(),
+			stalls: BTreeMap::new(),
+			stall_files: Vec::new(),
+			create_dirs: false,
+			throttle: None,
+			copy_buffer: None,
+			retry: None,
+			trace_log: None,
+			conflict_policy: ConflictPolicy::default(),
+			merge_tool: None,
+			version_retention: None,
+			escalation: None,
+			notify: None,
+			scan_paths: Vec::new(),
 		}
 	}
 
+	////////////////////////////////////////////////////////////////////////////
+	// Named stall methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Returns the path of the stall registered with the given name.
+	#[must_use]
+	pub fn named_stall(&self, name: &str) -> Option<&Path> {
+		self.stalls.get(name).map(PathBuf::as_path)
+	}
+
+	/// Returns an iterator over the registered stall names and paths.
+	pub fn named_stalls(&self) -> impl Iterator<Item=(&str, &Path)> {
+		self.stalls
+			.iter()
+			.map(|(name, path)| (name.as_str(), path.as_path()))
+	}
+
+	/// Registers a stall path with the given name, returning the previously
+	/// registered path, if any.
+	pub fn insert_named_stall<S, P>(&mut self, name: S, path: P)
+		-> Option<PathBuf>
+		where
+			S: Into<String>,
+			P: Into<PathBuf>,
+	{
+		self.set_modified(true);
+		self.stalls.insert(name.into(), path.into())
+	}
+
+	/// Unregisters the stall with the given name, returning its path.
+	pub fn remove_named_stall(&mut self, name: &str) -> Option<PathBuf> {
+		let removed = self.stalls.remove(name);
+		if removed.is_some() { self.set_modified(true); }
+		removed
+	}
+
+	////////////////////////////////////////////////////////////////////////////
+	// Key-value methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Sets the preference with the given key from its string value. The
+	/// supported keys are:
+	///
+	/// + `stall_files`: A comma-separated list of file names, or an empty
+	///   string.
+	/// + `create_dirs`: `true` or `false`.
+	/// + `throttle`: A rate in megabytes per second, or `off`.
+	/// + `copy_buffer`: A number of bytes, optionally followed by `K` or `M`,
+	///   or `default`.
+	/// + `retry`: A number of retries, optionally followed by `x` and a delay
+	///   in `s` or `ms`, or `default`.
+	/// + `trace_log`: `stall`, `state`, or `off`.
+	/// + `conflict_policy`: `prefer-local`, `prefer-remote`, `newest-wins`,
+	///   `prompt`, `merge`, or `fail`.
+	/// + `merge_tool`: `meld`, `vimdiff`, `kdiff3`, a command containing
+	///   `{merged}`, or `off`.
+	/// + `version_retention`: A number of versions, a number of days followed
+	///   by `d`, or `off`.
+	/// + `escalation`: `sudo`, `doas`, or another command which runs its
+	///   arguments as root.
+	/// + `notify`: `desktop`, or a command using the `{title}` and `{message}`
+	///   placeholders.
+	/// + `scan_paths`: A comma-separated list of glob patterns, or an empty
+	///   string.
+	/// + `stalls.<NAME>`: The absolute path of the stall to register as
+	///   `NAME`.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the key is not recognized or the value is
+	/// invalid for the preference.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn set(&mut self, key: &str, value: &str) -> Result<(), StallError> {
+		let invalid = |expected: &str| StallError::InvalidInput {
+			message: format!("invalid value for {key}: {value:?} \
+				(expected {expected})"),
+		};
+		if key == "stall_files" {
+			let names: Vec<String> = value
+				.split(',')
+				.map(str::trim)
+				.filter(|name| !name.is_empty())
+				.map(str::to_owned)
+				.collect();
+			if names.iter().any(|name| name.contains(['/', '\\'])) {
+				return Err(invalid("a comma-separated list of file names"));
+			}
+			self.set_stall_files(names);
+		} else if key == "create_dirs" {
+			let create_dirs = value
+				.parse()
+				.ok()
+				.ok_or_else(|| invalid("true or false"))?;
+			self.set_create_dirs(create_dirs);
+		} else if key == "throttle" {
+			let throttle = match value {
+				"off" => None,
+				_ => Some(value
+					.parse()
+					.ok()
+					.ok_or_else(|| invalid("a positive number or off"))?),
+			};
+			self.set_throttle(throttle);
+		} else if key == "copy_buffer" {
+			self.set_copy_buffer(parse_optional(value, "default")?);
+		} else if key == "retry" {
+			self.set_retry(parse_optional(value, "default")?);
+		} else if key == "trace_log" {
+			let trace_log = match value {
+				"stall" => Some(LogLocation::Stall),
+				"state" => Some(LogLocation::State),
+				"off"   => None,
+				_ => return Err(suggest::invalid_choice(
+					key,
+					value,
+					&["stall", "state", "off"])),
+			};
+			self.set_trace_log(trace_log);
+		} else if key == "conflict_policy" {
+			let policy = ConflictPolicy::try_from(value.to_owned())
+				.ok()
+				.ok_or_else(|| suggest::invalid_choice(
+					key,
+					value,
+					&["prefer-local", "prefer-remote", "newest-wins",
+						"prompt", "merge", "fail"]))?;
+			self.set_conflict_policy(policy);
+		} else if key == "merge_tool" {
+			let merge_tool = match value {
+				"off" => None,
+				_ => {
+					let _ = value
+						.parse::<MergeTool>()
+						.map_err(|message| StallError::InvalidInput {
+							message,
+						})?;
+					Some(value.to_owned())
+				},
+			};
+			self.set_merge_tool(merge_tool);
+		} else if key == "version_retention" {
+			self.set_version_retention(parse_optional(value, "off")?);
+		} else if key == "escalation" {
+			let escalation = value
+				.parse::<Escalation>()
+				.map_err(|message| StallError::InvalidInput { message })?;
+			self.set_escalation(&escalation);
+		} else if key == "notify" {
+			let notifier = value
+				.parse::<Notifier>()
+				.map_err(|message| StallError::InvalidInput { message })?;
+			self.set_notifier(&notifier);
+		} else if key == "scan_paths" {
+			self.set_scan_paths(value
+				.split(',')
+				.map(str::trim)
+				.filter(|pattern| !pattern.is_empty())
+				.map(str::to_owned)
+				.collect());
+		} else if let Some(name) = key.strip_prefix("stalls.") {
+			self.set_named_stall(name, value)?;
+		} else {
+			return Err(StallError::NotFound {
+				kind: "prefs key",
+				name: key.to_owned(),
+				suggestions: suggest::similar(key, Self::KEYS),
+			});
+		}
+		Ok(())
+	}
+
+	/// Registers the stall path given by the string value with the given name.
+	fn set_named_stall(&mut self, name: &str, value: &str)
+		-> Result<(), StallError>
+	{
+		if name.is_empty() || name.contains(['/', '\\']) {
+			return Err(StallError::InvalidInput {
+				message: format!("invalid stall name: {name:?}"),
+			});
+		}
+		let path = PathBuf::from(value);
+		if !path.is_absolute() {
+			return Err(StallError::InvalidInput {
+				message: format!("invalid value for stalls.{name}: {value:?} \
+					(expected an absolute path)"),
+			});
+		}
+		let _ = self.insert_named_stall(name, path);
+		Ok(())
+	}
+
+	////////////////////////////////////////////////////////////////////////////
+	// Stall file methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Returns the file names to look for stall files with in a stall
+	/// directory, in order of preference. The first name is used to create new
+	/// stall files.
+	#[must_use]
+	pub fn stall_file_names(&self) -> Vec<&str> {
+		if self.stall_files.is_empty() {
+			Config::STALL_FILE_NAMES.to_vec()
+		} else {
+			self.stall_files.iter().map(String::as_str).collect()
+		}
+	}
+
+	/// Sets the file names to look for stall files with. If empty, the
+	/// default names are used.
+	pub fn set_stall_files(&mut self, stall_files: Vec<String>) {
+		self.stall_files = stall_files;
+		self.set_modified(true);
+	}
+
+	////////////////////////////////////////////////////////////////////////////
+	// Distribute methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Returns true if `distribute` should create missing remote directories
+	/// by default.
+	#[must_use]
+	pub const fn create_dirs(&self) -> bool {
+		self.create_dirs
+	}
+
+	/// Sets whether `distribute` should create missing remote directories by
+	/// default.
+	pub fn set_create_dirs(&mut self, create_dirs: bool) {
+		self.create_dirs = create_dirs;
+		self.set_modified(true);
+	}
+
+	////////////////////////////////////////////////////////////////////////////
+	// Throttle methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Returns the default copy rate limit for `collect` and `distribute`.
+	#[must_use]
+	pub fn throttle(&self) -> Option<Throttle> {
+		self.throttle.and_then(Throttle::from_megabytes)
+	}
+
+	/// Sets the default copy rate limit for `collect` and `distribute`.
+	pub fn set_throttle(&mut self, throttle: Option<Throttle>) {
+		self.throttle = throttle.map(Throttle::megabytes_per_second);
+		self.set_modified(true);
+	}
+
+	/// Returns the size of the buffer for copying file contents in-process.
+	#[must_use]
+	pub fn copy_buffer(&self) -> BufferSize {
+		self.copy_buffer.unwrap_or_default()
+	}
+
+	/// Sets the size of the buffer for copying file contents in-process. If
+	/// `None`, the default size is used.
+	pub fn set_copy_buffer(&mut self, buffer_size: Option<BufferSize>) {
+		self.copy_buffer = buffer_size;
+		self.set_modified(true);
+	}
+
+	/// Returns the policy for retrying copies which fail with transient
+	/// errors.
+	#[must_use]
+	pub fn retry(&self) -> Retry {
+		self.retry.unwrap_or_default()
+	}
+
+	/// Sets the policy for retrying copies which fail with transient errors.
+	/// If `None`, the default policy is used.
+	pub fn set_retry(&mut self, retry: Option<Retry>) {
+		self.retry = retry;
+		self.set_modified(true);
+	}
+
+	////////////////////////////////////////////////////////////////////////////
+	// Conflict policy methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Returns the policy for resolving conflicts of entries without a policy.
+	#[must_use]
+	pub const fn conflict_policy(&self) -> ConflictPolicy {
+		self.conflict_policy
+	}
+
+	/// Sets the policy for resolving conflicts of entries without a policy.
+	pub fn set_conflict_policy(&mut self, policy: ConflictPolicy) {
+		self.conflict_policy = policy;
+		self.set_modified(true);
+	}
+
+	/// Returns the merge tool for conflicts with the `merge` policy, if one is
+	/// configured and valid.
+	#[must_use]
+	pub fn merge_tool(&self) -> Option<MergeTool> {
+		self.merge_tool
+			.as_deref()
+			.and_then(|tool| tool.parse().ok())
+	}
+
+	/// Sets the merge tool for conflicts with the `merge` policy.
+	pub fn set_merge_tool(&mut self, merge_tool: Option<String>) {
+		self.merge_tool = merge_tool;
+		self.set_modified(true);
+	}
+
+	////////////////////////////////////////////////////////////////////////////
+	// Version methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Returns the policy for keeping previous versions of stalled files
+	/// overwritten by `collect`, if any.
+	#[must_use]
+	pub const fn version_retention(&self) -> Option<Retention> {
+		self.version_retention
+	}
+
+	/// Sets the policy for keeping previous versions of stalled files
+	/// overwritten by `collect`.
+	pub fn set_version_retention(&mut self, retention: Option<Retention>) {
+		self.version_retention = retention;
+		self.set_modified(true);
+	}
+
+	////////////////////////////////////////////////////////////////////////////
+	// Escalation methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Returns the command used to copy files with elevated privileges.
+	#[must_use]
+	pub fn escalation(&self) -> Escalation {
+		self.escalation
+			.as_deref()
+			.and_then(|escalation| escalation.parse().ok())
+			.unwrap_or_default()
+	}
+
+	/// Sets the command used to copy files with elevated privileges.
+	pub fn set_escalation(&mut self, escalation: &Escalation) {
+		self.escalation = (*escalation != Escalation::default())
+			.then(|| escalation.to_string());
+		self.set_modified(true);
+	}
+
+	////////////////////////////////////////////////////////////////////////////
+	// Notify methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Returns the notifier used to report failures of commands run with
+	/// `--notify`.
+	#[must_use]
+	pub fn notifier(&self) -> Notifier {
+		self.notify
+			.as_deref()
+			.and_then(|notify| notify.parse().ok())
+			.unwrap_or_default()
+	}
+
+	/// Sets the notifier used to report failures of commands run with
+	/// `--notify`.
+	pub fn set_notifier(&mut self, notifier: &Notifier) {
+		self.notify = (*notifier != Notifier::default())
+			.then(|| notifier.to_string());
+		self.set_modified(true);
+	}
+
+	////////////////////////////////////////////////////////////////////////////
+	// Scan path methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Returns the glob patterns for additional files to check for with
+	/// `status --remote-missing`.
+	#[must_use]
+	pub fn scan_paths(&self) -> &[String] {
+		&self.scan_paths
+	}
+
+	/// Sets the glob patterns for additional files to check for with
+	/// `status --remote-missing`.
+	pub fn set_scan_paths(&mut self, scan_paths: Vec<String>) {
+		self.scan_paths = scan_paths;
+		self.set_modified(true);
+	}
+
+	////////////////////////////////////////////////////////////////////////////
+	// Trace log methods.
+	////////////////////////////////////////////////////////////////////////////
+
+	/// Returns where to write the trace log, if anywhere.
+	#[must_use]
+	pub const fn trace_log(&self) -> Option<LogLocation> {
+		self.trace_log
+	}
+
+	/// Sets where to write the trace log.
+	pub fn set_trace_log(&mut self, trace_log: Option<LogLocation>) {
+		self.trace_log = trace_log;
+		self.set_modified(true);
+	}
+
 	////////////////////////////////////////////////////////////////////////////
 	// File and serialization methods.
 	////////////////////////////////////////////////////////////////////////////
@@ -94,41 +669,42 @@ impl Prefs {
 
 	/// Constructs a new `Prefs` with options read from the given file path.
 	#[tracing::instrument(skip_all, err)]
-	pub fn read_from_path<P>(path: P) -> Result<Self, Error> 
+	pub fn read_from_path<P>(path: P) -> Result<Self, StallError> 
 		where P: AsRef<Path>
 	{
 		let path = path.as_ref();
-		let file = File::open(path)
-			.with_context(|| format!(
-				"Failed to open prefs file for reading: {}",
-				path.display()))?;
-		let mut prefs = Self::read_from_file(file)?;
+		let bytes = std::fs::read(path)
+			.with_path("Failed to read prefs file", path)?;
+		let mut prefs = Self::parse_ron_from_bytes(&bytes[..])
+			.map_err(|e| e.in_file(path))?;
 		prefs.set_load_path(path);
 		Ok(prefs)
 	}
 
 	/// Open a file at the given path and write the `Prefs` into it.
 	#[tracing::instrument(skip_all, err)]
-	pub fn write_to_path<P>(&self, path: P) -> Result<(), Error>
+	pub fn write_to_path<P>(&self, path: P) -> Result<(), StallError>
 		where P: AsRef<Path>
 	{
 		let path = path.as_ref();
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)
+				.with_path("Failed to create prefs directory", parent)?;
+		}
 		let file = OpenOptions::new()
 			.write(true)
 			.truncate(true)
 			.create(true)
 			.open(path)
-			.with_context(|| format!(
-				"Failed to create/open prefs file for writing: {}",
-				path.display()))?;
+			.with_path("Failed to create/open prefs file for writing", path)?;
 		self.write_to_file(file)
-			.context("Failed to write prefs file")?;
+			.map_err(|e| e.in_file(path))?;
 		Ok(())
 	}
 	
 	/// Create a new file at the given path and write the `Prefs` into it.
 	#[tracing::instrument(skip_all, err)]
-	pub fn write_to_path_if_new<P>(&self, path: P) -> Result<(), Error>
+	pub fn write_to_path_if_new<P>(&self, path: P) -> Result<(), StallError>
 		where P: AsRef<Path>
 	{
 		let path = path.as_ref();
@@ -137,18 +713,16 @@ impl Prefs {
 			.truncate(true)
 			.create_new(true)
 			.open(path)
-			.with_context(|| format!(
-				"Failed to create prefs file: {}",
-				path.display()))?;
+			.with_path("Failed to create prefs file", path)?;
 		self.write_to_file(file)
-			.context("Failed to write prefs file")?;
+			.map_err(|e| e.in_file(path))?;
 		Ok(())
 	}
 
 	/// Write the `Prefs` into the file is was loaded from. Returns true if the
 	/// data was written.
 	#[tracing::instrument(skip_all, err)]
-	pub fn write_to_load_path(&self) -> Result<bool, Error> {
+	pub fn write_to_load_path(&self) -> Result<bool, StallError> {
 		match self.load_status.load_path() {
 			Some(path) => {
 				self.write_to_path(path)?;
@@ -161,7 +735,7 @@ impl Prefs {
 	/// Write the `Prefs` into a new file using the load path. Returns true
 	/// if the data was written.
 	#[tracing::instrument(skip_all, err)]
-	pub fn write_to_load_path_if_new(&self) -> Result<bool, Error> {
+	pub fn write_to_load_path_if_new(&self) -> Result<bool, StallError> {
 		match self.load_status.load_path() {
 			Some(path) => {
 				self.write_to_path_if_new(path)?;
@@ -173,46 +747,45 @@ impl Prefs {
 
 	/// Constructs a new `Prefs` with options parsed from the given file.
 	#[tracing::instrument(skip_all, err)]
-	pub fn read_from_file(mut file: File) -> Result<Self, Error>  {
-		Self::parse_ron_from_file(&mut file)
+	pub fn read_from_file(file: File) -> Result<Self, StallError>  {
+		Self::parse_ron_from_reader(file)
 	}
 
-	/// Parses a `Prefs` from a file using the RON format.
+	/// Parses a `Prefs` from a reader using the RON format.
 	#[tracing::instrument(skip_all, err)]
-	fn parse_ron_from_file(file: &mut File) -> Result<Self, Error> {
-		let len = file.metadata()
-			.context("Failed to recover file metadata.")?
-			.len();
-		let mut buf = Vec::with_capacity(len.try_into()?);
-		let _ = file.read_to_end(&mut buf)
-			.context("Failed to read prefs file")?;
+	fn parse_ron_from_reader<R>(mut reader: R) -> Result<Self, StallError>
+		where R: Read
+	{
+		let mut buf = Vec::new();
+		let _ = reader.read_to_end(&mut buf)
+			.with_io_context("Failed to read prefs file")?;
 
 		Self::parse_ron_from_bytes(&buf[..])
 	}
 
 	/// Parses a `Prefs` from a buffer using the RON format.
 	#[tracing::instrument(skip_all, err)]
-	fn parse_ron_from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+	fn parse_ron_from_bytes(bytes: &[u8]) -> Result<Self, StallError> {
 		use ron::de::Deserializer;
 		let mut d = Deserializer::from_bytes(bytes)
-			.context("Failed deserializing RON file")?;
+			.map_err(|e| StallError::ron_parse("Failed deserializing RON file", None, &e))?;
 		let prefs = Self::deserialize(&mut d)
-			.context("Failed parsing RON file")?;
+			.map_err(|e| StallError::ron_parse("Failed parsing RON file", None, &e))?;
 		d.end()
-			.context("Failed parsing RON file")?;
+			.map_err(|e| StallError::ron_parse("Failed parsing RON file", None, &e))?;
 
 		Ok(prefs)
 	}
 
 	/// Write the `Prefs` into the given file.
 	#[tracing::instrument(skip_all, err)]
-	pub fn write_to_file(&self, mut file: File) -> Result<(), Error> {
+	pub fn write_to_file(&self, mut file: File) -> Result<(), StallError> {
 		self.generate_ron_into_file(&mut file)
 	}
 
 	/// Parses a `Prefs` from a file using the RON format.
 	#[tracing::instrument(skip_all, err)]
-	fn generate_ron_into_file(&self, file: &mut File) -> Result<(), Error> {
+	fn generate_ron_into_file(&self, file: &mut File) -> Result<(), StallError> {
 		tracing::debug!("Serializing & writing Prefs file.");
 		let pretty = ron::ser::PrettyConfig::new()
 			.depth_limit(2)
@@ -220,12 +793,12 @@ impl Prefs {
 			.enumerate_arrays(true)
 			.extensions(ron::extensions::Extensions::IMPLICIT_SOME);
 		let s = ron::ser::to_string_pretty(&self, pretty)
-			.context("Failed to serialize RON file")?;
+			.map_err(|e| StallError::ron_serialize("Failed to serialize RON file", &e))?;
 		let mut writer = BufWriter::new(file);
 		writer.write_all(s.as_bytes())
-			.context("Failed to write RON file")?;
+			.with_io_context("Failed to write RON file")?;
 		writer.flush()
-			.context("Failed to flush file buffer")
+			.with_io_context("Failed to flush file buffer")
 	}
 
 	////////////////////////////////////////////////////////////////////////////
@@ -240,3 +813,16 @@ impl std::fmt::Display for Prefs {
 	}
 }
 
+
+/// Parses an optional preference value, which is `None` if the value is the
+/// given `unset` keyword.
+fn parse_optional<T>(value: &str, unset: &str)
+	-> Result<Option<T>, StallError>
+	where T: std::str::FromStr<Err=String>
+{
+	if value == unset { return Ok(None); }
+	value
+		.parse()
+		.map(Some)
+		.map_err(|message| StallError::InvalidInput { message })
+}
@@ -8,16 +8,21 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 
+// Internal library imports.
+use crate::application::state_dir;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+
 // External library imports.
-use anyhow::Context as _;
-use anyhow::Error;
 use serde::Deserialize;
 use serde::Serialize;
 use tracing::subscriber::set_global_default;
 use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::RollingFileAppender;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::fmt::Layer;
+use tracing_subscriber::Layer as _;
 use tracing_subscriber::layer::SubscriberExt as _;
 use tracing_subscriber::Registry;
 
@@ -37,6 +42,16 @@ const TRACE_ENV_VAR: &str = "STALL_TRACE";
 /// Default setting for ANSI color usage.
 const DEFAULT_ANSI_COLORS: bool = true;
 
+/// The trace log directory, relative to the stall directory.
+const STALL_LOG_DIR: &str = ".stall-log";
+
+/// The file name prefix for trace log files. The rotation date is appended to
+/// the prefix.
+const LOG_FILE_PREFIX: &str = "stall.log";
+
+/// The number of trace log files to keep.
+const MAX_LOG_FILES: usize = 14;
+
 
 ////////////////////////////////////////////////////////////////////////////////
 // TraceGuard
@@ -47,6 +62,8 @@ const DEFAULT_ANSI_COLORS: bool = true;
 pub struct TraceGuard {
 	/// The worker thread guard for trace output to file.
 	file_output_guard: Option<WorkerGuard>,
+	/// The worker thread guard for trace output to the trace log.
+	log_output_guard: Option<WorkerGuard>,
 }
 
 
@@ -93,28 +110,44 @@ impl TraceConfig {
 	}
 
 	/// Initializes the global default tracing subscriber for the using this
-	/// configuration.
+	/// configuration. If a [`TraceLog`] is given, trace output is also written
+	/// into its rotating log files.
+	///
+	/// [`TraceLog`]: TraceLog
 	pub fn init_global_default<L>(
 		&self,
-		default_level_filter: L)
-		-> Result<TraceGuard, Error>
+		default_level_filter: L,
+		trace_log: Option<&TraceLog>)
+		-> Result<TraceGuard, StallError>
 		where L: Into<LevelFilter>
 	{
+		let default_level_filter = default_level_filter.into();
+		let log_level_filter = trace_log
+			.map_or(LevelFilter::OFF, |log| log.level_filter);
 		let mut env_filter_layer = EnvFilter::from_env(TRACE_ENV_VAR)
-			.add_directive(default_level_filter.into().into());
+			.add_directive(default_level_filter.max(log_level_filter).into());
 		for filter in &self.filters[..] {
 			let directive = filter
 				.parse()
-				.with_context(|| format!(
-					"failed to parse trace filter directive \"{:?}\"",
-					filter))?;
+				.map_err(|e| StallError::Trace {
+					message: format!(
+						"failed to parse trace filter directive {filter:?}: {e}"),
+				})?;
 			env_filter_layer = env_filter_layer.add_directive(directive);
 		}
 
+		// If the log is more verbose than the default level, the stdout output
+		// must be filtered separately.
+		let stdout_level_filter = if log_level_filter > default_level_filter {
+			default_level_filter
+		} else {
+			LevelFilter::TRACE
+		};
 		let fmt_layer = match self.output_stdout {
 			true => Some(Layer::new()
 				.without_time()
-				.with_ansi(self.ansi_colors)),
+				.with_ansi(self.ansi_colors)
+				.with_filter(stdout_level_filter)),
 			false => None,
 		};
 
@@ -128,9 +161,9 @@ impl TraceConfig {
 					.truncate(true)
 					.create(true)
 					.open(path)
-					.with_context(|| format!(
-						"Failed to create/open trace file for writing: {}",
-						path.display()))?;
+					.with_path(
+						"Failed to create/open trace file for writing",
+						path)?;
 				let (writer, guard) = tracing_appender::non_blocking(file);
 				let layer = Layer::new()
 					.without_time()
@@ -141,16 +174,34 @@ impl TraceConfig {
 			None => (None, None),
 		};
 
+		let (log_output_layer, log_output_guard) = match trace_log {
+			Some(trace_log) => {
+				let (writer, guard) = tracing_appender::non_blocking(
+					trace_log.open()?);
+				let layer = Layer::new()
+					.with_ansi(false)
+					.with_writer(writer)
+					.with_filter(trace_log.level_filter);
+				(Some(layer), Some(guard))
+			},
+			None => (None, None),
+		};
+
 		let subscriber = Registry::default()
 			.with(env_filter_layer)
 			.with(fmt_layer)
-			.with(file_output_layer);
+			.with(file_output_layer)
+			.with(log_output_layer);
 
 		set_global_default(subscriber)
-			.context("failed to set global tracing subscriber")?;
+			.map_err(|e| StallError::Trace {
+				message: format!(
+					"failed to set global tracing subscriber: {e}"),
+			})?;
 
 		Ok(TraceGuard {
 			file_output_guard,
+			log_output_guard,
 		})
 	}
 
@@ -173,3 +224,115 @@ impl TraceConfig {
 }
 
 
+////////////////////////////////////////////////////////////////////////////////
+// LogLocation
+////////////////////////////////////////////////////////////////////////////////
+/// Where to write the trace log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLocation {
+	/// Write the trace log into the stall directory.
+	Stall,
+	/// Write the trace log into the user's state directory, such as
+	/// `~/.local/state/stall`.
+	State,
+}
+
+impl std::fmt::Display for LogLocation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Stall => write!(f, "stall"),
+			Self::State => write!(f, "state"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TraceLog
+////////////////////////////////////////////////////////////////////////////////
+/// A trace log, written into daily rotating files in a log directory. Only
+/// the most recent log files are kept.
+#[derive(Debug, Clone)]
+pub struct TraceLog {
+	/// The log directory.
+	dir: PathBuf,
+	/// The maximum level of trace output to write to the log.
+	level_filter: LevelFilter,
+}
+
+impl TraceLog {
+	/// Constructs a new `TraceLog` for the given [`LogLocation`], writing
+	/// output up to the given level. The stall location uses the given stall
+	/// directory, or the state directory if there is none. Returns `None` if
+	/// the state directory cannot be determined.
+	///
+	/// [`LogLocation`]: LogLocation
+	#[must_use]
+	pub fn new<L>(
+		location: LogLocation,
+		stall_dir: Option<&Path>,
+		level_filter: L)
+		-> Option<Self>
+		where L: Into<LevelFilter>
+	{
+		let dir = match (location, stall_dir) {
+			(LogLocation::Stall, Some(stall_dir)) => stall_dir.join(STALL_LOG_DIR),
+			_ => state_dir()?,
+		};
+		Some(Self { dir, level_filter: level_filter.into() })
+	}
+
+	/// Returns the log directory.
+	#[must_use]
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// Returns the paths of the log files, oldest first.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the log directory exists but cannot be
+	/// read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn files(&self) -> Result<Vec<PathBuf>, StallError> {
+		if !self.dir.exists() { return Ok(Vec::new()); }
+
+		let mut files = Vec::new();
+		for dir_entry in std::fs::read_dir(&self.dir)
+			.with_path("failed to read log directory", &self.dir)?
+		{
+			let path = dir_entry
+				.with_path("failed to read log directory", &self.dir)?
+				.path();
+			let is_log = path
+				.file_name()
+				.and_then(|name| name.to_str())
+				.is_some_and(|name| name.starts_with(LOG_FILE_PREFIX));
+			if is_log { files.push(path); }
+		}
+		// Rotated file names end with the date, so they sort by age.
+		files.sort();
+		Ok(files)
+	}
+
+	/// Creates the log directory, removes old log files, and returns the
+	/// writer for the current log file.
+	fn open(&self) -> Result<RollingFileAppender, StallError> {
+		std::fs::create_dir_all(&self.dir)
+			.with_path("failed to create log directory", &self.dir)?;
+
+		// Keep room for the current log file, which may not exist yet.
+		let files = self.files()?;
+		let excess = (files.len() + 1).saturating_sub(MAX_LOG_FILES);
+		for old in &files[..excess.min(files.len())] {
+			std::fs::remove_file(old)
+				.with_path("failed to remove old log file", old)?;
+		}
+
+		Ok(tracing_appender::rolling::daily(&self.dir, LOG_FILE_PREFIX))
+	}
+}
@@ -0,0 +1,402 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Stall archives.
+//!
+//! An archive holds a stall file along with the stall's local files, so that
+//! the stall can be backed up or moved to another machine. Archives are
+//! created and extracted with the `tar` and `zip` commands, and encrypted
+//! archives with the `gpg` command.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+
+// External library imports.
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Read as _;
+use std::io::Write as _;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The environment variable used to provide the passphrase for encrypted
+/// archives. If it is not set, `gpg` prompts for the passphrase.
+pub const PASSPHRASE_ENV: &str = "STALL_PASSPHRASE";
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ArchiveFormat
+////////////////////////////////////////////////////////////////////////////////
+/// The file format of a stall archive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(clap::ArgEnum)]
+pub enum ArchiveFormat {
+	/// An uncompressed tar archive.
+	Tar,
+	/// A gzip compressed tar archive.
+	#[default]
+	TarGz,
+	/// A zip archive.
+	Zip,
+}
+
+impl ArchiveFormat {
+	/// Returns the format indicated by the given archive file's extension,
+	/// ignoring any `.gpg` extension.
+	#[must_use]
+	pub fn from_path(path: &Path) -> Option<Self> {
+		const EXTENSIONS: [(&str, ArchiveFormat); 4] = [
+			(".tar.gz", ArchiveFormat::TarGz),
+			(".tgz",    ArchiveFormat::TarGz),
+			(".tar",    ArchiveFormat::Tar),
+			(".zip",    ArchiveFormat::Zip),
+		];
+		let name = path.file_name()?.to_str()?.to_lowercase();
+		let name = name.strip_suffix(".gpg").unwrap_or(&name);
+		EXTENSIONS
+			.iter()
+			.find(|(ext, _)| name.ends_with(ext))
+			.map(|(_, format)| *format)
+	}
+
+	/// Returns the format of the given archive file, as determined by its
+	/// contents, or `None` if the format is not recognized.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the file can't be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn detect(path: &Path) -> Result<Option<Self>, StallError> {
+		let header = read_header(path)?;
+		if header.starts_with(b"PK") {
+			Ok(Some(Self::Zip))
+		} else if header.starts_with(&[0x1f, 0x8b]) {
+			Ok(Some(Self::TarGz))
+		} else if header.get(257..262) == Some(b"ustar") {
+			Ok(Some(Self::Tar))
+		} else {
+			Ok(None)
+		}
+	}
+}
+
+impl std::fmt::Display for ArchiveFormat {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Tar   => write!(f, "tar"),
+			Self::TarGz => write!(f, "tar-gz"),
+			Self::Zip   => write!(f, "zip"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Archive functions.
+////////////////////////////////////////////////////////////////////////////////
+/// Creates an archive at `archive` holding the given `files`, which are
+/// relative to `dir`. If `encrypt` is true, the archive is encrypted with a
+/// passphrase.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the archive or encryption command fails.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn create(
+	archive: &Path,
+	dir: &Path,
+	files: &[PathBuf],
+	format: ArchiveFormat,
+	encrypt: bool)
+	-> Result<(), StallError>
+{
+	if encrypt {
+		let plain = temp_path(archive);
+		let res = create(&plain, dir, files, format, false)
+			.and_then(|()| gpg(&plain, archive, true));
+		let _ = std::fs::remove_file(&plain);
+		return res;
+	}
+
+	let mut command = match format {
+		ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+			let create = match format {
+				ArchiveFormat::TarGz => "-czf",
+				_                    => "-cf",
+			};
+			let mut tar = Command::new("tar");
+			let _ = tar.arg(create)
+				.arg(archive)
+				.arg("-C")
+				.arg(dir)
+				.arg("--");
+			tar
+		},
+		ArchiveFormat::Zip => {
+			let mut zip = Command::new("zip");
+			let _ = zip.args(["-q", "-r", "-y"])
+				.arg(archive)
+				.current_dir(dir);
+			zip
+		},
+	};
+	let _ = command.args(files);
+	run(command, "create archive", archive)
+}
+
+/// Returns the relative paths of the files and directories in the given
+/// archive.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the archive's format is not recognized, if the
+/// listing command fails, or if the archive contains a path which is absolute
+/// or refers to a parent directory. Symbolic links and hard links are also
+/// rejected, as they could let later members be written outside of the
+/// directory the archive is extracted into.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn list(archive: &Path) -> Result<Vec<PathBuf>, StallError> {
+	let names = list_output(archive, false)?;
+	let names: Vec<&str> = names
+		.lines()
+		.filter(|line| !line.is_empty())
+		.collect();
+
+	// The verbose listing begins each member's line with its mode, whose
+	// first character is its type. Zip listings also have header and total
+	// lines, which don't begin with a mode.
+	let details = list_output(archive, true)?;
+	let kinds: Vec<char> = details
+		.lines()
+		.filter_map(|line| line.split_whitespace().next())
+		.filter(|mode| is_mode(mode))
+		.filter_map(|mode| mode.chars().next())
+		.collect();
+	if kinds.len() != names.len() {
+		return Err(StallError::CommandFailed {
+			message: format!("failed to read the member types of archive {}",
+				archive.display()),
+		});
+	}
+
+	names
+		.into_iter()
+		.zip(kinds)
+		.map(|(name, kind)| {
+			let path = PathBuf::from(name);
+			let escapes = path.components().any(|c| !matches!(c,
+				Component::Normal(_) | Component::CurDir));
+			let reason = if escapes {
+				"archive member is outside of the stall directory"
+			} else if matches!(kind, 'l' | 'h') {
+				"archive member is a link"
+			} else {
+				return Ok(path);
+			};
+			Err(StallError::InvalidPath { path, reason: reason.to_owned() })
+		})
+		.collect()
+}
+
+/// Extracts the given archive into `dir`, replacing any existing files. The
+/// archive's members are checked with [`list`] before it is extracted.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the archive's format is not recognized, if it
+/// contains members which [`list`] rejects, or if the extraction command
+/// fails.
+///
+/// [`list`]: fn.list.html
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn extract(archive: &Path, dir: &Path) -> Result<(), StallError> {
+	let _ = list(archive)?;
+	let mut command = match detect_format(archive)? {
+		ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+			let mut tar = Command::new("tar");
+			let _ = tar.arg("-xf").arg(archive).arg("-C").arg(dir);
+			tar
+		},
+		ArchiveFormat::Zip => {
+			let mut unzip = Command::new("unzip");
+			let _ = unzip.args(["-q", "-o"]).arg(archive).arg("-d").arg(dir);
+			unzip
+		},
+	};
+	let _ = command.stdin(Stdio::null());
+	run(command, "extract archive", archive)
+}
+
+/// Returns `true` if the given archive file is encrypted.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the file can't be read.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn is_encrypted(archive: &Path) -> Result<bool, StallError> {
+	// OpenPGP packets have the high bit of their first byte set, which is
+	// never the case for the supported archive formats.
+	Ok(ArchiveFormat::detect(archive)?.is_none()
+		&& read_header(archive)?.first().is_some_and(|b| b & 0x80 != 0))
+}
+
+/// Decrypts the given encrypted archive into the file at `target`.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the decryption command fails, e.g. because the
+/// passphrase is incorrect.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn decrypt(archive: &Path, target: &Path) -> Result<(), StallError> {
+	gpg(archive, target, false)
+}
+
+/// Returns a temporary path in the same directory as the given path.
+#[must_use]
+pub fn temp_path(path: &Path) -> PathBuf {
+	let name = path.file_name()
+		.map(|name| name.to_string_lossy().into_owned())
+		.unwrap_or_default();
+	path.with_file_name(format!(".{name}.stall-tmp"))
+}
+
+/// Returns the format of the given archive, or an error if it is not
+/// recognized.
+fn detect_format(archive: &Path) -> Result<ArchiveFormat, StallError> {
+	ArchiveFormat::detect(archive)?
+		.ok_or_else(|| StallError::InvalidPath {
+			path: archive.to_path_buf(),
+			reason: "unrecognized archive format".to_owned(),
+		})
+}
+
+/// Returns the output of the command listing the members of the given
+/// archive, with one member per line. If `verbose` is true, each member's line
+/// begins with its mode.
+fn list_output(archive: &Path, verbose: bool) -> Result<String, StallError> {
+	let mut command = match detect_format(archive)? {
+		ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+			let mut tar = Command::new("tar");
+			let _ = tar.arg(if verbose { "-tvf" } else { "-tf" }).arg(archive);
+			tar
+		},
+		ArchiveFormat::Zip => {
+			let mut unzip = Command::new("unzip");
+			let _ = unzip.arg(if verbose { "-Zs" } else { "-Z1" }).arg(archive);
+			unzip
+		},
+	};
+	let output = command
+		.output()
+		.with_path("failed to run command for", archive)?;
+	if !output.status.success() {
+		return Err(StallError::CommandFailed {
+			message: format!("failed to list archive {}: {}",
+				archive.display(),
+				String::from_utf8_lossy(&output.stderr).trim()),
+		});
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns `true` if the given text is a file mode, such as `-rw-r--r--`, as
+/// printed in archive listings.
+fn is_mode(text: &str) -> bool {
+	text.len() == 10 && text.chars().enumerate().all(|(i, c)| match i {
+		0 => "-dlhbcps".contains(c),
+		_ => "-rwxsStT".contains(c),
+	})
+}
+
+/// Reads the first bytes of the given file, which identify its format.
+fn read_header(path: &Path) -> Result<Vec<u8>, StallError> {
+	let file = std::fs::File::open(path)
+		.with_path("failed to open archive", path)?;
+	let mut header = Vec::with_capacity(512);
+	let _ = file.take(512)
+		.read_to_end(&mut header)
+		.with_path("failed to read archive", path)?;
+	Ok(header)
+}
+
+/// Runs `gpg` to encrypt or decrypt `source` into `target`. The passphrase is
+/// taken from the [`PASSPHRASE_ENV`] environment variable if it is set.
+///
+/// [`PASSPHRASE_ENV`]: constant.PASSPHRASE_ENV.html
+fn gpg(source: &Path, target: &Path, encrypt: bool) -> Result<(), StallError> {
+	let passphrase = std::env::var(PASSPHRASE_ENV).ok();
+	let mut command = Command::new("gpg");
+	let _ = command.args(["--quiet", "--yes", "--output"]).arg(target);
+	if passphrase.is_some() {
+		let _ = command
+			.args(["--batch", "--pinentry-mode", "loopback", "--passphrase-fd", "0"])
+			.stdin(Stdio::piped());
+	}
+	if encrypt {
+		let _ = command.args(["--cipher-algo", "AES256", "--symmetric"]);
+	} else {
+		let _ = command.arg("--decrypt");
+	}
+	let _ = command.arg(source);
+	event!(Level::DEBUG, "Running {:?}", command);
+
+	let mut child = command
+		.spawn()
+		.with_path("failed to run gpg for", source)?;
+	if let (Some(passphrase), Some(mut stdin)) = (passphrase, child.stdin.take()) {
+		writeln!(stdin, "{passphrase}")
+			.with_path("failed to write passphrase for", source)?;
+	}
+	let status = child
+		.wait()
+		.with_path("failed to run gpg for", source)?;
+	if status.success() {
+		Ok(())
+	} else {
+		Err(StallError::CommandFailed {
+			message: format!("failed to {} {}: {}",
+				if encrypt { "encrypt" } else { "decrypt" },
+				source.display(),
+				status),
+		})
+	}
+}
+
+/// Runs the given archive command, returning an error if it fails.
+fn run(mut command: Command, action: &str, archive: &Path)
+	-> Result<(), StallError>
+{
+	event!(Level::DEBUG, "Running {:?}", command);
+	let output = command
+		.output()
+		.with_path("failed to run command for", archive)?;
+	if output.status.success() {
+		Ok(())
+	} else {
+		Err(StallError::CommandFailed {
+			message: format!("failed to {} {}: {}",
+				action,
+				archive.display(),
+				String::from_utf8_lossy(&output.stderr).trim()),
+		})
+	}
+}
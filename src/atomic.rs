@@ -0,0 +1,132 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Atomic file replacement.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::platform;
+
+// External library imports.
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+
+/// The temporary files of the replacements in progress, which are removed by
+/// [`remove_temp_files`] if the process is aborted.
+///
+/// [`remove_temp_files`]: fn.remove_temp_files.html
+static TEMP_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+
+////////////////////////////////////////////////////////////////////////////////
+// replace_with
+////////////////////////////////////////////////////////////////////////////////
+/// Atomically replaces the file at `target` with a file written by the given
+/// function.
+///
+/// The function is given a temporary path in the same directory as `target`
+/// to write to, which is then renamed over `target`. If the function or the
+/// rename fails, the temporary file is removed and `target` is left
+/// unchanged. If `durable` is true, the temporary file and the parent
+/// directory are synced to disk, so the new file survives a crash. The
+/// temporary file is also removed by [`remove_temp_files`] if the process is
+/// aborted while it is being written.
+///
+/// [`remove_temp_files`]: fn.remove_temp_files.html
+pub fn replace_with<F>(target: &Path, durable: bool, write: F)
+	-> Result<(), StallError>
+	where F: FnOnce(&Path) -> Result<(), StallError>
+{
+	let temp = temp_path(target)?;
+	event!(Level::TRACE, "Writing {:?} via {:?}", target, temp);
+
+	temp_files().push(temp.clone());
+	let res = write(&temp)
+		.and_then(|()| if durable { sync_file(&temp) } else { Ok(()) })
+		.and_then(|()| std::fs::rename(&temp, target)
+			.with_path("failed to replace", target));
+	temp_files().retain(|path| path != &temp);
+	if res.is_err() {
+		let _ = std::fs::remove_file(&temp);
+		return res;
+	}
+
+	if durable {
+		if let Some(parent) = target.parent() {
+			sync_dir(parent)?;
+		}
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// remove_temp_files
+////////////////////////////////////////////////////////////////////////////////
+/// Removes the temporary files of any replacements in progress, so that their
+/// targets are left unchanged when the process is aborted.
+pub fn remove_temp_files() {
+	for temp in temp_files().drain(..) {
+		event!(Level::DEBUG, "Removing temporary file {:?}", temp);
+		let _ = std::fs::remove_file(temp);
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helper functions
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns the list of temporary files of the replacements in progress.
+fn temp_files() -> std::sync::MutexGuard<'static, Vec<PathBuf>> {
+	TEMP_FILES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns a temporary file path in the same directory as `target`.
+fn temp_path(target: &Path) -> Result<PathBuf, StallError> {
+	let name = target.file_name()
+		.ok_or_else(|| StallError::InvalidPath {
+			path: target.to_path_buf(),
+			reason: "invalid file name".to_owned(),
+		})?;
+	let mut temp_name = std::ffi::OsString::from(".");
+	temp_name.push(name);
+	temp_name.push(format!(".stall-tmp-{}", std::process::id()));
+	Ok(target.with_file_name(temp_name))
+}
+
+/// Syncs the contents of the file at the given path to disk.
+fn sync_file(path: &Path) -> Result<(), StallError> {
+	OpenOptions::new()
+		.write(true)
+		.open(path)
+		.and_then(|file| file.sync_all())
+		.with_path("failed to sync", path)
+}
+
+/// Syncs the directory at the given path to disk, ensuring renamed entries
+/// within it are persisted. This is a no-op on platforms which don't support
+/// opening directories.
+fn sync_dir(path: &Path) -> Result<(), StallError> {
+	if !platform::supports_dir_sync() { return Ok(()); }
+
+	// An empty parent refers to the current directory.
+	let path = if path.as_os_str().is_empty() { Path::new(".") } else { path };
+	File::open(path)
+		.and_then(|dir| dir.sync_all())
+		.with_path("failed to sync directory", path)
+}
@@ -10,15 +10,33 @@
 
 // Internal library imports.
 use stall::application::Config;
-use stall::application::Prefs;
+use stall::application::ConfigLevel;
+use stall::application::Defaults;
+use stall::application::LogLocation;
+use stall::application::StallLocation;
+use stall::application::TraceLog;
 use stall::Stall;
+use stall::confirm::Confirm;
+use stall::entry::CopyOptions;
+use stall::entry::EntryOrder;
+use stall::entry::Overwrite;
+use stall::ignore::IgnoreRules;
+use stall::journal::Journal;
+use stall::journal::JournalRecord;
 use stall::application::TraceGuard;
 use stall::CommandOptions;
+use stall::DistributeOptions;
+use stall::PrefsAction;
+use stall::lock::StallLock;
+use stall::pick::pick_entries;
+use stall::plan::Plan;
+use stall::plan::PlanCommand;
+use stall::plan::PlanFormat;
+use stall::privilege::Privilege;
 
 // External library imports.
 use anyhow::Context;
 use anyhow::Error;
-use anyhow::anyhow;
 use clap::Parser;
 use clap::ErrorKind;
 use clap::CommandFactory as _;
@@ -31,6 +49,10 @@ use tracing::span;
 ////////////////////////////////////////////////////////////////////////////////
 // main
 ////////////////////////////////////////////////////////////////////////////////
+/// The exit code for commands which fail with an error. It is the same code
+/// `status --check` returns for missing or unreadable files.
+const ERROR_EXIT_CODE: i32 = 2;
+
 /// The application entry point.
 pub fn main() {
 	// The worker_guard holds the worker thread handle for the nonblocking
@@ -38,24 +60,32 @@ pub fn main() {
 	// trace spans or events after it is dropped will be ignored.
 	let mut trace_guard = TraceGuard::default();
 
-	if let Err(err) = main_facade(&mut trace_guard) {
-		// Trace errors without coloring.
-		colored::control::set_override(false);
-		event!(Level::ERROR, "{:?}", err);
+	match main_facade(&mut trace_guard) {
+		Ok(0) => (),
+		Ok(exit_code) => {
+			// Flush any pending trace output before exiting.
+			drop(trace_guard);
+			std::process::exit(exit_code);
+		},
+		Err(err) => {
+			// Trace errors without coloring.
+			colored::control::set_override(false);
+			event!(Level::ERROR, "{:?}", err);
 
-		// Print errors to stderr and exit with error code.
-		colored::control::unset_override();
-		eprintln!("{:?}", err);
+			// Print errors to stderr and exit with error code.
+			colored::control::unset_override();
+			eprintln!("{:?}", err);
 
-		let exit_code = match err.downcast::<clap::Error>()
-			.map(|e| e.kind())
-		{
-			Ok(ErrorKind::DisplayHelp)    |
-			Ok(ErrorKind::DisplayVersion) => 0,
-			_ => 1,
-		};
+			let exit_code = match err.downcast::<clap::Error>()
+				.map(|e| e.kind())
+			{
+				Ok(ErrorKind::DisplayHelp)    |
+				Ok(ErrorKind::DisplayVersion) => 0,
+				_ => ERROR_EXIT_CODE,
+			};
 
-		std::process::exit(exit_code);
+			std::process::exit(exit_code);
+		},
 	}
 }
 
@@ -63,31 +93,128 @@ pub fn main() {
 ////////////////////////////////////////////////////////////////////////////////
 // main_facade
 ////////////////////////////////////////////////////////////////////////////////
-/// The application facade for propagating user errors.
-pub fn main_facade(trace_guard: &mut TraceGuard) -> Result<(), Error> {
+/// The application facade for propagating user errors. Returns the exit code
+/// for the process.
+pub fn main_facade(trace_guard: &mut TraceGuard) -> Result<i32, Error> {
 	// Parse command line options.
-	let command = CommandOptions::try_parse()?;
+	let mut command = CommandOptions::try_parse()?;
 	let common = command.common();
 
-	// Find the path for the config file.
+	// Completion and documentation commands are run before any config or trace
+	// output is set up, as their output is consumed by other tools.
+	match &command {
+		CommandOptions::Completions { shell, .. } => {
+			stall::completions(*shell, &mut std::io::stdout())?;
+			return Ok(0);
+		},
+		CommandOptions::Help { subcommand, all, .. } => {
+			stall::help(subcommand.as_deref(), *all, &mut std::io::stdout())?;
+			return Ok(0);
+		},
+		CommandOptions::GenManpage { out_dir, .. } => {
+			stall::gen_manpage(out_dir.as_deref(), &mut std::io::stdout())?;
+			return Ok(0);
+		},
+		_ => (),
+	}
+
 	// We do this up front because current_dir might fail due to access
 	// problems, and we only want to error out if we really need to use it.
 	let cur_dir = std::env::current_dir()?;
-	let config_path = match &common.config {
-		Some(path) => path.clone(),
-		None       => cur_dir.join(Config::DEFAULT_CONFIG_PATH),
+
+	// Load the system and user config files. The user config path may be
+	// given with the --config option.
+	let system_config_path = ConfigLevel::System.path(None);
+	let user_config_path = common.config
+		.clone()
+		.or_else(|| ConfigLevel::User.path(None));
+	let system_config = read_config(system_config_path.as_deref())?;
+	let user_config = read_config(user_config_path.as_deref())?;
+
+	// The trace config and prefs path are taken from the user config, or the
+	// system config if there is no user config.
+	let config = user_config
+		.as_ref()
+		.or(system_config.as_ref())
+		.cloned()
+		.unwrap_or_default();
+
+	// Find the path for the prefs file. It can only be undetermined if the
+	// config directory can't be found.
+	let prefs_path = common.prefs
+		.clone()
+		.or_else(|| config.resolved_prefs_path());
+
+	// Entry completion is run before the trace output is set up, as its output
+	// is consumed by the shell.
+	if let CommandOptions::CompleteEntries { .. } = &command {
+		let prefs = prefs_path
+			.as_ref()
+			.and_then(|path| stall::application::Prefs::read_from_path(path).ok())
+			.unwrap_or_default();
+		let location = StallLocation::resolve(
+			command.stall(),
+			command.is_init(),
+			&cur_dir,
+			&prefs)?;
+		let stall_data = Stall::read_from_path_as(
+			&location.file,
+			common.stall_format)?;
+		stall::complete_entries(&stall_data, &mut std::io::stdout())?;
+		return Ok(0);
+	}
+
+	// The prefs file is edited before it is loaded, so that invalid prefs can
+	// be fixed.
+	if let CommandOptions::Prefs { action: PrefsAction::Edit, .. } = &command {
+		let prefs_path = prefs_path
+			.ok_or_else(|| stall::error::StallError::InvalidInput {
+				message: "unable to locate the prefs file".to_owned(),
+			})?;
+		stall::prefs_edit(&prefs_path, common)?;
+		return Ok(0);
+	}
+
+	// Load the prefs file.
+	let mut prefs = match &prefs_path {
+		Some(path) => match stall::application::Prefs::read_from_path(path) {
+			Err(e) if common.prefs.is_some() => {
+				// Path is user-specified, so it is an error to now load it.
+				return Err(e).with_context(|| format!(
+					"Unable to load preferences file: {:?}", 
+					path));
+			},
+			Err(_) => {
+				// Path is default, so it is ok to use default prefs.
+				stall::application::Prefs::new().with_load_path(path)
+			},
+			Ok(prefs) => prefs,
+		},
+		None => stall::application::Prefs::new(),
 	};
 
-	// Load the config file.
-	let mut config_load_status = Ok(());
-	let config = Config::read_from_path(&config_path)
-		.with_context(|| format!("Unable to load config file: {:?}", 
-			config_path))
-		.unwrap_or_else(|e| {
-			// Store the error for output until after the logger is configured.
-			config_load_status = Err(e);
-			Config::new().with_load_path(&config_path)
-		});
+	// Find the stall directory. Named stall, prefs, config, log, and unpack
+	// commands may be run outside of a stall.
+	let location = match StallLocation::resolve(
+		command.stall(),
+		command.is_init(),
+		&cur_dir,
+		&prefs)
+	{
+		Ok(location) => Some(location),
+		Err(_) if command.stall().is_none() && matches!(command,
+			CommandOptions::Stalls { .. } |
+			CommandOptions::Prefs { .. }  |
+			CommandOptions::Config { .. } |
+			CommandOptions::Paths { .. }  |
+			CommandOptions::Log { .. }    |
+			CommandOptions::Unpack { .. }) => None,
+		Err(e) => return Err(e.into()),
+	};
+	let stall_config_dir = location
+		.as_ref()
+		.filter(|location| command.is_init() || location.file.exists())
+		.map(|location| location.dir.clone());
 
 	// Initialize the global tracing subscriber.
 	let base_level = match (common.verbose, common.quiet, common.trace) {
@@ -96,12 +223,27 @@ pub fn main_facade(trace_guard: &mut TraceGuard) -> Result<(), Error> {
 		(true, _, _) => Level::INFO,
 		_            => Level::WARN,
 	};
-	*trace_guard = config.trace_config.init_global_default(base_level)?;
+	// Operations are logged to the trace log at the info level, or at the
+	// trace level if --ztrace is given.
+	let trace_log = match (prefs.trace_log(), common.trace) {
+		(Some(location), trace) => TraceLog::new(
+			location,
+			stall_config_dir.as_deref(),
+			if trace { Level::TRACE } else { Level::INFO }),
+		(None, true) => TraceLog::new(LogLocation::State, None, Level::TRACE),
+		(None, false) => None,
+	};
+	*trace_guard = config.trace_config
+		.init_global_default(base_level, trace_log.as_ref())?;
 	let _span = span!(Level::INFO, "main").entered();
 
 
 	// Print version information.
 	event!(Level::INFO, "Atma version: {}", env!("CARGO_PKG_VERSION"));
+	event!(Level::INFO, "Command line: {}", std::env::args_os()
+		.map(|arg| arg.to_string_lossy().into_owned())
+		.collect::<Vec<_>>()
+		.join(" "));
 	let rustc_meta = rustc_version_runtime::version_meta();
 	event!(Level::DEBUG, "Rustc version: {} {:?}",
 		rustc_meta.semver,
@@ -112,71 +254,240 @@ pub fn main_facade(trace_guard: &mut TraceGuard) -> Result<(), Error> {
 	event!(Level::DEBUG, "{:#?}", common);
 	event!(Level::DEBUG, "{:#?}", command);
 	event!(Level::DEBUG, "{:#?}", config);
+	event!(Level::DEBUG, "{:#?}", prefs);
 
-	// Find the path for the prefs file.
-	let prefs_path = match &common.prefs {
-		Some(path) => path.clone(),
-		None       => cur_dir.join(&config.prefs_path),
-	};
+	// Load the stall config file.
+	let stall_config_path = ConfigLevel::Stall.path(stall_config_dir.as_deref());
+	let stall_config = read_config(stall_config_path.as_deref())?;
 
-	// Load the prefs file.
-	let prefs = match Prefs::read_from_path(&prefs_path) {
-		Err(e) if common.prefs.is_some() => {
-			// Path is user-specified, so it is an error to now load it.
-			return Err(e).with_context(|| format!(
-				"Unable to load preferences file: {:?}", 
-				prefs_path));
-		},
-		Err(_) => {
-			// Path is default, so it is ok to use default prefs.
-			event!(Level::DEBUG, "Using default prefs.");
-			Prefs::new().with_load_path(prefs_path)
-		},
-		Ok(prefs) => {
-			event!(Level::TRACE, "{:#?}", prefs); 
-			prefs
-		},
-	};
-	event!(Level::DEBUG, "{:#?}", prefs);
+	// Apply the configured defaults to the command options.
+	let layers: Vec<(ConfigLevel, &Defaults)> = [
+			(ConfigLevel::System, &system_config),
+			(ConfigLevel::User, &user_config),
+			(ConfigLevel::Stall, &stall_config),
+		]
+		.into_iter()
+		.filter_map(|(level, config)| config
+			.as_ref()
+			.map(|config| (level, &config.defaults)))
+		.collect();
+	let defaults = layers
+		.iter()
+		.fold(Defaults::default(), |acc, (_, d)| acc.layered(d));
+	event!(Level::DEBUG, "{:#?}", defaults);
+	command.common_mut().apply_defaults(&defaults);
+	let common = command.common();
 
-	// Find the paths for the stall directory and stall file.
-	let (stall_dir, stall_path) = match command.stall() {
-		Some(path) if path.is_file() && command.is_init() => {
-			return Err(anyhow!("file already exists: {}", path.display()));
-		},
+	// Color output even if it isn't written to a terminal, such as when it is
+	// piped into a pager.
+	if common.color == stall::ColorOption::Always {
+		colored::control::set_override(true);
+	}
 
-		Some(path) if path.is_file() => ( 
-			path.parent()
-				.ok_or_else(|| anyhow!(
-					"unable to determine stall parent directory: {}",
-					path.display()))?
-				.to_path_buf(),
-			path.to_path_buf(),
-		),
-
-		Some(path) => (
-			path.to_path_buf(),
-			path.join(Config::DEFAULT_STALL_PATH),
-		),
-
-		None => (
-			cur_dir.clone(),
-			cur_dir.join(Config::DEFAULT_STALL_PATH),
-		),
-	};
+	// Commands which copy files stop between files when interrupted with
+	// ctrl-C or when they time out.
+	if command.copies_files() {
+		stall::cancel::install_handler(common.quiet);
+	}
+	if let Some(timeout) = common.timeout {
+		stall::cancel::start_timeout(timeout);
+	}
 
-	// Load/create the stall file.
-	let mut stall_data = match Stall::read_from_path(&stall_path) {
-		Err(e) if !command.is_init() => {
+	// Config commands only modify the config files.
+	if let CommandOptions::Config { common, action, .. } = &command {
+		use stall::ConfigAction::*;
+		let (level, key, value) = match action {
+			List => return stall::config_list(&layers, common)
+				.map(|()| 0)
+				.map_err(Error::from),
+			Get { key } => return stall::config_get(&layers, key, common)
+				.map(|()| 0)
+				.map_err(Error::from),
+			Set { key, value, level } => (*level, key, Some(value.as_str())),
+			Unset { key, level }      => (*level, key, None),
+		};
+
+		// The stall config file is part of the stall, so it can't be changed
+		// for a read-only stall.
+		if level == ConfigLevel::Stall {
+			let stall_file = location
+				.as_ref()
+				.map(|location| location.file.clone())
+				.filter(|path| path.exists());
+			let readonly = common.readonly || match &stall_file {
+				Some(path) => Stall::read_from_path_as(
+						path,
+						common.stall_format)
+					.with_context(|| format!(
+						"Unable to load stall file: {path:?}"))?
+					.is_readonly(),
+				None => false,
+			};
+			if readonly {
+				return Err(stall::error::StallError::ReadOnly {
+					path: stall_file.unwrap_or_default(),
+				}.into());
+			}
+		}
+
+		let path = match level {
+			ConfigLevel::System => system_config_path,
+			ConfigLevel::User   => user_config_path,
+			ConfigLevel::Stall  => stall_config_path,
+		}.ok_or_else(|| stall::error::StallError::InvalidInput {
+			message: format!("unable to locate the {level} config file"),
+		})?;
+		let mut config = read_config(Some(&path))?
+			.unwrap_or_default()
+			.with_load_path(&path);
+		stall::config_set(&mut config, level, key, value, common)?;
+
+		if config.modified() && config.write_to_load_path()? {
+			event!(Level::INFO, "Config saved to {:?}.", path);
+		}
+		return Ok(0);
+	}
+
+	// Prefs and named stall commands only modify the prefs.
+	if let CommandOptions::Prefs {
+		common,
+		action: PrefsAction::Set { key, value },
+	} = &command {
+		stall::prefs_set(&mut prefs, key, value, common)?;
+		if prefs.modified() && prefs.write_to_load_path()? {
+			event!(Level::INFO, "Prefs saved.");
+		}
+		return Ok(0);
+	}
+	if let CommandOptions::Stalls { common, action } = &command {
+		use stall::StallsAction::*;
+		let res = match action {
+			List => stall::stalls_list(&prefs, common),
+			Add { name, path, force } => stall::stalls_add(
+				&mut prefs,
+				name,
+				path.as_deref().unwrap_or(&cur_dir),
+				&cur_dir,
+				*force,
+				common),
+			Remove { name } => stall::stalls_remove(&mut prefs, name, common),
+		};
+
+		if prefs.modified() && prefs.write_to_load_path()? {
+			event!(Level::INFO, "Prefs saved.");
+		}
+		return res.map(|()| 0).map_err(Error::from);
+	}
+
+	// Log commands only read the trace log.
+	if let CommandOptions::Log { common, action, .. } = &command {
+		let stall::LogAction::Show { since } = action;
+		let trace_log = TraceLog::new(
+				prefs.trace_log().unwrap_or(LogLocation::State),
+				stall_config_dir.as_deref(),
+				Level::INFO)
+			.ok_or_else(|| stall::error::StallError::InvalidInput {
+				message: "unable to locate the log directory".to_owned(),
+			})?;
+		stall::log_show(&trace_log, *since, common)?;
+		return Ok(0);
+	}
+
+	// Paths commands only print the locations of files.
+	if let CommandOptions::Paths { common, .. } = &command {
+		let log_dir = TraceLog::new(
+				prefs.trace_log().unwrap_or(LogLocation::State),
+				stall_config_dir.as_deref(),
+				Level::INFO)
+			.map(|trace_log| trace_log.dir().to_path_buf());
+		let stall_location = location
+			.as_ref()
+			.filter(|location| location.file.exists());
+		stall::paths(
+			system_config_path.as_deref(),
+			user_config_path.as_deref(),
+			prefs_path.as_deref(),
+			log_dir.as_deref(),
+			stall_location,
+			common)?;
+		return Ok(0);
+	}
+
+	// Schedule status commands list the schedules of every stall.
+	if let CommandOptions::Schedule {
+		common,
+		action: stall::ScheduleAction::Status { backend },
+		..
+	} = &command
+	{
+		stall::schedule_status(*backend, common)?;
+		return Ok(0);
+	}
+
+	// Unpack commands restore a stall into a directory, which need not
+	// already contain one.
+	if let CommandOptions::Unpack { common, archive, dir, force, dry_run } = &command {
+		stall::unpack(
+			&cur_dir.join(archive),
+			&dir.as_ref().map_or_else(|| cur_dir.clone(), |dir| cur_dir.join(dir)),
+			*force,
+			*dry_run,
+			common)?;
+		return Ok(0);
+	}
+
+	// Find the paths for the stall directory and stall file.
+	let location = location.expect("stall location resolved");
+	let (stall_dir, stall_path) = (location.dir.clone(), location.file.clone());
+
+	// Load the stall file. It is loaded before the stall is locked, so that
+	// commands which modify a read-only stall are refused without locking it.
+	// The stall file may be edited even if it fails to load, so that an
+	// invalid stall file can be fixed.
+	let loaded = match Stall::read_from_path_as(
+		&stall_path,
+		common.stall_format)
+	{
+		Err(e) if !command.is_init()
+			&& !matches!(command, CommandOptions::Edit { .. }) =>
+		{
 			return Err(e).with_context(|| format!(
 				"Unable to load stall file: {:?}", 
 				stall_path));
 		},
+		loaded => loaded,
+	};
+	let readonly = common.readonly
+		|| loaded.as_ref().is_ok_and(Stall::is_readonly);
+	if readonly && !command.allowed_readonly() {
+		return Err(stall::error::StallError::ReadOnly { path: stall_path }
+			.into());
+	}
+
+	// Lock the stall while it is being modified. The lock is released when the
+	// guard is dropped at the end of the function. Read-only stalls are not
+	// modified, so they aren't locked.
+	let lock = if command.modifies_stall() && !common.no_lock && !readonly {
+		Some(StallLock::acquire(&stall_dir, common.wait)?)
+	} else {
+		None
+	};
+
+	// The stall file is edited before it is used, so that an invalid stall
+	// file can be fixed.
+	if let CommandOptions::Edit { common, .. } = &command {
+		let stdin = std::io::stdin();
+		let mut input = stdin.lock();
+		stall::edit(&stall_path, &mut input, common)?;
+		return Ok(0);
+	}
+
+	let mut stall_data = match loaded {
 		Err(_) => {
 			// Path is default, so it is ok to use default stall.
 			event!(Level::DEBUG, "Creating stall file with path {:?}",
 				stall_path);
-			Stall::new(stall_path)
+			Stall::new(&stall_path)
 		},
 
 		Ok(stall_data) => {
@@ -184,23 +495,166 @@ pub fn main_facade(trace_guard: &mut TraceGuard) -> Result<(), Error> {
 			stall_data
 		},
 	};
+	// Another process may have changed the stall file while the lock was
+	// being acquired, in which case it is loaded again.
+	if lock.is_some() && stall_data.changed_on_disk()? {
+		event!(Level::DEBUG, "Reloading stall file changed while locking");
+		stall_data = Stall::read_from_path_as(
+				&stall_path,
+				common.stall_format)
+			.with_context(|| format!(
+				"Unable to load stall file: {:?}", 
+				stall_path))?;
+	}
 	event!(Level::DEBUG, "{:#?}", stall_data);
+	stall_data.set_durable(common.durable);
+	command.common_mut().readonly |= stall_data.is_readonly();
+	let readonly = command.common().readonly;
+
+	// Prepare a journal record for commands which modify entries. The entries
+	// are compared after the command runs to find which were changed.
+	// Read-only stalls have no journal.
+	let mut record = command
+		.journal_operation()
+		.filter(|_| !readonly)
+		.map(JournalRecord::new);
+	let original = record.as_ref().map(|_| stall_data.clone());
+	let notify = command.common().notify;
 	
 	// Dispatch to appropriate commands.
 	use CommandOptions::*;
 	let res = match command {
-		Init { common, dry_run, .. } => stall::init(
+		Init { common, rename_policy, from_dir, dry_run, .. } => stall::init(
 			stall_dir.as_path(),
 			&mut stall_data,
+			rename_policy,
+			from_dir
+				.map(|dir| stall::path::normalize(&dir, &cur_dir))
+				.as_deref(),
 			dry_run,
-			&common),
+			&common)
+			.map(|()| 0),
 		
-		Status { common, .. } => stall::status(
+		Status { common, remote_missing: true, .. } => stall::scan_unstalled(
 			stall_dir.as_path(),
 			&stall_data,
-			&common),
+			prefs.scan_paths(),
+			&common)
+			.map(|()| 0),
+
+		Status {
+			common,
+			files,
+			clean,
+			modified,
+			missing,
+			include_archived,
+			order,
+			sort,
+			columns,
+			check,
+			no_cache,
+			..
+		} => {
+			stall::status(
+				stall_dir.as_path(),
+				&stall_data,
+				files.iter().map(|f| f.as_path()),
+				stall::StateFilter {
+					clean,
+					modified,
+					missing,
+					archived: include_archived,
+				},
+				order,
+				!no_cache && !common.readonly)
+				.and_then(|report| {
+					if !check {
+						report.write(
+							&mut std::io::stdout(),
+							sort,
+							&columns[..],
+							&common)?;
+					}
+					Ok(report.state().exit_code())
+				})
+		},
+
+		Diff { common, files, text, word_diff, side_by_side, width, .. }
+			=> stall::diff(
+				stall_dir.as_path(),
+				&stall_data,
+				files.iter().map(|f| f.as_path()),
+				text,
+				match (word_diff, side_by_side) {
+					(true, _)     => stall::DiffStyle::Words,
+					(false, true) => stall::DiffStyle::SideBySide(width),
+					_             => stall::DiffStyle::Unified,
+				},
+				&common)
+			.map(i32::from),
 
-		Add { common, files, rename, into, collect, dry_run, .. } => {
+		Grep {
+			common,
+			pattern,
+			target,
+			ignore_case,
+			fixed_strings,
+			..
+		} => {
+			stall::grep(
+				stall_dir.as_path(),
+				&stall_data,
+				&pattern,
+				target,
+				ignore_case,
+				fixed_strings,
+				&common)
+				.map(|found| if found { 0 } else { 1 })
+		},
+
+		Show { common, file, cat, .. } => stall::show(
+			stall_dir.as_path(),
+			&stall_data,
+			&file,
+			cat,
+			&common)
+			.map(|()| 0),
+
+		Du { common, files, bytes, .. } => stall::du(
+			stall_dir.as_path(),
+			&stall_data,
+			files.iter().map(|f| f.as_path()),
+			bytes,
+			&common)
+			.map(|()| 0),
+
+		List { files, format, null, .. } => stall::list(
+			stall_dir.as_path(),
+			&stall_data,
+			files.iter().map(|f| f.as_path()),
+			&format,
+			null)
+			.map(|()| 0),
+
+		Add {
+			common,
+			files,
+			stdin,
+			null,
+			from_remote_list,
+			rename,
+			rename_template,
+			into,
+			append,
+			disambiguate,
+			force,
+			canonicalize,
+			no_gitignore,
+			collect,
+			dry_run,
+			..
+		} => {
 			// Emit error if using --rename with multiple files.
 			if files.len() > 1 && rename.is_some() {
 				// TODO: Figure out how to produce better error output.
@@ -215,60 +669,1053 @@ pub fn main_facade(trace_guard: &mut TraceGuard) -> Result<(), Error> {
                 	.exit()
 			}
 
-			stall::add(
-				&mut stall_data,
-				files.iter().map(|f| f.as_path()),
-				rename.as_deref(),
-				into.as_deref(),
-				if collect { Some(stall_dir.as_path()) } else { None },
-				dry_run,
-				&common)
+			// Normalize remote paths so that equivalent spellings refer to
+			// the same entry.
+			let normalize = |f: &std::path::Path, base: &std::path::Path| {
+				let f = stall::path::normalize(f, base);
+				if canonicalize { stall::path::canonicalize(&f) } else { f }
+			};
+			let mut files = stdin_files(stdin, null, files)?
+				.iter()
+				.map(|f| (None, normalize(f, &cur_dir)))
+				.collect::<Vec<_>>();
+			if let Some(list) = from_remote_list.map(|l| cur_dir.join(l)) {
+				let base = list.parent().unwrap_or(&cur_dir);
+				files.extend(stall::read_remote_list(&list)?
+					.into_iter()
+					.map(|(local, remote)| (local, normalize(&remote, base))));
+			}
+
+			// Remote paths listed with local paths are added one at a time.
+			let (named, files): (Vec<_>, Vec<_>) = files
+				.into_iter()
+				.partition(|(local, _)| local.is_some());
+			let add = |
+				stall_data: &mut Stall,
+				files: &[std::path::PathBuf],
+				rename|
+			{
+				stall::add(
+					stall_data,
+					files.iter().map(|f| f.as_path()),
+					rename,
+					rename_template.as_ref(),
+					into.as_deref(),
+					append,
+					disambiguate,
+					force,
+					no_gitignore,
+					if collect { Some(stall_dir.as_path()) } else { None },
+					dry_run,
+					&common)
+			};
+			let files = files.into_iter().map(|(_, f)| f).collect::<Vec<_>>();
+			add(&mut stall_data, &files, rename.as_deref())
+				.and_then(|()| named
+					.iter()
+					.try_for_each(|(local, remote)| add(
+						&mut stall_data,
+						std::slice::from_ref(remote),
+						local.as_deref())))
+				.map(|()| 0)
 		},
 
-		Remove { common, files, delete, remote_naming, dry_run, .. } => {
+		Remove {
+			common,
+			files,
+			delete,
+			no_trash,
+			remote_naming,
+			stdin,
+			null,
+			pick,
+			dry_run,
+			..
+		} => {
+			let files = stdin_files(stdin, null, files)?;
+			let Some(files) = pick_files(
+				pick, files, &stall_data, "remove", &common)? else {
+				return Ok(0);
+			};
+			let files = if remote_naming {
+				files.iter()
+					.map(|f| stall::path::normalize(f, &cur_dir))
+					.collect::<Vec<_>>()
+			} else {
+				files
+			};
+
 			stall::remove(
 				&mut stall_data,
 				files.iter().map(|f| f.as_path()),
 				if delete { Some(stall_dir.as_path()) } else { None },
 				remote_naming,
+				!no_trash,
 				dry_run,
 				&common)
+				.map(|()| 0)
 		},
 
-		Move { common, from, to, move_file, force, dry_run, .. } => {
+		Prune {
+			common,
+			yes,
+			pick,
+			delete,
+			no_trash,
+			include_archived,
+			dry_run,
+			..
+		} => stall::prune(
+				stall_dir.as_path(),
+				&mut stall_data,
+				&mut std::io::stdin().lock(),
+				yes,
+				pick,
+				delete,
+				!no_trash,
+				include_archived,
+				dry_run,
+				&common)
+			.map(|()| 0),
+
+		Move { common, from, to, move_file, force, swap, dry_run, .. } => {
 			stall::rename(
 				&mut stall_data,
 				from.as_path(),
 				to.as_path(),
 				if move_file { Some(stall_dir.as_path()) } else { None },
 				force,
+				swap,
 				dry_run,
 				&common)
+				.map(|()| 0)
 		},
 
-		Collect { common, files, force, dry_run, .. } => stall::collect(
+		Relocate { common, files, scan, yes, dry_run, .. } => stall::relocate(
+				stall_dir.as_path(),
+				&mut stall_data,
+				files.iter().map(|f| f.as_path()),
+				&mut std::io::stdin().lock(),
+				&[prefs.scan_paths(), &scan[..]].concat(),
+				yes,
+				dry_run,
+				&common)
+			.map(|()| 0),
+
+		Duplicate {
+			common,
+			from,
+			to,
+			remote,
+			copy_file,
+			force,
+			dry_run,
+			..
+		} => {
+			stall::duplicate(
+				&mut stall_data,
+				from.as_path(),
+				to.as_path(),
+				stall::path::normalize(&remote, &cur_dir).as_path(),
+				if copy_file { Some(stall_dir.as_path()) } else { None },
+				force,
+				dry_run,
+				&common)
+				.map(|()| 0)
+		},
+
+		Collect {
+			common,
+			files,
+			stdin,
+			null,
+			recursive,
+			pick,
+			force,
+			overwrite_newer,
+			ignore_errors,
+			unpin,
+			confirm,
+			confirm_plan,
+			copy_mode,
+			preserve,
+			throttle,
+			no_throttle,
+			retry,
+			verify,
+			order,
+			include_archived,
+			conflict,
+			summary_only,
+			dry_run,
+			plan_out,
+			plan_format,
+			..
+		} => {
+			let files = stdin_files(stdin, null, files)?;
+			let Some(files) = pick_files(
+				pick, files, &stall_data, "collect", &common)? else {
+				return Ok(0);
+			};
+			let mut stdin = std::io::stdin().lock();
+			let mut confirm = confirm.then(|| Confirm::new(&mut stdin));
+			let overwrite = Overwrite {
+				unmodified: force,
+				newer: force || overwrite_newer,
+				errors: ignore_errors,
+			};
+			let throttle = throttle.or_else(|| prefs.throttle())
+				.filter(|_| !no_throttle);
+			let copy_options = |dir: &std::path::Path| Ok::<_, stall::error::StallError>(
+				CopyOptions {
+					preserve: preserve.clone(),
+					mode: copy_mode,
+					throttle,
+					buffer_size: prefs.copy_buffer(),
+					retry: retry.unwrap_or_else(|| prefs.retry()),
+					verify,
+					order,
+					include_archived,
+					ignore: IgnoreRules::load(dir)?,
+					conflict,
+					default_conflict: prefs.conflict_policy(),
+					merge_tool: prefs.merge_tool(),
+					version_retention: prefs.version_retention(),
+					privilege: Privilege::default(),
+				});
+			// With --confirm-plan, the copies are planned by a dry run and
+			// applied after they are confirmed.
+			let mut plan = (plan_out.is_some() || confirm_plan)
+				.then(|| Plan::new(PlanCommand::Collect, &stall_dir));
+			let mut run = |dir: &std::path::Path, stall: &Stall| {
+				let mut out = std::io::stdout().lock();
+				write_stall_header(
+					&mut out,
+					dir,
+					stall,
+					summary_only || confirm_plan,
+					&common)?;
+				stall::collect(
+					&mut out,
+					dir,
+					stall,
+					files.iter().map(|f| f.as_path()),
+					overwrite,
+					unpin,
+					&copy_options(dir)?,
+					confirm.as_mut(),
+					plan.as_mut(),
+					summary_only || confirm_plan,
+					dry_run || confirm_plan,
+					&common)
+				.and_then(|report| {
+					if !confirm_plan { write_copy_report(&report, &common)?; }
+					Ok(report)
+				})
+			};
+
+			let res = run(&stall_dir, &stall_data).inspect(|report| {
+				if let Some(record) = record.as_mut().filter(|_| !confirm_plan) {
+					record.record_report(report);
+				}
+			});
+			match res {
+				// --confirm-plan conflicts with --recursive, but is checked
+				// first so that a plan is never left unapplied.
+				Ok(report) if confirm_plan => apply_confirmed_plan(
+					&mut stdin,
+					&stall_dir,
+					&stall_data,
+					plan.as_ref().expect("plan for --confirm-plan"),
+					&report,
+					false,
+					&copy_options(&stall_dir)?,
+					record.as_mut(),
+					&common),
+				Ok(report) if recursive => {
+					let mut reports = vec![(stall_dir.clone(), report)];
+					for nested in location.nested()? {
+						if stall::cancel::cancelled().is_some() { break; }
+						// Each nested stall is locked while it is collected
+						// into, and the collect is recorded in its journal.
+						let _lock = if common.no_lock { None } else {
+							Some(StallLock::acquire(&nested.dir, common.wait)?)
+						};
+						let nested_stall = Stall::read_from_path_as(
+								&nested.file,
+								common.stall_format)
+							.with_context(|| format!(
+								"Unable to load stall file: {:?}",
+								nested.file))?;
+						let report = run(&nested.dir, &nested_stall)?;
+						let mut nested_record = JournalRecord::new(
+							stall::journal::Operation::Collect);
+						nested_record.record_report(&report);
+						if !dry_run {
+							Journal::new(&nested.dir).append(&nested_record)?;
+						}
+						reports.push((nested.dir, report));
+					}
+					stall::write_stall_summaries(
+						&mut std::io::stdout(),
+						&reports,
+						&common)?;
+					Ok(reports
+						.iter()
+						.map(|(_, report)| report.exit_code())
+						.max()
+						.unwrap_or_default())
+				},
+				res => res.and_then(|report| {
+					write_plan(plan.as_ref(), plan_out, plan_format, &common)?;
+					Ok(report.exit_code())
+				}),
+			}
+		},
+
+		Distribute {
+			common,
+			files,
+			stdin,
+			null,
+			pick,
+			force,
+			overwrite_newer,
+			ignore_errors,
+			unpin,
+			confirm,
+			confirm_plan,
+			create_dirs,
+			no_create_dirs,
+			no_trash,
+			system,
+			privileged_script,
+			copy_mode,
+			preserve,
+			throttle,
+			no_throttle,
+			retry,
+			verify,
+			order,
+			include_archived,
+			conflict,
+			summary_only,
+			dry_run,
+			plan_out,
+			plan_format,
+			..
+		} => {
+			let files = stdin_files(stdin, null, files)?;
+			let Some(files) = pick_files(
+				pick, files, &stall_data, "distribute", &common)? else {
+				return Ok(0);
+			};
+			let mut stdin = std::io::stdin().lock();
+			let mut confirm = confirm.then(|| Confirm::new(&mut stdin));
+			let options = CopyOptions {
+				preserve,
+				mode: copy_mode,
+				throttle: throttle.or_else(|| prefs.throttle())
+					.filter(|_| !no_throttle),
+				buffer_size: prefs.copy_buffer(),
+				retry: retry.unwrap_or_else(|| prefs.retry()),
+				verify,
+				order,
+				include_archived,
+				ignore: IgnoreRules::load(&stall_dir)?,
+				conflict,
+				default_conflict: prefs.conflict_policy(),
+				merge_tool: prefs.merge_tool(),
+				version_retention: prefs.version_retention(),
+				privilege: Privilege {
+					system,
+					escalation: prefs.escalation(),
+					script: privileged_script
+						.map(|script| cur_dir.join(script)),
+				},
+			};
+			// With --confirm-plan, the copies are planned by a dry run and
+			// applied after they are confirmed.
+			let mut plan = (plan_out.is_some() || confirm_plan)
+				.then(|| Plan::new(PlanCommand::Distribute, &stall_dir));
+			let mut out = std::io::stdout().lock();
+			write_stall_header(
+				&mut out,
+				&stall_dir,
+				&stall_data,
+				summary_only || confirm_plan,
+				&common)?;
+			let res = stall::distribute(
+				&mut out,
+				stall_dir.as_path(),
+				&stall_data,
+				files.iter().map(|f| f.as_path()),
+				Overwrite {
+					unmodified: force,
+					newer: force || overwrite_newer,
+					errors: ignore_errors,
+				},
+				DistributeOptions {
+					unpin,
+					create_dirs: create_dirs
+						|| (!no_create_dirs && prefs.create_dirs()),
+					use_trash: !no_trash,
+					summary_only: summary_only || confirm_plan,
+					dry_run: dry_run || confirm_plan,
+				},
+				&options,
+				confirm.as_mut(),
+				plan.as_mut(),
+				&common);
+			match res {
+				Ok(report) if confirm_plan => apply_confirmed_plan(
+					&mut stdin,
+					&stall_dir,
+					&stall_data,
+					plan.as_ref().expect("plan for --confirm-plan"),
+					&report,
+					!no_trash,
+					&options,
+					record.as_mut(),
+					&common),
+				res => res.and_then(|report| {
+					use std::io::Write as _;
+
+					if let Some(record) = &mut record {
+						record.record_report(&report);
+					}
+					if let Some(script) = options.privilege.script.as_ref()
+						.filter(|_| !dry_run && !common.quiet)
+					{
+						writeln!(&mut out, "Wrote privileged commands to {}; \
+							run it as root.",
+							script.display())?;
+					}
+					write_copy_report(&report, &common)?;
+					write_plan(plan.as_ref(), plan_out, plan_format, &common)?;
+					Ok(report.exit_code())
+				}),
+			}
+		},
+
+		ApplyPlan { common, plan, force, no_trash, .. } => stall::apply_plan(
 			stall_dir.as_path(),
 			&stall_data,
-			files.iter().map(|f| f.as_path()),
+			&Plan::read_from_path(&cur_dir.join(plan))?,
 			force,
+			!no_trash,
+			&CopyOptions {
+				throttle: prefs.throttle(),
+				buffer_size: prefs.copy_buffer(),
+				retry: prefs.retry(),
+				ignore: IgnoreRules::load(&stall_dir)?,
+				default_conflict: prefs.conflict_policy(),
+				merge_tool: prefs.merge_tool(),
+				version_retention: prefs.version_retention(),
+				privilege: Privilege {
+					escalation: prefs.escalation(),
+					..Privilege::default()
+				},
+				..CopyOptions::default()
+			},
+			&common)
+			.and_then(|report| {
+				write_copy_report(&report, &common)?;
+				Ok(report.exit_code())
+			}),
+
+		Bootstrap {
+			common,
+			yes,
+			no_trash,
+			copy_mode,
+			preserve,
+			dry_run,
+			..
+		} => {
+			let mut out = std::io::stdout().lock();
+			write_stall_header(&mut out, &stall_dir, &stall_data, false, &common)?;
+			stall::bootstrap(
+				&mut out,
+				stall_dir.as_path(),
+				&stall_data,
+				&mut std::io::stdin().lock(),
+				yes,
+				!no_trash,
+				&CopyOptions {
+					preserve,
+					mode: copy_mode,
+					throttle: prefs.throttle(),
+					buffer_size: prefs.copy_buffer(),
+					retry: prefs.retry(),
+					verify: false,
+					order: EntryOrder::default(),
+					include_archived: false,
+					ignore: IgnoreRules::load(&stall_dir)?,
+					conflict: None,
+					default_conflict: prefs.conflict_policy(),
+					merge_tool: prefs.merge_tool(),
+					version_retention: prefs.version_retention(),
+					privilege: Privilege {
+						escalation: prefs.escalation(),
+						..Privilege::default()
+					},
+				},
+				dry_run,
+				&common)
+				.and_then(|report| {
+					if let Some(record) = &mut record {
+						record.record_report(&report);
+					}
+					report.write_summary(&mut std::io::stderr(), &common)?;
+					Ok(report.exit_code())
+				})
+		},
+
+		Flag {
+			common,
+			files,
+			pin,
+			unpin,
+			readonly,
+			writable,
+			gitignore,
+			no_gitignore,
+			binary,
+			no_binary,
+			privileged,
+			unprivileged,
+			archive,
+			unarchive,
+			eol,
+			transforms,
+			clear_transforms,
+			conflict,
+			clear_conflict,
+			after,
+			clear_after,
+			dry_run,
+			..
+		} => stall::flag(
+			&mut stall_data,
+			files.iter().map(|f| f.as_path()),
+			(pin || unpin).then_some(pin),
+			(readonly || writable).then_some(readonly),
+			(gitignore || no_gitignore).then_some(no_gitignore),
+			(binary || no_binary).then_some(binary),
+			(privileged || unprivileged).then_some(privileged),
+			(archive || unarchive).then_some(archive),
+			eol,
+			(!transforms.is_empty() || clear_transforms)
+				.then_some(transforms.as_slice()),
+			(conflict.is_some() || clear_conflict).then_some(conflict),
+			(!after.is_empty() || clear_after).then_some(after.as_slice()),
+			dry_run,
+			&common)
+			.map(|()| 0),
+
+		Group { common, action, .. } => {
+			use stall::GroupAction::*;
+			match action {
+				Create { name, files, dry_run } => stall::group_create(
+					&mut stall_data,
+					&name,
+					files.iter().map(|f| f.as_path()),
+					dry_run,
+					&common),
+				Add { name, files, dry_run } => stall::group_add(
+					&mut stall_data,
+					&name,
+					files.iter().map(|f| f.as_path()),
+					dry_run,
+					&common),
+				Remove { name, files, dry_run } => stall::group_remove(
+					&mut stall_data,
+					&name,
+					files.iter().map(|f| f.as_path()),
+					dry_run,
+					&common),
+				List { name } => stall::group_list(
+					&stall_data,
+					name.as_deref(),
+					&common),
+			}
+			.map(|()| 0)
+		},
+
+		Merge {
+			common,
+			other,
+			ours,
+			theirs,
+			interactive,
+			dry_run,
+			..
+		} => {
+			use stall::MergeResolution;
+			let resolution = match (ours, theirs, interactive) {
+				(true, _, _) => Some(MergeResolution::Ours),
+				(_, true, _) => Some(MergeResolution::Theirs),
+				(_, _, true) => Some(MergeResolution::Interactive),
+				_            => None,
+			};
+			let stdin = std::io::stdin();
+			let mut input = stdin.lock();
+			let canonical = |path: &std::path::Path| path
+				.canonicalize()
+				.unwrap_or_else(|_| path.to_path_buf());
+			let this_stall = stall_data.load_path().map(canonical);
+			StallLocation::resolve(Some(&other), false, &cur_dir, &prefs)
+				.and_then(|other| {
+					if Some(canonical(&other.file)) == this_stall {
+						return Err(stall::error::StallError::InvalidInput {
+							message: "cannot merge a stall into itself"
+								.to_owned(),
+						});
+					}
+					let other_data = Stall::read_from_path_as(
+						&other.file,
+						common.stall_format)?;
+					stall::merge(
+						stall_dir.as_path(),
+						&mut stall_data,
+						&other.dir,
+						&other_data,
+						resolution,
+						&mut input,
+						dry_run,
+						&common)
+				})
+				.map(|()| 0)
+		},
+
+		Split { common, by, dry_run, .. } => stall::split(
+			stall_dir.as_path(),
+			&mut stall_data,
+			by,
 			dry_run,
-			&common),
+			&common)
+			.map(|()| 0),
 
-		Distribute { common, files, force, dry_run, .. } => stall::distribute(
+		Foreach {
+			common,
+			command,
+			filter,
+			clean,
+			modified,
+			missing,
+			include_archived,
+			jobs,
+			dry_run,
+			..
+		} => stall::foreach(
 			stall_dir.as_path(),
 			&stall_data,
-			files.iter().map(|f| f.as_path()),
-			force,
+			&command.join(" "),
+			filter.iter().map(|f| f.as_path()),
+			stall::StateFilter {
+				clean,
+				modified,
+				missing,
+				archived: include_archived,
+			},
+			jobs.or(defaults.jobs).unwrap_or(1),
 			dry_run,
-			&common),
+			&common)
+			.map(|()| 0),
+
+		Manifest { common, action, .. } => match action {
+			stall::ManifestAction::Write { hash, dry_run } => stall::manifest_write(
+				stall_dir.as_path(),
+				&stall_data,
+				hash.or(defaults.hash_algorithm).unwrap_or_default(),
+				dry_run,
+				&common)
+				.map(|()| 0),
+			stall::ManifestAction::Verify => stall::manifest_verify(
+				stall_dir.as_path(),
+				&stall_data,
+				&common)
+				.map(|matched| if matched { 0 } else { 1 }),
+		},
+
+		Archive { common, output, format, encrypt, force, dry_run, .. }
+			=> stall::archive(
+				stall_dir.as_path(),
+				&stall_data,
+				&cur_dir.join(output),
+				format,
+				encrypt,
+				force,
+				dry_run,
+				&common)
+				.map(|()| 0),
+
+		ExportScript { common, output, shell, force, .. }
+			=> stall::export_script(
+				&stall_data,
+				output.map(|output| cur_dir.join(output)).as_deref(),
+				shell,
+				force,
+				&common)
+				.map(|()| 0),
+
+		History { common, entry, .. } => stall::history(
+			stall_dir.as_path(),
+			entry.as_deref(),
+			&common)
+			.map(|()| 0),
+
+		Clean { common, yes, pick, archive, no_trash, dry_run, .. }
+			=> stall::clean(
+				stall_dir.as_path(),
+				&stall_data,
+				&mut std::io::stdin().lock(),
+				yes,
+				pick,
+				archive.map(|archive| cur_dir.join(archive)).as_deref(),
+				!no_trash,
+				dry_run,
+				&common)
+			.map(|()| 0),
+
+		Trash { common, action, .. } => {
+			use stall::TrashAction::*;
+			match action {
+				List => stall::trash_list(stall_dir.as_path(), &common),
+				Restore { ids, force, dry_run } => stall::trash_restore(
+					stall_dir.as_path(),
+					&ids[..],
+					force,
+					dry_run,
+					&common),
+				Empty { dry_run } => stall::trash_empty(
+					stall_dir.as_path(),
+					dry_run,
+					&common),
+			}
+			.map(|()| 0)
+		},
+
+		Schedule { common, action, .. } => {
+			use stall::ScheduleAction::*;
+			match action {
+				Install { interval, timeout, command, backend, dry_run }
+					=> stall::schedule_install(
+						&location.file,
+						command,
+						interval,
+						timeout,
+						backend,
+						dry_run,
+						&common),
+				Remove { command, backend } => stall::schedule_remove(
+					&location.file,
+					command,
+					backend,
+					&common),
+				Status { backend } => stall::schedule_status(backend, &common),
+			}
+			.map(|()| 0)
+		},
+
+		Versions { common, action, .. } => {
+			use stall::VersionsAction::*;
+			match action {
+				List { file } => stall::versions_list(
+					stall_dir.as_path(),
+					&stall_data,
+					file.as_path(),
+					&common),
+				Show { file, version } => stall::versions_show(
+					stall_dir.as_path(),
+					&stall_data,
+					file.as_path(),
+					version.as_deref()),
+				Restore { file, version, dry_run } => stall::versions_restore(
+					stall_dir.as_path(),
+					&stall_data,
+					file.as_path(),
+					version.as_deref(),
+					prefs.version_retention(),
+					dry_run,
+					&common),
+			}
+			.map(|()| 0)
+		},
+
+		Edit { .. }            |
+		Log { .. }             |
+		Unpack { .. }          |
+		Config { .. }          |
+		Paths { .. }           |
+		Prefs { .. }           |
+		Stalls { .. }          |
+		Completions { .. }     |
+		CompleteEntries { .. } |
+		Help { .. }            |
+		GenManpage { .. }      => unreachable!("handled before dispatch"),
 	};
 
 	// Save the stall data if any changes occurred.
 	// TODO: Should the stall be saved if an error occurs above?
-	if stall_data.modified() && stall_data.write_to_load_path()? {
+	if stall_data.modified() && !readonly && stall_data.write_to_load_path()? {
 		event!(Level::INFO, "Stall saved.");
 	}
 
-	res
+	// Report failures of unattended commands.
+	if notify {
+		notify_failures(&prefs.notifier(), &stall_dir, &res, record.as_ref());
+	}
+
+	// Record the operation in the journal.
+	if let (Some(mut record), Some(original)) = (record, original) {
+		record.entries.extend(stall_data.changed_locals(&original));
+		if let Err(e) = &res {
+			record.error = Some(e.to_string());
+		} else if let Some(reason) = stall::cancel::cancelled() {
+			record.error = Some(format!("command {reason}"));
+		}
+		Journal::new(&stall_dir).append(&record)?;
+	}
+
+	Ok(res?)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// read_config
+////////////////////////////////////////////////////////////////////////////////
+/// Reads the config file at the given path, if there is one and it exists.
+fn read_config(path: Option<&std::path::Path>) -> Result<Option<Config>, Error> {
+	match path {
+		Some(path) => Config::read_if_exists(path)
+			.with_context(|| format!("Unable to load config file: {:?}",
+				path)),
+		None => Ok(None),
+	}
 }
 
+
+////////////////////////////////////////////////////////////////////////////////
+// pick_files
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the files to operate on, asking the user to pick them if `pick` is
+/// set. Returns `None` if the user picked no files.
+fn pick_files(
+	pick: bool,
+	files: Vec<std::path::PathBuf>,
+	stall: &Stall,
+	verb: &str,
+	common: &stall::CommonOptions)
+	-> Result<Option<Vec<std::path::PathBuf>>, Error>
+{
+	use std::io::Write as _;
+
+	if !pick { return Ok(Some(files)); }
+
+	let picked = pick_entries(stall, verb, common)?;
+	if picked.is_empty() {
+		if !common.quiet {
+			writeln!(std::io::stdout().lock(), "No entries picked.")?;
+		}
+		return Ok(None);
+	}
+	Ok(Some(picked))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// write_stall_header
+////////////////////////////////////////////////////////////////////////////////
+/// Writes the stall directory before the status of each entry copied by a
+/// command, or a hint for adding files if the stall is empty. The directory is
+/// not written in summary only mode, and nothing is written in quiet mode.
+fn write_stall_header(
+	out: &mut dyn std::io::Write,
+	stall_dir: &std::path::Path,
+	stall: &Stall,
+	summary_only: bool,
+	common: &stall::CommonOptions)
+	-> std::io::Result<()>
+{
+	use colored::Colorize as _;
+
+	if common.quiet { return Ok(()); }
+	if stall.is_empty() {
+		return writeln!(out, "No files in stall. Use `add` command to place \
+			files in the stall.");
+	}
+	if summary_only {
+		event!(Level::DEBUG, "Stall directory: {:?}", stall_dir);
+		Ok(())
+	} else if common.color.enabled() {
+		writeln!(out, "{} {}",
+			"Stall directory:".bright_white(),
+			stall_dir.display())
+	} else {
+		writeln!(out, "Stall directory: {}", stall_dir.display())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// write_copy_report
+////////////////////////////////////////////////////////////////////////////////
+/// Writes the totals and failures of a [`CopyReport`] after the status of each
+/// entry. Nothing is written for an empty stall.
+///
+/// [`CopyReport`]: stall::report::CopyReport
+fn write_copy_report(
+	report: &stall::report::CopyReport,
+	common: &stall::CommonOptions)
+	-> std::io::Result<()>
+{
+	if report.total() == 0 && report.cancelled().is_none() { return Ok(()); }
+	report.write(&mut std::io::stdout(), &mut std::io::stderr(), common)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// write_plan
+////////////////////////////////////////////////////////////////////////////////
+/// Writes the copies recorded by a dry run into the plan file, if one was
+/// given. The format is chosen from the plan file's extension if it is not
+/// given.
+fn write_plan(
+	plan: Option<&Plan>,
+	path: Option<std::path::PathBuf>,
+	format: Option<PlanFormat>,
+	common: &stall::CommonOptions)
+	-> Result<(), stall::error::StallError>
+{
+	use std::io::Write as _;
+
+	let (Some(plan), Some(path)) = (plan, path) else { return Ok(()) };
+	let format = format
+		.or_else(|| PlanFormat::from_path(&path))
+		.unwrap_or_default();
+	plan.write_to_path(&path, format)?;
+	if !common.quiet {
+		writeln!(&mut std::io::stdout(), "Wrote {} plan for {} files into {}",
+			format,
+			plan.steps().len(),
+			path.display())?;
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// apply_confirmed_plan
+////////////////////////////////////////////////////////////////////////////////
+/// Reviews the plan recorded by the dry run of a command given the
+/// `--confirm-plan` option, and applies it if the user confirms it. Returns
+/// the exit code of the command, which also accounts for the entries which
+/// failed in the dry run.
+#[allow(clippy::too_many_arguments)]
+fn apply_confirmed_plan(
+	input: &mut dyn std::io::BufRead,
+	stall_dir: &std::path::Path,
+	stall: &Stall,
+	plan: &Plan,
+	planned: &stall::report::CopyReport,
+	use_trash: bool,
+	options: &CopyOptions,
+	record: Option<&mut JournalRecord>,
+	common: &stall::CommonOptions)
+	-> Result<i32, stall::error::StallError>
+{
+	use std::io::Write as _;
+
+	// A cancelled dry run is not applied.
+	if planned.cancelled().is_some() {
+		write_copy_report(planned, common)?;
+		return Ok(planned.exit_code());
+	}
+
+	let mut out = std::io::stdout();
+	plan.write_review(&mut out, planned, common)?;
+	if plan.is_empty() { return Ok(planned.exit_code()); }
+
+	let count = plan.steps().len();
+	let question = format!("Copy {} {}?",
+		count,
+		if count == 1 { "file" } else { "files" });
+	if !Confirm::new(input).proceed(&mut out, &question)? {
+		if !common.quiet { writeln!(&mut out, "No files copied.")?; }
+		return Ok(planned.exit_code());
+	}
+
+	// The sources were hashed when the plan was recorded, so files which
+	// changed while the plan was reviewed are not copied.
+	let report = stall::apply_plan(
+		stall_dir,
+		stall,
+		plan,
+		false,
+		use_trash,
+		options,
+		common)?;
+	if let Some(record) = record {
+		record.record_report(&report);
+	}
+	write_copy_report(&report, common)?;
+	Ok(planned.exit_code().max(report.exit_code()))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// stdin_files
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the files to operate on, followed by the files read from stdin if
+/// `stdin` is set or if any of the files is `-`.
+fn stdin_files(
+	stdin: bool,
+	null: bool,
+	files: Vec<std::path::PathBuf>)
+	-> Result<Vec<std::path::PathBuf>, Error>
+{
+	let dash = std::path::Path::new("-");
+	if !stdin && !files.iter().any(|f| f == dash) { return Ok(files); }
+
+	let input = stall::path::read_paths(std::io::stdin().lock(), null)?;
+	let mut files: Vec<_> = files.into_iter().filter(|f| f != dash).collect();
+	files.extend(input);
+	Ok(files)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// notify_failures
+////////////////////////////////////////////////////////////////////////////////
+/// Sends a notification if a command run with `--notify` returned an error,
+/// was cancelled, or failed for any entries. Notification errors are logged rather than
+/// returned, so that they don't replace the command's result.
+fn notify_failures(
+	notifier: &stall::notify::Notifier,
+	stall_dir: &std::path::Path,
+	res: &Result<i32, stall::error::StallError>,
+	record: Option<&JournalRecord>)
+{
+	let message = match (res, record, stall::cancel::cancelled()) {
+		(Err(e), _, _) => e.to_string(),
+		(Ok(_), _, Some(reason)) => format!("command {reason}"),
+		(Ok(_), Some(record), None) if !record.failed.is_empty() => format!(
+			"{} failed: {}",
+			record.failed.len(),
+			record.failed
+				.iter()
+				.map(|local| local.display().to_string())
+				.collect::<Vec<_>>()
+				.join(", ")),
+		_ => return,
+	};
+	let title = record.map_or_else(
+		|| "stall failed".to_owned(),
+		|record| format!("stall {} failed", record.operation));
+	let message = format!("{}: {message}", stall_dir.display());
+	if let Err(e) = notifier.notify(&title, &message) {
+		event!(Level::WARN, "Unable to send notification: {e}");
+	}
+}
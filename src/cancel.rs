@@ -0,0 +1,211 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Cancellation of commands by ctrl-C or a timeout.
+//!
+//! Commands which copy files check for cancellation between entries, so a
+//! cancelled command finishes the copy in progress and then stops, reporting
+//! the entries which were not processed. A second ctrl-C, or a timed out
+//! command which hasn't stopped within the [`ABORT_GRACE`] period, aborts
+//! the process, removing the temporary file of any atomic copy in progress so
+//! that the file it would have replaced is left unchanged.
+//!
+//! [`ABORT_GRACE`]: constant.ABORT_GRACE.html
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::atomic::remove_temp_files;
+
+// External library imports.
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+
+/// The time a timed out command is given to stop before it is aborted.
+pub const ABORT_GRACE: Duration = Duration::from_secs(10);
+
+/// The [`Cancel`] reason of the process, encoded by [`Cancel::code`], or 0 if
+/// it hasn't been cancelled.
+///
+/// [`Cancel`]: enum.Cancel.html
+/// [`Cancel::code`]: enum.Cancel.html#method.code
+static CANCELLED: AtomicU8 = AtomicU8::new(0);
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Cancel
+////////////////////////////////////////////////////////////////////////////////
+/// The reason a command was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cancel {
+	/// The command was interrupted with ctrl-C.
+	Interrupted,
+	/// The command ran longer than its timeout.
+	TimedOut,
+}
+
+impl Cancel {
+	/// Returns the process exit code for a command cancelled for this reason:
+	/// 130 if interrupted, as for a shell command killed by ctrl-C, or 124 if
+	/// timed out, as for a command run by `timeout`.
+	#[must_use]
+	pub const fn exit_code(self) -> i32 {
+		match self {
+			Self::Interrupted => 130,
+			Self::TimedOut    => 124,
+		}
+	}
+
+	/// Returns the nonzero code of the reason stored in [`CANCELLED`].
+	///
+	/// [`CANCELLED`]: static.CANCELLED.html
+	const fn code(self) -> u8 {
+		match self {
+			Self::Interrupted => 1,
+			Self::TimedOut    => 2,
+		}
+	}
+}
+
+impl std::fmt::Display for Cancel {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Interrupted => write!(f, "interrupted"),
+			Self::TimedOut    => write!(f, "timed out"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Timeout
+////////////////////////////////////////////////////////////////////////////////
+/// The time a command may run before it is cancelled, in whole seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timeout {
+	/// The number of seconds the command may run.
+	seconds: u64,
+}
+
+impl Timeout {
+	/// Returns the time the command may run.
+	#[must_use]
+	pub const fn duration(&self) -> Duration {
+		Duration::from_secs(self.seconds)
+	}
+}
+
+impl std::str::FromStr for Timeout {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let text = text.trim();
+		let invalid = || format!("invalid timeout {text:?}: expected a \
+			duration of at least one second, such as 30s, 10m, or 1h");
+
+		let (count, unit) = match text.chars().last() {
+			Some(unit) if unit.is_ascii_alphabetic()
+				=> (&text[..text.len() - 1], unit),
+			_   => (text, 's'),
+		};
+		let count: u64 = count.parse().ok().ok_or_else(invalid)?;
+		let seconds = match unit {
+			's' => Some(count),
+			'm' => count.checked_mul(60),
+			'h' => count.checked_mul(60 * 60),
+			_   => None,
+		};
+		seconds
+			.filter(|&seconds| seconds > 0)
+			.map(|seconds| Self { seconds })
+			.ok_or_else(invalid)
+	}
+}
+
+impl std::fmt::Display for Timeout {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		const UNITS: [(u64, char); 2] = [(3600, 'h'), (60, 'm')];
+		for (size, unit) in UNITS {
+			if self.seconds.is_multiple_of(size) {
+				return write!(f, "{}{}", self.seconds / size, unit);
+			}
+		}
+		write!(f, "{}s", self.seconds)
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Cancellation
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the reason the process was cancelled, if it has been.
+#[must_use]
+pub fn cancelled() -> Option<Cancel> {
+	match CANCELLED.load(Ordering::SeqCst) {
+		1 => Some(Cancel::Interrupted),
+		2 => Some(Cancel::TimedOut),
+		_ => None,
+	}
+}
+
+/// Cancels the process for the given reason. Returns `false` if it was
+/// already cancelled, in which case the original reason is kept.
+pub fn cancel(reason: Cancel) -> bool {
+	CANCELLED
+		.compare_exchange(0, reason.code(), Ordering::SeqCst, Ordering::SeqCst)
+		.is_ok()
+}
+
+/// Installs a ctrl-C handler which cancels the process, or aborts it if it
+/// has already been cancelled. Messages are not printed if `quiet` is true.
+pub fn install_handler(quiet: bool) {
+	let res = ctrlc::set_handler(move || {
+		if !cancel(Cancel::Interrupted) {
+			abort(Cancel::Interrupted);
+		}
+		if !quiet {
+			let _ = writeln!(std::io::stderr(), "Interrupted: stopping after \
+				the current file. Press ctrl-C again to abort.");
+		}
+	});
+	if let Err(e) = res {
+		event!(Level::WARN, "Failed to install ctrl-C handler: {}", e);
+	}
+}
+
+/// Starts a thread which cancels the process after the given [`Timeout`], and
+/// aborts it if it is still running after the [`ABORT_GRACE`] period.
+///
+/// [`Timeout`]: struct.Timeout.html
+/// [`ABORT_GRACE`]: constant.ABORT_GRACE.html
+pub fn start_timeout(timeout: Timeout) {
+	let _ = std::thread::spawn(move || {
+		std::thread::sleep(timeout.duration());
+		if cancel(Cancel::TimedOut) {
+			event!(Level::WARN, "Timed out after {}: stopping after the \
+				current file.", timeout);
+		}
+		std::thread::sleep(ABORT_GRACE);
+		abort(Cancel::TimedOut);
+	});
+}
+
+/// Aborts the process with the exit code of the given [`Cancel`] reason,
+/// removing the temporary files of any atomic copies in progress.
+///
+/// [`Cancel`]: enum.Cancel.html
+#[allow(clippy::exit)]
+fn abort(reason: Cancel) -> ! {
+	event!(Level::ERROR, "Aborted: {}", reason);
+	remove_temp_files();
+	std::process::exit(reason.exit_code());
+}
@@ -10,24 +10,117 @@
 
 // Internal modules.
 mod add;
+mod apply_plan;
+mod archive;
+mod bootstrap;
+mod clean;
 mod collect;
+mod completions;
+mod config;
+mod diff;
 mod distribute;
+mod du;
+mod duplicate;
+mod edit;
+mod export_script;
+mod flag;
+mod foreach;
+mod grep;
+mod group;
+mod help;
+mod history;
 mod init;
+mod list;
+mod log;
+mod manifest;
+mod merge;
+mod paths;
+mod prefs;
+mod prune;
 mod remove;
+mod schedule;
+mod relocate;
 mod rename;
+mod scan;
+mod show;
+mod split;
+mod stalls;
+mod trash;
+mod versions;
 mod status;
 
 // Exports.
 pub use add::*;
+pub use apply_plan::*;
+pub use archive::*;
+pub use bootstrap::*;
+pub use clean::*;
 pub use collect::*;
+pub use completions::*;
+pub use config::*;
+pub use diff::*;
 pub use distribute::*;
+pub use du::*;
+pub use duplicate::*;
+pub use edit::*;
+pub use export_script::*;
+pub use flag::*;
+pub use foreach::*;
+pub use grep::*;
+pub use group::*;
+pub use help::*;
+pub use history::*;
 pub use init::*;
+pub use list::*;
+pub use log::*;
+pub use manifest::*;
+pub use merge::*;
+pub use paths::*;
+pub use prefs::*;
+pub use prune::*;
 pub use remove::*;
+pub use schedule::*;
+pub use relocate::*;
 pub use rename::*;
+pub use scan::*;
+pub use show::*;
+pub use split::*;
+pub use stalls::*;
+pub use trash::*;
+pub use versions::*;
 pub use status::*;
 
 
+// Internal library imports.
+use crate::application::ConfigLevel;
+use crate::application::Defaults;
+use crate::archive::ArchiveFormat;
+use crate::cancel::Timeout;
+use crate::entry::Attribute;
+use crate::entry::ConflictPolicy;
+use crate::entry::CopyMode;
+use crate::entry::EntryOrder;
+use crate::entry::Retry;
+use crate::entry::Throttle;
+use crate::journal::Operation;
+use crate::manifest::HashAlgorithm;
+use crate::plan::PlanFormat;
+use crate::schedule::Interval;
+use crate::schedule::ScheduleBackend;
+use crate::schedule::ScheduledCommand;
+use crate::script::ScriptShell;
+use crate::template::FormatTemplate;
+use crate::template::NameTemplate;
+use crate::template::Disambiguation;
+use crate::template::RenamePolicy;
+use crate::transform::LineEnding;
+use crate::transform::Transform;
+use crate::StallFormat;
+use crate::warning::WarningKind;
+
 // External library imports.
+use chrono::DateTime;
+use chrono::Local;
 use clap::Parser;
 use serde::Deserialize;
 use serde::Serialize;
@@ -64,18 +157,77 @@ pub struct CommonOptions {
 	/// Shorten filenames by omitting path prefixes.
 	#[clap(
 		short = 'o',
-		long = "short-names")]
+		long = "short-names",
+		overrides_with = "no_short_names")]
 	pub short_names: bool,
+
+	/// Don't shorten filenames, overriding the configured default.
+	#[clap(
+		long = "no-short-names",
+		overrides_with = "short_names")]
+	pub no_short_names: bool,
 	
 	/// Promote any warnings into errors and abort.
 	#[clap(long = "error")]
 	pub promote_warnings_to_errors: bool,
 
-	/// When to color output.
+	/// A comma-separated list of warning kinds to suppress.
+	#[clap(
+		long = "allow",
+		arg_enum,
+		use_value_delimiter = true)]
+	pub allow: Vec<WarningKind>,
+
+	/// Sync written files and their directories to disk before exiting.
+	#[clap(long = "durable")]
+	pub durable: bool,
+
+	/// Wait for other stall commands to finish instead of failing if the
+	/// stall is locked.
+	#[clap(long = "wait")]
+	pub wait: bool,
+
+	/// Modify the stall without locking it.
+	#[clap(long = "no-lock")]
+	pub no_lock: bool,
+
+	/// Refuse to run commands which modify the stall, other than distribute,
+	/// which then doesn't write to the stall directory. Implied for stalls
+	/// marked `readonly` in the stall file.
+	#[clap(name = "readonly-stall", long = "readonly-stall")]
+	pub readonly: bool,
+
+	/// Cancel the command if it runs longer than the given duration, such as
+	/// `30s`, `10m`, or `1h`. Commands which copy files stop after the
+	/// current file, and any command still running 10 seconds later is
+	/// aborted.
+	#[clap(
+		long = "timeout",
+		value_name = "DURATION")]
+	pub timeout: Option<Timeout>,
+
+	/// Report errors and failed files with a notification, as set by the
+	/// `notify` preference. Scheduled commands are run with this option.
+	#[clap(long = "notify")]
+	pub notify: bool,
+
+	/// The directory to keep deleted and overwritten files in, relative to
+	/// the stall directory. [default: .stall-trash]
+	#[clap(
+		long = "backup-dir",
+		parse(from_os_str))]
+	pub backup_dir: Option<PathBuf>,
+
+	/// When to color output. [default: auto]
 	#[clap(
 		long = "color",
-		default_value = "auto",
+		value_name = "WHEN",
 		arg_enum)]
+	pub color_choice: Option<ColorOption>,
+
+	/// The color option to use, resolved from the `--color` option and the
+	/// configured defaults.
+	#[clap(skip = ColorOption::Auto)]
 	pub color: ColorOption,
 	
 	/// Provide more detailed messages.
@@ -93,6 +245,13 @@ pub struct CommonOptions {
 		group = "verbosity")]
 	pub quiet: bool,
 
+	/// The format to read the stall file with, instead of detecting it.
+	#[clap(
+		long = "stall-format",
+		value_name = "FORMAT",
+		arg_enum)]
+	pub stall_format: Option<StallFormat>,
+
 	/// Print trace messages.
 	#[clap(
 		long = "ztrace",
@@ -100,6 +259,23 @@ pub struct CommonOptions {
 	pub trace: bool,
 }
 
+impl CommonOptions {
+	/// Applies the configured [`Defaults`] to any options which were not given
+	/// on the command line.
+	///
+	/// [`Defaults`]: ../application/struct.Defaults.html
+	pub fn apply_defaults(&mut self, defaults: &Defaults) {
+		self.color = self.color_choice
+			.or(defaults.color)
+			.unwrap_or(ColorOption::Auto);
+		self.short_names = self.short_names
+			|| (!self.no_short_names && defaults.short_names.unwrap_or(false));
+		if self.backup_dir.is_none() {
+			self.backup_dir.clone_from(&defaults.backup_dir);
+		}
+	}
+}
+
 
 ////////////////////////////////////////////////////////////////////////////////
 // CommandOptions
@@ -110,6 +286,7 @@ pub struct CommonOptions {
 #[derive(Parser)]
 #[clap(name = "stall")]
 #[clap(author, version, about, long_about = None)]
+#[clap(disable_help_subcommand = true)]
 pub enum CommandOptions {
 	/// Intitialize a stall directory by generating a stall file.
 	Init {
@@ -117,15 +294,38 @@ pub enum CommandOptions {
 		#[clap(flatten)]
 		common: CommonOptions,
 
-		/// The stall file to create.
+		/// The stall file or directory to create.
 		#[clap(parse(from_os_str))]
 		stall: Option<PathBuf>,
 
+		/// The stall file or directory to create.
+		#[clap(
+			short = 's',
+			long = "stall",
+			value_name = "STALL",
+			conflicts_with = "stall",
+			parse(from_os_str))]
+		stall_option: Option<PathBuf>,
+
+		/// The policy for naming files added to the stall. [default:
+		/// keep-name]
+		#[clap(
+			long = "rename-policy",
+			arg_enum)]
+		rename_policy: Option<RenamePolicy>,
+
+		/// Add the files within the given directory to the stall, skipping
+		/// those ignored by the stall's .stallignore file.
+		#[clap(
+			long = "from-dir",
+			value_name = "DIR",
+			parse(from_os_str))]
+		from_dir: Option<PathBuf>,
+
 		/// Print intended operations instead of running them.
 		#[clap(long = "dry-run")]
 		dry_run: bool,
 
-		// TODO: Set rename policy
 		// TODO: Create prefs file?
 	},
 
@@ -142,10 +342,237 @@ pub enum CommandOptions {
 			parse(from_os_str))]
 		stall: Option<PathBuf>,
 
-		// TODO: Filter entries?
+		/// Glob patterns for selecting entries by their paths within the
+		/// stall, or groups such as `@editor`. Defaults to all entries.
+		#[clap(parse(from_os_str))]
+		files: Vec<PathBuf>,
+
+		/// Show entries which are the same as their remote counterparts.
+		#[clap(long = "clean")]
+		clean: bool,
+
+		/// Show entries which are newer or older than their remote
+		/// counterparts.
+		#[clap(long = "modified")]
+		modified: bool,
+
+		/// Show entries with missing or unreadable files.
+		#[clap(long = "missing")]
+		missing: bool,
+
+		/// Show archived entries, which are skipped otherwise.
+		#[clap(long = "include-archived")]
+		include_archived: bool,
+
+		/// The order of the entries in the report.
+		#[clap(
+			long = "order",
+			default_value = "local",
+			arg_enum)]
+		order: EntryOrder,
+
+		/// The order in which to print entries, applied after `--order`.
+		#[clap(
+			long = "sort",
+			default_value = "name",
+			arg_enum)]
+		sort: StatusSort,
+
+		/// A comma-separated list of columns to print.
+		#[clap(
+			long = "columns",
+			arg_enum,
+			use_value_delimiter = true)]
+		columns: Vec<StatusColumn>,
+
+		/// Suppress all output and only set the exit code: 0 if the stall is
+		/// clean, 1 if any entries are out of date, or 2 if any files are
+		/// missing or unreadable. Errors also exit with code 2.
+		#[clap(long = "check")]
+		check: bool,
+
+		/// Hash files which differ only by modification time without using
+		/// or updating the cached hashes in the stall's state file.
+		#[clap(long = "no-cache")]
+		no_cache: bool,
+
+		/// Instead of printing the status of entries, check the common config
+		/// file locations and the `scan_paths` preference for files which are
+		/// not in the stall, and print an `add` command for each.
+		#[clap(
+			long = "remote-missing",
+			conflicts_with_all = &["check", "files"])]
+		remote_missing: bool,
+	},
+
+	/// Print the differences between stalled files and their remote
+	/// counterparts. Exits with 1 if any files differ.
+	Diff {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// Specific files to compare, entry indices and ranges such as `3` or
+		/// `7-9`, as printed by the status command, or groups such as
+		/// `@editor`. Defaults to all files.
+		#[clap(parse(from_os_str))]
+		files: Vec<PathBuf>,
+
+		/// Compare all files as text, including binary files and the files of
+		/// entries flagged as binary.
+		#[clap(long = "text")]
+		text: bool,
+
+		/// Show the changed words within each line, marked as `[-removed-]`
+		/// and `{+inserted+}`, or colored if color is enabled.
+		#[clap(
+			long = "word-diff",
+			conflicts_with = "side-by-side")]
+		word_diff: bool,
+
+		/// Show the stalled and remote files side by side, highlighting the
+		/// changed words if color is enabled.
+		#[clap(
+			short = 'y',
+			long = "side-by-side")]
+		side_by_side: bool,
+
+		/// The width of side-by-side diffs. Defaults to the terminal width.
+		#[clap(
+			long = "width",
+			value_name = "COLUMNS",
+			requires = "side-by-side")]
+		width: Option<usize>,
+	},
+
+	/// Search the contents of stalled files for a pattern.
+	Grep {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The regular expression to search for.
+		pattern: String,
+
+		/// Which files to search.
+		#[clap(
+			long = "in",
+			default_value = "local",
+			arg_enum)]
+		target: GrepTarget,
+
+		/// Match the pattern case-insensitively.
+		#[clap(
+			short = 'i',
+			long = "ignore-case")]
+		ignore_case: bool,
+
+		/// Interpret the pattern as a literal string instead of a regular
+		/// expression.
+		#[clap(
+			short = 'F',
+			long = "fixed-strings")]
+		fixed_strings: bool,
+	},
+
+	/// Print the details of a stalled file.
+	Show {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The file to show, or its entry index as printed by the status
+		/// command.
+		#[clap(parse(from_os_str))]
+		file: PathBuf,
+
+		/// Print the contents of the stalled or remote file.
+		#[clap(
+			long = "cat",
+			arg_enum,
+			value_name = "FILE")]
+		cat: Option<ShowTarget>,
+	},
+
+	/// Print the sizes of stalled and remote files.
+	Du {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// Specific files to print, entry indices and ranges such as `3` or
+		/// `7-9`, as printed by the status command, or groups such as
+		/// `@editor`. Defaults to all files.
+		#[clap(parse(from_os_str))]
+		files: Vec<PathBuf>,
+
+		/// Print sizes in bytes instead of human readable units.
+		#[clap(long = "bytes")]
+		bytes: bool,
 	},
 
-	// TODO: Add Diff subcommand.
+	/// Print the entries of a stall without accessing their files.
+	List {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// Specific files to print, entry indices and ranges such as `3` or
+		/// `7-9`, as printed by the status command, or groups such as
+		/// `@editor`. Defaults to all files.
+		#[clap(parse(from_os_str))]
+		files: Vec<PathBuf>,
+
+		/// The template to print each entry with, using the `{local}`,
+		/// `{remote}`, `{path}`, `{name}`, `{index}`, and `{flags}` tokens.
+		/// The escapes `\t`, `\n`, and `\\` are also accepted.
+		#[clap(
+			long = "format",
+			default_value = FormatTemplate::DEFAULT)]
+		format: FormatTemplate,
+
+		/// Terminate each entry with a NUL byte instead of a newline, for use
+		/// with `xargs -0`.
+		#[clap(
+			short = '0',
+			long = "null")]
+		null: bool,
+	},
 
 	/// Add files to a stall.
 	Add {
@@ -164,19 +591,79 @@ pub enum CommandOptions {
 		#[clap(parse(from_os_str))]
 		files: Vec<PathBuf>,
 
+		/// Read the files from stdin, one per line. A FILES argument of `-`
+		/// also reads the files from stdin.
+		#[clap(long = "stdin")]
+		stdin: bool,
+
+		/// Separate the files read from stdin with NUL bytes instead of
+		/// newlines, for use with `find -print0`.
+		#[clap(
+			short = '0',
+			long = "null")]
+		null: bool,
+
+		/// Also add the remote paths listed in the given file, one per line,
+		/// using the syntax of the list stall file format. Relative paths are
+		/// relative to the file's directory.
+		#[clap(
+			long = "from-remote-list",
+			value_name = "FILE",
+			parse(from_os_str))]
+		from_remote_list: Option<PathBuf>,
+
 		/// Rename the file within the stall. Cannot be used if multiple files
 		/// are added.
 		#[clap(
 			long = "rename",
-			parse(from_os_str))]
+			parse(from_os_str),
+			conflicts_with_all = &["rename-template", "from-remote-list"])]
 		rename: Option<PathBuf>,
 
+		/// Rename the files within the stall using a naming template. The
+		/// template may contain the tokens {name}, {stem}, {ext}, {parent},
+		/// and {n}.
+		#[clap(long = "rename-template")]
+		rename_template: Option<NameTemplate>,
+
 		/// Add stall files to a subdirectory.
 		#[clap(
 			long = "into",
 			parse(from_os_str))]
 		into: Option<PathBuf>,
 
+		/// Add the files as additional remote locations of any existing stall
+		/// files with the same name, instead of replacing them.
+		#[clap(
+			short = 'a',
+			long = "append")]
+		append: bool,
+
+		/// Choose another name for any file whose name is already used by a
+		/// different file in the stall.
+		#[clap(
+			long = "disambiguate",
+			arg_enum,
+			conflicts_with_all = &["append", "force"])]
+		disambiguate: Option<Disambiguation>,
+
+		/// Replace any stall files with the same name that belong to a
+		/// different file.
+		#[clap(
+			short = 'f',
+			long = "force",
+			conflicts_with = "append")]
+		force: bool,
+
+		/// Resolve symbolic links in the remote paths of the added files.
+		#[clap(long = "canonicalize")]
+		canonicalize: bool,
+
+		/// Collect all files of added directories within git repositories,
+		/// including those ignored by git.
+		#[clap(long = "no-gitignore")]
+		no_gitignore: bool,
+
 		/// Immediately collect the added files.
 		#[clap(
 			short = 'c',
@@ -186,10 +673,6 @@ pub enum CommandOptions {
 		/// Print intended operations instead of running them.
 		#[clap(long = "dry-run")]
 		dry_run: bool,
-
-		// TODO: Rename multiple files. Needs some kind of 'file iterator 
-		// naming schema'
-		// TODO: Rename if exists? Needs some kind of 'backup naming schema.'
 	},
 
 	/// Remove files from a stall.
@@ -207,16 +690,41 @@ pub enum CommandOptions {
 		stall: Option<PathBuf>,
 		
 
-		/// The files to remove from to the stall.
+		/// The files to remove from the stall, or groups such as `@editor`.
 		#[clap(parse(from_os_str))]
 		files: Vec<PathBuf>,
 
-		/// Delete the stalled file copy.
+		/// Read the files from stdin, one per line. A FILES argument of `-`
+		/// also reads the files from stdin.
+		#[clap(
+			long = "stdin",
+			conflicts_with = "pick")]
+		stdin: bool,
+
+		/// Separate the files read from stdin with NUL bytes instead of
+		/// newlines, for use with `find -print0`.
+		#[clap(
+			short = '0',
+			long = "null")]
+		null: bool,
+
+		/// Choose the files to remove using an interactive fuzzy selector.
+		#[clap(
+			long = "pick",
+			conflicts_with_all = &["files", "remote-naming"])]
+		pick: bool,
+
+		/// Delete the stalled file copy. The file is moved into the stall's
+		/// trash unless --no-trash is given.
 		#[clap(
 			short = 'd',
 			long = "delete")]
 		delete: bool,
 
+		/// Permanently delete files instead of moving them into the trash.
+		#[clap(long = "no-trash")]
+		no_trash: bool,
+
 		/// Select files do delete based on their remote paths instead of their
 		/// paths within the stall directory.
 		#[clap(long = "remote-naming")]
@@ -229,6 +737,52 @@ pub enum CommandOptions {
 		// TODO: Support glob naming?
 	},
 
+	/// Remove entries whose remote files no longer exist, such as the config
+	/// files of uninstalled applications. The entries are listed, and removed
+	/// after confirmation.
+	Prune {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// Remove the entries without asking for confirmation.
+		#[clap(
+			short = 'y',
+			long = "yes")]
+		yes: bool,
+
+		/// Choose the missing remote files whose entries to remove using an
+		/// interactive fuzzy selector.
+		#[clap(long = "pick")]
+		pick: bool,
+
+		/// Delete the stalled files of the removed entries. The files are
+		/// moved into the stall's trash unless --no-trash is given.
+		#[clap(
+			short = 'd',
+			long = "delete")]
+		delete: bool,
+
+		/// Permanently delete files instead of moving them into the trash.
+		#[clap(long = "no-trash")]
+		no_trash: bool,
+
+		/// Prune archived entries, which are skipped otherwise.
+		#[clap(long = "include-archived")]
+		include_archived: bool,
+
+		/// List the entries to remove instead of removing them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
 	/// Rename a file in a stall. Future collect/distribute actions will use
 	/// the new name.
 	#[clap(name = "mv")]
@@ -244,11 +798,14 @@ pub enum CommandOptions {
 			parse(from_os_str))]
 		stall: Option<PathBuf>,
 
-		/// The current name of the file in the stall.
+		/// The current name of the file in the stall. If this is a glob
+		/// pattern, all matching files will be renamed.
 		#[clap(parse(from_os_str))]
 		from: PathBuf,
 
-		/// The new name of the file in the stall.
+		/// The new name of the file in the stall. If renaming multiple files,
+		/// this is a naming template which may contain the tokens {name},
+		/// {stem}, {ext}, {parent}, and {n}.
 		#[clap(parse(from_os_str))]
 		to: PathBuf,
 
@@ -258,19 +815,29 @@ pub enum CommandOptions {
 			long = "move")]
 		move_file: bool,
 
-		/// Force copy even if files exist.
+		/// Replace any existing file in the stall with the new name. With
+		/// --move, the replaced stalled file is moved into the stall's trash.
 		#[clap(
 			short = 'f',
-			long = "force")]
+			long = "force",
+			conflicts_with = "swap")]
 		force: bool,
 
+		/// Exchange the names of two existing files in the stall.
+		#[clap(long = "swap")]
+		swap: bool,
+
 		/// Print intended operations instead of running them.
 		#[clap(long = "dry-run")]
 		dry_run: bool,
 	},
 
-	/// Copy files into the stall directory from their remote locations.
-	Collect {
+	/// Find the new locations of remote files which no longer exist, such as
+	/// config files moved by an application update, and update the remote
+	/// paths of their entries after confirmation. The common config file
+	/// locations and the `scan_paths` preference are searched for the same
+	/// files, or files with the same contents as the stalled files.
+	Relocate {
 		/// Common command options.
 		#[clap(flatten)]
 		common: CommonOptions,
@@ -281,13 +848,66 @@ pub enum CommandOptions {
 			long = "stall",
 			parse(from_os_str))]
 		stall: Option<PathBuf>,
-		
 
-		/// Specific files to collect. Defaults to all files.
+		/// The files in the stall to relocate, or groups such as `@editor`.
+		/// All entries except archived entries are relocated if none are
+		/// given.
 		#[clap(parse(from_os_str))]
 		files: Vec<PathBuf>,
 
-		/// Force copy even if files are unmodified.
+		/// Additional glob patterns of locations to search. Relative patterns
+		/// are relative to the user's home directory.
+		#[clap(
+			long = "scan",
+			value_name = "PATTERN")]
+		scan: Vec<String>,
+
+		/// Update entries with a single possible new location without asking
+		/// for confirmation. Entries with several are skipped.
+		#[clap(
+			short = 'y',
+			long = "yes")]
+		yes: bool,
+
+		/// List the possible new locations instead of updating the entries.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Duplicate a file in a stall, creating a new entry with a different
+	/// remote path.
+	#[clap(name = "cp")]
+	Duplicate {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The name of the existing file in the stall.
+		#[clap(parse(from_os_str))]
+		from: PathBuf,
+
+		/// The name of the new file in the stall.
+		#[clap(parse(from_os_str))]
+		to: PathBuf,
+
+		/// The remote path of the new file.
+		#[clap(parse(from_os_str))]
+		remote: PathBuf,
+
+		/// Copy the stalled file.
+		#[clap(
+			short = 'c',
+			long = "copy")]
+		copy_file: bool,
+
+		/// Replace any existing entries with the same name or remote path.
 		#[clap(
 			short = 'f',
 			long = "force")]
@@ -298,8 +918,8 @@ pub enum CommandOptions {
 		dry_run: bool,
 	},
 
-	/// Copi files from the stall directory to their remote locations.
-	Distribute {
+	/// Copy files into the stall directory from their remote locations.
+	Collect {
 		/// Common command options.
 		#[clap(flatten)]
 		common: CommonOptions,
@@ -312,41 +932,1282 @@ pub enum CommandOptions {
 		stall: Option<PathBuf>,
 		
 
-		/// Specific files to distribute. Defaults to all files.
+		/// Specific files to collect, entry indices and ranges such as `3` or
+		/// `7-9`, as printed by the status command, or groups such as
+		/// `@editor`. Defaults to all files.
 		#[clap(parse(from_os_str))]
 		files: Vec<PathBuf>,
 
-		/// Force copy even if files are unmodified.
+		/// Also collect into the stalls nested in subdirectories of the stall
+		/// directory, and print a summary for each stall.
+		#[clap(
+			short = 'r',
+			long = "recursive",
+			conflicts_with = "files")]
+		recursive: bool,
+
+		/// Read the files from stdin, one per line. A FILES argument of `-`
+		/// also reads the files from stdin.
+		#[clap(
+			long = "stdin",
+			conflicts_with_all = &["pick", "recursive"])]
+		stdin: bool,
+
+		/// Separate the files read from stdin with NUL bytes instead of
+		/// newlines, for use with `find -print0`.
+		#[clap(
+			short = '0',
+			long = "null")]
+		null: bool,
+
+		/// Choose the files to collect using an interactive fuzzy selector.
+		#[clap(
+			long = "pick",
+			conflicts_with_all = &["files", "recursive"])]
+		pick: bool,
+
+		/// Force copy even if files are unmodified. Implies
+		/// --overwrite-newer.
 		#[clap(
 			short = 'f',
 			long = "force")]
 		force: bool,
 
+		/// Copy files even if the destination file is newer.
+		#[clap(long = "overwrite-newer")]
+		overwrite_newer: bool,
+
+		/// Copy files even if the destination file's modification time can't
+		/// be read.
+		#[clap(long = "ignore-errors")]
+		ignore_errors: bool,
+
+		/// Allow --force to overwrite the files of pinned entries.
+		#[clap(
+			long = "unpin",
+			requires = "force")]
+		unpin: bool,
+
+		/// Ask before overwriting each existing file, showing how much newer
+		/// or older and larger or smaller it is.
+		#[clap(long = "confirm")]
+		confirm: bool,
+
+		/// Work out every copy first, print them as a single plan of the files
+		/// to create or overwrite and the entries which would not be copied,
+		/// and ask once before copying any files.
+		#[clap(
+			long = "confirm-plan",
+			conflicts_with_all = &["confirm", "dry-run", "plan-out", "recursive"])]
+		confirm_plan: bool,
+
+		/// The method for copying file contents. Reflinks clone files
+		/// instantly on file systems which support them, such as btrfs, XFS,
+		/// and APFS.
+		#[clap(
+			long = "copy-mode",
+			arg_enum,
+			default_value = "auto")]
+		copy_mode: CopyMode,
+
+		/// Copy file attributes along with the file contents. Attributes which
+		/// can't be copied produce a warning.
+		#[clap(
+			long = "preserve",
+			arg_enum,
+			use_value_delimiter = true,
+			value_name = "ATTRIBUTES")]
+		preserve: Vec<Attribute>,
+
+		/// Limit the rate at which file contents are copied, in megabytes per
+		/// second. Defaults to the `throttle` preference.
+		#[clap(
+			long = "throttle",
+			value_name = "MB/S",
+			conflicts_with = "no-throttle")]
+		throttle: Option<Throttle>,
+
+		/// Do not limit the rate at which file contents are copied.
+		#[clap(long = "no-throttle")]
+		no_throttle: bool,
+
+		/// How many times to retry copies which fail with transient errors,
+		/// optionally followed by x and the delay before the first retry,
+		/// such as 5x2s. Defaults to the `retry` preference.
+		#[clap(long = "retry", value_name = "POLICY")]
+		retry: Option<Retry>,
+
+		/// Verify each copy by comparing the hashes of the copied file and
+		/// its copy. A copy which doesn't match is copied again once, and
+		/// then reported as an error.
+		#[clap(long = "verify")]
+		verify: bool,
+
+		/// The order in which to process entries.
+		#[clap(
+			long = "order",
+			default_value = "local",
+			arg_enum)]
+		order: EntryOrder,
+
+		/// Process archived entries, which are skipped otherwise.
+		#[clap(long = "include-archived")]
+		include_archived: bool,
+
+		/// The policy for copying files when both the stalled and remote
+		/// files have been modified since they were last copied. Overrides
+		/// the policies of the entries and the `conflict_policy` preference.
+		#[clap(
+			long = "conflict",
+			arg_enum,
+			value_name = "POLICY")]
+		conflict: Option<ConflictPolicy>,
+
+		/// Print only a line counting the copied, unchanged, skipped, and
+		/// failed files, instead of the status of each file.
+		#[clap(long = "summary-only")]
+		summary_only: bool,
+
 		/// Print intended operations instead of running them.
 		#[clap(long = "dry-run")]
 		dry_run: bool,
-	},
-}
 
-impl CommandOptions {
-	/// Returns true if the command is an `Init` variant.
-	#[must_use]
-	pub fn is_init(&self) -> bool {
-		matches!(self, CommandOptions::Init { .. })
-	}
+		/// Write the copies of the dry run into a plan file, which can be
+		/// reviewed and applied with `apply-plan`.
+		#[clap(
+			long = "plan-out",
+			value_name = "FILE",
+			parse(from_os_str),
+			requires = "dry-run",
+			conflicts_with = "recursive")]
+		plan_out: Option<PathBuf>,
+
+		/// The format of the plan file. If not given, the format is chosen
+		/// from the plan file's extension, defaulting to json.
+		#[clap(
+			long = "plan-format",
+			arg_enum,
+			requires = "plan-out")]
+		plan_format: Option<PlanFormat>,
+	},
 
-	/// Returns the provided stall path, if any.
-	#[must_use]
-	pub fn stall(&self) -> Option<&Path> {
+	/// Copi files from the stall directory to their remote locations.
+	Distribute {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+		
+
+		/// Specific files to distribute, entry indices and ranges such as `3` or
+		/// `7-9`, as printed by the status command, or groups such as
+		/// `@editor`. Defaults to all files.
+		#[clap(parse(from_os_str))]
+		files: Vec<PathBuf>,
+
+		/// Read the files from stdin, one per line. A FILES argument of `-`
+		/// also reads the files from stdin.
+		#[clap(
+			long = "stdin",
+			conflicts_with = "pick")]
+		stdin: bool,
+
+		/// Separate the files read from stdin with NUL bytes instead of
+		/// newlines, for use with `find -print0`.
+		#[clap(
+			short = '0',
+			long = "null")]
+		null: bool,
+
+		/// Choose the files to distribute using an interactive fuzzy
+		/// selector.
+		#[clap(
+			long = "pick",
+			conflicts_with = "files")]
+		pick: bool,
+
+		/// Force copy even if files are unmodified. Implies
+		/// --overwrite-newer.
+		#[clap(
+			short = 'f',
+			long = "force")]
+		force: bool,
+
+		/// Copy files even if the destination file is newer.
+		#[clap(long = "overwrite-newer")]
+		overwrite_newer: bool,
+
+		/// Copy files even if the destination file's modification time can't
+		/// be read.
+		#[clap(long = "ignore-errors")]
+		ignore_errors: bool,
+
+		/// Allow --force to overwrite the files of readonly entries.
+		#[clap(
+			long = "unpin",
+			requires = "force")]
+		unpin: bool,
+
+		/// Ask before overwriting each existing file, showing how much newer
+		/// or older and larger or smaller it is.
+		#[clap(long = "confirm")]
+		confirm: bool,
+
+		/// Work out every copy first, print them as a single plan of the files
+		/// to create or overwrite and the entries which would not be copied,
+		/// and ask once before copying any files.
+		#[clap(
+			long = "confirm-plan",
+			conflicts_with_all = &[
+				"confirm",
+				"dry-run",
+				"plan-out",
+				"privileged-script"])]
+		confirm_plan: bool,
+
+		/// Create missing remote directories. Defaults to the `create_dirs`
+		/// preference.
+		#[clap(
+			long = "create-dirs",
+			overrides_with = "no-create-dirs")]
+		create_dirs: bool,
+
+		/// Do not create missing remote directories.
+		#[clap(
+			long = "no-create-dirs",
+			overrides_with = "create-dirs")]
+		no_create_dirs: bool,
+
+		/// Do not copy overwritten remote files into the stall's trash.
+		#[clap(long = "no-trash")]
+		no_trash: bool,
+
+		/// Copy the files of all entries with elevated privileges, as if they
+		/// were flagged as privileged. Privileged copies are run through the
+		/// `escalation` preference command, which defaults to `sudo`.
+		#[clap(long = "system")]
+		system: bool,
+
+		/// Write the privileged copy commands into a shell script at the given
+		/// path instead of running them. The script is meant to be reviewed
+		/// and run as root.
+		#[clap(
+			long = "privileged-script",
+			value_name = "FILE",
+			parse(from_os_str))]
+		privileged_script: Option<PathBuf>,
+
+		/// The method for copying file contents. Reflinks clone files
+		/// instantly on file systems which support them, such as btrfs, XFS,
+		/// and APFS.
+		#[clap(
+			long = "copy-mode",
+			arg_enum,
+			default_value = "auto")]
+		copy_mode: CopyMode,
+
+		/// Copy file attributes along with the file contents. Attributes which
+		/// can't be copied produce a warning.
+		#[clap(
+			long = "preserve",
+			arg_enum,
+			use_value_delimiter = true,
+			value_name = "ATTRIBUTES")]
+		preserve: Vec<Attribute>,
+
+		/// Limit the rate at which file contents are copied, in megabytes per
+		/// second. Defaults to the `throttle` preference.
+		#[clap(
+			long = "throttle",
+			value_name = "MB/S",
+			conflicts_with = "no-throttle")]
+		throttle: Option<Throttle>,
+
+		/// Do not limit the rate at which file contents are copied.
+		#[clap(long = "no-throttle")]
+		no_throttle: bool,
+
+		/// How many times to retry copies which fail with transient errors,
+		/// optionally followed by x and the delay before the first retry,
+		/// such as 5x2s. Defaults to the `retry` preference.
+		#[clap(long = "retry", value_name = "POLICY")]
+		retry: Option<Retry>,
+
+		/// Verify each copy by comparing the hashes of the copied file and
+		/// its copy. A copy which doesn't match is copied again once, and
+		/// then reported as an error.
+		#[clap(long = "verify")]
+		verify: bool,
+
+		/// The order in which to process entries.
+		#[clap(
+			long = "order",
+			default_value = "local",
+			arg_enum)]
+		order: EntryOrder,
+
+		/// Process archived entries, which are skipped otherwise.
+		#[clap(long = "include-archived")]
+		include_archived: bool,
+
+		/// The policy for copying files when both the stalled and remote
+		/// files have been modified since they were last copied. Overrides
+		/// the policies of the entries and the `conflict_policy` preference.
+		#[clap(
+			long = "conflict",
+			arg_enum,
+			value_name = "POLICY")]
+		conflict: Option<ConflictPolicy>,
+
+		/// Print only a line counting the copied, unchanged, skipped, and
+		/// failed files, instead of the status of each file.
+		#[clap(long = "summary-only")]
+		summary_only: bool,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+
+		/// Write the copies of the dry run into a plan file, which can be
+		/// reviewed and applied with `apply-plan`.
+		#[clap(
+			long = "plan-out",
+			value_name = "FILE",
+			parse(from_os_str),
+			requires = "dry-run")]
+		plan_out: Option<PathBuf>,
+
+		/// The format of the plan file. If not given, the format is chosen
+		/// from the plan file's extension, defaulting to json.
+		#[clap(
+			long = "plan-format",
+			arg_enum,
+			requires = "plan-out")]
+		plan_format: Option<PlanFormat>,
+	},
+
+	/// Copy the files planned by a dry run of collect or distribute.
+	///
+	/// Exactly the planned files are copied, whether or not they have been
+	/// modified. A file is not copied if it has changed since the plan was
+	/// written, unless `--force` is given.
+	ApplyPlan {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The JSON plan file written by `--plan-out`.
+		#[clap(
+			value_name = "PLAN",
+			parse(from_os_str))]
+		plan: PathBuf,
+
+		/// Copy files which have changed since the plan was written.
+		#[clap(short = 'f', long = "force")]
+		force: bool,
+
+		/// Don't copy overwritten remote files into the trash.
+		#[clap(long = "no-trash")]
+		no_trash: bool,
+	},
+
+	/// Distribute all files from a stall onto a new machine.
+	///
+	/// Verifies that each remote location is accessible, then distributes
+	/// every file, creating missing remote directories. Remote files which
+	/// differ from the stalled files are only overwritten after confirmation.
+	Bootstrap {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// Overwrite differing remote files without asking for confirmation.
+		#[clap(
+			short = 'y',
+			long = "yes")]
+		yes: bool,
+
+		/// Do not copy overwritten remote files into the stall's trash.
+		#[clap(long = "no-trash")]
+		no_trash: bool,
+
+		/// The method for copying file contents. Reflinks clone files
+		/// instantly on file systems which support them, such as btrfs, XFS,
+		/// and APFS.
+		#[clap(
+			long = "copy-mode",
+			arg_enum,
+			default_value = "auto")]
+		copy_mode: CopyMode,
+
+		/// Copy file attributes along with the file contents. Attributes which
+		/// can't be copied produce a warning.
+		#[clap(
+			long = "preserve",
+			arg_enum,
+			use_value_delimiter = true,
+			value_name = "ATTRIBUTES")]
+		preserve: Vec<Attribute>,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Set or clear the flags of files in a stall.
+	Flag {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The files to modify, or groups such as `@editor`.
+		#[clap(
+			required = true,
+			parse(from_os_str))]
+		files: Vec<PathBuf>,
+
+		/// Prevent collect from overwriting the stalled files.
+		#[clap(
+			long = "pin",
+			conflicts_with = "unpin")]
+		pin: bool,
+
+		/// Allow collect to overwrite the stalled files.
+		#[clap(long = "unpin")]
+		unpin: bool,
+
+		/// Prevent distribute from overwriting the remote files.
+		#[clap(
+			long = "readonly",
+			conflicts_with = "writable")]
+		readonly: bool,
+
+		/// Allow distribute to overwrite the remote files.
+		#[clap(long = "writable")]
+		writable: bool,
+
+		/// Skip the files of remote directories which are ignored by git when
+		/// collecting.
+		#[clap(
+			long = "gitignore",
+			conflicts_with = "no-gitignore")]
+		gitignore: bool,
+
+		/// Collect all files of remote directories, including those ignored
+		/// by git.
+		#[clap(long = "no-gitignore")]
+		no_gitignore: bool,
+
+		/// Treat the files as binary, so their contents are not compared by
+		/// status or printed by diff.
+		#[clap(
+			long = "binary",
+			conflicts_with = "no-binary")]
+		binary: bool,
+
+		/// Compare and print the contents of the files.
+		#[clap(long = "no-binary")]
+		no_binary: bool,
+
+		/// Distribute the files with elevated privileges, using the command
+		/// set by the `escalation` preference.
+		#[clap(
+			long = "privileged",
+			conflicts_with = "unprivileged")]
+		privileged: bool,
+
+		/// Distribute the files without elevated privileges.
+		#[clap(long = "unprivileged")]
+		unprivileged: bool,
+
+		/// Keep the entries for record-keeping, but skip them in status,
+		/// collect, and distribute unless `--include-archived` is given.
+		#[clap(
+			long = "archive",
+			conflicts_with = "unarchive")]
+		archive: bool,
+
+		/// Include the entries in status, collect, and distribute.
+		#[clap(long = "unarchive")]
+		unarchive: bool,
+
+		/// The line ending to convert the remote files to when distributing.
+		/// Unless it is `preserve`, collected files are normalized to `lf`.
+		#[clap(
+			long = "eol",
+			arg_enum,
+			value_name = "EOL")]
+		eol: Option<LineEnding>,
+
+		/// The transforms to apply to the files when they are copied, in the
+		/// order they are applied when distributing: `trim-whitespace`,
+		/// `collect-cmd=<CMD>`, or `distribute-cmd=<CMD>`. Replaces any
+		/// existing transforms.
+		#[clap(
+			long = "transform",
+			value_name = "TRANSFORM",
+			conflicts_with = "clear-transforms")]
+		transforms: Vec<Transform>,
+
+		/// Remove all transforms from the files.
+		#[clap(long = "clear-transforms")]
+		clear_transforms: bool,
+
+		/// The policy for copying the files when both have been modified
+		/// since they were last copied.
+		#[clap(
+			long = "conflict",
+			arg_enum,
+			value_name = "POLICY",
+			conflicts_with = "clear-conflict")]
+		conflict: Option<ConflictPolicy>,
+
+		/// Use the `conflict_policy` preference for the files.
+		#[clap(long = "clear-conflict")]
+		clear_conflict: bool,
+
+		/// The entries to distribute before the files, e.g. a directory
+		/// containing them. Replaces any existing dependencies.
+		#[clap(
+			long = "after",
+			value_name = "ENTRY",
+			parse(from_os_str),
+			conflicts_with = "clear-after")]
+		after: Vec<PathBuf>,
+
+		/// Remove all dependencies from the files.
+		#[clap(long = "clear-after")]
+		clear_after: bool,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Create, modify, or list named groups of entries in a stall.
+	Group {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The group action to perform.
+		#[clap(subcommand)]
+		action: GroupAction,
+	},
+
+	/// Open the stall file in an editor, checking it after the editor exits.
+	Edit {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+	},
+
+	/// Add the entries of another stall to a stall.
+	Merge {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The stall file or directory to merge entries from, or the name of
+		/// a registered stall.
+		#[clap(parse(from_os_str))]
+		other: PathBuf,
+
+		/// Keep this stall's entries when they conflict with the other
+		/// stall's entries.
+		#[clap(
+			long = "ours",
+			conflicts_with_all = &["theirs", "interactive"])]
+		ours: bool,
+
+		/// Replace this stall's entries with the other stall's entries when
+		/// they conflict.
+		#[clap(
+			long = "theirs",
+			conflicts_with = "interactive")]
+		theirs: bool,
+
+		/// Ask which entry to keep for each conflict.
+		#[clap(long = "interactive")]
+		interactive: bool,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Move groups of files in a stall into new stalls in subdirectories.
+	Split {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// How to group the files into new stalls.
+		#[clap(
+			long = "by",
+			arg_enum,
+			value_name = "METHOD")]
+		by: SplitBy,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Run a shell command for each file in a stall. The `STALL_LOCAL`,
+	/// `STALL_REMOTE`, and `STALL_NAME` environment variables are set for each
+	/// command.
+	Foreach {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The shell command to run. Use `--` to separate the command from
+		/// stall options.
+		#[clap(required = true)]
+		command: Vec<String>,
+
+		/// Glob patterns for selecting files by name, or groups such as
+		/// `@editor`. Defaults to all files.
+		#[clap(
+			long = "filter",
+			parse(from_os_str))]
+		filter: Vec<PathBuf>,
+
+		/// Only run for files which are up to date.
+		#[clap(long = "clean")]
+		clean: bool,
+
+		/// Only run for files which are out of date.
+		#[clap(long = "modified")]
+		modified: bool,
+
+		/// Only run for files which are missing or unreadable.
+		#[clap(long = "missing")]
+		missing: bool,
+
+		/// Also run for archived entries, which are skipped otherwise.
+		#[clap(long = "include-archived")]
+		include_archived: bool,
+
+		/// The number of commands to run in parallel. [default: 1]
+		#[clap(
+			short = 'j',
+			long = "jobs")]
+		jobs: Option<usize>,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Write or verify a checksum manifest of stalled and remote files.
+	Manifest {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The manifest action to perform.
+		#[clap(subcommand)]
+		action: ManifestAction,
+	},
+
+	/// Pack the stall file and stalled files into an archive.
+	Archive {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The archive file to write.
+		#[clap(parse(from_os_str))]
+		output: PathBuf,
+
+		/// The archive format. If not given, the format is chosen from the
+		/// archive file's extension, defaulting to tar-gz.
+		#[clap(
+			long = "format",
+			arg_enum)]
+		format: Option<ArchiveFormat>,
+
+		/// Encrypt the archive with a passphrase using gpg. The passphrase is
+		/// read from the `STALL_PASSPHRASE` environment variable if it is set.
+		#[clap(long = "encrypt")]
+		encrypt: bool,
+
+		/// Overwrite the archive file if it exists.
+		#[clap(short = 'f', long = "force")]
+		force: bool,
+
+		/// Print the files to archive instead of writing the archive.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Restore a stall from an archive written by the archive command.
+	Unpack {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The archive file to restore.
+		#[clap(parse(from_os_str))]
+		archive: PathBuf,
+
+		/// The directory to restore the stall into. Defaults to the current
+		/// directory.
+		#[clap(parse(from_os_str))]
+		dir: Option<PathBuf>,
+
+		/// Overwrite existing files.
+		#[clap(short = 'f', long = "force")]
+		force: bool,
+
+		/// Print the files to restore instead of restoring them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Write a shell script which restores the stalled files to their remote
+	/// locations without stall installed.
+	ExportScript {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The script file to write. If not given, the script is printed.
+		#[clap(
+			long = "output",
+			parse(from_os_str))]
+		output: Option<PathBuf>,
+
+		/// The shell to write the script for. If not given, the shell is
+		/// chosen from the script file's extension, defaulting to sh.
+		#[clap(
+			long = "shell",
+			arg_enum)]
+		shell: Option<ScriptShell>,
+
+		/// Overwrite the script file if it exists.
+		#[clap(short = 'f', long = "force")]
+		force: bool,
+	},
+
+	/// Remove orphaned files from the stall directory, which are not the
+	/// stalled file of any entry, such as files left behind by rm without
+	/// --delete. Orphaned files are listed, and moved into the stall's trash
+	/// after confirmation.
+	Clean {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// Remove the orphaned files without asking for confirmation.
+		#[clap(
+			short = 'y',
+			long = "yes")]
+		yes: bool,
+
+		/// Choose the orphaned files to remove using an interactive fuzzy
+		/// selector.
+		#[clap(long = "pick")]
+		pick: bool,
+
+		/// Pack the orphaned files into an archive file before removing them,
+		/// instead of moving them into the trash. The archive format is chosen
+		/// from the file's extension, defaulting to tar-gz.
+		#[clap(
+			long = "archive",
+			value_name = "FILE",
+			parse(from_os_str))]
+		archive: Option<PathBuf>,
+
+		/// Permanently delete orphaned files instead of moving them into the
+		/// trash.
+		#[clap(
+			long = "no-trash",
+			conflicts_with = "archive")]
+		no_trash: bool,
+
+		/// List the orphaned files instead of removing them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Manage files deleted or overwritten by stall commands.
+	Trash {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The trash action to perform.
+		#[clap(subcommand)]
+		action: TrashAction,
+	},
+
+	/// Manage previous versions of stalled files overwritten by collect.
+	Versions {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The versions action to perform.
+		#[clap(subcommand)]
+		action: VersionsAction,
+	},
+
+	/// Run collect or distribute periodically using systemd, launchd, or cron.
+	Schedule {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The schedule action to perform.
+		#[clap(subcommand)]
+		action: ScheduleAction,
+	},
+
+	/// Show the history of operations which modified a stall's entries.
+	History {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// Only show operations involving the file with this name in the
+		/// stall.
+		#[clap(
+			long = "entry",
+			parse(from_os_str))]
+		entry: Option<PathBuf>,
+	},
+
+	/// Show the log of past stall operations.
+	Log {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The log action to perform.
+		#[clap(subcommand)]
+		action: LogAction,
+	},
+
+	/// Get or set the configured default options.
+	Config {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+
+		/// The config action to perform.
+		#[clap(subcommand)]
+		action: ConfigAction,
+	},
+
+	/// Print the locations of the config, prefs, state, cache, and stall
+	/// files.
+	Paths {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+	},
+
+	/// Edit or set user preferences.
+	Prefs {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The prefs action to perform.
+		#[clap(subcommand)]
+		action: PrefsAction,
+	},
+
+	/// Manage named stalls.
+	Stalls {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The named stall action to perform.
+		#[clap(subcommand)]
+		action: StallsAction,
+	},
+
+	/// Generate a shell completion script.
+	Completions {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The shell to generate completions for.
+		#[clap(arg_enum)]
+		shell: clap_complete::Shell,
+	},
+
+	/// List stall entry names for shell completion.
+	#[clap(name = "complete-entries", hide = true)]
+	CompleteEntries {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The stall file or directory.
+		#[clap(
+			short = 's',
+			long = "stall",
+			parse(from_os_str))]
+		stall: Option<PathBuf>,
+	},
+
+	/// Print this message or the help of the given subcommand.
+	Help {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The subcommand to print help for.
+		subcommand: Option<String>,
+
+		/// Print the help for every subcommand.
+		#[clap(
+			long = "all",
+			conflicts_with = "subcommand")]
+		all: bool,
+	},
+
+	/// Generate man pages for stall and its subcommands.
+	GenManpage {
+		/// Common command options.
+		#[clap(flatten)]
+		common: CommonOptions,
+
+		/// The directory to write man pages into. If not provided, the man
+		/// page for stall is written to stdout.
+		#[clap(
+			long = "out-dir",
+			parse(from_os_str))]
+		out_dir: Option<PathBuf>,
+	},
+}
+
+impl CommandOptions {
+	/// Returns true if the command is an `Init` variant.
+	#[must_use]
+	pub fn is_init(&self) -> bool {
+		matches!(self, CommandOptions::Init { .. })
+	}
+
+	/// Returns true if the command modifies the stall and must hold the stall
+	/// lock while it runs.
+	#[must_use]
+	pub fn modifies_stall(&self) -> bool {
+		use CommandOptions::*;
+		match self {
+			Init { dry_run, .. }       |
+			Add { dry_run, .. }        |
+			Remove { dry_run, .. }     |
+			Prune { dry_run, .. }      |
+			Relocate { dry_run, .. }   |
+			Move { dry_run, .. }       |
+			Duplicate { dry_run, .. }  |
+			Collect { dry_run, .. }    |
+			Distribute { dry_run, .. } |
+			Bootstrap { dry_run, .. }  |
+			Flag { dry_run, .. }       |
+			Merge { dry_run, .. }      |
+			Split { dry_run, .. }      |
+			Clean { dry_run, .. }      => !dry_run,
+
+			Edit { .. }      |
+			ApplyPlan { .. } => true,
+
+			Group { action, .. } => match action {
+				GroupAction::Create { dry_run, .. } |
+				GroupAction::Add { dry_run, .. }    |
+				GroupAction::Remove { dry_run, .. } => !dry_run,
+				GroupAction::List { .. }            => false,
+			},
+
+			Manifest { action, .. } => match action {
+				ManifestAction::Write { dry_run, .. } => !dry_run,
+				ManifestAction::Verify            => false,
+			},
+
+			Trash { action, .. } => match action {
+				TrashAction::List                  => false,
+				TrashAction::Restore { dry_run, .. } |
+				TrashAction::Empty { dry_run }     => !dry_run,
+			},
+
+			Versions { action, .. } => match action {
+				VersionsAction::List { .. } |
+				VersionsAction::Show { .. }             => false,
+				VersionsAction::Restore { dry_run, .. } => !dry_run,
+			},
+
+			// The stall config file is kept in the stall directory.
+			Config { action, .. } => matches!(action,
+				ConfigAction::Set { level: ConfigLevel::Stall, .. } |
+				ConfigAction::Unset { level: ConfigLevel::Stall, .. }),
+
+			// Commands run by `foreach` may invoke stall themselves, so it
+			// doesn't hold the lock.
+			Status { .. }          |
+			Diff { .. }            |
+			Grep { .. }            |
+			Show { .. }            |
+			Du { .. }              |
+			List { .. }            |
+			Foreach { .. }         |
+			Schedule { .. }        |
+			Archive { .. }         |
+			Unpack { .. }          |
+			ExportScript { .. }    |
+			History { .. }         |
+			Log { .. }             |
+			Paths { .. }           |
+			Prefs { .. }           |
+			Stalls { .. }          |
+			Completions { .. }     |
+			CompleteEntries { .. } |
+			Help { .. }            |
+			GenManpage { .. }      => false,
+		}
+	}
+
+	/// Returns true if the command copies the files of entries, and so stops
+	/// between files when it is cancelled.
+	#[must_use]
+	pub fn copies_files(&self) -> bool {
+		use CommandOptions::*;
+		matches!(self,
+			Collect { .. }    |
+			Distribute { .. } |
+			Bootstrap { .. }  |
+			ApplyPlan { .. })
+	}
+
+	/// Returns true if the command may be run on a read-only stall.
+	/// `distribute` is allowed, as it only copies files out of the stall.
+	#[must_use]
+	pub fn allowed_readonly(&self) -> bool {
+		matches!(self, Self::Distribute { .. })
+			|| !self.modifies_stall()
+	}
+
+	/// Returns the [`Operation`] to record in the stall's journal when the
+	/// command is run, if any. Dry runs are not recorded.
+	///
+	/// [`Operation`]: ../journal/enum.Operation.html
+	#[must_use]
+	pub fn journal_operation(&self) -> Option<Operation> {
+		use CommandOptions::*;
+		let (operation, dry_run) = match self {
+			Add { dry_run, .. }        => (Operation::Add, dry_run),
+			Remove { dry_run, .. }     => (Operation::Remove, dry_run),
+			Prune { dry_run, .. }      => (Operation::Prune, dry_run),
+			Relocate { dry_run, .. }   => (Operation::Relocate, dry_run),
+			Move { dry_run, .. }       => (Operation::Move, dry_run),
+			Duplicate { dry_run, .. }  => (Operation::Duplicate, dry_run),
+			Collect { dry_run, .. }    => (Operation::Collect, dry_run),
+			Distribute { dry_run, .. } => (Operation::Distribute, dry_run),
+			Bootstrap { dry_run, .. }  => (Operation::Bootstrap, dry_run),
+			Flag { dry_run, .. }       => (Operation::Flag, dry_run),
+			Merge { dry_run, .. }      => (Operation::Merge, dry_run),
+			Split { dry_run, .. }      => (Operation::Split, dry_run),
+			_ => return None,
+		};
+		(!dry_run).then_some(operation)
+	}
+
+	/// Returns the provided stall path, if any.
+	#[must_use]
+	pub fn stall(&self) -> Option<&Path> {
 		use CommandOptions::*;
 		match self {
-			Init { stall, .. }       |
+			Init { stall, stall_option, .. } => stall
+				.as_deref()
+				.or(stall_option.as_deref()),
+
 			Status { stall, .. }     |
+			Diff { stall, .. }       |
+			Grep { stall, .. }       |
+			Show { stall, .. }       |
+			Du { stall, .. }         |
+			List { stall, .. }       |
 			Add { stall, .. }        |
 			Remove { stall, .. }     |
+			Prune { stall, .. }      |
+			Relocate { stall, .. }   |
 			Move { stall, .. }       |
+			Duplicate { stall, .. }  |
 			Collect { stall, .. }    |
-			Distribute { stall, .. } => stall.as_deref(),
+			Distribute { stall, .. } |
+			Bootstrap { stall, .. }  |
+			ApplyPlan { stall, .. }  |
+			Flag { stall, .. }       |
+			Group { stall, .. }      |
+			Edit { stall, .. }       |
+			Merge { stall, .. }      |
+			Split { stall, .. }      |
+			Foreach { stall, .. }    |
+			Manifest { stall, .. }   |
+			Archive { stall, .. }    |
+			ExportScript { stall, .. } |
+			Clean { stall, .. }      |
+			Trash { stall, .. }      |
+			Versions { stall, .. }   |
+			Schedule { stall, .. }   |
+			History { stall, .. }    |
+			Log { stall, .. }        |
+			Config { stall, .. }     |
+			Paths { stall, .. }      |
+			CompleteEntries { stall, .. } => stall.as_deref(),
+
+			Unpack { .. }      |
+			Prefs { .. }       |
+			Stalls { .. }      |
+			Completions { .. } |
+			Help { .. }        |
+			GenManpage { .. }  => None,
 		}
 	}
 
@@ -357,16 +2218,480 @@ impl CommandOptions {
 		match self {
 			Init { common, .. }       |
 			Status { common, .. }     |
+			Diff { common, .. }       |
+			Grep { common, .. }       |
+			Show { common, .. }       |
+			Du { common, .. }         |
+			List { common, .. }       |
+			Add { common, .. }        |
+			Remove { common, .. }     |
+			Prune { common, .. }      |
+			Relocate { common, .. }   |
+			Move { common, .. }       |
+			Duplicate { common, .. }  |
+			Collect { common, .. }    |
+			Distribute { common, .. } |
+			Bootstrap { common, .. }  |
+			ApplyPlan { common, .. }  |
+			Flag { common, .. } |
+			Group { common, .. } |
+			Edit { common, .. } |
+			Merge { common, .. } |
+			Split { common, .. } |
+			Foreach { common, .. } |
+			Manifest { common, .. } |
+			Archive { common, .. } |
+			Unpack { common, .. } |
+			ExportScript { common, .. } |
+			Clean { common, .. } |
+			Trash { common, .. } |
+			Versions { common, .. } |
+			Schedule { common, .. } |
+			History { common, .. } |
+			Log { common, .. } |
+			Config { common, .. } |
+			Paths { common, .. } |
+			Prefs { common, .. } |
+			Stalls { common, .. } |
+			Completions { common, .. } |
+			CompleteEntries { common, .. } |
+			Help { common, .. } |
+			GenManpage { common, .. } => common,
+		}
+	}
+
+	/// Returns the `CommonOptions` mutably.
+	#[must_use]
+	pub fn common_mut(&mut self) -> &mut CommonOptions {
+		use CommandOptions::*;
+		match self {
+			Init { common, .. }       |
+			Status { common, .. }     |
+			Diff { common, .. }       |
+			Grep { common, .. }       |
+			Show { common, .. }       |
+			Du { common, .. }         |
+			List { common, .. }       |
 			Add { common, .. }        |
 			Remove { common, .. }     |
+			Prune { common, .. }      |
+			Relocate { common, .. }   |
 			Move { common, .. }       |
+			Duplicate { common, .. }  |
 			Collect { common, .. }    |
-			Distribute { common, .. } => common,
+			Distribute { common, .. } |
+			Bootstrap { common, .. }  |
+			ApplyPlan { common, .. }  |
+			Flag { common, .. } |
+			Group { common, .. } |
+			Edit { common, .. } |
+			Merge { common, .. } |
+			Split { common, .. } |
+			Foreach { common, .. } |
+			Manifest { common, .. } |
+			Archive { common, .. } |
+			Unpack { common, .. } |
+			ExportScript { common, .. } |
+			Clean { common, .. } |
+			Trash { common, .. } |
+			Versions { common, .. } |
+			Schedule { common, .. } |
+			History { common, .. } |
+			Log { common, .. } |
+			Config { common, .. } |
+			Paths { common, .. } |
+			Prefs { common, .. } |
+			Stalls { common, .. } |
+			Completions { common, .. } |
+			CompleteEntries { common, .. } |
+			Help { common, .. } |
+			GenManpage { common, .. } => common,
 		}
 	}
 }
 
 
+////////////////////////////////////////////////////////////////////////////////
+// GroupAction
+////////////////////////////////////////////////////////////////////////////////
+/// Entry group subcommand options.
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+#[derive(clap::Subcommand)]
+pub enum GroupAction {
+	/// Create a named group of entries. Commands accepting entry names also
+	/// accept `@<NAME>` to select each entry in the group.
+	Create {
+		/// The name of the group.
+		#[clap(value_name = "NAME")]
+		name: String,
+
+		/// The initial entries of the group. Partial local names and other
+		/// groups are also accepted.
+		#[clap(parse(from_os_str))]
+		files: Vec<PathBuf>,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Add entries to a group.
+	Add {
+		/// The name of the group.
+		#[clap(value_name = "NAME")]
+		name: String,
+
+		/// The entries to add. Partial local names and other groups are also
+		/// accepted.
+		#[clap(
+			parse(from_os_str),
+			required = true)]
+		files: Vec<PathBuf>,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Remove entries from a group, or remove the group if no entries are
+	/// given. The entries are kept in the stall.
+	#[clap(name = "rm")]
+	Remove {
+		/// The name of the group.
+		#[clap(value_name = "NAME")]
+		name: String,
+
+		/// The entries to remove from the group.
+		#[clap(parse(from_os_str))]
+		files: Vec<PathBuf>,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// List the groups and their entries, or the entries of a single group.
+	List {
+		/// The name of the group to list.
+		#[clap(value_name = "NAME")]
+		name: Option<String>,
+	},
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ManifestAction
+////////////////////////////////////////////////////////////////////////////////
+/// Checksum manifest subcommand options.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy)]
+#[derive(clap::Subcommand)]
+pub enum ManifestAction {
+	/// Record the hashes of all stalled and remote files in the manifest.
+	Write {
+		/// The algorithm to hash files with. [default: sha256]
+		#[clap(
+			long = "hash",
+			arg_enum)]
+		hash: Option<HashAlgorithm>,
+
+		/// Print the hashes instead of writing the manifest.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Check stalled and remote files against the manifest. Exits with 1 if
+	/// any files have changed, are missing, or are not recorded.
+	Verify,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// LogAction
+////////////////////////////////////////////////////////////////////////////////
+/// Trace log subcommand options.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy)]
+#[derive(clap::Subcommand)]
+pub enum LogAction {
+	/// Print the trace log. The log is written when the `trace_log`
+	/// preference is set, or when `--ztrace` is given.
+	Show {
+		/// Only print entries logged since the given date (`2022-03-01`), date
+		/// and time (`2022-03-01 12:30:00`), or duration ago (`30m`, `2d`.)
+		#[clap(
+			long = "since",
+			parse(try_from_str = parse_since))]
+		since: Option<DateTime<Local>>,
+	},
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ConfigAction
+////////////////////////////////////////////////////////////////////////////////
+/// Config management subcommand options.
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+#[derive(clap::Subcommand)]
+pub enum ConfigAction {
+	/// List the configured default options and the level they are set at.
+	List,
+
+	/// Print the configured value of an option.
+	Get {
+		/// The option to print.
+		key: String,
+	},
+
+	/// Set the value of an option.
+	Set {
+		/// The option to set.
+		key: String,
+
+		/// The value to set.
+		value: String,
+
+		/// The config level to set the option at.
+		#[clap(
+			long = "level",
+			default_value = "user",
+			arg_enum)]
+		level: ConfigLevel,
+	},
+
+	/// Remove the value of an option.
+	Unset {
+		/// The option to remove.
+		key: String,
+
+		/// The config level to remove the option from.
+		#[clap(
+			long = "level",
+			default_value = "user",
+			arg_enum)]
+		level: ConfigLevel,
+	},
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TrashAction
+////////////////////////////////////////////////////////////////////////////////
+/// Trash management subcommand options.
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+#[derive(clap::Subcommand)]
+pub enum TrashAction {
+	/// List the files in the trash.
+	List,
+
+	/// Restore files from the trash to their original locations.
+	Restore {
+		/// The ids of the trash items to restore.
+		#[clap(required = true)]
+		ids: Vec<u64>,
+
+		/// Overwrite existing files at the original locations.
+		#[clap(
+			short = 'f',
+			long = "force")]
+		force: bool,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Permanently delete all files in the trash.
+	Empty {
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ScheduleAction
+////////////////////////////////////////////////////////////////////////////////
+/// Scheduled command subcommand options.
+#[allow(missing_docs)]
+#[allow(variant_size_differences)]
+#[derive(Debug, Clone, Copy)]
+#[derive(clap::Subcommand)]
+pub enum ScheduleAction {
+	/// Install a systemd timer, launchd agent, or cron job which runs a stall
+	/// command for the stall, replacing any existing schedule of the command.
+	Install {
+		/// The time between runs, such as `30m`, `1h`, `1d`, or `1w`.
+		#[clap(long = "interval")]
+		interval: Interval,
+
+		/// Cancel each run if it takes longer than the given duration, such
+		/// as `30s`, `10m`, or `1h`.
+		#[clap(
+			long = "timeout",
+			value_name = "DURATION")]
+		timeout: Option<Timeout>,
+
+		/// The stall command to run.
+		#[clap(
+			long = "command",
+			arg_enum,
+			default_value = "collect")]
+		command: ScheduledCommand,
+
+		/// The service to run the command with. Defaults to launchd on macOS,
+		/// systemd if it is available, and cron otherwise.
+		#[clap(
+			long = "backend",
+			arg_enum)]
+		backend: Option<ScheduleBackend>,
+
+		/// Print the files to install instead of installing them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+
+	/// Remove the installed schedules of the stall.
+	Remove {
+		/// The stall command whose schedule to remove. Defaults to all
+		/// commands.
+		#[clap(
+			long = "command",
+			arg_enum)]
+		command: Option<ScheduledCommand>,
+
+		/// The service the schedules are installed with.
+		#[clap(
+			long = "backend",
+			arg_enum)]
+		backend: Option<ScheduleBackend>,
+	},
+
+	/// List the installed schedules of every stall.
+	Status {
+		/// The service the schedules are installed with.
+		#[clap(
+			long = "backend",
+			arg_enum)]
+		backend: Option<ScheduleBackend>,
+	},
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// VersionsAction
+////////////////////////////////////////////////////////////////////////////////
+/// Previous version management subcommand options.
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+#[derive(clap::Subcommand)]
+pub enum VersionsAction {
+	/// List the previous versions of an entry's stalled file.
+	List {
+		/// The entry whose versions to list, or its index as printed by the
+		/// status command.
+		#[clap(parse(from_os_str))]
+		file: PathBuf,
+	},
+
+	/// Print the contents of a previous version of an entry's stalled file.
+	Show {
+		/// The entry whose version to show, or its index as printed by the
+		/// status command.
+		#[clap(parse(from_os_str))]
+		file: PathBuf,
+
+		/// The version to show, as printed by the list command. Defaults to
+		/// the most recent version.
+		version: Option<String>,
+	},
+
+	/// Replace an entry's stalled file with a previous version. The replaced
+	/// file is saved as a new version.
+	Restore {
+		/// The entry whose version to restore, or its index as printed by the
+		/// status command.
+		#[clap(parse(from_os_str))]
+		file: PathBuf,
+
+		/// The version to restore, as printed by the list command. Defaults to
+		/// the most recent version.
+		version: Option<String>,
+
+		/// Print intended operations instead of running them.
+		#[clap(long = "dry-run")]
+		dry_run: bool,
+	},
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PrefsAction
+////////////////////////////////////////////////////////////////////////////////
+/// Prefs management subcommand options.
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+#[derive(clap::Subcommand)]
+pub enum PrefsAction {
+	/// Open the prefs file in the editor given by the `VISUAL` or `EDITOR`
+	/// environment variables. A new prefs file is created if it doesn't
+	/// exist.
+	Edit,
+
+	/// Set a preference. The supported keys are `create_dirs`, `throttle`,
+	/// `copy_buffer`, `retry`, `trace_log`, `conflict_policy`, `merge_tool`,
+	/// `version_retention`, `escalation`, `notify`, `scan_paths`, and
+	/// `stalls.<NAME>`.
+	Set {
+		/// The preference to set.
+		key: String,
+
+		/// The value to set.
+		value: String,
+	},
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StallsAction
+////////////////////////////////////////////////////////////////////////////////
+/// Named stall management subcommand options.
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+#[derive(clap::Subcommand)]
+pub enum StallsAction {
+	/// List the registered named stalls.
+	List,
+
+	/// Register a stall with a name.
+	Add {
+		/// The name to register the stall as.
+		name: String,
+
+		/// The stall file or directory. Defaults to the current directory.
+		#[clap(parse(from_os_str))]
+		path: Option<PathBuf>,
+
+		/// Replace any stall already registered with the same name.
+		#[clap(
+			short = 'f',
+			long = "force")]
+		force: bool,
+	},
+
+	/// Unregister a named stall.
+	#[clap(name = "rm")]
+	Remove {
+		/// The name of the stall to unregister.
+		name: String,
+	},
+}
+
+
 
 ////////////////////////////////////////////////////////////////////////////////
 // ColorOption
@@ -401,6 +2726,16 @@ impl ColorOption {
 	}
 }
 
+impl std::fmt::Display for ColorOption {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Auto   => write!(f, "auto"),
+			Self::Always => write!(f, "always"),
+			Self::Never  => write!(f, "never"),
+		}
+	}
+}
+
 impl std::str::FromStr for ColorOption {
 	type Err = ColorOptionParseError;
 
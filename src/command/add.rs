@@ -8,17 +8,27 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Internal library imports.
+use crate::error::StallError;
 use crate::CommonOptions;
 use crate::Stall;
+use crate::entry::CopyOptions;
+use crate::entry::Overwrite;
+use crate::path;
+use crate::remote;
+use crate::report::warn;
+use crate::safety;
+use crate::state::StateFile;
+use crate::template::Disambiguation;
+use crate::template::NameTemplate;
+use crate::warning::Warning;
 
 // External library imports.
-use anyhow::anyhow;
-use anyhow::Error;
 use tracing::event;
 use tracing::Level;
 use tracing::span;
 
 // Standard library imports.
+use std::io::Write as _;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -36,7 +46,18 @@ use std::path::PathBuf;
 /// + `files`: An iterator over the [`Path`]s of the files to add.
 /// + `rename`: The name to use for any local stall path. (If use with multiple
 /// files, they will all end up with the same name.)
+/// + `rename_template`: A [`NameTemplate`] for generating the local stall path
+///   of each file from its remote path. If neither `rename` or
+///   `rename_template` are given, the stall's [`RenamePolicy`] is used.
 /// + `into`: A subdirectory within the stall to place the files.
+/// + `append`: Add the files as additional remotes of any existing entries
+///   with the same local path, instead of replacing them.
+/// + `disambiguate`: The [`Disambiguation`] to use for choosing another local
+///   path if a file's local path is used by a different file.
+/// + `force`: Replace the entries of any different files with the same local
+///   paths.
+/// + `no_gitignore`: Set the entries' no-gitignore flag, so that files ignored
+///   by git are collected from remote directories within git repositories.
 /// + `collect_stall_dir`: The stall directory to collect into, or `None` if no
 /// collect should occur.
 /// + `dry_run`: Do not modify any files.
@@ -44,31 +65,48 @@ use std::path::PathBuf;
 ///
 /// ### Errors
 /// 
-/// Returns an [`Error`] if both files exist but their metadata can't be read,
-/// if the copy operation fails, or if any IO errors occur.
+/// Returns a [`StallError`] if a remote path has an unsupported URL scheme, if
+/// a remote path is inside the stall directory or is the same file as its
+/// stalled file, if a local path is used by a different file and neither `disambiguate` nor
+/// `force` are given, if both files exist but their
+/// metadata can't be read,
+/// if a path has no file name, if the copy operation fails, or if any IO errors
+/// occur.
 /// 
 /// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
 /// [`Stall`]: ../struct.Stall.html
+/// [`NameTemplate`]: ../template/struct.NameTemplate.html
+/// [`RenamePolicy`]: ../template/enum.RenamePolicy.html
+/// [`Disambiguation`]: ../template/enum.Disambiguation.html
 /// [`CommonOptions`]: ../command/struct.CommonOptions.html
-/// [`Error`]: ../error/struct.Error.html
+/// [`StallError`]: ../error/enum.StallError.html
 /// 
+#[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
 pub fn add<'i, I>(
     stall: &mut Stall,
     files: I,
     rename: Option<&Path>,
+    rename_template: Option<&NameTemplate>,
     into: Option<&Path>,
+    append: bool,
+    disambiguate: Option<Disambiguation>,
+    force: bool,
+    no_gitignore: bool,
     collect_stall_dir: Option<&Path>,
     dry_run: bool,
     common: &CommonOptions)
-    -> Result<(), Error>
+    -> Result<(), StallError>
     where I: IntoIterator<Item=&'i Path>
 {
     let _span = span!(Level::INFO, "add").entered();
     if dry_run && common.quiet { return Ok(()); }
 
-    for remote in files {
+    for (n, remote) in files.into_iter().enumerate() {
         event!(Level::DEBUG, "Add entry with remote path: {:?}", remote);
 
+        // Reject remote paths which no backend can access.
+        let _ = remote::backend(remote)?;
+
         let mut local = PathBuf::new();
 
         if let Some(path) = into {
@@ -77,21 +115,51 @@ pub fn add<'i, I>(
 
         if let Some(f) = rename {
             local.push(f);
-        } else if let Some(f) = remote.file_name() {
-            local.push(f);
+        } else if let Some(template) = rename_template {
+            local.push(template.render(remote, n + 1));
+        } else if let Some(name) = stall.rename_policy().local_name(
+            remote,
+            |name| !append && stall
+                .entries_local(&local.join(name))
+                .any(|entry| !path::same(entry.remote, remote)))
+        {
+            local.push(name);
         } else {
             if !common.quiet {
                 println!("Invalid remote file name: {}", remote.display());
             }
-            event!(Level::WARN, "invalid remote file name: {:?}", remote);
-            if common.promote_warnings_to_errors {
-                return Err(anyhow!("invalid remote file name: {:?}", remote));
-            }
+            warn(Warning::InvalidRemoteName {
+                path: remote.to_path_buf(),
+            }, common)?;
             continue;
         };
 
+        // Resolve collisions with the entries of other files. Names are
+        // shared deliberately when appending remotes.
+        let in_use = |local: &Path| !append && stall
+            .entries_local(local)
+            .any(|entry| !path::same(entry.remote, remote));
+        if in_use(&local) && !force {
+            let Some(disambiguate) = disambiguate else {
+                return Err(StallError::Conflict {
+                    reason: "stall file already exists for a different \
+                        remote file".to_owned(),
+                    subject: local.display().to_string(),
+                    hint: Some("Use --disambiguate option to choose another \
+                        name, or --force option to replace it.".to_owned()),
+                });
+            };
+            local = disambiguate.resolve(&local, remote, in_use);
+        }
+
         event!(Level::DEBUG, "      ... with local path: {:?}", local);
 
+        // Reject remote paths which would copy a stalled file onto itself.
+        let stall_dir = collect_stall_dir.or_else(|| stall.load_dir());
+        if let Some(stall_dir) = stall_dir {
+            safety::check_remote(stall_dir, &local, remote)?;
+        }
+
         if dry_run {
             println!("Insert stall entry {} from {}",
                 local.display(),
@@ -99,14 +167,46 @@ pub fn add<'i, I>(
             return Ok(())
         }
 
-        stall.insert(local, remote.to_owned());
+        if append {
+            let moved = stall.insert_remote(local.clone(), remote)?;
+            if let Some((local, remote)) = moved.filter(|_| !common.quiet) {
+                writeln!(&mut std::io::stdout(),
+                    "Moved remote {} from stall entry {}",
+                    remote.display(),
+                    local.display())?;
+            }
+        } else {
+            let displaced = stall.insert(local.clone(), remote)?;
+            if let Some((local, remote)) = displaced.filter(|_| !common.quiet) {
+                writeln!(&mut std::io::stdout(),
+                    "Replaced stall entry {} from {}",
+                    local.display(),
+                    remote.display())?;
+            }
+        }
+
+        if no_gitignore {
+            if let Some(mut flags) = stall.flags(&local) {
+                flags.no_gitignore = true;
+                let _ = stall.set_flags(&local, flags);
+            }
+        }
 
         if let Some(stall_dir) = collect_stall_dir {
             let mut out = std::io::stdout();
 
-            stall.entry_remote(remote)
+            let _ = stall.entry_remote(remote)
                 .expect("get added entry for collect")
-                .collect(&mut out, stall_dir, false, dry_run, common)?;
+                .collect(
+                    &mut out,
+                    stall_dir,
+                    Overwrite::default(),
+                    false,
+                    &CopyOptions::default(),
+                    &mut StateFile::default(),
+                    None,
+                    dry_run,
+                    common)?;
         }
     }
 
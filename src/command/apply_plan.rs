@@ -0,0 +1,196 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Apply the copies of a plan file.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::CommonOptions;
+use crate::Stall;
+use crate::cancel;
+use crate::entry::CopyOptions;
+use crate::entry::Entry;
+use crate::entry::Outcome;
+use crate::entry::Overwrite;
+use crate::path;
+use crate::plan::Plan;
+use crate::plan::PlanCommand;
+use crate::plan::PlanStep;
+use crate::report::CopyReport;
+use crate::state::StateFile;
+use crate::trash::Trash;
+
+// External library imports.
+use colored::Colorize as _;
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// apply_plan
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-apply-plan' command.
+///
+/// This will copy the file of each entry recorded in the [`Plan`] in the
+/// direction of the planned command, in the planned order, bypassing the
+/// checks which decided whether the file should be copied. A file which has
+/// changed since the plan was written is not copied unless `force` is given.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory to apply the plan to.
+/// + `stall`: The loaded `Stall` data.
+/// + `plan`: The [`Plan`] to apply.
+/// + `force`: Copy files which have changed since the plan was written.
+/// + `use_trash`: Copy remote files overwritten by a distribute plan into the
+///   stall's [`Trash`], unless `common.readonly` is set.
+/// + `options`: The [`CopyOptions`] to use for copying files.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// Processing continues after a step fails, and the outcome of each step is
+/// returned in a [`CopyReport`] in the same order, whose totals and failures
+/// are not printed.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the plan was written for a different stall
+/// directory, or if an IO error occurs during writing.
+///
+/// [`Plan`]: ../plan/struct.Plan.html
+/// [`Trash`]: ../trash/struct.Trash.html
+/// [`CopyOptions`]: ../entry/struct.CopyOptions.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`CopyReport`]: ../report/struct.CopyReport.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn apply_plan(
+	stall_dir: &Path,
+	stall: &Stall,
+	plan: &Plan,
+	force: bool,
+	use_trash: bool,
+	options: &CopyOptions,
+	common: &CommonOptions)
+	-> Result<CopyReport, StallError>
+{
+	let _span = span!(Level::INFO, "apply_plan").entered();
+
+	let canonical = |dir: &Path| dir
+		.canonicalize()
+		.unwrap_or_else(|_| dir.to_path_buf());
+	if !path::same(&canonical(stall_dir), &canonical(plan.stall_dir())) {
+		return Err(StallError::InvalidInput {
+			message: format!("plan was written for the stall in {}",
+				plan.stall_dir().display()),
+		});
+	}
+
+	let mut out = std::io::stdout();
+	if common.color.enabled() {
+		writeln!(&mut out, "{} {}",
+			"Stall directory:".bright_white(),
+			stall_dir.display())?;
+	} else {
+		writeln!(&mut out, "Stall directory: {}",
+			stall_dir.display())?;
+	}
+
+	let mut trash = if use_trash
+		&& !common.readonly
+		&& plan.command() == PlanCommand::Distribute
+	{
+		Some(Trash::open(stall_dir, common.backup_dir.as_deref())?)
+	} else {
+		None
+	};
+
+	Entry::write_status_action_header(&mut out, common)?;
+	let mut state = StateFile::read_from_stall_dir(stall_dir);
+	let mut report = CopyReport::new();
+	let total = plan.steps().len();
+	for (index, step) in plan.steps().iter().enumerate() {
+		// Cancelled commands stop between entries.
+		if let Some(reason) = cancel::cancelled() {
+			report.set_cancelled(reason, total - index);
+			break;
+		}
+		let res = apply_step(
+			&mut out,
+			stall_dir,
+			stall,
+			plan,
+			step,
+			force,
+			options,
+			&mut state,
+			trash.as_mut(),
+			common);
+		report.record(&step.local, res);
+	}
+	if !common.readonly {
+		if let Err(e) = state.write_to_stall_dir(stall_dir) {
+			event!(Level::WARN, "{e}");
+		}
+	}
+
+	Ok(report)
+}
+
+/// Copies the file of the entry of a single plan step, returning its
+/// [`Outcome`].
+///
+/// [`Outcome`]: ../entry/enum.Outcome.html
+#[allow(clippy::too_many_arguments)]
+fn apply_step(
+	out: &mut dyn std::io::Write,
+	stall_dir: &Path,
+	stall: &Stall,
+	plan: &Plan,
+	step: &PlanStep,
+	force: bool,
+	options: &CopyOptions,
+	state: &mut StateFile,
+	trash: Option<&mut Trash>,
+	common: &CommonOptions)
+	-> Result<Outcome, StallError>
+{
+	let entry = stall
+		.entries_local(&step.local)
+		.find(|entry| path::same(entry.remote, &step.remote))
+		.ok_or_else(|| stall.missing_entry(&step.local))?;
+	if !force { step.check_source(plan)?; }
+
+	match plan.command() {
+		PlanCommand::Collect => entry.collect(
+			out,
+			stall_dir,
+			Overwrite::FORCE,
+			true,
+			options,
+			state,
+			None,
+			false,
+			common),
+		PlanCommand::Distribute => entry.distribute(
+			out,
+			stall_dir,
+			Overwrite::FORCE,
+			true,
+			true,
+			options,
+			state,
+			None,
+			trash,
+			false,
+			common),
+	}
+}
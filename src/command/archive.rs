@@ -0,0 +1,236 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Stall archive commands.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::archive::ArchiveFormat;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::report::warn;
+use crate::warning::Warning;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::collections::BTreeSet;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// archive
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-archive' command.
+///
+/// This will pack the stall file and the local files of each entry in the
+/// [`Stall`] into an archive file.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the local files.
+/// + `stall`: The loaded `Stall` data.
+/// + `output`: The path of the archive file to write.
+/// + `format`: The [`ArchiveFormat`] to write. If `None`, the format is chosen
+///   from the extension of `output`.
+/// + `encrypt`: Encrypt the archive with a passphrase.
+/// + `force`: Overwrite `output` if it exists.
+/// + `dry_run`: Print the files to archive instead of writing the archive.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if `output` exists and `force` is not given, if
+/// the stall file is not in the stall directory, if the archive command fails,
+/// or if an IO error occurs during writing.
+///
+/// [`Stall`]: ../struct.Stall.html
+/// [`ArchiveFormat`]: ../archive/enum.ArchiveFormat.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+#[allow(clippy::too_many_arguments)]
+pub fn archive(
+	stall_dir: &Path,
+	stall: &Stall,
+	output: &Path,
+	format: Option<ArchiveFormat>,
+	encrypt: bool,
+	force: bool,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "archive").entered();
+	let format = format
+		.or_else(|| ArchiveFormat::from_path(output))
+		.unwrap_or_default();
+
+	// The stall file is stored at the root of the archive.
+	let stall_file = stall.load_path()
+		.and_then(|path| path.strip_prefix(stall_dir).ok())
+		.ok_or_else(|| StallError::InvalidPath {
+			path: stall.load_path().unwrap_or(stall_dir).to_path_buf(),
+			reason: "stall file is not in the stall directory".to_owned(),
+		})?;
+	let mut files = vec![stall_file.to_path_buf()];
+
+	let locals: BTreeSet<&Path> = stall.entries()
+		.map(|entry| entry.local)
+		.collect();
+	for local in locals {
+		let full_local = stall_dir.join(local);
+		if full_local.exists() {
+			files.push(local.to_path_buf());
+		} else {
+			warn(Warning::MissingLocal { path: full_local }, common)?;
+		}
+	}
+
+	let mut out = std::io::stdout();
+	if dry_run {
+		if !common.quiet {
+			for file in &files {
+				writeln!(&mut out, "Archive {}", file.display())?;
+			}
+		}
+		return Ok(());
+	}
+
+	if output.exists() {
+		if !force {
+			return Err(StallError::Conflict {
+				reason: "archive file already exists".to_owned(),
+				subject: output.display().to_string(),
+				hint: Some("Use --force option to overwrite it.".to_owned()),
+			});
+		}
+		// Zip archives are updated rather than replaced, so any existing
+		// file is removed first.
+		std::fs::remove_file(output)
+			.with_path("failed to remove archive", output)?;
+	}
+
+	event!(Level::INFO, "Writing {} archive {:?}", format, output);
+	crate::archive::create(output, stall_dir, &files, format, encrypt)?;
+
+	if !common.quiet {
+		writeln!(&mut out, "Archived {} files into {}",
+			files.len(),
+			output.display())?;
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// unpack
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-unpack' command.
+///
+/// This will restore the stall file and local files from an archive created
+/// by the 'stall-archive' command into a stall directory. Encrypted archives
+/// are decrypted first.
+///
+/// ### Parameters
+///
+/// + `archive`: The path of the archive file to restore.
+/// + `dir`: The directory to restore the stall into.
+/// + `force`: Overwrite any existing files in `dir`.
+/// + `dry_run`: Print the files to restore instead of restoring them.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the archive's format is not recognized, if it
+/// contains paths outside of the stall directory, if any of its files exist in
+/// `dir` and `force` is not given, if the extraction or decryption commands
+/// fail, or if an IO error occurs during writing.
+///
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn unpack(
+	archive: &Path,
+	dir: &Path,
+	force: bool,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "unpack").entered();
+
+	if !dir.is_dir() {
+		return Err(StallError::InvalidPath {
+			path: dir.to_path_buf(),
+			reason: "directory does not exist".to_owned(),
+		});
+	}
+
+	if crate::archive::is_encrypted(archive)? {
+		let decrypted = crate::archive::temp_path(&dir.join(
+			archive.file_name().unwrap_or_default()));
+		event!(Level::INFO, "Decrypting {:?} into {:?}", archive, decrypted);
+		let res = crate::archive::decrypt(archive, &decrypted)
+			.and_then(|()| unpack_files(&decrypted, dir, force, dry_run, common));
+		let _ = std::fs::remove_file(&decrypted);
+		res
+	} else {
+		unpack_files(archive, dir, force, dry_run, common)
+	}
+}
+
+/// Restores the files of an unencrypted archive into `dir`.
+fn unpack_files(
+	archive: &Path,
+	dir: &Path,
+	force: bool,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let members = crate::archive::list(archive)?;
+	let files: Vec<&PathBuf> = members
+		.iter()
+		.filter(|member| !member.to_string_lossy().ends_with('/'))
+		.collect();
+
+	if !force {
+		if let Some(file) = files.iter().find(|file| dir.join(file).exists()) {
+			return Err(StallError::Conflict {
+				reason: "file already exists".to_owned(),
+				subject: dir.join(file).display().to_string(),
+				hint: Some("Use --force option to overwrite it.".to_owned()),
+			});
+		}
+	}
+
+	let mut out = std::io::stdout();
+	if dry_run {
+		if !common.quiet {
+			for file in &files {
+				writeln!(&mut out, "Unpack {}", file.display())?;
+			}
+		}
+		return Ok(());
+	}
+
+	crate::archive::extract(archive, dir)?;
+
+	if !common.quiet {
+		writeln!(&mut out, "Unpacked {} files into {}",
+			files.len(),
+			dir.display())?;
+	}
+	Ok(())
+}
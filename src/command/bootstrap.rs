@@ -0,0 +1,172 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Set up a new machine from a stall.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::cancel;
+use crate::confirm::Confirm;
+use crate::entry::Action;
+use crate::entry::CopyOptions;
+use crate::entry::Entry;
+use crate::entry::Overwrite;
+use crate::entry::Status;
+use crate::error::StallError;
+use crate::remote;
+use crate::report::CopyReport;
+use crate::state::StateFile;
+use crate::trash::Trash;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// bootstrap
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-bootstrap' command.
+///
+/// This will verify that each entry in the [`Stall`] can be distributed, and
+/// then distribute every file, creating any missing remote directories.
+/// Archived entries are skipped unless the `options` include them. Before
+/// any files are copied, the user is asked to confirm overwriting each remote
+/// file which exists and differs from the stalled file. Declined files are
+/// skipped.
+///
+/// ### Parameters
+///
+/// + `out`: The writer to write the status of each entry and the
+///   confirmation prompts into.
+/// + `stall_dir`: The stall directory to distribute from.
+/// + `stall`: The loaded `Stall` data.
+/// + `input`: The [`BufRead`] to read confirmations from.
+/// + `yes`: Overwrite conflicting files without asking for confirmation.
+/// + `use_trash`: Copy overwritten remote files into the stall's [`Trash`].
+/// + `options`: The [`CopyOptions`] to use for copying files.
+/// + `dry_run`: Do not copy any files.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// Processing continues after an entry fails, and the outcome of each entry is
+/// returned in a [`CopyReport`], whose totals and failures are not printed.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if a remote file's storage is not supported or
+/// accessible, if there is a cycle in the dependencies between entries, or if
+/// an IO error occurs during reading or writing.
+///
+/// [`Stall`]: ../struct.Stall.html
+/// [`BufRead`]: https://doc.rust-lang.org/stable/std/io/trait.BufRead.html
+/// [`Trash`]: ../trash/struct.Trash.html
+/// [`CopyOptions`]: ../entry/struct.CopyOptions.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`CopyReport`]: ../report/struct.CopyReport.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+#[allow(clippy::too_many_arguments)]
+pub fn bootstrap(
+	out: &mut dyn Write,
+	stall_dir: &Path,
+	stall: &Stall,
+	input: &mut dyn BufRead,
+	yes: bool,
+	use_trash: bool,
+	options: &CopyOptions,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<CopyReport, StallError>
+{
+	let _span = span!(Level::INFO, "bootstrap").entered();
+
+	// Nothing to do if there's no data.
+	if stall.is_empty() { return Ok(CopyReport::new()); }
+
+	// Verify that every entry can be distributed before copying anything.
+	let mut selected: Vec<_> = stall.entries()
+		.filter(|entry| options.include_archived || !entry.flags.archived)
+		.collect();
+	stall.sort_by_dependencies(&mut selected)?;
+	for entry in &selected {
+		remote::backend(entry.remote)?.check(entry.remote)?;
+	}
+
+	// Ask for confirmation of each overwrite.
+	let mut confirm = Confirm::new(input);
+	if yes { confirm.accept_all(); }
+	let mut state = StateFile::read_from_stall_dir(stall_dir);
+	let mut entries = Vec::new();
+	for entry in selected {
+		let (status_l, status_r) = entry.status_with_state(stall_dir, &mut state);
+		let conflict = matches!(status_r, Status::Newer | Status::Older)
+			&& status_l != Status::Error
+			&& !entry.flags.readonly;
+		let overwrite = conflict && confirm.overwrite(
+			out,
+			entry.remote,
+			&stall_dir.join(entry.local))?;
+		entries.push((entry, conflict, overwrite));
+	}
+
+	let mut trash = if use_trash && !dry_run {
+		Some(Trash::open(stall_dir, common.backup_dir.as_deref())?)
+	} else {
+		None
+	};
+
+	// Distribute the files.
+	Entry::write_status_action_header(out, common)?;
+	let mut report = CopyReport::new();
+	let total = entries.len();
+	for (index, (entry, conflict, overwrite))
+		in entries.into_iter().enumerate()
+	{
+		// Cancelled commands stop between entries.
+		if let Some(reason) = cancel::cancelled() {
+			report.set_cancelled(reason, total - index);
+			break;
+		}
+		if conflict && !overwrite {
+			let (status_l, status_r) = entry
+				.status_with_state(stall_dir, &mut state);
+			entry.write_status_action(
+				out,
+				status_l,
+				status_r,
+				Action::Skip,
+				common)?;
+			continue;
+		}
+		let res = entry.distribute(
+			out,
+			stall_dir,
+			if overwrite { Overwrite::FORCE } else { Overwrite::default() },
+			false,
+			true,
+			options,
+			&mut state,
+			None,
+			trash.as_mut(),
+			dry_run,
+			common);
+		report.record(entry.local, res);
+	}
+	if let Err(e) = state.write_to_stall_dir(stall_dir) {
+		event!(Level::WARN, "{e}");
+	}
+
+	Ok(report)
+}
+
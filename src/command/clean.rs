@@ -0,0 +1,263 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Remove orphaned files from the stall directory.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::archive::ArchiveFormat;
+use crate::confirm::Confirm;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::path;
+use crate::pick::pick_paths;
+use crate::report::warn;
+use crate::trash::remove_path;
+use crate::trash::Trash;
+use crate::warning::Warning;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::collections::BTreeSet;
+use std::io::BufRead;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// clean
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-clean' command.
+///
+/// This will list the orphaned files in the stall directory, as found by
+/// [`orphaned_files`], and remove them after asking for confirmation. Removed
+/// files are moved into the stall's [`Trash`], packed into an archive file, or
+/// permanently deleted.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory to clean.
+/// + `stall`: The loaded `Stall` data.
+/// + `input`: The input to read the confirmation from.
+/// + `yes`: Remove the files without asking for confirmation.
+/// + `pick`: Choose the files to remove using an interactive fuzzy selector,
+///   instead of asking for confirmation.
+/// + `archive`: The path of an archive file to pack the files into before
+///   they are deleted, if any.
+/// + `use_trash`: Move the files into the stall's [`Trash`] instead of
+///   deleting them. Ignored if `archive` is given.
+/// + `dry_run`: List the files instead of removing them.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// Files which can't be removed produce a warning.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the stall directory can't be read, if the
+/// archive file exists or can't be written, or if an IO error occurs during
+/// writing.
+///
+/// [`orphaned_files`]: fn.orphaned_files.html
+/// [`Trash`]: ../trash/struct.Trash.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::fn_params_excessive_bools)]
+pub fn clean(
+	stall_dir: &Path,
+	stall: &Stall,
+	input: &mut dyn BufRead,
+	yes: bool,
+	pick: bool,
+	archive: Option<&Path>,
+	use_trash: bool,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "clean").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let mut out = std::io::stdout();
+	let mut files = orphaned_files(stall_dir, stall, common)?;
+	if files.is_empty() {
+		if !common.quiet {
+			writeln!(&mut out, "No orphaned files in stall directory.")?;
+		}
+		return Ok(());
+	}
+
+	let action = match (archive, use_trash) {
+		(Some(_), _) => "Archive",
+		(None, true) => "Trash",
+		(None, false) => "Delete",
+	};
+	if dry_run {
+		for file in &files {
+			writeln!(&mut out, "{action} {}", file.display())?;
+		}
+		return Ok(());
+	}
+
+	if pick {
+		files = pick_paths(files, "clean", common)?;
+		if files.is_empty() { return Ok(()); }
+	} else if !yes {
+		writeln!(&mut out, "Orphaned files in {}:", stall_dir.display())?;
+		for file in &files {
+			writeln!(&mut out, "    {}", file.display())?;
+		}
+		let question = match (archive, use_trash) {
+			(Some(archive), _) => format!("Archive {} files into {} and \
+				delete them?", files.len(), archive.display()),
+			(None, true) => format!("Move {} files into the trash?",
+				files.len()),
+			(None, false) => format!("Permanently delete {} files?",
+				files.len()),
+		};
+		if !Confirm::new(input).proceed(&mut out, &question)? {
+			return Ok(());
+		}
+	}
+
+	if let Some(archive) = archive {
+		if archive.exists() {
+			return Err(StallError::Conflict {
+				reason: "archive file already exists".to_owned(),
+				subject: archive.display().to_string(),
+				hint: None,
+			});
+		}
+		let format = ArchiveFormat::from_path(archive).unwrap_or_default();
+		event!(Level::INFO, "Writing {} archive {:?}", format, archive);
+		crate::archive::create(archive, stall_dir, &files, format, false)?;
+	}
+
+	let mut trash = (use_trash && archive.is_none())
+		.then(|| Trash::open(stall_dir, common.backup_dir.as_deref()))
+		.transpose()?;
+	let mut removed = 0;
+	for file in &files {
+		let path = stall_dir.join(file);
+		let res = trash.as_mut().map_or_else(
+			|| remove_path(&path),
+			|trash| trash.move_in(&path).map(|_| ()));
+		match res {
+			Ok(()) => removed += 1,
+			Err(e) => warn(Warning::RemoveFailed {
+				path,
+				reason: e.to_string(),
+			}, common)?,
+		}
+	}
+
+	if !common.quiet {
+		match archive {
+			Some(archive) => writeln!(&mut out, "Archived {removed} \
+				orphaned files into {}", archive.display())?,
+			None => writeln!(&mut out, "Removed {removed} orphaned files")?,
+		}
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// orphaned_files
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the paths of the orphaned files in the stall directory, relative to
+/// the stall directory.
+///
+/// A file is orphaned if it is not the stalled file of any entry and doesn't
+/// lie inside an entry's stalled directory. Directories containing no stalled
+/// files are returned as a whole. The stall file, stall's own files and
+/// directories such as `.stall-trash` and `.stallignore`, and git files such
+/// as `.git` and `.gitignore` at the top of the stall directory are never
+/// orphaned.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the stall directory can't be read.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn orphaned_files(
+	stall_dir: &Path,
+	stall: &Stall,
+	common: &CommonOptions)
+	-> Result<Vec<PathBuf>, StallError>
+{
+	let locals: BTreeSet<&Path> = stall.entries()
+		.map(|entry| entry.local)
+		.collect();
+	let mut kept = vec![
+		Trash::open(stall_dir, common.backup_dir.as_deref())?
+			.dir()
+			.to_path_buf(),
+	];
+	kept.extend(stall.load_path().map(Path::to_path_buf));
+
+	let mut orphans = Vec::new();
+	find_orphans(stall_dir, Path::new(""), &locals, &kept, &mut orphans)?;
+	orphans.sort();
+	Ok(orphans)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helper functions
+////////////////////////////////////////////////////////////////////////////////
+
+/// Pushes the orphaned files within the directory `dir`, relative to the
+/// stall directory, into `orphans`. Directories containing stalled files or
+/// paths in `kept` are searched recursively, and paths in `kept` are never
+/// orphaned.
+fn find_orphans(
+	stall_dir: &Path,
+	dir: &Path,
+	locals: &BTreeSet<&Path>,
+	kept: &[PathBuf],
+	orphans: &mut Vec<PathBuf>)
+	-> Result<(), StallError>
+{
+	let full_dir = stall_dir.join(dir);
+	for entry in std::fs::read_dir(&full_dir)
+		.with_path("failed to read directory", &full_dir)?
+	{
+		let entry = entry.with_path("failed to read directory", &full_dir)?;
+		let name = entry.file_name();
+		let file = dir.join(&name);
+		let full_file = entry.path();
+		let internal = name.to_str().is_some_and(|name| name
+			.starts_with(".stall") || name.starts_with(".git"));
+		if (dir.as_os_str().is_empty() && internal)
+			|| locals.iter().any(|local| path::same(local, &file))
+			|| kept.iter().any(|path| path::same(path, &full_file))
+		{
+			continue;
+		}
+
+		let is_dir = entry.file_type()
+			.with_path("failed to read metadata for", &full_file)?
+			.is_dir();
+		let contains_kept = locals.iter().any(|local| local.starts_with(&file))
+			|| kept.iter().any(|path| path.starts_with(&full_file));
+		if is_dir && contains_kept {
+			find_orphans(stall_dir, &file, locals, kept, orphans)?;
+		} else {
+			event!(Level::DEBUG, "Orphaned file {:?}", file);
+			orphans.push(file);
+		}
+	}
+	Ok(())
+}
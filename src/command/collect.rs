@@ -8,21 +8,30 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Internal library imports.
+use crate::error::StallError;
 use crate::CommonOptions;
 use crate::Stall;
+use crate::cancel;
+use crate::confirm::Confirm;
+use crate::plan::Plan;
+use crate::entry::CopyOptions;
 use crate::entry::Entry;
+use crate::entry::Outcome;
+use crate::entry::Overwrite;
+use crate::report::CopyReport;
+use crate::report::warn;
+use crate::state::StateFile;
 
 // External library imports.
-use anyhow::anyhow;
-use anyhow::Error;
 use colored::Colorize as _;
-use either::Either;
+use tracing::event;
 use tracing::Level;
 use tracing::span;
 
 // Standard library imports.
 use std::path::Path;
-use std::io::Write as _;
+use std::path::PathBuf;
+use std::io::Write;
 
 ////////////////////////////////////////////////////////////////////////////////
 // collect
@@ -36,82 +45,167 @@ use std::io::Write as _;
 ///
 /// ### Parameters
 ///
+/// + `out`: The writer to write the status of each entry and any
+///   confirmation prompts into.
 /// + `stall_dir`: The stall directory to collect into.
 /// + `stall`: The loaded `Stall` data.
-/// + `files`: An iterator over the [`Path`]s of the files to collect.
-/// + `force`: Force overwrites even if the files are current.
+/// + `files`: An iterator over the [`Path`]s of the files to collect. Entry
+///   indices and ranges as printed by [`status`] and partial names are
+///   also accepted.
+/// + `overwrite`: The [`Overwrite`] safeguards to bypass when copying files.
+/// + `unpin`: Allow `overwrite` to overwrite the files of pinned entries.
+/// + `options`: The [`CopyOptions`] to use for copying files.
+/// + `confirm`: The [`Confirm`] to ask before overwriting existing files.
+/// + `plan`: The [`Plan`] to record the copies of a dry run in.
+/// + `summary_only`: Write only the summary line instead of the status of
+///   each entry.
 /// + `dry_run`: Do not copy any files.
 /// + `common`: The [`CommonOptions`] to use for the command.
 ///
+/// Entries are processed in the [`EntryOrder`] of the `options`, which orders
+/// them by their local paths by default, and archived entries are skipped
+/// unless the `options` include them. Processing continues after an entry
+/// fails, and the outcome of each entry is returned in a [`CopyReport`] in the
+/// same order, whose totals and failures are not printed.
+///
+/// A warning is emitted before any files are copied for each processed entry
+/// whose files overlap those of another entry.
+///
 /// ### Errors
 /// 
-/// Returns an [`Error`] if both files exist but their metadata can't be read,
-/// if the copy operation fails, or if any IO errors occur.
+/// Returns a [`StallError`] if any of the given files are not in the stall or
+/// match more than one entry, or if an IO error occurs during writing.
 /// 
 /// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
 /// [`Stall`]: ../struct.Stall.html
+/// [`status`]: fn.status.html
+/// [`Overwrite`]: ../entry/struct.Overwrite.html
+/// [`CopyOptions`]: ../entry/struct.CopyOptions.html
+/// [`EntryOrder`]: ../entry/enum.EntryOrder.html
+/// [`Confirm`]: ../confirm/struct.Confirm.html
+/// [`Plan`]: ../plan/struct.Plan.html
 /// [`CommonOptions`]: ../command/struct.CommonOptions.html
-/// [`Error`]: ../error/struct.Error.html
+/// [`CopyReport`]: ../report/struct.CopyReport.html
+/// [`StallError`]: ../error/enum.StallError.html
 /// 
+#[allow(clippy::too_many_arguments)]
 pub fn collect<'i, I>(
+	out: &mut dyn Write,
 	stall_dir: &Path,
 	stall: &Stall,
 	files: I,
-	force: bool,
+	overwrite: Overwrite,
+	unpin: bool,
+	options: &CopyOptions,
+	mut confirm: Option<&mut Confirm<'_>>,
+	mut plan: Option<&mut Plan>,
+	summary_only: bool,
 	dry_run: bool,
 	common: &CommonOptions) 
-	-> Result<(), Error>
+	-> Result<CopyReport, StallError>
 	where I: IntoIterator<Item=&'i Path>
 {
 	let _span = span!(Level::INFO, "collect").entered();
-	if dry_run && common.quiet { return Ok(()); }
+	if dry_run && common.quiet && plan.is_none() {
+		return Ok(CopyReport::new());
+	}
 
-	if stall.is_empty() {
-		if !common.quiet {
-			println!("No files in stall. Use `add` command to place files \
-			in the stall.");
-		}
-		// Nothing to do if there's no data.
-		return Ok(());
-	} 
+	// Nothing to do if there's no data.
+	if stall.is_empty() { return Ok(CopyReport::new()); }
 
-	// Identify stall files to process.
-	let selected = files
-		.into_iter()
-		.map(|f| stall
-			.entry_local(f)
-			.ok_or_else(|| anyhow!("unrecognized stall entry: {}",
-				f.display())))
-		.collect::<Result<Vec<_>, _>>()?;
+	// Identify stall files to process, in the order given by the options.
+	let mut entries = stall.select(files)?;
+	entries.retain(|entry| options.include_archived || !entry.flags.archived);
+	options.order.sort(&mut entries, stall_dir, |entry| entry);
 
-	let entries = if selected.is_empty() {
-		Either::Left(stall.entries())
-	} else {
-		Either::Right(selected.into_iter())
+	// Overlapping entries may copy the same files more than once.
+	let selected = |local: &Path| entries
+		.iter()
+		.any(|entry| entry.local == local);
+	for warning in stall.overlaps(selected) {
+		warn(warning, common)?;
+	}
+
+	// The status of each entry is not printed in summary only mode.
+	let entry_common = CommonOptions {
+		quiet: common.quiet || summary_only,
+		..common.clone()
 	};
 
-	let mut out = std::io::stdout();
+	// Process each entry table. Unchanged files are skipped using the
+	// hashes cached in the stall's state file.
+	Entry::write_status_action_header(out, &entry_common)?;
+	let mut state = StateFile::read_from_stall_dir(stall_dir);
+	let mut report = CopyReport::new();
+	let total = entries.len();
+	for (index, entry) in entries.into_iter().enumerate() {
+		// Cancelled commands stop between entries.
+		if let Some(reason) = cancel::cancelled() {
+			report.set_cancelled(reason, total - index);
+			break;
+		}
+		let res = entry.collect(
+			out,
+			stall_dir,
+			overwrite,
+			unpin,
+			options,
+			&mut state,
+			confirm.as_deref_mut(),
+			dry_run,
+			&entry_common);
+		// The copies of a dry run are recorded in the plan.
+		let res = match (plan.as_deref_mut(), res) {
+			(Some(plan), Ok(Outcome::Copied)) if dry_run => plan
+				.push(&entry)
+				.map(|()| Outcome::Copied),
+			(_, res) => res,
+		};
+		report.record(entry.local, res);
+	}
+	if let Err(e) = state.write_to_stall_dir(stall_dir) {
+		event!(Level::WARN, "{e}");
+	}
+
+	Ok(report)
+}
 
-	// Setup and print stall directory.
+/// Writes a summary of the results of collecting into several stalls.
+///
+/// Each stall directory is written on a line with the number of entries
+/// processed, copied, and failed. Nothing is written if `common.quiet` is
+/// set.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if an IO error occurs during writing.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn write_stall_summaries(
+	out: &mut dyn Write,
+	reports: &[(PathBuf, CopyReport)],
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	if common.quiet { return Ok(()); }
+
+	let header = format!("Collected {} stalls:", reports.len());
 	if common.color.enabled() {
-		writeln!(&mut out, "{} {}",
-			"Stall directory:".bright_white(),
-			stall_dir.display())?;
+		writeln!(out, "{}", header.bright_white())?;
 	} else {
-		writeln!(&mut out, "Stall directory: {}",
-			stall_dir.display())?;
+		writeln!(out, "{header}")?;
 	}
-
-	// Process each entry table.
-	Entry::write_status_action_header(&mut out, common)?;
-	for entry in entries {
-		entry.collect(
-			&mut out,
-			stall_dir,
-			force,
-			dry_run,
-			common)?;
+	for (stall_dir, report) in reports {
+		let line = format!("    {}: {} entries, {} copied, {} failed",
+			stall_dir.display(),
+			report.total(),
+			report.copied().count(),
+			report.failures().count());
+		if common.color.enabled() && !report.is_empty() {
+			writeln!(out, "{}", line.bright_red())?;
+		} else {
+			writeln!(out, "{line}")?;
+		}
 	}
-
 	Ok(())
 }
@@ -0,0 +1,149 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Shell completion script generation.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::CommandOptions;
+use crate::Stall;
+
+// External library imports.
+use clap::CommandFactory as _;
+use clap_complete::Shell;
+
+// Standard library imports.
+use std::io::Write;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The name of the hidden subcommand used to list stall entries during
+/// completion.
+pub const COMPLETE_ENTRIES_COMMAND: &str = "complete-entries";
+
+/// Bash completion wrapper providing dynamic stall entry completion.
+const BASH_ENTRY_COMPLETION: &str = r#"
+_stall_entries() {
+    local i stall_opt=()
+    for (( i=1; i < COMP_CWORD; i++ )); do
+        case "${COMP_WORDS[i]}" in
+            -s|--stall) stall_opt=(--stall "${COMP_WORDS[i+1]}") ;;
+        esac
+    done
+    case "${COMP_WORDS[1]}" in
+        collect|distribute|rm|mv|show)
+            if [[ "${COMP_WORDS[COMP_CWORD]}" != -* ]]; then
+                local IFS=$'\n'
+                COMPREPLY=( $(compgen -W \
+                    "$(stall complete-entries "${stall_opt[@]}" 2>/dev/null)" \
+                    -- "${COMP_WORDS[COMP_CWORD]}") )
+                return 0
+            fi
+            ;;
+    esac
+    _stall "$@"
+}
+
+complete -F _stall_entries -o bashdefault -o default stall
+"#;
+
+/// Zsh completion wrapper providing dynamic stall entry completion.
+const ZSH_ENTRY_COMPLETION: &str = r#"
+_stall_entries() {
+    if (( CURRENT > 2 )) \
+        && [[ ${words[2]} == (collect|distribute|rm|mv|show) ]] \
+        && [[ ${words[CURRENT]} != -* ]]
+    then
+        local -a entries stall_opt
+        local i=${words[(I)-s|--stall]}
+        (( i > 0 )) && stall_opt=(--stall ${words[i+1]})
+        entries=(${(f)"$(stall complete-entries $stall_opt 2>/dev/null)"})
+        _describe 'stall entry' entries
+    else
+        _stall "$@"
+    fi
+}
+
+compdef _stall_entries stall
+"#;
+
+/// Fish completions providing dynamic stall entry completion.
+const FISH_ENTRY_COMPLETION: &str = r#"
+complete -c stall \
+    -n "__fish_seen_subcommand_from collect distribute rm mv show" \
+    -f -a "(stall complete-entries 2>/dev/null)"
+"#;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// completions
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-completions' command.
+///
+/// This will write a completion script for the given shell into `out`. For
+/// bash, zsh, and fish, the script will also complete stall entry names for
+/// the `collect`, `distribute`, `rm`, `mv`, and `show` commands.
+///
+/// ### Parameters
+///
+/// + `shell`: The [`Shell`] to generate completions for.
+/// + `out`: The writer to write the completion script into.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if an IO error occurs during writing.
+/// 
+/// [`Shell`]: https://docs.rs/clap_complete/latest/clap_complete/shells/enum.Shell.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn completions(shell: Shell, out: &mut dyn Write) -> Result<(), StallError> {
+	let mut command = CommandOptions::command();
+	let name = command.get_name().to_owned();
+	clap_complete::generate(shell, &mut command, name, out);
+
+	match shell {
+		Shell::Bash => out.write_all(BASH_ENTRY_COMPLETION.as_bytes())?,
+		Shell::Zsh  => out.write_all(ZSH_ENTRY_COMPLETION.as_bytes())?,
+		Shell::Fish => out.write_all(FISH_ENTRY_COMPLETION.as_bytes())?,
+		_           => (),
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// complete_entries
+////////////////////////////////////////////////////////////////////////////////
+/// Writes the local name of each entry in the [`Stall`] into `out`, one per
+/// line. This is used by the completion scripts to complete entry names.
+///
+/// ### Parameters
+///
+/// + `stall`: The loaded `Stall` data.
+/// + `out`: The writer to write the entry names into.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if an IO error occurs during writing.
+/// 
+/// [`Stall`]: ../struct.Stall.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn complete_entries(stall: &Stall, out: &mut dyn Write)
+	-> Result<(), StallError>
+{
+	let mut last = None;
+	for entry in stall.entries() {
+		// Entries with multiple remotes are listed once.
+		if last == Some(entry.local) { continue; }
+		writeln!(out, "{}", entry.local.display())?;
+		last = Some(entry.local);
+	}
+	Ok(())
+}
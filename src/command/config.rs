@@ -0,0 +1,183 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Config management commands.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::application::Config;
+use crate::application::ConfigLevel;
+use crate::application::Defaults;
+use crate::CommonOptions;
+use crate::report::Cell;
+use crate::report::Table;
+
+// External library imports.
+use colored::Color;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// config_list
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-config-list' command.
+///
+/// This will print the value of each configurable option and the
+/// [`ConfigLevel`] it was set at. Options which are not set at any level are
+/// shown with their built-in default.
+///
+/// ### Parameters
+///
+/// + `layers`: The [`Defaults`] of each loaded config file, from lowest to
+///   highest precedence.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if an IO error occurs during writing.
+///
+/// [`ConfigLevel`]: ../application/enum.ConfigLevel.html
+/// [`Defaults`]: ../application/struct.Defaults.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn config_list(
+	layers: &[(ConfigLevel, &Defaults)],
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "config_list").entered();
+
+	let mut table = Table::new(["KEY", "VALUE", "LEVEL"]);
+	for key in Defaults::KEYS {
+		let (value, level) = resolve(layers, key)?;
+		table.push_row(vec![
+			Cell::new(key).with_color(Color::BrightWhite),
+			Cell::new(value),
+			level.map_or_else(
+				|| Cell::new("default").with_color(Color::BrightBlack),
+				|level| Cell::new(level.to_string())),
+		]);
+	}
+	table.write(&mut std::io::stdout(), common)?;
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// config_get
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-config-get' command.
+///
+/// This will print the value of the given option from the highest
+/// [`ConfigLevel`] it is set at, or its built-in default.
+///
+/// ### Parameters
+///
+/// + `layers`: The [`Defaults`] of each loaded config file, from lowest to
+///   highest precedence.
+/// + `key`: The option to print.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the key is not recognized or an IO error occurs
+/// during writing.
+///
+/// [`ConfigLevel`]: ../application/enum.ConfigLevel.html
+/// [`Defaults`]: ../application/struct.Defaults.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn config_get(
+	layers: &[(ConfigLevel, &Defaults)],
+	key: &str,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "config_get").entered();
+
+	let (value, _) = resolve(layers, key)?;
+	if !common.quiet {
+		writeln!(&mut std::io::stdout(), "{value}")?;
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// config_set
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-config-set' and 'stall-config-unset' commands.
+///
+/// This will set the given option in the [`Config`], or unset it if `value` is
+/// `None`. The caller is responsible for writing the modified `Config`.
+///
+/// ### Parameters
+///
+/// + `config`: The `Config` to modify.
+/// + `level`: The [`ConfigLevel`] of the `Config`.
+/// + `key`: The option to set.
+/// + `value`: The value to set, or `None` to unset the option.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the key is not recognized, the value is invalid
+/// for the option, or an IO error occurs during writing.
+///
+/// [`Config`]: ../application/struct.Config.html
+/// [`ConfigLevel`]: ../application/enum.ConfigLevel.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn config_set(
+	config: &mut Config,
+	level: ConfigLevel,
+	key: &str,
+	value: Option<&str>,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "config_set").entered();
+
+	config.defaults.set(key, value)?;
+	config.set_modified(true);
+
+	if !common.quiet {
+		let mut out = std::io::stdout();
+		match value {
+			Some(value) => writeln!(&mut out,
+				"Set {key} = {value} in {level} config")?,
+			None => writeln!(&mut out,
+				"Unset {key} in {level} config")?,
+		}
+	}
+	Ok(())
+}
+
+
+/// Returns the value of the option with the given key from the highest layer
+/// it is set in, along with the level of that layer. The built-in default is
+/// returned with no level if the option is not set in any layer.
+fn resolve(layers: &[(ConfigLevel, &Defaults)], key: &str)
+	-> Result<(String, Option<ConfigLevel>), StallError>
+{
+	for (level, defaults) in layers.iter().rev() {
+		if let Some(value) = defaults.get(key)? {
+			return Ok((value, Some(*level)));
+		}
+	}
+	let value = Defaults::builtin()
+		.get(key)?
+		.unwrap_or_default();
+	Ok((value, None))
+}
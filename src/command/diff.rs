@@ -0,0 +1,668 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Print the differences between stalled and remote files.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::entry::Entry;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::remote;
+use crate::state::StateFile;
+use crate::transform::is_binary;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use colored::Colorize as _;
+use dialoguer::console::Term;
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The number of unchanged lines to print around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// The largest product of the line counts of two files which are compared
+/// line by line. Larger files are printed as entirely replaced.
+const MAX_DIFF_CELLS: usize = 16 * 1024 * 1024;
+
+/// The number of hex digits of file hashes to print for binary files.
+const HASH_DISPLAY_LEN: usize = 16;
+
+/// The width of side-by-side diffs when the terminal width is unknown.
+const DEFAULT_WIDTH: usize = 80;
+
+/// The number of spaces printed for each tab in side-by-side diffs.
+const TAB_WIDTH: usize = 4;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// DiffStyle
+////////////////////////////////////////////////////////////////////////////////
+/// The format in which the differences between text files are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffStyle {
+	/// A unified diff of the changed lines.
+	#[default]
+	Unified,
+	/// The lines of each hunk with their changed words marked inline, as by
+	/// `git diff --word-diff`. The words are colored instead of marked if
+	/// color is enabled.
+	Words,
+	/// The stalled and remote files in two columns, fitting the given width,
+	/// or the terminal width if `None`. Changed words are highlighted if
+	/// color is enabled.
+	SideBySide(Option<usize>),
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// diff
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-diff' command.
+///
+/// This will print the differences between the stalled and remote files of
+/// each entry as a unified diff, with the stalled file as the original. The
+/// files of directories are compared individually. Binary files, and the
+/// files of entries flagged as binary, are reported with their sizes and
+/// hashes instead of their contents unless `text` is set. Returns `true` if
+/// any differences were found.
+///
+/// Config changes are often a single changed value, so the [`DiffStyle`] can
+/// show the changed words within each line instead of whole lines.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the stalled files.
+/// + `stall`: The loaded [`Stall`] data.
+/// + `files`: An iterator over the [`Path`]s of the entries to compare. Entry
+///   indices and ranges as printed by [`status`] and partial names are also
+///   accepted. Defaults to all entries.
+/// + `text`: Compare all files as text.
+/// + `style`: The [`DiffStyle`] to print text differences in.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if any of the given files are not in the stall,
+/// if a file can't be read, or if an IO error occurs during writing.
+///
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`status`]: fn.status.html
+/// [`DiffStyle`]: enum.DiffStyle.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn diff<'i, I>(
+	stall_dir: &Path,
+	stall: &Stall,
+	files: I,
+	text: bool,
+	style: DiffStyle,
+	common: &CommonOptions)
+	-> Result<bool, StallError>
+	where I: IntoIterator<Item=&'i Path>
+{
+	let _span = span!(Level::INFO, "diff").entered();
+
+	let mut selected = Vec::new();
+	for f in files {
+		selected.extend(stall.entries_selected(f)?);
+	}
+	if selected.is_empty() {
+		selected.extend(stall.entries());
+	}
+
+	let mut state = StateFile::read_from_stall_dir(stall_dir);
+	let mut out = std::io::stdout();
+	let mut differs = false;
+	for entry in &selected {
+		if remote::scheme(entry.remote).is_some() {
+			event!(Level::DEBUG, "Not comparing remote file {:?}",
+				entry.remote);
+			continue;
+		}
+		for (local, remote) in file_pairs(&stall_dir.join(entry.local), entry)? {
+			differs |= diff_files(
+				&mut out,
+				&local,
+				&remote,
+				entry.flags.binary && !text,
+				text,
+				style,
+				&mut state,
+				common)?;
+		}
+	}
+	if !common.readonly {
+		if let Err(e) = state.write_to_stall_dir(stall_dir) {
+			event!(Level::WARN, "{e}");
+		}
+	}
+	Ok(differs)
+}
+
+
+/// Returns the pairs of stalled and remote files to compare for the given
+/// entry. The files within directories are paired by their relative paths.
+fn file_pairs(local: &Path, entry: &Entry<'_>)
+	-> Result<Vec<(PathBuf, PathBuf)>, StallError>
+{
+	if !local.is_dir() && !entry.remote.is_dir() {
+		return Ok(vec![(local.to_path_buf(), entry.remote.to_path_buf())]);
+	}
+
+	let mut relative = BTreeSet::new();
+	for dir in [local, entry.remote] {
+		if dir.is_dir() { list_files(dir, Path::new(""), &mut relative)?; }
+	}
+	Ok(relative
+		.into_iter()
+		.map(|rel| (local.join(&rel), entry.remote.join(rel)))
+		.collect())
+}
+
+/// Adds the paths of the files within the given directory, relative to the
+/// directory, to `files`.
+fn list_files(dir: &Path, prefix: &Path, files: &mut BTreeSet<PathBuf>)
+	-> Result<(), StallError>
+{
+	let path = dir.join(prefix);
+	for entry in std::fs::read_dir(&path)
+		.with_path("failed to read directory", &path)?
+	{
+		let entry = entry.with_path("failed to read directory", &path)?;
+		let rel = prefix.join(entry.file_name());
+		if entry.path().is_dir() {
+			list_files(dir, &rel, files)?;
+		} else {
+			let _ = files.insert(rel);
+		}
+	}
+	Ok(())
+}
+
+/// Writes the differences between the given stalled and remote files into
+/// `out`. Returns `true` if the files differ.
+#[allow(clippy::too_many_arguments)]
+fn diff_files(
+	out: &mut dyn Write,
+	local: &Path,
+	remote: &Path,
+	binary: bool,
+	text: bool,
+	style: DiffStyle,
+	state: &mut StateFile,
+	common: &CommonOptions)
+	-> Result<bool, StallError>
+{
+	match (local.exists(), remote.exists()) {
+		(false, false) => return Ok(false),
+		(true, false)  => {
+			writeln!(out, "Only in stall: {}", local.display())?;
+			return Ok(true);
+		},
+		(false, true)  => {
+			writeln!(out, "Only in remote: {}", remote.display())?;
+			return Ok(true);
+		},
+		(true, true)   => (),
+	}
+
+	if binary {
+		if state.same_contents(local, remote)? { return Ok(false); }
+		write_binary_diff(out, local, remote, state)?;
+		return Ok(true);
+	}
+
+	let bytes_l = std::fs::read(local)
+		.with_path("failed to read stalled file", local)?;
+	let bytes_r = std::fs::read(remote)
+		.with_path("failed to read remote file", remote)?;
+	if bytes_l == bytes_r { return Ok(false); }
+
+	if !text && (is_binary(&bytes_l) || is_binary(&bytes_r)) {
+		write_binary_diff(out, local, remote, state)?;
+		return Ok(true);
+	}
+
+	let text_l = String::from_utf8_lossy(&bytes_l);
+	let text_r = String::from_utf8_lossy(&bytes_r);
+	let lines_l: Vec<&str> = text_l.lines().collect();
+	let lines_r: Vec<&str> = text_r.lines().collect();
+
+	let header = format!("--- {}\n+++ {}", local.display(), remote.display());
+	if common.color.enabled() {
+		writeln!(out, "{}", header.bold())?;
+	} else {
+		writeln!(out, "{header}")?;
+	}
+	let edits = diff_lines(&lines_l, &lines_r);
+	let hunks = hunks(&edits);
+	if hunks.is_empty() {
+		writeln!(out, "Files differ only in line endings")?;
+	}
+	let color = common.color.enabled();
+	for hunk in hunks {
+		let edits = &edits[hunk.0..hunk.1];
+		write_hunk_header(out, edits, color)?;
+		match style {
+			DiffStyle::Unified => write_hunk(
+				out, edits, &lines_l, &lines_r, color)?,
+			DiffStyle::Words => write_word_hunk(
+				out, edits, &lines_l, &lines_r, color)?,
+			DiffStyle::SideBySide(width) => write_side_by_side_hunk(
+				out, edits, &lines_l, &lines_r,
+				width.unwrap_or_else(terminal_width),
+				color)?,
+		}
+	}
+	Ok(true)
+}
+
+/// Writes a summary of the differences between two binary files into `out`.
+fn write_binary_diff(
+	out: &mut dyn Write,
+	local: &Path,
+	remote: &Path,
+	state: &mut StateFile)
+	-> Result<(), StallError>
+{
+	let len = |path: &Path| std::fs::metadata(path)
+		.map(|m| m.len())
+		.with_path("failed to read file metadata", path);
+	let (len_l, len_r) = (len(local)?, len(remote)?);
+	let (hash_l, hash_r) = (state.hash(local)?, state.hash(remote)?);
+	let short = |hash: &str| hash.chars().take(HASH_DISPLAY_LEN).collect::<String>();
+
+	writeln!(out, "Binary files {} and {} differ",
+		local.display(),
+		remote.display())?;
+	let delta = i128::from(len_r) - i128::from(len_l);
+	writeln!(out, "    size: {len_l} -> {len_r} bytes ({delta:+})")?;
+	writeln!(out, "    hash: {} -> {}", short(&hash_l), short(&hash_r))?;
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Line differences
+////////////////////////////////////////////////////////////////////////////////
+/// A single step in transforming one list of lines into another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+	/// The line at the given indices is in both lists.
+	Keep(usize, usize),
+	/// The line at the given index of the first list is removed.
+	Remove(usize),
+	/// The line at the given index of the second list is inserted.
+	Insert(usize),
+}
+
+/// Returns the edits which transform `old` into `new`, found using the longest
+/// common subsequence of their lines. Lists which are too large to compare
+/// are entirely replaced.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Edit> {
+	let (old_len, new_len) = (old.len(), new.len());
+	if old_len.saturating_mul(new_len) > MAX_DIFF_CELLS {
+		return (0..old_len).map(Edit::Remove)
+			.chain((0..new_len).map(Edit::Insert))
+			.collect();
+	}
+
+	// lcs[i][j] is the length of the longest common subsequence of old[i..]
+	// and new[j..].
+	let mut lcs = vec![vec![0_u32; new_len + 1]; old_len + 1];
+	for i in (0..old_len).rev() {
+		for j in (0..new_len).rev() {
+			lcs[i][j] = if old[i] == new[j] {
+				lcs[i + 1][j + 1] + 1
+			} else {
+				lcs[i + 1][j].max(lcs[i][j + 1])
+			};
+		}
+	}
+
+	let mut edits = Vec::with_capacity(old_len.max(new_len));
+	let (mut i, mut j) = (0, 0);
+	while i < old_len || j < new_len {
+		if old.get(i).is_some_and(|line| Some(line) == new.get(j)) {
+			edits.push(Edit::Keep(i, j));
+			i += 1;
+			j += 1;
+		} else if i < old_len
+			&& (j == new_len || lcs[i + 1][j] >= lcs[i][j + 1])
+		{
+			edits.push(Edit::Remove(i));
+			i += 1;
+		} else {
+			edits.push(Edit::Insert(j));
+			j += 1;
+		}
+	}
+	edits
+}
+
+/// Returns the ranges of the edits to print as hunks, each including up to
+/// `CONTEXT_LINES` unchanged lines around its changes.
+fn hunks(edits: &[Edit]) -> Vec<(usize, usize)> {
+	let mut hunks: Vec<(usize, usize)> = Vec::new();
+	for (idx, edit) in edits.iter().enumerate() {
+		if matches!(edit, Edit::Keep(..)) { continue; }
+		let start = idx.saturating_sub(CONTEXT_LINES);
+		let end = (idx + 1 + CONTEXT_LINES).min(edits.len());
+		match hunks.last_mut() {
+			Some(last) if start <= last.1 => last.1 = end,
+			_ => hunks.push((start, end)),
+		}
+	}
+	hunks
+}
+
+/// Writes the header of a hunk of edits into `out`, giving the ranges of the
+/// lines in the hunk.
+fn write_hunk_header(out: &mut dyn Write, edits: &[Edit], color: bool)
+	-> Result<(), StallError>
+{
+	// Hunk line numbers start at 1, or are 0 for an empty range.
+	let start = |first: Option<usize>, len: usize| match first {
+		Some(first) if len > 0 => first + 1,
+		_ => 0,
+	};
+	let first_a = edits.iter().find_map(|e| match e {
+		Edit::Keep(i, _) | Edit::Remove(i) => Some(*i),
+		Edit::Insert(_) => None,
+	});
+	let first_b = edits.iter().find_map(|e| match e {
+		Edit::Keep(_, j) | Edit::Insert(j) => Some(*j),
+		Edit::Remove(_) => None,
+	});
+	let len_a = edits.iter().filter(|e| !matches!(e, Edit::Insert(_))).count();
+	let len_b = edits.iter().filter(|e| !matches!(e, Edit::Remove(_))).count();
+
+	let header = format!("@@ -{},{} +{},{} @@",
+		start(first_a, len_a), len_a,
+		start(first_b, len_b), len_b);
+	if color {
+		writeln!(out, "{}", header.cyan())?;
+	} else {
+		writeln!(out, "{header}")?;
+	}
+	Ok(())
+}
+
+/// Writes a hunk of edits into `out` in the unified diff format.
+fn write_hunk(
+	out: &mut dyn Write,
+	edits: &[Edit],
+	a: &[&str],
+	b: &[&str],
+	color: bool)
+	-> Result<(), StallError>
+{
+	for edit in edits {
+		let (line, prefix) = match edit {
+			Edit::Keep(i, _) => (a[*i], ' '),
+			Edit::Remove(i)  => (a[*i], '-'),
+			Edit::Insert(j)  => (b[*j], '+'),
+		};
+		let line = format!("{prefix}{line}");
+		match prefix {
+			'-' if color => writeln!(out, "{}", line.red())?,
+			'+' if color => writeln!(out, "{}", line.green())?,
+			_            => writeln!(out, "{line}")?,
+		}
+	}
+	Ok(())
+}
+
+/// Writes a hunk of edits into `out` with the changed words of each line
+/// marked inline, as `[-removed-]` and `{+inserted+}`, or colored if `color`
+/// is set.
+fn write_word_hunk(
+	out: &mut dyn Write,
+	edits: &[Edit],
+	a: &[&str],
+	b: &[&str],
+	color: bool)
+	-> Result<(), StallError>
+{
+	for row in rows(edits) {
+		let words = match row {
+			Row::Keep(i, _) => {
+				writeln!(out, "{}", a[i])?;
+				continue;
+			},
+			Row::Change(i, j) => diff_words(
+				i.map_or("", |i| a[i]),
+				j.map_or("", |j| b[j])),
+		};
+		for (change, word) in &words {
+			match change {
+				Change::Same              => write!(out, "{word}")?,
+				Change::Removed if color  => write!(out, "{}", word.red())?,
+				Change::Inserted if color => write!(out, "{}", word.green())?,
+				Change::Removed           => write!(out, "[-{word}-]")?,
+				Change::Inserted          => write!(out, "{{+{word}+}}")?,
+			}
+		}
+		writeln!(out)?;
+	}
+	Ok(())
+}
+
+/// Writes a hunk of edits into `out` with the lines of the stalled and remote
+/// files side by side, fitting the given width. The columns are separated by
+/// `|` for changed lines, `<` for removed lines, and `>` for inserted lines.
+fn write_side_by_side_hunk(
+	out: &mut dyn Write,
+	edits: &[Edit],
+	a: &[&str],
+	b: &[&str],
+	width: usize,
+	color: bool)
+	-> Result<(), StallError>
+{
+	let column = width.saturating_sub(3) / 2;
+	for row in rows(edits) {
+		let (left, right, separator) = match row {
+			Row::Keep(i, j) => (
+				vec![(Change::Same, a[i].to_owned())],
+				vec![(Change::Same, b[j].to_owned())],
+				' '),
+			Row::Change(i, j) => {
+				let words = diff_words(
+					i.map_or("", |i| a[i]),
+					j.map_or("", |j| b[j]));
+				let separator = match (i, j) {
+					(Some(_), Some(_)) => '|',
+					(Some(_), None)    => '<',
+					_                  => '>',
+				};
+				let left = words
+					.iter()
+					.filter(|(change, _)| *change != Change::Inserted)
+					.cloned()
+					.collect();
+				let right = words
+					.into_iter()
+					.filter(|(change, _)| *change != Change::Removed)
+					.collect();
+				(left, right, separator)
+			},
+		};
+		let (left, len) = fit_words(&left, column, color);
+		let (right, _) = fit_words(&right, column, color);
+		let line = format!("{left}{} {separator} {right}",
+			" ".repeat(column - len));
+		writeln!(out, "{}", line.trim_end())?;
+	}
+	Ok(())
+}
+
+/// Returns the width of the terminal, or `DEFAULT_WIDTH` if the output is not
+/// written to a terminal.
+fn terminal_width() -> usize {
+	Term::stdout()
+		.size_checked()
+		.map_or(DEFAULT_WIDTH, |(_, cols)| usize::from(cols))
+}
+
+/// Returns the words of one side of a side-by-side diff row, truncated to
+/// the given number of characters, along with the number of characters
+/// kept. Changed words are colored if `color` is set.
+fn fit_words(words: &[(Change, String)], width: usize, color: bool)
+	-> (String, usize)
+{
+	let mut line = String::new();
+	let mut len = 0;
+	for (change, word) in words {
+		let word: String = word
+			.replace('\t', &" ".repeat(TAB_WIDTH))
+			.chars()
+			.take(width - len)
+			.collect();
+		len += word.chars().count();
+		match change {
+			Change::Removed if color => line
+				.push_str(&word.red().to_string()),
+			Change::Inserted if color => line
+				.push_str(&word.green().to_string()),
+			_ => line.push_str(&word),
+		}
+		if len == width { break; }
+	}
+	(line, len)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Word differences
+////////////////////////////////////////////////////////////////////////////////
+/// A row of a hunk, pairing the lines of the two files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+	/// The line at the given indices is in both files.
+	Keep(usize, usize),
+	/// The line at the given index of the first file was changed into the
+	/// line at the given index of the second file. Either line may be
+	/// missing if more lines were removed than inserted, or the reverse.
+	Change(Option<usize>, Option<usize>),
+}
+
+/// Returns the rows of a hunk of edits. The removed and inserted lines
+/// between each pair of kept lines are paired in order.
+fn rows(edits: &[Edit]) -> Vec<Row> {
+	let mut rows = Vec::new();
+	let mut removed = Vec::new();
+	let mut inserted = Vec::new();
+	let flush = |removed: &mut Vec<usize>, inserted: &mut Vec<usize>,
+		rows: &mut Vec<Row>|
+	{
+		for k in 0..removed.len().max(inserted.len()) {
+			rows.push(Row::Change(
+				removed.get(k).copied(),
+				inserted.get(k).copied()));
+		}
+		removed.clear();
+		inserted.clear();
+	};
+	for edit in edits {
+		match *edit {
+			Edit::Keep(i, j) => {
+				flush(&mut removed, &mut inserted, &mut rows);
+				rows.push(Row::Keep(i, j));
+			},
+			Edit::Remove(i) => removed.push(i),
+			Edit::Insert(j) => inserted.push(j),
+		}
+	}
+	flush(&mut removed, &mut inserted, &mut rows);
+	rows
+}
+
+/// The change made to a run of words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+	/// The words are in both lines.
+	Same,
+	/// The words are removed from the first line.
+	Removed,
+	/// The words are inserted into the second line.
+	Inserted,
+}
+
+/// Returns the runs of words which transform the line `old` into `new`. The
+/// removed words of each change precede the inserted words.
+fn diff_words(old: &str, new: &str) -> Vec<(Change, String)> {
+	let (old, new) = (words(old), words(new));
+	let mut runs: Vec<(Change, String)> = Vec::new();
+	let mut removed = String::new();
+	let mut inserted = String::new();
+	let flush = |removed: &mut String, inserted: &mut String,
+		runs: &mut Vec<(Change, String)>|
+	{
+		if !removed.is_empty() {
+			runs.push((Change::Removed, std::mem::take(removed)));
+		}
+		if !inserted.is_empty() {
+			runs.push((Change::Inserted, std::mem::take(inserted)));
+		}
+	};
+	for edit in diff_lines(&old, &new) {
+		match edit {
+			Edit::Keep(i, _) => {
+				flush(&mut removed, &mut inserted, &mut runs);
+				match runs.last_mut() {
+					Some((Change::Same, run)) => run.push_str(old[i]),
+					_ => runs.push((Change::Same, old[i].to_owned())),
+				}
+			},
+			Edit::Remove(i) => removed.push_str(old[i]),
+			Edit::Insert(j) => inserted.push_str(new[j]),
+		}
+	}
+	flush(&mut removed, &mut inserted, &mut runs);
+	runs
+}
+
+/// Splits a line into words: runs of alphanumeric characters and
+/// underscores, runs of whitespace, and single other characters.
+fn words(line: &str) -> Vec<&str> {
+	let class = |c: char| if c.is_alphanumeric() || c == '_' {
+		1
+	} else if c.is_whitespace() {
+		2
+	} else {
+		0
+	};
+	let mut words = Vec::new();
+	let mut start = 0;
+	let mut prev = None;
+	for (idx, c) in line.char_indices() {
+		let current = class(c);
+		if idx > start && (current == 0 || prev != Some(current)) {
+			words.push(&line[start..idx]);
+			start = idx;
+		}
+		prev = Some(current);
+	}
+	if start < line.len() { words.push(&line[start..]); }
+	words
+}
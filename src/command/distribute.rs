@@ -8,21 +8,55 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Internal library imports.
+use crate::error::StallError;
 use crate::CommonOptions;
 use crate::Stall;
+use crate::cancel;
+use crate::trash::Trash;
+use crate::confirm::Confirm;
+use crate::plan::Plan;
+use crate::entry::CopyOptions;
 use crate::entry::Entry;
+use crate::entry::Outcome;
+use crate::entry::Overwrite;
+use crate::report::CopyReport;
+use crate::report::warn;
+use crate::state::StateFile;
 
 // External library imports.
-use anyhow::anyhow;
-use anyhow::Error;
-use colored::Colorize as _;
-use either::Either;
+use tracing::event;
 use tracing::Level;
 use tracing::span;
 
 // Standard library imports.
 use std::path::Path;
-use std::io::Write as _;
+use std::io::Write;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// DistributeOptions
+////////////////////////////////////////////////////////////////////////////////
+/// The switches controlling a run of the [`distribute`] command.
+///
+/// [`distribute`]: fn.distribute.html
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct DistributeOptions {
+	/// Allow the [`Overwrite`] safeguards to be bypassed for the files of
+	/// pinned entries.
+	///
+	/// [`Overwrite`]: ../entry/struct.Overwrite.html
+	pub unpin: bool,
+	/// Create missing remote directories.
+	pub create_dirs: bool,
+	/// Copy overwritten remote files into the stall's trash, unless the
+	/// command is run on a read-only stall.
+	pub use_trash: bool,
+	/// Write only the summary line instead of the status of each entry.
+	pub summary_only: bool,
+	/// Do not copy any files.
+	pub dry_run: bool,
+}
 
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -33,86 +67,153 @@ use std::io::Write as _;
 /// This will iterate over each entry in the [`Stall`], checking if it is older
 /// than its counterpart in the remote directory by comparing their modification
 /// times. If the stall file is newer, it will be copied into the remote
-/// directory, overwriting the existing file.
+/// directory, overwriting the existing file. The files of privileged entries
+/// are copied using the [`Privilege`] options, or the copy commands are
+/// written into its script.
 ///
 /// ### Parameters
 ///
+/// + `out`: The writer to write the status of each entry and any
+///   confirmation prompts into.
 /// + `stall_dir`: The stall directory to distribute from.
 /// + `stall`: The loaded `Stall` data.
-/// + `files`: An iterator over the [`Path`]s of the files to distribute.
-/// + `force`: Force overwrites even if the files are current.
-/// + `dry_run`: Do not copy any files.
+/// + `files`: An iterator over the [`Path`]s of the files to distribute. Entry
+///   indices and ranges as printed by [`status`] and partial names are
+///   also accepted.
+/// + `overwrite`: The [`Overwrite`] safeguards to bypass when copying files.
+/// + `run`: The [`DistributeOptions`] switches, including whether to copy
+///   overwritten remote files into the stall's [`Trash`].
+/// + `options`: The [`CopyOptions`] to use for copying files.
+/// + `confirm`: The [`Confirm`] to ask before overwriting existing files.
+/// + `plan`: The [`Plan`] to record the copies of a dry run in.
 /// + `common`: The [`CommonOptions`] to use for the command.
 ///
+/// Entries are processed in the [`EntryOrder`] of the `options`, which orders
+/// them by their local paths by default, except that each entry is processed
+/// after the entries it depends on. Archived entries are skipped unless the
+/// `options` include them. Processing continues after an entry fails, and the
+/// outcome of each entry is returned in a [`CopyReport`] in the same order,
+/// whose totals and failures are not printed.
+///
+/// A warning is emitted before any files are copied for each processed entry
+/// whose files overlap those of another entry.
+///
 /// ### Errors
 /// 
-/// Returns an [`Error`] if both files exist but their metadata can't be read,
-/// if the copy operation fails, or if any IO errors occur.
+/// Returns a [`StallError`] if any of the given files are not in the stall or
+/// match more than one entry, if there is a cycle in the dependencies between
+/// entries, or if an IO error occurs during writing.
 /// 
 /// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
 /// [`Stall`]: ../struct.Stall.html
+/// [`status`]: fn.status.html
+/// [`Trash`]: ../trash/struct.Trash.html
+/// [`Overwrite`]: ../entry/struct.Overwrite.html
+/// [`DistributeOptions`]: struct.DistributeOptions.html
+/// [`CopyOptions`]: ../entry/struct.CopyOptions.html
+/// [`EntryOrder`]: ../entry/enum.EntryOrder.html
+/// [`Confirm`]: ../confirm/struct.Confirm.html
+/// [`Plan`]: ../plan/struct.Plan.html
+/// [`Privilege`]: ../privilege/struct.Privilege.html
 /// [`CommonOptions`]: ../command/struct.CommonOptions.html
-/// [`Error`]: ../error/struct.Error.html
+/// [`CopyReport`]: ../report/struct.CopyReport.html
+/// [`StallError`]: ../error/enum.StallError.html
 /// 
+#[allow(clippy::too_many_arguments)]
 pub fn distribute<'i, I>(
+	out: &mut dyn Write,
 	stall_dir: &Path,
 	stall: &Stall,
 	files: I,
-	force: bool,
-	dry_run: bool,
+	overwrite: Overwrite,
+	run: DistributeOptions,
+	options: &CopyOptions,
+	mut confirm: Option<&mut Confirm<'_>>,
+	mut plan: Option<&mut Plan>,
 	common: &CommonOptions) 
-	-> Result<(), Error>
+	-> Result<CopyReport, StallError>
 	where I: IntoIterator<Item=&'i Path>
 {
+	let DistributeOptions {
+		unpin,
+		create_dirs,
+		use_trash,
+		summary_only,
+		dry_run,
+	} = run;
 	let _span = span!(Level::INFO, "distribute").entered();
-	if dry_run && common.quiet { return Ok(()); }
+	if dry_run && common.quiet && plan.is_none() {
+		return Ok(CopyReport::new());
+	}
 
-	if stall.is_empty() {
-		if !common.quiet {
-			println!("No files in stall. Use `add` command to place files \
-			in the stall.");
-		}
-		// Nothing to do if there's no data.
-		return Ok(());
-	} 
+	// Nothing to do if there's no data.
+	if stall.is_empty() { return Ok(CopyReport::new()); }
 
-	// Identify stall files to process.
-	let selected = files
-		.into_iter()
-		.map(|f| stall
-			.entry_local(f)
-			.ok_or_else(|| anyhow!("unrecognized stall entry: {}",
-				f.display())))
-		.collect::<Result<Vec<_>, _>>()?;
+	// Identify stall files to process, in the order given by the options,
+	// with each entry following its dependencies.
+	let mut entries = stall.select(files)?;
+	entries.retain(|entry| options.include_archived || !entry.flags.archived);
+	options.order.sort(&mut entries, stall_dir, |entry| entry);
+	stall.sort_by_dependencies(&mut entries)?;
 
-	let entries = if selected.is_empty() {
-		Either::Left(stall.entries())
-	} else {
-		Either::Right(selected.into_iter())
-	};
+	// Overlapping entries may copy the same files more than once.
+	let selected = |local: &Path| entries
+		.iter()
+		.any(|entry| entry.local == local);
+	for warning in stall.overlaps(selected) {
+		warn(warning, common)?;
+	}
 
-	let mut out = std::io::stdout();
+	// The status of each entry is not printed in summary only mode.
+	let entry_common = CommonOptions {
+		quiet: common.quiet || summary_only,
+		..common.clone()
+	};
 
-	// Setup and print stall directory.
-	if common.color.enabled() {
-		writeln!(&mut out, "{} {}",
-			"Stall directory:".bright_white(),
-			stall_dir.display())?;
+	let mut trash = if use_trash && !dry_run && !common.readonly {
+		Some(Trash::open(stall_dir, common.backup_dir.as_deref())?)
 	} else {
-		writeln!(&mut out, "Stall directory: {}",
-			stall_dir.display())?;
-	}
+		None
+	};
+	if !dry_run { options.privilege.start_script()?; }
 
-	// Process each entry table.
-	Entry::write_status_action_header(&mut out, common)?;
-	for entry in entries {
-		entry.distribute(
-			&mut out,
+	// Process each entry table. Unchanged files are skipped using the
+	// hashes cached in the stall's state file.
+	Entry::write_status_action_header(out, &entry_common)?;
+	let mut state = StateFile::read_from_stall_dir(stall_dir);
+	let mut report = CopyReport::new();
+	let total = entries.len();
+	for (index, entry) in entries.into_iter().enumerate() {
+		// Cancelled commands stop between entries.
+		if let Some(reason) = cancel::cancelled() {
+			report.set_cancelled(reason, total - index);
+			break;
+		}
+		let res = entry.distribute(
+			out,
 			stall_dir,
-			force,
+			overwrite,
+			unpin,
+			create_dirs,
+			options,
+			&mut state,
+			confirm.as_deref_mut(),
+			trash.as_mut(),
 			dry_run,
-			common)?;
+			&entry_common);
+		// The copies of a dry run are recorded in the plan.
+		let res = match (plan.as_deref_mut(), res) {
+			(Some(plan), Ok(Outcome::Copied)) if dry_run => plan
+				.push(&entry)
+				.map(|()| Outcome::Copied),
+			(_, res) => res,
+		};
+		report.record(entry.local, res);
 	}
-
-	Ok(())
+	if !common.readonly {
+		if let Err(e) = state.write_to_stall_dir(stall_dir) {
+			event!(Level::WARN, "{e}");
+		}
+	}
+	Ok(report)
 }
@@ -0,0 +1,162 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Print the disk usage of stalled files.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::entry::disk_usage;
+use crate::report::Cell;
+use crate::report::Table;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use colored::Color;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The size in bytes above which an entry is highlighted as large.
+const LARGE_ENTRY_SIZE: u64 = 10 * 1024 * 1024;
+
+/// The fraction of the larger size by which the sizes of an entry's files
+/// must differ for them to be highlighted.
+const SIZE_DIFFERENCE_RATIO: f64 = 0.5;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// du
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-du' command.
+///
+/// This will print the size of the stalled and remote files of each entry,
+/// along with the total sizes. Large entries are highlighted, as are entries
+/// whose stalled and remote files differ greatly in size, which may indicate
+/// that one of them was truncated.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the stalled files.
+/// + `stall`: The loaded [`Stall`] data.
+/// + `files`: An iterator over the [`Path`]s of the entries to print. Entry
+///   indices and ranges as printed by [`status`] and partial names are also
+///   accepted. Defaults to all entries.
+/// + `bytes`: Print sizes in bytes instead of human readable units.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if any of the given files are not in the stall,
+/// or if an IO error occurs during writing.
+///
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`status`]: fn.status.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn du<'i, I>(
+	stall_dir: &Path,
+	stall: &Stall,
+	files: I,
+	bytes: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+	where I: IntoIterator<Item=&'i Path>
+{
+	let _span = span!(Level::INFO, "du").entered();
+
+	let mut selected = Vec::new();
+	for f in files {
+		selected.extend(stall.entries_selected(f)?);
+	}
+	if selected.is_empty() {
+		selected.extend(stall.entries());
+	}
+
+	let mut out = std::io::stdout();
+	if selected.is_empty() {
+		if !common.quiet {
+			writeln!(&mut out, "No files in stall. Use `add` command to place \
+				files in the stall.")?;
+		}
+		return Ok(());
+	}
+
+	let format = |size: Option<u64>| size
+		.map_or_else(|| "-".to_owned(), |size| format_size(size, bytes));
+
+	let mut table = Table::new(["Stall", "Remote", "Local Path", "Note"]);
+	let mut total_l = 0;
+	let mut total_r = 0;
+	for entry in &selected {
+		let size_l = disk_usage(&stall_dir.join(entry.local));
+		let size_r = disk_usage(entry.remote);
+		total_l += size_l.unwrap_or_default();
+		total_r += size_r.unwrap_or_default();
+
+		let note = match (size_l, size_r) {
+			(Some(l), Some(r)) if sizes_differ(l, r) => Some(
+				Cell::new("sizes differ").with_color(Color::BrightRed)),
+			(Some(l), r) if l.max(r.unwrap_or_default()) > LARGE_ENTRY_SIZE
+				=> Some(Cell::new("large").with_color(Color::BrightYellow)),
+			(None, Some(r)) if r > LARGE_ENTRY_SIZE => Some(
+				Cell::new("large").with_color(Color::BrightYellow)),
+			_ => None,
+		};
+		let color = note.as_ref().and_then(|note| note.color);
+		let cell = |text: String| match color {
+			Some(color) => Cell::new(text).with_color(color),
+			None        => Cell::new(text),
+		};
+		table.push_row(vec![
+			cell(format(size_l)),
+			cell(format(size_r)),
+			Cell::new(entry.local.display().to_string()),
+			note.unwrap_or_else(|| Cell::new("")),
+		]);
+	}
+	table.push_row(vec![
+		Cell::new(format_size(total_l, bytes)),
+		Cell::new(format_size(total_r, bytes)),
+		Cell::new(format!("Total of {} entries", selected.len())),
+		Cell::new(""),
+	]);
+	table.write(&mut out, common)?;
+	Ok(())
+}
+
+/// Returns `true` if the given sizes differ by more than the
+/// `SIZE_DIFFERENCE_RATIO` of the larger size.
+#[allow(clippy::cast_precision_loss)]
+fn sizes_differ(a: u64, b: u64) -> bool {
+	let larger = a.max(b) as f64;
+	(a.abs_diff(b) as f64) > larger * SIZE_DIFFERENCE_RATIO
+}
+
+/// Formats a size in bytes, using binary units unless `bytes` is set.
+#[allow(clippy::cast_precision_loss)]
+fn format_size(size: u64, bytes: bool) -> String {
+	const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+	if bytes || size < 1024 { return format!("{size} B"); }
+
+	let mut value = size as f64 / 1024.0;
+	let mut unit = 0;
+	while value >= 1024.0 && unit < UNITS.len() - 1 {
+		value /= 1024.0;
+		unit += 1;
+	}
+	format!("{value:.1} {}", UNITS[unit])
+}
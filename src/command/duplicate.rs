@@ -0,0 +1,132 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Duplicate a file within a stall.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::CommonOptions;
+use crate::Stall;
+use crate::entry::copy;
+use crate::entry::CopyMethod;
+
+// External library imports.
+use tracing::event;
+use tracing::Level;
+use tracing::span;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// duplicate
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-cp' command.
+///
+/// This will add a new entry to the stall which shares its stalled file with
+/// an existing entry, but which has a different local name and remote path.
+///
+/// ### Parameters
+///
+/// + `stall`: The loaded [`Stall`] data.
+/// + `from`: The name of the existing stalled file.
+/// + `to`: The name of the new stalled file.
+/// + `remote`: The remote path of the new stalled file.
+/// + `copy_stall_dir`: The stall directory to copy the files within, or `None`
+///   if no copy should occur.
+/// + `force`: Force overwrite if the new entry already exists.
+/// + `dry_run`: Do not modify any files.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if the existing entry is not found, if the new entry
+/// already exists and `force` is not given, or if the copy operation fails.
+/// 
+/// [`Stall`]: ../struct.Stall.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+#[allow(clippy::too_many_arguments)]
+pub fn duplicate(
+	stall: &mut Stall,
+	from: &Path,
+	to: &Path,
+	remote: &Path,
+	copy_stall_dir: Option<&Path>,
+	force: bool,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "duplicate").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	if stall.entry_local(from).is_none() {
+		return Err(stall.missing_entry(from));
+	}
+
+	if !force {
+		// The new entry would overwrite an existing entry.
+		if stall.entry_local(to).is_some() {
+			return Err(StallError::Conflict {
+				reason: "stall file already exists".to_owned(),
+				subject: to.display().to_string(),
+				hint: Some("Use --force option to overwrite it.".to_owned()),
+			});
+		}
+		if let Some(entry) = stall.entry_remote(remote) {
+			return Err(StallError::Conflict {
+				reason: "remote file is already stalled as".to_owned(),
+				subject: entry.local.display().to_string(),
+				hint: Some("Use --force option to overwrite it.".to_owned()),
+			});
+		}
+	}
+
+	event!(Level::DEBUG, "Duplicate entry {:?} as {:?} with remote path {:?}",
+		from, to, remote);
+
+	if dry_run {
+		let mut out = std::io::stdout();
+		writeln!(&mut out, "Insert stall entry {} from {} copying {}",
+			to.display(),
+			remote.display(),
+			from.display())?;
+		return Ok(());
+	}
+
+	// With --force, the remote file is moved from the entry containing it.
+	let moved = stall.entry_remote(remote)
+		.filter(|entry| entry.local != to)
+		.map(|entry| entry.remote.to_path_buf());
+	if let Some((local, remote)) = moved
+		.and_then(|remote| stall.remove_remote(&remote))
+		.filter(|_| !common.quiet)
+	{
+		writeln!(&mut std::io::stdout(), "Moved remote {} from stall entry {}",
+			remote.display(),
+			local.display())?;
+	}
+	let displaced = stall.insert(to.to_path_buf(), remote)?;
+	if let Some((local, remote)) = displaced.filter(|_| !common.quiet) {
+		writeln!(&mut std::io::stdout(), "Replaced stall entry {} from {}",
+			local.display(),
+			remote.display())?;
+	}
+
+	if let Some(stall_dir) = copy_stall_dir {
+		copy(
+			stall_dir.join(from).as_path(),
+			stall_dir.join(to).as_path(),
+			CopyMethod::Direct)?;
+	}
+
+	Ok(())
+}
@@ -0,0 +1,154 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Edit the stall file.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::command::prefs::run_editor;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::report::warn;
+use crate::warning::Warning;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// edit
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-edit' command.
+///
+/// This will open the stall file in the user's editor, as with the
+/// `prefs edit` command. The edited file is checked after the editor exits,
+/// and warnings are given for any remote files which don't exist. If the file
+/// can't be read, or stalls a remote file more than once, the user is asked
+/// whether to edit it again or to restore the version from before the edit.
+///
+/// ### Parameters
+///
+/// + `stall_path`: The path of the stall file.
+/// + `input`: The input to read the user's answers from.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the stall file cannot be read or written, the
+/// editor fails, or the edited stall file is invalid and was restored.
+///
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn edit(
+	stall_path: &Path,
+	input: &mut dyn BufRead,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "edit").entered();
+
+	let original = std::fs::read(stall_path)
+		.with_path("failed to read stall file", stall_path)?;
+	let mut out = std::io::stdout();
+
+	loop {
+		if let Err(e) = run_editor(stall_path) {
+			restore(stall_path, &original)?;
+			return Err(e);
+		}
+
+		let e = match check(stall_path, common) {
+			Ok(()) => break,
+			Err(e) => e,
+		};
+		writeln!(&mut std::io::stderr(), "{e}")?;
+
+		if !ask_edit_again(&mut out, input)? {
+			restore(stall_path, &original)?;
+			return Err(StallError::InvalidInput {
+				message: format!("edited stall file is invalid; restored \
+					the previous version of {}", stall_path.display()),
+			});
+		}
+	}
+
+	if !common.quiet {
+		writeln!(&mut out, "Stall file saved to {}", stall_path.display())?;
+	}
+	Ok(())
+}
+
+/// Checks that the stall file at the given path can be read and doesn't
+/// stall any remote file more than once, and warns about missing remote
+/// files.
+fn check(stall_path: &Path, common: &CommonOptions) -> Result<(), StallError> {
+	let stall = Stall::read_from_path_as(stall_path, common.stall_format)?;
+
+	let mut remotes = BTreeMap::new();
+	for entry in stall.entries() {
+		if let Some(other) = remotes.insert(entry.remote, entry.local) {
+			return Err(StallError::Conflict {
+				reason: "remote file is stalled more than once".to_owned(),
+				subject: entry.remote.display().to_string(),
+				hint: Some(format!("It is stalled as {} and {}.",
+					other.display(),
+					entry.local.display())),
+			});
+		}
+	}
+
+	for entry in stall.entries() {
+		if !entry.remote.exists() {
+			warn(Warning::MissingRemote {
+				path: entry.remote.to_path_buf(),
+			}, common)?;
+		}
+	}
+	Ok(())
+}
+
+/// Asks the user whether to edit an invalid stall file again, returning
+/// `false` if it should be restored. The end of the input is treated as
+/// restoring the file.
+fn ask_edit_again(out: &mut dyn std::io::Write, input: &mut dyn BufRead)
+	-> Result<bool, StallError>
+{
+	loop {
+		write!(out, "Edit the stall file again, or restore the previous \
+			version? [e/r] ")?;
+		out.flush()?;
+
+		let mut line = String::new();
+		if input.read_line(&mut line)? == 0 {
+			writeln!(out)?;
+			return Ok(false);
+		}
+		match line.trim().to_lowercase().as_str() {
+			"e" | "edit"    => return Ok(true),
+			"r" | "restore" => return Ok(false),
+			_ => writeln!(out, "Enter e to edit the stall file again, or r to \
+				restore the previous version.")?,
+		}
+	}
+}
+
+/// Writes the original contents of the stall file back to the given path.
+fn restore(stall_path: &Path, original: &[u8]) -> Result<(), StallError> {
+	event!(Level::INFO, "Restoring stall file {:?}", stall_path);
+	std::fs::write(stall_path, original)
+		.with_path("failed to restore stall file", stall_path)
+}
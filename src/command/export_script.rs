@@ -0,0 +1,102 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Export a standalone restore script.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::platform;
+use crate::script::write_restore_script;
+use crate::script::ScriptShell;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// export_script
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-export-script' command.
+///
+/// This will write a shell script which copies the stalled file of each entry
+/// in the [`Stall`] to its remote location, so that the stall can be restored
+/// on a machine without stall installed.
+///
+/// ### Parameters
+///
+/// + `stall`: The loaded `Stall` data.
+/// + `output`: The path of the script to write. If `None`, the script is
+///   printed to stdout.
+/// + `shell`: The [`ScriptShell`] to write the script for. If `None`, the
+///   shell is chosen from the extension of `output`, defaulting to `sh`.
+/// + `force`: Overwrite `output` if it exists.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if `output` exists and `force` is not given, or if
+/// an IO error occurs during writing.
+///
+/// [`Stall`]: ../struct.Stall.html
+/// [`ScriptShell`]: ../script/enum.ScriptShell.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn export_script(
+	stall: &Stall,
+	output: Option<&Path>,
+	shell: Option<ScriptShell>,
+	force: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "export_script").entered();
+	let shell = shell
+		.or_else(|| output.and_then(ScriptShell::from_path))
+		.unwrap_or_default();
+
+	let Some(output) = output else {
+		let _ = write_restore_script(
+			&mut std::io::stdout().lock(),
+			stall.entries(),
+			shell)?;
+		return Ok(());
+	};
+
+	if output.exists() && !force {
+		return Err(StallError::Conflict {
+			reason: "script file already exists".to_owned(),
+			subject: output.display().to_string(),
+			hint: Some("Use --force option to overwrite it.".to_owned()),
+		});
+	}
+
+	event!(Level::INFO, "Writing {} restore script {:?}", shell, output);
+	let mut script = Vec::new();
+	let count = write_restore_script(&mut script, stall.entries(), shell)?;
+	std::fs::write(output, script)
+		.with_path("failed to write restore script", output)?;
+	if shell == ScriptShell::Sh { platform::set_executable(output)?; }
+
+	if !common.quiet {
+		writeln!(&mut std::io::stdout(), "Wrote {} restore script for {} \
+			files into {}",
+			shell,
+			count,
+			output.display())?;
+	}
+	Ok(())
+}
@@ -0,0 +1,190 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Set stall entry flags.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::CommonOptions;
+use crate::Stall;
+use crate::entry::ConflictPolicy;
+use crate::transform::LineEnding;
+use crate::transform::Transform;
+
+// External library imports.
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// flag
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-flag' command.
+///
+/// This will set or clear the pinned, readonly, no-gitignore, binary,
+/// privileged, and archived flags, the line ending, the transforms, the
+/// conflict policy, and the dependencies of entries in the [`Stall`]. A pinned entry's stalled file
+/// will not be overwritten by `collect`, and a readonly entry's remote files
+/// will not be overwritten by `distribute`. A no-gitignore entry's remote
+/// directory will be collected including any files ignored by git. A binary
+/// entry's file contents are not compared by `status` or printed by `diff`. A
+/// privileged entry's remote files are copied with elevated privileges by
+/// `distribute`. An archived entry is skipped by `status`, `collect`, and
+/// `distribute` unless archived entries are included. An entry's remote files
+/// are converted to its line ending when distributed, and an entry's
+/// [`Transform`]s are applied to its files whenever they are copied. An entry is
+/// distributed after the entries it depends on. If no flag changes are given,
+/// the current flags are printed.
+///
+/// ### Parameters
+///
+/// + `stall`: The loaded `Stall` data.
+/// + `files`: An iterator over the local [`Path`]s of the entries to modify.
+///   Group names prefixed with `@` are also accepted.
+/// + `pinned`: The new pinned flag, or `None` if it should not be changed.
+/// + `readonly`: The new readonly flag, or `None` if it should not be
+///   changed.
+/// + `no_gitignore`: The new no-gitignore flag, or `None` if it should not be
+///   changed.
+/// + `binary`: The new binary flag, or `None` if it should not be changed.
+/// + `privileged`: The new privileged flag, or `None` if it should not be
+///   changed.
+/// + `archived`: The new archived flag, or `None` if it should not be
+///   changed.
+/// + `eol`: The new [`LineEnding`], or `None` if it should not be changed.
+/// + `transforms`: The new [`Transform`]s, or `None` if they should not be
+///   changed.
+/// + `conflict`: The new [`ConflictPolicy`], or `None` if it should not be
+///   changed. A policy of `None` uses the `conflict_policy` preference.
+/// + `after`: The local [`Path`]s of the entries the entries depend on, or
+///   `None` if they should not be changed. Partial local names and group
+///   names prefixed with `@` are also accepted.
+/// + `dry_run`: Do not modify the stall.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if any of the files are not in the stall, if the
+/// dependencies would form a cycle, or if an IO error occurs during writing.
+/// 
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`ConflictPolicy`]: ../entry/enum.ConflictPolicy.html
+/// [`LineEnding`]: ../transform/enum.LineEnding.html
+/// [`Transform`]: ../transform/enum.Transform.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::option_option)]
+pub fn flag<'i, I>(
+	stall: &mut Stall,
+	files: I,
+	pinned: Option<bool>,
+	readonly: Option<bool>,
+	no_gitignore: Option<bool>,
+	binary: Option<bool>,
+	privileged: Option<bool>,
+	archived: Option<bool>,
+	eol: Option<LineEnding>,
+	transforms: Option<&[Transform]>,
+	conflict: Option<Option<ConflictPolicy>>,
+	after: Option<&[PathBuf]>,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+	where I: IntoIterator<Item=&'i Path>
+{
+	let _span = span!(Level::INFO, "flag").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let after = match after {
+		Some(after) => {
+			let mut locals = Vec::new();
+			for local in stall.expand_groups(after.iter().map(PathBuf::as_path))? {
+				locals.push(stall.resolve_local(&local)?.to_path_buf());
+			}
+			Some(locals)
+		},
+		None => None,
+	};
+
+	let files = stall.expand_groups(files)?;
+
+	// Dependency cycles are checked before any entries are modified.
+	if let Some(after) = after.as_ref().filter(|_| !dry_run) {
+		let mut checked = stall.clone();
+		for file in &files {
+			let _ = checked.set_dependencies(file, after.clone());
+		}
+		checked.check_dependencies()?;
+	}
+
+	let mut out = std::io::stdout();
+	for file in &files {
+		let file = file.as_path();
+		let mut flags = stall.flags(file)
+			.ok_or_else(|| stall.missing_entry(file))?;
+
+		if let Some(pinned) = pinned { flags.pinned = pinned; }
+		if let Some(readonly) = readonly { flags.readonly = readonly; }
+		if let Some(no_gitignore) = no_gitignore {
+			flags.no_gitignore = no_gitignore;
+		}
+		if let Some(binary) = binary { flags.binary = binary; }
+		if let Some(privileged) = privileged { flags.privileged = privileged; }
+		if let Some(archived) = archived { flags.archived = archived; }
+		if let Some(eol) = eol { flags.eol = eol; }
+		if let Some(conflict) = conflict { flags.conflict = conflict; }
+
+		let unchanged = pinned.is_none()
+			&& readonly.is_none()
+			&& no_gitignore.is_none()
+			&& binary.is_none()
+			&& privileged.is_none()
+			&& archived.is_none()
+			&& eol.is_none()
+			&& transforms.is_none()
+			&& conflict.is_none()
+			&& after.is_none();
+		if dry_run || unchanged {
+			writeln!(&mut out, "{}: {}",
+				file.display(),
+				flags.description())?;
+			let current = stall.transforms(file).unwrap_or_default();
+			for transform in transforms.unwrap_or(current) {
+				writeln!(&mut out, "    transform: {transform}")?;
+			}
+			let shown: Vec<&Path> = after.as_ref().map_or_else(
+				|| stall.dependencies(file)
+					.unwrap_or_default()
+					.iter()
+					.map(AsRef::as_ref)
+					.collect(),
+				|after| after.iter().map(PathBuf::as_path).collect());
+			for local in shown {
+				writeln!(&mut out, "    after: {}", local.display())?;
+			}
+			continue;
+		}
+
+		let _ = stall.set_flags(file, flags);
+		if let Some(transforms) = transforms {
+			let _ = stall.set_transforms(file, transforms.to_vec());
+		}
+		if let Some(after) = &after {
+			let _ = stall.set_dependencies(file, after.clone());
+		}
+	}
+
+	Ok(())
+}
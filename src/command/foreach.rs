@@ -0,0 +1,195 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Run a command for each stall entry.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::CommonOptions;
+use crate::Stall;
+use crate::command::StallState;
+use crate::command::StateFilter;
+use crate::platform::shell_command;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The environment variable holding the path of the entry's local file.
+pub const STALL_LOCAL_ENV: &str = "STALL_LOCAL";
+
+/// The environment variable holding the path of the entry's remote file.
+pub const STALL_REMOTE_ENV: &str = "STALL_REMOTE";
+
+/// The environment variable holding the name of the entry within the stall.
+pub const STALL_NAME_ENV: &str = "STALL_NAME";
+
+
+////////////////////////////////////////////////////////////////////////////////
+// foreach
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-foreach' command.
+///
+/// This will run a shell command once for each selected entry in the
+/// [`Stall`]. The `STALL_LOCAL`, `STALL_REMOTE`, and `STALL_NAME` environment
+/// variables are set for each command.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the local files.
+/// + `stall`: The loaded `Stall` data.
+/// + `command`: The shell command to run.
+/// + `patterns`: An iterator over glob patterns to match against the local
+///   paths of entries, or group names prefixed with `@`. If empty, all
+///   entries will match.
+/// + `filter`: A [`StateFilter`] for selecting entries by state.
+/// + `jobs`: The maximum number of commands to run in parallel.
+/// + `dry_run`: Print the entries the command would run for instead of running
+///   it.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if any of the patterns are invalid, or if the command
+/// could not be run or failed for any entry.
+/// 
+/// [`Stall`]: ../struct.Stall.html
+/// [`StateFilter`]: ../command/struct.StateFilter.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+#[allow(clippy::too_many_arguments)]
+pub fn foreach<'i, I>(
+	stall_dir: &Path,
+	stall: &Stall,
+	command: &str,
+	patterns: I,
+	filter: StateFilter,
+	jobs: usize,
+	dry_run: bool,
+	common: &CommonOptions) 
+	-> Result<(), StallError>
+	where I: IntoIterator<Item=&'i Path>
+{
+	let _span = span!(Level::INFO, "foreach").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let patterns = stall.entry_patterns(patterns)?;
+
+	// Select entries to run the command for.
+	let mut targets = Vec::new();
+	for entry in stall.entries() {
+		if !filter.matches_flags(entry.flags) { continue; }
+		if !patterns.as_ref().is_none_or(|patterns| patterns
+			.iter()
+			.any(|p| p.matches_path(entry.local)))
+		{
+			continue;
+		}
+
+		if !filter.is_empty() {
+			let (status_l, status_r) = entry.status(stall_dir);
+			let state = StallState::from_statuses(status_l, status_r);
+			if !filter.matches(state) { continue; }
+		}
+
+		targets.push(Target {
+			name: entry.local.to_path_buf(),
+			local: stall_dir.join(entry.local),
+			remote: entry.remote.to_path_buf(),
+		});
+	}
+
+	if dry_run {
+		let mut out = std::io::stdout();
+		for target in &targets {
+			writeln!(&mut out, "Run command for {} ({})",
+				target.name.display(),
+				target.remote.display())?;
+		}
+		return Ok(());
+	}
+
+	// Run the command for each target, using a shared index as a work queue.
+	let next = AtomicUsize::new(0);
+	let failures = Mutex::new(Vec::new());
+	std::thread::scope(|scope| {
+		for _ in 0..jobs.clamp(1, targets.len().max(1)) {
+			let _ = scope.spawn(|| loop {
+				let i = next.fetch_add(1, Ordering::SeqCst);
+				let Some(target) = targets.get(i) else { break };
+				if let Err(e) = target.run(command) {
+					event!(Level::WARN, "{:?}", e);
+					failures
+						.lock()
+						.expect("lock foreach failures")
+						.push(target.name.clone());
+				}
+			});
+		}
+	});
+
+	let failures = failures.into_inner().expect("lock foreach failures");
+	if failures.is_empty() {
+		Ok(())
+	} else {
+		Err(StallError::CommandFailed {
+			message: format!("command failed for {} of {} entries",
+				failures.len(),
+				targets.len()),
+		})
+	}
+}
+
+
+/// A stall entry to run a command for.
+#[derive(Debug, Clone)]
+struct Target {
+	/// The name of the entry within the stall.
+	name: PathBuf,
+	/// The path of the local file.
+	local: PathBuf,
+	/// The path of the remote file.
+	remote: PathBuf,
+}
+
+impl Target {
+	/// Runs the given shell command for the target.
+	fn run(&self, command: &str) -> Result<(), StallError> {
+		event!(Level::INFO, "Running command for {:?}", self.name);
+		let status = shell_command(command)
+			.env(STALL_LOCAL_ENV, &self.local)
+			.env(STALL_REMOTE_ENV, &self.remote)
+			.env(STALL_NAME_ENV, &self.name)
+			.status()
+			.with_path("failed to run command for", &self.name)?;
+
+		if status.success() {
+			Ok(())
+		} else {
+			Err(StallError::CommandFailed {
+				message: format!("command failed for {}: {}",
+					self.name.display(),
+					status),
+			})
+		}
+	}
+}
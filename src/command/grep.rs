@@ -0,0 +1,219 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Search the contents of stalled files.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use colored::Colorize as _;
+use regex::Regex;
+use regex::RegexBuilder;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::collections::BTreeSet;
+use std::io::BufRead as _;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// grep
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-grep' command.
+///
+/// This will search the contents of the files of each entry in the [`Stall`]
+/// for lines matching a regular expression, and print the matching lines.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the local files.
+/// + `stall`: The loaded `Stall` data.
+/// + `pattern`: The regular expression to search for.
+/// + `target`: The [`GrepTarget`] files to search.
+/// + `ignore_case`: Match the pattern case-insensitively.
+/// + `fixed_strings`: Interpret the pattern as a literal string.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// Returns `true` if any matches were found.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if the pattern is invalid or if an IO errors during
+/// writing occur. Unreadable files are skipped.
+/// 
+/// [`Stall`]: ../struct.Stall.html
+/// [`GrepTarget`]: ../command/enum.GrepTarget.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn grep(
+	stall_dir: &Path,
+	stall: &Stall,
+	pattern: &str,
+	target: GrepTarget,
+	ignore_case: bool,
+	fixed_strings: bool,
+	common: &CommonOptions) 
+	-> Result<bool, StallError>
+{
+	let _span = span!(Level::INFO, "grep").entered();
+
+	let regex = if fixed_strings {
+		RegexBuilder::new(&regex::escape(pattern))
+	} else {
+		RegexBuilder::new(pattern)
+	}
+		.case_insensitive(ignore_case)
+		.build()
+		.map_err(|e| StallError::InvalidPattern {
+			pattern: pattern.to_owned(),
+			message: e.to_string(),
+		})?;
+
+	let mut out = std::io::stdout();
+	let mut found = false;
+	let mut searched_local = BTreeSet::new();
+
+	for entry in stall.entries() {
+		// Entries with multiple remotes share a local file, so only search it
+		// once.
+		if target.local() && searched_local.insert(entry.local) {
+			let path = stall_dir.join(entry.local);
+			let header = format!("{} [local]", entry.local.display());
+			found |= grep_file(&mut out, &path, &header, &regex, common)?;
+		}
+
+		if target.remote() {
+			let header = format!("{} ({}) [remote]",
+				entry.local.display(),
+				entry.remote.display());
+			found |= grep_file(&mut out, entry.remote, &header, &regex,
+				common)?;
+		}
+	}
+
+	Ok(found)
+}
+
+
+/// Searches the file at the given path and prints any matching lines under
+/// the given header. Returns `true` if any matches were found.
+fn grep_file(
+	out: &mut dyn Write,
+	path: &Path,
+	header: &str,
+	regex: &Regex,
+	common: &CommonOptions)
+	-> Result<bool, StallError>
+{
+	let file = match std::fs::File::open(path) {
+		Ok(file) if path.is_file() => file,
+		Ok(_) => return Ok(false),
+		Err(e) => {
+			event!(Level::DEBUG, "Skipping unreadable file {:?}: {}", path, e);
+			return Ok(false);
+		},
+	};
+
+	let mut found = false;
+	let mut reader = BufReader::new(file);
+	let mut buf = Vec::new();
+	let mut line_number = 0;
+	loop {
+		buf.clear();
+		let read = reader.read_until(b'\n', &mut buf)
+			.with_path("failed to read file", path)?;
+		if read == 0 { break; }
+		line_number += 1;
+
+		let line = String::from_utf8_lossy(&buf);
+		let line = line.trim_end_matches(['\n', '\r']);
+		if !regex.is_match(line) { continue; }
+
+		if common.quiet { return Ok(true); }
+		if !found {
+			if common.color.enabled() {
+				writeln!(out, "{}", header.bright_white().bold())?;
+			} else {
+				writeln!(out, "{header}")?;
+			}
+			found = true;
+		}
+		write_match(out, line_number, line, regex, common)?;
+	}
+
+	Ok(found)
+}
+
+
+/// Writes a matching line into `out`, highlighting the matched text.
+fn write_match(
+	out: &mut dyn Write,
+	line_number: usize,
+	line: &str,
+	regex: &Regex,
+	common: &CommonOptions)
+	-> std::io::Result<()>
+{
+	if !common.color.enabled() {
+		return writeln!(out, "    {line_number}: {line}");
+	}
+
+	write!(out, "    {}: ", line_number.to_string().bright_green())?;
+	let mut last = 0;
+	for m in regex.find_iter(line) {
+		write!(out, "{}{}",
+			&line[last..m.start()],
+			m.as_str().bright_red().bold())?;
+		last = m.end();
+	}
+	writeln!(out, "{}", &line[last..])
+}
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// GrepTarget
+////////////////////////////////////////////////////////////////////////////////
+/// The files to search with the `grep` command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[derive(clap::ArgEnum)]
+pub enum GrepTarget {
+	/// Search the files in the stall directory.
+	#[default]
+	Local,
+	/// Search the files at their remote locations.
+	Remote,
+	/// Search both the stall directory files and the remote files.
+	Both,
+}
+
+impl GrepTarget {
+	/// Returns `true` if files in the stall directory should be searched.
+	#[must_use]
+	pub const fn local(self) -> bool {
+		matches!(self, Self::Local | Self::Both)
+	}
+
+	/// Returns `true` if remote files should be searched.
+	#[must_use]
+	pub const fn remote(self) -> bool {
+		matches!(self, Self::Remote | Self::Both)
+	}
+}
@@ -0,0 +1,350 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Entry group management commands.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::CommonOptions;
+use crate::Stall;
+use crate::report::Cell;
+use crate::report::Table;
+
+// External library imports.
+use colored::Color;
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// group_create
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-group-create' command.
+///
+/// This will create a named group of entries in the [`Stall`], which can then
+/// be selected by commands using its name prefixed with `@`.
+///
+/// ### Parameters
+///
+/// + `stall`: The loaded `Stall` data.
+/// + `name`: The name of the group to create, optionally prefixed with `@`.
+/// + `files`: An iterator over the [`Path`]s of the group's initial members.
+///   Partial local names and other groups prefixed with `@` are also
+///   accepted.
+/// + `dry_run`: Do not modify the stall.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the name is invalid or already used by a
+/// group, if any of the files are not in the stall, or if an IO error occurs
+/// during writing.
+///
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn group_create<'i, I>(
+	stall: &mut Stall,
+	name: &str,
+	files: I,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+	where I: IntoIterator<Item=&'i Path>
+{
+	let _span = span!(Level::INFO, "group_create").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let name = parse_group_name(name)?;
+	if stall.group(name).is_ok() {
+		return Err(StallError::Conflict {
+			reason: "group already exists".to_owned(),
+			subject: name.to_owned(),
+			hint: Some("Use `group add` to add entries to it.".to_owned()),
+		});
+	}
+	let members = resolve_members(stall, files)?;
+
+	if dry_run {
+		let mut out = std::io::stdout();
+		writeln!(&mut out, "Create group {name}")?;
+		for local in &members {
+			writeln!(&mut out, "Add {} to group {name}", local.display())?;
+		}
+		return Ok(());
+	}
+
+	let _ = stall.insert_group(name);
+	for local in &members {
+		let _ = stall.insert_group_member(name, local);
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// group_add
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-group-add' command.
+///
+/// This will add entries to an existing group in the [`Stall`]. Entries which
+/// are already members of the group are ignored.
+///
+/// ### Parameters
+///
+/// + `stall`: The loaded `Stall` data.
+/// + `name`: The name of the group, optionally prefixed with `@`.
+/// + `files`: An iterator over the [`Path`]s of the entries to add. Partial
+///   local names and other groups prefixed with `@` are also accepted.
+/// + `dry_run`: Do not modify the stall.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the group does not exist, if any of the files
+/// are not in the stall, or if an IO error occurs during writing.
+///
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn group_add<'i, I>(
+	stall: &mut Stall,
+	name: &str,
+	files: I,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+	where I: IntoIterator<Item=&'i Path>
+{
+	let _span = span!(Level::INFO, "group_add").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let name = parse_group_name(name)?;
+	let _ = stall.group(name)?;
+	let members = resolve_members(stall, files)?;
+
+	let mut out = std::io::stdout();
+	for local in &members {
+		if dry_run {
+			writeln!(&mut out, "Add {} to group {name}", local.display())?;
+		} else if !stall.insert_group_member(name, local) {
+			event!(Level::DEBUG, "{:?} is already in group {}", local, name);
+		}
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// group_remove
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-group-rm' command.
+///
+/// This will remove entries from a group in the [`Stall`], or remove the
+/// group itself if no entries are given. The entries remain in the stall.
+///
+/// ### Parameters
+///
+/// + `stall`: The loaded `Stall` data.
+/// + `name`: The name of the group, optionally prefixed with `@`.
+/// + `files`: An iterator over the [`Path`]s of the entries to remove from the
+///   group. Partial local names and other groups prefixed with `@` are also
+///   accepted. If empty, the group is removed.
+/// + `dry_run`: Do not modify the stall.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the group does not exist, if any of the files
+/// are not members of the group, or if an IO error occurs during writing.
+///
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn group_remove<'i, I>(
+	stall: &mut Stall,
+	name: &str,
+	files: I,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+	where I: IntoIterator<Item=&'i Path>
+{
+	let _span = span!(Level::INFO, "group_remove").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let name = parse_group_name(name)?;
+	let group = stall.group(name)?;
+
+	// Members are matched exactly first, so that members whose entries no
+	// longer exist can be removed.
+	let mut locals = Vec::new();
+	for file in stall.expand_groups(files)? {
+		let local = if group.contains(file.as_path()) {
+			file
+		} else {
+			stall.resolve_local(&file)?.to_path_buf()
+		};
+		if !group.contains(local.as_path()) {
+			return Err(StallError::InvalidInput {
+				message: format!("{} is not in group {name}",
+					local.display()),
+			});
+		}
+		locals.push(local);
+	}
+
+	let mut out = std::io::stdout();
+	if locals.is_empty() {
+		if dry_run {
+			writeln!(&mut out, "Remove group {name}")?;
+		} else {
+			let _ = stall.remove_group(name);
+		}
+		return Ok(());
+	}
+
+	for local in &locals {
+		if dry_run {
+			writeln!(&mut out, "Remove {} from group {name}",
+				local.display())?;
+		} else {
+			let _ = stall.remove_group_member(name, local);
+		}
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// group_list
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-group-list' command.
+///
+/// This will print each group in the [`Stall`] along with its members, or the
+/// members of a single group, one per line. Members whose entries are not in
+/// the stall are highlighted.
+///
+/// ### Parameters
+///
+/// + `stall`: The loaded `Stall` data.
+/// + `name`: The name of the group to list, optionally prefixed with `@`, or
+///   `None` to list every group.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the group does not exist, or if an IO error
+/// occurs during writing.
+///
+/// [`Stall`]: ../struct.Stall.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn group_list(
+	stall: &Stall,
+	name: Option<&str>,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "group_list").entered();
+
+	let mut out = std::io::stdout();
+	if let Some(name) = name {
+		let name = parse_group_name(name)?;
+		for local in stall.group(name)? {
+			writeln!(&mut out, "{}", local.display())?;
+		}
+		return Ok(());
+	}
+
+	let mut table = Table::new(["Group", "Entries"]);
+	for (name, members) in stall.groups() {
+		let mut row = vec![Cell::new(format!("@{name}"))
+			.with_color(Color::BrightWhite)];
+		let mut members = members.iter();
+		let first = members.next();
+		row.push(first.map_or_else(|| Cell::new("-"), |local| member_cell(
+			stall,
+			local)));
+		table.push_row(row);
+		for local in members {
+			table.push_row(vec![Cell::new(""), member_cell(stall, local)]);
+		}
+	}
+
+	if table.is_empty() {
+		if !common.quiet {
+			writeln!(&mut out, "No groups in stall. Use `group create` to \
+				create a group.")?;
+		}
+		return Ok(());
+	}
+	table.write(&mut out, common)?;
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helper functions
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns the given group name without any `@` prefix.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the name is empty or contains whitespace, path
+/// separators, or commas.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+fn parse_group_name(name: &str) -> Result<&str, StallError> {
+	let name = name.strip_prefix('@').unwrap_or(name);
+	if name.is_empty() || name.contains(|c: char| c.is_whitespace()
+		|| matches!(c, '/' | '\\' | ',' | '@'))
+	{
+		return Err(StallError::InvalidInput {
+			message: format!("invalid group name: {name:?}"),
+		});
+	}
+	Ok(name)
+}
+
+/// Returns the local paths of the entries named by the given files, which may
+/// be partial local names or groups prefixed with `@`.
+fn resolve_members<'i, I>(stall: &Stall, files: I)
+	-> Result<Vec<PathBuf>, StallError>
+	where I: IntoIterator<Item=&'i Path>
+{
+	let mut members = Vec::new();
+	for file in stall.expand_groups(files)? {
+		members.push(stall.resolve_local(&file)?.to_path_buf());
+	}
+	Ok(members)
+}
+
+/// Returns the table cell for a group member, colored if its entry is not in
+/// the stall.
+fn member_cell(stall: &Stall, local: &Path) -> Cell {
+	let cell = Cell::new(local.display().to_string());
+	if stall.entry_local(local).is_some() {
+		cell
+	} else {
+		cell.with_color(Color::BrightRed)
+	}
+}
@@ -0,0 +1,136 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Help and manual page generation.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::suggest;
+use crate::CommandOptions;
+
+// External library imports.
+use clap::CommandFactory as _;
+use clap_mangen::Man;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// help
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-help' command.
+///
+/// This will write the help text for the given subcommand into `out`, or the
+/// help for the stall command itself if no subcommand is given.
+///
+/// ### Parameters
+///
+/// + `subcommand`: The name of the subcommand to print help for.
+/// + `all`: Print the help for the stall command and every subcommand.
+/// + `out`: The writer to write the help text into.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if the subcommand is not recognized or if an IO error
+/// occurs during writing.
+/// 
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn help(subcommand: Option<&str>, all: bool, mut out: &mut dyn Write)
+	-> Result<(), StallError>
+{
+	let mut command = CommandOptions::command();
+	command.build();
+
+	if all {
+		command.write_long_help(&mut out)?;
+		for sub in command.get_subcommands_mut() {
+			if sub.is_hide_set() { continue; }
+			writeln!(out)?;
+			writeln!(out)?;
+			sub.write_long_help(&mut out)?;
+		}
+		return Ok(());
+	}
+
+	let suggestions = subcommand.map(|name| suggest::similar(
+		name,
+		command.get_subcommands().map(clap::Command::get_name)));
+	match subcommand {
+		Some(name) => command
+			.find_subcommand_mut(name)
+			.ok_or_else(|| StallError::NotFound {
+				kind: "subcommand",
+				name: name.to_owned(),
+				suggestions: suggestions.unwrap_or_default(),
+			})?
+			.write_long_help(&mut out)?,
+		None => command.write_long_help(&mut out)?,
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// gen_manpage
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-gen-manpage' command.
+///
+/// This will generate man pages for the stall command and each of its
+/// subcommands. If an output directory is given, the pages will be written
+/// into it as `stall.1`, `stall-add.1`, etc. Otherwise only the page for the
+/// stall command is written into `out`.
+///
+/// ### Parameters
+///
+/// + `out_dir`: The directory to write the man pages into.
+/// + `out`: The writer to use if no output directory is given.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if an IO error occurs during writing.
+/// 
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn gen_manpage(out_dir: Option<&Path>, out: &mut dyn Write)
+	-> Result<(), StallError>
+{
+	let mut command = CommandOptions::command();
+	command.build();
+
+	let Some(out_dir) = out_dir else {
+		Man::new(command).render(out)?;
+		return Ok(());
+	};
+
+	let name = command.get_name().to_owned();
+	write_manpage(out_dir, &name, command.clone())?;
+	for sub in command.get_subcommands() {
+		if sub.is_hide_set() { continue; }
+		let sub_name = format!("{name}-{}", sub.get_name());
+		write_manpage(out_dir, &sub_name, sub.clone().name(&sub_name))?;
+	}
+	Ok(())
+}
+
+/// Renders the man page for the given command into a file in `out_dir`.
+fn write_manpage(out_dir: &Path, name: &str, command: clap::Command<'_>)
+	-> Result<(), StallError>
+{
+	let path = out_dir.join(format!("{name}.1"));
+	event!(Level::INFO, "Writing man page {:?}", path);
+
+	let mut buf = Vec::new();
+	Man::new(command).render(&mut buf)?;
+	std::fs::write(&path, buf)
+		.with_path("failed to write man page", &path)
+}
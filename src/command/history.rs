@@ -0,0 +1,93 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Operation history command.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::CommonOptions;
+use crate::journal::Journal;
+use crate::report::Cell;
+use crate::report::Table;
+
+// External library imports.
+use chrono::DateTime;
+use chrono::Local;
+use colored::Color;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// history
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-history' command.
+///
+/// This will print the time, operation, outcome, and affected entries of each
+/// operation recorded in the stall's [`Journal`], oldest first.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the journal.
+/// + `entry`: If given, only operations involving the entry with this local
+///   path are printed.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the journal cannot be read or if an IO error
+/// occurs during writing.
+///
+/// [`Journal`]: ../journal/struct.Journal.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn history(
+	stall_dir: &Path,
+	entry: Option<&Path>,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "history").entered();
+	let journal = Journal::new(stall_dir);
+	let mut out = std::io::stdout();
+
+	let mut table = Table::new(["TIME", "OPERATION", "OUTCOME", "ENTRIES"]);
+	for record in journal.records()? {
+		if entry.is_some_and(|entry| !record.involves(entry)) { continue; }
+
+		let time: DateTime<Local> = record.time().into();
+		let outcome = Cell::new(record.outcome());
+		let entries = record.entries
+			.iter()
+			.chain(record.failed.iter())
+			.map(|local| local.display().to_string())
+			.collect::<Vec<_>>()
+			.join(", ");
+		table.push_row(vec![
+			Cell::new(time.format("%Y-%m-%d %H:%M:%S").to_string()),
+			Cell::new(record.operation.to_string()),
+			if record.error.is_none() && record.failed.is_empty() {
+				outcome
+			} else {
+				outcome.with_color(Color::BrightRed)
+			},
+			Cell::new(entries),
+		]);
+	}
+
+	if table.is_empty() {
+		if !common.quiet { writeln!(&mut out, "No recorded operations.")?; }
+		return Ok(());
+	}
+	table.write(&mut out, common)?;
+	Ok(())
+}
@@ -8,15 +8,19 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Internal library imports.
+use crate::error::StallError;
+use crate::ignore::IgnoreRules;
 use crate::CommonOptions;
 use crate::Stall;
+use crate::template::RenamePolicy;
 
 // External library imports.
-use anyhow::Error;
+use tracing::event;
 use tracing::Level;
 use tracing::span;
 
 // Standard library imports.
+use std::io::Write as _;
 use std::path::Path;
 
 
@@ -31,32 +35,73 @@ use std::path::Path;
 ///
 /// + `stall_dir`: The stall directory to initialize.
 /// + `stall`: The loaded [`Stall`] data.
+/// + `rename_policy`: The [`RenamePolicy`] to store in a new stall file.
+/// + `from_dir`: A directory whose files are added to the stall. Each file is
+///   stalled at its path within the directory, and files ignored by the stall
+///   directory's [`IgnoreRules`] are skipped.
 /// + `dry_run`: Do not modify any files.
 /// + `common`: The [`CommonOptions`] to use for the command.
 ///
 /// ### Errors
 /// 
-/// Returns an [`Error`] if writing the stall file fails.
+/// Returns a [`StallError`] if writing the stall file fails, if `from_dir`
+/// can't be read, or if the ignore file is invalid.
 /// 
 /// [`Stall`]: ../struct.Stall.html
+/// [`RenamePolicy`]: ../template/enum.RenamePolicy.html
+/// [`IgnoreRules`]: ../ignore/struct.IgnoreRules.html
 /// [`CommonOptions`]: ../command/struct.CommonOptions.html
-/// [`Error`]: ../error/struct.Error.html
+/// [`StallError`]: ../error/enum.StallError.html
 /// 
 pub fn init(
-    _stall_dir: &Path,
+    stall_dir: &Path,
     stall: &mut Stall,
+    rename_policy: Option<RenamePolicy>,
+    from_dir: Option<&Path>,
     dry_run: bool,
     common: &CommonOptions)
-    -> Result<(), Error>
+    -> Result<(), StallError>
 {
     let _span = span!(Level::INFO, "init").entered();
     if dry_run && common.quiet { return Ok(()); }
 
+    // Don't modify the policy of an existing stall.
+    let exists = stall.load_path().is_some_and(Path::exists);
+    if let Some(rename_policy) = rename_policy.filter(|_| !exists) {
+        stall.set_rename_policy(rename_policy);
+    }
+
+    let mut added = 0;
+    if let Some(from_dir) = from_dir {
+        let ignore = IgnoreRules::load(stall_dir)?;
+        for local in ignore.walk(from_dir)? {
+            let remote = from_dir.join(&local);
+            event!(Level::DEBUG, "Add entry {:?} from {:?}", local, remote);
+            if dry_run {
+                writeln!(&mut std::io::stdout(),
+                    "Insert stall entry {} from {}",
+                    local.display(),
+                    remote.display())?;
+                continue;
+            }
+            let displaced = stall.insert(local, &remote)?;
+            if let Some((local, remote)) = displaced.filter(|_| !common.quiet) {
+                writeln!(&mut std::io::stdout(),
+                    "Replaced stall entry {} from {}",
+                    local.display(),
+                    remote.display())?;
+            }
+            added += 1;
+        }
+    }
+
     let written = if dry_run {
         true
     } else {
         stall.write_to_load_path_if_new()?
     };
+    // The new stall file is already up to date.
+    stall.set_modified(false);
 
     if !common.quiet {
         if written {
@@ -70,6 +115,11 @@ pub fn init(
                 .expect("retrieve stall load path")
                 .display());
         }
+        if let Some(from_dir) = from_dir.filter(|_| !dry_run) {
+            writeln!(&mut std::io::stdout(), "Added {} files from {}",
+                added,
+                from_dir.display())?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,81 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! List the entries of a stall.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::template::FormatTemplate;
+use crate::Stall;
+
+// External library imports.
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// list
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-list' command.
+///
+/// This will print each entry in the [`Stall`] using a [`FormatTemplate`].
+/// Unlike [`status`], no files are accessed, so the output is suitable for
+/// scripts.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the local files.
+/// + `stall`: The loaded [`Stall`] data.
+/// + `files`: An iterator over the [`Path`]s of the entries to list. Entry
+///   indices and ranges as printed by [`status`] and partial names are also
+///   accepted. Defaults to all entries.
+/// + `format`: The [`FormatTemplate`] to print each entry with.
+/// + `null`: Terminate each entry with a NUL byte instead of a newline.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if any of the given files are not in the stall,
+/// or if an IO error occurs during writing.
+///
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`status`]: fn.status.html
+/// [`FormatTemplate`]: ../template/struct.FormatTemplate.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn list<'i, I>(
+	stall_dir: &Path,
+	stall: &Stall,
+	files: I,
+	format: &FormatTemplate,
+	null: bool)
+	-> Result<(), StallError>
+	where I: IntoIterator<Item=&'i Path>
+{
+	let _span = span!(Level::INFO, "list").entered();
+
+	let mut selected = Vec::new();
+	for f in files {
+		selected.extend(stall.entries_selected(f)?);
+	}
+
+	let terminator = if null { '\0' } else { '\n' };
+	let mut out = std::io::stdout().lock();
+	// Entries are enumerated before filtering so that their indices match
+	// those printed by the status command.
+	for (i, entry) in stall.entries().enumerate() {
+		if !selected.is_empty() && !selected.contains(&entry) { continue; }
+		write!(&mut out, "{}{}",
+			format.render(stall_dir, &entry, i + 1),
+			terminator)?;
+	}
+	Ok(())
+}
@@ -0,0 +1,140 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Trace log commands.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::application::TraceLog;
+use crate::CommonOptions;
+
+// External library imports.
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::TimeZone as _;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::BufRead as _;
+use std::io::BufReader;
+use std::io::Write as _;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// log_show
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-log-show' command.
+///
+/// This will print the contents of the [`TraceLog`] files, oldest first.
+///
+/// ### Parameters
+///
+/// + `trace_log`: The `TraceLog` to print.
+/// + `since`: If given, only lines logged at or after this time are printed.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the log files cannot be read or if an IO error
+/// occurs during writing.
+///
+/// [`TraceLog`]: ../application/struct.TraceLog.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn log_show(
+	trace_log: &TraceLog,
+	since: Option<DateTime<Local>>,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "log_show").entered();
+	let mut out = std::io::stdout();
+
+	let files = trace_log.files()?;
+	if files.is_empty() {
+		if !common.quiet {
+			writeln!(&mut out, "No log files in {}",
+				trace_log.dir().display())?;
+		}
+		return Ok(());
+	}
+
+	for path in &files {
+		let file = std::fs::File::open(path)
+			.with_path("failed to open log file", path)?;
+
+		// Lines without a timestamp continue the previous event, so they are
+		// printed if it was.
+		let mut show = since.is_none();
+		for line in BufReader::new(file).lines() {
+			let line = line.with_path("failed to read log file", path)?;
+			if let (Some(since), Some(time)) = (since, line_time(&line)) {
+				show = time >= since;
+			}
+			if show { writeln!(&mut out, "{line}")?; }
+		}
+	}
+	Ok(())
+}
+
+/// Returns the timestamp at the start of a trace log line, if any.
+fn line_time(line: &str) -> Option<DateTime<Local>> {
+	let timestamp = line.split_whitespace().next()?;
+	DateTime::parse_from_rfc3339(timestamp)
+		.ok()
+		.map(|time| time.with_timezone(&Local))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// parse_since
+////////////////////////////////////////////////////////////////////////////////
+/// Parses a `--since` time.
+///
+/// The time may be a date (`2022-03-01`), a local date and time
+/// (`2022-03-01 12:30:00` or `2022-03-01T12:30:00`), or a duration before the
+/// current time in seconds, minutes, hours, days, or weeks (`30m`, `2d`,
+/// `1w`.)
+///
+/// ### Errors
+///
+/// Returns a description of the failure if the time cannot be parsed.
+pub fn parse_since(text: &str) -> Result<DateTime<Local>, String> {
+	let text = text.trim();
+	let invalid = || format!("invalid time {text:?}: expected a date, \
+		a date and time, or a duration such as 2d");
+
+	let unit = text.chars().last().ok_or_else(invalid)?;
+	if let Ok(count) = text[..text.len() - unit.len_utf8()].parse::<i64>() {
+		let duration = match unit {
+			's' => Duration::seconds(count),
+			'm' => Duration::minutes(count),
+			'h' => Duration::hours(count),
+			'd' => Duration::days(count),
+			'w' => Duration::weeks(count),
+			_   => return Err(invalid()),
+		};
+		return Ok(Local::now() - duration);
+	}
+
+	let naive = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+		.or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S"))
+		.ok()
+		.or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d")
+			.ok()
+			.and_then(|date| date.and_hms_opt(0, 0, 0)))
+		.ok_or_else(invalid)?;
+	Local.from_local_datetime(&naive)
+		.earliest()
+		.ok_or_else(invalid)
+}
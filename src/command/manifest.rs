@@ -0,0 +1,169 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Checksum manifest commands.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::CommonOptions;
+use crate::Stall;
+use crate::manifest::HashAlgorithm;
+use crate::manifest::Manifest;
+use crate::manifest::ManifestStatus;
+use crate::report::Cell;
+use crate::report::warn;
+use crate::report::Table;
+
+// External library imports.
+use colored::Color;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// manifest_write
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-manifest-write' command.
+///
+/// This will hash the local and remote files of each entry in the [`Stall`]
+/// and record the hashes in the stall's manifest file, replacing any previous
+/// manifest.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory to write the manifest into.
+/// + `stall`: The loaded `Stall` data.
+/// + `algorithm`: The [`HashAlgorithm`] to hash the files with.
+/// + `dry_run`: Print the hashes instead of writing the manifest.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if any file cannot be read, if the manifest cannot be
+/// written, or if an IO error occurs during writing.
+/// 
+/// [`Stall`]: ../struct.Stall.html
+/// [`HashAlgorithm`]: ../manifest/enum.HashAlgorithm.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn manifest_write(
+	stall_dir: &Path,
+	stall: &Stall,
+	algorithm: HashAlgorithm,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "manifest_write").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let manifest = Manifest::generate(stall_dir, stall, algorithm)?;
+	let mut out = std::io::stdout();
+
+	if dry_run {
+		let mut table = Table::new([algorithm.name(), "LOCATION", "FILE"]);
+		for (location, path, hash) in manifest.files() {
+			table.push_row(vec![
+				Cell::new(hash),
+				Cell::new(location.to_string()),
+				Cell::new(path.display().to_string()),
+			]);
+		}
+		table.write(&mut out, common)?;
+		return Ok(());
+	}
+
+	let path = manifest.write_to_stall_dir(stall_dir)?;
+	if !common.quiet {
+		writeln!(&mut out, "Recorded {} files in {}",
+			manifest.len(),
+			path.display())?;
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// manifest_verify
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-manifest-verify' command.
+///
+/// This will hash the local and remote files of each entry in the [`Stall`]
+/// and compare them against the stall's manifest file, printing any files
+/// which have changed, are missing, or are not recorded in the manifest.
+/// Entries whose files overlap are reported as warnings. Returns `true` if all
+/// files match the manifest.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the manifest.
+/// + `stall`: The loaded `Stall` data.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if the manifest does not exist or cannot be read, if
+/// any file cannot be read, or if an IO error occurs during writing.
+/// 
+/// [`Stall`]: ../struct.Stall.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn manifest_verify(
+	stall_dir: &Path,
+	stall: &Stall,
+	common: &CommonOptions)
+	-> Result<bool, StallError>
+{
+	let _span = span!(Level::INFO, "manifest_verify").entered();
+
+	let manifest_path = stall_dir.join(Manifest::DEFAULT_MANIFEST_PATH);
+	if !manifest_path.exists() {
+		return Err(StallError::NotFound {
+			kind: "manifest",
+			name: manifest_path.display().to_string(),
+			suggestions: Vec::new(),
+		});
+	}
+	let recorded = Manifest::read_from_stall_dir(stall_dir)?;
+	let current = Manifest::generate(stall_dir, stall, recorded.algorithm())?;
+	for warning in stall.overlaps(|_| true) {
+		warn(warning, common)?;
+	}
+	let mut out = std::io::stdout();
+
+	let mut table = Table::new(["STATUS", "LOCATION", "FILE"]);
+	let mut count = 0;
+	for (location, path, status) in recorded.verify(&current) {
+		count += 1;
+		let color = match status {
+			ManifestStatus::Ok         => continue,
+			ManifestStatus::Changed    => Color::BrightYellow,
+			ManifestStatus::Missing    => Color::BrightRed,
+			ManifestStatus::Unrecorded => Color::BrightCyan,
+		};
+		table.push_row(vec![
+			Cell::new(status.to_string()).with_color(color),
+			Cell::new(location.to_string()),
+			Cell::new(path.display().to_string()),
+		]);
+	}
+
+	if table.is_empty() {
+		if !common.quiet {
+			writeln!(&mut out, "All {count} files match the manifest.")?;
+		}
+		return Ok(true);
+	}
+	table.write(&mut out, common)?;
+	Ok(false)
+}
@@ -0,0 +1,253 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Merge the entries of another stall into a stall.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::entry::copy;
+use crate::entry::CopyMethod;
+use crate::entry::EntryFlags;
+use crate::error::StallError;
+use crate::path;
+use crate::trash::Trash;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// merge
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-merge' command.
+///
+/// This will add the entries of another stall to the stall, copying their
+/// stalled files into the stall directory. Entries which have the same local
+/// path as an existing entry, but different remote paths, are conflicts, and
+/// are resolved using the given [`MergeResolution`]. If no resolution is
+/// given, the conflicts are reported and nothing is merged.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory to copy stalled files into.
+/// + `stall`: The loaded [`Stall`] data to merge into.
+/// + `other_dir`: The stall directory of the other stall.
+/// + `other`: The loaded [`Stall`] data of the other stall.
+/// + `resolution`: The [`MergeResolution`] to use for conflicting entries.
+/// + `input`: The input to read answers from if the resolution is
+///   interactive.
+/// + `dry_run`: Do not modify any files.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if there are conflicting entries and no
+/// resolution is given, or if an IO error occurs while copying files.
+///
+/// [`Stall`]: ../struct.Stall.html
+/// [`MergeResolution`]: ../command/enum.MergeResolution.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+#[allow(clippy::too_many_arguments)]
+pub fn merge(
+	stall_dir: &Path,
+	stall: &mut Stall,
+	other_dir: &Path,
+	other: &Stall,
+	resolution: Option<MergeResolution>,
+	input: &mut dyn BufRead,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "merge").entered();
+
+	// Group the other stall's remotes by their local paths.
+	let mut theirs: BTreeMap<&Path, (Vec<&Path>, EntryFlags)> = BTreeMap::new();
+	for entry in other.entries() {
+		theirs
+			.entry(entry.local)
+			.or_insert_with(|| (Vec::new(), entry.flags))
+			.0
+			.push(entry.remote);
+	}
+
+	let conflicts: Vec<&Path> = theirs
+		.iter()
+		.filter(|(local, (remotes, _))| is_conflict(stall, local, remotes))
+		.map(|(local, _)| *local)
+		.collect();
+	if !conflicts.is_empty() && resolution.is_none() {
+		return Err(StallError::Conflict {
+			reason: "entries differ from the other stall".to_owned(),
+			subject: conflicts
+				.iter()
+				.map(|local| local.display().to_string())
+				.collect::<Vec<_>>()
+				.join(", "),
+			hint: Some("Use --ours to keep these entries, --theirs to replace \
+				them, or --interactive to choose for each.".to_owned()),
+		});
+	}
+
+	let mut out = std::io::stdout();
+	for (local, (remotes, flags)) in theirs {
+		let exists = stall.entry_local(local).is_some();
+		if exists && !conflicts.contains(&local) { continue; }
+
+		if exists {
+			let take = match resolution {
+				Some(MergeResolution::Theirs) => true,
+				Some(MergeResolution::Interactive) => ask_theirs(
+					&mut out,
+					input,
+					stall,
+					local,
+					&remotes)?,
+				_ => false,
+			};
+			if !take {
+				if !common.quiet {
+					writeln!(&mut out, "Keep stall entry {}",
+						local.display())?;
+				}
+				continue;
+			}
+		}
+
+		if dry_run {
+			for remote in &remotes {
+				writeln!(&mut out, "Insert stall entry {} from {}",
+					local.display(),
+					remote.display())?;
+			}
+			continue;
+		}
+
+		event!(Level::DEBUG, "Merging entry {:?} with remotes {:?}",
+			local, remotes);
+		let _ = stall.remove_local(local);
+		for remote in &remotes {
+			let displaced = stall.insert_remote(local.to_path_buf(), remote)?;
+			if let Some((displaced, remote)) = displaced {
+				if !common.quiet {
+					writeln!(&mut out, "Replaced stall entry {} from {}",
+						displaced.display(),
+						remote.display())?;
+				}
+			}
+		}
+		let _ = stall.set_flags(local, flags);
+		copy_local(stall_dir, other_dir, local, common)?;
+
+		if !common.quiet {
+			writeln!(&mut out, "Merged stall entry {}", local.display())?;
+		}
+	}
+	Ok(())
+}
+
+/// Returns `true` if the stall has an entry with the given local path whose
+/// remote paths differ from the given remote paths.
+fn is_conflict(stall: &Stall, local: &Path, remotes: &[&Path]) -> bool {
+	let ours: Vec<&Path> = stall
+		.entries_local(local)
+		.map(|entry| entry.remote)
+		.collect();
+	!ours.is_empty() && (ours.len() != remotes.len() || !ours
+		.iter()
+		.all(|o| remotes.iter().any(|r| path::same(o, r))))
+}
+
+/// Asks the user whether to replace the stall's entry with the other stall's
+/// entry. The end of the input is treated as keeping the entry.
+fn ask_theirs(
+	out: &mut dyn Write,
+	input: &mut dyn BufRead,
+	stall: &Stall,
+	local: &Path,
+	remotes: &[&Path])
+	-> Result<bool, StallError>
+{
+	let display = |remotes: Vec<&Path>| remotes
+		.iter()
+		.map(|r| r.display().to_string())
+		.collect::<Vec<_>>()
+		.join(", ");
+	let ours = display(stall
+		.entries_local(local)
+		.map(|entry| entry.remote)
+		.collect());
+	let theirs = display(remotes.to_vec());
+
+	loop {
+		write!(out, "Stall entry {} differs:\n    ours:   {}\n    theirs: {}\n\
+			Keep ours or take theirs? [o/t] ",
+			local.display(),
+			ours,
+			theirs)?;
+		out.flush()?;
+
+		let mut line = String::new();
+		if input.read_line(&mut line)? == 0 {
+			writeln!(out)?;
+			return Ok(false);
+		}
+		match line.trim().to_lowercase().as_str() {
+			"o" | "ours"   => return Ok(false),
+			"t" | "theirs" => return Ok(true),
+			_ => writeln!(out, "Enter o to keep this stall's entry, or t to \
+				take the other stall's entry.")?,
+		}
+	}
+}
+
+/// Copies the stalled file for the given local path from the other stall
+/// directory, if it exists. Any existing stalled file is moved into the trash.
+fn copy_local(
+	stall_dir: &Path,
+	other_dir: &Path,
+	local: &Path,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let source: PathBuf = other_dir.join(local);
+	if !source.exists() { return Ok(()); }
+
+	let target = stall_dir.join(local);
+	if target.exists() {
+		let _ = Trash::open(stall_dir, common.backup_dir.as_deref())
+			.and_then(|mut trash| trash.move_in(&target))?;
+	}
+	copy(&source, &target, CopyMethod::Atomic { durable: common.durable })
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// MergeResolution
+////////////////////////////////////////////////////////////////////////////////
+/// How to resolve conflicting entries when merging stalls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeResolution {
+	/// Keep the stall's entries.
+	Ours,
+	/// Replace the stall's entries with the other stall's entries.
+	Theirs,
+	/// Ask the user which entry to keep for each conflict.
+	Interactive,
+}
@@ -0,0 +1,110 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Print the locations of application and stall files.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::application::cache_dir;
+use crate::application::config_dir;
+use crate::application::state_dir;
+use crate::application::Config;
+use crate::application::StallLocation;
+use crate::error::StallError;
+use crate::journal::Journal;
+use crate::state::StateFile;
+use crate::trash::Trash;
+use crate::CommonOptions;
+
+// External library imports.
+use colored::Colorize as _;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// paths
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-paths' command.
+///
+/// This will print the application's config, state, and cache directories,
+/// the config, prefs, and log files within them, and the files of the stall,
+/// if there is one. Paths which can't be determined are printed as
+/// `(unknown)`.
+///
+/// ### Parameters
+///
+/// + `system_config`: The path of the system config file.
+/// + `user_config`: The path of the user config file.
+/// + `prefs`: The path of the prefs file.
+/// + `log_dir`: The directory the trace log is written to.
+/// + `stall`: The [`StallLocation`] of the stall, if there is one.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if an IO error occurs during writing.
+///
+/// [`StallLocation`]: ../application/struct.StallLocation.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn paths(
+	system_config: Option<&Path>,
+	user_config: Option<&Path>,
+	prefs: Option<&Path>,
+	log_dir: Option<&Path>,
+	stall: Option<&StallLocation>,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "paths").entered();
+
+	let mut paths = vec![
+		("config dir", config_dir()),
+		("state dir", state_dir()),
+		("cache dir", cache_dir()),
+		("system config", system_config.map(Path::to_path_buf)),
+		("user config", user_config.map(Path::to_path_buf)),
+		("prefs", prefs.map(Path::to_path_buf)),
+		("logs", log_dir.map(Path::to_path_buf)),
+	];
+	if let Some(stall) = stall {
+		let trash_dir = stall.dir.join(common.backup_dir
+			.as_deref()
+			.unwrap_or_else(|| Path::new(Trash::DEFAULT_TRASH_PATH)));
+		paths.extend([
+			("stall file", Some(stall.file.clone())),
+			("stall config", Some(stall.dir.join(Config::DEFAULT_CONFIG_PATH))),
+			("journal", Some(Journal::new(&stall.dir).path().to_path_buf())),
+			("state", Some(StateFile::path(&stall.dir))),
+			("trash", Some(trash_dir)),
+		]);
+	}
+
+	let mut out = std::io::stdout();
+	for (name, path) in paths {
+		let label = format!("{name}:");
+		let label = if common.color.enabled() {
+			label.bright_white().to_string()
+		} else {
+			label
+		};
+		// The padding is measured without the color codes, so that the
+		// paths are aligned.
+		let padding = " ".repeat(15 - name.len());
+		match path {
+			Some(path) => writeln!(&mut out, "{label}{padding}{}",
+				path.display())?,
+			None => writeln!(&mut out, "{label}{padding}(unknown)")?,
+		}
+	}
+	Ok(())
+}
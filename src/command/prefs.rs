@@ -0,0 +1,145 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! User preferences commands.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::application::Prefs;
+use crate::CommonOptions;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+use std::process::Command;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// prefs_edit
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-prefs-edit' command.
+///
+/// This will open the prefs file in the user's editor, creating it from
+/// [`Prefs::TEMPLATE`] if it does not exist. The editor is given by the
+/// `VISUAL` or `EDITOR` environment variables, falling back to `vi`, or
+/// `notepad` on windows. The edited file is checked after the editor exits.
+///
+/// ### Parameters
+///
+/// + `prefs_path`: The path of the prefs file.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the prefs file cannot be created, the editor
+/// fails, or the edited prefs file is invalid.
+///
+/// [`Prefs::TEMPLATE`]: ../application/struct.Prefs.html#associatedconstant.TEMPLATE
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn prefs_edit(prefs_path: &Path, common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "prefs_edit").entered();
+
+	if !prefs_path.exists() {
+		event!(Level::INFO, "Creating prefs file {:?}", prefs_path);
+		if let Some(parent) = prefs_path.parent() {
+			std::fs::create_dir_all(parent)
+				.with_path("failed to create prefs directory", parent)?;
+		}
+		std::fs::write(prefs_path, Prefs::TEMPLATE)
+			.with_path("failed to create prefs file", prefs_path)?;
+	}
+
+	run_editor(prefs_path)?;
+
+	let _ = Prefs::read_from_path(prefs_path)?;
+	if !common.quiet {
+		writeln!(&mut std::io::stdout(), "Prefs saved to {}",
+			prefs_path.display())?;
+	}
+	Ok(())
+}
+
+/// Opens the file at the given path in the editor given by the `VISUAL` or
+/// `EDITOR` environment variables, falling back to `vi`, or `notepad` on
+/// windows, and waits for it to exit.
+pub(in crate::command) fn run_editor(path: &Path) -> Result<(), StallError> {
+	let editor = std::env::var("VISUAL")
+		.or_else(|_| std::env::var("EDITOR"))
+		.ok()
+		.filter(|editor| !editor.trim().is_empty())
+		.unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }
+			.to_owned());
+	event!(Level::DEBUG, "Running editor {:?}", editor);
+
+	// The editor may include arguments, e.g. `code --wait`.
+	let mut args = editor.split_whitespace();
+	let program = args.next().unwrap_or_default();
+	let status = Command::new(program)
+		.args(args)
+		.arg(path)
+		.status()
+		.with_path("failed to run editor", Path::new(program))?;
+	if !status.success() {
+		return Err(StallError::CommandFailed {
+			message: format!("editor {editor:?} failed: {status}"),
+		});
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// prefs_set
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-prefs-set' command.
+///
+/// This will set the given preference in the [`Prefs`]. The caller is
+/// responsible for writing the modified `Prefs`.
+///
+/// ### Parameters
+///
+/// + `prefs`: The loaded `Prefs` data.
+/// + `key`: The preference to set. See [`Prefs::set`] for the supported keys.
+/// + `value`: The value to set.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the key is not recognized, the value is invalid
+/// for the preference, or an IO error occurs during writing.
+///
+/// [`Prefs`]: ../application/struct.Prefs.html
+/// [`Prefs::set`]: ../application/struct.Prefs.html#method.set
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn prefs_set(
+	prefs: &mut Prefs,
+	key: &str,
+	value: &str,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "prefs_set").entered();
+
+	prefs.set(key, value)?;
+
+	if !common.quiet {
+		writeln!(&mut std::io::stdout(), "Set {key} = {value}")?;
+	}
+	Ok(())
+}
@@ -0,0 +1,182 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Remove entries whose remote files no longer exist.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::confirm::Confirm;
+use crate::error::StallError;
+use crate::pick::pick_paths;
+use crate::remote;
+use crate::report::warn;
+use crate::trash::remove_path;
+use crate::trash::Trash;
+use crate::warning::Warning;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::BufRead;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// prune
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-prune' command.
+///
+/// This will list the entries of the [`Stall`] whose remote files no longer
+/// exist, and remove them after asking for confirmation. Remote files which
+/// can't be checked, such as those on unreachable hosts, are not treated as
+/// missing. An entry with several remote files only loses its missing remote
+/// files, and its stalled file is kept while it has any remote files.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the stalled files.
+/// + `stall`: The loaded `Stall` data.
+/// + `input`: The input to read the confirmation from.
+/// + `yes`: Remove the entries without asking for confirmation.
+/// + `pick`: Choose the missing remote files whose entries to remove using an
+///   interactive fuzzy selector, instead of asking for confirmation.
+/// + `delete`: Delete the stalled files of the removed entries.
+/// + `use_trash`: Move deleted files into the stall's [`Trash`] instead of
+///   deleting them permanently.
+/// + `include_archived`: Prune archived entries, which are skipped otherwise.
+/// + `dry_run`: List the entries instead of removing them.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// Stalled files which can't be deleted produce a warning.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if an IO error occurs during writing.
+///
+/// [`Stall`]: ../struct.Stall.html
+/// [`Trash`]: ../trash/struct.Trash.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::fn_params_excessive_bools)]
+pub fn prune(
+	stall_dir: &Path,
+	stall: &mut Stall,
+	input: &mut dyn BufRead,
+	yes: bool,
+	pick: bool,
+	delete: bool,
+	use_trash: bool,
+	include_archived: bool,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "prune").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let mut out = std::io::stdout();
+	let mut missing: Vec<(PathBuf, PathBuf)> = stall.entries()
+		.filter(|entry| include_archived || !entry.flags.archived)
+		.filter(|entry| is_missing(entry.remote))
+		.map(|entry| (entry.local.to_path_buf(), entry.remote.to_path_buf()))
+		.collect();
+	if missing.is_empty() {
+		if !common.quiet {
+			writeln!(&mut out, "No entries with missing remote files.")?;
+		}
+		return Ok(());
+	}
+
+	if dry_run {
+		for (local, remote) in &missing {
+			writeln!(&mut out, "Remove {} ({})",
+				local.display(),
+				remote.display())?;
+		}
+		return Ok(());
+	}
+
+	if pick {
+		let picked = pick_paths(
+			missing.iter().map(|(_, remote)| remote.clone()).collect(),
+			"prune",
+			common)?;
+		missing.retain(|(_, remote)| picked.contains(remote));
+		if missing.is_empty() { return Ok(()); }
+	} else if !yes {
+		writeln!(&mut out, "Entries with missing remote files:")?;
+		for (local, remote) in &missing {
+			writeln!(&mut out, "    {} ({})",
+				local.display(),
+				remote.display())?;
+		}
+		let question = match (delete, use_trash) {
+			(false, _)    => format!("Remove {} entries from the stall?",
+				missing.len()),
+			(true, true)  => format!("Remove {} entries from the stall and \
+				move their stalled files into the trash?", missing.len()),
+			(true, false) => format!("Remove {} entries from the stall and \
+				permanently delete their stalled files?", missing.len()),
+		};
+		if !Confirm::new(input).proceed(&mut out, &question)? {
+			return Ok(());
+		}
+	}
+
+	let mut trash = (delete && use_trash)
+		.then(|| Trash::open(stall_dir, common.backup_dir.as_deref()))
+		.transpose()?;
+	for (local, remote) in &missing {
+		let _ = stall.remove_remote(remote);
+		// Keep the stalled file if the entry has other remotes.
+		if stall.entry_local(local).is_some() { continue; }
+
+		stall.remove_from_groups(local);
+		stall.remove_from_dependencies(local);
+		let path = stall_dir.join(local);
+		if !delete || path.symlink_metadata().is_err() { continue; }
+		let res = trash.as_mut().map_or_else(
+			|| remove_path(&path),
+			|trash| trash.move_in(&path).map(|_| ()));
+		if let Err(e) = res {
+			warn(Warning::RemoveFailed {
+				path,
+				reason: e.to_string(),
+			}, common)?;
+		}
+	}
+
+	if !common.quiet {
+		writeln!(&mut out, "Removed {} entries", missing.len())?;
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helper functions
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns `true` if the remote file is known not to exist. Remote files
+/// which can't be checked are not treated as missing.
+fn is_missing(remote: &Path) -> bool {
+	match remote::backend(remote).and_then(|backend| backend.modified(remote)) {
+		Ok(modified) => modified.is_none(),
+		Err(e) => {
+			event!(Level::DEBUG, "Unable to check {:?}: {}", remote, e);
+			false
+		},
+	}
+}
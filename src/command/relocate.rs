@@ -0,0 +1,275 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Find the new locations of moved remote files.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::command::scan::scan_locations;
+use crate::confirm::Confirm;
+use crate::entry::same_contents;
+use crate::error::StallError;
+use crate::platform;
+use crate::remote;
+use crate::state::StateFile;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::BufRead;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The number of directory levels searched below each scanned location.
+const SEARCH_DEPTH: usize = 4;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// relocate
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-relocate' command.
+///
+/// This will search the scanned config file locations for the new locations of
+/// entries whose remote files no longer exist, and offer to update their
+/// remote paths. A file is a candidate if it is the same file the remote file
+/// was when it was last copied, as recorded by its device and inode numbers,
+/// or if it has the same contents as the stalled file. Files which are already
+/// stalled are never candidates.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the stalled files.
+/// + `stall`: The loaded `Stall` data.
+/// + `files`: The entries to relocate, or all entries if empty. Archived
+///   entries are only relocated if they are selected.
+/// + `input`: The input to read the answers from.
+/// + `scan_paths`: Additional glob patterns of locations to search, as
+///   accepted by [`scan_locations`].
+/// + `yes`: Update entries with a single candidate without asking for
+///   confirmation. Entries with several candidates are skipped.
+/// + `dry_run`: List the candidates instead of updating the entries.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if any of the entries or patterns are invalid, or
+/// if an IO error occurs during writing.
+///
+/// [`scan_locations`]: fn.scan_locations.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+#[allow(clippy::too_many_arguments)]
+pub fn relocate<'p, I>(
+	stall_dir: &Path,
+	stall: &mut Stall,
+	files: I,
+	input: &mut dyn BufRead,
+	scan_paths: &[String],
+	yes: bool,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+	where I: IntoIterator<Item=&'p Path>
+{
+	let _span = span!(Level::INFO, "relocate").entered();
+
+	let mut files = files.into_iter().peekable();
+	let all = files.peek().is_none();
+	let missing: Vec<(PathBuf, PathBuf)> = stall.select(files)?
+		.into_iter()
+		.filter(|entry| !all || !entry.flags.archived)
+		.filter(|entry| remote::scheme(entry.remote).is_none()
+			&& is_missing(entry.remote))
+		.map(|entry| (entry.local.to_path_buf(), entry.remote.to_path_buf()))
+		.collect();
+
+	let mut out = std::io::stdout();
+	if missing.is_empty() {
+		if !common.quiet {
+			writeln!(&mut out, "No entries with missing remote files.")?;
+		}
+		return Ok(());
+	}
+
+	let mut candidates = unstalled_files(stall_dir, stall, scan_paths)?;
+	let mut state = StateFile::read_from_stall_dir(stall_dir);
+	let mut confirm = Confirm::new(input);
+	let mut relocated = 0;
+	for (local, remote) in &missing {
+		let matches = find_matches(
+			&stall_dir.join(local),
+			remote,
+			&candidates,
+			&mut state)?;
+		if matches.is_empty() {
+			if !common.quiet {
+				writeln!(&mut out, "No new location found for {} ({})",
+					local.display(),
+					remote.display())?;
+			}
+			continue;
+		}
+
+		if dry_run {
+			for (path, reason) in &matches {
+				writeln!(&mut out, "Relocate {} from {} to {} ({reason})",
+					local.display(),
+					remote.display(),
+					path.display())?;
+			}
+			continue;
+		}
+
+		if yes && matches.len() > 1 {
+			writeln!(&mut out, "Skipping {}: {} possible new locations found",
+				local.display(),
+				matches.len())?;
+			continue;
+		}
+		let choice = if yes {
+			Some(0)
+		} else {
+			writeln!(&mut out, "{} ({}) no longer exists. Found:",
+				local.display(),
+				remote.display())?;
+			for (i, (path, reason)) in matches.iter().enumerate() {
+				writeln!(&mut out, "    {}. {} ({reason})",
+					i + 1,
+					path.display())?;
+			}
+			if matches.len() == 1 {
+				confirm
+					.proceed(&mut out, "Update the remote path?")?
+					.then_some(0)
+			} else {
+				confirm.choose(&mut out, "Update the remote path to",
+					matches.len())?
+			}
+		};
+		let Some(choice) = choice else { continue };
+
+		let new_remote = &matches[choice].0;
+		let _ = stall.replace_remote(remote, new_remote)?;
+		state.record_file_id(new_remote);
+		candidates.retain(|path| !path.starts_with(new_remote)
+			&& !new_remote.starts_with(path));
+		relocated += 1;
+		if !common.quiet {
+			writeln!(&mut out, "Relocated {} to {}",
+				local.display(),
+				new_remote.display())?;
+		}
+	}
+
+	if !dry_run && !common.readonly {
+		if let Err(e) = state.write_to_stall_dir(stall_dir) {
+			event!(Level::WARN, "{e}");
+		}
+	}
+	if !dry_run && !common.quiet && relocated > 0 {
+		writeln!(&mut out, "Relocated {relocated} entries")?;
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helper functions
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns `true` if the local remote file doesn't exist.
+fn is_missing(remote: &Path) -> bool {
+	matches!(std::fs::symlink_metadata(remote),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// Returns the files and directories in the scanned locations which are not
+/// stalled.
+fn unstalled_files(stall_dir: &Path, stall: &Stall, scan_paths: &[String])
+	-> Result<Vec<PathBuf>, StallError>
+{
+	let mut candidates = Vec::new();
+	for location in scan_locations(scan_paths)? {
+		if location.starts_with(stall_dir) { continue; }
+		find_candidates(&location, SEARCH_DEPTH, &mut candidates);
+	}
+	candidates.retain(|path| !path.starts_with(stall_dir)
+		&& !stall.entries().any(|entry| path.starts_with(entry.remote)
+			|| entry.remote.starts_with(path)));
+	candidates.sort();
+	candidates.dedup();
+	event!(Level::DEBUG, "Found {} candidate files", candidates.len());
+	Ok(candidates)
+}
+
+/// Pushes the path and the paths of the files and directories within it into
+/// `candidates`, searching `depth` levels of directories. Symbolic links are
+/// not followed, and unreadable directories are skipped.
+fn find_candidates(path: &Path, depth: usize, candidates: &mut Vec<PathBuf>) {
+	candidates.push(path.to_path_buf());
+	let is_dir = std::fs::symlink_metadata(path)
+		.is_ok_and(|metadata| metadata.is_dir());
+	if !is_dir || depth == 0 { return; }
+
+	let Ok(read_dir) = std::fs::read_dir(path) else {
+		event!(Level::DEBUG, "Unable to read directory {:?}", path);
+		return;
+	};
+	for entry in read_dir.filter_map(Result::ok) {
+		find_candidates(&entry.path(), depth - 1, candidates);
+	}
+}
+
+/// Returns the candidates which may be the new location of the `remote` file,
+/// along with the reason they match. Candidates which are the same file the
+/// remote file was when it was last copied are returned first, followed by
+/// those with the same contents as the `stalled` file.
+fn find_matches(
+	stalled: &Path,
+	remote: &Path,
+	candidates: &[PathBuf],
+	state: &mut StateFile)
+	-> Result<Vec<(PathBuf, &'static str)>, StallError>
+{
+	let Ok(metadata) = std::fs::symlink_metadata(stalled) else {
+		return Ok(Vec::new());
+	};
+	let same_kind = |path: &Path| std::fs::symlink_metadata(path)
+		.is_ok_and(|m| m.is_dir() == metadata.is_dir()
+			&& m.is_symlink() == metadata.is_symlink());
+
+	let mut matches = Vec::new();
+	if let Some(id) = state.file_id(remote) {
+		matches.extend(candidates
+			.iter()
+			.filter(|path| same_kind(path)
+				&& platform::file_id(path) == Some(id))
+			.map(|path| (path.clone(), "same file")));
+	}
+	for path in candidates {
+		if !same_kind(path) || matches.iter().any(|(p, _)| p == path) {
+			continue;
+		}
+		let same = if metadata.is_file() {
+			state.same_contents(stalled, path)?
+		} else {
+			same_contents(stalled, path)?
+		};
+		if same { matches.push((path.clone(), "same contents")); }
+	}
+	Ok(matches)
+}
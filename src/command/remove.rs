@@ -8,17 +8,23 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
 use crate::CommonOptions;
 use crate::Stall;
+use crate::path;
+use crate::report::warn;
+use crate::trash::Trash;
+use crate::warning::Warning;
 
 // External library imports.
-use anyhow::Error;
 use tracing::event;
 use tracing::Level;
 use tracing::span;
 
 // Standard library imports.
 use std::path::Path;
+use std::path::PathBuf;
 
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -31,38 +37,51 @@ use std::path::Path;
 /// ### Parameters
 ///
 /// + `stall`: The loaded `Stall` data.
-/// + `files`: An iterator over the [`Path`]s of the files to remove.
+/// + `files`: An iterator over the [`Path`]s of the files to remove. Partial
+///   local names and group names prefixed with `@` are also accepted.
+///   Removed entries are removed from their groups.
 /// + `delete_stall_dir`: The stall directory to delete from, or None if no
 /// delete should occur.
 /// + `remote_naming`: Lookup stall entries using the remote name instead of the
 /// local name.
+/// + `use_trash`: Move deleted files into the stall's [`Trash`] instead of
+///   deleting them permanently.
 /// + `dry_run`: Do not modify any files.
 /// + `common`: The [`CommonOptions`] to use for the command.
 ///
 /// ### Errors
 /// 
-/// Returns an [`Error`] if both files exist but their metadata can't be read,
-/// if the copy operation fails, or if any IO errors occur.
+/// Returns a [`StallError`] if both files exist but their metadata can't be read,
+/// if the copy operation fails, if a local name doesn't match exactly one
+/// entry, or if any IO errors occur.
 /// 
 /// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
 /// [`Stall`]: ../struct.Stall.html
+/// [`Trash`]: ../trash/struct.Trash.html
 /// [`CommonOptions`]: ../command/struct.CommonOptions.html
-/// [`Error`]: ../error/struct.Error.html
+/// [`StallError`]: ../error/enum.StallError.html
 /// 
 pub fn remove<'i, I>(
     stall: &mut Stall,
     files: I,
     delete_stall_dir: Option<&Path>,
     remote_naming: bool,
+    use_trash: bool,
     dry_run: bool,
     common: &CommonOptions)
-    -> Result<(), Error>
+    -> Result<(), StallError>
     where I: IntoIterator<Item=&'i Path>
 {
     let _span = span!(Level::INFO, "add").entered();
     if dry_run && common.quiet { return Ok(()); }
 
-    for file in files {
+    // Groups are expanded into the local paths of their members.
+    let files = if remote_naming {
+        files.into_iter().map(Path::to_path_buf).collect()
+    } else {
+        stall.expand_groups(files)?
+    };
+    for file in files.iter().map(PathBuf::as_path) {
         event!(Level::DEBUG, "Remove entry with path: {:?}", file);
 
         if dry_run {
@@ -74,19 +93,35 @@ pub fn remove<'i, I>(
 
         let removed = if remote_naming {
             stall.remove_remote(file)
+                // The entry may have been added with a canonical path.
+                .or_else(|| stall.remove_remote(&path::canonicalize(file)))
+                .map(|(local, _)| local)
+                // Keep the stalled file if the entry has other remotes.
+                .filter(|local| stall.entry_local(local).is_none())
         } else {
-            stall.remove_local(file)
+            // Partial names are matched against the local paths.
+            let local = stall.resolve_local(file)?.to_path_buf();
+            stall.remove_local(&local).map(|(local, _)| local)
         };
 
-        if let (Some((local, _)), Some(stall_dir)) = (removed, delete_stall_dir)
-        {
+        if let Some(local) = &removed {
+            stall.remove_from_groups(local);
+            stall.remove_from_dependencies(local);
+        }
+        if let (Some(local), Some(stall_dir)) = (removed, delete_stall_dir) {
             let path = stall_dir.to_owned().join(local);
-            if let Err(e) = std::fs::remove_file(path) {
-
-                event!(Level::WARN, "{}", e);
-                if common.promote_warnings_to_errors {
-                    return Err(e.into());
-                }
+            let res = if use_trash {
+                Trash::open(stall_dir, common.backup_dir.as_deref())
+                    .and_then(|mut trash| trash.move_in(&path))
+                    .map(|_| ())
+            } else {
+                std::fs::remove_file(&path).with_path("failed to remove", &path)
+            };
+            if let Err(e) = res {
+                warn(Warning::RemoveFailed {
+                    path,
+                    reason: e.to_string(),
+                }, common)?;
             }
         }
     }
@@ -8,55 +8,77 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
 use crate::CommonOptions;
 use crate::Stall;
+use crate::safety;
+use crate::template::NameTemplate;
+use crate::trash::Trash;
 
 // External library imports.
-use anyhow::anyhow;
-use anyhow::Error;
+use glob::Pattern;
+use tracing::event;
 use tracing::Level;
 use tracing::span;
 
 // Standard library imports.
+use std::io::Write as _;
 use std::path::Path;
+use std::path::PathBuf;
 
 ////////////////////////////////////////////////////////////////////////////////
 // rename
 ////////////////////////////////////////////////////////////////////////////////
 /// Executes the 'stall-mv' command.
 ///
-/// This will rename a file in the stall
+/// This will rename a file in the stall. If `from` is a glob pattern, every
+/// matching file will be renamed, and `to` will be interpreted as a
+/// [`NameTemplate`] for generating the new names.
+///
+/// Renaming a file onto an existing stall file fails unless `force` is given,
+/// in which case the existing entry is replaced, or `swap` is given, in which
+/// case the two entries exchange names.
 ///
 /// ### Parameters
 ///
 /// + `stall`: The loaded [`Stall`] data.
-/// + `from`: The current name of the stalled file.
-/// + `to`: The new name of the stalled file.
+/// + `from`: The current name of the stalled file, or a glob pattern.
+/// + `to`: The new name of the stalled file, or a naming template.
 /// + `move_stall_dir`: The stall directory to move the files within, or `None`
-/// if no move should occur.
-/// + `force`: Force overwrite if the new file already exists.
+///   if no move should occur.
+/// + `force`: Replace any existing stall files with the new names. Replaced
+///   files in the stall directory are moved into the stall's [`Trash`].
+/// + `swap`: Exchange the names of two existing stall files.
 /// + `dry_run`: Do not copy any files.
 /// + `common`: The [`CommonOptions`] to use for the command.
 ///
 /// ### Errors
 /// 
-/// Returns an [`Error`] if both files exist but their metadata can't be read,
-/// if the copy operation fails, or if any IO errors occur.
+/// Returns a [`StallError`] if a new name is already in use and neither
+/// `force` nor `swap` is given, if `swap` is given and either file is not in
+/// the stall, if a remote file of a renamed entry is the same file as its new
+/// stalled file, if the files cannot be moved, or if any IO errors occur.
 /// 
 /// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
 /// [`Stall`]: ../struct.Stall.html
+/// [`NameTemplate`]: ../template/struct.NameTemplate.html
+/// [`Trash`]: ../trash/struct.Trash.html
 /// [`CommonOptions`]: ../command/struct.CommonOptions.html
-/// [`Error`]: ../error/struct.Error.html
+/// [`StallError`]: ../error/enum.StallError.html
 /// 
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::fn_params_excessive_bools)]
 pub fn rename(
     stall: &mut Stall,
     from: &Path,
     to: &Path,
     move_stall_dir: Option<&Path>,
     force: bool,
+    swap: bool,
     dry_run: bool,
     common: &CommonOptions)
-    -> Result<(), Error>
+    -> Result<(), StallError>
 {
     let _span = span!(Level::INFO, "rename").entered();
     if dry_run && common.quiet { return Ok(()); }
@@ -70,34 +92,239 @@ pub fn rename(
         return Ok(());
     }
 
-    if stall.entry_local(to).is_some() {
-        // This move will overwrite an existing file.
+    if swap {
+        return swap_entries(stall, from, to, move_stall_dir, dry_run);
+    }
+
+    let renames = rename_pairs(stall, from, to)?;
+
+    let mut replaced = Vec::new();
+    for (i, (old, new)) in renames.iter().enumerate() {
+        check_renamed(stall, move_stall_dir, old, new)?;
+        if renames[..i].iter().any(|(_, n)| n == new) {
+            return Err(StallError::Conflict {
+                reason: "multiple stall files would be renamed to".to_owned(),
+                subject: new.display().to_string(),
+                hint: None,
+            });
+        }
+        // Entries which are themselves being renamed are not replaced.
+        if old == new
+            || stall.entry_local(new).is_none()
+            || renames.iter().any(|(o, _)| o == new)
+        {
+            continue;
+        }
         if !force {
-            return Err(anyhow!("stall file already exists: {:?}\nUse --force \
-                option to overwrite it.", to))
+            // This move will overwrite an existing file.
+            return Err(StallError::Conflict {
+                reason: "stall file already exists".to_owned(),
+                subject: new.display().to_string(),
+                hint: Some("Use --force option to replace it, or --swap to \
+                    exchange the names of the files.".to_owned()),
+            });
         }
+        replaced.push(new.clone());
     }
 
-    if !dry_run {
-        let (_, r) = stall
-            .remove_local(from)
-            .ok_or_else(|| anyhow!("no stall file found: {:?}",
-                from.display()))?;
-        stall.insert(to.to_path_buf(), r);
+    let mut out = std::io::stdout();
+    if dry_run {
+        for new in &replaced {
+            writeln!(&mut out, "Replace stall entry {}", new.display())?;
+        }
+        for (old, new) in &renames {
+            writeln!(&mut out, "Rename stall entry {} to {}",
+                old.display(),
+                new.display())?;
+        }
+        return Ok(());
+    }
 
+    // Replaced entries are removed first, and every renamed entry is detached
+    // before any are reinserted, so that entries may be renamed onto the old
+    // names of other renamed entries.
+    for new in &replaced {
+        let _ = stall.remove_local(new);
+        stall.remove_from_groups(new);
+        stall.remove_from_dependencies(new);
         if let Some(stall_dir) = move_stall_dir {
-            let old = stall_dir.join(from);
-            let new = stall_dir.join(to);
-            let status = std::process::Command::new("mv")
-                .args([old, new])
-                .arg("-f")
-                .status()?;
-
-            if !status.success() && !common.quiet {
-                println!("Failed to move files.");
+            let path = stall_dir.join(new);
+            if path.exists() {
+                let _ = Trash::open(stall_dir, common.backup_dir.as_deref())?
+                    .move_in(&path)?;
             }
         }
     }
+    let mut detached = Vec::with_capacity(renames.len());
+    for (old, new) in &renames {
+        let flags = stall.flags(old).unwrap_or_default();
+        let after: Vec<PathBuf> = stall.dependencies(old).unwrap_or_default()
+            .iter().map(|dependency| dependency.to_path_buf()).collect();
+        let (_, remotes) = stall
+            .remove_local(old)
+            .ok_or_else(|| StallError::MissingEntry {
+                path: old.clone(),
+                suggestions: Vec::new(),
+            })?;
+        detached.push((new, remotes, flags, after));
+    }
+    for (new, remotes, flags, after) in detached {
+        for remote in remotes {
+            let _ = stall.insert_remote(new.clone(), &remote)?;
+        }
+        let _ = stall.set_flags(new, flags);
+        let _ = stall.set_dependencies(new, after);
+    }
+    stall.rename_in_dependencies(&renames);
+    // Group memberships follow the renamed entries.
+    let members: Vec<_> = stall.groups()
+        .map(|(name, members)| (name.to_owned(), renames
+            .iter()
+            .filter(|(old, _)| members.contains(old.as_path()))
+            .map(|(_, new)| new.clone())
+            .collect::<Vec<_>>()))
+        .collect();
+    for (old, _) in &renames { stall.remove_from_groups(old); }
+    for (name, news) in members {
+        for new in news { let _ = stall.insert_group_member(&name, &new); }
+    }
+
+    if let Some(stall_dir) = move_stall_dir {
+        move_files(stall_dir, &renames)?;
+    }
+
+    Ok(())
+}
+
+
+/// Exchanges the names of two stall entries, and their files in the stall
+/// directory if one is given.
+fn swap_entries(
+    stall: &mut Stall,
+    a: &Path,
+    b: &Path,
+    move_stall_dir: Option<&Path>,
+    dry_run: bool)
+    -> Result<(), StallError>
+{
+    for local in [a, b] {
+        if stall.entry_local(local).is_none() {
+            return Err(stall.missing_entry(local));
+        }
+    }
+    check_renamed(stall, move_stall_dir, a, b)?;
+    check_renamed(stall, move_stall_dir, b, a)?;
+
+    if dry_run {
+        writeln!(&mut std::io::stdout(), "Swap stall entries {} and {}",
+            a.display(),
+            b.display())?;
+        return Ok(());
+    }
+
+    let _ = stall.swap_locals(a, b);
+    if let Some(stall_dir) = move_stall_dir {
+        move_files(stall_dir, &[
+            (a.to_path_buf(), b.to_path_buf()),
+            (b.to_path_buf(), a.to_path_buf()),
+        ])?;
+    }
+    Ok(())
+}
+
+
+/// Checks that the remote files of the entry being renamed from `old` to
+/// `new` are not the stalled file with the new name.
+fn check_renamed(
+    stall: &Stall,
+    move_stall_dir: Option<&Path>,
+    old: &Path,
+    new: &Path)
+    -> Result<(), StallError>
+{
+    let Some(stall_dir) = move_stall_dir.or_else(|| stall.load_dir()) else {
+        return Ok(());
+    };
+    for entry in stall.entries_local(old) {
+        safety::check_remote(stall_dir, new, entry.remote)?;
+    }
+    Ok(())
+}
+
+
+/// Moves the files of renamed entries within the stall directory. Each file is
+/// first moved to a temporary name, so that files may be moved onto the old
+/// names of other moved files.
+fn move_files(stall_dir: &Path, renames: &[(PathBuf, PathBuf)])
+    -> Result<(), StallError>
+{
+    let mut staged = Vec::with_capacity(renames.len());
+    for (i, (old, new)) in renames.iter().enumerate() {
+        let source = stall_dir.join(old);
+        if !source.exists() {
+            event!(Level::DEBUG, "No stalled file to move: {:?}", source);
+            continue;
+        }
+        let temp = stall_dir.join(format!(".stall-mv-{}-{i}",
+            std::process::id()));
+        std::fs::rename(&source, &temp)
+            .with_path("failed to move stalled file", &source)?;
+        staged.push((temp, stall_dir.join(new)));
+    }
 
+    for (temp, target) in staged {
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_path("failed to create directory", parent)?;
+        }
+        std::fs::rename(&temp, &target)
+            .with_path("failed to move stalled file", &target)?;
+    }
     Ok(())
 }
+
+
+/// Returns the local paths to rename, paired with their new names.
+fn rename_pairs(stall: &Stall, from: &Path, to: &Path)
+    -> Result<Vec<(PathBuf, PathBuf)>, StallError>
+{
+    let from_str = from.to_string_lossy();
+    if Pattern::escape(&from_str) == from_str {
+        // Not a glob pattern; rename a single file.
+        return Ok(vec![(from.to_path_buf(), to.to_path_buf())]);
+    }
+
+    let pattern = Pattern::new(&from_str)
+        .map_err(|e| StallError::InvalidPattern {
+            pattern: from_str.to_string(),
+            message: e.to_string(),
+        })?;
+    let template: NameTemplate = to.to_string_lossy().parse()?;
+
+    let matched: Vec<PathBuf> = stall.entries()
+        .filter(|e| pattern.matches_path(e.local))
+        .map(|e| e.local.to_path_buf())
+        .collect();
+    if matched.is_empty() {
+        return Err(StallError::NotFound {
+            kind: "stall files matching pattern",
+            name: from.display().to_string(),
+            suggestions: Vec::new(),
+        });
+    }
+    if matched.len() > 1 && template.is_constant() {
+        return Err(StallError::InvalidInput {
+            message: format!("multiple stall files match pattern {}, but the \
+                new name contains no template tokens", from.display()),
+        });
+    }
+
+    Ok(matched
+        .into_iter()
+        .enumerate()
+        .map(|(n, old)| {
+            let new = template.render(&old, n + 1);
+            (old, new)
+        })
+        .collect())
+}
@@ -0,0 +1,185 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Scan for config files which are not in the stall.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::platform;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use colored::Colorize as _;
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The glob patterns for the common config file locations checked by
+/// [`scan_unstalled`]. A leading `$XDG_CONFIG_HOME` is replaced with the
+/// user's config directory.
+///
+/// [`scan_unstalled`]: fn.scan_unstalled.html
+pub const DEFAULT_SCAN_PATHS: [&str; 11] = [
+	"$XDG_CONFIG_HOME/*",
+	"~/.bashrc",
+	"~/.bash_profile",
+	"~/.profile",
+	"~/.zshrc",
+	"~/.zprofile",
+	"~/.gitconfig",
+	"~/.vimrc",
+	"~/.tmux.conf",
+	"~/.inputrc",
+	"~/.ssh/config",
+];
+
+
+////////////////////////////////////////////////////////////////////////////////
+// scan_unstalled
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-status --remote-missing' command.
+///
+/// This will check the common config file locations given by
+/// [`DEFAULT_SCAN_PATHS`], along with any additional glob patterns, for files
+/// which are not stalled, and print an `add` command for each of them. Files
+/// which contain or are contained by a stalled remote file are considered to
+/// be stalled.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory, which is never suggested.
+/// + `stall`: The loaded [`Stall`] data.
+/// + `scan_paths`: Additional glob patterns of files to check. Relative
+///   patterns are relative to the user's home directory.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if any of the patterns are invalid or if an IO
+/// error occurs during writing.
+///
+/// [`DEFAULT_SCAN_PATHS`]: constant.DEFAULT_SCAN_PATHS.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn scan_unstalled(
+	stall_dir: &Path,
+	stall: &Stall,
+	scan_paths: &[String],
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "scan_unstalled").entered();
+
+	let unstalled: Vec<PathBuf> = scan_locations(scan_paths)?
+		.into_iter()
+		.filter(|path| !path.starts_with(stall_dir)
+			&& !stall_dir.starts_with(path)
+			&& !stall.entries().any(|entry| path.starts_with(entry.remote)
+				|| entry.remote.starts_with(path)))
+		.collect();
+
+	if common.quiet { return Ok(()); }
+	let mut out = std::io::stdout();
+	if unstalled.is_empty() {
+		writeln!(&mut out, "No unstalled config files found.")?;
+		return Ok(());
+	}
+
+	let header = format!("Found {} config files which are not in the stall:",
+		unstalled.len());
+	if common.color.enabled() {
+		writeln!(&mut out, "{}", header.bright_white())?;
+	} else {
+		writeln!(&mut out, "{header}")?;
+	}
+	for path in &unstalled {
+		let path = path.display().to_string();
+		if path.contains(char::is_whitespace) {
+			writeln!(&mut out, "    stall add {path:?}")?;
+		} else {
+			writeln!(&mut out, "    stall add {path}")?;
+		}
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// scan_locations
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the existing paths matching the common config file locations given
+/// by [`DEFAULT_SCAN_PATHS`] or any of the additional glob patterns, sorted and
+/// without duplicates.
+///
+/// ### Parameters
+///
+/// + `scan_paths`: Additional glob patterns of files to check. Relative
+///   patterns are relative to the user's home directory.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if any of the patterns are invalid.
+///
+/// [`DEFAULT_SCAN_PATHS`]: constant.DEFAULT_SCAN_PATHS.html
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn scan_locations(scan_paths: &[String])
+	-> Result<Vec<PathBuf>, StallError>
+{
+	let home = platform::home_dir().unwrap_or_default();
+	let mut candidates = Vec::new();
+	for pattern in DEFAULT_SCAN_PATHS
+		.iter()
+		.copied()
+		.chain(scan_paths.iter().map(String::as_str))
+	{
+		let expanded = expand_pattern(pattern, &home);
+		event!(Level::DEBUG, "Scanning for {:?}", expanded);
+		let paths = glob::glob(&expanded.to_string_lossy())
+			.map_err(|e| StallError::InvalidPattern {
+				pattern: pattern.to_owned(),
+				message: e.to_string(),
+			})?;
+		// Unreadable paths can't be stalled, so they are skipped.
+		candidates.extend(paths.filter_map(Result::ok));
+	}
+	candidates.sort();
+	candidates.dedup();
+	Ok(candidates)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helper functions
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns the given scan pattern with a leading `~` or `$XDG_CONFIG_HOME`
+/// expanded, and relative patterns made relative to the home directory.
+fn expand_pattern(pattern: &str, home: &Path) -> PathBuf {
+	if let Some(rest) = pattern.strip_prefix("$XDG_CONFIG_HOME") {
+		let config_home = std::env::var_os("XDG_CONFIG_HOME")
+			.filter(|dir| !dir.is_empty())
+			.map_or_else(|| home.join(".config"), PathBuf::from);
+		let rest = rest.trim_start_matches(['/', '\\']);
+		return config_home.join(rest);
+	}
+	let rest = pattern
+		.strip_prefix('~')
+		.map_or(pattern, |rest| rest.trim_start_matches(['/', '\\']));
+	home.join(rest)
+}
@@ -0,0 +1,226 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Scheduled command management commands.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::cancel::Timeout;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::path::canonicalize;
+use crate::schedule::installed;
+use crate::schedule::uninstall;
+use crate::schedule::Interval;
+use crate::schedule::Schedule;
+use crate::schedule::ScheduleBackend;
+use crate::schedule::ScheduledCommand;
+use crate::CommonOptions;
+
+// External library imports.
+use colored::Colorize as _;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// schedule_install
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-schedule-install' command.
+///
+/// This will install a [`Schedule`] which periodically runs a stall command
+/// for the stall, replacing any existing schedule of the same command.
+///
+/// ### Parameters
+///
+/// + `stall_file`: The path of the stall file.
+/// + `command`: The [`ScheduledCommand`] to run.
+/// + `interval`: The [`Interval`] between runs.
+/// + `timeout`: The [`Timeout`] of each run, if any.
+/// + `backend`: The [`ScheduleBackend`] to install the schedule with. If
+///   `None`, the backend is detected.
+/// + `dry_run`: Print the files to install instead of installing them.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the interval can't be used with the backend,
+/// if the backend's command fails, or if an IO error occurs during writing.
+///
+/// [`Schedule`]: ../schedule/struct.Schedule.html
+/// [`ScheduledCommand`]: ../schedule/enum.ScheduledCommand.html
+/// [`Interval`]: ../schedule/struct.Interval.html
+/// [`Timeout`]: ../cancel/struct.Timeout.html
+/// [`ScheduleBackend`]: ../schedule/enum.ScheduleBackend.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn schedule_install(
+	stall_file: &Path,
+	command: ScheduledCommand,
+	interval: Interval,
+	timeout: Option<Timeout>,
+	backend: Option<ScheduleBackend>,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "schedule_install").entered();
+	let backend = backend.unwrap_or_else(ScheduleBackend::detect);
+	let schedule = Schedule {
+		command,
+		interval,
+		timeout,
+		stall: canonicalize(stall_file),
+	};
+	let exe = std::env::current_exe()
+		.with_path("failed to locate", Path::new("stall"))?;
+
+	let mut out = std::io::stdout();
+	if dry_run {
+		if common.quiet { return Ok(()); }
+		for (path, contents) in schedule.render(backend, &exe)? {
+			if path.as_os_str().is_empty() {
+				writeln!(&mut out, "Add to crontab:")?;
+			} else {
+				writeln!(&mut out, "Write {}:", path.display())?;
+			}
+			writeln!(&mut out, "{contents}")?;
+		}
+		return Ok(());
+	}
+
+	let files = schedule.install(backend, &exe)?;
+	if !common.quiet {
+		writeln!(&mut out, "Scheduled {} every {} using {} ({})",
+			command,
+			interval,
+			backend,
+			schedule.name())?;
+		for path in files {
+			writeln!(&mut out, "    {}", path.display())?;
+		}
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// schedule_remove
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-schedule-remove' command.
+///
+/// This will remove the installed schedules of the stall.
+///
+/// ### Parameters
+///
+/// + `stall_file`: The path of the stall file.
+/// + `command`: The [`ScheduledCommand`] whose schedule to remove. If `None`,
+///   the schedules of every command are removed.
+/// + `backend`: The [`ScheduleBackend`] the schedules are installed with. If
+///   `None`, the backend is detected.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the backend's command fails, or if an IO error
+/// occurs during writing.
+///
+/// [`ScheduledCommand`]: ../schedule/enum.ScheduledCommand.html
+/// [`ScheduleBackend`]: ../schedule/enum.ScheduleBackend.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn schedule_remove(
+	stall_file: &Path,
+	command: Option<ScheduledCommand>,
+	backend: Option<ScheduleBackend>,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "schedule_remove").entered();
+	let backend = backend.unwrap_or_else(ScheduleBackend::detect);
+	let stall_file = canonicalize(stall_file);
+	let commands = command.map_or_else(
+		|| vec![ScheduledCommand::Collect, ScheduledCommand::Distribute],
+		|command| vec![command]);
+
+	let mut removed = 0;
+	let mut out = std::io::stdout();
+	for command in commands {
+		if uninstall(backend, &stall_file, command)? {
+			removed += 1;
+			if !common.quiet {
+				writeln!(&mut out, "Removed {command} schedule.")?;
+			}
+		}
+	}
+	if removed == 0 && !common.quiet {
+		writeln!(&mut out, "No schedules installed for this stall.")?;
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// schedule_status
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-schedule-status' command.
+///
+/// This will print the installed schedules of every stall.
+///
+/// ### Parameters
+///
+/// + `backend`: The [`ScheduleBackend`] the schedules are installed with. If
+///   `None`, the backend is detected.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the installed schedules can't be read, or if
+/// an IO error occurs during writing.
+///
+/// [`ScheduleBackend`]: ../schedule/enum.ScheduleBackend.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn schedule_status(
+	backend: Option<ScheduleBackend>,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "schedule_status").entered();
+	let backend = backend.unwrap_or_else(ScheduleBackend::detect);
+	let schedules = installed(backend)?;
+
+	let mut out = std::io::stdout();
+	if schedules.is_empty() {
+		writeln!(&mut out, "No schedules installed using {backend}.")?;
+		return Ok(());
+	}
+	for schedule in schedules {
+		let name = schedule.name();
+		let name = if common.color.enabled() {
+			name.bright_white().to_string()
+		} else {
+			name
+		};
+		writeln!(&mut out, "{}: {} every {} for {}{}",
+			name,
+			schedule.command,
+			schedule.interval,
+			schedule.stall.display(),
+			schedule.timeout
+				.map(|timeout| format!(" (timeout {timeout})"))
+				.unwrap_or_default())?;
+	}
+	Ok(())
+}
+
@@ -0,0 +1,155 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Print the details of a stall entry.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::command::status::format_time;
+use crate::entry::Entry;
+use crate::error::StallError;
+use crate::report::write_contents;
+use crate::state::StateFile;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use colored::Colorize as _;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// show
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-show' command.
+///
+/// This will print the paths, state, flags, transforms, and copy times of the
+/// entry with the given local path, once for each of its remotes, followed by
+/// the contents of its files if requested.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the local files.
+/// + `stall`: The loaded `Stall` data.
+/// + `file`: The [`Path`] of the entry to show. Entry indices and partial
+///   names are also accepted.
+/// + `cat`: The [`ShowTarget`] files whose contents to print, if any.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the entry is not in the stall, if a file to
+/// print can't be read, or if an IO error occurs during writing.
+///
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`ShowTarget`]: ../command/enum.ShowTarget.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn show(
+	stall_dir: &Path,
+	stall: &Stall,
+	file: &Path,
+	cat: Option<ShowTarget>,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "show").entered();
+
+	let entries = stall.entries_selected(file)?;
+	let mut state = StateFile::read_from_stall_dir(stall_dir);
+	let mut out = std::io::stdout();
+
+	for (i, entry) in entries.iter().enumerate() {
+		if i > 0 { writeln!(&mut out)?; }
+		write_details(&mut out, stall_dir, entry, &mut state, common)?;
+	}
+
+	match cat {
+		Some(ShowTarget::Local) => if let Some(entry) = entries.first() {
+			writeln!(&mut out)?;
+			write_contents(&mut out, &stall_dir.join(entry.local))?;
+		},
+		Some(ShowTarget::Remote) => for entry in &entries {
+			writeln!(&mut out)?;
+			write_contents(&mut out, entry.remote)?;
+		},
+		None => (),
+	}
+
+	Ok(())
+}
+
+
+/// Writes the details of an entry into `out`.
+fn write_details(
+	out: &mut dyn Write,
+	stall_dir: &Path,
+	entry: &Entry<'_>,
+	state: &mut StateFile,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let (status_l, status_r) = entry.status_with_state(stall_dir, state);
+	let full_local = stall_dir.join(entry.local);
+
+	let header = entry.local.display().to_string();
+	if common.color.enabled() {
+		writeln!(out, "{}", header.bright_white().bold())?;
+	} else {
+		writeln!(out, "{header}")?;
+	}
+	writeln!(out, "    local:       {}", full_local.display())?;
+	writeln!(out, "    remote:      {}", entry.remote.display())?;
+	if common.color.enabled() {
+		writeln!(out, "    state:       {} {}",
+			status_l.label().color(status_l.color()),
+			status_r.label().color(status_r.color()))?;
+	} else {
+		writeln!(out, "    state:       {} {}",
+			status_l.label(),
+			status_r.label())?;
+	}
+	writeln!(out, "    flags:       {}", entry.flags.description())?;
+	if !entry.transforms.is_empty() {
+		let transforms: Vec<String> = entry.transforms
+			.iter()
+			.map(ToString::to_string)
+			.collect();
+		writeln!(out, "    transforms:  {}", transforms.join("; "))?;
+	}
+	writeln!(out, "    modified:    {}",
+		format_time(entry.modified(stall_dir)))?;
+	writeln!(out, "    collected:   {}",
+		format_time(state.collected(entry.local)))?;
+	writeln!(out, "    distributed: {}",
+		format_time(state.distributed(entry.local)))?;
+	Ok(())
+}
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ShowTarget
+////////////////////////////////////////////////////////////////////////////////
+/// The files to print with the `show` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[derive(clap::ArgEnum)]
+pub enum ShowTarget {
+	/// Print the file in the stall directory.
+	Local,
+	/// Print the files at their remote locations.
+	Remote,
+}
@@ -0,0 +1,211 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Split a stall into sub-stalls.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::application::Config;
+use crate::entry::EntryFlags;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// split
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-split' command.
+///
+/// This will partition the entries of the stall into groups using the given
+/// [`SplitBy`] method, and move each group into a new stall in a subdirectory
+/// of the stall directory named after the group. The stalled files are moved
+/// into the new stall directories, and the entries are removed from the
+/// stall.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the stalled files.
+/// + `stall`: The loaded [`Stall`] data.
+/// + `by`: The [`SplitBy`] method for grouping the entries.
+/// + `dry_run`: Do not modify any files.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if a stall file already exists in one of the new
+/// stall directories, or if an IO error occurs while moving files.
+///
+/// [`Stall`]: ../struct.Stall.html
+/// [`SplitBy`]: ../command/enum.SplitBy.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn split(
+	stall_dir: &Path,
+	stall: &mut Stall,
+	by: SplitBy,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "split").entered();
+
+	let mut entries: BTreeMap<PathBuf, Group> = BTreeMap::new();
+	for entry in stall.entries() {
+		entries
+			.entry(entry.local.to_path_buf())
+			.or_insert_with(|| Group { remotes: Vec::new(), flags: entry.flags })
+			.remotes
+			.push(entry.remote.to_path_buf());
+	}
+
+	// Entries with several remotes are grouped by their first remote.
+	let mut groups: BTreeMap<String, BTreeMap<PathBuf, Group>> =
+		BTreeMap::new();
+	for (local, group) in entries {
+		let name = by.group(&group.remotes[0]);
+		let _ = groups.entry(name).or_default().insert(local, group);
+	}
+
+	let file_name = stall
+		.load_path()
+		.and_then(Path::file_name)
+		.unwrap_or_else(|| OsStr::new(Config::DEFAULT_STALL_PATH))
+		.to_owned();
+
+	// Check every new stall before moving anything.
+	for name in groups.keys() {
+		let dir = stall_dir.join(name);
+		if dir.exists() && !dir.is_dir() {
+			return Err(StallError::InvalidPath {
+				path: dir,
+				reason: "stall directory is an existing file".to_owned(),
+			});
+		}
+		let path = stall_dir.join(name).join(&file_name);
+		if path.exists() {
+			return Err(StallError::Conflict {
+				reason: "stall file already exists".to_owned(),
+				subject: path.display().to_string(),
+				hint: None,
+			});
+		}
+	}
+
+	let mut out = std::io::stdout();
+	for (name, entries) in groups {
+		let sub_dir = stall_dir.join(&name);
+		let sub_path = sub_dir.join(&file_name);
+		event!(Level::DEBUG, "Split {} entries into {:?}",
+			entries.len(), sub_path);
+
+		if dry_run {
+			writeln!(&mut out, "Create stall file at {}", sub_path.display())?;
+			for local in entries.keys() {
+				writeln!(&mut out, "Move stall entry {} to {}",
+					local.display(),
+					Path::new(&name).join(local).display())?;
+			}
+			continue;
+		}
+
+		std::fs::create_dir_all(&sub_dir)
+			.with_path("failed to create stall directory", &sub_dir)?;
+		let mut sub_stall = Stall::new(&sub_path);
+		sub_stall.set_rename_policy(stall.rename_policy());
+		sub_stall.set_durable(common.durable);
+		for (local, group) in &entries {
+			for remote in &group.remotes {
+				let _ = sub_stall.insert_remote(local.clone(), remote)?;
+			}
+			let _ = sub_stall.set_flags(local, group.flags);
+			// Dependencies are kept between entries moved into the same stall.
+			let after = stall.dependencies(local)
+				.unwrap_or_default()
+				.iter()
+				.filter(|dependency| entries.contains_key(dependency.as_ref()))
+				.map(|dependency| dependency.to_path_buf())
+				.collect();
+			let _ = sub_stall.set_dependencies(local, after);
+		}
+		let _ = sub_stall.write_to_load_path_if_new()?;
+
+		for local in entries.keys() {
+			let source = stall_dir.join(local);
+			if source.exists() {
+				let target = sub_dir.join(local);
+				std::fs::rename(&source, &target)
+					.with_path("failed to move stalled file", &source)?;
+			}
+			let _ = stall.remove_local(local);
+			stall.remove_from_groups(local);
+			stall.remove_from_dependencies(local);
+		}
+
+		if !common.quiet {
+			writeln!(&mut out, "Created stall file at {} with {} entries",
+				sub_path.display(),
+				entries.len())?;
+		}
+	}
+	Ok(())
+}
+
+
+/// The remote paths and flags of an entry moved into a sub-stall.
+#[derive(Debug)]
+struct Group {
+	/// The remote paths of the entry.
+	remotes: Vec<PathBuf>,
+	/// The flags of the entry.
+	flags: EntryFlags,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// SplitBy
+////////////////////////////////////////////////////////////////////////////////
+/// The method for grouping entries when splitting a stall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(clap::ArgEnum)]
+pub enum SplitBy {
+	/// Group entries by the directory containing their remote path, so
+	/// `~/.config/nvim/init.vim` is moved into the `nvim` sub-stall. Leading
+	/// `.`s are removed from the directory name.
+	RemotePrefix,
+}
+
+impl SplitBy {
+	/// Returns the name of the group for the entry with the given remote
+	/// path.
+	#[must_use]
+	pub fn group(self, remote: &Path) -> String {
+		match self {
+			Self::RemotePrefix => remote
+				.parent()
+				.and_then(Path::file_name)
+				.map(|name| name.to_string_lossy()
+					.trim_start_matches('.')
+					.to_owned())
+				.filter(|name| !name.is_empty())
+				.unwrap_or_else(|| "root".to_owned()),
+		}
+	}
+}
@@ -0,0 +1,191 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Named stall management commands.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::suggest;
+use crate::application::Prefs;
+use crate::CommonOptions;
+use crate::report::Cell;
+use crate::report::Table;
+use crate::report::warn;
+use crate::warning::Warning;
+
+// External library imports.
+use colored::Color;
+use tracing::event;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// stalls_list
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-stalls-list' command.
+///
+/// This will print the name and path of each stall registered in the
+/// [`Prefs`].
+///
+/// ### Parameters
+///
+/// + `prefs`: The loaded `Prefs` data.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if an IO error occurs during writing.
+/// 
+/// [`Prefs`]: ../application/struct.Prefs.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn stalls_list(prefs: &Prefs, common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "stalls_list").entered();
+
+	let mut out = std::io::stdout();
+	let mut table = Table::new(["NAME", "PATH"]);
+	for (name, path) in prefs.named_stalls() {
+		let path_cell = Cell::new(path.display().to_string());
+		table.push_row(vec![
+			Cell::new(name).with_color(Color::BrightWhite),
+			if path.exists() {
+				path_cell
+			} else {
+				path_cell.with_color(Color::BrightRed)
+			},
+		]);
+	}
+
+	if table.is_empty() {
+		if !common.quiet { writeln!(&mut out, "No named stalls.")?; }
+		return Ok(());
+	}
+	table.write(&mut out, common)?;
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// stalls_add
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-stalls-add' command.
+///
+/// This will register a stall path with the given name in the [`Prefs`].
+///
+/// ### Parameters
+///
+/// + `prefs`: The loaded `Prefs` data.
+/// + `name`: The name to register the stall as.
+/// + `path`: The path of the stall file or directory. Relative paths are
+///   resolved against `cur_dir`.
+/// + `cur_dir`: The current directory.
+/// + `force`: Replace any stall already registered with the given name.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if the name is invalid or already registered.
+/// 
+/// [`Prefs`]: ../application/struct.Prefs.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn stalls_add(
+	prefs: &mut Prefs,
+	name: &str,
+	path: &Path,
+	cur_dir: &Path,
+	force: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "stalls_add").entered();
+
+	if name.is_empty() || name.contains(['/', '\\']) {
+		return Err(StallError::InvalidInput {
+			message: format!("invalid stall name: {name:?}"),
+		});
+	}
+	if !force && prefs.named_stall(name).is_some() {
+		return Err(StallError::Conflict {
+			reason: "stall name already registered".to_owned(),
+			subject: name.to_owned(),
+			hint: Some("Use --force option to replace it.".to_owned()),
+		});
+	}
+
+	let path = std::fs::canonicalize(cur_dir.join(path))
+		.unwrap_or_else(|_| cur_dir.join(path));
+	if !path.exists() {
+		warn(Warning::MissingStall { path: path.clone() }, common)?;
+	}
+
+	if let Some(old) = prefs.insert_named_stall(name, &path) {
+		event!(Level::INFO, "Replaced stall {:?} path {:?}", name, old);
+	}
+
+	if !common.quiet {
+		writeln!(&mut std::io::stdout(), "Registered stall {} at {}",
+			name,
+			path.display())?;
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// stalls_remove
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-stalls-rm' command.
+///
+/// This will unregister the stall with the given name from the [`Prefs`]. The
+/// stall itself is not modified.
+///
+/// ### Parameters
+///
+/// + `prefs`: The loaded `Prefs` data.
+/// + `name`: The name of the stall to unregister.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if the name is not registered.
+/// 
+/// [`Prefs`]: ../application/struct.Prefs.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn stalls_remove(prefs: &mut Prefs, name: &str, common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "stalls_remove").entered();
+
+	let suggestions = suggest::similar(
+		name,
+		prefs.named_stalls().map(|(name, _)| name));
+	let path = prefs.remove_named_stall(name)
+		.ok_or_else(|| StallError::NotFound {
+			kind: "stall name",
+			name: name.to_owned(),
+			suggestions,
+		})?;
+
+	if !common.quiet {
+		writeln!(&mut std::io::stdout(), "Unregistered stall {} at {}",
+			name,
+			path.display())?;
+	}
+	Ok(())
+}
@@ -8,81 +8,513 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Internal library imports.
+use crate::error::StallError;
 use crate::CommonOptions;
 use crate::Stall;
-use crate::entry::Entry;
+use crate::entry::EntryFlags;
+use crate::entry::EntryOrder;
+use crate::entry::Status;
+use crate::report::Cell;
+use crate::report::Table;
+use crate::state::StateFile;
 
 // External library imports.
-use anyhow::Error;
+use chrono::DateTime;
+use chrono::Local;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::event;
 use tracing::span;
 use tracing::Level;
+use colored::Color;
 use colored::Colorize as _;
 
 // Standard library imports.
 use std::path::Path;
-use std::io::Write as _;
+use std::path::PathBuf;
+use std::io::Write;
+use std::time::SystemTime;
 
 
 ////////////////////////////////////////////////////////////////////////////////
-// collect
+// status
 ////////////////////////////////////////////////////////////////////////////////
-/// Executes the 'stall-distribute' command.
+/// Executes the 'stall-status' command.
 ///
-/// This will iterate over each entry in the [`Stall`], print the comparative
-/// status of each file.
+/// This will determine the comparative status of the files of each entry in
+/// the [`Stall`], and return them in a [`StatusReport`], which can be printed
+/// with [`StatusReport::write`].
 ///
 /// ### Parameters
 ///
 /// + `stall_dir`: The stall directory to distribute from.
 /// + `stall`: The loaded `Stall` data.
-/// + `common`: The [`CommonOptions`] to use for the command.
+/// + `patterns`: An iterator over glob patterns to match against the local
+///   paths of entries, or group names prefixed with `@`. If empty, all
+///   entries will match.
+/// + `filter`: A [`StateFilter`] for selecting entries by state.
+/// + `order`: The [`EntryOrder`] of the entries in the report.
+/// + `use_cache`: Use and update the file hashes cached in the stall's
+///   [`StateFile`].
 ///
 /// ### Errors
 /// 
-/// Returns an [`Error`] if an IO errors during writing occur.
+/// Returns a [`StallError`] if any of the patterns are invalid.
 /// 
 /// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
 /// [`Stall`]: ../struct.Stall.html
-/// [`CommonOptions`]: ../command/struct.CommonOptions.html
-/// [`Error`]: ../error/struct.Error.html
+/// [`StatusReport`]: ../command/struct.StatusReport.html
+/// [`StatusReport::write`]: ../command/struct.StatusReport.html#method.write
+/// [`StateFilter`]: ../command/struct.StateFilter.html
+/// [`EntryOrder`]: ../entry/enum.EntryOrder.html
+/// [`StateFile`]: ../state/struct.StateFile.html
+/// [`StallError`]: ../error/enum.StallError.html
 /// 
-pub fn status(
+pub fn status<'i, I>(
 	stall_dir: &Path,
 	stall: &Stall,
-	common: &CommonOptions) 
-	-> Result<(), Error>
+	patterns: I,
+	filter: StateFilter,
+	order: EntryOrder,
+	use_cache: bool) 
+	-> Result<StatusReport, StallError>
+	where I: IntoIterator<Item=&'i Path>
 {
 	let _span = span!(Level::INFO, "status").entered();
-	
-	if stall.is_empty() || common.quiet {
-		if !common.quiet {
-			println!("No files in stall. Use `add` command to place files \
-				in the stall.");
+
+	let patterns = stall.entry_patterns(patterns)?;
+
+	let mut report = StatusReport {
+		stall_dir: stall_dir.to_path_buf(),
+		total: 0,
+		entries: Vec::new(),
+	};
+	// Nothing to do if there's no data.
+	if stall.is_empty() { return Ok(report); }
+
+	// Determine the status of selected entries. Files which differ only by
+	// modification time are compared by their hashes, which are cached
+	// between runs unless the cache is disabled.
+	// Entries keep their indices in the stall's order when reordered.
+	let mut state_file = StateFile::read_from_stall_dir(stall_dir);
+	if !use_cache { state_file.clear_hashes(); }
+	let mut entries: Vec<_> = stall.entries()
+		.enumerate()
+		.filter(|(_, entry)| filter.matches_flags(entry.flags))
+		.filter(|(_, entry)| patterns.as_ref().is_none_or(|patterns| patterns
+			.iter()
+			.any(|p| p.matches_path(entry.local))))
+		.collect();
+	report.total = stall.entries().count();
+	order.sort(&mut entries, stall_dir, |(_, entry)| entry);
+	for (index, entry) in entries {
+		let (status_l, status_r) = entry
+			.status_with_state(stall_dir, &mut state_file);
+		let entry_state = StallState::from_statuses(status_l, status_r);
+		if !filter.matches(entry_state) { continue; }
+
+		report.entries.push(EntryStatus {
+			index: index + 1,
+			local: entry.local.to_path_buf(),
+			remote: entry.remote.to_path_buf(),
+			flags: entry.flags,
+			status_l,
+			status_r,
+			state: entry_state,
+			modified: entry.modified(stall_dir),
+			collected: state_file.collected(entry.local),
+			distributed: state_file.distributed(entry.local),
+		});
+	}
+	if use_cache {
+		if let Err(e) = state_file.write_to_stall_dir(stall_dir) {
+			event!(Level::WARN, "{e}");
+		}
+	}
+
+	Ok(report)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StatusReport
+////////////////////////////////////////////////////////////////////////////////
+/// The result of the 'stall-status' command, holding the status of each
+/// selected entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusReport {
+	/// The stall directory.
+	pub stall_dir: PathBuf,
+	/// The number of entries in the stall, including unselected entries.
+	pub total: usize,
+	/// The status of each selected entry, in the order given to [`status`].
+	///
+	/// [`status`]: ../command/fn.status.html
+	pub entries: Vec<EntryStatus>,
+}
+
+impl StatusReport {
+	/// Returns the overall [`StallState`] of the selected entries.
+	///
+	/// [`StallState`]: ../command/enum.StallState.html
+	#[must_use]
+	pub fn state(&self) -> StallState {
+		self.entries
+			.iter()
+			.map(|entry| entry.state)
+			.max()
+			.unwrap_or(StallState::Clean)
+	}
+
+	/// Writes the stall directory and a table of the status of each entry
+	/// into `out`.
+	///
+	/// ### Parameters
+	///
+	/// + `out`: The output to write into.
+	/// + `sort`: The [`StatusSort`] order of the printed entries.
+	/// + `columns`: The [`StatusColumn`]s to print. If empty, the default
+	///   columns will be used, along with the times each entry was last
+	///   copied if the `verbose` common option is given.
+	/// + `common`: The [`CommonOptions`] to use for the command.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if an IO error occurs during writing.
+	///
+	/// [`StatusSort`]: ../command/enum.StatusSort.html
+	/// [`StatusColumn`]: ../command/enum.StatusColumn.html
+	/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn write(
+		&self,
+		out: &mut dyn Write,
+		sort: StatusSort,
+		columns: &[StatusColumn],
+		common: &CommonOptions)
+		-> Result<(), StallError>
+	{
+		if common.quiet { return Ok(()); }
+		if self.total == 0 {
+			writeln!(out, "No files in stall. Use `add` command to place \
+				files in the stall.")?;
+			return Ok(());
+		}
+
+		if common.color.enabled() {
+			writeln!(out, "{} {}",
+				"Stall directory:".bright_white(),
+				self.stall_dir.display())?;
+		} else {
+			writeln!(out, "Stall directory: {}",
+				self.stall_dir.display())?;
+		}
+
+		// Entries are already in the report's order, which is kept by the
+		// stable sorts.
+		let mut entries: Vec<_> = self.entries.iter().collect();
+		match sort {
+			StatusSort::Name  => (),
+			StatusSort::State => entries.sort_by_key(|entry| entry.state),
+			StatusSort::Mtime => entries.sort_by_key(|entry| entry.modified),
+		}
+
+		// Write status table. The flags column is added to the default
+		// columns if any entry has flags set, and the copy time columns are
+		// added in verbose mode.
+		let columns = if columns.is_empty() {
+			let mut defaults = if entries.iter()
+				.any(|entry| entry.flags != EntryFlags::default())
+			{
+				StatusColumn::DEFAULT_WITH_FLAGS.to_vec()
+			} else {
+				StatusColumn::DEFAULT.to_vec()
+			};
+			if common.verbose {
+				defaults.extend_from_slice(StatusColumn::VERBOSE);
+			}
+			defaults
+		} else {
+			columns.to_vec()
+		};
+		let mut table = Table::new(columns
+			.iter()
+			.flat_map(|c| c.headers().iter().copied()));
+		for entry in entries {
+			table.push_row(columns
+				.iter()
+				.flat_map(|c| entry.cells(*c, common))
+				.collect());
+		}
+		table.write(out, common)?;
+		Ok(())
+	}
+}
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StallState
+////////////////////////////////////////////////////////////////////////////////
+/// The overall state of a stall, as determined by the status of its entries.
+///
+/// Variants are ordered by severity, so the state of a whole stall is the
+/// maximum of the states of its entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StallState {
+	/// All entries are the same as their counterparts.
+	Clean,
+	/// Some entries are newer or older than their counterparts.
+	OutOfDate,
+	/// Some entries are missing or unreadable.
+	Error,
+}
+
+impl StallState {
+	/// Returns the `StallState` corresponding to the local and remote status
+	/// of a single entry.
+	#[must_use]
+	pub const fn from_statuses(status_l: Status, status_r: Status) -> Self {
+		use Status::*;
+		match (status_l, status_r) {
+			(Same, Same)            => Self::Clean,
+			(Error | Absent, _)     |
+			(_, Error | Absent)     => Self::Error,
+			_                       => Self::OutOfDate,
+		}
+	}
+
+	/// Returns the process exit code for the state.
+	#[must_use]
+	pub const fn exit_code(self) -> i32 {
+		match self {
+			Self::Clean     => 0,
+			Self::OutOfDate => 1,
+			Self::Error     => 2,
+		}
+	}
+}
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StateFilter
+////////////////////////////////////////////////////////////////////////////////
+/// A filter for selecting entries by their [`StallState`]. An empty filter
+/// matches every entry in any state. Archived entries are only matched if
+/// they are included.
+///
+/// [`StallState`]: ../command/enum.StallState.html
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct StateFilter {
+	/// Select entries which are the same as their counterparts.
+	pub clean: bool,
+	/// Select entries which are newer or older than their counterparts.
+	pub modified: bool,
+	/// Select entries with missing or unreadable files.
+	pub missing: bool,
+	/// Select archived entries, which are skipped otherwise.
+	pub archived: bool,
+}
+
+impl StateFilter {
+	/// Returns true if no states are selected by the filter.
+	#[must_use]
+	pub const fn is_empty(&self) -> bool {
+		!(self.clean || self.modified || self.missing)
+	}
+
+	/// Returns true if entries with the given [`EntryFlags`] are selected by
+	/// the filter.
+	///
+	/// [`EntryFlags`]: ../entry/struct.EntryFlags.html
+	#[must_use]
+	pub const fn matches_flags(&self, flags: EntryFlags) -> bool {
+		self.archived || !flags.archived
+	}
+
+	/// Returns true if the given `StallState` is selected by the filter.
+	#[must_use]
+	pub const fn matches(&self, state: StallState) -> bool {
+		if self.is_empty() { return true; }
+		match state {
+			StallState::Clean     => self.clean,
+			StallState::OutOfDate => self.modified,
+			StallState::Error     => self.missing,
 		}
-		// Nothing to do if asking for status with --quiet.
-		return Ok(());
 	}
+}
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// EntryStatus
+////////////////////////////////////////////////////////////////////////////////
+/// The computed status of a single entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryStatus {
+	/// The 1-based index of the entry within the stall.
+	pub index: usize,
+	/// The local path of the entry.
+	pub local: PathBuf,
+	/// The remote path of the entry.
+	pub remote: PathBuf,
+	/// The flags of the entry.
+	pub flags: EntryFlags,
+	/// The status of the local file.
+	pub status_l: Status,
+	/// The status of the remote file.
+	pub status_r: Status,
+	/// The state of the entry.
+	pub state: StallState,
+	/// The most recent modification time of the entry files.
+	pub modified: Option<SystemTime>,
+	/// The time the entry was last collected.
+	pub collected: Option<SystemTime>,
+	/// The time the entry was last distributed.
+	pub distributed: Option<SystemTime>,
+}
+
+impl EntryStatus {
+	/// Returns the table cells for the given column.
+	fn cells(&self, column: StatusColumn, common: &CommonOptions) -> Vec<Cell> {
+		match column {
+			StatusColumn::Index => vec![Cell::new(self.index.to_string())],
+
+			StatusColumn::State => vec![
+				Cell::new(self.status_l.label())
+					.with_color(self.status_l.color()),
+				Cell::new(self.status_r.label())
+					.with_color(self.status_r.color()),
+			],
+
+			StatusColumn::Local => vec![
+				Cell::new(self.local.display().to_string()),
+			],
 
+			StatusColumn::Remote => {
+				let remote = if common.short_names {
+					self.remote
+						.file_name()
+						.map_or(self.remote.as_path(), AsRef::as_ref)
+				} else {
+					self.remote.as_path()
+				};
+				vec![Cell::new(remote.display().to_string())]
+			},
 
-	let mut out = std::io::stdout();
+			StatusColumn::Flags => vec![
+				Cell::new(self.flags.label())
+					.with_color(Color::BrightYellow),
+			],
 
-	// Setup and print stall directory.
-	if common.color.enabled() {
-		writeln!(&mut out, "{} {}",
-			"Stall directory:".bright_white(),
-			stall_dir.display())?;
-	} else {
-		writeln!(&mut out, "Stall directory: {}",
-			stall_dir.display())?;
+			StatusColumn::Mtime       => vec![time_cell(self.modified)],
+			StatusColumn::Collected   => vec![time_cell(self.collected)],
+			StatusColumn::Distributed => vec![time_cell(self.distributed)],
+		}
 	}
+}
+
+/// Returns a table cell displaying the given time in the local time zone.
+fn time_cell(time: Option<SystemTime>) -> Cell {
+	Cell::new(format_time(time))
+}
+
+/// Formats the given time in the local time zone, or `-` if it is unknown.
+pub(in crate::command) fn format_time(time: Option<SystemTime>) -> String {
+	time.map_or_else(
+		|| "-".to_owned(),
+		|t| DateTime::<Local>::from(t)
+			.format("%Y-%m-%d %H:%M:%S")
+			.to_string())
+}
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StatusColumn
+////////////////////////////////////////////////////////////////////////////////
+/// A column of the status table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[derive(clap::ArgEnum)]
+pub enum StatusColumn {
+	/// The index of the entry, which may be used to select it in the collect
+	/// and distribute commands.
+	Index,
+	/// The path of the entry within the stall directory.
+	Local,
+	/// The remote path of the entry.
+	Remote,
+	/// The status of the local and remote files.
+	State,
+	/// The most recent modification time of the local and remote files.
+	Mtime,
+	/// The pinned and readonly flags of the entry.
+	Flags,
+	/// The time the entry was last collected.
+	Collected,
+	/// The time the entry was last distributed.
+	Distributed,
+}
+
+impl StatusColumn {
+	/// The columns to display if none are specified.
+	pub const DEFAULT: &'static [Self] = &[
+		Self::Index,
+		Self::State,
+		Self::Local,
+		Self::Remote,
+	];
 
-	// Write status table.
-	Entry::write_status_header(&mut out, common)?;
-	for entry in stall.entries() {
+	/// The columns to display if none are specified and any entry has flags
+	/// set.
+	pub const DEFAULT_WITH_FLAGS: &'static [Self] = &[
+		Self::Index,
+		Self::State,
+		Self::Local,
+		Self::Remote,
+		Self::Flags,
+	];
 
-		let (status_l, status_r) = entry.status(stall_dir);
-		entry.write_status(&mut out, status_l, status_r, common)?;
+	/// The columns to append to the default columns in verbose mode.
+	pub const VERBOSE: &'static [Self] = &[Self::Collected, Self::Distributed];
+
+	/// Returns the header text for the column.
+	#[must_use]
+	pub const fn headers(self) -> &'static [&'static str] {
+		match self {
+			Self::Index       => &["#"],
+			Self::Local       => &["FILE"],
+			Self::Remote      => &["REMOTE PATH"],
+			Self::State       => &["LOCAL", "REMOTE"],
+			Self::Mtime       => &["MODIFIED"],
+			Self::Flags       => &["FLAGS"],
+			Self::Collected   => &["COLLECTED"],
+			Self::Distributed => &["DISTRIBUTED"],
+		}
 	}
+}
+
+
 
-	Ok(())
+////////////////////////////////////////////////////////////////////////////////
+// StatusSort
+////////////////////////////////////////////////////////////////////////////////
+/// The sort order of the status table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[derive(clap::ArgEnum)]
+pub enum StatusSort {
+	/// Keep entries in the order of the report, which is by their path within
+	/// the stall directory unless `--order` is given.
+	#[default]
+	Name,
+	/// Sort entries by their state, with out of date and missing entries
+	/// last.
+	State,
+	/// Sort entries by their most recent modification time, with the newest
+	/// entries last.
+	Mtime,
 }
@@ -0,0 +1,175 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Trash management commands.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::CommonOptions;
+use crate::report::Cell;
+use crate::report::Table;
+use crate::trash::Trash;
+
+// External library imports.
+use chrono::DateTime;
+use chrono::Local;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// trash_list
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-trash-list' command.
+///
+/// This will print the id, deletion time, and original path of each file in
+/// the stall's [`Trash`].
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the trash.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if the trash index cannot be read or if an IO error
+/// occurs during writing.
+/// 
+/// [`Trash`]: ../trash/struct.Trash.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn trash_list(stall_dir: &Path, common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "trash_list").entered();
+	let trash = Trash::open(stall_dir, common.backup_dir.as_deref())?;
+	let mut out = std::io::stdout();
+
+	if trash.is_empty() {
+		if !common.quiet { writeln!(&mut out, "Trash is empty.")?; }
+		return Ok(());
+	}
+
+	let mut table = Table::new(["ID", "DELETED", "ORIGINAL PATH"]);
+	for item in trash.items() {
+		let deleted: DateTime<Local> = item.deleted_time().into();
+		table.push_row(vec![
+			Cell::new(item.id.to_string()),
+			Cell::new(deleted.format("%Y-%m-%d %H:%M:%S").to_string()),
+			Cell::new(item.original.display().to_string()),
+		]);
+	}
+	table.write(&mut out, common)?;
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// trash_restore
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-trash-restore' command.
+///
+/// This will move files from the stall's [`Trash`] back to their original
+/// locations.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the trash.
+/// + `ids`: The ids of the trash items to restore.
+/// + `force`: Overwrite any files at the original locations.
+/// + `dry_run`: Do not modify any files.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if any of the items do not exist, if a file exists at
+/// an original location and `force` is false, or if any IO errors occur.
+/// 
+/// [`Trash`]: ../trash/struct.Trash.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn trash_restore(
+	stall_dir: &Path,
+	ids: &[u64],
+	force: bool,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "trash_restore").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let mut trash = Trash::open(stall_dir, common.backup_dir.as_deref())?;
+	let mut out = std::io::stdout();
+
+	for &id in ids {
+		if dry_run {
+			let item = trash.items().iter().find(|item| item.id == id);
+			if let Some(item) = item {
+				writeln!(&mut out, "Restore trash item {} to {}",
+					id,
+					item.original.display())?;
+			}
+			continue;
+		}
+
+		let restored = trash.restore(id, force)?;
+		if !common.quiet {
+			writeln!(&mut out, "Restored {}", restored.display())?;
+		}
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// trash_empty
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-trash-empty' command.
+///
+/// This will permanently delete all files in the stall's [`Trash`].
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the trash.
+/// + `dry_run`: Do not modify any files.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+/// 
+/// Returns a [`StallError`] if the trash cannot be removed.
+/// 
+/// [`Trash`]: ../trash/struct.Trash.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+/// 
+pub fn trash_empty(stall_dir: &Path, dry_run: bool, common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "trash_empty").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let mut trash = Trash::open(stall_dir, common.backup_dir.as_deref())?;
+	let mut out = std::io::stdout();
+
+	if dry_run {
+		writeln!(&mut out, "Delete {} trash items", trash.items().len())?;
+		return Ok(());
+	}
+
+	let count = trash.empty()?;
+	if !common.quiet {
+		writeln!(&mut out, "Deleted {count} trash items")?;
+	}
+	Ok(())
+}
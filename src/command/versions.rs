@@ -0,0 +1,217 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Previous version management commands.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::report::write_contents;
+use crate::report::Cell;
+use crate::report::Table;
+use crate::versions::Retention;
+use crate::versions::Versions;
+use crate::CommonOptions;
+use crate::Stall;
+
+// External library imports.
+use chrono::DateTime;
+use chrono::Local;
+use tracing::span;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// versions_list
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-versions-list' command.
+///
+/// This will print the name and save time of each previous version of an
+/// entry's stalled file, oldest first.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the versions.
+/// + `stall`: The loaded [`Stall`] data.
+/// + `file`: The [`Path`] of the entry whose versions to list. Entry indices
+///   and partial names are also accepted.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the entry is not in the stall, if the versions
+/// cannot be read, or if an IO error occurs during writing.
+///
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn versions_list(
+	stall_dir: &Path,
+	stall: &Stall,
+	file: &Path,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "versions_list").entered();
+	let (local, versions) = open_versions(stall_dir, stall, file)?;
+	let versions = versions.list()?;
+	let mut out = std::io::stdout();
+
+	if versions.is_empty() {
+		if !common.quiet {
+			writeln!(&mut out, "No versions of {}.", local.display())?;
+		}
+		return Ok(());
+	}
+
+	let mut table = Table::new(["VERSION", "SAVED"]);
+	for version in &versions {
+		let saved: DateTime<Local> = version.saved.into();
+		table.push_row(vec![
+			Cell::new(version.name.clone()),
+			Cell::new(saved.format("%Y-%m-%d %H:%M:%S").to_string()),
+		]);
+	}
+	table.write(&mut out, common)?;
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// versions_show
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-versions-show' command.
+///
+/// This will print the contents of a previous version of an entry's stalled
+/// file. Directories are listed instead.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the versions.
+/// + `stall`: The loaded [`Stall`] data.
+/// + `file`: The [`Path`] of the entry whose version to show. Entry indices
+///   and partial names are also accepted.
+/// + `version`: The name of the version to show. Defaults to the most recent
+///   version.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the entry or version does not exist, if the
+/// version cannot be read, or if an IO error occurs during writing.
+///
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn versions_show(
+	stall_dir: &Path,
+	stall: &Stall,
+	file: &Path,
+	version: Option<&str>)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "versions_show").entered();
+	let (_, versions) = open_versions(stall_dir, stall, file)?;
+	let version = versions.get(version)?;
+	write_contents(&mut std::io::stdout(), &version.path)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// versions_restore
+////////////////////////////////////////////////////////////////////////////////
+/// Executes the 'stall-versions-restore' command.
+///
+/// This will replace an entry's stalled file with one of its previous
+/// versions. The replaced file is saved as a new version, so that the restore
+/// can be undone, and then the versions not kept by the `retention` policy are
+/// deleted.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the versions.
+/// + `stall`: The loaded [`Stall`] data.
+/// + `file`: The [`Path`] of the entry whose version to restore. Entry indices
+///   and partial names are also accepted.
+/// + `version`: The name of the version to restore. Defaults to the most
+///   recent version.
+/// + `retention`: The [`Retention`] policy for the entry's versions, if any.
+/// + `dry_run`: Do not modify any files.
+/// + `common`: The [`CommonOptions`] to use for the command.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the entry or version does not exist, if the
+/// files cannot be copied, or if an IO error occurs during writing.
+///
+/// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
+/// [`Stall`]: ../struct.Stall.html
+/// [`Retention`]: ../versions/enum.Retention.html
+/// [`CommonOptions`]: ../command/struct.CommonOptions.html
+/// [`StallError`]: ../error/enum.StallError.html
+///
+pub fn versions_restore(
+	stall_dir: &Path,
+	stall: &Stall,
+	file: &Path,
+	version: Option<&str>,
+	retention: Option<Retention>,
+	dry_run: bool,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	let _span = span!(Level::INFO, "versions_restore").entered();
+	if dry_run && common.quiet { return Ok(()); }
+
+	let (local, versions) = open_versions(stall_dir, stall, file)?;
+	let version = versions.get(version)?;
+	let mut out = std::io::stdout();
+
+	if dry_run {
+		writeln!(&mut out, "Restore version {} of {}",
+			version.name,
+			local.display())?;
+		return Ok(());
+	}
+
+	versions.restore(&version, &stall_dir.join(&local))?;
+	if let Some(retention) = retention {
+		let _ = versions.prune(retention)?;
+	}
+	if !common.quiet {
+		writeln!(&mut out, "Restored version {} of {}",
+			version.name,
+			local.display())?;
+	}
+	Ok(())
+}
+
+
+/// Returns the local path and [`Versions`] of the entry selected by `file`.
+///
+/// [`Versions`]: ../versions/struct.Versions.html
+fn open_versions(stall_dir: &Path, stall: &Stall, file: &Path)
+	-> Result<(PathBuf, Versions), StallError>
+{
+	let entries = stall.entries_selected(file)?;
+	let local = entries
+		.first()
+		.map(|entry| entry.local.to_path_buf())
+		.ok_or_else(|| StallError::NotFound {
+			kind: "entry",
+			name: file.display().to_string(),
+			suggestions: Vec::new(),
+		})?;
+	let versions = Versions::open(stall_dir, &local);
+	Ok((local, versions))
+}
@@ -0,0 +1,219 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Overwrite confirmation prompts.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+
+// External library imports.
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Confirm
+////////////////////////////////////////////////////////////////////////////////
+/// Asks the user to confirm each overwritten file.
+///
+/// The user may answer for a single file, or for all remaining files. The end
+/// of the input is treated as declining all remaining overwrites.
+pub struct Confirm<'a> {
+	/// The input to read answers from.
+	input: &'a mut dyn BufRead,
+	/// The answer for all remaining files, if one has been given.
+	remaining: Option<bool>,
+}
+
+impl<'a> Confirm<'a> {
+	/// Constructs a new `Confirm` which reads answers from the given input.
+	#[must_use]
+	pub fn new(input: &'a mut dyn BufRead) -> Self {
+		Self { input, remaining: None }
+	}
+
+	/// Confirms all remaining overwrites without asking.
+	pub fn accept_all(&mut self) {
+		self.remaining = Some(true);
+	}
+
+	/// Returns whether the file at `target` should be overwritten by the file
+	/// at `source`, asking the user if necessary. The prompt shows how much
+	/// newer or older and larger or smaller the target file is, if they can be
+	/// determined.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if an IO error occurs while reading the input
+	/// or writing the prompt.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn overwrite(
+		&mut self,
+		out: &mut dyn Write,
+		target: &Path,
+		source: &Path)
+		-> Result<bool, StallError>
+	{
+		loop {
+			if let Some(answer) = self.remaining { return Ok(answer); }
+
+			let details = differences(target, source);
+			if details.is_empty() {
+				write!(out, "Overwrite {}? [y/n/a/q] ", target.display())?;
+			} else {
+				write!(out, "Overwrite {} ({})? [y/n/a/q] ",
+					target.display(),
+					details.join(", "))?;
+			}
+			out.flush()?;
+
+			let mut line = String::new();
+			if self.input.read_line(&mut line)? == 0 {
+				writeln!(out)?;
+				event!(Level::DEBUG, "End of input: declining overwrites");
+				self.remaining = Some(false);
+				continue;
+			}
+			match line.trim().to_lowercase().as_str() {
+				"y" | "yes"  => return Ok(true),
+				"n" | "no"   => return Ok(false),
+				"a" | "all"  => self.remaining = Some(true),
+				"q" | "quit" => self.remaining = Some(false),
+				_ => writeln!(out, "Enter y to overwrite the file, n to skip \
+					it, a to overwrite all remaining files, or q to skip all \
+					remaining files.")?,
+			}
+		}
+	}
+
+	/// Asks the user a yes or no question, returning `true` if they answered
+	/// yes. The end of the input is treated as answering no.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if an IO error occurs while reading the input
+	/// or writing the prompt.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn proceed(&mut self, out: &mut dyn Write, question: &str)
+		-> Result<bool, StallError>
+	{
+		loop {
+			write!(out, "{question} [y/n] ")?;
+			out.flush()?;
+
+			let mut line = String::new();
+			if self.input.read_line(&mut line)? == 0 {
+				writeln!(out)?;
+				event!(Level::DEBUG, "End of input: declining");
+				return Ok(false);
+			}
+			match line.trim().to_lowercase().as_str() {
+				"y" | "yes" => return Ok(true),
+				"n" | "no"  => return Ok(false),
+				_ => writeln!(out, "Enter y to continue or n to stop.")?,
+			}
+		}
+	}
+
+	/// Asks the user to choose one of `count` numbered options, returning the
+	/// index of the chosen option, or `None` if they chose none. The end of
+	/// the input is treated as choosing none.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if an IO error occurs while reading the input
+	/// or writing the prompt.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn choose(&mut self, out: &mut dyn Write, question: &str, count: usize)
+		-> Result<Option<usize>, StallError>
+	{
+		loop {
+			write!(out, "{question} [1-{count}/n] ")?;
+			out.flush()?;
+
+			let mut line = String::new();
+			if self.input.read_line(&mut line)? == 0 {
+				writeln!(out)?;
+				event!(Level::DEBUG, "End of input: choosing none");
+				return Ok(None);
+			}
+			let answer = line.trim().to_lowercase();
+			if matches!(answer.as_str(), "n" | "no") { return Ok(None); }
+			match answer.parse::<usize>() {
+				Ok(n) if (1..=count).contains(&n) => return Ok(Some(n - 1)),
+				_ => writeln!(out, "Enter a number from 1 to {count} to \
+					choose an option, or n to skip.")?,
+			}
+		}
+	}
+}
+
+impl std::fmt::Debug for Confirm<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Confirm")
+			.field("remaining", &self.remaining)
+			.finish_non_exhaustive()
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helper functions
+////////////////////////////////////////////////////////////////////////////////
+/// Returns descriptions of the differences in age and size of the `target`
+/// file compared to the `source` file. Sizes are only compared for regular
+/// files.
+fn differences(target: &Path, source: &Path) -> Vec<String> {
+	use std::cmp::Ordering::*;
+
+	let mut details = Vec::new();
+	let (Ok(target), Ok(source)) = (
+		std::fs::metadata(target),
+		std::fs::metadata(source))
+	else {
+		return details;
+	};
+
+	if let (Ok(t), Ok(s)) = (target.modified(), source.modified()) {
+		let age = |a: SystemTime, b: SystemTime| a.duration_since(b)
+			.map_or(0, |d| d.as_secs());
+		details.push(match t.cmp(&s) {
+			Greater => format!("{} newer", format_age(age(t, s))),
+			Less    => format!("{} older", format_age(age(s, t))),
+			Equal   => "same age".to_owned(),
+		});
+	}
+
+	if target.is_file() && source.is_file() {
+		let (t, s) = (target.len(), source.len());
+		details.push(match t.cmp(&s) {
+			Greater => format!("{} bytes larger", t - s),
+			Less    => format!("{} bytes smaller", s - t),
+			Equal   => "same size".to_owned(),
+		});
+	}
+	details
+}
+
+/// Formats a number of seconds using its largest whole unit.
+fn format_age(secs: u64) -> String {
+	match secs {
+		0..=59          => format!("{secs}s"),
+		60..=3599       => format!("{}m", secs / 60),
+		3600..=86_399   => format!("{}h", secs / 3600),
+		_               => format!("{}d", secs / 86_400),
+	}
+}
@@ -8,14 +8,37 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::atomic::replace_with;
 use crate::command::CommonOptions;
+use crate::confirm::Confirm;
+use crate::ignore;
+use crate::ignore::IgnoreRules;
+use crate::manifest::hash_file;
+use crate::manifest::HashAlgorithm;
+use crate::mergetool::MergeTool;
+use crate::platform;
+use crate::privilege::Privilege;
+use crate::remote;
+use crate::report::warn;
+use crate::safety;
+use crate::state::StateFile;
+use crate::transform::Direction;
+use crate::transform::LineEnding;
+use crate::transform::Pipeline;
+use crate::transform::Transform;
+use crate::trash::remove_path;
+use crate::trash::Trash;
+use crate::versions::Retention;
+use crate::versions::Versions;
+use crate::warning::Warning;
 
 // External library imports.
-use anyhow::Error;
-use anyhow::anyhow;
+use colored::Color;
 use colored::Colorize as _;
-use fcmp::FileCmp;
-use fcmp::DiffOp;
+use serde::Deserialize;
+use serde::Serialize;
 use tracing::event;
 use tracing::span;
 use tracing::Level;
@@ -23,6 +46,10 @@ use tracing::Level;
 // Standard library imports.
 use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
 
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -35,168 +62,639 @@ pub struct Entry<'a> {
 	pub local: &'a Path,
 	/// The remote path of a stall entry.
 	pub remote: &'a Path,
+	/// The flags of the stall entry.
+	pub flags: EntryFlags,
+	/// The transforms applied to the entry's files when they are copied.
+	pub transforms: &'a [Transform],
 }
 
 
 impl<'a> Entry<'a> {
-	/// Returns the file statuses for the local and remote entry paths.
+	/// Returns the file statuses for the local and remote entry paths. The
+	/// statuses are determined by the [`RemoteBackend`] for the remote path.
+	///
+	/// [`RemoteBackend`]: ../remote/trait.RemoteBackend.html
 	#[must_use]
 	pub fn status(&self, stall_dir: &Path) -> (Status, Status) {
-		use Status::*;
-		use std::cmp::Ordering::*;
-
-		let diff_op = DiffOp::None;
-
 		let mut full_local = stall_dir.to_path_buf();
 		full_local.push(self.local);
 
-		let file_cmp_l = FileCmp::try_from(full_local.clone())
-			.map_err(|e| event!(Level::DEBUG, "{e}: {:?}",
-				full_local.as_path()));
-		let file_cmp_r = FileCmp::try_from(self.remote.to_path_buf())
-			.map_err(|e| event!(Level::DEBUG, "{e}: {:?}", self.remote));
-
-		event!(Level::TRACE, "LOCAL {:?}", file_cmp_l);
-		event!(Level::TRACE, "REMOTE {:?}", file_cmp_r);
-
-		match (file_cmp_l, file_cmp_r) {
-			(Err(_), Err(_))                 => (Error, Error),
-			(Err(_), Ok(f)) if !f.is_found() => (Error, Absent),
-			(Err(_), Ok(_))                  => (Error, Exists),
-			(Ok(f), Err(_)) if !f.is_found() => (Absent, Error),
-			(Ok(_), Err(_))                  => (Exists, Error),
-
-			(Ok(l), Ok(r)) => match (l.is_found(), r.is_found()) {
-				(false, false) => (Absent, Absent),
-				(true, false)  => (Exists, Absent),
-				(false, true)  => (Absent, Exists),
-				(true, true) => match l.partial_cmp(&r, &diff_op, false) {
-					Some(Less)    => (Older, Newer),
-					Some(Equal)   => (Same,  Same),
-					Some(Greater) => (Newer, Older),
-					None          => (Error, Error),
-				},
-			}
+		match remote::backend(self.remote) {
+			Ok(backend) => backend.status(&full_local, self.remote),
+			Err(e) => {
+				event!(Level::DEBUG, "{e}");
+				remote::unavailable_status(&full_local)
+			},
 		}
 	}
 
+	/// Returns the file statuses for the local and remote entry paths, as
+	/// [`status`] does, except that local files which differ from their
+	/// remote file only by modification time are reported as the same. The
+	/// files' contents are compared using the hashes in the given
+	/// [`StateFile`], unless the entry is flagged as binary.
+	///
+	/// [`status`]: #method.status
+	/// [`StateFile`]: ../state/struct.StateFile.html
+	#[must_use]
+	pub fn status_with_state(&self, stall_dir: &Path, state: &mut StateFile)
+		-> (Status, Status)
+	{
+		let (status_l, status_r) = self.status(stall_dir);
+		if !matches!(status_l, Status::Newer | Status::Older)
+			|| remote::scheme(self.remote).is_some()
+			|| self.flags.binary
+		{
+			return (status_l, status_r);
+		}
+
+		match state.same_contents(&stall_dir.join(self.local), self.remote) {
+			Ok(true) => (Status::Same, Status::Same),
+			Ok(false) => (status_l, status_r),
+			Err(e) => {
+				event!(Level::DEBUG, "{e}");
+				(status_l, status_r)
+			},
+		}
+	}
+
+	/// Returns the most recent modification time of the local and remote
+	/// files, if either can be determined.
+	#[must_use]
+	pub fn modified(&self, stall_dir: &Path) -> Option<SystemTime> {
+		let modified = |path: &Path| std::fs::metadata(path)
+			.and_then(|m| m.modified())
+			.ok();
+
+		let local = modified(stall_dir.join(self.local).as_path());
+		let remote = modified(self.remote);
+		local.max(remote)
+	}
+
+	/// Returns the larger of the sizes of the local and remote files, if
+	/// either can be determined. The sizes of directories are the total sizes
+	/// of their contents.
+	#[must_use]
+	pub fn size(&self, stall_dir: &Path) -> Option<u64> {
+		let local = disk_usage(&stall_dir.join(self.local));
+		let remote = disk_usage(self.remote);
+		local.max(remote)
+	}
+
 	/// Prints the status of the stall entry and copies the remote file into the
-	/// stall directory.
+	/// stall directory using the given [`CopyOptions`]. Files with the same
+	/// contents are not copied, as determined by [`status_with_state`], and
+	/// the time of the copy is recorded in the [`StateFile`]. The given
+	/// [`Overwrite`] selects which safeguards against copying are bypassed.
+	/// Returns the [`Outcome`] for the entry.
+	///
+	/// [`CopyOptions`]: struct.CopyOptions.html
+	/// [`Overwrite`]: struct.Overwrite.html
+	/// [`Outcome`]: enum.Outcome.html
+	/// [`status_with_state`]: #method.status_with_state
+	/// [`StateFile`]: ../state/struct.StateFile.html
+	#[allow(clippy::too_many_arguments)]
 	pub fn collect(
 		&self,
 		out: &mut dyn Write,
 		stall_dir: &Path,
-		force: bool,
+		overwrite: Overwrite,
+		unpin: bool,
+		options: &CopyOptions,
+		state: &mut StateFile,
+		confirm: Option<&mut Confirm<'_>>,
 		dry_run: bool,
 		common: &CommonOptions)
-		-> Result<(), Error>
+		-> Result<Outcome, StallError>
 	{
 		use Status::*;
 
-		let (status_l, status_r) = self.status(stall_dir);
+		safety::check_remote(stall_dir, self.local, self.remote)?;
+		let (status_l, status_r) = self.status_with_state(stall_dir, state);
 		let action = match (&status_l, &status_r) {
 			(Absent, Exists) |
 			(Older,  Newer)  => Action::Copy,
 
-			(Same,   Same)  if force => Action::Force,
-			(Newer,  Older) if force => Action::Force,
+			(Same,   Same)   if overwrite.unmodified => Action::Force,
+			(Newer,  Older)  if overwrite.newer      => Action::Force,
+			(Error,  Exists) if overwrite.errors     => Action::Force,
 
 			(_, Error) |
 			(Error, _) => Action::Stop,
 
 			_ => Action::Skip,
 		};
+		let conflict = !overwrite.newer && self.conflicted(stall_dir, status_l, state);
+		let action = if conflict {
+			self.resolve_conflict(out, action, options, true)?
+		} else {
+			action
+		};
+		let action = match action {
+			// Pinned entries may only be overwritten if forced and unpinned.
+			Action::Force | Action::Copy | Action::Merge
+				if self.flags.pinned
+					&& status_l != Absent
+					&& !(overwrite.any() && unpin) => Action::Keep,
+			_ => action,
+		};
+		let action = self.confirm_action(
+			out,
+			action,
+			status_l,
+			&stall_dir.join(self.local),
+			self.remote,
+			confirm,
+			dry_run)?;
 
 		if !common.quiet {
 			self.write_status_action(out, status_l, status_r, action, common)?;
 		}
-		if common.promote_warnings_to_errors && matches!(action, Action::Stop) {
-			return Err(anyhow!("abort collect due to file error"));
+		if status_r == Absent {
+			warn(Warning::MissingRemote {
+				path: self.remote.to_path_buf(),
+			}, common)?;
+		}
+		self.warn_unreadable(stall_dir, status_l, status_r, common)?;
+		if conflict && action == Action::Stop {
+			return Err(self.conflict_error());
 		}
 
-		if matches!(action, Action::Force | Action::Copy) {
+		if matches!(action, Action::Force | Action::Copy | Action::Merge) {
 			let mut full_local = stall_dir.to_path_buf();
 			full_local.push(self.local);
 
-			let copy_method = if dry_run {
-				CopyMethod::None
+			if dry_run {
+				event!(Level::DEBUG, "no-run flag was specified: \
+					Not copying data from {:?} to {:?}", self.remote, full_local);
+				if action != Action::Merge {
+					self.write_transforms(out, Direction::Collect, common)?;
+				}
+			} else if action == Action::Merge {
+				// The base is found before the stalled file is saved as a new
+				// version, and is skipped if the save pruned it.
+				let base = self.merge_base(stall_dir)?;
+				self.save_version(stall_dir, status_l, options)?;
+				let base = base.filter(|base| base.exists());
+				self.merge(&full_local, &full_local, base.as_deref(),
+					options, common)?;
+				state.record_collected(self.local);
+				state.record_file_id(self.remote);
 			} else {
-				CopyMethod::Subprocess
-			};
-
-			copy(self.remote, full_local.as_path(), copy_method)?;
+				self.save_version(stall_dir, status_l, options)?;
+				// Files ignored by git are skipped when collecting a directory
+				// within a git work tree.
+				let git_options = self.git_ignored().map(|paths| {
+					let ignore = if options.ignore.is_empty() {
+						IgnoreRules::new(stall_dir)
+					} else {
+						options.ignore.clone()
+					};
+					CopyOptions {
+						ignore: ignore.with_paths(self.local, &paths),
+						..options.clone()
+					}
+				});
+				let options = git_options.as_ref().unwrap_or(options);
+				let used = remote::backend(self.remote)?
+					.fetch(self.remote, &full_local, options)?;
+				event!(Level::INFO, "Copied {:?} to {:?} using {} copy",
+					self.remote, full_local, used);
+				preserve(self.remote, &full_local, options, common)?;
+				self.pipeline().apply(&full_local, Direction::Collect)?;
+				state.record_collected(self.local);
+				state.record_file_id(self.remote);
+			}
 		}
 
-		Ok(())
+		Ok(Outcome::of(action, status_l, status_r))
 	}
 
 	/// Prints the status of the stall entry and copies the stalled file into
-	/// the remote directory.
+	/// the remote directory using the given [`CopyOptions`]. If a `Trash` is
+	/// provided, any overwritten remote file will be copied into it first.
+	/// Missing remote directories are created if `create_dirs` is true,
+	/// otherwise the entry is not copied. Files with the same contents are not
+	/// copied, as determined by [`status_with_state`], and the time of the
+	/// copy is recorded in the [`StateFile`]. The given [`Overwrite`] selects
+	/// which safeguards against copying are bypassed. Returns the
+	/// [`Outcome`] for the entry.
+	///
+	/// [`CopyOptions`]: struct.CopyOptions.html
+	/// [`Overwrite`]: struct.Overwrite.html
+	/// [`Outcome`]: enum.Outcome.html
+	/// [`status_with_state`]: #method.status_with_state
+	/// [`StateFile`]: ../state/struct.StateFile.html
+	#[allow(clippy::too_many_arguments)]
+	#[allow(clippy::fn_params_excessive_bools)]
 	pub fn distribute(
 		&self,
 		out: &mut dyn Write,
 		stall_dir: &Path,
-		force: bool,
+		overwrite: Overwrite,
+		unpin: bool,
+		create_dirs: bool,
+		options: &CopyOptions,
+		state: &mut StateFile,
+		confirm: Option<&mut Confirm<'_>>,
+		trash: Option<&mut Trash>,
 		dry_run: bool,
 		common: &CommonOptions)
-		-> Result<(), Error>
+		-> Result<Outcome, StallError>
 	{
 		use Status::*;
 
-		let (status_l, status_r) = self.status(stall_dir);
+		safety::check_remote(stall_dir, self.local, self.remote)?;
+		let (status_l, status_r) = self.status_with_state(stall_dir, state);
 		let action = match (&status_l, &status_r) {
 			(Exists, Absent) |
 			(Newer,  Older)  => Action::Copy,
 
-			(Same,   Same)  if force => Action::Force,
-			(Older,  Newer) if force => Action::Force,
+			(Same,   Same)   if overwrite.unmodified => Action::Force,
+			(Older,  Newer)  if overwrite.newer      => Action::Force,
+			(Exists, Error)  if overwrite.errors     => Action::Force,
 
 			(_, Error) |
 			(Error, _) => Action::Stop,
 
 			_ => Action::Skip,
 		};
+		let conflict = !overwrite.newer && self.conflicted(stall_dir, status_l, state);
+		let action = if conflict {
+			self.resolve_conflict(out, action, options, false)?
+		} else {
+			action
+		};
+		let action = match action {
+			// Readonly entries may only be overwritten if forced and unpinned.
+			Action::Force | Action::Copy | Action::Merge
+				if self.flags.readonly
+					&& status_r != Absent
+					&& !(overwrite.any() && unpin) => Action::Keep,
+			_ => action,
+		};
+
+		// Find any remote directories which must be created for the copy.
+		let missing_dirs = if matches!(action, Action::Force | Action::Copy) {
+			remote::backend(self.remote)
+				.map(|backend| backend.missing_dirs(self.remote))
+				.unwrap_or_default()
+		} else {
+			Vec::new()
+		};
+		let missing_dir = (!create_dirs)
+			.then(|| missing_dirs.first().cloned())
+			.flatten();
+		// Only local remote files can be copied with privileges.
+		let privileged = (self.flags.privileged || options.privilege.system)
+			&& remote::scheme(self.remote).is_none();
+		let action = if missing_dir.is_some() { Action::Stop } else { action };
+		let action = self.confirm_action(
+			out,
+			action,
+			status_r,
+			self.remote,
+			&stall_dir.join(self.local),
+			confirm,
+			dry_run)?;
 
 		if !common.quiet {
 			self.write_status_action(out, status_l, status_r, action, common)?;
 		}
-		if common.promote_warnings_to_errors && matches!(action, Action::Stop) {
-			return Err(anyhow!("abort collect due to file error"));
+		if let Some(path) = missing_dir {
+			warn(Warning::MissingRemoteDir { path }, common)?;
+		}
+		if status_l == Absent {
+			warn(Warning::MissingLocal {
+				path: stall_dir.join(self.local),
+			}, common)?;
+		}
+		self.warn_unreadable(stall_dir, status_l, status_r, common)?;
+		if conflict && action == Action::Stop {
+			return Err(self.conflict_error());
 		}
 
-		if matches!(action, Action::Force | Action::Copy) {
+		if matches!(action, Action::Force | Action::Copy | Action::Merge) {
 			let mut full_local = stall_dir.to_path_buf();
 			full_local.push(self.local);
 
-			let copy_method = if dry_run {
-				CopyMethod::None
+			for dir in &missing_dirs {
+				Self::write_mkdir(out, dir, common)?;
+				if !dry_run { create_remote_dir(dir, privileged, options)?; }
+			}
+
+			if let Some(trash) = trash {
+				if !dry_run && status_r != Absent {
+					let _ = trash.copy_in(self.remote)?;
+				}
+			}
+
+			if dry_run {
+				event!(Level::DEBUG, "no-run flag was specified: \
+					Not copying data from {:?} to {:?}", full_local, self.remote);
+				if action != Action::Merge
+					&& remote::scheme(self.remote).is_none()
+				{
+					self.write_transforms(out, Direction::Distribute, common)?;
+				}
+			} else if action == Action::Merge {
+				self.merge(&full_local, self.remote,
+					self.merge_base(stall_dir)?.as_deref(), options, common)?;
+				state.record_distributed(self.local);
+				state.record_file_id(self.remote);
+			} else if privileged {
+				self.distribute_privileged(stall_dir, &full_local, options)?;
+				// Scripted copies haven't happened yet.
+				if options.privilege.script.is_none() {
+					state.record_distributed(self.local);
+					state.record_file_id(self.remote);
+				}
 			} else {
-				CopyMethod::Subprocess
-			};
+				self.push(&full_local, options, common)?;
+				state.record_distributed(self.local);
+				state.record_file_id(self.remote);
+			}
+		}
 
-			copy(full_local.as_path(), self.remote, copy_method)?;
+		Ok(Outcome::of(action, status_l, status_r))
+	}
+
+	/// Returns the action to take after asking the user to confirm that the
+	/// `target` file should be overwritten by the `source` file, if a
+	/// [`Confirm`] is given. Copies to absent targets and dry runs are not
+	/// confirmed, and declined copies are skipped.
+	///
+	/// [`Confirm`]: ../confirm/struct.Confirm.html
+	#[allow(clippy::too_many_arguments)]
+	fn confirm_action(
+		&self,
+		out: &mut dyn Write,
+		action: Action,
+		status_target: Status,
+		target: &Path,
+		source: &Path,
+		confirm: Option<&mut Confirm<'_>>,
+		dry_run: bool)
+		-> Result<Action, StallError>
+	{
+		let Some(confirm) = confirm else { return Ok(action) };
+		if dry_run
+			|| status_target == Status::Absent
+			|| !matches!(action, Action::Force | Action::Copy)
+		{
+			return Ok(action);
+		}
+		event!(Level::DEBUG, "Confirming overwrite for {:?}", self.local);
+		if confirm.overwrite(out, target, source)? {
+			Ok(action)
+		} else {
+			Ok(Action::Skip)
 		}
+	}
 
-		Ok(())
+	/// Returns `true` if both of the entry's files have been modified since the
+	/// entry was last collected or distributed. If the entry has never been
+	/// copied, files with differing modification times are treated as
+	/// conflicted. Only local remote files are checked for conflicts.
+	fn conflicted(&self, stall_dir: &Path, status_l: Status, state: &StateFile)
+		-> bool
+	{
+		if !matches!(status_l, Status::Newer | Status::Older)
+			|| remote::scheme(self.remote).is_some()
+		{
+			return false;
+		}
+		let modified = |path: &Path| std::fs::metadata(path)
+			.and_then(|m| m.modified())
+			.ok();
+		let (Some(modified_l), Some(modified_r)) = (
+			modified(&stall_dir.join(self.local)),
+			modified(self.remote))
+			else { return false };
 
+		// Without a recorded copy time, files with different contents and
+		// modification times may both have changed.
+		state.copied(self.local).map_or_else(
+			|| modified_l != modified_r,
+			|copied| modified_l > copied && modified_r > copied)
 	}
 
-	/// Writes the header for the output of `write_status` into `out`.
-	pub(in crate) fn write_status_header(
+	/// Returns the action to take for a conflicted entry using its
+	/// [`ConflictPolicy`], given the `action` determined by comparing
+	/// modification times. The `collecting` parameter gives the direction of
+	/// the copy. Failing policies return [`Action::Stop`].
+	///
+	/// [`ConflictPolicy`]: enum.ConflictPolicy.html
+	/// [`Action::Stop`]: enum.Action.html#variant.Stop
+	fn resolve_conflict(
+		&self,
 		out: &mut dyn Write,
+		action: Action,
+		options: &CopyOptions,
+		collecting: bool)
+		-> Result<Action, StallError>
+	{
+		let policy = options.conflict
+			.or(self.flags.conflict)
+			.unwrap_or(options.default_conflict);
+		event!(Level::DEBUG, "Resolving conflict for {:?} using {} policy",
+			self.local, policy);
+
+		let overwrite = match policy {
+			ConflictPolicy::NewestWins   => return Ok(action),
+			ConflictPolicy::Fail         => return Ok(Action::Stop),
+			ConflictPolicy::Merge if options.merge_tool.is_some() => {
+				return Ok(Action::Merge);
+			},
+			ConflictPolicy::Merge        => return Err(StallError::Conflict {
+				reason: "no merge tool is configured".to_owned(),
+				subject: self.local.display().to_string(),
+				hint: Some("Use `stall prefs set merge_tool <TOOL>` to \
+					configure one.".to_owned()),
+			}),
+			ConflictPolicy::PreferLocal  => !collecting,
+			ConflictPolicy::PreferRemote => collecting,
+			ConflictPolicy::Prompt       => {
+				let target = if collecting {
+					self.local
+				} else {
+					self.remote
+				};
+				write!(out, "Both {} and {} changed since they were last \
+					copied. Overwrite {}? [y/n] ",
+					self.local.display(),
+					self.remote.display(),
+					target.display())?;
+				out.flush()?;
+				let mut line = String::new();
+				if std::io::stdin().read_line(&mut line)? == 0 {
+					writeln!(out)?;
+				}
+				matches!(line.trim().to_lowercase().as_str(), "y" | "yes")
+			},
+		};
+		Ok(if overwrite { Action::Copy } else { Action::Skip })
+	}
+
+	/// Merges the entry's stalled file at `full_local` and its remote file using
+	/// the [`MergeTool`] of the given [`CopyOptions`], writing the merged
+	/// result to `target`. The `base` file is given to the tool as their common
+	/// ancestor, if there is one.
+	///
+	/// [`MergeTool`]: ../mergetool/struct.MergeTool.html
+	/// [`CopyOptions`]: struct.CopyOptions.html
+	fn merge(
+		&self,
+		full_local: &Path,
+		target: &Path,
+		base: Option<&Path>,
+		options: &CopyOptions,
 		common: &CommonOptions)
-		-> std::io::Result<()>
+		-> Result<(), StallError>
 	{
-		if common.quiet { return Ok(()); }
+		let tool = options.merge_tool
+			.as_ref()
+			.expect("merge tool for merge action");
+		event!(Level::INFO, "Merging {:?} and {:?} into {:?}",
+			full_local, self.remote, target);
+		tool.merge(full_local, self.remote, base, target, common.durable)
+	}
 
-		if common.color.enabled() {
-			writeln!(out, "    {:<6} {:<6} {}", 
-				"LOCAL".bright_white().bold(),
-				"REMOTE".bright_white().bold(),
-				"FILE".bright_white().bold())
-		} else {
-			writeln!(out, "    LOCAL  REMOTE FILE")
+	/// Returns the path of the most recently saved version of the entry's
+	/// stalled file, to be used as the base of a merge, or `None` if no
+	/// versions have been saved.
+	fn merge_base(&self, stall_dir: &Path)
+		-> Result<Option<PathBuf>, StallError>
+	{
+		let version = Versions::open(stall_dir, self.local).list()?.pop();
+		Ok(version.map(|version| version.path))
+	}
+
+	/// Saves the entry's stalled file as a previous version before it is
+	/// overwritten, if the given [`CopyOptions`] have a version retention
+	/// policy, and then deletes the versions which are no longer retained.
+	///
+	/// [`CopyOptions`]: struct.CopyOptions.html
+	fn save_version(
+		&self,
+		stall_dir: &Path,
+		status_l: Status,
+		options: &CopyOptions)
+		-> Result<(), StallError>
+	{
+		let Some(retention) = options.version_retention else { return Ok(()) };
+		if status_l == Status::Absent { return Ok(()); }
+
+		let versions = Versions::open(stall_dir, self.local);
+		let _ = versions.save(&stall_dir.join(self.local))?;
+		let _ = versions.prune(retention)?;
+		Ok(())
+	}
+
+	/// Copies the entry's stalled file to its remote location, preserving the
+	/// requested attributes and applying the entry's transforms.
+	fn push(
+		&self,
+		full_local: &Path,
+		options: &CopyOptions,
+		common: &CommonOptions)
+		-> Result<(), StallError>
+	{
+		let used = remote::backend(self.remote)?
+			.push(full_local, self.remote, options, common.durable)?;
+		event!(Level::INFO, "Copied {:?} to {:?} using {} copy",
+			full_local, self.remote, used);
+		preserve(full_local, self.remote, options, common)?;
+		if remote::scheme(self.remote).is_none() {
+			self.pipeline().apply(self.remote, Direction::Distribute)?;
+		}
+		Ok(())
+	}
+
+	/// Copies the entry's stalled file to its remote location with elevated
+	/// privileges. If the entry has transforms, they are applied to a staged
+	/// copy of the file, which is kept if the copy is scripted.
+	fn distribute_privileged(
+		&self,
+		stall_dir: &Path,
+		full_local: &Path,
+		options: &CopyOptions)
+		-> Result<(), StallError>
+	{
+		let privilege = &options.privilege;
+		let pipeline = self.pipeline();
+		if pipeline.is_empty(Direction::Distribute) {
+			return privilege.copy(full_local, self.remote);
+		}
+
+		let staged = stall_dir.join(Privilege::STAGED_PATH).join(self.local);
+		if staged.exists() { remove_path(&staged)?; }
+		if let Some(parent) = staged.parent() {
+			std::fs::create_dir_all(parent)
+				.with_path("failed to create staging directory", parent)?;
+		}
+		copy(full_local, &staged, CopyMethod::Direct)?;
+		pipeline.apply(&staged, Direction::Distribute)?;
+		privilege.copy(&staged, self.remote)?;
+		if privilege.script.is_none() { remove_path(&staged)?; }
+		Ok(())
+	}
+
+	/// Returns the error for a conflicted entry with the `fail` policy.
+	fn conflict_error(&self) -> StallError {
+		StallError::Conflict {
+			reason: "both files changed since they were last copied".to_owned(),
+			subject: self.local.display().to_string(),
+			hint: Some("Use --conflict option to choose which file to keep."
+				.to_owned()),
+		}
+	}
+
+	/// Returns the [`Pipeline`] of transforms applied to the entry's files.
+	///
+	/// [`Pipeline`]: ../transform/struct.Pipeline.html
+	#[must_use]
+	pub const fn pipeline(&self) -> Pipeline<'a> {
+		Pipeline::new(self.flags, self.transforms)
+	}
+
+	/// Returns the paths of the files within the entry's remote directory which
+	/// are ignored by git, relative to the directory. Returns `None` if the
+	/// remote is not a local directory within a git work tree, or if the entry
+	/// has the `no_gitignore` flag.
+	fn git_ignored(&self) -> Option<Vec<PathBuf>> {
+		if self.flags.no_gitignore
+			|| remote::scheme(self.remote).is_some()
+			|| !self.remote.is_dir()
+		{
+			return None;
+		}
+		ignore::git_ignored(self.remote)
+	}
+
+	/// Emits an [`UnreadableFile`] warning for each of the entry's files whose
+	/// status could not be determined.
+	///
+	/// [`UnreadableFile`]: ../warning/enum.Warning.html#variant.UnreadableFile
+	fn warn_unreadable(
+		&self,
+		stall_dir: &Path,
+		status_l: Status,
+		status_r: Status,
+		common: &CommonOptions)
+		-> Result<(), StallError>
+	{
+		if status_l == Status::Error {
+			warn(Warning::UnreadableFile {
+				path: stall_dir.join(self.local),
+			}, common)?;
 		}
+		if status_r == Status::Error {
+			warn(Warning::UnreadableFile {
+				path: self.remote.to_path_buf(),
+			}, common)?;
+		}
+		Ok(())
 	}
 
 	/// Writes the header for the output of `write_status_action` into `out`.
@@ -218,12 +716,13 @@ impl<'a> Entry<'a> {
 		}
 	}
 
-	/// Writes the status of the entry into `out`.
-	pub(in crate) fn write_status(
+	/// Writes the status of the entry and an `Action` selection into `out`.
+	pub(in crate) fn write_status_action(
 		&self,
 		out: &mut dyn Write,
 		status_l: Status,
 		status_r: Status,
+		action: Action,
 		common: &CommonOptions)
 		-> std::io::Result<()>
 	{
@@ -234,31 +733,54 @@ impl<'a> Entry<'a> {
 		write!(out, " ")?;
 		status_r.write(out, common)?;
 		write!(out, " ")?;
+		action.write(out, common)?;
+		write!(out, " ")?;
 		self.write_paths(out, common)?;
 		writeln!(out)
 	}
 
-	/// Writes the status of the entry and an `Action` selection into `out`.
-	pub(in crate) fn write_status_action(
+	/// Writes a directory creation action for the output of
+	/// `write_status_action` into `out`.
+	fn write_mkdir(
+		out: &mut dyn Write,
+		dir: &Path,
+		common: &CommonOptions)
+		-> std::io::Result<()>
+	{
+		if common.quiet { return Ok(()); }
+
+		if common.color.enabled() {
+			writeln!(out, "    {:<6} {:<6} {:<6} {}", "", "",
+				"mkdir".bright_green(),
+				dir.display())
+		} else {
+			writeln!(out, "    {:<6} {:<6} {:<6} {}", "", "", "mkdir",
+				dir.display())
+		}
+	}
+
+	/// Writes the transforms applied to the entry's files in the given
+	/// direction for the output of `write_status_action` into `out`.
+	fn write_transforms(
 		&self,
 		out: &mut dyn Write,
-		status_l: Status,
-		status_r: Status,
-		action: Action,
+		direction: Direction,
 		common: &CommonOptions)
 		-> std::io::Result<()>
 	{
 		if common.quiet { return Ok(()); }
 
-		write!(out, "    ")?;
-		status_l.write(out, common)?;
-		write!(out, " ")?;
-		status_r.write(out, common)?;
-		write!(out, " ")?;
-		action.write(out, common)?;
-		write!(out, " ")?;
-		self.write_paths(out, common)?;
-		writeln!(out)
+		for stage in self.pipeline().describe(direction) {
+			if common.color.enabled() {
+				writeln!(out, "    {:<6} {:<6} {:<6} {}", "", "",
+					"filter".bright_cyan(),
+					stage)?;
+			} else {
+				writeln!(out, "    {:<6} {:<6} {:<6} {}", "", "", "filter",
+					stage)?;
+			}
+		}
+		Ok(())
 	}
 
 	/// Writes the paths of the entry into `out`.
@@ -291,6 +813,631 @@ impl<'a> Entry<'a> {
 
 
 
+////////////////////////////////////////////////////////////////////////////////
+// CopyOptions
+////////////////////////////////////////////////////////////////////////////////
+/// Options for copying files between the stall directory and their remote
+/// locations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CopyOptions {
+	/// The file attributes to copy along with the file contents.
+	pub preserve: Vec<Attribute>,
+	/// The method for copying file contents.
+	pub mode: CopyMode,
+	/// The maximum rate at which to copy file contents, if any.
+	pub throttle: Option<Throttle>,
+	/// The size of the buffer for copying file contents in-process.
+	pub buffer_size: BufferSize,
+	/// The policy for retrying copies which fail with transient errors.
+	pub retry: Retry,
+	/// Verify each copy by comparing the hashes of the copied file and its
+	/// copy, copying it again once if they differ.
+	pub verify: bool,
+	/// The order in which to process entries.
+	pub order: EntryOrder,
+	/// Process archived entries, which are skipped otherwise.
+	pub include_archived: bool,
+	/// The rules for files within directories which should not be copied.
+	pub ignore: IgnoreRules,
+	/// The policy for resolving conflicts, overriding the policies of the
+	/// entries.
+	pub conflict: Option<ConflictPolicy>,
+	/// The policy for resolving conflicts of entries without a policy.
+	pub default_conflict: ConflictPolicy,
+	/// The tool for merging conflicted files with the `merge` policy.
+	pub merge_tool: Option<MergeTool>,
+	/// The policy for keeping previous versions of stalled files overwritten
+	/// when collecting. No versions are kept if it is `None`.
+	pub version_retention: Option<Retention>,
+	/// The options for copying files with elevated privileges when
+	/// distributing.
+	pub privilege: Privilege,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Overwrite
+////////////////////////////////////////////////////////////////////////////////
+/// The safeguards to bypass when copying an entry's files.
+///
+/// Each safeguard is bypassed separately, so that files may be copied over
+/// newer files without also attempting to copy over files which are
+/// unreadable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Overwrite {
+	/// Copy files even if they are unmodified.
+	pub unmodified: bool,
+	/// Copy files over newer files.
+	pub newer: bool,
+	/// Copy files over files whose modification time can't be read.
+	pub errors: bool,
+}
+
+impl Overwrite {
+	/// Overwrites unmodified and newer files, as the `--force` option does.
+	pub const FORCE: Self = Self {
+		unmodified: true,
+		newer: true,
+		errors: false,
+	};
+
+	/// Returns `true` if any of the safeguards are bypassed.
+	#[must_use]
+	pub const fn any(self) -> bool {
+		self.unmodified || self.newer || self.errors
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Throttle
+////////////////////////////////////////////////////////////////////////////////
+/// A limit on the rate at which file contents are copied.
+///
+/// Throttled copies are performed in-process in small chunks rather than by the
+/// kernel or a copy command, so that the rate can be controlled. Reflink clones copy no file contents, so
+/// they are never throttled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Throttle {
+	/// The maximum number of bytes to copy per second.
+	bytes_per_second: u64,
+}
+
+impl Throttle {
+	/// Constructs a new `Throttle` limiting copies to the given number of
+	/// megabytes per second. Returns `None` if the rate is not positive.
+	#[must_use]
+	#[allow(clippy::cast_possible_truncation)]
+	#[allow(clippy::cast_sign_loss)]
+	pub fn from_megabytes(megabytes_per_second: f64) -> Option<Self> {
+		let bytes_per_second = (megabytes_per_second * 1_000_000.0).round();
+		(bytes_per_second.is_finite() && bytes_per_second >= 1.0)
+			.then_some(Self { bytes_per_second: bytes_per_second as u64 })
+	}
+
+	/// Returns the maximum number of bytes to copy per second.
+	#[must_use]
+	pub const fn bytes_per_second(self) -> u64 {
+		self.bytes_per_second
+	}
+
+	/// Returns the maximum number of megabytes to copy per second.
+	#[must_use]
+	pub fn megabytes_per_second(self) -> f64 {
+		self.bytes_per_second as f64 / 1_000_000.0
+	}
+
+	/// Returns how long copying the given number of bytes should take.
+	fn duration(self, bytes: u64) -> Duration {
+		Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64)
+	}
+}
+
+impl std::str::FromStr for Throttle {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		text.trim()
+			.parse()
+			.ok()
+			.and_then(Self::from_megabytes)
+			.ok_or_else(|| format!("invalid throttle rate {text:?}: \
+				expected a positive number of megabytes per second"))
+	}
+}
+
+impl std::fmt::Display for Throttle {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} MB/s", self.megabytes_per_second())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// BufferSize
+////////////////////////////////////////////////////////////////////////////////
+/// The size of the buffer used to copy file contents in-process.
+///
+/// The buffer is only used for throttled copies, and on platforms where file
+/// contents can't be copied by the kernel. Sizes are rounded up to a whole
+/// number of pages.
+///
+/// Buffer sizes are serialized as strings: a number of bytes, optionally
+/// followed by `K` for kibibytes or `M` for mebibytes, such as `256K`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BufferSize {
+	/// The number of bytes in the buffer.
+	bytes: usize,
+}
+
+impl BufferSize {
+	/// The size of a page, which buffer sizes are a multiple of.
+	const PAGE_SIZE: usize = 4 * 1024;
+
+	/// The largest supported buffer size.
+	const MAX_BYTES: usize = 64 * 1024 * 1024;
+
+	/// The default buffer size.
+	pub const DEFAULT: Self = Self { bytes: 1024 * 1024 };
+
+	/// Constructs a new `BufferSize` with at least the given number of bytes.
+	/// Returns `None` if the size is zero or larger than 64 MiB.
+	#[must_use]
+	pub const fn from_bytes(bytes: usize) -> Option<Self> {
+		if bytes == 0 || bytes > Self::MAX_BYTES { return None; }
+		Some(Self { bytes: bytes.div_ceil(Self::PAGE_SIZE) * Self::PAGE_SIZE })
+	}
+
+	/// Returns the number of bytes in the buffer.
+	#[must_use]
+	pub const fn bytes(self) -> usize {
+		self.bytes
+	}
+}
+
+impl Default for BufferSize {
+	fn default() -> Self {
+		Self::DEFAULT
+	}
+}
+
+impl std::str::FromStr for BufferSize {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let trimmed = text.trim();
+		let (number, unit) = match trimmed.char_indices().last() {
+			Some((i, 'k' | 'K')) => (&trimmed[..i], 1024),
+			Some((i, 'm' | 'M')) => (&trimmed[..i], 1024 * 1024),
+			_                    => (trimmed, 1),
+		};
+		number
+			.trim()
+			.parse::<usize>()
+			.ok()
+			.and_then(|n| n.checked_mul(unit))
+			.and_then(Self::from_bytes)
+			.ok_or_else(|| format!("invalid buffer size {text:?}: expected \
+				a number of bytes up to 64M, optionally followed by K or M"))
+	}
+}
+
+impl std::fmt::Display for BufferSize {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.bytes.is_multiple_of(1024 * 1024) {
+			write!(f, "{}M", self.bytes / (1024 * 1024))
+		} else {
+			write!(f, "{}K", self.bytes / 1024)
+		}
+	}
+}
+
+impl TryFrom<String> for BufferSize {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		value.parse()
+	}
+}
+
+impl From<BufferSize> for String {
+	fn from(buffer_size: BufferSize) -> Self {
+		buffer_size.to_string()
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Retry
+////////////////////////////////////////////////////////////////////////////////
+/// A policy for retrying copies which fail with transient IO errors, such as
+/// a resource which is temporarily unavailable, a lost network file system
+/// connection, or a sharing violation on windows.
+///
+/// The delay before each retry is double the delay before the previous one,
+/// up to one minute. Copies are not retried once the command is cancelled.
+///
+/// Retry policies are serialized as strings: a number of retries, optionally
+/// followed by `x` and the delay before the first retry in seconds or
+/// milliseconds, such as `5x2s` or `3x500ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Retry {
+	/// The number of times to retry a failed copy.
+	retries: u32,
+	/// The delay before the first retry.
+	delay: Duration,
+}
+
+impl Retry {
+	/// The longest delay between retries.
+	const MAX_DELAY: Duration = Duration::from_mins(1);
+
+	/// The default retry policy, which retries failed copies three times,
+	/// after one, two, and four seconds.
+	pub const DEFAULT: Self = Self {
+		retries: 3,
+		delay: Duration::from_secs(1),
+	};
+
+	/// Constructs a new `Retry` policy with the given number of retries and
+	/// delay before the first retry.
+	#[must_use]
+	pub const fn new(retries: u32, delay: Duration) -> Self {
+		Self { retries, delay }
+	}
+
+	/// Returns the number of times to retry a failed copy.
+	#[must_use]
+	pub const fn retries(self) -> u32 {
+		self.retries
+	}
+
+	/// Returns the delay before the first retry.
+	#[must_use]
+	pub const fn delay(self) -> Duration {
+		self.delay
+	}
+
+	/// Returns the delay before the retry with the given zero-based index.
+	fn delay_before(self, retry: u32) -> Duration {
+		self.delay
+			.saturating_mul(2_u32.saturating_pow(retry))
+			.min(Self::MAX_DELAY)
+	}
+
+	/// Runs the given copy of `source`, retrying it while it fails with a
+	/// transient error. Each failed attempt is logged at the info level, so
+	/// that it is reported in verbose mode.
+	fn run<T, F>(self, source: &Path, mut copy: F) -> Result<T, StallError>
+		where F: FnMut() -> Result<T, StallError>
+	{
+		let mut retry = 0;
+		loop {
+			match copy() {
+				Err(e) if e.is_transient()
+					&& retry < self.retries
+					&& crate::cancel::cancelled().is_none() =>
+				{
+					let delay = self.delay_before(retry);
+					retry += 1;
+					event!(Level::INFO, "Attempt {} of {} to copy {:?} \
+						failed: {}. Retrying in {:?}.",
+						retry,
+						self.retries + 1,
+						source,
+						e,
+						delay);
+					std::thread::sleep(delay);
+				},
+				Err(e) => return Err(e),
+				Ok(value) => {
+					if retry > 0 {
+						event!(Level::INFO, "Copied {:?} on attempt {} of {}.",
+							source,
+							retry + 1,
+							self.retries + 1);
+					}
+					return Ok(value);
+				},
+			}
+		}
+	}
+}
+
+impl Default for Retry {
+	fn default() -> Self {
+		Self::DEFAULT
+	}
+}
+
+impl std::str::FromStr for Retry {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("invalid retry policy {text:?}: expected a \
+			number of retries, optionally followed by x and a delay in s or \
+			ms, such as 5x2s");
+		let trimmed = text.trim();
+		let (retries, delay) = match trimmed.split_once(['x', 'X']) {
+			Some((retries, delay)) => (retries, Some(delay.trim())),
+			None                   => (trimmed, None),
+		};
+		let retries = retries.trim().parse().ok().ok_or_else(invalid)?;
+		let delay = match delay {
+			None => Self::DEFAULT.delay,
+			Some(delay) => {
+				let (number, millis) = delay.strip_suffix("ms").map_or_else(
+					|| (delay.strip_suffix('s').unwrap_or(delay), 1000),
+					|number| (number, 1));
+				number
+					.trim()
+					.parse::<u64>()
+					.ok()
+					.and_then(|n| n.checked_mul(millis))
+					.map(Duration::from_millis)
+					.filter(|delay| *delay <= Self::MAX_DELAY)
+					.ok_or_else(invalid)?
+			},
+		};
+		Ok(Self { retries, delay })
+	}
+}
+
+impl std::fmt::Display for Retry {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.delay.subsec_millis() == 0 {
+			write!(f, "{}x{}s", self.retries, self.delay.as_secs())
+		} else {
+			write!(f, "{}x{}ms", self.retries, self.delay.as_millis())
+		}
+	}
+}
+
+impl TryFrom<String> for Retry {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		value.parse()
+	}
+}
+
+impl From<Retry> for String {
+	fn from(retry: Retry) -> Self {
+		retry.to_string()
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CopyMode
+////////////////////////////////////////////////////////////////////////////////
+/// The method for copying file contents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(clap::ArgEnum)]
+pub enum CopyMode {
+	/// Clone files using copy-on-write reflinks where the file system
+	/// supports them, and copy their contents otherwise.
+	#[default]
+	Auto,
+	/// Clone files using copy-on-write reflinks, failing if the file system
+	/// doesn't support them.
+	Reflink,
+	/// Copy file contents.
+	Standard,
+}
+
+impl std::fmt::Display for CopyMode {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Auto     => write!(f, "auto"),
+			Self::Reflink  => write!(f, "reflink"),
+			Self::Standard => write!(f, "standard"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// EntryOrder
+////////////////////////////////////////////////////////////////////////////////
+/// The order in which entries are processed and reported.
+///
+/// Entries are ordered by their local paths by default, and the other orders
+/// are stable, so entries with equal keys remain ordered by their local paths.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[derive(clap::ArgEnum)]
+pub enum EntryOrder {
+	/// Order entries by their paths within the stall directory.
+	#[default]
+	Local,
+	/// Order entries by their remote paths.
+	Remote,
+	/// Order entries by their most recent modification time, with the newest
+	/// entries last.
+	Mtime,
+	/// Order entries by the size of their largest file, with the largest
+	/// entries last.
+	Size,
+}
+
+impl EntryOrder {
+	/// Sorts the given items by the entries returned by `entry`, which are
+	/// assumed to be ordered by their local paths.
+	pub fn sort<'e, T, F>(self, items: &mut [T], stall_dir: &Path, entry: F)
+		where F: Fn(&T) -> &Entry<'e>
+	{
+		match self {
+			Self::Local  => (),
+			Self::Remote => items.sort_by_key(|item| entry(item).remote),
+			Self::Mtime  => items
+				.sort_by_cached_key(|item| entry(item).modified(stall_dir)),
+			Self::Size   => items
+				.sort_by_cached_key(|item| entry(item).size(stall_dir)),
+		}
+	}
+}
+
+impl std::fmt::Display for EntryOrder {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Local  => write!(f, "local"),
+			Self::Remote => write!(f, "remote"),
+			Self::Mtime  => write!(f, "mtime"),
+			Self::Size   => write!(f, "size"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ConflictPolicy
+////////////////////////////////////////////////////////////////////////////////
+/// The policy for copying an entry's files when both have been modified since
+/// the entry was last collected or distributed.
+///
+/// Policies are serialized as strings, as untagged stall file entries can't
+/// hold optional enum variants.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+#[derive(clap::ArgEnum)]
+pub enum ConflictPolicy {
+	/// Keep the stalled file, overwriting the remote file when distributing.
+	PreferLocal,
+	/// Keep the remote file, overwriting the stalled file when collecting.
+	PreferRemote,
+	/// Copy the most recently modified file, as if there were no conflict.
+	#[default]
+	NewestWins,
+	/// Ask whether to overwrite the file.
+	Prompt,
+	/// Merge the files using the `merge_tool` preference, writing the result
+	/// to the file being copied over.
+	Merge,
+	/// Fail without copying either file.
+	Fail,
+}
+
+impl std::fmt::Display for ConflictPolicy {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::PreferLocal  => write!(f, "prefer-local"),
+			Self::PreferRemote => write!(f, "prefer-remote"),
+			Self::NewestWins   => write!(f, "newest-wins"),
+			Self::Prompt       => write!(f, "prompt"),
+			Self::Merge        => write!(f, "merge"),
+			Self::Fail         => write!(f, "fail"),
+		}
+	}
+}
+
+impl TryFrom<String> for ConflictPolicy {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		<Self as clap::ArgEnum>::from_str(&value, false)
+	}
+}
+
+impl From<ConflictPolicy> for String {
+	fn from(policy: ConflictPolicy) -> Self {
+		policy.to_string()
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Attribute
+////////////////////////////////////////////////////////////////////////////////
+/// A file attribute which is not copied with the file contents by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(clap::ArgEnum)]
+pub enum Attribute {
+	/// Extended attributes, such as `SELinux` labels or macOS quarantine flags.
+	Xattr,
+	/// Access control lists.
+	Acl,
+}
+
+impl std::fmt::Display for Attribute {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Xattr => write!(f, "xattr"),
+			Self::Acl   => write!(f, "acl"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// EntryFlags
+////////////////////////////////////////////////////////////////////////////////
+/// Flags restricting how an entry's files are copied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct EntryFlags {
+	/// Prevents `collect` from overwriting the stalled file.
+	pub pinned: bool,
+	/// Prevents `distribute` from overwriting the remote file.
+	pub readonly: bool,
+	/// Prevents `collect` from skipping the files of a remote directory which
+	/// are ignored by git.
+	pub no_gitignore: bool,
+	/// The policy for copying the entry's files when both have been modified.
+	pub conflict: Option<ConflictPolicy>,
+	/// Marks the entry's files as binary, so `status` never compares their
+	/// contents and `diff` doesn't print them.
+	pub binary: bool,
+	/// The line ending of the entry's remote files. Collected files are
+	/// normalized to `\n` unless line endings are preserved.
+	pub eol: LineEnding,
+	/// Makes `distribute` copy the entry's files with elevated privileges.
+	pub privileged: bool,
+	/// Keeps the entry for record-keeping, excluding it from `status`,
+	/// `collect`, and `distribute` unless archived entries are included.
+	pub archived: bool,
+}
+
+impl EntryFlags {
+	/// Returns the text of the pinned and readonly flags, or an empty string
+	/// if neither are set.
+	#[must_use]
+	pub const fn label(self) -> &'static str {
+		match (self.pinned, self.readonly) {
+			(true,  true)  => "pinned,readonly",
+			(true,  false) => "pinned",
+			(false, true)  => "readonly",
+			(false, false) => "",
+		}
+	}
+
+	/// Returns the text of all of the flags which are set, or `-` if none
+	/// are set.
+	#[must_use]
+	pub fn description(self) -> String {
+		let conflict = self.conflict
+			.map(|policy| format!("conflict={policy}"));
+		let eol = (!self.eol.is_preserve())
+			.then(|| format!("eol={}", self.eol));
+		let mut labels: Vec<&str> = vec![self.label()];
+		if self.no_gitignore { labels.push("no-gitignore"); }
+		if self.binary { labels.push("binary"); }
+		if self.privileged { labels.push("privileged"); }
+		if self.archived { labels.push("archived"); }
+		if let Some(conflict) = &conflict { labels.push(conflict); }
+		if let Some(eol) = &eol { labels.push(eol); }
+		labels.retain(|label| !label.is_empty());
+		if labels.is_empty() { "-".to_owned() } else { labels.join(",") }
+	}
+}
+
+
+
 ////////////////////////////////////////////////////////////////////////////////
 // Status
 ////////////////////////////////////////////////////////////////////////////////
@@ -312,6 +1459,30 @@ pub enum Status {
 }
 
 impl Status {
+	/// Returns the status text.
+	#[must_use]
+	pub const fn label(self) -> &'static str {
+		match self {
+			Self::Error  => "error",
+			Self::Absent => "absent",
+			Self::Exists => "exists",
+			Self::Newer  => "newer",
+			Self::Older  => "older",
+			Self::Same   => "same",
+		}
+	}
+
+	/// Returns the color to use for the status text.
+	#[must_use]
+	pub const fn color(self) -> Color {
+		match self {
+			Self::Error                 => Color::BrightRed,
+			Self::Absent | Self::Older  => Color::BrightYellow,
+			Self::Exists | Self::Newer  => Color::BrightGreen,
+			Self::Same                  => Color::BrightWhite,
+		}
+	}
+
 	/// Write the status text into `out`.
 	fn write(
 		self,
@@ -322,23 +1493,9 @@ impl Status {
 		if common.quiet { return Ok(()); }
 
 		if common.color.enabled() {
-			write!(out, "{:<6}", match self {
-				Status::Error  => "error".bright_red(),
-				Status::Absent => "absent".bright_yellow(),
-				Status::Exists => "exists".bright_green(),
-				Status::Newer  => "newer".bright_green(),
-				Status::Older  => "older".bright_yellow(),
-				Status::Same   => "same".bright_white(),
-			})
+			write!(out, "{:<6}", self.label().color(self.color()))
 		} else {
-			write!(out, "{:<6}", match self {
-				Status::Error  => "error",
-				Status::Absent => "absent",
-				Status::Exists => "exists",
-				Status::Newer  => "newer",
-				Status::Older  => "older",
-				Status::Same   => "same",
-			})
+			write!(out, "{:<6}", self.label())
 		}
 	}
 }
@@ -357,6 +1514,10 @@ pub enum Action {
 	Copy,
 	/// The file will be skipped.
 	Skip,
+	/// The file will be kept because the entry is pinned or readonly.
+	Keep,
+	/// The files will be merged.
+	Merge,
 	/// The command was stopped.
 	Stop,
 }
@@ -373,17 +1534,21 @@ impl Action {
 
 		if common.color.enabled() {
 			write!(out, "{:<6}", match self {
-				Action::Force => "force".bright_green(),
-				Action::Copy  => "copy".bright_green(),
-				Action::Skip  => "skip".bright_white(),
-				Action::Stop  => "stop".bright_red(),
+				Self::Force => "force".bright_green(),
+				Self::Copy  => "copy".bright_green(),
+				Self::Skip  => "skip".bright_white(),
+				Self::Keep  => "keep".bright_yellow(),
+				Self::Merge => "merge".bright_cyan(),
+				Self::Stop  => "stop".bright_red(),
 			})
 		} else {
 			write!(out, "{:<6}", match self {
-				Action::Force => "force",
-				Action::Copy  => "copy",
-				Action::Skip  => "skip",
-				Action::Stop  => "stop",
+				Self::Force => "force",
+				Self::Copy  => "copy",
+				Self::Skip  => "skip",
+				Self::Keep  => "keep",
+				Self::Merge => "merge",
+				Self::Stop  => "stop",
 			})
 		}
 	}
@@ -391,38 +1556,538 @@ impl Action {
 
 
 
+////////////////////////////////////////////////////////////////////////////////
+// Outcome
+////////////////////////////////////////////////////////////////////////////////
+/// The outcome of copying an entry's files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+	/// The files were copied, or would be copied in a dry run.
+	Copied,
+	/// The files were not copied because they are the same.
+	Unchanged,
+	/// The files were not copied for another reason, such as the destination
+	/// file being newer or the entry being pinned.
+	Skipped,
+}
+
+impl Outcome {
+	/// Returns the outcome of taking the given [`Action`] for files with the
+	/// given statuses.
+	///
+	/// [`Action`]: enum.Action.html
+	fn of(action: Action, status_l: Status, status_r: Status) -> Self {
+		match action {
+			Action::Force | Action::Copy | Action::Merge => Self::Copied,
+			_ if status_l == Status::Same
+				&& status_r == Status::Same => Self::Unchanged,
+			_ => Self::Skipped,
+		}
+	}
+}
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Directory creation functions.
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the ancestor directories of `path` which do not exist, ordered from
+/// outermost to innermost.
+pub(in crate) fn missing_dirs(path: &Path) -> Vec<PathBuf> {
+	let mut missing: Vec<PathBuf> = path
+		.ancestors()
+		.skip(1)
+		.take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+		.map(Path::to_path_buf)
+		.collect();
+	missing.reverse();
+	missing
+}
+
+/// Creates the directory at `dir`, whose parent must exist. On unix systems,
+/// the new directory is given the same permissions as its parent.
+pub(in crate) fn create_dir(dir: &Path) -> Result<(), StallError> {
+	event!(Level::DEBUG, "Creating directory {:?}", dir);
+	let mut builder = std::fs::DirBuilder::new();
+
+	let parent = match dir.parent() {
+		Some(parent) if !parent.as_os_str().is_empty() => parent,
+		_ => Path::new("."),
+	};
+	platform::inherit_permissions(&mut builder, parent)?;
+
+	builder.create(dir)
+		.with_path("failed to create directory", dir)
+}
+
+/// Creates the missing remote directory at `dir` using its remote backend, or
+/// with elevated privileges if `privileged` is set.
+fn create_remote_dir(dir: &Path, privileged: bool, options: &CopyOptions)
+	-> Result<(), StallError>
+{
+	if privileged {
+		options.privilege.create_dir(dir)
+	} else {
+		remote::backend(dir)?.create_dir(dir)
+	}
+}
+
+
 ////////////////////////////////////////////////////////////////////////////////
 // File copy function.
 ////////////////////////////////////////////////////////////////////////////////
 /// Copies a file from `source` to `target` using the given `CopyMethod`
-fn copy(source: &Path, target: &Path, method: CopyMethod)
-	-> Result<(), Error>
+pub(in crate) fn copy(source: &Path, target: &Path, method: CopyMethod)
+	-> Result<(), StallError>
+{
+	copy_with_mode(
+			source,
+			target,
+			method,
+			CopyMode::Standard,
+			None,
+			BufferSize::DEFAULT)
+		.map(|_| ())
+}
+
+/// Copies a file from `source` to `target` using the given `CopyMethod` and
+/// [`CopyMode`], limiting standard copies to the rate of the given
+/// [`Throttle`]. Returns the mode which was used to copy the file contents.
+///
+/// Files and throttled directories are copied in-process using a buffer of the
+/// given [`BufferSize`], and other directories are copied with the platform's
+/// copy command.
+///
+/// [`CopyMode`]: enum.CopyMode.html
+/// [`Throttle`]: struct.Throttle.html
+/// [`BufferSize`]: struct.BufferSize.html
+pub(in crate) fn copy_with_mode(
+	source: &Path,
+	target: &Path,
+	method: CopyMethod,
+	mode: CopyMode,
+	throttle: Option<Throttle>,
+	buffer_size: BufferSize)
+	-> Result<CopyMode, StallError>
 {
 	use CopyMethod::*;
 	let _span = span!(Level::DEBUG, "copy").entered();
 
 	match method {
-		None => event!(Level::DEBUG, "no-run flag was specified: \
-			Not copying data from {:?} to {:?}", source, target),
-
-		Subprocess => {
-			let _status = if cfg!(target_os = "windows") {
-				std::process::Command::new("Xcopy")
-					.arg(source)
-					.arg(target)
-					.args(["/h", "/s", "/e", "/x", "/y", "/i"])
-					.status()?
-			} else {
-				// NOTE: -R (recursive dir copy) and -p (preserve attribute
-				// such as timestamps) are POSIX requirements.
-				std::process::Command::new("cp")
-					.args(["-R", "-p"])
-					.arg(source)
-					.arg(target)
-					.status()?
-			};
+		// Directories can't be renamed over existing directories, so they are
+		// always copied directly.
+		Atomic { .. } if source.is_dir() => copy_with_mode(
+			source,
+			target,
+			Direct,
+			mode,
+			throttle,
+			buffer_size),
+
+		Atomic { durable } => {
+			let mut used = mode;
+			replace_with(target, durable, |temp| {
+				used = copy_with_mode(
+					source,
+					temp,
+					Direct,
+					mode,
+					throttle,
+					buffer_size)?;
+				if temp.exists() {
+					Ok(())
+				} else {
+					Err(StallError::CommandFailed {
+						message: format!("failed to copy {} to {}",
+							source.display(),
+							temp.display()),
+					})
+				}
+			})?;
+			Ok(used)
+		},
+
+		// Links are recreated rather than copied, as the copy commands
+		// differ in whether they follow them.
+		Direct if source.is_symlink() => {
+			if target.is_symlink() || target.is_file() {
+				std::fs::remove_file(target)
+					.with_path("failed to replace", target)?;
+			}
+			platform::copy_symlink(source, target)?;
+			Ok(CopyMode::Standard)
+		},
+
+		Direct if mode != CopyMode::Standard => {
+			match clone(source, target) {
+				Ok(()) => Ok(CopyMode::Reflink),
+				Err(e) if mode == CopyMode::Reflink => Err(e),
+				Err(e) => {
+					event!(Level::DEBUG, "Falling back to standard copy: {}", e);
+					copy_with_mode(
+						source,
+						target,
+						Direct,
+						CopyMode::Standard,
+						throttle,
+						buffer_size)
+				},
+			}
+		},
+
+		Direct if throttle.is_some() || !source.is_dir() => {
+			event!(Level::DEBUG, "Copying {:?} to {:?}{}",
+				source,
+				target,
+				throttle.map(|t| format!(" at {t}")).unwrap_or_default());
+			copy_in_process(source, target, throttle, buffer_size)?;
+			Ok(CopyMode::Standard)
+		},
+
+		Direct => {
+			let status = platform::copy_command(source, target)
+				.status()
+				.with_path("failed to run copy command for", source)?;
+			if !status.success() {
+				return Err(StallError::CommandFailed {
+					message: format!("failed to copy {} to {}: {}",
+						source.display(),
+						target.display(),
+						status),
+				});
+			}
+			Ok(CopyMode::Standard)
 		},
 	}
+}
+
+/// Copies a file or directory from `source` to `target` using the given
+/// `CopyMethod` and [`CopyOptions`]. Files within a directory which are
+/// ignored by the options' [`IgnoreRules`] are skipped. They are matched using
+/// whichever of their paths is within the rules' root directory. Returns the
+/// mode which was used to copy the file contents.
+///
+/// If `target` is an existing directory, the directory is copied file by file,
+/// and files within it whose target has the same size and modification time as
+/// their source are skipped without being opened. Each file whose copy fails
+/// with a transient error is retried according to the options' [`Retry`]
+/// policy. If the options' `verify` flag is set, each copy is verified by
+/// [`same_contents`], and copied again once if it fails verification.
+///
+/// [`CopyOptions`]: struct.CopyOptions.html
+/// [`IgnoreRules`]: ../ignore/struct.IgnoreRules.html
+/// [`Retry`]: struct.Retry.html
+/// [`same_contents`]: fn.same_contents.html
+pub(in crate) fn copy_with_options(
+	source: &Path,
+	target: &Path,
+	method: CopyMethod,
+	options: &CopyOptions)
+	-> Result<CopyMode, StallError>
+{
+	let is_dir = std::fs::symlink_metadata(source)
+		.is_ok_and(|metadata| metadata.is_dir());
+	if !is_dir || (options.ignore.is_empty() && !target.is_dir()) {
+		let copy = || options.retry.run(source, || copy_with_mode(
+			source,
+			target,
+			method,
+			options.mode,
+			options.throttle,
+			options.buffer_size));
+		let used = copy()?;
+		if !options.verify || same_contents(source, target)? {
+			return Ok(used);
+		}
+		event!(Level::WARN, "Copy of {:?} to {:?} failed verification: \
+			copying it again.", source, target);
+		let used = copy()?;
+		if same_contents(source, target)? { return Ok(used); }
+		return Err(StallError::VerifyFailed {
+			source: source.to_path_buf(),
+			target: target.to_path_buf(),
+		});
+	}
+
+	let metadata = std::fs::metadata(source)
+		.with_path("failed to read metadata for", source)?;
+	if !target.is_dir() {
+		std::fs::create_dir_all(target)
+			.with_path("failed to create directory", target)?;
+	}
+
+	let in_root = source.starts_with(options.ignore.root());
+	let mut used = None;
+	for entry in std::fs::read_dir(source)
+		.with_path("failed to read directory", source)?
+	{
+		let entry = entry.with_path("failed to read directory", source)?;
+		let source = entry.path();
+		let target = target.join(entry.file_name());
+		let is_dir = entry.file_type()
+			.with_path("failed to read metadata for", &source)?
+			.is_dir();
+		let path = if in_root { &source } else { &target };
+		if options.ignore.is_ignored(path, is_dir) {
+			event!(Level::DEBUG, "Ignoring {:?}", source);
+			continue;
+		}
+		if !is_dir && unchanged(&source, &target) {
+			event!(Level::TRACE, "Skipping unchanged file {:?}", source);
+			continue;
+		}
+
+		let mode = copy_with_options(&source, &target, method, options)?;
+		used = match used {
+			Some(CopyMode::Standard) => used,
+			_                        => Some(mode),
+		};
+	}
+
+	// Restore the directory's attributes, as the copy command would.
+	std::fs::set_permissions(target, metadata.permissions())
+		.with_path("failed to set permissions for", target)?;
+	if let (Ok(dir), Ok(modified)) = (std::fs::File::open(target), metadata.modified()) {
+		let _ = dir.set_modified(modified);
+	}
+	Ok(used.unwrap_or(CopyMode::Standard))
+}
+
+/// Returns the total size in bytes of the file or directory at the given
+/// path, or `None` if it can't be read. Symbolic links are not followed.
+pub(crate) fn disk_usage(path: &Path) -> Option<u64> {
+	let metadata = std::fs::symlink_metadata(path).ok()?;
+	if !metadata.is_dir() { return Some(metadata.len()); }
+
+	let mut total = 0;
+	for entry in std::fs::read_dir(path).ok()? {
+		total += disk_usage(&entry.ok()?.path())?;
+	}
+	Some(total)
+}
+
+/// Returns `true` if the file at `target` has the same size and modification
+/// time as the file at `source`, as it does after being copied.
+fn unchanged(source: &Path, target: &Path) -> bool {
+	let (Ok(source), Ok(target)) = (
+		std::fs::symlink_metadata(source),
+		std::fs::symlink_metadata(target))
+		else { return false };
+	source.is_file()
+		&& target.is_file()
+		&& source.len() == target.len()
+		&& source.modified().ok().is_some_and(|m| target.modified().ok() == Some(m))
+}
+
+/// Returns `true` if the file or directory at `target` has the same contents
+/// as the one at `source`. Files are compared by their SHA-256 hashes,
+/// symbolic links by their destinations, and directories by their files.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if either file can't be read.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub(crate) fn same_contents(source: &Path, target: &Path)
+	-> Result<bool, StallError>
+{
+	let metadata = std::fs::symlink_metadata(source)
+		.with_path("failed to read metadata for", source)?;
+	let Ok(target_metadata) = std::fs::symlink_metadata(target)
+		else { return Ok(false) };
+
+	if metadata.is_symlink() {
+		return Ok(target_metadata.is_symlink()
+			&& std::fs::read_link(source).ok() == std::fs::read_link(target).ok());
+	}
+
+	if metadata.is_dir() {
+		if !target_metadata.is_dir() { return Ok(false); }
+		for entry in std::fs::read_dir(source)
+			.with_path("failed to read directory", source)?
+		{
+			let entry = entry.with_path("failed to read directory", source)?;
+			if !same_contents(&entry.path(), &target.join(entry.file_name()))? {
+				return Ok(false);
+			}
+		}
+		return Ok(true);
+	}
+
+	Ok(target_metadata.is_file()
+		&& metadata.len() == target_metadata.len()
+		&& hash_file(source, HashAlgorithm::Sha256)?
+			== hash_file(target, HashAlgorithm::Sha256)?)
+}
+
+/// The largest size of the chunks in which throttled copies are performed.
+const THROTTLE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Copies a file or directory from `source` to `target`, limiting the copy
+/// rate to the given [`Throttle`]. Permissions and modification times are
+/// preserved, as they are by the copy command.
+///
+/// [`Throttle`]: struct.Throttle.html
+fn copy_in_process(
+	source: &Path,
+	target: &Path,
+	throttle: Option<Throttle>,
+	buffer_size: BufferSize)
+	-> Result<(), StallError>
+{
+	use std::fs::FileTimes;
+
+	let metadata = std::fs::symlink_metadata(source)
+		.with_path("failed to read metadata for", source)?;
+
+	if metadata.is_symlink() {
+		return platform::copy_symlink(source, target);
+	}
+
+	if metadata.is_dir() {
+		if !target.is_dir() {
+			std::fs::create_dir_all(target)
+				.with_path("failed to create directory", target)?;
+		}
+		for entry in std::fs::read_dir(source)
+			.with_path("failed to read directory", source)?
+		{
+			let entry = entry.with_path("failed to read directory", source)?;
+			copy_in_process(
+				&entry.path(),
+				&target.join(entry.file_name()),
+				throttle,
+				buffer_size)?;
+		}
+	} else {
+		let writer = copy_file_contents(
+			source,
+			target,
+			metadata.len(),
+			throttle,
+			buffer_size)?;
+
+		let mut times = FileTimes::new();
+		if let Ok(accessed) = metadata.accessed() {
+			times = times.set_accessed(accessed);
+		}
+		if let Ok(modified) = metadata.modified() {
+			times = times.set_modified(modified);
+		}
+		writer.set_times(times)
+			.with_path("failed to set modification time for", target)?;
+	}
+
+	std::fs::set_permissions(target, metadata.permissions())
+		.with_path("failed to set permissions for", target)
+}
+
+/// Copies the contents of the file at `source`, which has the given length,
+/// to a new file at `target`, returning the target file.
+///
+/// Space for the target file is preallocated where it is supported.
+/// Unthrottled copies are performed by the kernel where it is supported, and
+/// other copies are performed in chunks of the given [`BufferSize`], sleeping
+/// between chunks so that the copy rate doesn't exceed the [`Throttle`].
+///
+/// [`BufferSize`]: struct.BufferSize.html
+/// [`Throttle`]: struct.Throttle.html
+fn copy_file_contents(
+	source: &Path,
+	target: &Path,
+	len: u64,
+	throttle: Option<Throttle>,
+	buffer_size: BufferSize)
+	-> Result<std::fs::File, StallError>
+{
+	use std::fs::File;
+	use std::io::Read as _;
+
+	let mut reader = File::open(source)
+		.with_path("failed to open", source)?;
+	let mut writer = File::create(target)
+		.with_path("failed to create", target)?;
+	if let Err(e) = platform::preallocate(&writer, len) {
+		event!(Level::DEBUG, "Failed to preallocate {:?}: {}", target, e);
+	}
+
+	if throttle.is_none() && platform::supports_kernel_copy() {
+		let _ = std::io::copy(&mut reader, &mut writer)
+			.with_path("failed to copy", source)?;
+		return Ok(writer);
+	}
+
+	let chunk_size = match throttle {
+		Some(_) => buffer_size.bytes().min(THROTTLE_CHUNK_SIZE),
+		None    => buffer_size.bytes(),
+	};
+	let mut buf = vec![0; chunk_size];
+	let mut copied: u64 = 0;
+	let start = Instant::now();
+	loop {
+		let len = reader.read(&mut buf)
+			.with_path("failed to read", source)?;
+		if len == 0 { break; }
+		writer.write_all(&buf[..len])
+			.with_path("failed to write", target)?;
+		copied += len as u64;
+
+		let Some(throttle) = throttle else { continue };
+		let expected = throttle.duration(copied);
+		if let Some(remaining) = expected.checked_sub(start.elapsed()) {
+			std::thread::sleep(remaining);
+		}
+	}
+	Ok(writer)
+}
+
+/// Clones a file from `source` to `target` using a copy-on-write reflink.
+fn clone(source: &Path, target: &Path) -> Result<(), StallError> {
+	let mut command = platform::reflink_command(source, target)
+		.ok_or_else(|| StallError::CommandFailed {
+			message: "reflink copies are not supported on this platform"
+				.to_owned(),
+		})?;
+	let output = command
+		.output()
+		.with_path("failed to run copy command for", source)?;
+	if output.status.success() {
+		Ok(())
+	} else {
+		Err(StallError::CommandFailed {
+			message: format!("failed to clone {} to {}: {}",
+				source.display(),
+				target.display(),
+				String::from_utf8_lossy(&output.stderr).trim()),
+		})
+	}
+}
+
+
+/// Copies the attributes selected by the given [`CopyOptions`] from `source`
+/// to `target`. Attributes which can't be copied, e.g. because the file system
+/// doesn't support them, produce a [`PreserveFailed`] warning.
+///
+/// [`CopyOptions`]: struct.CopyOptions.html
+/// [`PreserveFailed`]: ../warning/enum.Warning.html#variant.PreserveFailed
+fn preserve(
+	source: &Path,
+	target: &Path,
+	options: &CopyOptions,
+	common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	for &attribute in &options.preserve {
+		event!(Level::DEBUG, "Copying {} from {:?} to {:?}",
+			attribute, source, target);
+		if let Err(reason) = platform::copy_attribute(source, target, attribute) {
+			warn(Warning::PreserveFailed {
+				path: target.to_path_buf(),
+				attribute,
+				reason,
+			}, common)?;
+		}
+	}
 	Ok(())
 }
 
@@ -432,9 +2097,20 @@ fn copy(source: &Path, target: &Path, method: CopyMethod)
 ////////////////////////////////////////////////////////////////////////////////
 /// The method to use when copying files.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum CopyMethod {
-	/// Do not copy files.
-	None,
-	/// Copy files using a command in a subprocess.
-	Subprocess,
+pub(in crate) enum CopyMethod {
+	/// Copy files directly over the target, by cloning them, copying their
+	/// contents in-process, or running the platform's copy command for
+	/// directories, depending on the [`CopyMode`] and [`Throttle`].
+	///
+	/// [`CopyMode`]: enum.CopyMode.html
+	/// [`Throttle`]: struct.Throttle.html
+	Direct,
+	/// Copy files as with [`Direct`] into a temporary file, then rename it
+	/// over the target.
+	///
+	/// [`Direct`]: #variant.Direct
+	Atomic {
+		/// Sync the copied file and its directory to disk.
+		durable: bool,
+	},
 }
@@ -7,47 +7,485 @@
 //! Error types.
 ////////////////////////////////////////////////////////////////////////////////
 
+// Internal library imports.
+use crate::suggest;
+use crate::warning::Warning;
 
 // Standard library imports.
 use std::path::Path;
+use std::path::PathBuf;
 
 
 ////////////////////////////////////////////////////////////////////////////////
-// InvalidFile
+// StallError
 ////////////////////////////////////////////////////////////////////////////////
-/// The specified file was invalid.
-#[allow(missing_copy_implementations)]
-#[derive(Debug, Clone)]
-pub struct InvalidFile;
+/// An error produced by a stall operation.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StallError {
+	/// An IO operation failed.
+	Io {
+		/// A description of the failed operation.
+		context: String,
+		/// The path of the file the operation was applied to, if any.
+		path: Option<PathBuf>,
+		/// The underlying IO error.
+		source: std::io::Error,
+	},
 
-impl std::error::Error for InvalidFile {}
+	/// A file could not be parsed.
+	Parse {
+		/// A description of the parsed data.
+		context: String,
+		/// The path of the parsed file, if known.
+		path: Option<PathBuf>,
+		/// The 1-based line number of the error.
+		line: usize,
+		/// The 1-based column number of the error.
+		column: usize,
+		/// A description of the parse failure.
+		message: String,
+		/// The source line containing the error, marked at the error column,
+		/// if known.
+		snippet: Option<Box<str>>,
+		/// A suggestion for fixing the error, if any.
+		hint: Option<&'static str>,
+	},
 
-impl std::fmt::Display for InvalidFile {
+	/// A file was written with a newer schema version than is supported.
+	UnsupportedVersion {
+		/// The path of the file, if known.
+		path: Option<PathBuf>,
+		/// The schema version of the file.
+		version: u32,
+		/// The newest supported schema version.
+		supported: u32,
+	},
+
+	/// Data could not be serialized.
+	Serialize {
+		/// A description of the serialized data.
+		context: String,
+		/// A description of the serialization failure.
+		message: String,
+	},
+
+	/// An operation would overwrite an existing file or entry.
+	Conflict {
+		/// A description of the conflict.
+		reason: String,
+		/// The conflicting file, entry, or name.
+		subject: String,
+		/// A suggestion for resolving the conflict, if any.
+		hint: Option<String>,
+	},
+
+	/// A stall entry was not found.
+	MissingEntry {
+		/// The local path of the entry.
+		path: PathBuf,
+		/// The local paths of similar entries.
+		suggestions: Vec<String>,
+	},
+
+	/// A name matched more than one stall entry.
+	AmbiguousEntry {
+		/// The name given for the entry.
+		name: PathBuf,
+		/// The local paths of the matching entries.
+		candidates: Vec<PathBuf>,
+	},
+
+	/// A named item other than a stall entry was not found.
+	NotFound {
+		/// The kind of item, e.g. "trash item".
+		kind: &'static str,
+		/// The name of the item.
+		name: String,
+		/// The names of similar items.
+		suggestions: Vec<String>,
+	},
+
+	/// A path was invalid for the requested operation.
+	InvalidPath {
+		/// The invalid path.
+		path: PathBuf,
+		/// Why the path is invalid.
+		reason: String,
+	},
+
+	/// A pattern or template could not be parsed.
+	InvalidPattern {
+		/// The invalid pattern.
+		pattern: String,
+		/// A description of the parse failure.
+		message: String,
+	},
+
+	/// An argument or option value was invalid.
+	InvalidInput {
+		/// A description of the invalid input.
+		message: String,
+	},
+
+	/// The stall is locked by another process.
+	Locked {
+		/// The path of the lock file.
+		path: PathBuf,
+	},
+
+	/// A command which modifies the stall was run on a read-only stall.
+	ReadOnly {
+		/// The path of the stall file.
+		path: PathBuf,
+	},
+
+	/// A copied file's contents didn't match the file it was copied from.
+	VerifyFailed {
+		/// The path of the copied file.
+		source: PathBuf,
+		/// The path of the copy.
+		target: PathBuf,
+	},
+
+	/// An external command failed.
+	CommandFailed {
+		/// A description of the failure.
+		message: String,
+	},
+
+	/// A warning was promoted to an error.
+	Warning {
+		/// The promoted warning.
+		warning: Warning,
+	},
+
+	/// Tracing could not be initialized.
+	Trace {
+		/// A description of the failure.
+		message: String,
+	},
+
+	/// A background task or a call through the C interface panicked, or a
+	/// background task was cancelled.
+	Task {
+		/// A description of the failure.
+		message: String,
+	},
+}
+
+impl StallError {
+	/// Constructs a `StallError::Parse` from a RON parse error.
+	#[must_use]
+	pub fn ron_parse(context: &str, path: Option<&Path>, error: &ron::Error)
+		-> Self
+	{
+		Self::Parse {
+			context: context.to_owned(),
+			path: path.map(Path::to_path_buf),
+			line: error.position.line,
+			column: error.position.col,
+			message: error.code.to_string(),
+			snippet: None,
+			hint: None,
+		}
+	}
+
+	/// Constructs a `StallError::Serialize` from a RON serialization error.
+	#[must_use]
+	pub fn ron_serialize(context: &str, error: &ron::Error) -> Self {
+		Self::Serialize {
+			context: context.to_owned(),
+			message: error.code.to_string(),
+		}
+	}
+
+	/// Constructs a `StallError::Parse` from a JSON parse error.
+	#[must_use]
+	pub fn json_parse(
+		context: &str,
+		path: Option<&Path>,
+		error: &serde_json::Error)
+		-> Self
+	{
+		// The error's message includes its position, which is reported
+		// separately.
+		let message = error.to_string();
+		let message = message
+			.split(" at line ")
+			.next()
+			.unwrap_or_default()
+			.to_owned();
+		Self::Parse {
+			context: context.to_owned(),
+			path: path.map(Path::to_path_buf),
+			line: error.line(),
+			column: error.column(),
+			message,
+			snippet: None,
+			hint: None,
+		}
+	}
+
+	/// Returns the error with a snippet of the given source text attached, if
+	/// it is a parse error. The snippet shows the line containing the error,
+	/// marked at the error column.
+	#[must_use]
+	pub fn with_source(mut self, source: &str) -> Self {
+		if let Self::Parse { line, column, snippet, .. } = &mut self {
+			*snippet = source_snippet(source, *line, *column)
+				.map(String::into_boxed_str);
+		}
+		self
+	}
+
+	/// Returns the error with the given hint attached, if it is a parse error.
+	#[must_use]
+	pub fn with_hint(mut self, text: &'static str) -> Self {
+		if let Self::Parse { hint, .. } = &mut self {
+			*hint = Some(text);
+		}
+		self
+	}
+
+	/// Returns the error with the given path attached, if it is an IO, parse,
+	/// or version error without a path.
+	#[must_use]
+	pub fn in_file(mut self, file: &Path) -> Self {
+		match &mut self {
+			Self::Io { path: path @ None, .. } |
+			Self::Parse { path: path @ None, .. } |
+			Self::UnsupportedVersion { path: path @ None, .. } => {
+				*path = Some(file.to_path_buf());
+			},
+			_ => (),
+		}
+		self
+	}
+
+	/// Returns `true` if the error is an IO error which is likely to be
+	/// transient, so that the failed operation may succeed if it is retried.
+	#[must_use]
+	pub fn is_transient(&self) -> bool {
+		matches!(self, Self::Io { source, .. }
+			if crate::platform::is_transient(source))
+	}
+}
+
+impl std::error::Error for StallError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io { source, .. } => Some(source),
+			_ => None,
+		}
+	}
+}
+
+impl std::fmt::Display for StallError {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>)
-		-> Result<(), std::fmt::Error> 
+		-> Result<(), std::fmt::Error>
 	{
-		write!(f, "Invalid file.")
+		match self {
+			Self::Io { context, path: Some(path), .. } => {
+				write!(f, "{}: {}", context, path.display())
+			},
+			Self::Io { context, path: None, .. } => write!(f, "{context}"),
+
+			Self::Parse {
+				context,
+				path,
+				line,
+				column,
+				message,
+				snippet,
+				hint,
+			} => {
+				write!(f, "{context}: ")?;
+				// Errors from deserializing values have no position.
+				match (path, *line > 0) {
+					(Some(path), true)  => write!(f, "{}:", path.display())?,
+					(Some(path), false) => write!(f, "{}: ", path.display())?,
+					(None, _)           => (),
+				}
+				if *line > 0 {
+					write!(f, "{line}:{column}: ")?;
+				}
+				write!(f, "{message}")?;
+				if let Some(snippet) = snippet {
+					write!(f, "\n{snippet}")?;
+				}
+				if let Some(hint) = hint {
+					write!(f, "\n{hint}")?;
+				}
+				Ok(())
+			},
+
+			Self::UnsupportedVersion { path, version, supported } => {
+				write!(f, "unsupported schema version {version}")?;
+				if let Some(path) = path {
+					write!(f, " in {}", path.display())?;
+				}
+				write!(f, " (newest supported version is {supported})\n\
+					Upgrade stall to read this file.")
+			},
+
+			Self::Serialize { context, message } => {
+				write!(f, "{context}: {message}")
+			},
+
+			Self::Conflict { reason, subject, hint } => {
+				write!(f, "{reason}: {subject}")?;
+				if let Some(hint) = hint {
+					write!(f, "\n{hint}")?;
+				}
+				Ok(())
+			},
+
+			Self::MissingEntry { path, suggestions } => {
+				write!(f, "unrecognized stall entry: {}", path.display())?;
+				if let Some(hint) = suggest::hint(suggestions) {
+					write!(f, "\n{hint}")?;
+				}
+				Ok(())
+			},
+
+			Self::AmbiguousEntry { name, candidates } => {
+				write!(f, "ambiguous stall entry: {}", name.display())?;
+				write!(f, "\nCandidates are:")?;
+				for candidate in candidates {
+					write!(f, "\n    {}", candidate.display())?;
+				}
+				Ok(())
+			},
+
+			Self::NotFound { kind, name, suggestions } => {
+				write!(f, "{kind} not found: {name}")?;
+				if let Some(hint) = suggest::hint(suggestions) {
+					write!(f, "\n{hint}")?;
+				}
+				Ok(())
+			},
+
+			Self::InvalidPath { path, reason } => {
+				write!(f, "{}: {}", reason, path.display())
+			},
+
+			Self::InvalidPattern { pattern, message } => {
+				write!(f, "invalid pattern {pattern:?}: {message}")
+			},
+
+			Self::InvalidInput { message }   |
+			Self::CommandFailed { message }  |
+			Self::Trace { message }          |
+			Self::Task { message }           => write!(f, "{message}"),
+
+			Self::Locked { path } => write!(f,
+				"stall is locked by another process: {}\n\
+				Use --wait to wait for the lock, or --no-lock to ignore it.",
+				path.display()),
+
+			Self::ReadOnly { path } => write!(f,
+				"stall is read-only: {}\n\
+				Only distribute and commands which don't modify the stall may \
+				be run.",
+				path.display()),
+
+			Self::VerifyFailed { source, target } => write!(f,
+				"copy failed verification: {} doesn't match {}",
+				target.display(),
+				source.display()),
+
+			Self::Warning { warning } => write!(f, "{warning}"),
+		}
+	}
+}
+
+impl From<std::io::Error> for StallError {
+	fn from(source: std::io::Error) -> Self {
+		Self::Io {
+			context: "IO error".to_owned(),
+			path: None,
+			source,
+		}
+	}
+}
+
+impl From<crate::InsertError> for StallError {
+	fn from(error: crate::InsertError) -> Self {
+		let reason = match error {
+			crate::InsertError::InvalidLocal(_)  => "stall path has no file name",
+			crate::InsertError::InvalidRemote(_) => "remote path has no file name",
+			crate::InsertError::RemoteInUse { local, .. } => {
+				return Self::Conflict {
+					reason: "remote file is already stalled as".to_owned(),
+					subject: local.display().to_string(),
+					hint: Some("Use `mv` to rename the existing entry, or \
+						`rm` to remove it first.".to_owned()),
+				};
+			},
+		};
+		Self::InvalidPath {
+			path: error.path().to_path_buf(),
+			reason: reason.to_owned(),
+		}
+	}
+}
+
+impl From<crate::template::NameTemplateParseError> for StallError {
+	fn from(error: crate::template::NameTemplateParseError) -> Self {
+		Self::InvalidInput { message: error.to_string() }
 	}
 }
 
 
+/// Returns the given 1-based line of the source text, followed by a line
+/// with a marker under the given 1-based column, or `None` if the line is not
+/// in the source.
+fn source_snippet(source: &str, line: usize, column: usize) -> Option<String> {
+	let text = source.lines().nth(line.checked_sub(1)?)?;
+	let number = line.to_string();
+	let padding = " ".repeat(number.len());
+	// Keep tabs in the marker line so the marker aligns with the text.
+	let indent: String = text.chars()
+		.take(column.saturating_sub(1))
+		.map(|c| if c == '\t' { '\t' } else { ' ' })
+		.collect();
+	Some(format!("{number} | {text}\n{padding} | {indent}^"))
+}
+
 
 ////////////////////////////////////////////////////////////////////////////////
-// MissingFile
+// IoResultExt
 ////////////////////////////////////////////////////////////////////////////////
-/// The specified file was missing.
-#[derive(Debug, Clone)]
-pub struct MissingFile { 
-	/// The path of the missing file.
-	pub path: Box<Path>,
+/// Extension methods for converting IO results into `StallError`s.
+pub trait IoResultExt<T> {
+	/// Converts an IO error into a [`StallError::Io`] for the operation
+	/// described by `context` on the file at `path`.
+	///
+	/// [`StallError::Io`]: StallError::Io
+	fn with_path(self, context: &str, path: &Path) -> Result<T, StallError>;
+
+	/// Converts an IO error into a [`StallError::Io`] for the operation
+	/// described by `context`.
+	///
+	/// [`StallError::Io`]: StallError::Io
+	fn with_io_context(self, context: &str) -> Result<T, StallError>;
 }
 
-impl std::error::Error for MissingFile {}
+impl<T> IoResultExt<T> for std::io::Result<T> {
+	fn with_path(self, context: &str, path: &Path) -> Result<T, StallError> {
+		self.map_err(|source| StallError::Io {
+			context: context.to_owned(),
+			path: Some(path.to_path_buf()),
+			source,
+		})
+	}
 
-impl std::fmt::Display for MissingFile {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>)
-		-> Result<(), std::fmt::Error> 
-	{
-		write!(f, "missing file: {}.", self.path.display())
+	fn with_io_context(self, context: &str) -> Result<T, StallError> {
+		self.map_err(|source| StallError::Io {
+			context: context.to_owned(),
+			path: None,
+			source,
+		})
 	}
 }
@@ -0,0 +1,426 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! C-compatible interface.
+//!
+//! These functions allow stall to be used from other languages without
+//! running the `stall` executable. A stall is opened with [`stall_open`],
+//! which returns an opaque [`StallHandle`] to pass to the other functions, and
+//! which must be released with [`stall_close`].
+//!
+//! Functions which fail return a null pointer or `-1`, and a description of
+//! the error can be retrieved with [`stall_last_error`]. Panics are caught
+//! before they reach the caller, and are reported as errors. Paths are given as
+//! NUL-terminated strings, and are passed to callbacks as UTF-8, with invalid
+//! sequences replaced. Strings passed to callbacks are only valid for the
+//! duration of the call.
+//!
+//! This module requires the `ffi` feature. A shared library can be built with
+//! `cargo rustc --release --features ffi --crate-type cdylib`.
+//!
+//! [`stall_open`]: fn.stall_open.html
+//! [`StallHandle`]: struct.StallHandle.html
+//! [`stall_close`]: fn.stall_close.html
+//! [`stall_last_error`]: fn.stall_last_error.html
+////////////////////////////////////////////////////////////////////////////////
+#![allow(unsafe_code)]
+
+// Internal library imports.
+use crate::application::Config;
+use crate::application::StallLocation;
+use crate::entry::CopyOptions;
+use crate::entry::Outcome;
+use crate::entry::Overwrite;
+use crate::error::StallError;
+use crate::ignore::IgnoreRules;
+use crate::lock::StallLock;
+use crate::platform;
+use crate::report::CopyReport;
+use crate::CommonOptions;
+use crate::DistributeOptions;
+use crate::Stall;
+
+// External library imports.
+use clap::Parser as _;
+
+// Standard library imports.
+use std::cell::RefCell;
+use std::ffi::c_char;
+use std::ffi::c_int;
+use std::ffi::c_void;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The outcome passed to a [`ProgressCallback`] for copied files.
+///
+/// [`ProgressCallback`]: type.ProgressCallback.html
+pub const STALL_COPIED: c_int = 0;
+
+/// The outcome passed to a [`ProgressCallback`] for unchanged files.
+///
+/// [`ProgressCallback`]: type.ProgressCallback.html
+pub const STALL_UNCHANGED: c_int = 1;
+
+/// The outcome passed to a [`ProgressCallback`] for skipped files.
+///
+/// [`ProgressCallback`]: type.ProgressCallback.html
+pub const STALL_SKIPPED: c_int = 2;
+
+/// The outcome passed to a [`ProgressCallback`] for failed entries.
+///
+/// [`ProgressCallback`]: type.ProgressCallback.html
+pub const STALL_FAILED: c_int = 3;
+
+
+thread_local! {
+	/// The message of the last error to occur on this thread.
+	static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Callbacks
+////////////////////////////////////////////////////////////////////////////////
+/// A function called with the local path, remote path, and user data of an
+/// entry.
+pub type EntryCallback = Option<unsafe extern "C" fn(
+	local: *const c_char,
+	remote: *const c_char,
+	user_data: *mut c_void)>;
+
+/// A function called with the local path, outcome, error message, and user
+/// data of an entry after it is copied.
+///
+/// The outcome is one of the `STALL_*` constants, and the error message is
+/// null unless the outcome is `STALL_FAILED`.
+pub type ProgressCallback = Option<unsafe extern "C" fn(
+	local: *const c_char,
+	outcome: c_int,
+	message: *const c_char,
+	user_data: *mut c_void)>;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StallHandle
+////////////////////////////////////////////////////////////////////////////////
+/// An opened stall.
+#[derive(Debug)]
+pub struct StallHandle {
+	/// The location of the stall.
+	location: StallLocation,
+	/// The loaded stall data.
+	stall: Stall,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Functions
+////////////////////////////////////////////////////////////////////////////////
+/// Opens the stall with the given stall file or directory.
+///
+/// Returns null if the stall can't be loaded.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn stall_open(path: *const c_char)
+	-> *mut StallHandle
+{
+	guard(std::ptr::null_mut(), || {
+		if path.is_null() {
+			set_last_error(&StallError::InvalidInput {
+				message: "stall path is null".to_owned(),
+			});
+			return std::ptr::null_mut();
+		}
+		// SAFETY: The caller guarantees that `path` is a valid string.
+		let path = unsafe { path_from_ptr(path) };
+
+		let res = StallLocation::from_path(&path, &Config::STALL_FILE_NAMES)
+			.and_then(|location| Stall::read_from_path(&location.file)
+				.map(|stall| StallHandle { location, stall }));
+		match res {
+			Ok(handle) => Box::into_raw(Box::new(handle)),
+			Err(e)     => {
+				set_last_error(&e);
+				std::ptr::null_mut()
+			},
+		}
+	})
+}
+
+/// Closes a stall opened with [`stall_open`].
+///
+/// # Safety
+///
+/// `handle` must be null or a handle returned by [`stall_open`] which has not
+/// been closed.
+///
+/// [`stall_open`]: fn.stall_open.html
+#[no_mangle]
+pub unsafe extern "C" fn stall_close(handle: *mut StallHandle) {
+	guard((), || {
+		if handle.is_null() { return; }
+		// SAFETY: The caller guarantees that `handle` came from `stall_open`.
+		drop(unsafe { Box::from_raw(handle) });
+	});
+}
+
+/// Returns the message of the error from the last call to a function in this
+/// module on the calling thread, or null if that call succeeded.
+///
+/// The message is valid until the next call to a function in this module on
+/// the same thread, other than this one.
+#[no_mangle]
+pub extern "C" fn stall_last_error() -> *const c_char {
+	LAST_ERROR.with(|last| last
+		.borrow()
+		.as_ref()
+		.map_or(std::ptr::null(), |message| message.as_ptr()))
+}
+
+/// Calls `callback` with each entry of the stall, and returns the number of
+/// entries, or `-1` if an error occurs.
+///
+/// # Safety
+///
+/// `handle` must be a handle returned by [`stall_open`] which has not been
+/// closed.
+///
+/// [`stall_open`]: fn.stall_open.html
+#[no_mangle]
+pub unsafe extern "C" fn stall_entries(
+	handle: *const StallHandle,
+	callback: EntryCallback,
+	user_data: *mut c_void)
+	-> c_int
+{
+	guard(-1, || {
+		// SAFETY: The caller guarantees that `handle` came from `stall_open`.
+		let Some(handle) = (unsafe { handle_ref(handle) }) else { return -1 };
+
+		let mut count: c_int = 0;
+		for entry in handle.stall.entries() {
+			count = count.saturating_add(1);
+			let Some(callback) = callback else { continue };
+			let local = c_string(entry.local);
+			let remote = c_string(entry.remote);
+			// SAFETY: The caller guarantees that `callback` is valid.
+			unsafe { callback(local.as_ptr(), remote.as_ptr(), user_data) };
+		}
+		count
+	})
+}
+
+/// Runs the 'stall-collect' command for the stall, calling `callback` with
+/// the result of each entry.
+///
+/// Returns `0` if every entry succeeds, `1` if any entry fails, or `-1` if
+/// the command can't be run.
+///
+/// # Safety
+///
+/// `handle` must be a handle returned by [`stall_open`] which has not been
+/// closed.
+///
+/// [`stall_open`]: fn.stall_open.html
+#[no_mangle]
+pub unsafe extern "C" fn stall_collect(
+	handle: *const StallHandle,
+	callback: ProgressCallback,
+	user_data: *mut c_void)
+	-> c_int
+{
+	guard(-1, || {
+		// SAFETY: The caller guarantees that `handle` came from `stall_open`.
+		let Some(handle) = (unsafe { handle_ref(handle) }) else { return -1 };
+
+		run_copy(handle, callback, user_data, |options, common|
+			crate::collect(
+				&mut std::io::sink(),
+				&handle.location.dir,
+				&handle.stall,
+				[],
+				Overwrite::default(),
+				false,
+				options,
+				None,
+				None,
+				false,
+				false,
+				common))
+	})
+}
+
+/// Runs the 'stall-distribute' command for the stall, calling `callback` with
+/// the result of each entry.
+///
+/// Returns `0` if every entry succeeds, `1` if any entry fails, or `-1` if
+/// the command can't be run.
+///
+/// # Safety
+///
+/// `handle` must be a handle returned by [`stall_open`] which has not been
+/// closed.
+///
+/// [`stall_open`]: fn.stall_open.html
+#[no_mangle]
+pub unsafe extern "C" fn stall_distribute(
+	handle: *const StallHandle,
+	callback: ProgressCallback,
+	user_data: *mut c_void)
+	-> c_int
+{
+	guard(-1, || {
+		// SAFETY: The caller guarantees that `handle` came from `stall_open`.
+		let Some(handle) = (unsafe { handle_ref(handle) }) else { return -1 };
+
+		run_copy(handle, callback, user_data, |options, common|
+			crate::distribute(
+				&mut std::io::sink(),
+				&handle.location.dir,
+				&handle.stall,
+				[],
+				Overwrite::default(),
+				DistributeOptions { use_trash: true, ..Default::default() },
+				options,
+				None,
+				None,
+				common))
+	})
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helpers
+////////////////////////////////////////////////////////////////////////////////
+/// Runs a copy command for every entry of the stall, calling `callback` with
+/// the result of each entry.
+fn run_copy<F>(
+	handle: &StallHandle,
+	callback: ProgressCallback,
+	user_data: *mut c_void,
+	copy: F)
+	-> c_int
+	where F: FnOnce(&CopyOptions, &CommonOptions)
+		-> Result<CopyReport, StallError>
+{
+	let common = CommonOptions::parse_from(["stall", "--quiet"]);
+	let res = IgnoreRules::load(&handle.location.dir)
+		.and_then(|ignore| StallLock::acquire(&handle.location.dir, false)
+			.map(|lock| (ignore, lock)));
+	let (ignore, _lock) = match res {
+		Ok(res) => res,
+		Err(e)  => {
+			set_last_error(&e);
+			return -1;
+		},
+	};
+	let options = CopyOptions { ignore, ..CopyOptions::default() };
+
+	let report = match copy(&options, &common) {
+		Ok(report) => report,
+		Err(e)     => {
+			set_last_error(&e);
+			return -1;
+		},
+	};
+
+	let mut failed = false;
+	for entry in report.entries() {
+		let (outcome, message) = match &entry.result {
+			Ok(Outcome::Copied)    => (STALL_COPIED, None),
+			Ok(Outcome::Unchanged) => (STALL_UNCHANGED, None),
+			Ok(Outcome::Skipped)   => (STALL_SKIPPED, None),
+			Err(e)                 => {
+				failed = true;
+				set_last_error(e);
+				(STALL_FAILED, Some(c_string(e.to_string())))
+			},
+		};
+		let Some(callback) = callback else { continue };
+		let local = c_string(&entry.local);
+		let message = message
+			.as_ref()
+			.map_or(std::ptr::null(), |message| message.as_ptr());
+		// SAFETY: The caller guarantees that `callback` is valid.
+		unsafe { callback(local.as_ptr(), outcome, message, user_data) };
+	}
+	c_int::from(failed)
+}
+
+/// Runs the body of an exported function. The last error is cleared before
+/// it runs, and if it panics, the panic is recorded as the last error and
+/// `on_panic` is returned, as unwinding into the caller is undefined behavior.
+fn guard<T, F>(on_panic: T, body: F) -> T
+	where F: FnOnce() -> T
+{
+	LAST_ERROR.with(|last| *last.borrow_mut() = None);
+	std::panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|panic| {
+		let message = panic.downcast_ref::<&str>()
+			.map(|message| (*message).to_owned())
+			.or_else(|| panic.downcast_ref::<String>().cloned())
+			.unwrap_or_else(|| "unknown panic".to_owned());
+		set_last_error(&StallError::Task {
+			message: format!("stall panicked: {message}"),
+		});
+		on_panic
+	})
+}
+
+/// Returns a reference to the stall handle, recording an error if it is
+/// null.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle returned by `stall_open` which has not
+/// been closed.
+unsafe fn handle_ref<'a>(handle: *const StallHandle)
+	-> Option<&'a StallHandle>
+{
+	// SAFETY: The caller guarantees that `handle` is valid if non-null.
+	let handle = unsafe { handle.as_ref() };
+	if handle.is_none() {
+		set_last_error(&StallError::InvalidInput {
+			message: "stall handle is null".to_owned(),
+		});
+	}
+	handle
+}
+
+/// Returns the path given by a NUL-terminated string.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string.
+unsafe fn path_from_ptr(path: *const c_char) -> PathBuf {
+	// SAFETY: The caller guarantees that `path` is a valid string.
+	let bytes = unsafe { CStr::from_ptr(path) }.to_bytes();
+	platform::path_from_bytes(bytes.to_vec())
+}
+
+/// Returns the given path or message as a NUL-terminated string. Interior
+/// NUL bytes are removed.
+fn c_string<S>(text: S) -> CString
+	where S: AsRef<std::ffi::OsStr>
+{
+	let mut bytes = text.as_ref().to_string_lossy().into_owned().into_bytes();
+	bytes.retain(|&b| b != 0);
+	CString::new(bytes).unwrap_or_default()
+}
+
+/// Records the message of the last error to occur on this thread.
+fn set_last_error(error: &StallError) {
+	LAST_ERROR.with(|last| *last.borrow_mut() = Some(c_string(
+		error.to_string())));
+}
@@ -0,0 +1,310 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Ignore rules.
+//!
+//! Ignore rules select files within directories which should never be copied
+//! into or out of a stall, such as caches, lock files, and editor temporary
+//! files. They are read from a `.stallignore` file in the stall directory,
+//! which uses the same syntax as a `.gitignore` file:
+//!
+//! + Blank lines and lines starting with `#` are skipped.
+//! + Patterns starting with `!` re-include files excluded by earlier patterns.
+//! + Patterns ending with `/` only match directories.
+//! + Patterns containing a `/` other than a trailing one match paths relative
+//!   to the stall directory. Other patterns match file names at any depth.
+//! + `*` and `?` don't match `/`, and `**` matches any number of directories.
+//!
+//! The files within an ignored directory are always ignored.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+
+// External library imports.
+use glob::MatchOptions;
+use glob::Pattern;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The name of the ignore file in the stall directory.
+pub const IGNORE_FILE_NAME: &str = ".stallignore";
+
+/// The options used for matching ignore patterns.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+	case_sensitive: true,
+	require_literal_separator: true,
+	require_literal_leading_dot: false,
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+// IgnoreRules
+////////////////////////////////////////////////////////////////////////////////
+/// A set of ignore rules for the files within a root directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IgnoreRules {
+	/// The directory which the rules' paths are relative to.
+	root: PathBuf,
+	/// The rules, in the order they were given.
+	rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreRules {
+	/// Constructs an empty `IgnoreRules` for the given root directory.
+	#[must_use]
+	pub fn new<P>(root: P) -> Self
+		where P: Into<PathBuf>
+	{
+		Self {
+			root: root.into(),
+			rules: Vec::new(),
+		}
+	}
+
+	/// Reads the ignore rules in the `.stallignore` file of the given stall
+	/// directory. If the file doesn't exist, the rules are empty.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the file can't be read, or if it contains
+	/// an invalid pattern.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn load(stall_dir: &Path) -> Result<Self, StallError> {
+		let path = stall_dir.join(IGNORE_FILE_NAME);
+		let text = match std::fs::read_to_string(&path) {
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+				return Ok(Self::new(stall_dir));
+			},
+			res => res.with_path("failed to read ignore file", &path)?,
+		};
+		event!(Level::DEBUG, "Loaded ignore file {:?}", path);
+		Self::new(stall_dir).with_rules(&text)
+	}
+
+	/// Appends the rules given in gitignore syntax to the `IgnoreRules`.
+	/// Later rules take precedence over earlier ones.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the text contains an invalid pattern.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn with_rules(mut self, text: &str) -> Result<Self, StallError> {
+		for line in text.lines() {
+			if let Some(rule) = IgnoreRule::parse(line)? {
+				self.rules.push(rule);
+			}
+		}
+		Ok(self)
+	}
+
+	/// Appends rules ignoring each of the given paths within `base`, which is
+	/// relative to the root directory. Paths ending with a separator only
+	/// match directories. Later rules take precedence over earlier ones.
+	#[must_use]
+	pub fn with_paths(mut self, base: &Path, paths: &[PathBuf]) -> Self {
+		for path in paths {
+			let Some(text) = path.to_str() else { continue };
+			let dir_only = text.ends_with('/');
+			let path = base.join(text.trim_end_matches('/'));
+			let Some(text) = path.to_str() else { continue };
+			let pattern = Pattern::new(&Pattern::escape(text))
+				.expect("escaped pattern is valid");
+			self.rules.push(IgnoreRule { pattern, negated: false, dir_only });
+		}
+		self
+	}
+
+	/// Returns the directory which the rules' paths are relative to.
+	#[must_use]
+	pub fn root(&self) -> &Path {
+		&self.root
+	}
+
+	/// Returns `true` if there are no rules.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.rules.is_empty()
+	}
+
+	/// Returns `true` if the file or directory at the given path is ignored.
+	/// The path may be relative to the root directory, or an absolute path
+	/// within it. Paths outside of the root directory are never ignored.
+	#[must_use]
+	pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+		if self.rules.is_empty() { return false; }
+
+		let path = match path.strip_prefix(&self.root) {
+			Ok(relative)               => relative,
+			Err(_) if path.is_relative() => path,
+			Err(_)                     => return false,
+		};
+
+		// Files within an ignored directory are ignored, so each of the
+		// path's ancestors is checked as a directory first.
+		let mut ancestors: Vec<&Path> = path
+			.ancestors()
+			.skip(1)
+			.filter(|dir| !dir.as_os_str().is_empty())
+			.collect();
+		ancestors.reverse();
+		ancestors.into_iter().any(|dir| self.matches(dir, true))
+			|| self.matches(path, is_dir)
+	}
+
+	/// Returns `true` if the last rule matching the given relative path
+	/// excludes it.
+	fn matches(&self, path: &Path, is_dir: bool) -> bool {
+		self.rules
+			.iter()
+			.rev()
+			.find(|rule| rule.matches(path, is_dir))
+			.is_some_and(|rule| !rule.negated)
+	}
+
+	/// Returns the paths of the files within `dir` which are not ignored,
+	/// relative to `dir`, in sorted order. The files are matched by their
+	/// relative paths, as though `dir` were the root directory. Ignored
+	/// directories are not searched, and symbolic links are not followed.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if a directory can't be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn walk(&self, dir: &Path) -> Result<Vec<PathBuf>, StallError> {
+		let mut files = Vec::new();
+		self.walk_into(dir, Path::new(""), &mut files)?;
+		files.sort();
+		Ok(files)
+	}
+
+	/// Appends the paths of the files within `dir.join(relative)` which are
+	/// not ignored to `files`.
+	fn walk_into(&self, dir: &Path, relative: &Path, files: &mut Vec<PathBuf>)
+		-> Result<(), StallError>
+	{
+		let current = dir.join(relative);
+		for entry in std::fs::read_dir(&current)
+			.with_path("failed to read directory", &current)?
+		{
+			let entry = entry.with_path("failed to read directory", &current)?;
+			let path = relative.join(entry.file_name());
+			let is_dir = entry.file_type()
+				.with_path("failed to read metadata for", &entry.path())?
+				.is_dir();
+			if self.is_ignored(&path, is_dir) {
+				event!(Level::DEBUG, "Ignoring {:?}", entry.path());
+			} else if is_dir {
+				self.walk_into(dir, &path, files)?;
+			} else {
+				files.push(path);
+			}
+		}
+		Ok(())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// IgnoreRule
+////////////////////////////////////////////////////////////////////////////////
+/// A single ignore rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IgnoreRule {
+	/// The pattern to match paths against.
+	pattern: Pattern,
+	/// Whether the rule re-includes matching paths.
+	negated: bool,
+	/// Whether the rule only matches directories.
+	dir_only: bool,
+}
+
+impl IgnoreRule {
+	/// Parses a line of an ignore file, returning `None` if it is blank or a
+	/// comment.
+	fn parse(line: &str) -> Result<Option<Self>, StallError> {
+		let mut text = line.trim_end();
+		if text.is_empty() || text.starts_with('#') { return Ok(None); }
+
+		// A leading backslash escapes a literal `!` or `#`.
+		let negated = text.starts_with('!');
+		if negated || text.starts_with("\\!") || text.starts_with("\\#") {
+			text = &text[1..];
+		}
+
+		let dir_only = text.ends_with('/');
+		let text = text.trim_end_matches('/');
+		if text.is_empty() { return Ok(None); }
+
+		// Patterns with a separator are relative to the root directory, and
+		// other patterns may match at any depth.
+		let pattern = if text.contains('/') {
+			text.trim_start_matches('/').to_owned()
+		} else {
+			format!("**/{text}")
+		};
+
+		let pattern = Pattern::new(&pattern)
+			.map_err(|e| StallError::InvalidPattern {
+				pattern: line.to_owned(),
+				message: e.to_string(),
+			})?;
+		Ok(Some(Self { pattern, negated, dir_only }))
+	}
+
+	/// Returns `true` if the rule matches the given relative path.
+	fn matches(&self, path: &Path, is_dir: bool) -> bool {
+		(is_dir || !self.dir_only)
+			&& self.pattern.matches_path_with(path, MATCH_OPTIONS)
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// git_ignored
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the paths of the untracked files within `dir` which are ignored by
+/// git, relative to `dir`, or `None` if `dir` is not within a git work tree.
+///
+/// Ignored directories are listed once, with a trailing separator. The paths
+/// are listed by the `git` command, so all of git's ignore files and settings
+/// are respected.
+#[must_use]
+pub fn git_ignored(dir: &Path) -> Option<Vec<PathBuf>> {
+	let output = Command::new("git")
+		.arg("-C")
+		.arg(dir)
+		.args(["ls-files", "--others", "--ignored", "--exclude-standard",
+			"--directory", "-z"])
+		.stdin(Stdio::null())
+		.output()
+		.map_err(|e| event!(Level::DEBUG, "Unable to run git: {e}"))
+		.ok()?;
+	if !output.status.success() {
+		event!(Level::DEBUG, "Not a git work tree: {:?}", dir);
+		return None;
+	}
+
+	Some(String::from_utf8_lossy(&output.stdout)
+		.split('\0')
+		.filter(|path| !path.is_empty())
+		.map(PathBuf::from)
+		.collect())
+}
@@ -0,0 +1,257 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Journal of operations performed on a stall.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::application::migrate_stall_file;
+use crate::application::stall_state_dir;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::report::CopyReport;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Journal
+////////////////////////////////////////////////////////////////////////////////
+/// An append-only journal recording the operations performed on a stall.
+///
+/// Each record is written as a single line of RON, so that records can be
+/// appended without rewriting the journal.
+#[derive(Debug, Clone)]
+pub struct Journal {
+	/// The path of the journal file.
+	path: PathBuf,
+	/// The path of the journal file in the stall directory, where older
+	/// versions kept it.
+	legacy_path: PathBuf,
+}
+
+impl Journal {
+	/// The journal file name within the stall's state directory.
+	pub const JOURNAL_FILE_NAME: &'static str = "journal";
+
+	/// The journal file path used before the state directory, relative to the
+	/// stall directory.
+	pub const LEGACY_JOURNAL_PATH: &'static str = ".stall-journal";
+
+	/// Constructs a new `Journal` for the given stall directory. The journal
+	/// is kept in the stall's directory within the state directory, or in the
+	/// stall directory if the state directory can't be determined.
+	#[must_use]
+	pub fn new(stall_dir: &Path) -> Self {
+		let legacy_path = stall_dir.join(Self::LEGACY_JOURNAL_PATH);
+		let path = stall_state_dir(stall_dir).map_or_else(
+			|| legacy_path.clone(),
+			|dir| dir.join(Self::JOURNAL_FILE_NAME));
+		Self { path, legacy_path }
+	}
+
+	/// Returns the path of the journal file.
+	#[must_use]
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Appends a record to the journal, creating the journal file if it does
+	/// not exist. A journal in the stall directory is moved to the state
+	/// directory first.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the record cannot be serialized or the
+	/// journal file cannot be written.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn append(&self, record: &JournalRecord) -> Result<(), StallError> {
+		let text = ron::to_string(record)
+			.map_err(|e| StallError::ron_serialize(
+				"failed to serialize journal record",
+				&e))?;
+		migrate_stall_file(&self.path, &self.legacy_path)?;
+		let mut file = std::fs::OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(&self.path)
+			.with_path("failed to open journal", &self.path)?;
+		writeln!(file, "{text}")
+			.with_path("failed to write journal", &self.path)
+	}
+
+	/// Returns the records in the journal, oldest first. Returns no records
+	/// if the journal file does not exist. A journal in the stall directory is
+	/// read if it hasn't been moved to the state directory yet.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the journal file cannot be read or a record
+	/// cannot be parsed.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn records(&self) -> Result<Vec<JournalRecord>, StallError> {
+		let path = if self.path.exists() {
+			&self.path
+		} else if self.legacy_path.exists() {
+			&self.legacy_path
+		} else {
+			return Ok(Vec::new());
+		};
+
+		let text = std::fs::read_to_string(path)
+			.with_path("failed to read journal", path)?;
+		text.lines()
+			.enumerate()
+			.filter(|(_, line)| !line.trim().is_empty())
+			.map(|(i, line)| ron::from_str(line)
+				.map_err(|e| StallError::Parse {
+					context: "failed to parse journal".to_owned(),
+					path: Some(path.clone()),
+					line: i + 1,
+					column: e.position.col,
+					message: e.code.to_string(),
+					snippet: None,
+					hint: None,
+				}))
+			.collect()
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// JournalRecord
+////////////////////////////////////////////////////////////////////////////////
+/// A record of a single operation performed on a stall.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct JournalRecord {
+	/// The time the operation was performed, in seconds since the Unix epoch.
+	pub time: u64,
+	/// The operation performed.
+	pub operation: Operation,
+	/// The local paths of the entries affected by the operation.
+	pub entries: Vec<PathBuf>,
+	/// The local paths of the entries for which the operation failed.
+	#[serde(default)]
+	pub failed: Vec<PathBuf>,
+	/// The error which stopped the operation, if any.
+	#[serde(default)]
+	pub error: Option<String>,
+}
+
+impl JournalRecord {
+	/// Constructs a new `JournalRecord` for the given operation, performed at
+	/// the current time.
+	#[must_use]
+	pub fn new(operation: Operation) -> Self {
+		let time = SystemTime::now()
+			.duration_since(SystemTime::UNIX_EPOCH)
+			.map_or(0, |d| d.as_secs());
+		Self {
+			time,
+			operation,
+			entries: Vec::new(),
+			failed: Vec::new(),
+			error: None,
+		}
+	}
+
+	/// Returns the time the operation was performed.
+	#[must_use]
+	pub fn time(&self) -> SystemTime {
+		SystemTime::UNIX_EPOCH + Duration::from_secs(self.time)
+	}
+
+	/// Records the copied and failed entries of a [`CopyReport`].
+	///
+	/// [`CopyReport`]: ../report/struct.CopyReport.html
+	pub fn record_report(&mut self, report: &CopyReport) {
+		self.entries.extend(report.copied().map(Path::to_path_buf));
+		self.failed.extend(report.failures()
+			.map(|(local, _)| local.to_path_buf()));
+	}
+
+	/// Returns `true` if the operation affected or failed for the entry with
+	/// the given local path.
+	#[must_use]
+	pub fn involves(&self, local: &Path) -> bool {
+		self.entries.iter().chain(self.failed.iter()).any(|l| l == local)
+	}
+
+	/// Returns a short description of the outcome of the operation.
+	#[must_use]
+	pub fn outcome(&self) -> String {
+		match (&self.error, self.failed.len()) {
+			(Some(error), _) => format!("error: {error}"),
+			(None, 0)        => "ok".to_owned(),
+			(None, failed)   => format!("{failed} failed"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Operation
+////////////////////////////////////////////////////////////////////////////////
+/// A journaled stall operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum Operation {
+	/// Files were added to the stall.
+	Add,
+	/// Files were removed from the stall.
+	Remove,
+	/// Entries whose remote files no longer exist were removed.
+	Prune,
+	/// Files in the stall were renamed.
+	Move,
+	/// The remote paths of entries were updated to their new locations.
+	Relocate,
+	/// A file in the stall was duplicated.
+	Duplicate,
+	/// Files were collected into the stall directory.
+	Collect,
+	/// Files were distributed to their remote locations.
+	Distribute,
+	/// Entry flags were changed.
+	Flag,
+	/// Files were distributed to a new machine.
+	Bootstrap,
+	/// Entries were merged from another stall.
+	Merge,
+	/// Entries were moved into new stalls.
+	Split,
+}
+
+impl std::fmt::Display for Operation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Add        => write!(f, "add"),
+			Self::Remove     => write!(f, "rm"),
+			Self::Prune      => write!(f, "prune"),
+			Self::Move       => write!(f, "mv"),
+			Self::Relocate   => write!(f, "relocate"),
+			Self::Duplicate  => write!(f, "cp"),
+			Self::Collect    => write!(f, "collect"),
+			Self::Distribute => write!(f, "distribute"),
+			Self::Flag       => write!(f, "flag"),
+			Self::Bootstrap  => write!(f, "bootstrap"),
+			Self::Merge      => write!(f, "merge"),
+			Self::Split      => write!(f, "split"),
+		}
+	}
+}
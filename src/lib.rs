@@ -7,7 +7,8 @@
 //! Stall library modules.
 ////////////////////////////////////////////////////////////////////////////////
 #![forbid(non_ascii_idents)]
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "ffi"), forbid(unsafe_code))]
+#![cfg_attr(feature = "ffi", deny(unsafe_code))]
 #![deny(keyword_idents)]
 #![deny(macro_use_extern_crate)]
 #![deny(missing_abi)]
@@ -111,8 +112,39 @@ mod test;
 // Public modules.
 pub mod command;
 pub mod application;
+pub mod archive;
+pub mod atomic;
+pub mod cancel;
+pub mod confirm;
 pub mod entry;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod ignore;
+pub mod journal;
+pub mod lock;
+pub mod manifest;
+pub mod mergetool;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod notify;
+pub mod path;
+pub mod pick;
+pub mod plan;
+pub mod platform;
+pub mod privilege;
+pub mod remote;
+pub mod report;
+pub mod safety;
+pub mod schedule;
+pub mod script;
+pub mod state;
+pub mod suggest;
+pub mod template;
+pub mod transform;
+pub mod trash;
+pub mod versions;
+pub mod warning;
 
 // Exports.
 pub use crate::stall::*;
@@ -0,0 +1,120 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Advisory locking of stall directories.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::platform;
+
+// External library imports.
+use fs2::FileExt as _;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StallLock
+////////////////////////////////////////////////////////////////////////////////
+/// An exclusive advisory lock on a stall directory, held by commands which
+/// modify the stall. The lock is released when the `StallLock` is dropped.
+///
+/// The lock is taken on a lock file in the stall directory. On unix systems,
+/// the lock file is removed before the lock is released, and a process which
+/// was waiting on a removed lock file notices that it no longer has the lock
+/// file's path after locking it, and retries with a new lock file. Open files
+/// can't be removed on windows, so the lock file is left in place there.
+#[derive(Debug)]
+pub struct StallLock {
+	/// The locked file.
+	file: File,
+	/// The path of the locked file.
+	path: PathBuf,
+}
+
+impl StallLock {
+	/// The name of the lock file within the stall directory.
+	pub const LOCK_FILE_NAME: &'static str = ".stall.lock";
+
+	/// Acquires the lock for the given stall directory. If `wait` is true,
+	/// this blocks until any other process holding the lock releases it.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the lock file cannot be opened, or if `wait`
+	/// is false and the lock is held by another process.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn acquire(stall_dir: &Path, wait: bool) -> Result<Self, StallError> {
+		let path = stall_dir.join(Self::LOCK_FILE_NAME);
+		loop {
+			let file = OpenOptions::new()
+				.read(true)
+				.write(true)
+				.create(true)
+				.truncate(false)
+				.open(&path)
+				.with_path("failed to open lock file", &path)?;
+
+			if file.try_lock_exclusive().is_err() {
+				if !wait {
+					return Err(StallError::Locked { path });
+				}
+				event!(Level::INFO, "Waiting for lock on {:?}", path);
+				file.lock_exclusive()
+					.with_path("failed to lock", &path)?;
+			}
+
+			// The previous holder may have removed the file while we waited.
+			if Self::is_current(&file, &path) {
+				event!(Level::DEBUG, "Acquired lock on {:?}", path);
+				return Ok(Self { file, path });
+			}
+			event!(Level::DEBUG, "Lock file {:?} was removed, retrying", path);
+		}
+	}
+
+	/// Returns true if the open lock `file` is still the file at `path`. Lock
+	/// files are only removed if their file IDs are known, so a file without
+	/// one is always current.
+	fn is_current(file: &File, path: &Path) -> bool {
+		platform::open_file_id(file)
+			.is_none_or(|id| platform::file_id(path) == Some(id))
+	}
+
+	/// Returns the path of the lock file.
+	#[must_use]
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl Drop for StallLock {
+	fn drop(&mut self) {
+		// The file is removed while it is still locked, so that no other
+		// process can lock it before it is gone. It is only removed if it is
+		// confirmed to be the locked file.
+		let id = platform::open_file_id(&self.file);
+		if id.is_some() && platform::file_id(&self.path) == id {
+			if let Err(e) = std::fs::remove_file(&self.path) {
+				event!(Level::WARN, "Failed to remove lock file {:?}: {}",
+					self.path, e);
+			}
+		}
+		if let Err(e) = self.file.unlock() {
+			event!(Level::WARN, "Failed to release lock on {:?}: {}",
+				self.path, e);
+		}
+	}
+}
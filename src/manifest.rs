@@ -0,0 +1,344 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Checksum manifests of stalled files.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::Stall;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use sha2::Sha512;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Manifest
+////////////////////////////////////////////////////////////////////////////////
+/// A record of the hashes of the files managed by a stall, used to detect
+/// corruption or tampering.
+///
+/// Local files are recorded by their paths relative to the stall directory, so
+/// the manifest remains valid if the stall directory is moved. Remote files
+/// are recorded by their full paths. Directories are recorded as the files
+/// they contain.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+	/// The algorithm used to hash the files.
+	#[serde(default)]
+	algorithm: HashAlgorithm,
+	/// The hashes of the stalled files, by local path.
+	local: BTreeMap<PathBuf, String>,
+	/// The hashes of the remote files, by remote path.
+	remote: BTreeMap<PathBuf, String>,
+}
+
+impl Manifest {
+	/// The default manifest file path, relative to the stall directory.
+	pub const DEFAULT_MANIFEST_PATH: &'static str = ".stall-manifest";
+
+	/// Constructs a new `Manifest` by hashing the current local and remote
+	/// files of the given [`Stall`] with the given [`HashAlgorithm`]. Missing
+	/// files are not recorded.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if any existing file cannot be read.
+	///
+	/// [`Stall`]: ../struct.Stall.html
+	/// [`HashAlgorithm`]: HashAlgorithm
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn generate(
+		stall_dir: &Path,
+		stall: &Stall,
+		algorithm: HashAlgorithm)
+		-> Result<Self, StallError>
+	{
+		let mut manifest = Self {
+			algorithm,
+			.. Self::default()
+		};
+		let locals: BTreeSet<&Path> = stall
+			.entries()
+			.map(|entry| entry.local)
+			.collect();
+		for local in locals {
+			hash_path(&mut manifest.local,
+				local,
+				&stall_dir.join(local),
+				algorithm)?;
+		}
+		for entry in stall.entries() {
+			hash_path(&mut manifest.remote,
+				entry.remote,
+				entry.remote,
+				algorithm)?;
+		}
+		Ok(manifest)
+	}
+
+	/// Reads the manifest file in the given stall directory.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the manifest file cannot be read or parsed.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn read_from_stall_dir(stall_dir: &Path) -> Result<Self, StallError> {
+		let path = stall_dir.join(Self::DEFAULT_MANIFEST_PATH);
+		let text = std::fs::read_to_string(&path)
+			.with_path("failed to read manifest", &path)?;
+		ron::from_str(&text)
+			.map_err(|e| StallError::ron_parse(
+				"failed to parse manifest",
+				Some(&path),
+				&e))
+	}
+
+	/// Writes the manifest file into the given stall directory, returning the
+	/// path of the written file.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the manifest file cannot be written.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn write_to_stall_dir(&self, stall_dir: &Path)
+		-> Result<PathBuf, StallError>
+	{
+		let path = stall_dir.join(Self::DEFAULT_MANIFEST_PATH);
+		let pretty = ron::ser::PrettyConfig::new();
+		let text = ron::ser::to_string_pretty(self, pretty)
+			.map_err(|e| StallError::ron_serialize(
+				"failed to serialize manifest",
+				&e))?;
+		std::fs::write(&path, text)
+			.with_path("failed to write manifest", &path)?;
+		Ok(path)
+	}
+
+	/// Returns the algorithm used to hash the files.
+	#[must_use]
+	pub const fn algorithm(&self) -> HashAlgorithm {
+		self.algorithm
+	}
+
+	/// Returns the number of files recorded in the manifest.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.local.len() + self.remote.len()
+	}
+
+	/// Returns `true` if the manifest records no files.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.local.is_empty() && self.remote.is_empty()
+	}
+
+	/// Returns an iterator over the recorded file locations, paths, and
+	/// hashes.
+	pub fn files(&self)
+		-> impl Iterator<Item=(FileLocation, &Path, &str)>
+	{
+		self.local
+			.iter()
+			.map(|(p, h)| (FileLocation::Local, p.as_path(), h.as_str()))
+			.chain(self.remote
+				.iter()
+				.map(|(p, h)| (FileLocation::Remote, p.as_path(), h.as_str())))
+	}
+
+	/// Compares the manifest against a `Manifest` of the current files,
+	/// returning the [`ManifestStatus`] of every file in either manifest.
+	///
+	/// [`ManifestStatus`]: ManifestStatus
+	#[must_use]
+	pub fn verify(&self, current: &Self)
+		-> Vec<(FileLocation, PathBuf, ManifestStatus)>
+	{
+		let mut res = Vec::new();
+		for (location, recorded, found) in [
+			(FileLocation::Local, &self.local, &current.local),
+			(FileLocation::Remote, &self.remote, &current.remote),
+		] {
+			let paths: BTreeSet<&PathBuf> = recorded
+				.keys()
+				.chain(found.keys())
+				.collect();
+			for path in paths {
+				let status = match (recorded.get(path), found.get(path)) {
+					(Some(a), Some(b)) if a == b => ManifestStatus::Ok,
+					(Some(_), Some(_))           => ManifestStatus::Changed,
+					(Some(_), None)              => ManifestStatus::Missing,
+					(None, _)                    => ManifestStatus::Unrecorded,
+				};
+				res.push((location, path.clone(), status));
+			}
+		}
+		res
+	}
+}
+
+
+/// Records the hashes of the file or directory at `path` into `hashes` using
+/// the given key. Files within directories are recorded with the key joined
+/// with their relative path.
+fn hash_path(
+	hashes: &mut BTreeMap<PathBuf, String>,
+	key: &Path,
+	path: &Path,
+	algorithm: HashAlgorithm)
+	-> Result<(), StallError>
+{
+	if path.is_dir() {
+		let dir = std::fs::read_dir(path)
+			.with_path("failed to read directory", path)?;
+		for dir_entry in dir {
+			let name = dir_entry
+				.with_path("failed to read directory", path)?
+				.file_name();
+			hash_path(hashes,
+				&key.join(&name),
+				&path.join(&name),
+				algorithm)?;
+		}
+	} else if path.exists() {
+		let _ = hashes.insert(key.to_path_buf(), hash_file(path, algorithm)?);
+	} else {
+		event!(Level::DEBUG, "Not hashing missing file {:?}", path);
+	}
+	Ok(())
+}
+
+
+/// Returns the hex-encoded hash of the file at the given path.
+pub(crate) fn hash_file(path: &Path, algorithm: HashAlgorithm)
+	-> Result<String, StallError>
+{
+	let mut file = File::open(path)
+		.with_path("failed to open", path)?;
+	let digest = match algorithm {
+		HashAlgorithm::Sha256 => digest::<Sha256>(&mut file),
+		HashAlgorithm::Sha512 => digest::<Sha512>(&mut file),
+	}.with_path("failed to read", path)?;
+
+	Ok(digest
+		.iter()
+		.fold(String::new(), |mut hex, b| {
+			let _ = write!(hex, "{b:02x}");
+			hex
+		}))
+}
+
+/// Returns the digest of the data read from `reader`.
+fn digest<D>(reader: &mut dyn std::io::Read) -> std::io::Result<Vec<u8>>
+	where D: Digest + std::io::Write
+{
+	let mut hasher = D::new();
+	let _ = std::io::copy(reader, &mut hasher)?;
+	Ok(hasher.finalize().to_vec())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// HashAlgorithm
+////////////////////////////////////////////////////////////////////////////////
+/// The algorithm used to hash manifest files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[derive(clap::ArgEnum)]
+pub enum HashAlgorithm {
+	/// The SHA-256 algorithm.
+	#[default]
+	Sha256,
+	/// The SHA-512 algorithm.
+	Sha512,
+}
+
+impl HashAlgorithm {
+	/// Returns the display name of the algorithm.
+	#[must_use]
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::Sha256 => "SHA-256",
+			Self::Sha512 => "SHA-512",
+		}
+	}
+}
+
+impl std::fmt::Display for HashAlgorithm {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Sha256 => write!(f, "sha256"),
+			Self::Sha512 => write!(f, "sha512"),
+		}
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// FileLocation
+////////////////////////////////////////////////////////////////////////////////
+/// Whether a manifest file is in the stall directory or a remote location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileLocation {
+	/// The file is in the stall directory.
+	Local,
+	/// The file is in a remote location.
+	Remote,
+}
+
+impl std::fmt::Display for FileLocation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Local  => write!(f, "local"),
+			Self::Remote => write!(f, "remote"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ManifestStatus
+////////////////////////////////////////////////////////////////////////////////
+/// The result of verifying a file against a manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestStatus {
+	/// The file matches its recorded hash.
+	Ok,
+	/// The file differs from its recorded hash.
+	Changed,
+	/// The file is recorded but no longer exists.
+	Missing,
+	/// The file exists but is not recorded.
+	Unrecorded,
+}
+
+impl std::fmt::Display for ManifestStatus {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Ok         => write!(f, "ok"),
+			Self::Changed    => write!(f, "changed"),
+			Self::Missing    => write!(f, "missing"),
+			Self::Unrecorded => write!(f, "new"),
+		}
+	}
+}
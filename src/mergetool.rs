@@ -0,0 +1,147 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! External merge tools for resolving conflicts.
+//!
+//! A merge tool is a command template configured with the `merge_tool`
+//! preference, either the name of a known tool (`meld`, `vimdiff`, or
+//! `kdiff3`) or a shell command containing these placeholders:
+//!
+//! + `{local}`: The stalled file.
+//! + `{remote}`: The remote file.
+//! + `{base}`: The most recently saved version of the stalled file, or an
+//!   empty file if no versions have been saved.
+//! + `{merged}`: The file to write the merged result to. It initially holds
+//!   the contents of the file being overwritten.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::atomic::replace_with;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::platform;
+
+// External library imports.
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The command templates of the known merge tools, by name.
+const KNOWN_TOOLS: [(&str, &str); 3] = [
+	("meld", "meld {local} {base} {remote} --output {merged}"),
+	("vimdiff", "vim -f -d -c \"wincmd J\" {merged} {local} {base} {remote}"),
+	("kdiff3", "kdiff3 {base} {local} {remote} -o {merged}"),
+];
+
+
+////////////////////////////////////////////////////////////////////////////////
+// MergeTool
+////////////////////////////////////////////////////////////////////////////////
+/// An external merge tool command.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MergeTool {
+	/// The command template, with the placeholders unsubstituted.
+	command: String,
+}
+
+impl MergeTool {
+	/// Returns the command template of the merge tool.
+	#[must_use]
+	pub fn command(&self) -> &str {
+		&self.command
+	}
+
+	/// Runs the merge tool for a conflict between the `local` and `remote`
+	/// files, replacing `target` with the merged result if the tool succeeds.
+	/// The `base` file is the most recently saved version of the stalled
+	/// file, if any.
+	pub(in crate) fn merge(
+		&self,
+		local: &Path,
+		remote: &Path,
+		base: Option<&Path>,
+		target: &Path,
+		durable: bool)
+		-> Result<(), StallError>
+	{
+		// Tools expect a base file, so an empty one is given if no version
+		// is recorded.
+		let empty_base = std::env::temp_dir()
+			.join(format!(".stall-merge-base-{}", std::process::id()));
+		let base = match base {
+			Some(base) => base,
+			None => {
+				std::fs::write(&empty_base, b"")
+					.with_path("failed to create", &empty_base)?;
+				&empty_base
+			},
+		};
+
+		let res = replace_with(target, durable, |merged| {
+			let _ = std::fs::copy(target, merged)
+				.with_path("failed to copy", target)?;
+			let placeholders = [
+				("local", local),
+				("remote", remote),
+				("base", base),
+				("merged", merged),
+			];
+			let mut command = self.command.clone();
+			for (name, path) in placeholders {
+				command = command.replace(
+					&format!("{{{name}}}"),
+					&platform::shell_quote(path));
+			}
+			event!(Level::INFO, "Running merge tool: {}", command);
+			let status = platform::shell_command(&command)
+				.status()
+				.with_path("failed to run merge tool for", target)?;
+			if status.success() {
+				Ok(())
+			} else {
+				Err(StallError::CommandFailed {
+					message: format!("merge tool failed for {}: {}",
+						target.display(),
+						status),
+				})
+			}
+		});
+		if base == empty_base { let _ = std::fs::remove_file(&empty_base); }
+		res
+	}
+}
+
+impl std::str::FromStr for MergeTool {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let text = text.trim();
+		if let Some((_, command)) = KNOWN_TOOLS
+			.iter()
+			.find(|(name, _)| *name == text)
+		{
+			return Ok(Self { command: (*command).to_owned() });
+		}
+		if text.contains("{merged}") {
+			Ok(Self { command: text.to_owned() })
+		} else {
+			Err(format!("invalid merge tool {text:?}: expected meld, vimdiff, \
+				kdiff3, or a command containing {{merged}}"))
+		}
+	}
+}
+
+impl std::fmt::Display for MergeTool {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.command)
+	}
+}
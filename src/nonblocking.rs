@@ -0,0 +1,134 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Async variants of the copy commands.
+//!
+//! These functions run the [`collect`] and [`distribute`] commands on tokio's
+//! blocking thread pool, so that applications embedding stall can run them
+//! without blocking their async tasks. They take the same options and return
+//! the same [`CopyReport`] as the sync API, but take their arguments by value
+//! so that they can be moved onto another thread. Conflicts can't be confirmed
+//! interactively, so the `confirm` option is not supported.
+//!
+//! This module requires the `async` feature.
+//!
+//! [`collect`]: ../command/fn.collect.html
+//! [`distribute`]: ../command/fn.distribute.html
+//! [`CopyReport`]: ../report/struct.CopyReport.html
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::entry::CopyOptions;
+use crate::entry::Overwrite;
+use crate::error::StallError;
+use crate::report::CopyReport;
+use crate::CommonOptions;
+use crate::DistributeOptions;
+use crate::Stall;
+
+// External library imports.
+use tokio::task::JoinError;
+
+// Standard library imports.
+use std::path::PathBuf;
+use std::sync::Arc;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// collect
+////////////////////////////////////////////////////////////////////////////////
+/// Runs the 'stall-collect' command on the blocking thread pool.
+///
+/// See [`collect`] for a description of the parameters. The status of each entry
+/// is not written, and the outcome of each entry is returned in the report.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the command fails, or if its task panics or is
+/// cancelled.
+///
+/// [`collect`]: ../command/fn.collect.html
+/// [`StallError`]: ../error/enum.StallError.html
+#[allow(clippy::too_many_arguments)]
+pub async fn collect(
+	stall_dir: PathBuf,
+	stall: Arc<Stall>,
+	files: Vec<PathBuf>,
+	overwrite: Overwrite,
+	unpin: bool,
+	options: CopyOptions,
+	dry_run: bool,
+	common: CommonOptions)
+	-> Result<CopyReport, StallError>
+{
+	tokio::task::spawn_blocking(move || crate::collect(
+			&mut std::io::sink(),
+			&stall_dir,
+			&stall,
+			files.iter().map(PathBuf::as_path),
+			overwrite,
+			unpin,
+			&options,
+			None,
+			None,
+			false,
+			dry_run,
+			&common))
+		.await
+		.map_err(task_error)?
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// distribute
+////////////////////////////////////////////////////////////////////////////////
+/// Runs the 'stall-distribute' command on the blocking thread pool.
+///
+/// See [`distribute`] for a description of the parameters. The status of each entry
+/// is not written, and the outcome of each entry is returned in the report.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the command fails, or if its task panics or is
+/// cancelled.
+///
+/// [`distribute`]: ../command/fn.distribute.html
+/// [`StallError`]: ../error/enum.StallError.html
+pub async fn distribute(
+	stall_dir: PathBuf,
+	stall: Arc<Stall>,
+	files: Vec<PathBuf>,
+	overwrite: Overwrite,
+	run: DistributeOptions,
+	options: CopyOptions,
+	common: CommonOptions)
+	-> Result<CopyReport, StallError>
+{
+	tokio::task::spawn_blocking(move || crate::distribute(
+			&mut std::io::sink(),
+			&stall_dir,
+			&stall,
+			files.iter().map(PathBuf::as_path),
+			overwrite,
+			run,
+			&options,
+			None,
+			None,
+			&common))
+		.await
+		.map_err(task_error)?
+}
+
+
+/// Converts the error of a failed task into a [`StallError`].
+///
+/// [`StallError`]: ../error/enum.StallError.html
+#[allow(clippy::needless_pass_by_value)]
+fn task_error(error: JoinError) -> StallError {
+	StallError::Task {
+		message: format!("copy task failed: {error}"),
+	}
+}
@@ -0,0 +1,144 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Notifications for failures of unattended commands.
+//!
+//! Commands run with `--notify`, such as scheduled commands, report errors and
+//! failed entries with a [`Notifier`], so that they don't go unnoticed. The
+//! notifier is configured with the `notify` preference: `desktop` for a
+//! desktop notification, or a shell command containing these placeholders:
+//!
+//! + `{title}`: The title of the notification.
+//! + `{message}`: The body of the notification.
+//!
+//! The title and message are also given to the command in the
+//! `STALL_NOTIFY_TITLE` and `STALL_NOTIFY_MESSAGE` environment variables.
+//!
+//! [`Notifier`]: enum.Notifier.html
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::platform;
+
+// External library imports.
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::path::Path;
+use std::process::Command;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Notifier
+////////////////////////////////////////////////////////////////////////////////
+/// The method of sending notifications.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Notifier {
+	/// Send a desktop notification, using `notify-send` on linux and
+	/// `osascript` on macOS.
+	#[default]
+	Desktop,
+	/// Run a shell command template.
+	Command(String),
+}
+
+impl Notifier {
+	/// Sends a notification with the given title and message.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the notification command can't be run or
+	/// fails, or if desktop notifications are not supported on the platform.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	#[allow(clippy::literal_string_with_formatting_args)]
+	pub fn notify(&self, title: &str, message: &str) -> Result<(), StallError> {
+		let mut command = match self {
+			Self::Desktop => desktop_command(title, message)?,
+			Self::Command(template) => {
+				let command = template
+					.replace("{title}", &platform::shell_quote(Path::new(title)))
+					.replace("{message}",
+						&platform::shell_quote(Path::new(message)));
+				platform::shell_command(&command)
+			},
+		};
+		let _ = command
+			.env("STALL_NOTIFY_TITLE", title)
+			.env("STALL_NOTIFY_MESSAGE", message);
+
+		event!(Level::INFO, "Running notification command: {:?}", command);
+		let program = command.get_program().to_owned();
+		let status = command
+			.status()
+			.with_path("failed to run", Path::new(&program))?;
+		if status.success() {
+			Ok(())
+		} else {
+			Err(StallError::CommandFailed {
+				message: format!("notification command failed: {status}"),
+			})
+		}
+	}
+}
+
+impl std::str::FromStr for Notifier {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		match text.trim() {
+			"desktop" => Ok(Self::Desktop),
+			""        => Err("invalid notifier: expected desktop, or a \
+				command using the {title} and {message} placeholders"
+				.to_owned()),
+			command   => Ok(Self::Command(command.to_owned())),
+		}
+	}
+}
+
+impl std::fmt::Display for Notifier {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Desktop          => write!(f, "desktop"),
+			Self::Command(command) => write!(f, "{command}"),
+		}
+	}
+}
+
+/// Returns the [`Command`] which sends a desktop notification.
+///
+/// [`Command`]: https://doc.rust-lang.org/stable/std/process/struct.Command.html
+fn desktop_command(title: &str, message: &str) -> Result<Command, StallError> {
+	if cfg!(windows) {
+		return Err(StallError::InvalidInput {
+			message: "desktop notifications are not supported on windows: \
+				set the notify preference to a command".to_owned(),
+		});
+	}
+
+	if cfg!(target_os = "macos") {
+		let quote = |text: &str| format!("\"{}\"", text
+			.replace('\\', r"\\")
+			.replace('"', "\\\""));
+		let mut command = Command::new("osascript");
+		let _ = command
+			.arg("-e")
+			.arg(format!("display notification {} with title {}",
+				quote(message),
+				quote(title)));
+		return Ok(command);
+	}
+
+	let mut command = Command::new("notify-send");
+	let _ = command
+		.args(["--app-name", "stall"])
+		.arg(title)
+		.arg(message);
+	Ok(command)
+}
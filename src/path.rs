@@ -0,0 +1,182 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Path normalization.
+//!
+//! The same remote file may be spelled many ways (`~/.bashrc`,
+//! `/home/user/.bashrc`, `../user/.bashrc`.) Remote paths are normalized when
+//! they are given on the command line, and cleaned when they are inserted into
+//! or looked up in a stall, so that equivalent spellings refer to the same
+//! entry. Remote paths with a URL scheme are cleaned by their
+//! [`RemoteBackend`].
+//!
+//! [`RemoteBackend`]: ../remote/trait.RemoteBackend.html
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::platform;
+use crate::remote;
+
+// Standard library imports.
+use std::io::BufRead;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// normalize
+////////////////////////////////////////////////////////////////////////////////
+/// Returns an absolute, cleaned version of the given path.
+///
+/// A leading `~` component is replaced with the user's home directory, and
+/// relative paths are resolved against `base`. The result is then [`clean`]ed.
+/// The file system is not accessed, so symbolic links are not resolved.
+/// Paths with a URL scheme are only [`clean`]ed.
+///
+/// ### Parameters
+///
+/// + `path`: The path to normalize.
+/// + `base`: The directory to resolve relative paths against, typically the
+///   current directory.
+///
+/// [`clean`]: fn.clean.html
+#[must_use]
+pub fn normalize(path: &Path, base: &Path) -> PathBuf {
+	if remote::scheme(path).is_some() { return clean(path); }
+
+	let mut components = path.components();
+	let expanded = match components.next() {
+		Some(Component::Normal(first)) if first == "~" => platform::home_dir()
+			.map(|home| home.join(components.as_path())),
+		_ => None,
+	};
+	let path = expanded.as_deref().unwrap_or(path);
+
+	if path.is_absolute() {
+		clean(path)
+	} else {
+		clean(&base.join(path))
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// canonicalize
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the canonical form of the given path, with all symbolic links
+/// resolved. If the path does not exist, the [`clean`]ed path is returned.
+///
+/// [`clean`]: fn.clean.html
+#[must_use]
+pub fn canonicalize(path: &Path) -> PathBuf {
+	if remote::scheme(path).is_some() { return clean(path); }
+	std::fs::canonicalize(path)
+		.map_or_else(|_| clean(path), |canonical| clean(&canonical))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// clean
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the given path with `.` components removed, and `..` components
+/// removed along with the components they refer to.
+///
+/// Leading `..` components of a relative path are retained, and `..`
+/// components following a root are dropped. On windows, verbatim (`\\?\`)
+/// prefixes are removed where possible, and drive letters are converted to
+/// upper case.
+///
+/// Paths with a URL scheme are cleaned by the [`RemoteBackend`] for the
+/// scheme, and paths with unsupported schemes are returned unchanged.
+///
+/// [`RemoteBackend`]: ../remote/trait.RemoteBackend.html
+#[must_use]
+pub fn clean(path: &Path) -> PathBuf {
+	remote::backend(path)
+		.map_or_else(|_| path.to_path_buf(), |backend| backend.clean(path))
+}
+
+/// Returns the given local path [`clean`]ed.
+///
+/// [`clean`]: fn.clean.html
+pub(crate) fn clean_local(path: &Path) -> PathBuf {
+	platform::normalize_drive(platform::strip_verbatim(clean_lexical(path)))
+}
+
+/// Returns the given path with `.` and `..` components resolved, as described
+/// for [`clean`].
+///
+/// [`clean`]: fn.clean.html
+pub(crate) fn clean_lexical(path: &Path) -> PathBuf {
+	let mut cleaned: Vec<Component<'_>> = Vec::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => (),
+			Component::ParentDir => match cleaned.last() {
+				Some(Component::Normal(_)) => { let _ = cleaned.pop(); },
+				Some(Component::RootDir | Component::Prefix(_)) => (),
+				Some(Component::ParentDir | Component::CurDir) |
+				None => cleaned.push(component),
+			},
+			_ => cleaned.push(component),
+		}
+	}
+
+	let mut path: PathBuf = cleaned.iter().collect();
+	if path.as_os_str().is_empty() { path.push("."); }
+	path
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// same
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the given paths refer to the same file, ignoring case on
+/// Windows. The paths are compared as given, so they should be [`clean`]ed
+/// first.
+///
+/// [`clean`]: fn.clean.html
+#[must_use]
+pub fn same(a: &Path, b: &Path) -> bool {
+	if cfg!(windows) {
+		a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+	} else {
+		a == b
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// read_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Reads the paths given by the lines of `input`, such as the output of `find`
+/// or `fd` piped into stdin. Empty paths are skipped.
+///
+/// ### Parameters
+///
+/// + `input`: The input to read the paths from.
+/// + `null`: Separate the paths with NUL bytes instead of newlines, as in the
+///   output of `find -print0`.
+///
+/// ### Errors
+///
+/// Returns an [`io::Error`] if the input can't be read.
+///
+/// [`io::Error`]: https://doc.rust-lang.org/stable/std/io/struct.Error.html
+pub fn read_paths<R>(input: R, null: bool) -> std::io::Result<Vec<PathBuf>>
+	where R: BufRead
+{
+	let separator = if null { b'\0' } else { b'\n' };
+	let mut paths = Vec::new();
+	for path in input.split(separator) {
+		let mut path = path?;
+		if !null && path.last() == Some(&b'\r') { let _ = path.pop(); }
+		if path.is_empty() { continue; }
+		paths.push(platform::path_from_bytes(path));
+	}
+	Ok(paths)
+}
@@ -0,0 +1,132 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Interactive entry selection.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::command::CommonOptions;
+use crate::error::StallError;
+use crate::Stall;
+
+// External library imports.
+use dialoguer::console::Term;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::theme::SimpleTheme;
+use dialoguer::theme::Theme;
+use dialoguer::FuzzySelect;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// pick_entries
+////////////////////////////////////////////////////////////////////////////////
+/// Asks the user to pick entries of the [`Stall`] using a fuzzy selector,
+/// returning the local paths of the picked entries.
+///
+/// Entries are picked one at a time, and picked entries are removed from the
+/// selector. Picking ends when the user presses `Esc` or `q`, or when no
+/// entries remain. The selector is drawn on stderr.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if stderr is not a terminal, or if an IO error
+/// occurs while drawing the selector or reading the input.
+///
+/// [`Stall`]: ../struct.Stall.html
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn pick_entries(stall: &Stall, verb: &str, common: &CommonOptions)
+	-> Result<Vec<PathBuf>, StallError>
+{
+	let mut items: Vec<(PathBuf, String)> = Vec::new();
+	for entry in stall.entries() {
+		if items.iter().any(|(local, _)| local == entry.local) { continue; }
+		let item = format!("{}  ({})",
+			entry.local.display(),
+			entry.remote.display());
+		items.push((entry.local.to_path_buf(), item));
+	}
+	pick_items(items, verb, common)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// pick_paths
+////////////////////////////////////////////////////////////////////////////////
+/// Asks the user to pick from the given paths using a fuzzy selector,
+/// returning the picked paths. Picking works as for [`pick_entries`].
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if stderr is not a terminal, or if an IO error
+/// occurs while drawing the selector or reading the input.
+///
+/// [`pick_entries`]: fn.pick_entries.html
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn pick_paths(paths: Vec<PathBuf>, verb: &str, common: &CommonOptions)
+	-> Result<Vec<PathBuf>, StallError>
+{
+	let items = paths
+		.into_iter()
+		.map(|path| {
+			let item = path.display().to_string();
+			(path, item)
+		})
+		.collect();
+	pick_items(items, verb, common)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helper functions
+////////////////////////////////////////////////////////////////////////////////
+/// Asks the user to pick from the given paths, shown in the selector as their
+/// paired items, returning the picked paths.
+fn pick_items(
+	mut remaining: Vec<(PathBuf, String)>,
+	verb: &str,
+	common: &CommonOptions)
+	-> Result<Vec<PathBuf>, StallError>
+{
+	let term = Term::stderr();
+	if !term.is_term() {
+		return Err(StallError::InvalidInput {
+			message: "--pick requires an interactive terminal".to_owned(),
+		});
+	}
+
+	let colorful = ColorfulTheme::default();
+	let theme: &dyn Theme = if common.color.enabled() {
+		&colorful
+	} else {
+		&SimpleTheme
+	};
+
+	let mut picked = Vec::new();
+	while !remaining.is_empty() {
+		let items: Vec<&str> = remaining
+			.iter()
+			.map(|(_, item)| item.as_str())
+			.collect();
+		let prompt = format!("Pick files to {verb} ({} picked, Esc to finish)",
+			picked.len());
+		let selection = FuzzySelect::with_theme(theme)
+			.with_prompt(prompt)
+			.items(&items)
+			.default(0)
+			.interact_on_opt(&term)?;
+
+		let Some(index) = selection else { break };
+		let (path, _) = remaining.remove(index);
+		event!(Level::DEBUG, "Picked {:?}", path);
+		picked.push(path);
+	}
+	Ok(picked)
+}
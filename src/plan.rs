@@ -0,0 +1,378 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Plans of the copies made by `collect` and `distribute`.
+//!
+//! A plan records the entries a dry run would copy, so that the copies can be
+//! reviewed and then applied with `stall apply-plan`, which copies exactly the
+//! planned entries. The hash of each planned source file is recorded, and the
+//! copy is refused if the file has changed since the plan was written.
+//!
+//! Plans are also used by the `--confirm-plan` option of `collect` and
+//! `distribute`, which reviews the plan of a dry run and applies it after a
+//! single confirmation.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::CommonOptions;
+use crate::entry::Entry;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::manifest::hash_file;
+use crate::manifest::HashAlgorithm;
+use crate::remote;
+use crate::report::Cell;
+use crate::report::CopyReport;
+use crate::report::Table;
+use crate::script::write_plan_script;
+
+// External library imports.
+use colored::Color;
+use colored::Colorize as _;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PlanFormat
+////////////////////////////////////////////////////////////////////////////////
+/// The format a plan file is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(clap::ArgEnum)]
+pub enum PlanFormat {
+	/// A JSON plan, which can be applied with `stall apply-plan`.
+	#[default]
+	Json,
+	/// A POSIX shell script which makes the planned copies.
+	Sh,
+}
+
+impl PlanFormat {
+	/// Returns the format indicated by the given plan file's extension.
+	#[must_use]
+	pub fn from_path(path: &Path) -> Option<Self> {
+		let ext = path.extension()?.to_str()?.to_lowercase();
+		match ext.as_str() {
+			"json" => Some(Self::Json),
+			"sh"   => Some(Self::Sh),
+			_      => None,
+		}
+	}
+}
+
+impl std::fmt::Display for PlanFormat {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Json => write!(f, "json"),
+			Self::Sh   => write!(f, "sh"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PlanCommand
+////////////////////////////////////////////////////////////////////////////////
+/// The command whose copies are planned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanCommand {
+	/// Remote files are copied into the stall directory.
+	Collect,
+	/// Stalled files are copied to their remote locations.
+	Distribute,
+}
+
+impl std::fmt::Display for PlanCommand {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Collect    => write!(f, "collect"),
+			Self::Distribute => write!(f, "distribute"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Plan
+////////////////////////////////////////////////////////////////////////////////
+/// The copies planned by a dry run of `collect` or `distribute`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct Plan {
+	/// The schema version of the plan file.
+	version: u32,
+	/// The command whose copies are planned.
+	command: PlanCommand,
+	/// The stall directory the plan was written for.
+	stall_dir: PathBuf,
+	/// The planned copies, in the order they are made.
+	steps: Vec<PlanStep>,
+}
+
+impl Plan {
+	/// The current schema version of plan files.
+	pub const SCHEMA_VERSION: u32 = 1;
+
+	/// Constructs a new empty `Plan` for the given command and stall
+	/// directory. The stall directory is recorded as an absolute path, so the
+	/// plan can be applied from any working directory.
+	#[must_use]
+	pub fn new(command: PlanCommand, stall_dir: &Path) -> Self {
+		Self {
+			version: Self::SCHEMA_VERSION,
+			command,
+			stall_dir: std::path::absolute(stall_dir)
+				.unwrap_or_else(|_| stall_dir.to_path_buf()),
+			steps: Vec::new(),
+		}
+	}
+
+	/// Returns the command whose copies are planned.
+	#[must_use]
+	pub const fn command(&self) -> PlanCommand {
+		self.command
+	}
+
+	/// Returns the stall directory the plan was written for.
+	#[must_use]
+	pub fn stall_dir(&self) -> &Path {
+		&self.stall_dir
+	}
+
+	/// Returns the planned copies, in the order they are made.
+	#[must_use]
+	pub fn steps(&self) -> &[PlanStep] {
+		&self.steps
+	}
+
+	/// Returns `true` if no copies are planned.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.steps.is_empty()
+	}
+
+	/// Adds a copy of the given entry's file to the plan, recording the hash
+	/// of its source file if it is a local file.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the source file cannot be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn push(&mut self, entry: &Entry<'_>) -> Result<(), StallError> {
+		let step = PlanStep {
+			local: entry.local.to_path_buf(),
+			remote: entry.remote.to_path_buf(),
+			hash: None,
+		};
+		let source = step.source(self);
+		let hash = if remote::scheme(&source).is_none() && source.is_file() {
+			Some(hash_file(&source, HashAlgorithm::Sha256)?)
+		} else {
+			None
+		};
+		self.steps.push(PlanStep { hash, ..step });
+		Ok(())
+	}
+
+	/// Reads a JSON plan file from the given path.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the file cannot be read or parsed, or if it
+	/// was written with a newer schema version.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn read_from_path(path: &Path) -> Result<Self, StallError> {
+		let text = std::fs::read_to_string(path)
+			.with_path("failed to read plan", path)?;
+		let plan: Self = serde_json::from_str(&text)
+			.map_err(|e| StallError::json_parse(
+				"failed to parse plan",
+				Some(path),
+				&e))?;
+		if plan.version > Self::SCHEMA_VERSION {
+			return Err(StallError::UnsupportedVersion {
+				path: Some(path.to_path_buf()),
+				version: plan.version,
+				supported: Self::SCHEMA_VERSION,
+			});
+		}
+		Ok(plan)
+	}
+
+	/// Writes the plan to the given path in the given [`PlanFormat`].
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the plan cannot be serialized or written.
+	///
+	/// [`PlanFormat`]: PlanFormat
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn write_to_path(&self, path: &Path, format: PlanFormat)
+		-> Result<(), StallError>
+	{
+		event!(Level::INFO, "Writing {} plan {:?}", format, path);
+		let mut text = Vec::new();
+		match format {
+			PlanFormat::Json => {
+				serde_json::to_writer_pretty(&mut text, self)
+					.map_err(|e| StallError::Serialize {
+						context: "failed to serialize plan".to_owned(),
+						message: e.to_string(),
+					})?;
+				text.push(b'\n');
+			},
+			PlanFormat::Sh => write_plan_script(&mut text, self)?,
+		}
+		std::fs::write(path, text)
+			.with_path("failed to write plan", path)?;
+		if format == PlanFormat::Sh { crate::platform::set_executable(path)?; }
+		Ok(())
+	}
+
+	/// Writes a review of the plan into `out`, listing the files which would
+	/// be created or overwritten, followed by the failed entries of the dry
+	/// run `report` the plan was recorded from, such as entries with
+	/// conflicts, which are not planned to be copied. Nothing is written if
+	/// `common.quiet` is set.
+	///
+	/// ### Errors
+	///
+	/// Returns an error if writing into `out` fails.
+	pub fn write_review(
+		&self,
+		out: &mut dyn Write,
+		report: &CopyReport,
+		common: &CommonOptions)
+		-> std::io::Result<()>
+	{
+		if common.quiet { return Ok(()); }
+
+		let mut table = Table::new(["Action", "File"]);
+		let mut creates = 0;
+		for step in &self.steps {
+			let target = step.target(self);
+			let action = if step.creates(self) {
+				creates += 1;
+				Cell::new("create").with_color(Color::BrightGreen)
+			} else {
+				Cell::new("overwrite").with_color(Color::BrightYellow)
+			};
+			table.push_row(vec![action, Cell::new(target.display()
+				.to_string())]);
+		}
+		for (local, error) in report.failures() {
+			let action = match error {
+				StallError::Conflict { .. } => "conflict",
+				_                           => "error",
+			};
+			table.push_row(vec![
+				Cell::new(action).with_color(Color::BrightRed),
+				Cell::new(format!("{}: {}", local.display(), error)),
+			]);
+		}
+
+		if table.is_empty() {
+			return writeln!(out, "Nothing to {}.", self.command);
+		}
+		writeln!(out, "Plan to {} in {}:", self.command,
+			self.stall_dir.display())?;
+		table.write(out, common)?;
+
+		let failures = report.failures().count();
+		let totals = format!("{} to create, {} to overwrite, {} not copied",
+			creates,
+			self.steps.len() - creates,
+			failures);
+		if common.color.enabled() {
+			writeln!(out, "{}", totals.bright_white())
+		} else {
+			writeln!(out, "{totals}")
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PlanStep
+////////////////////////////////////////////////////////////////////////////////
+/// A planned copy of an entry's file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct PlanStep {
+	/// The local path of the entry.
+	pub local: PathBuf,
+	/// The remote path of the entry.
+	pub remote: PathBuf,
+	/// The SHA-256 hash of the source file when the plan was written, if it
+	/// was a local file.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub hash: Option<String>,
+}
+
+impl PlanStep {
+	/// Returns the path of the file copied by the step of the given plan.
+	#[must_use]
+	pub fn source(&self, plan: &Plan) -> PathBuf {
+		match plan.command {
+			PlanCommand::Collect    => self.remote.clone(),
+			PlanCommand::Distribute => plan.stall_dir.join(&self.local),
+		}
+	}
+
+	/// Returns the path of the file overwritten by the step of the given plan.
+	#[must_use]
+	pub fn target(&self, plan: &Plan) -> PathBuf {
+		match plan.command {
+			PlanCommand::Collect    => plan.stall_dir.join(&self.local),
+			PlanCommand::Distribute => self.remote.clone(),
+		}
+	}
+
+	/// Returns `true` if the file overwritten by the step of the given plan
+	/// does not exist yet.
+	#[must_use]
+	pub fn creates(&self, plan: &Plan) -> bool {
+		let target = self.target(plan);
+		remote::backend(&target)
+			.and_then(|backend| backend.modified(&target))
+			.is_ok_and(|modified| modified.is_none())
+	}
+
+	/// Checks that the source file has not changed since the plan was written.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the source file's hash differs from the
+	/// recorded hash, or if it cannot be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn check_source(&self, plan: &Plan) -> Result<(), StallError> {
+		let Some(hash) = &self.hash else { return Ok(()) };
+		let source = self.source(plan);
+		let current = source
+			.is_file()
+			.then(|| hash_file(&source, HashAlgorithm::Sha256))
+			.transpose()?;
+		if current.as_ref() == Some(hash) { return Ok(()); }
+		Err(StallError::Conflict {
+			reason: "file has changed since the plan was written".to_owned(),
+			subject: source.display().to_string(),
+			hint: Some("Write a new plan, or use --force option to apply \
+				it anyway.".to_owned()),
+		})
+	}
+}
@@ -0,0 +1,572 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Platform-specific file system and process functions.
+//!
+//! Code which depends on the target platform is kept in this module, so that
+//! the rest of the crate behaves the same on unix systems and on windows. The
+//! path text functions are compiled on every platform so that they can be
+//! tested anywhere.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::entry::Attribute;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+
+// External library imports.
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::fs::DirBuilder;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Environment
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the user's home directory, from `USERPROFILE` on windows and from
+/// `HOME` otherwise.
+#[must_use]
+pub fn home_dir() -> Option<PathBuf> {
+	let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+	std::env::var_os(var)
+		.filter(|home| !home.is_empty())
+		.map(PathBuf::from)
+}
+
+/// Returns a [`Command`] which runs the given command string using the system
+/// shell: `cmd /C` on windows and `sh -c` otherwise.
+///
+/// [`Command`]: https://doc.rust-lang.org/stable/std/process/struct.Command.html
+#[must_use]
+pub fn shell_command(command: &str) -> Command {
+	let mut shell = if cfg!(windows) {
+		let mut shell = Command::new("cmd");
+		let _ = shell.arg("/C");
+		shell
+	} else {
+		let mut shell = Command::new("sh");
+		let _ = shell.arg("-c");
+		shell
+	};
+	let _ = shell.arg(command);
+	shell
+}
+
+/// Returns the given path quoted for use as a single word in a command run by
+/// [`shell_command`]: in double quotes on windows and in single quotes
+/// otherwise.
+///
+/// [`shell_command`]: fn.shell_command.html
+#[must_use]
+pub fn shell_quote(path: &Path) -> String {
+	let text = path.to_string_lossy();
+	if cfg!(windows) {
+		format!("\"{}\"", text.replace('"', ""))
+	} else {
+		format!("'{}'", text.replace('\'', r"'\''"))
+	}
+}
+
+/// Returns a [`Command`] which recursively copies `source` to `target`,
+/// preserving file attributes: `Xcopy` on windows and `cp -R -p` otherwise.
+///
+/// If both are directories, the contents of `source` are copied into
+/// `target`, rather than `source` itself.
+///
+/// [`Command`]: https://doc.rust-lang.org/stable/std/process/struct.Command.html
+#[must_use]
+pub fn copy_command(source: &Path, target: &Path) -> Command {
+	if cfg!(windows) {
+		let mut copy = Command::new("Xcopy");
+		let _ = copy
+			.arg(source)
+			.arg(target)
+			.args(["/h", "/s", "/e", "/x", "/y", "/i"]);
+		copy
+	} else {
+		// NOTE: -R (recursive dir copy) and -p (preserve attribute such as
+		// timestamps) are POSIX requirements.
+		let source = if source.is_dir() && target.is_dir() {
+			source.join(".")
+		} else {
+			source.to_path_buf()
+		};
+		let mut copy = Command::new("cp");
+		let _ = copy
+			.args(["-R", "-p"])
+			.arg(source)
+			.arg(target);
+		copy
+	}
+}
+
+/// Returns a [`Command`] which clones `source` to `target` using copy-on-write
+/// reflinks, or `None` if the platform has no such command. The command fails
+/// if the file system doesn't support reflinks.
+///
+/// On linux, files are cloned with `cp --reflink=always`, which supports btrfs
+/// and XFS, among others. On macOS, files are cloned with `cp -c`, which
+/// supports APFS.
+///
+/// [`Command`]: https://doc.rust-lang.org/stable/std/process/struct.Command.html
+#[must_use]
+pub fn reflink_command(source: &Path, target: &Path) -> Option<Command> {
+	let clone_arg = if cfg!(target_os = "linux") {
+		"--reflink=always"
+	} else if cfg!(target_os = "macos") {
+		"-c"
+	} else {
+		return None;
+	};
+	let mut copy = Command::new("cp");
+	let _ = copy
+		.args(["-R", "-p", clone_arg])
+		.arg(source)
+		.arg(target);
+	Some(copy)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Attributes
+////////////////////////////////////////////////////////////////////////////////
+/// Copies the given [`Attribute`] of the file at `source` to the file at
+/// `target`. Returns a description of the failure if the attribute can't be
+/// copied.
+///
+/// On linux, the attributes are copied with `cp --attributes-only`. On macOS,
+/// extended attributes and ACLs are already copied by `cp -p`, so nothing is
+/// done. Other platforms are not supported.
+///
+/// [`Attribute`]: ../entry/enum.Attribute.html
+pub fn copy_attribute(source: &Path, target: &Path, attribute: Attribute)
+	-> Result<(), String>
+{
+	if cfg!(target_os = "macos") { return Ok(()); }
+	if !cfg!(target_os = "linux") {
+		return Err("not supported on this platform".to_owned());
+	}
+
+	// The `mode` attribute includes ACLs.
+	let preserve = match attribute {
+		Attribute::Xattr => "--preserve=xattr",
+		Attribute::Acl   => "--preserve=mode",
+	};
+	let mut cp = Command::new("cp");
+	let _ = cp.args(["--attributes-only", "--no-dereference", preserve]);
+	if source.is_dir() {
+		// Copy onto the existing directory rather than into it.
+		let _ = cp.arg("-R").arg(source.join(".")).arg(target);
+	} else {
+		let _ = cp.arg(source).arg(target);
+	}
+
+	let output = cp.output().map_err(|e| e.to_string())?;
+	if output.status.success() {
+		Ok(())
+	} else {
+		Err(String::from_utf8_lossy(&output.stderr).trim().to_owned())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Permissions
+////////////////////////////////////////////////////////////////////////////////
+/// Sets the permissions of directories created by the given [`DirBuilder`] to
+/// the permissions of the directory at `parent`. Windows has no permission
+/// bits, so this is a no-op there.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the permissions of `parent` can't be read.
+///
+/// [`DirBuilder`]: https://doc.rust-lang.org/stable/std/fs/struct.DirBuilder.html
+/// [`StallError`]: ../error/enum.StallError.html
+#[cfg(unix)]
+pub fn inherit_permissions(builder: &mut DirBuilder, parent: &Path)
+	-> Result<(), StallError>
+{
+	use std::os::unix::fs::DirBuilderExt as _;
+	use std::os::unix::fs::PermissionsExt as _;
+
+	let mode = std::fs::metadata(parent)
+		.with_path("failed to read permissions of", parent)?
+		.permissions()
+		.mode();
+	let _ = builder.mode(mode & 0o7777);
+	Ok(())
+}
+
+/// Sets the permissions of directories created by the given [`DirBuilder`] to
+/// the permissions of the directory at `parent`. Windows has no permission
+/// bits, so this is a no-op there.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the permissions of `parent` can't be read.
+///
+/// [`DirBuilder`]: https://doc.rust-lang.org/stable/std/fs/struct.DirBuilder.html
+/// [`StallError`]: ../error/enum.StallError.html
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+pub fn inherit_permissions(_builder: &mut DirBuilder, _parent: &Path)
+	-> Result<(), StallError>
+{
+	Ok(())
+}
+
+/// Makes the file at `path` executable by everyone who can read it. Windows
+/// has no permission bits, so this is a no-op there.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the permissions of the file can't be read or
+/// changed.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+#[cfg(unix)]
+pub fn set_executable(path: &Path) -> Result<(), StallError> {
+	use std::os::unix::fs::PermissionsExt as _;
+
+	let mut permissions = std::fs::metadata(path)
+		.with_path("failed to read permissions of", path)?
+		.permissions();
+	let mode = permissions.mode();
+	permissions.set_mode(mode | ((mode & 0o444) >> 2));
+	std::fs::set_permissions(path, permissions)
+		.with_path("failed to set permissions of", path)
+}
+
+/// Makes the file at `path` executable by everyone who can read it. Windows
+/// has no permission bits, so this is a no-op there.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the permissions of the file can't be read or
+/// changed.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+pub fn set_executable(_path: &Path) -> Result<(), StallError> {
+	Ok(())
+}
+
+/// Returns the path with the given bytes. On unix, paths may be any bytes, but
+/// elsewhere they must be UTF-8, so invalid bytes are replaced.
+#[cfg(unix)]
+#[must_use]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+	use std::os::unix::ffi::OsStringExt as _;
+
+	PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+/// Returns the path with the given bytes. On unix, paths may be any bytes, but
+/// elsewhere they must be UTF-8, so invalid bytes are replaced.
+#[cfg(not(unix))]
+#[must_use]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+	PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Returns `true` if directories can be opened and synced to disk.
+#[must_use]
+pub const fn supports_dir_sync() -> bool {
+	!cfg!(windows)
+}
+
+/// Returns `true` if file contents can be copied by the kernel, using
+/// `copy_file_range` or `sendfile`, without being read into a buffer.
+#[must_use]
+pub const fn supports_kernel_copy() -> bool {
+	cfg!(any(target_os = "linux", target_os = "android"))
+}
+
+/// Allocates disk space for `len` bytes of the file, without changing its
+/// size, so that it isn't fragmented as it is written. Does nothing on
+/// platforms without `fallocate`.
+///
+/// ### Errors
+///
+/// Returns an error if the space can't be allocated, e.g. because the file
+/// system doesn't support it.
+#[cfg(target_os = "linux")]
+pub fn preallocate(file: &std::fs::File, len: u64) -> std::io::Result<()> {
+	use nix::fcntl::FallocateFlags;
+	use std::os::unix::io::AsRawFd as _;
+
+	if len == 0 { return Ok(()); }
+	let len = len.try_into()
+		.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+	nix::fcntl::fallocate(
+			file.as_raw_fd(),
+			FallocateFlags::FALLOC_FL_KEEP_SIZE,
+			0,
+			len)
+		.map_err(std::io::Error::from)
+}
+
+/// Allocates disk space for `len` bytes of the file, without changing its
+/// size, so that it isn't fragmented as it is written. Does nothing on
+/// platforms without `fallocate`.
+///
+/// ### Errors
+///
+/// Returns an error if the space can't be allocated, e.g. because the file
+/// system doesn't support it.
+#[cfg(not(target_os = "linux"))]
+#[allow(clippy::unnecessary_wraps)]
+pub fn preallocate(_file: &std::fs::File, _len: u64) -> std::io::Result<()> {
+	Ok(())
+}
+
+/// Returns the device and inode numbers of the file at `path`, following
+/// symbolic links, or `None` if its metadata can't be read. Hard links to the
+/// same file have the same numbers.
+#[cfg(unix)]
+#[must_use]
+pub fn file_id(path: &Path) -> Option<(u64, u64)> {
+	use std::os::unix::fs::MetadataExt as _;
+
+	let metadata = std::fs::metadata(path).ok()?;
+	Some((metadata.dev(), metadata.ino()))
+}
+
+/// Returns the device and inode numbers of the file at `path`, following
+/// symbolic links, or `None` if its metadata can't be read. File IDs are not
+/// available from the standard library on windows, so `None` is returned.
+#[cfg(not(unix))]
+#[must_use]
+pub fn file_id(_path: &Path) -> Option<(u64, u64)> {
+	None
+}
+
+/// Returns the device and inode numbers of an open file, or `None` if its
+/// metadata can't be read.
+///
+/// Unlike [`file_id`], this identifies the file that was opened even if its
+/// path has since been removed or replaced.
+///
+/// [`file_id`]: fn.file_id.html
+#[cfg(unix)]
+#[must_use]
+pub fn open_file_id(file: &std::fs::File) -> Option<(u64, u64)> {
+	use std::os::unix::fs::MetadataExt as _;
+
+	let metadata = file.metadata().ok()?;
+	Some((metadata.dev(), metadata.ino()))
+}
+
+/// Returns the device and inode numbers of an open file, or `None` if its
+/// metadata can't be read. File IDs are not available from the standard
+/// library on windows, so `None` is returned.
+#[cfg(not(unix))]
+#[must_use]
+pub fn open_file_id(_file: &std::fs::File) -> Option<(u64, u64)> {
+	None
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Errors
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the IO error is likely to be transient.
+///
+/// Transient errors include resources which are temporarily unavailable and
+/// lost network file system connections, so that the failed operation may
+/// succeed if it is retried.
+#[must_use]
+pub fn is_transient(error: &std::io::Error) -> bool {
+	use std::io::ErrorKind::*;
+
+	matches!(error.kind(),
+		WouldBlock
+		| Interrupted
+		| TimedOut
+		| ConnectionReset
+		| ConnectionAborted
+		| NotConnected
+		| ResourceBusy
+		| NetworkDown
+		| NetworkUnreachable
+		| HostUnreachable
+		| StaleNetworkFileHandle)
+		|| is_transient_os_error(error)
+}
+
+/// Returns `true` if the IO error has a platform error code which is likely
+/// to be transient.
+#[cfg(windows)]
+fn is_transient_os_error(error: &std::io::Error) -> bool {
+	// ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION, ERROR_UNEXP_NET_ERR,
+	// ERROR_NETNAME_DELETED, and ERROR_SEM_TIMEOUT.
+	matches!(error.raw_os_error(), Some(32 | 33 | 59 | 64 | 121))
+}
+
+/// Returns `true` if the IO error has a platform error code which is likely
+/// to be transient.
+#[cfg(not(windows))]
+const fn is_transient_os_error(_error: &std::io::Error) -> bool {
+	false
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Symbolic links
+////////////////////////////////////////////////////////////////////////////////
+/// Copies the symbolic link at `source` to `target`, creating a new link with
+/// the same destination.
+///
+/// Creating symbolic links on windows requires elevated privileges or
+/// developer mode. If the link can't be created there, a link to a directory
+/// is replaced with a junction, and a link to a file is replaced with a copy
+/// of the file.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the link can't be read or if no link, junction,
+/// or copy can be created.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn copy_symlink(source: &Path, target: &Path) -> Result<(), StallError> {
+	let destination = std::fs::read_link(source)
+		.with_path("failed to read link", source)?;
+	event!(Level::DEBUG, "Copying link {:?} -> {:?} to {:?}",
+		source, destination, target);
+	symlink(source, &destination, target)
+}
+
+/// Creates a symbolic link at `target` to `destination`, which is the
+/// destination of the existing link at `source`.
+#[cfg(unix)]
+fn symlink(_source: &Path, destination: &Path, target: &Path)
+	-> Result<(), StallError>
+{
+	std::os::unix::fs::symlink(destination, target)
+		.with_path("failed to create link", target)
+}
+
+/// Creates a symbolic link at `target` to `destination`, which is the
+/// destination of the existing link at `source`, falling back to a junction or
+/// copy if symbolic links are not permitted.
+#[cfg(windows)]
+fn symlink(source: &Path, destination: &Path, target: &Path)
+	-> Result<(), StallError>
+{
+	use std::os::windows::fs::symlink_dir;
+	use std::os::windows::fs::symlink_file;
+
+	let is_dir = source.is_dir();
+	let linked = if is_dir {
+		symlink_dir(destination, target)
+	} else {
+		symlink_file(destination, target)
+	};
+	match linked {
+		Ok(()) => return Ok(()),
+		// ERROR_PRIVILEGE_NOT_HELD
+		Err(e) if e.raw_os_error() == Some(1314) => (),
+		Err(e) => return Err(e).with_path("failed to create link", target),
+	}
+
+	if is_dir {
+		event!(Level::DEBUG, "Creating junction {:?} instead of link", target);
+		// Junctions must have absolute destinations.
+		let destination = std::fs::canonicalize(source)
+			.map(strip_verbatim)
+			.with_path("failed to resolve link", source)?;
+		let status = Command::new("cmd")
+			.args(["/C", "mklink", "/J"])
+			.arg(target)
+			.arg(&destination)
+			.status()
+			.with_path("failed to run mklink for", target)?;
+		if !status.success() {
+			return Err(StallError::CommandFailed {
+				message: format!("failed to create junction {}: {}",
+					target.display(),
+					status),
+			});
+		}
+	} else {
+		event!(Level::DEBUG, "Copying {:?} instead of creating link", source);
+		let _ = std::fs::copy(source, target)
+			.with_path("failed to copy", source)?;
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Path text
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the given windows path text without its `\\?\` verbatim prefix.
+///
+/// Returns `None` if the path can't be represented without the prefix. Verbatim
+/// UNC paths (`\\?\UNC\server\share`) are converted to plain UNC paths
+/// (`\\server\share`.)
+#[must_use]
+pub fn strip_verbatim_prefix(text: &str) -> Option<String> {
+	/// The maximum length of a windows path without a verbatim prefix.
+	const MAX_PATH: usize = 260;
+
+	let rest = text.strip_prefix(r"\\?\")?;
+	let plain = match rest.strip_prefix(r"UNC\") {
+		Some(unc) => format!(r"\\{unc}"),
+		None if rest.as_bytes().get(1) == Some(&b':') => rest.to_owned(),
+		None => return None,
+	};
+	(plain.len() < MAX_PATH).then_some(plain)
+}
+
+/// Returns the given windows path text with a lower case drive letter
+/// converted to upper case, or `None` if the path doesn't begin with a lower
+/// case drive letter.
+#[must_use]
+pub fn upper_case_drive_letter(text: &str) -> Option<String> {
+	let mut chars = text.chars();
+	match (chars.next(), chars.next()) {
+		(Some(drive), Some(':')) if drive.is_ascii_lowercase() => {
+			Some(format!("{}{}", drive.to_ascii_uppercase(), &text[1..]))
+		},
+		_ => None,
+	}
+}
+
+/// Converts a lower case drive letter prefix to upper case.
+#[cfg(windows)]
+pub(in crate) fn normalize_drive(path: PathBuf) -> PathBuf {
+	let upper = path.to_str().and_then(upper_case_drive_letter);
+	upper.map_or(path, PathBuf::from)
+}
+
+/// Converts a lower case drive letter prefix to upper case.
+#[cfg(not(windows))]
+pub(in crate) fn normalize_drive(path: PathBuf) -> PathBuf {
+	path
+}
+
+/// Removes the `\\?\` prefix added to canonicalized paths on windows, if the
+/// path can be represented without it.
+#[cfg(windows)]
+pub(in crate) fn strip_verbatim(path: PathBuf) -> PathBuf {
+	let stripped = path.to_str().and_then(strip_verbatim_prefix);
+	stripped.map_or(path, PathBuf::from)
+}
+
+/// Removes the `\\?\` prefix added to canonicalized paths on windows, if the
+/// path can be represented without it.
+#[cfg(not(windows))]
+pub(in crate) fn strip_verbatim(path: PathBuf) -> PathBuf {
+	path
+}
@@ -0,0 +1,260 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Privileged copies of root-owned remote files.
+//!
+//! The remote files of entries flagged as privileged, or of all entries when
+//! distributing with `--system`, are copied by commands run through an
+//! [`Escalation`] command such as `sudo`, so that files under `/etc` can be
+//! managed without running stall as root. The commands may instead be written
+//! into a shell script, to be reviewed and run separately as root.
+//!
+//! Privileged copies don't preserve the ownership or permissions of the
+//! stalled files: overwritten remote files keep their own, and new remote
+//! files are owned by the escalated user.
+//!
+//! [`Escalation`]: struct.Escalation.html
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::platform;
+
+// External library imports.
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The escalation commands which are known to work, by name.
+const KNOWN_ESCALATIONS: [&str; 3] = ["sudo", "doas", "pkexec"];
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Escalation
+////////////////////////////////////////////////////////////////////////////////
+/// A command which runs another command with elevated privileges, such as
+/// `sudo` or `doas`. Arguments may be included, e.g. `sudo -A`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Escalation {
+	/// The escalation command line.
+	command: String,
+}
+
+impl Escalation {
+	/// Returns the escalation command line.
+	#[must_use]
+	pub fn command(&self) -> &str {
+		&self.command
+	}
+
+	/// Returns a [`Command`] which runs the given [`PrivilegedCommand`]
+	/// through the escalation command.
+	///
+	/// [`Command`]: https://doc.rust-lang.org/stable/std/process/struct.Command.html
+	fn wrap(&self, privileged: &PrivilegedCommand) -> Command {
+		let mut words = self.command.split_whitespace();
+		let mut command = Command::new(words.next().unwrap_or_default());
+		let _ = command
+			.args(words)
+			.arg(privileged.program)
+			.args(&privileged.flags)
+			.args(&privileged.paths);
+		command
+	}
+}
+
+impl Default for Escalation {
+	fn default() -> Self {
+		Self { command: KNOWN_ESCALATIONS[0].to_owned() }
+	}
+}
+
+impl std::str::FromStr for Escalation {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let text = text.trim();
+		if text.is_empty() {
+			return Err(format!("invalid escalation command: expected {}, or \
+				another command", KNOWN_ESCALATIONS.join(", ")));
+		}
+		Ok(Self { command: text.to_owned() })
+	}
+}
+
+impl std::fmt::Display for Escalation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.command)
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Privilege
+////////////////////////////////////////////////////////////////////////////////
+/// Options for copying files to remote locations with elevated privileges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Privilege {
+	/// Copy the files of all entries with privileges, as if they were flagged
+	/// as privileged.
+	pub system: bool,
+	/// The command used to run privileged commands.
+	pub escalation: Escalation,
+	/// The shell script to append privileged commands to, instead of running
+	/// them. The script is meant to be run as root, so the commands are
+	/// written without the escalation command.
+	pub script: Option<PathBuf>,
+}
+
+impl Privilege {
+	/// The directory holding transformed copies of stalled files for
+	/// privileged copies, relative to the stall directory.
+	pub const STAGED_PATH: &'static str = ".stall-staged";
+
+	/// Creates the privileged command script, if there is one, replacing any
+	/// existing file.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the script can't be written, or if
+	/// privileged copies are not supported on the platform.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn start_script(&self) -> Result<(), StallError> {
+		let Some(script) = &self.script else { return Ok(()) };
+		check_supported()?;
+		std::fs::write(script, "#!/bin/sh\n\
+			# Privileged copies generated by `stall distribute`.\n\
+			set -e\n")
+			.with_path("failed to create privileged command script", script)?;
+		platform::set_executable(script)
+	}
+
+	/// Creates the given remote directory with privileges.
+	pub(in crate) fn create_dir(&self, dir: &Path) -> Result<(), StallError> {
+		self.execute(&[PrivilegedCommand {
+			program: "mkdir",
+			flags: vec!["-p"],
+			paths: vec![dir.to_path_buf()],
+		}])
+	}
+
+	/// Copies `source` to `target` with privileges. The copied file keeps
+	/// the modification time of `source`. If both are directories, the
+	/// contents of `source` are copied into `target`.
+	pub(in crate) fn copy(&self, source: &Path, target: &Path)
+		-> Result<(), StallError>
+	{
+		let contents = if source.is_dir() && target.is_dir() {
+			source.join(".")
+		} else {
+			source.to_path_buf()
+		};
+		self.execute(&[
+			PrivilegedCommand {
+				program: "cp",
+				flags: vec!["-R"],
+				paths: vec![contents, target.to_path_buf()],
+			},
+			PrivilegedCommand {
+				program: "touch",
+				flags: vec!["-r"],
+				paths: vec![source.to_path_buf(), target.to_path_buf()],
+			},
+		])
+	}
+
+	/// Runs the given commands through the escalation command, or appends
+	/// them to the script if there is one.
+	fn execute(&self, commands: &[PrivilegedCommand])
+		-> Result<(), StallError>
+	{
+		check_supported()?;
+		if let Some(script) = &self.script {
+			let mut file = OpenOptions::new()
+				.append(true)
+				.create(true)
+				.open(script)
+				.with_path("failed to open privileged command script",
+					script)?;
+			for privileged in commands {
+				writeln!(file, "{}", privileged.script_line())
+					.with_path("failed to write privileged command script",
+						script)?;
+			}
+			return Ok(());
+		}
+
+		for privileged in commands {
+			let mut command = self.escalation.wrap(privileged);
+			event!(Level::INFO, "Running privileged command: {:?}", command);
+			let status = command
+				.status()
+				.with_path("failed to run", Path::new(
+					self.escalation.command()))?;
+			if !status.success() {
+				return Err(StallError::CommandFailed {
+					message: format!("privileged command `{} {}` failed: {}",
+						self.escalation,
+						privileged.script_line(),
+						status),
+				});
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Returns an error if privileged copies are not supported on the platform.
+fn check_supported() -> Result<(), StallError> {
+	if cfg!(windows) {
+		Err(StallError::InvalidInput {
+			message: "privileged copies are not supported on windows"
+				.to_owned(),
+		})
+	} else {
+		Ok(())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PrivilegedCommand
+////////////////////////////////////////////////////////////////////////////////
+/// A command to run with elevated privileges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PrivilegedCommand {
+	/// The program to run.
+	program: &'static str,
+	/// The flags given before the paths.
+	flags: Vec<&'static str>,
+	/// The paths the command is applied to.
+	paths: Vec<PathBuf>,
+}
+
+impl PrivilegedCommand {
+	/// Returns the command as a line of a shell script, with the paths
+	/// quoted. The paths are absolute, so they can't be mistaken for flags.
+	fn script_line(&self) -> String {
+		let mut words: Vec<String> = vec![self.program.to_owned()];
+		words.extend(self.flags.iter().map(|&flag| flag.to_owned()));
+		words.extend(self.paths
+			.iter()
+			.map(|path| platform::shell_quote(path)));
+		words.join(" ")
+	}
+}
@@ -0,0 +1,676 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Remote file backends.
+//!
+//! Remote paths usually refer to files on the local machine, but may also be
+//! URLs of files stored elsewhere. Each remote path is handled by the
+//! [`RemoteBackend`] for its URL scheme, which is responsible for comparing,
+//! copying, and creating directories for the file. Plain paths and `file://`
+//! URLs are handled by the [`LocalBackend`].
+//!
+//! Files on other machines may be accessed over ssh using
+//! `ssh://[USER@]HOST[:PORT]/PATH` or `[USER@]HOST:PATH` paths. These are
+//! handled by the [`SshBackend`], which runs the `ssh` and `scp` commands, so
+//! any keys, agents, and host aliases in the user's ssh configuration are used.
+//!
+//! [`RemoteBackend`]: trait.RemoteBackend.html
+//! [`LocalBackend`]: struct.LocalBackend.html
+//! [`SshBackend`]: struct.SshBackend.html
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::entry::copy_with_options;
+use crate::entry::create_dir;
+use crate::entry::missing_dirs;
+use crate::entry::CopyMethod;
+use crate::entry::CopyMode;
+use crate::entry::CopyOptions;
+use crate::entry::Status;
+use crate::entry::Throttle;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::path;
+
+// External library imports.
+use fcmp::DiffOp;
+use fcmp::FileCmp;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Backend selection
+////////////////////////////////////////////////////////////////////////////////
+/// The available remote backends.
+static BACKENDS: [&dyn RemoteBackend; 2] = [&LocalBackend, &SshBackend];
+
+/// Returns the URL scheme of the given remote path, or `None` if it is a plain
+/// path. `[USER@]HOST:PATH` paths have the `ssh` scheme.
+#[must_use]
+pub fn scheme(path: &Path) -> Option<&str> {
+	let text = path.to_str()?;
+	if let Some((scheme, _)) = split_scheme(text) { return Some(scheme); }
+	SshPath::parse(path).map(|_| SshBackend.scheme())
+}
+
+/// Returns the [`RemoteBackend`] for the given remote path's URL scheme.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if no backend supports the path's scheme.
+///
+/// [`RemoteBackend`]: trait.RemoteBackend.html
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn backend(path: &Path) -> Result<&'static dyn RemoteBackend, StallError> {
+	let Some(scheme) = scheme(path) else { return Ok(&LocalBackend); };
+	BACKENDS
+		.iter()
+		.copied()
+		.find(|backend| backend.scheme() == scheme)
+		.ok_or_else(|| StallError::InvalidPath {
+			path: path.to_path_buf(),
+			reason: format!("unsupported remote scheme {scheme:?}"),
+		})
+}
+
+/// Splits a URL into its scheme and the text following `://`.
+fn split_scheme(text: &str) -> Option<(&str, &str)> {
+	let (scheme, rest) = text.split_once("://")?;
+	let mut chars = scheme.chars();
+	let valid = scheme.len() > 1
+		&& chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+		&& chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+	valid.then_some((scheme, rest))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// RemoteBackend
+////////////////////////////////////////////////////////////////////////////////
+/// A storage backend for the remote files of stall entries.
+///
+/// Backends are selected by the URL scheme of the remote path, and are given
+/// the full remote path, including the scheme, in each method. Local paths are
+/// always paths on the local machine.
+pub trait RemoteBackend: std::fmt::Debug + Sync {
+	/// Returns the URL scheme of the paths handled by the backend.
+	fn scheme(&self) -> &'static str;
+
+	/// Returns the normal form of the given remote path, which is used to
+	/// store and compare it.
+	fn clean(&self, remote: &Path) -> PathBuf;
+
+	/// Returns the modification time of the remote file, or `None` if it does
+	/// not exist.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the file's metadata can't be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	fn modified(&self, remote: &Path) -> Result<Option<SystemTime>, StallError>;
+
+	/// Returns the statuses of the local file and the remote file. By default,
+	/// their modification times are compared to the second.
+	fn status(&self, local: &Path, remote: &Path) -> (Status, Status) {
+		let remote = self.modified(remote)
+			.map_err(|e| event!(Level::DEBUG, "{e}"));
+		compare_modified(local, remote)
+	}
+
+	/// Copies the remote file to the local path using the given
+	/// [`CopyOptions`]. Returns the mode which was used to copy the file
+	/// contents.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the file can't be copied.
+	///
+	/// [`CopyOptions`]: ../entry/struct.CopyOptions.html
+	/// [`StallError`]: ../error/enum.StallError.html
+	fn fetch(&self, remote: &Path, local: &Path, options: &CopyOptions)
+		-> Result<CopyMode, StallError>;
+
+	/// Copies the local file to the remote path using the given
+	/// [`CopyOptions`], replacing any existing file. If `durable` is true, the
+	/// file is synced to storage where the backend supports it. Returns the
+	/// mode which was used to copy the file contents.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the file can't be copied.
+	///
+	/// [`CopyOptions`]: ../entry/struct.CopyOptions.html
+	/// [`StallError`]: ../error/enum.StallError.html
+	fn push(
+		&self,
+		local: &Path,
+		remote: &Path,
+		options: &CopyOptions,
+		durable: bool)
+		-> Result<CopyMode, StallError>;
+
+	/// Returns the ancestor directories of the remote path which do not exist,
+	/// ordered from outermost to innermost. By default, directories are not
+	/// checked, and an empty list is returned.
+	fn missing_dirs(&self, remote: &Path) -> Vec<PathBuf> {
+		let _ = remote;
+		Vec::new()
+	}
+
+	/// Creates the remote directory, whose parent must exist.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the directory can't be created. By default,
+	/// creating directories is not supported.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	fn create_dir(&self, dir: &Path) -> Result<(), StallError> {
+		Err(StallError::InvalidPath {
+			path: dir.to_path_buf(),
+			reason: format!("can't create directories for {} remotes",
+				self.scheme()),
+		})
+	}
+
+	/// Verifies that the remote file's storage is accessible, e.g. that any
+	/// commands the backend requires are installed. By default, no checks are
+	/// made.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the storage is not accessible.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	fn check(&self, remote: &Path) -> Result<(), StallError> {
+		let _ = remote;
+		Ok(())
+	}
+}
+
+/// Returns the statuses of the local file and a remote file with the given
+/// modification time, compared to the second.
+fn compare_modified(
+	local: &Path,
+	remote: Result<Option<SystemTime>, ()>)
+	-> (Status, Status)
+{
+	use Status::*;
+	use std::cmp::Ordering::*;
+
+	let local = match std::fs::metadata(local) {
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+		metadata => metadata.and_then(|m| m.modified()).map(Some),
+	};
+
+	let secs = |time: SystemTime| time
+		.duration_since(SystemTime::UNIX_EPOCH)
+		.map_or(0, |d| d.as_secs());
+
+	match (local, remote) {
+		(Err(_), Err(()))         => (Error, Error),
+		(Err(_), Ok(None))        => (Error, Absent),
+		(Err(_), Ok(Some(_)))     => (Error, Exists),
+		(Ok(None), Err(()))       => (Absent, Error),
+		(Ok(Some(_)), Err(()))    => (Exists, Error),
+		(Ok(None), Ok(None))      => (Absent, Absent),
+		(Ok(Some(_)), Ok(None))   => (Exists, Absent),
+		(Ok(None), Ok(Some(_)))   => (Absent, Exists),
+		(Ok(Some(l)), Ok(Some(r))) => match secs(l).cmp(&secs(r)) {
+			Less    => (Older, Newer),
+			Equal   => (Same,  Same),
+			Greater => (Newer, Older),
+		},
+	}
+}
+
+/// Returns the statuses of the local file when the remote file's backend is
+/// unavailable.
+#[must_use]
+pub fn unavailable_status(local: &Path) -> (Status, Status) {
+	compare_modified(local, Err(()))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// LocalBackend
+////////////////////////////////////////////////////////////////////////////////
+/// The backend for files on the local machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LocalBackend;
+
+impl RemoteBackend for LocalBackend {
+	fn scheme(&self) -> &'static str {
+		"file"
+	}
+
+	fn clean(&self, remote: &Path) -> PathBuf {
+		remote.to_str()
+			.and_then(|text| text.strip_prefix("file://"))
+			.map_or_else(
+				|| path::clean_local(remote),
+				|local| path::clean_local(Path::new(local)))
+	}
+
+	fn modified(&self, remote: &Path) -> Result<Option<SystemTime>, StallError> {
+		match std::fs::metadata(remote) {
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+			metadata => metadata
+				.and_then(|m| m.modified())
+				.map(Some)
+				.with_path("failed to read metadata for", remote),
+		}
+	}
+
+	fn status(&self, local: &Path, remote: &Path) -> (Status, Status) {
+		use Status::*;
+		use std::cmp::Ordering::*;
+
+		let diff_op = DiffOp::None;
+
+		let file_cmp_l = FileCmp::try_from(local.to_path_buf())
+			.map_err(|e| event!(Level::DEBUG, "{e}: {:?}", local));
+		let file_cmp_r = FileCmp::try_from(remote.to_path_buf())
+			.map_err(|e| event!(Level::DEBUG, "{e}: {:?}", remote));
+
+		event!(Level::TRACE, "LOCAL {:?}", file_cmp_l);
+		event!(Level::TRACE, "REMOTE {:?}", file_cmp_r);
+
+		match (file_cmp_l, file_cmp_r) {
+			(Err(()), Err(()))                => (Error, Error),
+			(Err(()), Ok(f)) if !f.is_found() => (Error, Absent),
+			(Err(()), Ok(_))                  => (Error, Exists),
+			(Ok(f), Err(())) if !f.is_found() => (Absent, Error),
+			(Ok(_), Err(()))                  => (Exists, Error),
+
+			(Ok(l), Ok(r)) => match (l.is_found(), r.is_found()) {
+				(false, false) => (Absent, Absent),
+				(true, false)  => (Exists, Absent),
+				(false, true)  => (Absent, Exists),
+				(true, true) => match l.partial_cmp(&r, &diff_op, false) {
+					Some(Less)    => (Older, Newer),
+					Some(Equal)   => (Same,  Same),
+					Some(Greater) => (Newer, Older),
+					None          => (Error, Error),
+				},
+			}
+		}
+	}
+
+	fn fetch(&self, remote: &Path, local: &Path, options: &CopyOptions)
+		-> Result<CopyMode, StallError>
+	{
+		copy_with_options(remote, local, CopyMethod::Direct, options)
+	}
+
+	fn push(
+		&self,
+		local: &Path,
+		remote: &Path,
+		options: &CopyOptions,
+		durable: bool)
+		-> Result<CopyMode, StallError>
+	{
+		copy_with_options(local, remote, CopyMethod::Atomic { durable }, options)
+	}
+
+	fn missing_dirs(&self, remote: &Path) -> Vec<PathBuf> {
+		missing_dirs(remote)
+	}
+
+	fn create_dir(&self, dir: &Path) -> Result<(), StallError> {
+		create_dir(dir)
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// SshBackend
+////////////////////////////////////////////////////////////////////////////////
+/// The backend for files on other machines, accessed over ssh.
+///
+/// Files are copied with `scp`, which can't clone files or replace them
+/// atomically. Modification times are preserved to the second. Missing remote
+/// directories are not created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SshBackend;
+
+impl SshBackend {
+	/// Parses the [`SshPath`] of the given remote path.
+	///
+	/// [`SshPath`]: struct.SshPath.html
+	fn parse(remote: &Path) -> Result<SshPath, StallError> {
+		SshPath::parse(remote).ok_or_else(|| StallError::InvalidPath {
+			path: remote.to_path_buf(),
+			reason: "invalid ssh path".to_owned(),
+		})
+	}
+}
+
+impl RemoteBackend for SshBackend {
+	fn scheme(&self) -> &'static str {
+		"ssh"
+	}
+
+	fn clean(&self, remote: &Path) -> PathBuf {
+		SshPath::parse(remote)
+			.map_or_else(|| remote.to_path_buf(), |ssh| ssh.to_path_buf())
+	}
+
+	fn modified(&self, remote: &Path) -> Result<Option<SystemTime>, StallError> {
+		Self::parse(remote)?.modified()
+	}
+
+	fn fetch(&self, remote: &Path, local: &Path, options: &CopyOptions)
+		-> Result<CopyMode, StallError>
+	{
+		let ssh = Self::parse(remote)?;
+		ssh.scp([ssh.scp_arg(), scp_local_arg(local)], options.throttle)?;
+		Ok(CopyMode::Standard)
+	}
+
+	fn push(
+		&self,
+		local: &Path,
+		remote: &Path,
+		options: &CopyOptions,
+		_durable: bool)
+		-> Result<CopyMode, StallError>
+	{
+		let ssh = Self::parse(remote)?;
+		ssh.scp([scp_local_arg(local), ssh.scp_arg()], options.throttle)?;
+		Ok(CopyMode::Standard)
+	}
+
+	fn check(&self, remote: &Path) -> Result<(), StallError> {
+		let _ = Self::parse(remote)?;
+		for program in ["ssh", "scp"] {
+			let res = Command::new(program)
+				.stdin(Stdio::null())
+				.stdout(Stdio::null())
+				.stderr(Stdio::null())
+				.status();
+			if matches!(&res, Err(e) if e.kind() == std::io::ErrorKind::NotFound) {
+				return Err(StallError::CommandFailed {
+					message: format!("the {program} command is required to \
+						access {}, but was not found", remote.display()),
+				});
+			}
+		}
+		// The scp options are probed once, before any files are copied.
+		let _ = scp_has_protocol_option();
+		Ok(())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// SshPath
+////////////////////////////////////////////////////////////////////////////////
+/// The path of a file on another machine, accessed over ssh.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SshPath {
+	/// The host name, including any user name.
+	host: String,
+	/// The ssh port, if not the default.
+	port: Option<u16>,
+	/// The path of the file. Relative paths are relative to the user's home
+	/// directory.
+	path: PathBuf,
+}
+
+impl SshPath {
+	/// Parses an `SshPath` from the given path. Returns `None` if the path
+	/// does not refer to another machine.
+	///
+	/// Paths of the form `ssh://[USER@]HOST[:PORT]/PATH` or `[USER@]HOST:PATH`
+	/// are recognized. As with `scp`, a path is only treated as a `HOST:PATH`
+	/// path if no `/` precedes the first `:`. It must also name a valid host,
+	/// and must not be an existing local file, so that local paths containing
+	/// a `:` are not mistaken for it. Single letter hosts are treated as
+	/// windows drive letters. In either form, a path beginning with `~/` is
+	/// relative to the user's home directory.
+	#[must_use]
+	pub fn parse(path: &Path) -> Option<Self> {
+		let text = path.to_str()?;
+		let (host, port, path) = if let Some((scheme, rest)) = split_scheme(text) {
+			if scheme != "ssh" { return None; }
+			let (authority, path) = rest.split_once('/')?;
+			let (host, port) = match authority.rsplit_once(':') {
+				Some((host, port)) => (host, Some(port.parse().ok()?)),
+				None               => (authority, None),
+			};
+			let path = path.strip_prefix('~').map_or_else(
+				|| format!("/{path}"),
+				|relative| relative.trim_start_matches('/').to_owned());
+			(host, port, path)
+		} else {
+			let (host, path) = text.split_once(':')?;
+			if host.len() < 2 || !is_host(host) || Path::new(text).exists() {
+				return None;
+			}
+			let path = path.strip_prefix('~')
+				.map_or(path, |relative| relative.trim_start_matches('/'));
+			(host, None, path.to_owned())
+		};
+		if host.is_empty() || host.starts_with('-') { return None; }
+
+		// The home directory is represented by an empty path.
+		let path = path::clean_lexical(Path::new(&path));
+		Some(Self {
+			host: host.to_owned(),
+			port,
+			path: if path == Path::new(".") { PathBuf::new() } else { path },
+		})
+	}
+
+	/// Returns the host name, including any user name.
+	#[must_use]
+	pub fn host(&self) -> &str {
+		&self.host
+	}
+
+	/// Returns the ssh port, if not the default.
+	#[must_use]
+	pub const fn port(&self) -> Option<u16> {
+		self.port
+	}
+
+	/// Returns the path of the file on the remote machine. Relative paths are
+	/// relative to the user's home directory.
+	#[must_use]
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Returns the `ssh://` form of the path.
+	#[must_use]
+	pub fn to_path_buf(&self) -> PathBuf {
+		PathBuf::from(self.to_string())
+	}
+
+	/// Returns the path text used for the file on the remote machine.
+	fn remote_text(&self) -> String {
+		if self.path.as_os_str().is_empty() {
+			".".to_owned()
+		} else {
+			self.path.to_string_lossy().replace('\\', "/")
+		}
+	}
+
+	/// Returns the modification time of the file, or `None` if the file
+	/// does not exist.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the ssh command can't be run, or if it
+	/// fails, e.g. because the host can't be reached.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn modified(&self) -> Result<Option<SystemTime>, StallError> {
+		let path = shell_quote(&self.remote_text());
+		let script = format!("if [ -e {path} ]; then \
+			stat -c %Y {path} 2>/dev/null || stat -f %m {path}; \
+			else echo absent; fi");
+
+		let output = self.ssh_command()
+			.arg(script)
+			.output()
+			.with_path("failed to run ssh command for", &self.to_path_buf())?;
+		if !output.status.success() {
+			return Err(StallError::CommandFailed {
+				message: format!("failed to read metadata for {}: {}",
+					self,
+					String::from_utf8_lossy(&output.stderr).trim()),
+			});
+		}
+
+		let stdout = String::from_utf8_lossy(&output.stdout);
+		match stdout.trim() {
+			"absent" => Ok(None),
+			secs => secs
+				.parse()
+				.map(|secs| Some(SystemTime::UNIX_EPOCH
+					+ Duration::from_secs(secs)))
+				.map_err(|e| StallError::CommandFailed {
+					message: format!("invalid modification time for {self}: \
+						{secs:?}: {e}"),
+				}),
+		}
+	}
+
+	/// Returns an `ssh` [`Command`] for running a shell command on the host.
+	///
+	/// [`Command`]: https://doc.rust-lang.org/stable/std/process/struct.Command.html
+	fn ssh_command(&self) -> Command {
+		let mut ssh = Command::new("ssh");
+		if let Some(port) = self.port {
+			let _ = ssh.arg("-p").arg(port.to_string());
+		}
+		let _ = ssh.arg("--").arg(&self.host);
+		ssh
+	}
+
+	/// Returns the argument used to refer to the file in an `scp` command. The
+	/// path is quoted, as it is interpreted by the remote shell.
+	fn scp_arg(&self) -> OsString {
+		format!("{}:{}", self.host, shell_quote(&self.remote_text())).into()
+	}
+
+	/// Runs an `scp` command copying between the given source and target
+	/// arguments. Modification times and permissions are preserved, and
+	/// directories are copied recursively. If a [`Throttle`] is given, the copy
+	/// is limited to its rate. The original scp protocol is used, so that
+	/// remote paths are always interpreted by the remote shell. Newer versions
+	/// of `scp` are given `-O` to select it.
+	///
+	/// [`Throttle`]: ../entry/struct.Throttle.html
+	fn scp(&self, args: [OsString; 2], throttle: Option<Throttle>)
+		-> Result<(), StallError>
+	{
+		let mut scp = Command::new("scp");
+		if scp_has_protocol_option() {
+			let _ = scp.arg("-O");
+		}
+		let _ = scp.args(["-p", "-r", "-q"]);
+		if let Some(port) = self.port {
+			let _ = scp.arg("-P").arg(port.to_string());
+		}
+		if let Some(throttle) = throttle {
+			// The scp limit is given in Kbit/s.
+			let limit = (throttle.bytes_per_second() * 8 / 1000).max(1);
+			let _ = scp.arg("-l").arg(limit.to_string());
+		}
+		let [source, target] = args;
+		let status = scp
+			.arg(&source)
+			.arg(&target)
+			.status()
+			.with_path("failed to run scp command for", &self.to_path_buf())?;
+		if status.success() {
+			Ok(())
+		} else {
+			Err(StallError::CommandFailed {
+				message: format!("failed to copy {} to {}: {}",
+					source.to_string_lossy(),
+					target.to_string_lossy(),
+					status),
+			})
+		}
+	}
+}
+
+impl std::fmt::Display for SshPath {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "ssh://{}", self.host)?;
+		if let Some(port) = self.port {
+			write!(f, ":{port}")?;
+		}
+		if self.path.is_absolute() || self.path.has_root() {
+			write!(f, "{}", self.path.to_string_lossy().replace('\\', "/"))
+		} else if self.path.as_os_str().is_empty() {
+			write!(f, "/~")
+		} else {
+			write!(f, "/~/{}", self.path.to_string_lossy().replace('\\', "/"))
+		}
+	}
+}
+
+
+/// Returns `true` if the given text is a host name or IPv4 address,
+/// optionally preceded by a user name and `@`.
+fn is_host(text: &str) -> bool {
+	let host = match text.rsplit_once('@') {
+		Some(("", _))     => return false,
+		Some((_, host))   => host,
+		None              => text,
+	};
+	!host.is_empty()
+		&& !host.starts_with(['-', '.'])
+		&& host.chars()
+			.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.'))
+}
+
+/// Returns `true` if `scp` accepts the `-O` option, which selects the original
+/// scp protocol. Versions of OpenSSH before 9.0 always use the original
+/// protocol, and reject the option. The result is cached after the first call.
+fn scp_has_protocol_option() -> bool {
+	static HAS_OPTION: OnceLock<bool> = OnceLock::new();
+	*HAS_OPTION.get_or_init(|| Command::new("scp")
+		.arg("-O")
+		.stdin(Stdio::null())
+		.output()
+		.is_ok_and(|output| !String::from_utf8_lossy(&output.stderr)
+			.contains("option -- ")))
+}
+
+/// Returns the argument used to refer to a local file in an `scp` command.
+/// Relative paths are prefixed with `./`, so that a `:` in them is not read as
+/// following a host name.
+fn scp_local_arg(local: &Path) -> OsString {
+	if local.is_relative() {
+		Path::new(".").join(local).into()
+	} else {
+		local.into()
+	}
+}
+
+/// Returns the given text quoted for use as a single word in a POSIX shell.
+fn shell_quote(text: &str) -> String {
+	format!("'{}'", text.replace('\'', r"'\''"))
+}
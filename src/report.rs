@@ -0,0 +1,485 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Command output rendering.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::cancel::Cancel;
+use crate::command::CommonOptions;
+use crate::entry::Outcome;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::warning::Warning;
+
+// External library imports.
+use colored::Color;
+use colored::Colorize as _;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The indentation to use for table rows.
+const TABLE_INDENT: &str = "    ";
+
+/// The separator to place between table columns.
+const TABLE_SEPARATOR: &str = " ";
+
+
+////////////////////////////////////////////////////////////////////////////////
+// warn
+////////////////////////////////////////////////////////////////////////////////
+/// Emits a [`Warning`].
+///
+/// Warnings of kinds allowed by the `--allow` option are only traced at the
+/// debug level. Other warnings are returned as errors if the `--error` option
+/// is set, and are otherwise printed.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] containing the warning if it is not allowed and
+/// warnings are promoted to errors.
+///
+/// [`Warning`]: ../warning/enum.Warning.html
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn warn(warning: Warning, common: &CommonOptions)
+	-> Result<(), StallError>
+{
+	if common.allow.contains(&warning.kind()) {
+		event!(Level::DEBUG, "Allowed warning: {}", warning);
+		Ok(())
+	} else if common.promote_warnings_to_errors {
+		Err(StallError::Warning { warning })
+	} else {
+		event!(Level::WARN, "{}", warning);
+		Ok(())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// write_contents
+////////////////////////////////////////////////////////////////////////////////
+/// Writes the contents of the file at the given path into `out`. Directories
+/// are listed instead.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the file can't be read or if an IO error occurs
+/// during writing.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn write_contents(out: &mut dyn Write, path: &Path)
+	-> Result<(), StallError>
+{
+	if path.is_dir() {
+		let mut names = std::fs::read_dir(path)
+			.with_path("failed to read directory", path)?
+			.map(|entry| entry
+				.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+			.collect::<Result<Vec<_>, _>>()
+			.with_path("failed to read directory", path)?;
+		names.sort();
+		for name in names {
+			writeln!(out, "{name}")?;
+		}
+		return Ok(());
+	}
+
+	let contents = std::fs::read(path)
+		.with_path("failed to read file", path)?;
+	out.write_all(&contents)?;
+	if !contents.is_empty() && !contents.ends_with(b"\n") {
+		writeln!(out)?;
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Cell
+////////////////////////////////////////////////////////////////////////////////
+/// A single table cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+	/// The text of the cell.
+	pub text: String,
+	/// The color of the cell text, if color output is enabled.
+	pub color: Option<Color>,
+}
+
+impl Cell {
+	/// Constructs a new uncolored `Cell`.
+	#[must_use]
+	pub fn new<S>(text: S) -> Self
+		where S: Into<String>
+	{
+		Self {
+			text: text.into(),
+			color: None,
+		}
+	}
+
+	/// Returns the `Cell` with the given color.
+	#[must_use]
+	pub const fn with_color(mut self, color: Color) -> Self {
+		self.color = Some(color);
+		self
+	}
+
+	/// Returns the display width of the cell text.
+	fn width(&self) -> usize {
+		self.text.chars().count()
+	}
+
+	/// Writes the cell into `out`, padded to the given width.
+	fn write(
+		&self,
+		out: &mut dyn Write,
+		width: usize,
+		common: &CommonOptions)
+		-> std::io::Result<()>
+	{
+		// Pad before coloring, as escape codes would otherwise count towards
+		// the width.
+		let padded = format!("{:<width$}", self.text);
+		match self.color {
+			Some(color) if common.color.enabled() => {
+				write!(out, "{}", padded.color(color))
+			},
+			_ => write!(out, "{padded}"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Table
+////////////////////////////////////////////////////////////////////////////////
+/// A table of cells with a header row. Column widths are adjusted to fit the
+/// widest cell in each column.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+	/// The column headers.
+	header: Vec<String>,
+	/// The table rows.
+	rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+	/// Constructs a new `Table` with the given column headers.
+	#[must_use]
+	pub fn new<I, S>(header: I) -> Self
+		where
+			I: IntoIterator<Item=S>,
+			S: Into<String>,
+	{
+		Self {
+			header: header.into_iter().map(Into::into).collect(),
+			rows: Vec::new(),
+		}
+	}
+
+	/// Appends a row to the table.
+	pub fn push_row(&mut self, row: Vec<Cell>) {
+		self.rows.push(row);
+	}
+
+	/// Returns `true` if the table has no rows.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.rows.is_empty()
+	}
+
+	/// Returns the width of each column.
+	fn column_widths(&self) -> Vec<usize> {
+		let mut widths: Vec<usize> = self.header
+			.iter()
+			.map(|h| h.chars().count())
+			.collect();
+
+		for row in &self.rows {
+			for (i, cell) in row.iter().enumerate() {
+				if i >= widths.len() { widths.push(0); }
+				widths[i] = widths[i].max(cell.width());
+			}
+		}
+		widths
+	}
+
+	/// Writes the table into `out`.
+	pub fn write(
+		&self,
+		out: &mut dyn Write,
+		common: &CommonOptions)
+		-> std::io::Result<()>
+	{
+		if common.quiet { return Ok(()); }
+
+		let widths = self.column_widths();
+		let last = widths.len().saturating_sub(1);
+
+		// Write header.
+		write!(out, "{TABLE_INDENT}")?;
+		for (i, h) in self.header.iter().enumerate() {
+			if i > 0 { write!(out, "{TABLE_SEPARATOR}")?; }
+			// Don't pad the last column, to avoid trailing whitespace.
+			let width = if i == last { 0 } else { widths[i] };
+			let padded = format!("{h:<width$}");
+			if common.color.enabled() {
+				write!(out, "{}", padded.bright_white().bold())?;
+			} else {
+				write!(out, "{padded}")?;
+			}
+		}
+		writeln!(out)?;
+
+		// Write rows.
+		for row in &self.rows {
+			write!(out, "{TABLE_INDENT}")?;
+			for (i, cell) in row.iter().enumerate() {
+				if i > 0 { write!(out, "{TABLE_SEPARATOR}")?; }
+				let width = if i == last { 0 } else { widths[i] };
+				cell.write(out, width, common)?;
+			}
+			writeln!(out)?;
+		}
+		Ok(())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CopyReport
+////////////////////////////////////////////////////////////////////////////////
+/// The result of a command which copies the files of each entry, such as
+/// `collect` or `distribute`, holding the outcome of each processed entry.
+///
+/// Commands continue processing entries after a failure, so the failures are
+/// recorded in the report rather than returned as errors. Cancelled commands
+/// stop before processing the remaining entries, which are counted in the
+/// report.
+#[derive(Debug, Default)]
+pub struct CopyReport {
+	/// The processed entries, in the order they were processed.
+	entries: Vec<EntryReport>,
+	/// The reason the command was cancelled, if it was.
+	cancelled: Option<Cancel>,
+	/// The number of entries not processed because the command was cancelled.
+	remaining: usize,
+}
+
+impl CopyReport {
+	/// Constructs a new empty `CopyReport`.
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Records the result of processing the entry with the given local path.
+	/// The result holds the [`Outcome`] of copying the entry's file.
+	///
+	/// [`Outcome`]: ../entry/enum.Outcome.html
+	pub fn record(&mut self, local: &Path, result: Result<Outcome, StallError>) {
+		if let Err(error) = &result {
+			event!(Level::ERROR, "{:?}: {}", local, error);
+		}
+		self.entries.push(EntryReport {
+			local: local.to_path_buf(),
+			result,
+		});
+	}
+
+	/// Records that the command was cancelled for the given reason before the
+	/// given number of remaining entries were processed.
+	pub fn set_cancelled(&mut self, reason: Cancel, remaining: usize) {
+		event!(Level::INFO, "Command {}: {} entries not processed",
+			reason, remaining);
+		self.cancelled = Some(reason);
+		self.remaining = remaining;
+	}
+
+	/// Returns the reason the command was cancelled, if it was.
+	#[must_use]
+	pub const fn cancelled(&self) -> Option<Cancel> {
+		self.cancelled
+	}
+
+	/// Returns the processed entries, in the order they were processed.
+	#[must_use]
+	pub fn entries(&self) -> &[EntryReport] {
+		&self.entries
+	}
+
+	/// Returns `true` if no failures were recorded.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.entries.iter().all(|entry| entry.result.is_ok())
+	}
+
+	/// Returns the number of entries processed.
+	#[must_use]
+	pub fn total(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Returns an iterator over the local paths of the entries whose files
+	/// were copied.
+	pub fn copied(&self) -> impl Iterator<Item=&Path> + '_ {
+		self.with_outcome(Outcome::Copied)
+	}
+
+	/// Returns the number of entries whose files were unchanged.
+	#[must_use]
+	pub fn unchanged(&self) -> usize {
+		self.with_outcome(Outcome::Unchanged).count()
+	}
+
+	/// Returns the number of entries whose files were skipped for reasons
+	/// other than being unchanged.
+	#[must_use]
+	pub fn skipped(&self) -> usize {
+		self.with_outcome(Outcome::Skipped).count()
+	}
+
+	/// Returns an iterator over the local paths and errors of the failed
+	/// entries.
+	pub fn failures(&self) -> impl Iterator<Item=(&Path, &StallError)> + '_ {
+		self.entries
+			.iter()
+			.filter_map(|entry| entry.result
+				.as_ref()
+				.err()
+				.map(|error| (entry.local.as_path(), error)))
+	}
+
+	/// Returns the process exit code for the report: 0 if no failures were
+	/// recorded, 1 if any were, or the [`Cancel::exit_code`] if the command
+	/// was cancelled.
+	///
+	/// [`Cancel::exit_code`]: ../cancel/enum.Cancel.html#method.exit_code
+	#[must_use]
+	pub fn exit_code(&self) -> i32 {
+		self.cancelled.map_or_else(
+			|| i32::from(!self.is_empty()),
+			Cancel::exit_code)
+	}
+
+	/// Returns an iterator over the local paths of the entries with the given
+	/// outcome.
+	fn with_outcome(&self, outcome: Outcome)
+		-> impl Iterator<Item=&Path> + '_
+	{
+		self.entries
+			.iter()
+			.filter(move |entry| entry.result.as_ref().ok() == Some(&outcome))
+			.map(|entry| entry.local.as_path())
+	}
+
+	/// Writes the totals and failure summary of the report, as printed after
+	/// the status of each entry. The totals are written into `out`, and the
+	/// failures are written into `err`.
+	pub fn write(
+		&self,
+		out: &mut dyn Write,
+		err: &mut dyn Write,
+		common: &CommonOptions)
+		-> std::io::Result<()>
+	{
+		self.write_totals(out, common)?;
+		self.write_summary(err, common)
+	}
+
+	/// Writes a line counting the copied, unchanged, skipped, and failed
+	/// entries into `out`. Nothing is written if `common.quiet` is set.
+	pub fn write_totals(
+		&self,
+		out: &mut dyn Write,
+		common: &CommonOptions)
+		-> std::io::Result<()>
+	{
+		if common.quiet { return Ok(()); }
+
+		let failures = self.failures().count();
+		let not_processed = match self.cancelled {
+			Some(_) => format!(", {} not processed", self.remaining),
+			None    => String::new(),
+		};
+		let totals = format!("{} copied, {} unchanged, {} skipped, {} {}{}",
+			self.copied().count(),
+			self.unchanged(),
+			self.skipped(),
+			failures,
+			if failures == 1 { "error" } else { "errors" },
+			not_processed);
+		if common.color.enabled() {
+			writeln!(out, "{}", totals.bright_white())
+		} else {
+			writeln!(out, "{totals}")
+		}
+	}
+
+	/// Writes a summary of the failures into `out`, followed by the number of
+	/// entries not processed if the command was cancelled. Nothing is written
+	/// if no failures were recorded and the command was not cancelled.
+	pub fn write_summary(
+		&self,
+		out: &mut dyn Write,
+		common: &CommonOptions)
+		-> std::io::Result<()>
+	{
+		if !self.is_empty() {
+			let summary = format!("{} of {} entries failed:",
+				self.failures().count(),
+				self.total());
+			if common.color.enabled() {
+				writeln!(out, "{}", summary.bright_red())?;
+			} else {
+				writeln!(out, "{summary}")?;
+			}
+			for (local, error) in self.failures() {
+				writeln!(out, "{TABLE_INDENT}{}: {}", local.display(), error)?;
+			}
+		}
+
+		if let Some(reason) = self.cancelled {
+			let summary = format!("Stopped early ({reason}): {} of {} entries \
+				not processed.",
+				self.remaining,
+				self.total() + self.remaining);
+			if common.color.enabled() {
+				writeln!(out, "{}", summary.bright_red())?;
+			} else {
+				writeln!(out, "{summary}")?;
+			}
+		}
+		Ok(())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// EntryReport
+////////////////////////////////////////////////////////////////////////////////
+/// The result of processing a single entry.
+#[derive(Debug)]
+pub struct EntryReport {
+	/// The local path of the entry.
+	pub local: PathBuf,
+	/// The [`Outcome`] of copying the entry's files, or the error which
+	/// occurred.
+	///
+	/// [`Outcome`]: ../entry/enum.Outcome.html
+	pub result: Result<Outcome, StallError>,
+}
@@ -0,0 +1,109 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Safety checks for the remote paths of stall entries.
+//!
+//! A remote path which resolves to the entry's stalled file, through a
+//! symbolic link or a hard link, would make a copy truncate the file it is
+//! reading, and a remote path inside the stall directory would let a copy
+//! overwrite other stalled files. Such remote paths are refused by `add`,
+//! `mv`, `collect`, and `distribute`.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+use crate::path;
+use crate::platform;
+use crate::remote;
+
+// Standard library imports.
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// check_remote
+////////////////////////////////////////////////////////////////////////////////
+/// Checks that the remote file of an entry is safe to copy to and from.
+///
+/// ### Parameters
+///
+/// + `stall_dir`: The stall directory containing the entry's stalled file.
+/// + `local`: The local path of the entry, relative to `stall_dir`.
+/// + `remote`: The remote path of the entry. Paths with a URL scheme are not
+///   checked.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the remote path resolves to a path inside the
+/// stall directory, or if it is the same file as the stalled file.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn check_remote(stall_dir: &Path, local: &Path, remote: &Path)
+	-> Result<(), StallError>
+{
+	if remote::scheme(remote).is_some() { return Ok(()); }
+
+	let stall_dir = path::canonicalize(stall_dir);
+	let resolved = resolve(remote);
+	if is_within(&resolved, &stall_dir) {
+		return Err(StallError::InvalidPath {
+			path: remote.to_path_buf(),
+			reason: format!("remote file is inside the stall directory {}",
+				stall_dir.display()),
+		});
+	}
+
+	// Symbolic links are copied as links, so a stalled link may resolve to
+	// the same file as its remote link.
+	let full_local = stall_dir.join(local);
+	let is_link = full_local
+		.symlink_metadata()
+		.is_ok_and(|metadata| metadata.file_type().is_symlink());
+	let same_id = platform::file_id(&full_local)
+		.is_some_and(|id| platform::file_id(remote) == Some(id));
+	if same_id && !is_link {
+		return Err(StallError::InvalidPath {
+			path: remote.to_path_buf(),
+			reason: format!("remote file is the same file as stalled file {}",
+				local.display()),
+		});
+	}
+	Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helper functions
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns the given path with the symbolic links of its longest existing
+/// ancestor resolved, so that files which don't exist yet are resolved
+/// through the directories they would be created in.
+fn resolve(path: &Path) -> PathBuf {
+	for ancestor in path.ancestors() {
+		if let Ok(canonical) = std::fs::canonicalize(ancestor) {
+			let rest = path
+				.strip_prefix(ancestor)
+				.unwrap_or_else(|_| Path::new(""));
+			return path::clean(&canonical.join(rest));
+		}
+	}
+	path::clean(path)
+}
+
+/// Returns `true` if `path` is `dir` or lies inside it, ignoring case on
+/// windows. Both paths should be [`clean`]ed first.
+///
+/// [`clean`]: ../path/fn.clean.html
+fn is_within(path: &Path, dir: &Path) -> bool {
+	if cfg!(windows) {
+		let lower = |p: &Path| PathBuf::from(p.to_string_lossy().to_lowercase());
+		lower(path).starts_with(lower(dir))
+	} else {
+		path.starts_with(dir)
+	}
+}
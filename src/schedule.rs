@@ -0,0 +1,613 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Scheduled stall commands.
+//!
+//! A [`Schedule`] runs `stall collect` or `stall distribute` for a stall at a
+//! fixed [`Interval`], using a systemd user timer, a launchd agent, or a cron
+//! job. Each installed schedule is marked with a `stall-schedule:` comment,
+//! which records the schedule so that it can be listed and removed.
+//!
+//! [`Schedule`]: struct.Schedule.html
+//! [`Interval`]: struct.Interval.html
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::application::config_dir;
+use crate::cancel::Timeout;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::platform::home_dir;
+
+// External library imports.
+use sha2::Digest as _;
+use sha2::Sha256;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The comment prefix marking an installed schedule.
+const MARKER: &str = "stall-schedule:";
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ScheduleBackend
+////////////////////////////////////////////////////////////////////////////////
+/// The service used to run scheduled commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(clap::ArgEnum)]
+pub enum ScheduleBackend {
+	/// A systemd user service and timer.
+	Systemd,
+	/// A launchd user agent.
+	Launchd,
+	/// A line in the user's crontab.
+	Cron,
+}
+
+impl ScheduleBackend {
+	/// Returns the default backend for the platform: launchd on macOS, systemd
+	/// if `systemctl` is available, and cron otherwise.
+	#[must_use]
+	pub fn detect() -> Self {
+		if cfg!(target_os = "macos") {
+			return Self::Launchd;
+		}
+		let systemd = Command::new("systemctl")
+			.arg("--version")
+			.stdout(Stdio::null())
+			.stderr(Stdio::null())
+			.status()
+			.is_ok_and(|status| status.success());
+		if systemd { Self::Systemd } else { Self::Cron }
+	}
+
+	/// Returns the directory the backend's unit files are written to, if it
+	/// uses them and the directory can be determined.
+	fn unit_dir(self) -> Option<PathBuf> {
+		match self {
+			Self::Systemd => config_dir()
+				.and_then(|dir| dir.parent().map(Path::to_path_buf))
+				.map(|dir| dir.join("systemd/user")),
+			Self::Launchd => home_dir()
+				.map(|home| home.join("Library/LaunchAgents")),
+			Self::Cron    => None,
+		}
+	}
+}
+
+impl std::fmt::Display for ScheduleBackend {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Systemd => write!(f, "systemd"),
+			Self::Launchd => write!(f, "launchd"),
+			Self::Cron    => write!(f, "cron"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ScheduledCommand
+////////////////////////////////////////////////////////////////////////////////
+/// The stall command run by a schedule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(clap::ArgEnum)]
+pub enum ScheduledCommand {
+	/// Run `stall collect`.
+	#[default]
+	Collect,
+	/// Run `stall distribute`.
+	Distribute,
+}
+
+impl std::str::FromStr for ScheduledCommand {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		<Self as clap::ArgEnum>::from_str(text, false)
+	}
+}
+
+impl std::fmt::Display for ScheduledCommand {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Collect    => write!(f, "collect"),
+			Self::Distribute => write!(f, "distribute"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Interval
+////////////////////////////////////////////////////////////////////////////////
+/// The time between runs of a scheduled command, in whole minutes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Interval {
+	/// The number of minutes between runs.
+	minutes: u64,
+}
+
+impl Interval {
+	/// Returns the number of seconds between runs.
+	#[must_use]
+	pub const fn seconds(&self) -> u64 {
+		self.minutes * 60
+	}
+
+	/// Returns the cron schedule expression for the interval.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the interval doesn't evenly divide an hour
+	/// or a day, and is not a day or a week.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn cron_expression(&self) -> Result<String, StallError> {
+		let (minutes, hours) = (self.minutes, self.minutes / 60);
+		match minutes {
+			1 => Ok("* * * * *".to_owned()),
+			m if m < 60 && 60 % m == 0 => Ok(format!("*/{m} * * * *")),
+			m if m % 60 == 0 && hours < 24 && 24 % hours == 0
+				=> Ok(format!("0 */{hours} * * *")),
+			1440 => Ok("0 0 * * *".to_owned()),
+			10080 => Ok("0 0 * * 0".to_owned()),
+			_ => Err(StallError::InvalidInput {
+				message: format!("interval {self} can't be written as a cron \
+					schedule: use an interval which evenly divides an hour or \
+					a day"),
+			}),
+		}
+	}
+}
+
+impl std::str::FromStr for Interval {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let text = text.trim();
+		let invalid = || format!("invalid interval {text:?}: expected a \
+			duration of at least one minute, such as 30m, 1h, or 1d");
+
+		let unit = text.chars().last().ok_or_else(invalid)?;
+		let count: u64 = text[..text.len() - unit.len_utf8()]
+			.parse()
+			.ok()
+			.ok_or_else(invalid)?;
+		let minutes = match unit {
+			'm' => Some(count),
+			'h' => count.checked_mul(60),
+			'd' => count.checked_mul(60 * 24),
+			'w' => count.checked_mul(60 * 24 * 7),
+			_   => None,
+		};
+		minutes
+			.filter(|&minutes| minutes > 0)
+			.map(|minutes| Self { minutes })
+			.ok_or_else(invalid)
+	}
+}
+
+impl std::fmt::Display for Interval {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		const UNITS: [(u64, char); 3] = [(10080, 'w'), (1440, 'd'), (60, 'h')];
+		for (size, unit) in UNITS {
+			if self.minutes.is_multiple_of(size) {
+				return write!(f, "{}{}", self.minutes / size, unit);
+			}
+		}
+		write!(f, "{}m", self.minutes)
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Schedule
+////////////////////////////////////////////////////////////////////////////////
+/// A stall command run periodically for a stall.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+	/// The command to run.
+	pub command: ScheduledCommand,
+	/// The time between runs.
+	pub interval: Interval,
+	/// The time each run may take before it is cancelled, if any.
+	pub timeout: Option<Timeout>,
+	/// The stall file to run the command for.
+	pub stall: PathBuf,
+}
+
+impl Schedule {
+	/// Returns the name of the schedule's unit, agent, or job, which is
+	/// unique for each stall and command.
+	#[must_use]
+	pub fn name(&self) -> String {
+		schedule_name(&self.stall, self.command)
+	}
+
+	/// Returns the files to install for the schedule with the given backend,
+	/// paired with their contents. For cron, the path is empty and the
+	/// contents are the lines to add to the crontab.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the backend's unit directory can't be
+	/// determined, or if the interval can't be used with the backend.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn render(&self, backend: ScheduleBackend, exe: &Path)
+		-> Result<Vec<(PathBuf, String)>, StallError>
+	{
+		let name = self.name();
+		let marker = format!("{MARKER} name={name} command={} interval={}{} \
+			stall={}",
+			self.command,
+			self.interval,
+			self.timeout
+				.map(|timeout| format!(" timeout={timeout}"))
+				.unwrap_or_default(),
+			self.stall.display());
+		let mut args = vec![
+			exe.to_string_lossy().into_owned(),
+			self.command.to_string(),
+			"--stall".to_owned(),
+			self.stall.to_string_lossy().into_owned(),
+			"--quiet".to_owned(),
+			"--notify".to_owned(),
+		];
+		if let Some(timeout) = self.timeout {
+			args.extend(["--timeout".to_owned(), timeout.to_string()]);
+		}
+
+		if backend == ScheduleBackend::Cron {
+			let command = args
+				.iter()
+				.map(|arg| format!("'{}'", arg.replace('\'', r"'\''")))
+				.collect::<Vec<_>>()
+				.join(" ");
+			return Ok(vec![(PathBuf::new(), format!("# {marker}\n{} {}\n",
+				self.interval.cron_expression()?,
+				command.replace('%', r"\%")))]);
+		}
+
+		let unit_dir = unit_dir(backend)?;
+		if backend == ScheduleBackend::Launchd {
+			let args: String = args
+				.iter()
+				.map(|arg| ["\t\t<string>", &xml_escape(arg), "</string>\n"]
+					.concat())
+				.collect();
+			return Ok(vec![(unit_dir.join(format!("{name}.plist")), format!(
+				"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+				<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+				\"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+				<!-- {} -->\n\
+				<plist version=\"1.0\">\n\
+				<dict>\n\
+				\t<key>Label</key>\n\
+				\t<string>{name}</string>\n\
+				\t<key>ProgramArguments</key>\n\
+				\t<array>\n\
+				{args}\
+				\t</array>\n\
+				\t<key>StartInterval</key>\n\
+				\t<integer>{}</integer>\n\
+				</dict>\n\
+				</plist>\n",
+				xml_escape(&marker),
+				self.interval.seconds()))]);
+		}
+
+		let exec = args
+			.iter()
+			.map(|arg| format!("\"{}\"", arg
+				.replace('\\', r"\\")
+				.replace('"', "\\\"")
+				.replace('%', "%%")))
+			.collect::<Vec<_>>()
+			.join(" ");
+		Ok(vec![
+			(unit_dir.join(format!("{name}.service")), format!(
+				"# {marker}\n\
+				[Unit]\n\
+				Description=stall {} for {}\n\
+				\n\
+				[Service]\n\
+				Type=oneshot\n\
+				ExecStart={exec}\n",
+				self.command,
+				self.stall.display().to_string().replace('%', "%%"))),
+			(unit_dir.join(format!("{name}.timer")), format!(
+				"# {marker}\n\
+				[Unit]\n\
+				Description=Run stall {} every {}\n\
+				\n\
+				[Timer]\n\
+				OnBootSec=5m\n\
+				OnUnitActiveSec={}\n\
+				\n\
+				[Install]\n\
+				WantedBy=timers.target\n",
+				self.command,
+				self.interval,
+				self.interval)),
+		])
+	}
+
+	/// Installs and enables the schedule with the given backend, replacing
+	/// any existing schedule for the same stall and command. Returns the
+	/// installed files.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the files can't be written, or if the
+	/// backend's command fails.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn install(&self, backend: ScheduleBackend, exe: &Path)
+		-> Result<Vec<PathBuf>, StallError>
+	{
+		let files = self.render(backend, exe)?;
+		let name = self.name();
+
+		if backend == ScheduleBackend::Cron {
+			let mut crontab = without_schedule(&read_crontab()?, &name);
+			for (_, lines) in &files { crontab.push_str(lines); }
+			write_crontab(&crontab)?;
+			return Ok(Vec::new());
+		}
+
+		for (path, contents) in &files {
+			if let Some(parent) = path.parent() {
+				std::fs::create_dir_all(parent)
+					.with_path("failed to create directory", parent)?;
+			}
+			event!(Level::INFO, "Writing schedule file {:?}", path);
+			std::fs::write(path, contents)
+				.with_path("failed to write schedule file", path)?;
+		}
+
+		if backend == ScheduleBackend::Launchd {
+			let plist = &files[0].0;
+			// Unloading fails if the agent was not loaded before.
+			let _ = run(Command::new("launchctl").arg("unload").arg(plist));
+			run(Command::new("launchctl").args(["load", "-w"]).arg(plist))?;
+		} else {
+			run(Command::new("systemctl").args(["--user", "daemon-reload"]))?;
+			run(Command::new("systemctl")
+				.args(["--user", "enable", "--now"])
+				.arg(format!("{name}.timer")))?;
+		}
+		Ok(files.into_iter().map(|(path, _)| path).collect())
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Installed schedules
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the schedules installed with the given backend, for any stall.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the crontab or unit directory can't be read.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn installed(backend: ScheduleBackend)
+	-> Result<Vec<Schedule>, StallError>
+{
+	let mut schedules = Vec::new();
+	if backend == ScheduleBackend::Cron {
+		schedules.extend(read_crontab()?
+			.lines()
+			.filter_map(parse_marker));
+		return Ok(schedules);
+	}
+
+	let unit_dir = unit_dir(backend)?;
+	if !unit_dir.exists() { return Ok(schedules); }
+	let extension = if backend == ScheduleBackend::Launchd {
+		"plist"
+	} else {
+		"timer"
+	};
+	let read_dir = std::fs::read_dir(&unit_dir)
+		.with_path("failed to read directory", &unit_dir)?;
+	for dir_entry in read_dir {
+		let path = dir_entry
+			.with_path("failed to read directory", &unit_dir)?
+			.path();
+		if path.extension().and_then(|ext| ext.to_str()) != Some(extension) {
+			continue;
+		}
+		// Unreadable files are not schedules written by stall.
+		let Ok(contents) = std::fs::read_to_string(&path) else { continue };
+		schedules.extend(contents.lines().find_map(parse_marker));
+	}
+	schedules.sort_by(|a, b| (&a.stall, a.command)
+		.cmp(&(&b.stall, b.command)));
+	Ok(schedules)
+}
+
+/// Removes and disables the schedule for the given stall and command, if it
+/// is installed with the given backend. Returns whether it was installed.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the files can't be removed, or if the
+/// backend's command fails.
+///
+/// [`StallError`]: ../error/enum.StallError.html
+pub fn uninstall(
+	backend: ScheduleBackend,
+	stall: &Path,
+	command: ScheduledCommand)
+	-> Result<bool, StallError>
+{
+	let name = schedule_name(stall, command);
+	if backend == ScheduleBackend::Cron {
+		let crontab = read_crontab()?;
+		let updated = without_schedule(&crontab, &name);
+		if updated == crontab { return Ok(false); }
+		write_crontab(&updated)?;
+		return Ok(true);
+	}
+
+	let unit_dir = unit_dir(backend)?;
+	if backend == ScheduleBackend::Launchd {
+		let plist = unit_dir.join(format!("{name}.plist"));
+		if !plist.exists() { return Ok(false); }
+		run(Command::new("launchctl").args(["unload", "-w"]).arg(&plist))?;
+		std::fs::remove_file(&plist)
+			.with_path("failed to remove schedule file", &plist)?;
+		return Ok(true);
+	}
+
+	let timer = unit_dir.join(format!("{name}.timer"));
+	let service = unit_dir.join(format!("{name}.service"));
+	if !timer.exists() && !service.exists() { return Ok(false); }
+	run(Command::new("systemctl")
+		.args(["--user", "disable", "--now"])
+		.arg(format!("{name}.timer")))?;
+	for path in [timer, service] {
+		if path.exists() {
+			std::fs::remove_file(&path)
+				.with_path("failed to remove schedule file", &path)?;
+		}
+	}
+	run(Command::new("systemctl").args(["--user", "daemon-reload"]))?;
+	Ok(true)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Helper functions.
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the schedule name for the given stall file and command.
+fn schedule_name(stall: &Path, command: ScheduledCommand) -> String {
+	let digest = Sha256::digest(stall.to_string_lossy().as_bytes());
+	let id = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]);
+	format!("stall-{command}-{id:08x}")
+}
+
+/// Returns the unit directory of the given backend.
+fn unit_dir(backend: ScheduleBackend) -> Result<PathBuf, StallError> {
+	backend.unit_dir().ok_or_else(|| StallError::InvalidInput {
+		message: format!("unable to locate the {backend} unit directory"),
+	})
+}
+
+/// Parses the schedule recorded in a `stall-schedule:` marker comment.
+fn parse_marker(line: &str) -> Option<Schedule> {
+	let (_, fields) = line.split_once(MARKER)?;
+	// The stall path is last, as it may contain spaces.
+	let (fields, stall) = fields.split_once(" stall=")?;
+	let stall = stall.trim_end().trim_end_matches("-->").trim_end();
+	let mut command = None;
+	let mut interval = None;
+	let mut timeout = None;
+	for field in fields.split_whitespace() {
+		match field.split_once('=') {
+			Some(("command", value))  => command = value.parse().ok(),
+			Some(("interval", value)) => interval = value.parse().ok(),
+			Some(("timeout", value))  => timeout = value.parse().ok(),
+			_ => (),
+		}
+	}
+	Some(Schedule {
+		command: command?,
+		interval: interval?,
+		timeout,
+		stall: PathBuf::from(stall),
+	})
+}
+
+/// Returns the crontab with the named schedule's lines removed.
+fn without_schedule(crontab: &str, name: &str) -> String {
+	let marker = format!("{MARKER} name={name} ");
+	let mut result = String::with_capacity(crontab.len());
+	let mut lines = crontab.lines();
+	while let Some(line) = lines.next() {
+		if line.contains(&marker) {
+			// Skip the job line following the marker.
+			let _ = lines.next();
+			continue;
+		}
+		result.push_str(line);
+		result.push('\n');
+	}
+	result
+}
+
+/// Returns the contents of the user's crontab, which is empty if the user
+/// has no crontab.
+fn read_crontab() -> Result<String, StallError> {
+	let output = Command::new("crontab")
+		.arg("-l")
+		.output()
+		.with_path("failed to run", Path::new("crontab"))?;
+	if output.status.success() {
+		Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+	} else {
+		Ok(String::new())
+	}
+}
+
+/// Replaces the user's crontab.
+fn write_crontab(crontab: &str) -> Result<(), StallError> {
+	let mut child = Command::new("crontab")
+		.arg("-")
+		.stdin(Stdio::piped())
+		.spawn()
+		.with_path("failed to run", Path::new("crontab"))?;
+	if let Some(mut stdin) = child.stdin.take() {
+		stdin.write_all(crontab.as_bytes())
+			.with_path("failed to write", Path::new("crontab"))?;
+	}
+	let status = child.wait()
+		.with_path("failed to run", Path::new("crontab"))?;
+	if status.success() {
+		Ok(())
+	} else {
+		Err(StallError::CommandFailed {
+			message: format!("crontab failed: {status}"),
+		})
+	}
+}
+
+/// Runs the given command, returning an error if it fails.
+fn run(command: &mut Command) -> Result<(), StallError> {
+	event!(Level::INFO, "Running {:?}", command);
+	let program = PathBuf::from(command.get_program());
+	let status = command
+		.status()
+		.with_path("failed to run", &program)?;
+	if status.success() {
+		Ok(())
+	} else {
+		Err(StallError::CommandFailed {
+			message: format!("{command:?} failed: {status}"),
+		})
+	}
+}
+
+/// Escapes the XML special characters in the given text.
+fn xml_escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+}
@@ -0,0 +1,273 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Standalone restore scripts.
+//!
+//! A restore script copies the stalled files of each entry to their remote
+//! locations, like `stall distribute --force`, so that a stall can be restored
+//! on a machine where stall is not installed. The stall directory is given as
+//! the script's first argument, and defaults to the directory containing the
+//! script.
+//!
+//! Remote paths within the home directory are written relative to `$HOME`, so
+//! the script can restore the files of a different user. Entries with remote
+//! URLs are skipped, and transforms are not applied.
+//!
+//! Plan scripts make the copies recorded in a [`Plan`] in the same way.
+//!
+//! [`Plan`]: ../plan/struct.Plan.html
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::entry::Entry;
+use crate::platform::home_dir;
+use crate::plan::Plan;
+use crate::plan::PlanCommand;
+use crate::remote;
+use crate::transform::Direction;
+
+// Standard library imports.
+use std::io::Write;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ScriptShell
+////////////////////////////////////////////////////////////////////////////////
+/// The shell a restore script is written for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(clap::ArgEnum)]
+pub enum ScriptShell {
+	/// A POSIX shell script.
+	#[default]
+	Sh,
+	/// A PowerShell script.
+	#[clap(name = "powershell")]
+	PowerShell,
+}
+
+impl ScriptShell {
+	/// Returns the shell indicated by the given script file's extension.
+	#[must_use]
+	pub fn from_path(path: &Path) -> Option<Self> {
+		let ext = path.extension()?.to_str()?.to_lowercase();
+		match ext.as_str() {
+			"sh"  => Some(Self::Sh),
+			"ps1" => Some(Self::PowerShell),
+			_     => None,
+		}
+	}
+
+	/// Returns the given text as a single quoted string literal.
+	fn quote(self, text: &str) -> String {
+		match self {
+			Self::Sh         => format!("'{}'", text.replace('\'', r"'\''")),
+			Self::PowerShell => format!("'{}'", text.replace('\'', "''")),
+		}
+	}
+
+	/// Returns an expression for the given remote path, relative to the home
+	/// directory if it is within `home`.
+	fn remote_expr(self, remote: &Path, home: Option<&Path>) -> String {
+		let relative = home
+			.and_then(|home| remote.strip_prefix(home).ok())
+			.filter(|relative| !relative.as_os_str().is_empty());
+		let Some(relative) = relative else {
+			return self.quote(&remote.to_string_lossy());
+		};
+		let relative = self.quote(&relative.to_string_lossy());
+		match self {
+			Self::Sh         => format!("\"$HOME\"/{relative}"),
+			Self::PowerShell => format!("(Join-Path $HOME {relative})"),
+		}
+	}
+
+	/// Returns the script's header, including the restore function.
+	const fn header(self) -> &'static str {
+		match self {
+			Self::Sh         => SH_HEADER,
+			Self::PowerShell => POWERSHELL_HEADER,
+		}
+	}
+
+	/// Returns the script line which restores the given local path to the
+	/// remote path expression.
+	fn restore_line(self, local: &Path, remote: &str) -> String {
+		let local = self.quote(&local.to_string_lossy());
+		match self {
+			Self::Sh         => format!("restore {local} {remote}"),
+			Self::PowerShell => format!("Restore-StallFile {local} {remote}"),
+		}
+	}
+}
+
+impl std::fmt::Display for ScriptShell {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Sh         => write!(f, "sh"),
+			Self::PowerShell => write!(f, "powershell"),
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Script headers
+////////////////////////////////////////////////////////////////////////////////
+/// The header of a POSIX shell restore script.
+const SH_HEADER: &str = r#"#!/bin/sh
+# Restores the files of a stall to their remote locations.
+# Generated by `stall export-script`.
+#
+# Usage: sh restore.sh [STALL_DIR]
+#
+# STALL_DIR defaults to the directory containing this script.
+set -e
+STALL_DIR="${1:-$(cd "$(dirname "$0")" && pwd)}"
+
+restore() {
+    if [ ! -e "$STALL_DIR/$1" ]; then
+        echo "missing stalled file: $1" >&2
+        return 0
+    fi
+    mkdir -p "$(dirname "$2")"
+    if [ -d "$STALL_DIR/$1" ]; then
+        mkdir -p "$2"
+        cp -R "$STALL_DIR/$1/." "$2"
+    else
+        cp "$STALL_DIR/$1" "$2"
+    fi
+    echo "restored $2"
+}
+"#;
+
+/// The header of a PowerShell restore script.
+const POWERSHELL_HEADER: &str = r#"# Restores the files of a stall to their remote locations.
+# Generated by `stall export-script`.
+#
+# Usage: .\restore.ps1 [STALL_DIR]
+#
+# STALL_DIR defaults to the directory containing this script.
+param([string]$StallDir = $PSScriptRoot)
+$ErrorActionPreference = 'Stop'
+
+function Restore-StallFile([string]$Local, [string]$Remote) {
+    $source = Join-Path $StallDir $Local
+    if (-not (Test-Path -LiteralPath $source)) {
+        Write-Warning "missing stalled file: $Local"
+        return
+    }
+    $parent = Split-Path -Parent $Remote
+    if ($parent) {
+        New-Item -ItemType Directory -Force -Path $parent | Out-Null
+    }
+    if (Test-Path -LiteralPath $source -PathType Container) {
+        New-Item -ItemType Directory -Force -Path $Remote | Out-Null
+        Copy-Item -Recurse -Force -Path (Join-Path $source '*') `
+            -Destination $Remote
+    } else {
+        Copy-Item -Force -LiteralPath $source -Destination $Remote
+    }
+    Write-Output "restored $Remote"
+}
+"#;
+
+/// The copy function of a POSIX shell plan script.
+const SH_PLAN_COPY: &str = r#"copy() {
+    mkdir -p "$(dirname "$2")"
+    if [ -d "$1" ]; then
+        mkdir -p "$2"
+        cp -R "$1/." "$2"
+    else
+        cp "$1" "$2"
+    fi
+    echo "copied $1 to $2"
+}
+"#;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// write_restore_script
+////////////////////////////////////////////////////////////////////////////////
+/// Writes a restore script for the given entries into `out`, returning the
+/// number of entries it restores.
+///
+/// ### Errors
+///
+/// Returns an error if an IO error occurs during writing.
+pub fn write_restore_script<'a, I>(
+	out: &mut dyn Write,
+	entries: I,
+	shell: ScriptShell)
+	-> std::io::Result<usize>
+	where I: IntoIterator<Item=Entry<'a>>
+{
+	let home = home_dir();
+	write!(out, "{}", shell.header())?;
+	writeln!(out)?;
+
+	let mut count = 0;
+	for entry in entries {
+		if let Some(scheme) = remote::scheme(entry.remote) {
+			writeln!(out, "# Skipped {}: {scheme} remotes are not supported.",
+				entry.remote.display())?;
+			continue;
+		}
+		if !entry.pipeline().is_empty(Direction::Distribute) {
+			writeln!(out, "# The transforms of {} are not applied.",
+				entry.local.display())?;
+		}
+		let remote = shell.remote_expr(entry.remote, home.as_deref());
+		writeln!(out, "{}", shell.restore_line(entry.local, &remote))?;
+		count += 1;
+	}
+	Ok(count)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// write_plan_script
+////////////////////////////////////////////////////////////////////////////////
+/// Writes a POSIX shell script which makes the copies of the given [`Plan`]
+/// into `out`. Steps with remote URLs are skipped, and transforms are not
+/// applied.
+///
+/// ### Errors
+///
+/// Returns an error if an IO error occurs during writing.
+///
+/// [`Plan`]: ../plan/struct.Plan.html
+pub fn write_plan_script(out: &mut dyn Write, plan: &Plan)
+	-> std::io::Result<()>
+{
+	let shell = ScriptShell::Sh;
+	writeln!(out, "#!/bin/sh")?;
+	writeln!(out, "# Makes the copies planned by `stall {} --dry-run`.",
+		plan.command())?;
+	writeln!(out, "# Generated by `stall {} --plan-out`.", plan.command())?;
+	writeln!(out, "set -e")?;
+	writeln!(out, "STALL_DIR={}",
+		shell.quote(&plan.stall_dir().to_string_lossy()))?;
+	writeln!(out)?;
+	write!(out, "{SH_PLAN_COPY}")?;
+	writeln!(out)?;
+
+	for step in plan.steps() {
+		if let Some(scheme) = remote::scheme(&step.remote) {
+			writeln!(out, "# Skipped {}: {scheme} remotes are not supported.",
+				step.remote.display())?;
+			continue;
+		}
+		let local = format!("\"$STALL_DIR\"/{}",
+			shell.quote(&step.local.to_string_lossy()));
+		let remote = shell.quote(&step.remote.to_string_lossy());
+		match plan.command() {
+			PlanCommand::Collect    => writeln!(out, "copy {remote} {local}")?,
+			PlanCommand::Distribute => writeln!(out, "copy {local} {remote}")?,
+		}
+	}
+	Ok(())
+}
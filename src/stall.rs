@@ -8,31 +8,49 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::application::Fingerprint;
 use crate::application::LoadStatus;
+use crate::atomic::replace_with;
+use crate::entry::ConflictPolicy;
 use crate::entry::Entry;
+use crate::entry::EntryFlags;
+use crate::path;
+use crate::suggest;
+use crate::template::RenamePolicy;
+use crate::transform::LineEnding;
+use crate::transform::Transform;
+use crate::warning::Overlap;
+use crate::warning::Warning;
 
 // External library imports.
-use anyhow::Context as _;
-use anyhow::Error;
-use bimap::BiBTreeMap;
+use glob::Pattern;
 use serde::Deserialize;
+use serde::Deserializer;
+use serde::de::MapAccess;
+use serde::de::Visitor;
 use serde::Serialize;
+use serde::Serializer;
 use tracing::event;
 use tracing::Level;
 
 // Standard library imports.
-use std::convert::TryInto as _;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::BufWriter;
-use std::io::SeekFrom;
-use std::io::BufRead as _;
-use std::io::Seek as _;
+use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Read as _;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 
 
@@ -45,24 +63,89 @@ use std::path::PathBuf;
 #[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Stall {
+    /// The schema version of the stall file. Files written before schema
+    /// versions were introduced have no version, and are version 0.
+    #[serde(default)]
+    version: u32,
+
+    /// The schema version the stall file was loaded with, if it has been
+    /// migrated from an older version.
+    #[serde(skip)]
+    migrated_from: Option<u32>,
+
     /// The stall file's load status.
     #[serde(skip)]
     load_status: LoadStatus,
 
-    /// The stall file entries. (Left = Local, Right = Remote)
-    entries: BiBTreeMap<PathBuf, PathBuf>,
+    /// Whether to sync the stall file to disk when it is written.
+    #[serde(skip)]
+    durable: bool,
+
+    /// The format the stall file was read in.
+    #[serde(skip)]
+    format: StallFormat,
+
+    /// The paths of the stall files whose entries are included in this
+    /// stall, relative to the stall file's directory. Later includes take
+    /// precedence over earlier ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include: Vec<PathBuf>,
+
+    /// The loaded included stalls, in the order of `include`.
+    #[serde(skip)]
+    included: Vec<Self>,
+
+    /// The index of the included stall each included entry was loaded from,
+    /// by local path.
+    #[serde(skip)]
+    origins: BTreeMap<Arc<Path>, usize>,
+
+    /// The policy for deriving the stall paths of added files.
+    #[serde(default, skip_serializing_if = "RenamePolicy::is_default")]
+    rename_policy: RenamePolicy,
+
+    /// Whether the stall is read-only. Commands which would modify the stall
+    /// file or directory are refused for read-only stalls.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    readonly: bool,
+
+    /// The stall file entries, mapping local paths to remote paths. Paths are
+    /// shared, so that clones of the stall, the entries merged from included
+    /// stalls, and the group members and dependencies naming an entry don't
+    /// copy them.
+    #[serde(deserialize_with = "deserialize_entries")]
+    entries: BTreeMap<Arc<Path>, EntryData>,
+
+    /// The named groups of entries, mapping group names to the local paths
+    /// of their members. Member paths share the storage of the entries' local
+    /// paths.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    groups: BTreeMap<String, BTreeSet<Arc<Path>>>,
 }
 
 impl Stall {
+    /// The current schema version of stall files.
+    pub const SCHEMA_VERSION: u32 = 1;
+
     /// Constructs a new `Stall` with the given load path.
     #[must_use]
     pub fn new<P>(path: P) -> Self
         where P: AsRef<Path>
     {
         Self {
+            version: Self::SCHEMA_VERSION,
+            migrated_from: None,
             load_status: LoadStatus::default()
                 .with_load_path(path),
-            entries: BiBTreeMap::new(),
+            durable: false,
+            format: StallFormat::default(),
+            include: Vec::new(),
+            included: Vec::new(),
+            origins: BTreeMap::new(),
+            rename_policy: RenamePolicy::default(),
+            readonly: false,
+            entries: BTreeMap::new(),
+            groups: BTreeMap::new(),
         }
     }
 
@@ -70,8 +153,18 @@ impl Stall {
     #[must_use]
     fn new_detached() -> Self {
         Self {
+            version: Self::SCHEMA_VERSION,
+            migrated_from: None,
             load_status: LoadStatus::default(),
-            entries: BiBTreeMap::new(),
+            durable: false,
+            format: StallFormat::default(),
+            include: Vec::new(),
+            included: Vec::new(),
+            origins: BTreeMap::new(),
+            rename_policy: RenamePolicy::default(),
+            readonly: false,
+            entries: BTreeMap::new(),
+            groups: BTreeMap::new(),
         }
     }
 
@@ -83,84 +176,1013 @@ impl Stall {
     }
 
     /// Returns the entry associated with the given local path, if it exists.
+    /// If the local path has multiple remotes, the entry for the first remote
+    /// is returned.
     #[must_use]
     pub fn entry_local<'a>(&'a self, local: &'a Path) -> Option<Entry<'a>> {
+        self.entries_local(local).next()
+    }
+
+    /// Returns an iterator over the entries associated with the given local
+    /// path, one for each of its remotes.
+    pub fn entries_local<'a>(&'a self, local: &'a Path)
+        -> impl Iterator<Item=Entry<'a>>
+    {
         self.entries
-            .get_by_left(local)
-            .map(|remote| Entry { local, remote })
+            .get(local)
+            .into_iter()
+            .flat_map(move |data| data.remotes
+                .iter()
+                .map(move |remote| data.entry(local, remote)))
     }
 
     /// Returns the entry associated with the given remote path, if it exists.
+    /// The remote path is [`clean`]ed before lookup.
+    ///
+    /// [`clean`]: path/fn.clean.html
     #[must_use]
-    pub fn entry_remote<'a>(&'a self, remote: &'a Path) -> Option<Entry<'a>> {
+    pub fn entry_remote<'a>(&'a self, remote: &Path) -> Option<Entry<'a>> {
+        let remote = path::clean(remote);
         self.entries
-            .get_by_right(remote)
-            .map(|local| Entry { local, remote })
+            .iter()
+            .find_map(|(local, data)| data.remotes
+                .iter()
+                .find(|r| path::same(r, &remote))
+                .map(|remote| data.entry(local, remote)))
+    }
+
+    /// Returns the local path of the entry with the given name. If no entry
+    /// has the name as its local path, the entries whose local paths contain
+    /// the name are matched, ignoring case. If none do, the entries whose
+    /// file names contain the characters of the name in order are matched.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] if no entry matches the name, or if more than
+    /// one entry matches.
+    ///
+    /// [`StallError`]: error/enum.StallError.html
+    pub fn resolve_local<'a>(&'a self, name: &'a Path)
+        -> Result<&'a Path, StallError>
+    {
+        if self.entries.contains_key(name) { return Ok(name); }
+
+        let needle = name.to_string_lossy().to_lowercase();
+        let mut candidates: Vec<&Path> = self.entries
+            .keys()
+            .filter(|local| local
+                .to_string_lossy()
+                .to_lowercase()
+                .contains(&needle))
+            .map(AsRef::as_ref)
+            .collect();
+        if candidates.is_empty() {
+            candidates = self.entries
+                .keys()
+                .filter(|local| local
+                    .file_name()
+                    .is_some_and(|file_name| is_subsequence(
+                        &needle,
+                        &file_name.to_string_lossy().to_lowercase())))
+                .map(AsRef::as_ref)
+                .collect();
+        }
+
+        match candidates.as_slice() {
+            [] => Err(self.missing_entry(name)),
+            [local] => {
+                event!(Level::INFO, "Using entry {:?} for {:?}", local, name);
+                Ok(local)
+            },
+            _ => Err(StallError::AmbiguousEntry {
+                name: name.to_path_buf(),
+                candidates: candidates
+                    .into_iter()
+                    .map(Path::to_path_buf)
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Returns the error for an unrecognized entry with the given local path,
+    /// suggesting the local paths of any similarly named entries.
+    #[must_use]
+    pub fn missing_entry(&self, local: &Path) -> StallError {
+        let locals: Vec<String> = self.entries
+            .keys()
+            .map(|l| l.to_string_lossy().into_owned())
+            .collect();
+        StallError::MissingEntry {
+            path: local.to_path_buf(),
+            suggestions: suggest::similar(
+                &local.to_string_lossy(),
+                locals.iter().map(String::as_str)),
+        }
+    }
+
+    /// Returns the entries selected by the given argument. The argument is
+    /// either an entry index or range of entry indices such as `3` or `7-9`,
+    /// as printed by the `status` command, a group name prefixed with `@`,
+    /// such as `@editor`, or a name as accepted by [`resolve_local`]. Local
+    /// paths take precedence over indices and groups.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] if no entries are selected, or if the name
+    /// matches more than one entry or no group.
+    ///
+    /// [`resolve_local`]: #method.resolve_local
+    /// [`StallError`]: error/enum.StallError.html
+    pub fn entries_selected<'a>(&'a self, arg: &'a Path)
+        -> Result<Vec<Entry<'a>>, StallError>
+    {
+        if self.entries.contains_key(arg) {
+            return Ok(self.entries_local(arg).collect());
+        }
+
+        if let Some(name) = self.group_name(arg) {
+            return Ok(self.group(name)?
+                .iter()
+                .flat_map(|local| self.entries_local(local))
+                .collect());
+        }
+
+        if let Some((first, last)) = arg.to_str().and_then(parse_index_range) {
+            let entries: Vec<_> = self.entries()
+                .enumerate()
+                .filter(|(i, _)| (first..=last).contains(&(i + 1)))
+                .map(|(_, entry)| entry)
+                .collect();
+            if entries.is_empty() {
+                return Err(StallError::MissingEntry {
+                    path: arg.to_path_buf(),
+                    suggestions: Vec::new(),
+                });
+            }
+            return Ok(entries);
+        }
+
+        let local = self.resolve_local(arg)?;
+        Ok(self.entries_local(local).collect())
+    }
+
+    /// Returns the entries selected by any of the given arguments, as
+    /// accepted by [`entries_selected`], or every entry if there are no
+    /// arguments. The entries are returned in the order of [`entries`], and
+    /// entries selected by more than one argument are returned once.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] if any argument selects no entries, or if a
+    /// name matches more than one entry.
+    ///
+    /// [`entries_selected`]: #method.entries_selected
+    /// [`entries`]: #method.entries
+    /// [`StallError`]: error/enum.StallError.html
+    pub fn select<'a, 'p, I>(&'a self, args: I)
+        -> Result<Vec<Entry<'a>>, StallError>
+        where I: IntoIterator<Item=&'p Path>
+    {
+        let mut selected = BTreeSet::new();
+        let mut all = true;
+        for arg in args {
+            all = false;
+            selected.extend(self.entries_selected(arg)?
+                .into_iter()
+                .map(|entry| (entry.local, entry.remote)));
+        }
+        Ok(self.entries()
+            .filter(|entry| all
+                || selected.contains(&(entry.local, entry.remote)))
+            .collect())
     }
 
-    /// Returns an iterator over the entries in the stall.
+    /// Returns an iterator over the entries in the stall. Entries are ordered
+    /// by their local paths, and local paths with multiple remotes will
+    /// produce an entry for each remote, in the order they were added.
     pub fn entries(&self) -> impl Iterator<Item=Entry<'_>> {
         self.entries
             .iter()
-            .map(|(l, r)| Entry {
-                local: l.as_path(),
-                remote: r.as_path(),
-            })
+            .flat_map(|(l, data)| data.remotes
+                .iter()
+                .map(|r| data.entry(l, r)))
     }
 
-    /// Adds a new entry to the stall with the given local and remote paths.
+    /// Returns the policy for deriving the stall paths of added files.
+    #[must_use]
+    pub const fn rename_policy(&self) -> RenamePolicy {
+        self.rename_policy
+    }
+
+    /// Sets the policy for deriving the stall paths of added files.
+    pub fn set_rename_policy(&mut self, rename_policy: RenamePolicy) {
+        self.rename_policy = rename_policy;
+        self.load_status.set_modified(true);
+    }
+
+    /// Returns `true` if the stall is read-only.
+    #[must_use]
+    pub const fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
+    /// Returns the flags of the entry with the given local path, if it exists.
+    #[must_use]
+    pub fn flags(&self, local: &Path) -> Option<EntryFlags> {
+        self.entries.get(local).map(|data| data.flags)
+    }
+
+    /// Returns the transforms of the entry with the given local path, if it
+    /// exists.
+    #[must_use]
+    pub fn transforms(&self, local: &Path) -> Option<&[Transform]> {
+        self.entries.get(local).map(|data| data.transforms.as_slice())
+    }
+
+    /// Sets the transforms of the entry with the given local path. Returns
+    /// `false` if the entry does not exist.
+    pub fn set_transforms(&mut self, local: &Path, transforms: Vec<Transform>)
+        -> bool
+    {
+        match self.entries.get_mut(local) {
+            Some(data) => {
+                event!(Level::INFO, "Setting transforms of local: {} to {:?}",
+                    local.display(),
+                    transforms);
+                data.transforms = transforms;
+                self.load_status.set_modified(true);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Returns the local paths of the entries which differ from those of the
+    /// given `Stall`, including entries which exist in only one of them.
+    #[must_use]
+    pub fn changed_locals(&self, other: &Self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self.entries
+            .iter()
+            .filter(|(local, data)| other.entries.get(*local) != Some(data))
+            .map(|(local, _)| local.to_path_buf())
+            .collect();
+        changed.extend(other.entries
+            .keys()
+            .filter(|local| !self.entries.contains_key(*local))
+            .map(|local| local.to_path_buf()));
+        changed.sort();
+        changed
+    }
+
+    /// Sets the flags of the entry with the given local path. Returns `false`
+    /// if the entry does not exist.
+    pub fn set_flags(&mut self, local: &Path, flags: EntryFlags) -> bool {
+        match self.entries.get_mut(local) {
+            Some(data) => {
+                event!(Level::INFO, "Setting flags of local: {} to {:?}",
+                    local.display(),
+                    flags);
+                data.flags = flags;
+                self.load_status.set_modified(true);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Adds a new entry to the stall with the given local and remote paths,
+    /// replacing any existing remotes for the local path. The flags of an
+    /// existing entry are retained. The remote path is [`clean`]ed before it is
+    /// inserted.
+    ///
+    /// Returns the local path and replaced remote path of the displaced entry,
+    /// if the local path was already used for a different remote. If the entry
+    /// had multiple remotes, only the first replaced remote is returned.
+    ///
+    /// ### Errors
+    ///
+    /// Returns an [`InsertError`] if either of the given paths do not have a
+    /// valid file name (e.g., `/` or `/abc/..`,) or if the remote path is
+    /// already stalled with a different local path. Use [`remove_remote`] to
+    /// detach it from that entry first.
     ///
-    /// ### Panics
+    /// [`remove_remote`]: #method.remove_remote
     ///
-    /// Panics if either of the given paths do not have a valid file name (e.g.,
-    /// `/` or `/abc/..`.)
-    pub fn insert(&mut self, local: PathBuf, remote: PathBuf) {
+    /// [`clean`]: path/fn.clean.html
+    /// [`InsertError`]: enum.InsertError.html
+    pub fn insert(&mut self, local: PathBuf, remote: &Path)
+        -> Result<Option<(PathBuf, PathBuf)>, InsertError>
+    {
         event!(Level::INFO, "Adding local: {} remote: {}",
             local.display(),
             remote.display());
-        assert!(local.file_name().is_some());
-        assert!(remote.file_name().is_some());
+        let remote = path::clean(remote);
+        InsertError::check(&local, &remote)?;
+        if let Some(owner) = self.remote_owner(&remote)
+            .filter(|owner| **owner != *local)
+        {
+            return Err(InsertError::RemoteInUse {
+                local: owner.to_path_buf(),
+                remote,
+            });
+        }
+
+        self.load_status.set_modified(true);
+        let data = self.entries
+            .entry(Arc::from(local.as_path()))
+            .or_default();
+        let overwrite = std::mem::replace(
+            &mut data.remotes,
+            vec![Arc::from(remote)]);
+        let displaced = overwrite
+            .into_iter()
+            .find(|r| !path::same(r, &data.remotes[0]))
+            .map(|r| (local, r.to_path_buf()));
+        event!(Level::DEBUG, "Displaced: {:?}", displaced);
+        Ok(displaced)
+    }
+
+    /// Exchanges the remote paths, flags, and group memberships of the entries
+    /// with the given local paths. Returns `false` if either entry does not
+    /// exist.
+    pub fn swap_locals(&mut self, a: &Path, b: &Path) -> bool {
+        if !self.entries.contains_key(a) || !self.entries.contains_key(b) {
+            return false;
+        }
+        event!(Level::INFO, "Swapping local: {} with local: {}",
+            a.display(),
+            b.display());
+        if a == b { return true; }
 
         self.load_status.set_modified(true);
-        let overwrite = self.entries.insert(local, remote);
-        event!(Level::DEBUG, "Overwrite: {:?}", overwrite);
+        let data_a = self.entries.remove(a).expect("get swapped entry");
+        let data_b = self.entries.insert(Arc::from(b), data_a)
+            .expect("get swapped entry");
+        let _ = self.entries.insert(Arc::from(a), data_b);
+        let (a, b) = (self.shared_local(a), self.shared_local(b));
+        for members in self.groups.values_mut() {
+            if members.contains(&a) != members.contains(&b) {
+                for local in [&a, &b] {
+                    if !members.remove(local) {
+                        let _ = members.insert(Arc::clone(local));
+                    }
+                }
+            }
+        }
+        for data in self.entries.values_mut() {
+            for dependency in &mut data.after {
+                if *dependency == a {
+                    *dependency = Arc::clone(&b);
+                } else if *dependency == b {
+                    *dependency = Arc::clone(&a);
+                }
+            }
+        }
+        true
+    }
+
+    /// Adds a remote path to the entry with the given local path, creating the
+    /// entry if it does not exist. The remote path is [`clean`]ed before it is
+    /// inserted.
+    ///
+    /// Returns the previous local path and the remote path, if the remote was
+    /// already stalled with a different local path.
+    ///
+    /// ### Errors
+    ///
+    /// Returns an [`InsertError`] if either of the given paths do not have a
+    /// valid file name (e.g., `/` or `/abc/..`.)
+    ///
+    /// [`clean`]: path/fn.clean.html
+    /// [`InsertError`]: enum.InsertError.html
+    pub fn insert_remote(&mut self, local: PathBuf, remote: &Path)
+        -> Result<Option<(PathBuf, PathBuf)>, InsertError>
+    {
+        event!(Level::INFO, "Adding remote: {} to local: {}",
+            remote.display(),
+            local.display());
+        let remote = path::clean(remote);
+        InsertError::check(&local, &remote)?;
+        if self.has_remote(&local, &remote) { return Ok(None); }
+
+        self.load_status.set_modified(true);
+        let displaced = self.detach_remote(&remote);
+        event!(Level::DEBUG, "Displaced: {:?}", displaced);
+        self.entries
+            .entry(Arc::from(local))
+            .or_default()
+            .remotes
+            .push(Arc::from(remote));
+        Ok(displaced)
     }
 
     /// Removes an entry from the stall with the given local path, if one
-    /// exists.
+    /// exists. Returns the local path along with all of its remote paths.
     pub fn remove_local(&mut self, local: &Path)
-        -> Option<(PathBuf, PathBuf)>
+        -> Option<(PathBuf, Vec<PathBuf>)>
     {
         event!(Level::INFO, "Removing local: {}", local.display());
-        self.load_status.set_modified(true);
-        let removed = self.entries.remove_by_left(local);
+        let removed = self.entries
+            .remove_entry(local)
+            .map(|(l, data)| (l.to_path_buf(), data.remotes
+                .iter()
+                .map(|r| r.to_path_buf())
+                .collect()));
         event!(Level::DEBUG, "Removed: {:?}", removed);
+        if removed.is_some() { self.load_status.set_modified(true); }
         removed
     }
 
     /// Removes an entry from the stall with the given remote path, if one
-    /// exists.
+    /// exists. The local path is retained if it has other remotes. The remote
+    /// path is [`clean`]ed before lookup.
+    ///
+    /// [`clean`]: path/fn.clean.html
     pub fn remove_remote(&mut self, remote: &Path)
         -> Option<(PathBuf, PathBuf)>
     {
         event!(Level::INFO, "Removing remote: {}", remote.display());
-        self.load_status.set_modified(true);
-        let removed = self.entries.remove_by_right(remote);
+        let removed = self.detach_remote(&path::clean(remote));
         event!(Level::DEBUG, "Removed: {:?}", removed);
+        if removed.is_some() { self.load_status.set_modified(true); }
         removed
     }
 
-    /// Inserts a new stall entry from a list file parse. Doesn't update the
-    /// load status of the Stall.
+    /// Replaces a remote path of an entry with a new remote path, keeping the
+    /// entry's flags, transforms, and dependencies. Returns the local path of
+    /// the entry, or `None` if no entry has the old remote path. Both remote
+    /// paths are [`clean`]ed.
+    ///
+    /// ### Errors
+    ///
+    /// Returns an [`InsertError`] if the new remote path does not have a valid
+    /// file name.
+    ///
+    /// [`clean`]: path/fn.clean.html
+    /// [`InsertError`]: enum.InsertError.html
+    pub fn replace_remote(&mut self, remote: &Path, new_remote: &Path)
+        -> Result<Option<PathBuf>, InsertError>
+    {
+        event!(Level::INFO, "Replacing remote: {} with: {}",
+            remote.display(),
+            new_remote.display());
+        let remote = path::clean(remote);
+        let new_remote = path::clean(new_remote);
+        let Some((local, data)) = self.entries
+            .iter_mut()
+            .find(|(_, data)| data.remotes
+                .iter()
+                .any(|r| path::same(r, &remote)))
+            else { return Ok(None) };
+        InsertError::check(local, &new_remote)?;
+
+        for r in &mut data.remotes {
+            if path::same(r, &remote) { *r = Arc::from(new_remote.as_path()); }
+        }
+        let local = local.to_path_buf();
+        self.load_status.set_modified(true);
+        Ok(Some(local))
+    }
+
+    /// Returns `true` if the entry with the given local path has the given
+    /// remote path.
+    fn has_remote(&self, local: &Path, remote: &Path) -> bool {
+        self.entries
+            .get(local)
+            .is_some_and(|data| data.remotes
+                .iter()
+                .any(|r| path::same(r, remote)))
+    }
+
+    /// Returns the local path of the entry containing the given remote path.
+    fn remote_owner(&self, remote: &Path) -> Option<Arc<Path>> {
+        self.entries
+            .iter()
+            .find(|(_, data)| data.remotes
+                .iter()
+                .any(|r| path::same(r, remote)))
+            .map(|(local, _)| Arc::clone(local))
+    }
+
+    /// Removes the given remote path from whichever entry contains it, and
+    /// removes that entry if it has no remaining remotes.
+    fn detach_remote(&mut self, remote: &Path) -> Option<(PathBuf, PathBuf)> {
+        let local = self.remote_owner(remote)?;
+
+        let data = self.entries
+            .get_mut(&local)
+            .expect("get entry remotes");
+        let idx = data.remotes
+            .iter()
+            .position(|r| path::same(r, remote))
+            .expect("find entry remote");
+        let removed = data.remotes.remove(idx);
+        if data.remotes.is_empty() {
+            let _ = self.entries.remove(&local);
+        }
+        Some((local.to_path_buf(), removed.to_path_buf()))
+    }
+
+    /// Inserts a remote path from a list file parse, adding it to the entry
+    /// with the given local path, or the file name of the remote path if
+    /// `None`. Doesn't update the load status of the Stall.
     ///
-    /// ### Panics
+    /// ### Errors
     ///
-    /// Panics if the given path does not have a valid file name (e.g., `/` or
-    /// `/abc/..`.)
-    fn insert_list_remote(&mut self, remote: PathBuf) {
-        let local = remote.file_name().expect("invalid stall file_name");
+    /// Returns an [`InsertError`] if either of the paths do not have a valid
+    /// file name (e.g., `/` or `/abc/..`.)
+    ///
+    /// [`InsertError`]: enum.InsertError.html
+    fn insert_list_remote(&mut self, local: Option<&Path>, remote: &Path)
+        -> Result<(), InsertError>
+    {
+        let remote = path::clean(remote);
+        let local = match local {
+            Some(local) => local.to_path_buf(),
+            None => remote
+                .file_name()
+                .map(PathBuf::from)
+                .ok_or_else(|| InsertError::InvalidRemote(remote.clone()))?,
+        };
+        InsertError::check(&local, &remote)?;
+
+        self.entries
+            .entry(Arc::from(local))
+            .or_default()
+            .remotes
+            .push(Arc::from(remote));
+        Ok(())
+    }
+
+    /// Returns the given local path, sharing the storage of the local path of
+    /// the entry it names if there is one.
+    fn shared_local(&self, local: &Path) -> Arc<Path> {
+        self.entries
+            .get_key_value(local)
+            .map_or_else(|| Arc::from(local), |(key, _)| Arc::clone(key))
+    }
+
+    /// Shares the storage of the group members and dependencies which name
+    /// an entry with the entry's local path. Deserialized paths are allocated
+    /// separately, so this is done once the entries are loaded.
+    fn share_locals(&mut self) {
+        if self.groups.is_empty()
+            && self.entries.values().all(|data| data.after.is_empty())
+        {
+            return;
+        }
+
+        // Paths are hashed faster than they are compared, so the local paths
+        // are looked up in a set instead of the entries map.
+        let locals: HashSet<Arc<Path>> = self.entries.keys().cloned().collect();
+        let share = |path: &mut Arc<Path>| {
+            if let Some(local) = locals.get(&**path) {
+                *path = Arc::clone(local);
+            }
+        };
+        for members in self.groups.values_mut() {
+            *members = std::mem::take(members)
+                .into_iter()
+                .map(|mut member| { share(&mut member); member })
+                .collect();
+        }
+        for data in self.entries.values_mut() {
+            data.after.iter_mut().for_each(share);
+        }
+    }
 
-        let _overwrite = self.entries.insert(local.into(), remote);
+    /// Returns `true` if the `Stall` can be written in the list format
+    /// without losing any of its data.
+    fn is_list_compatible(&self) -> bool {
+        self.rename_policy.is_default()
+            && !self.readonly
+            && self.groups.is_empty()
+            && self.include.iter().all(|p| is_list_path(p))
+            && self.entries
+            .iter()
+            .all(|(local, data)| data.flags == EntryFlags::default()
+                && data.transforms.is_empty()
+                && data.after.is_empty()
+                && is_list_path(local)
+                && data.remotes.iter().all(|r| is_list_path(r)))
+    }
+
+    ////////////////////////////////////////////////////////////////////////////
+    // Group methods.
+    ////////////////////////////////////////////////////////////////////////////
+
+    /// Returns an iterator over the names of the stall's groups and the local
+    /// paths of their members, ordered by name.
+    pub fn groups(&self)
+        -> impl Iterator<Item=(&str, &BTreeSet<Arc<Path>>)>
+    {
+        self.groups
+            .iter()
+            .map(|(name, members)| (name.as_str(), members))
+    }
+
+    /// Returns the local paths of the members of the group with the given
+    /// name.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] if there is no group with the given name.
+    ///
+    /// [`StallError`]: error/enum.StallError.html
+    pub fn group(&self, name: &str)
+        -> Result<&BTreeSet<Arc<Path>>, StallError>
+    {
+        self.groups
+            .get(name)
+            .ok_or_else(|| StallError::NotFound {
+                kind: "group",
+                name: name.to_owned(),
+                suggestions: suggest::similar(
+                    name,
+                    self.groups.keys().map(String::as_str)),
+            })
+    }
+
+    /// Returns the name of the group referred to by the given argument, if it
+    /// is a group name prefixed with `@` and not the local path of an entry.
+    #[must_use]
+    pub fn group_name<'a>(&self, arg: &'a Path) -> Option<&'a str> {
+        if self.entries.contains_key(arg) { return None; }
+        arg.to_str()?.strip_prefix('@')
+    }
+
+    /// Returns the local paths named by the given arguments, replacing each
+    /// group name prefixed with `@` with the local paths of its members.
+    /// Other arguments are returned unchanged.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] if there is no group with one of the given
+    /// names.
+    ///
+    /// [`StallError`]: error/enum.StallError.html
+    pub fn expand_groups<'a, I>(&self, args: I)
+        -> Result<Vec<PathBuf>, StallError>
+        where I: IntoIterator<Item=&'a Path>
+    {
+        let mut expanded = Vec::new();
+        for arg in args {
+            match self.group_name(arg) {
+                Some(name) => expanded.extend(self.group(name)?
+                    .iter()
+                    .map(|local| local.to_path_buf())),
+                None => expanded.push(arg.to_path_buf()),
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Compiles glob patterns for matching the local paths of entries, or
+    /// returns `None` if there are no patterns. Group names prefixed with `@`
+    /// are replaced with patterns matching the local paths of their members.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] if a pattern is invalid, or if there is no
+    /// group with one of the given names.
+    ///
+    /// [`StallError`]: error/enum.StallError.html
+    pub fn entry_patterns<'a, I>(&self, patterns: I)
+        -> Result<Option<Vec<Pattern>>, StallError>
+        where I: IntoIterator<Item=&'a Path>
+    {
+        let compile = |pattern: &str| Pattern::new(pattern)
+            .map_err(|e| StallError::InvalidPattern {
+                pattern: pattern.to_owned(),
+                message: e.to_string(),
+            });
+
+        let mut compiled = None;
+        for p in patterns {
+            let compiled = compiled.get_or_insert_with(Vec::new);
+            match self.group_name(p) {
+                Some(name) => for local in self.group(name)? {
+                    compiled.push(compile(
+                        &Pattern::escape(&local.to_string_lossy()))?);
+                },
+                None => compiled.push(compile(&p.to_string_lossy())?),
+            }
+        }
+        Ok(compiled)
+    }
+
+    /// Creates an empty group with the given name. Returns `false` if the
+    /// group already exists.
+    pub fn insert_group(&mut self, name: &str) -> bool {
+        if self.groups.contains_key(name) { return false; }
+        event!(Level::INFO, "Creating group: {}", name);
+        let _ = self.groups.insert(name.to_owned(), BTreeSet::new());
+        self.load_status.set_modified(true);
+        true
+    }
+
+    /// Removes the group with the given name, returning the local paths of
+    /// its members if it exists. The entries of the group are not removed.
+    pub fn remove_group(&mut self, name: &str)
+        -> Option<BTreeSet<Arc<Path>>>
+    {
+        let removed = self.groups.remove(name);
+        if removed.is_some() {
+            event!(Level::INFO, "Removing group: {}", name);
+            self.load_status.set_modified(true);
+        }
+        removed
+    }
+
+    /// Adds the entry with the given local path to the group with the given
+    /// name. Returns `false` if the group does not exist or the entry is
+    /// already a member.
+    pub fn insert_group_member(&mut self, name: &str, local: &Path) -> bool {
+        let member = self.shared_local(local);
+        let inserted = self.groups
+            .get_mut(name)
+            .is_some_and(|members| members.insert(member));
+        if inserted {
+            event!(Level::INFO, "Adding local: {} to group: {}",
+                local.display(),
+                name);
+            self.load_status.set_modified(true);
+        }
+        inserted
+    }
+
+    /// Removes the entry with the given local path from the group with the
+    /// given name. Returns `false` if the entry is not a member of the group.
+    pub fn remove_group_member(&mut self, name: &str, local: &Path) -> bool {
+        let removed = self.groups
+            .get_mut(name)
+            .is_some_and(|members| members.remove(local));
+        if removed {
+            event!(Level::INFO, "Removing local: {} from group: {}",
+                local.display(),
+                name);
+            self.load_status.set_modified(true);
+        }
+        removed
+    }
+
+    /// Removes the entry with the given local path from every group.
+    pub fn remove_from_groups(&mut self, local: &Path) {
+        for members in self.groups.values_mut() {
+            if members.remove(local) { self.load_status.set_modified(true); }
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////////////////
+    // Dependency methods.
+    ////////////////////////////////////////////////////////////////////////////
+
+    /// Returns the local paths of the entries which are distributed before
+    /// the entry with the given local path, if it exists.
+    #[must_use]
+    pub fn dependencies(&self, local: &Path) -> Option<&[Arc<Path>]> {
+        self.entries.get(local).map(|data| data.after.as_slice())
+    }
+
+    /// Sets the local paths of the entries which are distributed before the
+    /// entry with the given local path. Returns `false` if the entry does not
+    /// exist. Use [`check_dependencies`] to check for dependency cycles.
+    ///
+    /// [`check_dependencies`]: #method.check_dependencies
+    pub fn set_dependencies(&mut self, local: &Path, after: Vec<PathBuf>)
+        -> bool
+    {
+        let after: Vec<_> = after
+            .into_iter()
+            .map(|dependency| self.shared_local(&dependency))
+            .collect();
+        match self.entries.get_mut(local) {
+            Some(data) => {
+                event!(Level::INFO, "Setting dependencies of local: {} to {:?}",
+                    local.display(),
+                    after);
+                data.after = after;
+                self.load_status.set_modified(true);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Returns the local paths of a cycle of dependencies between entries,
+    /// starting and ending with the same path, if there is one.
+    #[must_use]
+    pub fn dependency_cycle(&self) -> Option<Vec<PathBuf>> {
+        /// Searches for a cycle through the dependencies of `local`, with
+        /// the current search path in `path`.
+        fn visit<'a>(
+            stall: &'a Stall,
+            local: &'a Path,
+            path: &mut Vec<&'a Path>,
+            done: &mut BTreeSet<&'a Path>)
+            -> Option<Vec<PathBuf>>
+        {
+            if done.contains(local) { return None; }
+            if let Some(start) = path.iter().position(|p| *p == local) {
+                let mut cycle: Vec<PathBuf> = path[start..]
+                    .iter()
+                    .map(|p| p.to_path_buf())
+                    .collect();
+                cycle.push(local.to_path_buf());
+                return Some(cycle);
+            }
+            path.push(local);
+            for dependency in stall.dependencies(local).unwrap_or_default() {
+                if let Some(cycle) = visit(stall, dependency, path, done) {
+                    return Some(cycle);
+                }
+            }
+            let _ = path.pop();
+            let _ = done.insert(local);
+            None
+        }
+
+        let mut done = BTreeSet::new();
+        self.entries
+            .iter()
+            .filter(|(_, data)| !data.after.is_empty())
+            .find_map(|(local, _)| visit(self, local, &mut Vec::new(), &mut done))
+    }
+
+    /// Checks that there are no cycles in the dependencies between entries.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] describing the cycle if there is one.
+    ///
+    /// [`StallError`]: error/enum.StallError.html
+    pub fn check_dependencies(&self) -> Result<(), StallError> {
+        let Some(cycle) = self.dependency_cycle() else { return Ok(()) };
+        Err(StallError::InvalidInput {
+            message: format!("dependency cycle between entries: {}", cycle
+                .iter()
+                .map(|local| local.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> ")),
+        })
+    }
+
+    /// Reorders the given entries so that each entry follows the entries it
+    /// depends on, directly or through entries which are not given. Entries
+    /// are otherwise kept in their given order.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] if there is a cycle in the dependencies
+    /// between entries.
+    ///
+    /// [`StallError`]: error/enum.StallError.html
+    pub fn sort_by_dependencies(&self, entries: &mut Vec<Entry<'_>>)
+        -> Result<(), StallError>
+    {
+        if self.entries.values().all(|data| data.after.is_empty()) {
+            return Ok(());
+        }
+        self.check_dependencies()?;
+
+        let order = {
+            let mut selected: BTreeMap<&Path, Vec<usize>> = BTreeMap::new();
+            for (i, entry) in entries.iter().enumerate() {
+                selected.entry(entry.local).or_default().push(i);
+            }
+
+            // Count the dependencies of each entry which must be processed
+            // before it, and record the entries waiting on each one.
+            let mut pending = vec![0_usize; entries.len()];
+            let mut waiting = vec![Vec::new(); entries.len()];
+            for (i, entry) in entries.iter().enumerate() {
+                let mut visited = BTreeSet::new();
+                let mut stack = vec![entry.local];
+                while let Some(local) = stack.pop() {
+                    for dependency in self.dependencies(local)
+                        .unwrap_or_default()
+                    {
+                        let dependency = dependency.as_ref();
+                        if !visited.insert(dependency) { continue; }
+                        match selected.get(dependency) {
+                            Some(indices) => for &j in indices {
+                                pending[i] += 1;
+                                waiting[j].push(i);
+                            },
+                            None => stack.push(dependency),
+                        }
+                    }
+                }
+            }
+
+            // Process the earliest entry whose dependencies are done.
+            let mut ready: BinaryHeap<Reverse<usize>> = (0..entries.len())
+                .filter(|&i| pending[i] == 0)
+                .map(Reverse)
+                .collect();
+            let mut order = Vec::with_capacity(entries.len());
+            while let Some(Reverse(i)) = ready.pop() {
+                order.push(i);
+                for &j in &waiting[i] {
+                    pending[j] -= 1;
+                    if pending[j] == 0 { ready.push(Reverse(j)); }
+                }
+            }
+            order
+        };
+
+        let sorted = order.iter().map(|&i| entries[i].clone()).collect();
+        *entries = sorted;
+        Ok(())
+    }
+
+    /// Removes the given local path from the dependencies of every entry.
+    pub fn remove_from_dependencies(&mut self, local: &Path) {
+        for data in self.entries.values_mut() {
+            let len = data.after.len();
+            data.after.retain(|dependency| **dependency != *local);
+            if data.after.len() != len { self.load_status.set_modified(true); }
+        }
+    }
+
+    /// Replaces the old local paths of renamed entries with their new local
+    /// paths in the dependencies of every entry. Each path is replaced at
+    /// most once, so entries may be renamed onto each other's names.
+    pub fn rename_in_dependencies(&mut self, renames: &[(PathBuf, PathBuf)]) {
+        let renames: Vec<_> = renames
+            .iter()
+            .map(|(old, new)| (old.as_path(), self.shared_local(new)))
+            .collect();
+        for data in self.entries.values_mut() {
+            for dependency in &mut data.after {
+                if let Some((_, new)) = renames
+                    .iter()
+                    .find(|(old, _)| **old == **dependency)
+                {
+                    *dependency = Arc::clone(new);
+                    self.load_status.set_modified(true);
+                }
+            }
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////////////////
+    // Overlap methods.
+    ////////////////////////////////////////////////////////////////////////////
+
+    /// Returns an [`OverlappingEntries`] warning for each pair of entries
+    /// whose files overlap, where at least one of the entries' local paths is
+    /// `selected`. Entries overlap if one's remote file is inside the other's
+    /// remote directory, if one's stalled file is inside the other's stalled
+    /// directory, or if their local paths are the same after case folding and
+    /// cleaning.
+    ///
+    /// [`OverlappingEntries`]: ../warning/enum.Warning.html#variant.OverlappingEntries
+    pub fn overlaps<F>(&self, mut selected: F) -> Vec<Warning>
+        where F: FnMut(&Path) -> bool
+    {
+        let fold = |local: &Path| path::clean_lexical(local)
+            .to_string_lossy()
+            .to_lowercase();
+        // Entries with several remotes would report their overlaps once for
+        // each remote.
+        let mut warnings = Vec::new();
+        let mut push = |local: &Path, other: &Path, overlap| {
+            let warning = Warning::OverlappingEntries {
+                local: local.to_path_buf(),
+                other: other.to_path_buf(),
+                overlap,
+            };
+            if !warnings.contains(&warning) { warnings.push(warning); }
+        };
+
+        let entries: Vec<_> = self.entries().collect();
+        for (i, a) in entries.iter().enumerate() {
+            for b in &entries[i + 1..] {
+                if !selected(a.local) && !selected(b.local) { continue; }
+
+                // Remote files are nested if either is inside the other.
+                for (x, y) in [(a, b), (b, a)] {
+                    if x.remote != y.remote && x.remote.starts_with(y.remote) {
+                        push(x.local, y.local, Overlap::NestedRemote);
+                    }
+                }
+
+                // Entries with several remotes share their local path.
+                if a.local == b.local { continue; }
+                if fold(a.local) == fold(b.local) {
+                    push(a.local, b.local, Overlap::AliasedLocal);
+                    continue;
+                }
+                for (x, y) in [(a, b), (b, a)] {
+                    if x.local.starts_with(y.local) {
+                        push(x.local, y.local, Overlap::NestedLocal);
+                    }
+                }
+            }
+        }
+
+        warnings
     }
 
     ////////////////////////////////////////////////////////////////////////////
@@ -182,6 +1204,19 @@ impl Stall {
         self.load_status.load_path()
     }
 
+    /// Returns the directory containing the `Stall`'s load path, which is the
+    /// stall directory.
+    #[must_use]
+    pub fn load_dir(&self) -> Option<&Path> {
+        self.load_path()
+            .and_then(Path::parent)
+            .map(|dir| if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            })
+    }
+
     /// Sets the `Stall`'s load path.
     pub fn set_load_path<P>(&mut self, path: P)
         where P: AsRef<Path>
@@ -200,40 +1235,171 @@ impl Stall {
         self.load_status.set_modified(modified);
     }
 
+    /// Sets whether the stall file and its directory are synced to disk when
+    /// the `Stall` is written.
+    pub fn set_durable(&mut self, durable: bool) {
+        self.durable = durable;
+    }
+
     /// Constructs a new `Stall` with options read from the given file path.
-    pub fn read_from_path<P>(path: P) -> Result<Self, Error> 
+    pub fn read_from_path<P>(path: P) -> Result<Self, StallError> 
         where P: AsRef<Path>
     {
-        let path = path.as_ref();
+        Self::read_from_path_as(path, None)
+    }
+
+    /// Constructs a new `Stall` with options read from the given file path
+    /// using the given format, or the detected format if `None`.
+    ///
+    /// The entries of any included stall files are merged into the `Stall`.
+    /// Included files are read using their detected formats.
+    pub fn read_from_path_as<P>(path: P, format: Option<StallFormat>)
+        -> Result<Self, StallError> 
+        where P: AsRef<Path>
+    {
+        Self::read_from_path_including(path.as_ref(), format, &mut Vec::new())
+    }
+
+    /// Constructs a new `Stall` with options read from the given file path,
+    /// along with its included stall files. The canonical paths of the stall
+    /// files which are including this one are given in `chain`, so that
+    /// include cycles can be detected.
+    fn read_from_path_including(
+        path: &Path,
+        format: Option<StallFormat>,
+        chain: &mut Vec<PathBuf>)
+        -> Result<Self, StallError> 
+    {
+        // The fingerprint is taken first, so any change made while the file
+        // is being parsed is detected when it is written.
+        let fingerprint = Fingerprint::read(path)
+            .with_path("Failed to read stall file", path)?;
         let file = File::open(path)
-            .with_context(|| format!(
-                "Failed to open stall file for reading: {}",
-                path.display()))?;
-        let mut stall = Self::read_from_file(file)?;
+            .with_path("Failed to open stall file for reading", path)?;
+        let mut stall = Self::read_from_file_as(file, format)
+            .map_err(|e| e.in_file(path))?;
         stall.set_load_path(path);
+        stall.load_status.set_fingerprint(fingerprint);
+
+        if !stall.include.is_empty() {
+            chain.push(path.canonicalize()
+                .unwrap_or_else(|_| path.to_path_buf()));
+            stall.load_included(path, chain)?;
+            let _ = chain.pop();
+        }
         Ok(stall)
     }
 
-    /// Open a file at the given path and write the `Stall` into it.
-    pub fn write_to_path<P>(&self, path: P) -> Result<(), Error>
+    /// Reads the included stall files of the `Stall` loaded from the given
+    /// path and merges their entries into it. An included entry is used
+    /// unless an entry from a file with higher precedence has the same local
+    /// path or stalls one of its remote paths. The including file has the
+    /// highest precedence, followed by its includes in reverse order.
+    fn load_included(&mut self, path: &Path, chain: &mut Vec<PathBuf>)
+        -> Result<(), StallError>
+    {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for include in &self.include {
+            let include_path = dir.join(include);
+            let canonical = include_path.canonicalize()
+                .with_path("Failed to open included stall file",
+                    &include_path)?;
+            if chain.contains(&canonical) {
+                return Err(StallError::Conflict {
+                    reason: "stall file includes itself".to_owned(),
+                    subject: chain
+                        .iter()
+                        .chain(std::iter::once(&canonical))
+                        .map(|p| p.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(" -> "),
+                    hint: None,
+                });
+            }
+            event!(Level::DEBUG, "Including stall file {:?}", include_path);
+            self.included.push(
+                Self::read_from_path_including(&include_path, None, chain)?);
+        }
+
+        for (idx, included) in self.included.iter().enumerate().rev() {
+            for (local, data) in &included.entries {
+                let shadowed = self.entries.contains_key(local)
+                    || data.remotes.iter().any(|r| self.entries
+                        .values()
+                        .any(|d| d.remotes.iter().any(|o| path::same(o, r))));
+                if shadowed {
+                    event!(Level::DEBUG, "Included entry {:?} is shadowed",
+                        local);
+                    continue;
+                }
+                let _ = self.entries.insert(Arc::clone(local), data.clone());
+                let _ = self.origins.insert(Arc::clone(local), idx);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the `Stall` with only the entries of the including file, as
+    /// written to its own stall file.
+    fn without_included(&self) -> Self {
+        let mut own = self.clone();
+        own.included.clear();
+        own.origins.clear();
+        own.entries.retain(|local, _| !self.origins.contains_key(local));
+        own
+    }
+
+    /// Writes the included entries of the `Stall` back into the included
+    /// stall files they were loaded from. Only the files whose entries have
+    /// changed are written. Included entries which were removed are removed
+    /// from their files.
+    fn write_included(&self) -> Result<(), StallError> {
+        for (idx, included) in self.included.iter().enumerate() {
+            let mut part = included.clone();
+            for (local, _) in self.origins.iter().filter(|(_, i)| **i == idx) {
+                let _ = match self.entries.get(local) {
+                    Some(data) => part.entries
+                        .insert(Arc::clone(local), data.clone()),
+                    None => part.entries.remove(local),
+                };
+            }
+            if part.entries != included.entries {
+                event!(Level::INFO, "Writing included stall file {:?}",
+                    part.load_path());
+                let _ = part.write_to_load_path()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the `Stall` into a file at the given path. The file is written
+    /// to a temporary file and renamed into place, so an existing file is
+    /// never left partially written.
+    pub fn write_to_path<P>(&self, path: P) -> Result<(), StallError>
         where P: AsRef<Path>
     {
         let path = path.as_ref();
-        let file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(path)
-            .with_context(|| format!(
-                "Failed to open stall file for writing: {}",
-                path.display()))?;
-        self.write_to_file(file)
-            .context("Failed to write stall file")?;
-        Ok(())
+        if self.write_format() == StallFormat::List {
+            event!(Level::DEBUG, "Keeping list format for stall file {:?}",
+                path);
+        } else if let Some(from) = self.migrated_from {
+            event!(Level::WARN, "Upgrading stall file {:?} from schema \
+                version {} to {}. Older versions of stall may not be able to \
+                read it.", path, from, Self::SCHEMA_VERSION);
+        }
+        replace_with(path, self.durable, |temp| {
+            let file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(temp)
+                .with_path("Failed to open stall file for writing", temp)?;
+            self.write_to_file(file)
+                .map_err(|e| e.in_file(path))
+        })
     }
     
     /// Create a new file at the given path and write the `Stall` into it.
-    pub fn write_to_path_if_new<P>(&self, path: P) -> Result<(), Error>
+    pub fn write_to_path_if_new<P>(&self, path: P) -> Result<(), StallError>
         where P: AsRef<Path>
     {
         let path = path.as_ref();
@@ -242,19 +1408,49 @@ impl Stall {
             .truncate(true)
             .create_new(true)
             .open(path)
-            .with_context(|| format!(
-                "Failed to create stall file: {}",
-                path.display()))?;
+            .with_path("Failed to create stall file", path)?;
         self.write_to_file(file)
-            .context("Failed to write stall file")?;
+            .map_err(|e| e.in_file(path))?;
         Ok(())
     }
 
+    /// Returns `true` if the file the `Stall` was loaded from has been changed
+    /// by another program since it was loaded.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] if the file can't be read.
+    ///
+    /// [`StallError`]: error/enum.StallError.html
+    pub fn changed_on_disk(&self) -> Result<bool, StallError> {
+        self.load_status.load_path().map_or(Ok(false), |path| self
+            .load_status
+            .changed_on_disk()
+            .with_path("Failed to read stall file", path))
+    }
+
     /// Write the `Stall` into the file is was loaded from. Returns true if the
     /// data was written.
-    pub fn write_to_load_path(&self) -> Result<bool, Error> {
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] if the file was changed by another program
+    /// since it was loaded, or if an IO error occurs during writing.
+    ///
+    /// [`StallError`]: error/enum.StallError.html
+    pub fn write_to_load_path(&self) -> Result<bool, StallError> {
         match self.load_status.load_path() {
             Some(path) => {
+                if self.changed_on_disk()? {
+                    return Err(StallError::Conflict {
+                        reason: "stall file was changed by another program \
+                            since it was loaded".to_owned(),
+                        subject: path.display().to_string(),
+                        hint: Some("Run the command again to apply it to the \
+                            changed stall file.".to_owned()),
+                    });
+                }
+                self.write_included()?;
                 self.write_to_path(path)?;
                 Ok(true)
             },
@@ -264,7 +1460,7 @@ impl Stall {
 
     /// Write the `Stall` into a new file using the load path. Returns true
     /// if the data was written.
-    pub fn write_to_load_path_if_new(&self) -> Result<bool, Error> {
+    pub fn write_to_load_path_if_new(&self) -> Result<bool, StallError> {
         match self.load_status.load_path() {
             Some(path) => {
                 self.write_to_path_if_new(path)?;
@@ -275,76 +1471,226 @@ impl Stall {
     }
 
     /// Constructs a new `Stall` with options parsed from the given file.
-    pub fn read_from_file(mut file: File) -> Result<Self, Error>  {
-        // TODO: Consider returning RON error.
-        match Self::parse_ron_from_file(&mut file) {
-            Ok(stall) => Ok(stall),
-            Err(e)     => {
-                event!(Level::DEBUG, "Error in RON, switching to list format.\n\
-                    {:?}", e);
-                let _ = file.seek(SeekFrom::Start(0))?;
-                Self::parse_list_from_file(&mut file)
-            },
+    /// Stall files with older schema versions are migrated to the current
+    /// version.
+    ///
+    /// Files which are clearly in the RON format report RON parse errors
+    /// instead of being parsed using the list format.
+    pub fn read_from_file(file: File) -> Result<Self, StallError>  {
+        Self::read_from_file_as(file, None)
+    }
+
+    /// Returns the format to write the `Stall` in. Stall files read in the
+    /// list format are written in the list format, unless they have flags or
+    /// options which can't be written in it.
+    fn write_format(&self) -> StallFormat {
+        match self.format {
+            StallFormat::List if self.is_list_compatible() => StallFormat::List,
+            _ => StallFormat::Ron,
         }
     }
 
-    /// Parses a `Stall` from a file using the RON format.
-    fn parse_ron_from_file(file: &mut File) -> Result<Self, Error> {
+    /// Constructs a new `Stall` with options parsed from the given file using
+    /// the given format, or the detected format if `None`. Stall files with
+    /// older schema versions are migrated to the current version.
+    pub fn read_from_file_as(mut file: File, format: Option<StallFormat>)
+        -> Result<Self, StallError>
+    {
+        if format == Some(StallFormat::List) {
+            event!(Level::INFO, "Reading stall file using the list format");
+            let mut stall = Self::parse_list_from_reader(BufReader::new(file))?;
+            stall.share_locals();
+            stall.migrate()?;
+            return Ok(stall);
+        }
+
         let len = file.metadata()
-            .context("Failed to recover file metadata.")?
+            .with_io_context("Failed to recover file metadata.")?
             .len();
-        let mut buf = Vec::with_capacity(len.try_into()?);
+        let mut buf = Vec::with_capacity(usize::try_from(len).unwrap_or_default());
         let _ = file.read_to_end(&mut buf)
-            .context("Failed to read stall file")?;
+            .with_io_context("Failed to read stall file")?;
 
-        Self::parse_ron_from_bytes(&buf[..])
+        let mut stall = match Self::parse_ron_from_bytes(&buf[..]) {
+            Ok(stall) => {
+                event!(Level::INFO, "Reading stall file using the RON format");
+                stall
+            },
+            Err(e @ StallError::UnsupportedVersion { .. }) => return Err(e),
+            Err(e) if format.is_some() || is_clearly_ron(&buf[..]) => {
+                let source = String::from_utf8_lossy(&buf[..]);
+                return Err(e.with_source(&source));
+            },
+            Err(e)     => {
+                event!(Level::DEBUG, "Error in RON, switching to list format.\n\
+                    {:?}", e);
+                event!(Level::INFO, "Reading stall file using the list format");
+                Self::parse_list_from_reader(&buf[..])?
+            },
+        };
+        stall.share_locals();
+        stall.migrate()?;
+        Ok(stall)
     }
 
+    /// Upgrades the `Stall` from the schema version it was loaded with to the
+    /// current schema version, one version at a time.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a [`StallError`] if the schema version is newer than the
+    /// current version.
+    ///
+    /// [`StallError`]: error/enum.StallError.html
+    fn migrate(&mut self) -> Result<(), StallError> {
+        if self.version > Self::SCHEMA_VERSION {
+            return Err(StallError::UnsupportedVersion {
+                path: None,
+                version: self.version,
+                supported: Self::SCHEMA_VERSION,
+            });
+        }
+
+        let from = self.version;
+        while self.version < Self::SCHEMA_VERSION {
+            event!(Level::DEBUG, "Migrating stall from schema version {}",
+                self.version);
+            match self.version {
+                // Version 0 files have no version field, and may use the list
+                // format. Their entries need no changes.
+                0 => (),
+                version => unreachable!("no migration from stall schema \
+                    version {version}"),
+            }
+            self.version += 1;
+        }
+        if from < Self::SCHEMA_VERSION {
+            self.migrated_from = Some(from);
+        }
+        Ok(())
+    }
 
     /// Parses a `Stall` from a file using a newline-delimited file list
-    /// format.
-    fn parse_list_from_file(file: &mut File) -> Result<Self, Error> {
+    /// format. Each line is a remote path, stalled under its file name, or a
+    /// local path and remote path separated by `->` or `=`. Lines of the form
+    /// `include "path"` include another stall file.
+    fn parse_list_from_reader<R>(reader: R) -> Result<Self, StallError>
+        where R: BufRead
+    {
         let mut stall = Self::new_detached();
-        let buf_reader = BufReader::new(file);
-        for line in buf_reader.lines() {
+        stall.version = 0;
+        stall.format = StallFormat::List;
+        for line in reader.lines() {
             let line = line
-                .with_context(|| "Failed to read stall file")?;
-            
-            // Skip empty lines.
-            let line = line.trim();
-            if line.is_empty() { continue }
-
-            // Skip comment lines.
-            if line.starts_with("//") { continue }
-            if line.starts_with('#') { continue }
+                .with_io_context("Failed to read stall file")?;
 
-            let path: PathBuf = line.into();
-            stall.insert_list_remote(path);
+            match ListLine::parse(&line) {
+                None => (),
+                Some(ListLine::Include(include)) => {
+                    stall.include.push(PathBuf::from(include));
+                },
+                Some(ListLine::Entry { local, remote }) => stall
+                    .insert_list_remote(
+                        local.map(Path::new),
+                        Path::new(remote))?,
+            }
         }
+        // Rebuilding the map fills its nodes, and the remotes are trimmed
+        // after being grown by each insert.
+        stall.entries = std::mem::take(&mut stall.entries)
+            .into_iter()
+            .map(|(local, mut data)| {
+                data.remotes.shrink_to_fit();
+                (local, data)
+            })
+            .collect();
 
         Ok(stall) 
     }
 
     /// Parses a `Stall` from a buffer using the RON format.
-    fn parse_ron_from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+    fn parse_ron_from_bytes(bytes: &[u8]) -> Result<Self, StallError> {
         use ron::de::Deserializer;
-        let mut d = Deserializer::from_bytes(bytes)
-            .context("Failed deserializing RON file")?;
-        let stall = Self::deserialize(&mut d)
-            .context("Failed parsing RON file")?;
-        d.end()
-            .context("Failed parsing RON file")?;
 
-        Ok(stall) 
+        /// The schema version of a stall file, read without parsing its
+        /// other fields, which may not be supported.
+        #[derive(Deserialize)]
+        struct VersionProbe {
+            /// The schema version of the stall file.
+            #[serde(default)]
+            version: u32,
+        }
+        let parse_error = |context, e: ron::Error| {
+            StallError::ron_parse(context, None, &e)
+                .with_hint(ron_hint(&e.code))
+        };
+        let parse = || {
+            let mut d = Deserializer::from_bytes(bytes)
+                .map_err(|e| parse_error("Failed deserializing RON file", e))?;
+            let stall = Self::deserialize(&mut d)
+                .map_err(|e| parse_error("Failed parsing RON file", e))?;
+            d.end()
+                .map_err(|e| parse_error("Failed parsing RON file", e))?;
+            Ok(stall)
+        };
+
+        // Large files are expensive to parse, so the version is only probed
+        // if the file fails to parse, in case it has fields from a newer
+        // schema. The version of a parsed file is checked when it is
+        // migrated.
+        parse().map_err(|e| match ron::de::from_bytes::<VersionProbe>(bytes) {
+            Ok(probe) if probe.version > Self::SCHEMA_VERSION => {
+                StallError::UnsupportedVersion {
+                    path: None,
+                    version: probe.version,
+                    supported: Self::SCHEMA_VERSION,
+                }
+            },
+            _ => e,
+        })
     }
 
     /// Write the `Stall` into the given file.
-    pub fn write_to_file(&self, mut file: File) -> Result<(), Error> {
-        self.generate_ron_into_file(&mut file)
+    /// Entries from included stall files are not written.
+    pub fn write_to_file(&self, mut file: File) -> Result<(), StallError> {
+        if !self.origins.is_empty() {
+            return self.without_included().write_to_file(file);
+        }
+        match self.write_format() {
+            StallFormat::Ron  => self.generate_ron_into_file(&mut file),
+            StallFormat::List => self.generate_list_into_file(&mut file),
+        }
+    }
+
+    /// Writes the `Stall` into a file using the newline-delimited file list
+    /// format. Remote paths are written alone if they are stalled under their
+    /// file name, and as `local -> remote` otherwise.
+    fn generate_list_into_file(&self, file: &mut File)
+        -> Result<(), StallError>
+    {
+        tracing::debug!("Serializing & writing Stall list file.");
+        let mut writer = BufWriter::new(file);
+        for include in &self.include {
+            writeln!(writer, "include \"{}\"", include.display())
+                .with_io_context("Failed to write list file")?;
+        }
+        for (local, data) in &self.entries {
+            for remote in &data.remotes {
+                if remote.file_name() == Some(local.as_os_str()) {
+                    writeln!(writer, "{}", remote.display())
+                } else {
+                    writeln!(writer, "{} -> {}",
+                        local.display(),
+                        remote.display())
+                }.with_io_context("Failed to write list file")?;
+            }
+        }
+        writer.flush()
+            .with_io_context("Failed to flush file buffer")
     }
 
     /// Parses a `Stall` from a file using the RON format.
-    fn generate_ron_into_file(&self, file: &mut File) -> Result<(), Error> {
+    fn generate_ron_into_file(&self, file: &mut File) -> Result<(), StallError> {
         tracing::debug!("Serializing & writing Stall file.");
         let pretty = ron::ser::PrettyConfig::new()
             .depth_limit(2)
@@ -352,11 +1698,477 @@ impl Stall {
             .enumerate_arrays(true)
             .extensions(ron::extensions::Extensions::IMPLICIT_SOME);
         let s = ron::ser::to_string_pretty(&self, pretty)
-            .context("Failed to serialize RON file")?;
+            .map_err(|e| StallError::ron_serialize("Failed to serialize RON file", &e))?;
         let mut writer = BufWriter::new(file);
         writer.write_all(s.as_bytes())
-            .context("Failed to write RON file")?;
+            .with_io_context("Failed to write RON file")?;
         writer.flush()
-            .context("Failed to flush file buffer")
+            .with_io_context("Failed to flush file buffer")
+    }
+}
+
+
+
+////////////////////////////////////////////////////////////////////////////////
+// EntryData
+////////////////////////////////////////////////////////////////////////////////
+/// The remote paths, flags, transforms, and dependencies of a stall entry. An
+/// entry with a single remote path and no flags, transforms, or dependencies is
+/// serialized as a plain path, and an entry with only remote paths is
+/// serialized as a list of paths, for compatibility with older stall files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct EntryData {
+    /// The remote paths of the entry.
+    remotes: Vec<Arc<Path>>,
+    /// The entry flags.
+    flags: EntryFlags,
+    /// The transforms applied to the entry's files when they are copied.
+    transforms: Vec<Transform>,
+    /// The local paths of the entries which are distributed before this one.
+    after: Vec<Arc<Path>>,
+}
+
+impl EntryData {
+    /// Returns the [`Entry`] view for the given local path and one of the
+    /// entry's remote paths.
+    fn entry<'a>(&'a self, local: &'a Path, remote: &'a Path) -> Entry<'a> {
+        Entry {
+            local,
+            remote,
+            flags: self.flags,
+            transforms: &self.transforms,
+        }
+    }
+}
+
+impl Serialize for EntryData {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        /// The serialized form of `EntryData` with flags.
+        #[derive(Serialize)]
+        #[allow(clippy::struct_excessive_bools)]
+        struct Flagged<'a> {
+            /// The remote paths of the entry.
+            remotes: &'a [Arc<Path>],
+            /// Whether the entry is pinned.
+            #[serde(skip_serializing_if = "std::ops::Not::not")]
+            pinned: bool,
+            /// Whether the entry is readonly.
+            #[serde(skip_serializing_if = "std::ops::Not::not")]
+            readonly: bool,
+            /// Whether the entry ignores git's ignored files.
+            #[serde(skip_serializing_if = "std::ops::Not::not")]
+            no_gitignore: bool,
+            /// The entry's conflict policy.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            conflict: Option<ConflictPolicy>,
+            /// Whether the entry's files are binary.
+            #[serde(skip_serializing_if = "std::ops::Not::not")]
+            binary: bool,
+            /// The line ending of the entry's remote files.
+            #[serde(skip_serializing_if = "LineEnding::is_preserve")]
+            eol: LineEnding,
+            /// Whether the entry's files are distributed with privileges.
+            #[serde(skip_serializing_if = "std::ops::Not::not")]
+            privileged: bool,
+            /// Whether the entry is archived.
+            #[serde(skip_serializing_if = "std::ops::Not::not")]
+            archived: bool,
+            /// The entry's transforms.
+            #[serde(skip_serializing_if = "<[Transform]>::is_empty")]
+            transforms: &'a [Transform],
+            /// The local paths of the entry's dependencies.
+            #[serde(skip_serializing_if = "<[Arc<Path>]>::is_empty")]
+            after: &'a [Arc<Path>],
+        }
+
+        if self.flags != EntryFlags::default()
+            || !self.transforms.is_empty()
+            || !self.after.is_empty()
+        {
+            return Flagged {
+                remotes: &self.remotes,
+                pinned: self.flags.pinned,
+                readonly: self.flags.readonly,
+                no_gitignore: self.flags.no_gitignore,
+                conflict: self.flags.conflict,
+                binary: self.flags.binary,
+                eol: self.flags.eol,
+                privileged: self.flags.privileged,
+                archived: self.flags.archived,
+                transforms: &self.transforms,
+                after: &self.after,
+            }.serialize(serializer);
+        }
+
+        match &self.remotes[..] {
+            [remote] => remote.serialize(serializer),
+            remotes  => remotes.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for EntryData {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        /// The serialized forms of `EntryData`.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum EntryDataRepr {
+            /// A single remote path.
+            One(PathBuf),
+            /// Multiple remote paths.
+            Many(Vec<PathBuf>),
+            /// Remote paths with flags.
+            Flagged {
+                /// The remote paths of the entry.
+                remotes: Vec<PathBuf>,
+                /// Whether the entry is pinned.
+                #[serde(default)]
+                pinned: bool,
+                /// Whether the entry is readonly.
+                #[serde(default)]
+                readonly: bool,
+                /// Whether the entry ignores git's ignored files.
+                #[serde(default)]
+                no_gitignore: bool,
+                /// The entry's conflict policy.
+                #[serde(default)]
+                conflict: Option<ConflictPolicy>,
+                /// Whether the entry's files are binary.
+                #[serde(default)]
+                binary: bool,
+                /// The line ending of the entry's remote files.
+                #[serde(default)]
+                eol: LineEnding,
+                /// Whether the entry's files are distributed with privileges.
+                #[serde(default)]
+                privileged: bool,
+                /// Whether the entry is archived.
+                #[serde(default)]
+                archived: bool,
+                /// The entry's transforms.
+                #[serde(default)]
+                transforms: Vec<Transform>,
+                /// The local paths of the entry's dependencies.
+                #[serde(default)]
+                after: Vec<Arc<Path>>,
+            },
+        }
+
+        let repr = EntryDataRepr::deserialize(deserializer)?;
+        let (remotes, flags, transforms, after) = match repr {
+            EntryDataRepr::One(remote)   => {
+                (vec![remote], EntryFlags::default(), Vec::new(), Vec::new())
+            },
+            EntryDataRepr::Many(remotes) => {
+                (remotes, EntryFlags::default(), Vec::new(), Vec::new())
+            },
+            EntryDataRepr::Flagged {
+                remotes,
+                pinned,
+                readonly,
+                no_gitignore,
+                conflict,
+                binary,
+                eol,
+                privileged,
+                archived,
+                transforms,
+                after,
+            } => (remotes, EntryFlags {
+                pinned,
+                readonly,
+                no_gitignore,
+                conflict,
+                binary,
+                eol,
+                privileged,
+                archived,
+            }, transforms, after),
+        };
+        if remotes.is_empty() {
+            return Err(serde::de::Error::invalid_length(0,
+                &"at least one remote"));
+        }
+        // Stall files may be shared between platforms, so drive letters and
+        // separators are normalized for the current one.
+        let remotes = remotes
+            .iter()
+            .map(|remote| Arc::from(path::clean(remote)))
+            .collect();
+        Ok(Self { remotes, flags, transforms, after })
+    }
+}
+
+/// Deserializes the entries of a [`Stall`]. The entries are collected before
+/// the map is built, so that its nodes are filled instead of being split as
+/// each entry is inserted, which reduces the memory used by large stalls.
+/// Entries with the same local path are rejected.
+///
+/// [`Stall`]: Stall
+fn deserialize_entries<'de, D>(deserializer: D)
+    -> Result<BTreeMap<Arc<Path>, EntryData>, D::Error>
+    where D: Deserializer<'de>
+{
+    /// A visitor which collects the entries of a map, sorted by local path.
+    struct EntriesVisitor;
+
+    impl<'de> Visitor<'de> for EntriesVisitor {
+        type Value = BTreeMap<Arc<Path>, EntryData>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter<'_>)
+            -> std::fmt::Result
+        {
+            write!(f, "a map of local paths to remote paths")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where A: MapAccess<'de>
+        {
+            let mut entries = Vec::with_capacity(map
+                .size_hint()
+                .unwrap_or_default());
+            while let Some(entry) = map.next_entry::<Arc<Path>, _>()? {
+                entries.push(entry);
+            }
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            if let Some(pair) = entries.windows(2).find(|p| p[0].0 == p[1].0) {
+                return Err(serde::de::Error::custom(format_args!(
+                    "duplicate stall entry {}",
+                    pair[0].0.display())));
+            }
+            Ok(entries.into_iter().collect())
+        }
+    }
+
+    deserializer.deserialize_map(EntriesVisitor)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// InsertError
+////////////////////////////////////////////////////////////////////////////////
+/// An error indicating a failure to insert an entry into a [`Stall`].
+///
+/// [`Stall`]: Stall
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsertError {
+    /// The local path does not have a valid file name.
+    InvalidLocal(PathBuf),
+    /// The remote path does not have a valid file name.
+    InvalidRemote(PathBuf),
+    /// The remote path is already stalled with a different local path.
+    RemoteInUse {
+        /// The local path of the entry containing the remote path.
+        local: PathBuf,
+        /// The remote path.
+        remote: PathBuf,
+    },
+}
+
+impl InsertError {
+    /// Checks that the given local and remote paths have valid file names.
+    fn check(local: &Path, remote: &Path) -> Result<(), Self> {
+        if local.file_name().is_none() {
+            return Err(Self::InvalidLocal(local.to_path_buf()));
+        }
+        if remote.file_name().is_none() {
+            return Err(Self::InvalidRemote(remote.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    /// Returns the invalid path.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::InvalidLocal(path) |
+            Self::InvalidRemote(path) |
+            Self::RemoteInUse { remote: path, .. } => path,
+        }
+    }
+}
+
+impl std::error::Error for InsertError {}
+
+impl std::fmt::Display for InsertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLocal(path) => write!(f,
+                "invalid stall path {}: no file name", path.display()),
+            Self::InvalidRemote(path) => write!(f,
+                "invalid remote path {}: no file name", path.display()),
+            Self::RemoteInUse { local, remote } => write!(f,
+                "remote path {} is already stalled as {}",
+                remote.display(),
+                local.display()),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Entry selection
+////////////////////////////////////////////////////////////////////////////////
+/// Parses a 1-based entry index such as `3`, or an inclusive range of entry
+/// indices such as `7-9`, returning the first and last indices.
+fn parse_index_range(text: &str) -> Option<(usize, usize)> {
+    let (first, last) = text.split_once('-').unwrap_or((text, text));
+    let first = first.parse::<usize>().ok()?;
+    let last = last.parse::<usize>().ok()?;
+    (first > 0 && first <= last).then_some((first, last))
+}
+
+/// Returns `true` if the characters of `needle` appear in `haystack` in
+/// order.
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|c| chars.any(|h| h == c))
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StallFormat
+////////////////////////////////////////////////////////////////////////////////
+/// The format of a stall file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(clap::ArgEnum)]
+pub enum StallFormat {
+    /// The RON format written by stall.
+    #[default]
+    Ron,
+    /// A list of remote paths, one per line.
+    List,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ListLine
+////////////////////////////////////////////////////////////////////////////////
+/// A line of a stall file in the list format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListLine<'a> {
+    /// An `include "path"` line.
+    Include(&'a str),
+    /// A remote path, with an optional local path.
+    Entry {
+        /// The local path, if given.
+        local: Option<&'a str>,
+        /// The remote path.
+        remote: &'a str,
+    },
+}
+
+impl<'a> ListLine<'a> {
+    /// Parses a line of a stall file in the list format, returning `None` for
+    /// empty lines and comment lines.
+    fn parse(line: &'a str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() { return None; }
+        if line.starts_with("//") || line.starts_with('#') { return None; }
+
+        if let Some(include) = line.strip_prefix("include ") {
+            let include = include.trim();
+            let include = include
+                .strip_prefix('"')
+                .and_then(|i| i.strip_suffix('"'))
+                .unwrap_or(include);
+            return Some(Self::Include(include));
+        }
+
+        Some(match line.split_once(" -> ").or_else(|| line.split_once(" = ")) {
+            Some((local, remote)) => Self::Entry {
+                local: Some(local.trim_end()),
+                remote: remote.trim_start(),
+            },
+            None => Self::Entry { local: None, remote: line },
+        })
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// read_remote_list
+////////////////////////////////////////////////////////////////////////////////
+/// Reads a list of remote paths to add to a stall from the given file.
+///
+/// The file uses the syntax of the list stall file format: each line is a
+/// remote path, or a local path and remote path separated by `->` or `=`.
+/// Empty lines and comment lines are skipped. The remote paths are returned
+/// in the order they are listed, along with their local paths, if given.
+///
+/// ### Errors
+///
+/// Returns a [`StallError`] if the file can't be read, or if it contains an
+/// `include` line.
+///
+/// [`StallError`]: error/enum.StallError.html
+pub fn read_remote_list(path: &Path)
+    -> Result<Vec<(Option<PathBuf>, PathBuf)>, StallError>
+{
+    let file = File::open(path)
+        .with_path("Failed to open remote list for reading", path)?;
+    let mut remotes = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line
+            .with_path("Failed to read remote list", path)?;
+        match ListLine::parse(&line) {
+            None => (),
+            Some(ListLine::Include(_)) => return Err(StallError::InvalidInput {
+                message: format!("include lines are not supported in remote \
+                    lists: {}", path.display()),
+            }),
+            Some(ListLine::Entry { local, remote }) => remotes.push((
+                local.map(PathBuf::from),
+                PathBuf::from(remote))),
+        }
+    }
+    Ok(remotes)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Format detection
+////////////////////////////////////////////////////////////////////////////////
+/// Returns `true` if the stall file contents are clearly meant to be in the
+/// RON format, so RON errors should not fall back to the list format.
+fn is_clearly_ron(bytes: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(bytes);
+    let start = text.trim_start();
+    start.starts_with('(')
+        || start.starts_with("#![")
+        || text.contains("entries:")
+}
+
+/// Returns `true` if the path can be written on a line of a list format stall
+/// file and read back unchanged.
+fn is_list_path(path: &Path) -> bool {
+    path.to_str().is_some_and(|text| !text.is_empty()
+        && text.trim() == text
+        && !text.starts_with('#')
+        && !text.starts_with("//")
+        && !text.contains(" -> ")
+        && !text.contains(" = ")
+        && !text.starts_with("include "))
+}
+
+/// Returns a suggestion for fixing the given RON parse error.
+fn ron_hint(code: &ron::error::ErrorCode) -> &'static str {
+    use ron::error::ErrorCode::*;
+    match code {
+        ExpectedComma => "Check for a missing comma after the previous \
+            value.",
+        ExpectedStringEnd => "Check for a missing closing quote.",
+        ExpectedArrayEnd | ExpectedMapEnd | ExpectedStructEnd => "Check for \
+            a missing comma after the previous value, or a missing closing \
+            bracket.",
+        Eof => "Check for a missing closing bracket or parenthesis.",
+        ExpectedMapColon => "Map entries are written as `key: value`.",
+        _ => "Stall files are written as RON, e.g. `(entries: \
+            {\"local\": \"/remote/path\"})`, or as a list of remote paths, \
+            one per line.",
     }
 }
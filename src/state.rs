@@ -0,0 +1,312 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Cached state of a stall.
+//!
+//! The state file records information which is expensive to recompute, such
+//! as the hashes of stalled and remote files, along with when each entry was
+//! last copied. Unlike the stall file, it is never edited by hand, and it can
+//! be deleted at any time without losing anything important.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::application::migrate_stall_file;
+use crate::application::stall_cache_dir;
+use crate::atomic;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::manifest::hash_file;
+use crate::manifest::HashAlgorithm;
+use crate::platform;
+use crate::remote;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// StateFile
+////////////////////////////////////////////////////////////////////////////////
+/// The cached state of a stall.
+///
+/// File hashes are keyed by the full path, modification time, and size of the
+/// hashed file, so a cached hash is discarded as soon as the file changes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct StateFile {
+	/// The algorithm used to hash the files.
+	#[serde(default)]
+	algorithm: HashAlgorithm,
+	/// The cached hashes of files, by full path.
+	#[serde(default)]
+	hashes: BTreeMap<PathBuf, CachedHash>,
+	/// The times at which entries were last copied, by local path.
+	#[serde(default)]
+	entries: BTreeMap<PathBuf, EntryTimes>,
+	/// The device and inode numbers of remote files when they were last
+	/// copied, by full path.
+	#[serde(default)]
+	file_ids: BTreeMap<PathBuf, (u64, u64)>,
+	/// Whether the state has changed since it was loaded.
+	#[serde(skip)]
+	changed: bool,
+}
+
+impl StateFile {
+	/// The state file name within the stall's cache directory.
+	pub const STATE_FILE_NAME: &'static str = "state";
+
+	/// The state file path used before the cache directory, relative to the
+	/// stall directory.
+	pub const LEGACY_STATE_PATH: &'static str = ".stall-state";
+
+	/// Returns the path of the state file of the stall in the given
+	/// directory. The state file is kept in the stall's directory within the
+	/// cache directory, or in the stall directory if the cache directory
+	/// can't be determined.
+	#[must_use]
+	pub fn path(stall_dir: &Path) -> PathBuf {
+		stall_cache_dir(stall_dir).map_or_else(
+			|| stall_dir.join(Self::LEGACY_STATE_PATH),
+			|dir| dir.join(Self::STATE_FILE_NAME))
+	}
+
+	/// Reads the state file of the stall in the given directory. A state file
+	/// in the stall directory is read if it hasn't been moved to the cache
+	/// directory yet. If the file doesn't exist or can't be parsed, the state
+	/// is empty.
+	#[must_use]
+	pub fn read_from_stall_dir(stall_dir: &Path) -> Self {
+		let path = Some(Self::path(stall_dir))
+			.filter(|path| path.exists())
+			.unwrap_or_else(|| stall_dir.join(Self::LEGACY_STATE_PATH));
+		let text = match std::fs::read_to_string(&path) {
+			Ok(text) => text,
+			Err(e) => {
+				event!(Level::DEBUG, "Unable to read state file {:?}: {e}",
+					path);
+				return Self::default();
+			},
+		};
+		match ron::from_str::<Self>(&text) {
+			Ok(state) if state.algorithm == HashAlgorithm::default() => state,
+			Ok(mut state) => {
+				event!(Level::DEBUG, "Discarding hashes in state file {:?}",
+					path);
+				state.clear_hashes();
+				state
+			},
+			Err(e) => {
+				event!(Level::DEBUG, "Discarding invalid state file {:?}: {e}",
+					path);
+				Self::default()
+			},
+		}
+	}
+
+	/// Writes the state file of the stall in the given directory if the state
+	/// has changed since it was read. Cached hashes of files which have since
+	/// changed or been removed are discarded. A state file in the stall
+	/// directory is moved to the cache directory first.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the state file cannot be written.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn write_to_stall_dir(&mut self, stall_dir: &Path)
+		-> Result<(), StallError>
+	{
+		let count = self.hashes.len();
+		self.hashes.retain(|path, cached| std::fs::metadata(path)
+			.is_ok_and(|metadata| cached.matches(&metadata)));
+		if !self.changed && self.hashes.len() == count { return Ok(()); }
+
+		let path = Self::path(stall_dir);
+		migrate_stall_file(&path, &stall_dir.join(Self::LEGACY_STATE_PATH))?;
+		let pretty = ron::ser::PrettyConfig::new();
+		let text = ron::ser::to_string_pretty(self, pretty)
+			.map_err(|e| StallError::ron_serialize(
+				"failed to serialize state file",
+				&e))?;
+		atomic::replace_with(&path, false, |temp| std::fs::write(temp, text)
+			.with_path("failed to write state file", temp))?;
+		self.changed = false;
+		Ok(())
+	}
+
+	/// Discards all cached hashes.
+	pub fn clear_hashes(&mut self) {
+		self.algorithm = HashAlgorithm::default();
+		self.hashes.clear();
+		self.changed = true;
+	}
+
+	/// Returns the time at which the entry with the given local path was last
+	/// collected into the stall, if it is known.
+	#[must_use]
+	pub fn collected(&self, local: &Path) -> Option<SystemTime> {
+		self.entries.get(local).and_then(|times| times.collected)
+	}
+
+	/// Returns the time at which the entry with the given local path was last
+	/// distributed from the stall, if it is known.
+	#[must_use]
+	pub fn distributed(&self, local: &Path) -> Option<SystemTime> {
+		self.entries.get(local).and_then(|times| times.distributed)
+	}
+
+	/// Returns the time at which the entry with the given local path was last
+	/// collected or distributed, if it is known.
+	#[must_use]
+	pub fn copied(&self, local: &Path) -> Option<SystemTime> {
+		self.collected(local).max(self.distributed(local))
+	}
+
+	/// Records that the entry with the given local path was collected now.
+	pub fn record_collected(&mut self, local: &Path) {
+		self.entries
+			.entry(local.to_path_buf())
+			.or_default()
+			.collected = Some(SystemTime::now());
+		self.changed = true;
+	}
+
+	/// Records that the entry with the given local path was distributed now.
+	pub fn record_distributed(&mut self, local: &Path) {
+		self.entries
+			.entry(local.to_path_buf())
+			.or_default()
+			.distributed = Some(SystemTime::now());
+		self.changed = true;
+	}
+
+	/// Returns the device and inode numbers which the remote file at the given
+	/// path had when it was last copied, if they are known.
+	#[must_use]
+	pub fn file_id(&self, remote: &Path) -> Option<(u64, u64)> {
+		self.file_ids.get(remote).copied()
+	}
+
+	/// Records the current device and inode numbers of the remote file at the
+	/// given path, so the file can be found by [`file_id`] if it is moved.
+	/// Remote files on other hosts are ignored.
+	///
+	/// [`file_id`]: #method.file_id
+	pub fn record_file_id(&mut self, remote: &Path) {
+		if remote::scheme(remote).is_some() { return; }
+		let Some(id) = platform::file_id(remote) else { return };
+		if self.file_ids.insert(remote.to_path_buf(), id) != Some(id) {
+			self.changed = true;
+		}
+	}
+
+	/// Returns the hex-encoded hash of the file at the given path, using the
+	/// cached hash if the file is unchanged.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the file cannot be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn hash(&mut self, path: &Path) -> Result<String, StallError> {
+		let metadata = std::fs::metadata(path)
+			.with_path("failed to read metadata for", path)?;
+		if let Some(cached) = self.hashes.get(path) {
+			if cached.matches(&metadata) {
+				event!(Level::TRACE, "Using cached hash for {:?}", path);
+				return Ok(cached.hash.clone());
+			}
+		}
+
+		event!(Level::DEBUG, "Hashing {:?}", path);
+		let hash = hash_file(path, self.algorithm)?;
+		if let Ok(modified) = metadata.modified() {
+			let _ = self.hashes.insert(path.to_path_buf(), CachedHash {
+				modified,
+				size: metadata.len(),
+				hash: hash.clone(),
+			});
+			self.changed = true;
+		}
+		Ok(hash)
+	}
+
+	/// Returns `true` if the regular files at the given paths have the same
+	/// contents. Files of different sizes are never hashed.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if either file cannot be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn same_contents(&mut self, a: &Path, b: &Path)
+		-> Result<bool, StallError>
+	{
+		let metadata_a = std::fs::metadata(a)
+			.with_path("failed to read metadata for", a)?;
+		let metadata_b = std::fs::metadata(b)
+			.with_path("failed to read metadata for", b)?;
+		if !metadata_a.is_file()
+			|| !metadata_b.is_file()
+			|| metadata_a.len() != metadata_b.len()
+		{
+			return Ok(false);
+		}
+		Ok(self.hash(a)? == self.hash(b)?)
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// CachedHash
+////////////////////////////////////////////////////////////////////////////////
+/// The cached hash of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+struct CachedHash {
+	/// The modification time of the file when it was hashed.
+	modified: SystemTime,
+	/// The size of the file when it was hashed.
+	size: u64,
+	/// The hex-encoded hash of the file.
+	hash: String,
+}
+
+impl CachedHash {
+	/// Returns `true` if the cached hash is valid for a file with the given
+	/// metadata.
+	fn matches(&self, metadata: &Metadata) -> bool {
+		metadata.len() == self.size
+			&& metadata.modified().is_ok_and(|m| m == self.modified)
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// EntryTimes
+////////////////////////////////////////////////////////////////////////////////
+/// The times at which an entry was last copied.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+struct EntryTimes {
+	/// The time the entry was last collected.
+	#[serde(default)]
+	collected: Option<SystemTime>,
+	/// The time the entry was last distributed.
+	#[serde(default)]
+	distributed: Option<SystemTime>,
+}
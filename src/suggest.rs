@@ -0,0 +1,94 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! "Did you mean" suggestions for unrecognized names and values.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::StallError;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The maximum number of suggestions to make.
+const MAX_SUGGESTIONS: usize = 3;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Suggestions
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the candidates which are similar to the given name, most similar
+/// first.
+///
+/// Candidates are similar if they are within an edit distance of a third of
+/// the name's length, and at most three candidates are returned.
+#[must_use]
+pub fn similar<'a, I>(name: &str, candidates: I) -> Vec<String>
+	where I: IntoIterator<Item=&'a str>
+{
+	let limit = (name.chars().count() / 3).max(1);
+	let mut scored: Vec<(usize, &str)> = candidates
+		.into_iter()
+		.map(|candidate| (edit_distance(name, candidate), candidate))
+		.filter(|(distance, _)| *distance <= limit)
+		.collect();
+	scored.sort_unstable();
+	scored.dedup();
+	scored
+		.into_iter()
+		.take(MAX_SUGGESTIONS)
+		.map(|(_, candidate)| candidate.to_owned())
+		.collect()
+}
+
+/// Returns a hint suggesting the given candidates, or `None` if there are no
+/// candidates.
+#[must_use]
+pub fn hint(suggestions: &[String]) -> Option<String> {
+	let quoted: Vec<String> = suggestions
+		.iter()
+		.map(|s| format!("'{s}'"))
+		.collect();
+	match quoted.as_slice() {
+		[]         => None,
+		[one]      => Some(format!("Did you mean {one}?")),
+		[rest @ .., last] => Some(format!("Did you mean {} or {last}?",
+			rest.join(", "))),
+	}
+}
+
+/// Returns an error for a value which is not one of the given choices for
+/// the named option, suggesting any similar choices.
+#[must_use]
+pub fn invalid_choice(key: &str, value: &str, choices: &[&str]) -> StallError {
+	let mut message = format!("invalid value for {key}: {value:?} \
+		(expected one of {})", choices.join(", "));
+	if let Some(hint) = hint(&similar(value, choices.iter().copied())) {
+		message.push('\n');
+		message.push_str(&hint);
+	}
+	StallError::InvalidInput { message }
+}
+
+/// Returns the number of single character insertions, deletions, and
+/// substitutions needed to change `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut diagonal = row[0];
+		row[0] = i + 1;
+		for (j, cb) in b.iter().enumerate() {
+			let substitution = diagonal + usize::from(ca != *cb);
+			diagonal = row[j + 1];
+			row[j + 1] = substitution
+				.min(row[j] + 1)
+				.min(diagonal + 1);
+		}
+	}
+	row[b.len()]
+}
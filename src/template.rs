@@ -0,0 +1,523 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Templates for generating stall paths and formatting entries.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::entry::Entry;
+use crate::platform;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest as _;
+use sha2::Sha256;
+
+// Standard library imports.
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// NameTemplate
+////////////////////////////////////////////////////////////////////////////////
+/// A template for generating file names from existing paths.
+///
+/// Templates are made of literal text and tokens enclosed in braces. The
+/// supported tokens are:
+///
+/// + `{name}`: The file name.
+/// + `{stem}`: The file name without its extension.
+/// + `{ext}`: The file extension, including the leading `.`, or nothing if the
+///   file has no extension.
+/// + `{parent}`: The name of the parent directory, or nothing if there is no
+///   parent directory.
+/// + `{n}`: The 1-based index of the file within a batch.
+///
+/// Literal braces can be written as `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+	/// The parsed template segments.
+	segments: Vec<Segment>,
+}
+
+impl NameTemplate {
+	/// Returns `true` if the template contains no tokens, meaning every path
+	/// will be rendered to the same name.
+	#[must_use]
+	pub fn is_constant(&self) -> bool {
+		self.segments.iter().all(|s| matches!(s, Segment::Literal(_)))
+	}
+
+	/// Renders the template for the given path and batch index.
+	#[must_use]
+	pub fn render(&self, path: &Path, n: usize) -> PathBuf {
+		let mut rendered = String::new();
+		for segment in &self.segments {
+			match segment {
+				Segment::Literal(text) => rendered.push_str(text),
+				Segment::Name => if let Some(name) = path.file_name() {
+					rendered.push_str(&name.to_string_lossy());
+				},
+				Segment::Stem => if let Some(stem) = path.file_stem() {
+					rendered.push_str(&stem.to_string_lossy());
+				},
+				Segment::Ext => if let Some(ext) = path.extension() {
+					rendered.push('.');
+					rendered.push_str(&ext.to_string_lossy());
+				},
+				Segment::Parent => if let Some(parent) = path
+					.parent()
+					.and_then(Path::file_name)
+				{
+					rendered.push_str(&parent.to_string_lossy());
+				},
+				Segment::Index => rendered.push_str(&n.to_string()),
+			}
+		}
+		rendered.into()
+	}
+}
+
+impl std::str::FromStr for NameTemplate {
+	type Err = NameTemplateParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut segments = Vec::new();
+		let mut literal = String::new();
+		let mut chars = s.chars().peekable();
+
+		while let Some(c) = chars.next() {
+			match c {
+				'{' if chars.peek() == Some(&'{') => {
+					let _ = chars.next();
+					literal.push('{');
+				},
+				'}' if chars.peek() == Some(&'}') => {
+					let _ = chars.next();
+					literal.push('}');
+				},
+				'{' => {
+					let mut token = String::new();
+					loop {
+						match chars.next() {
+							Some('}') => break,
+							Some(c)   => token.push(c),
+							None      => return Err(NameTemplateParseError(
+								format!("unclosed token '{{{token}'"))),
+						}
+					}
+					let segment = match token.as_str() {
+						"name"   => Segment::Name,
+						"stem"   => Segment::Stem,
+						"ext"    => Segment::Ext,
+						"parent" => Segment::Parent,
+						"n"      => Segment::Index,
+						_ => return Err(NameTemplateParseError(
+							format!("unrecognized token '{{{token}}}'"))),
+					};
+					if !literal.is_empty() {
+						segments.push(Segment::Literal(
+							std::mem::take(&mut literal)));
+					}
+					segments.push(segment);
+				},
+				'}' => return Err(NameTemplateParseError(
+					"unmatched '}'".to_owned())),
+				_ => literal.push(c),
+			}
+		}
+		if !literal.is_empty() {
+			segments.push(Segment::Literal(literal));
+		}
+
+		Ok(Self { segments })
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Segment
+////////////////////////////////////////////////////////////////////////////////
+/// A parsed section of a `NameTemplate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+	/// Literal text.
+	Literal(String),
+	/// The `{name}` token.
+	Name,
+	/// The `{stem}` token.
+	Stem,
+	/// The `{ext}` token.
+	Ext,
+	/// The `{parent}` token.
+	Parent,
+	/// The `{n}` token.
+	Index,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FormatTemplate
+////////////////////////////////////////////////////////////////////////////////
+/// A template for formatting stall entries as text.
+///
+/// Templates are made of literal text and tokens enclosed in braces. The
+/// supported tokens are:
+///
+/// + `{local}`: The entry's path within the stall.
+/// + `{remote}`: The entry's remote path.
+/// + `{path}`: The full path of the entry's stalled file.
+/// + `{name}`: The file name of the entry's path within the stall.
+/// + `{index}`: The 1-based index of the entry, as printed by the `status`
+///   command.
+/// + `{flags}`: The entry's flags, as printed by the `flag` command.
+///
+/// Literal braces can be written as `{{` and `}}`, and the escapes `\t`, `\n`,
+/// and `\\` are replaced with a tab, a newline, and a backslash, so that
+/// templates can be given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatTemplate {
+	/// The parsed template segments.
+	segments: Vec<FormatSegment>,
+}
+
+impl FormatTemplate {
+	/// The template used when none is given.
+	pub const DEFAULT: &'static str = "{local}\\t{remote}";
+
+	/// Renders the template for the given entry of a stall in `stall_dir`,
+	/// with the given 1-based index.
+	#[must_use]
+	pub fn render(&self, stall_dir: &Path, entry: &Entry<'_>, index: usize)
+		-> String
+	{
+		let mut rendered = String::new();
+		for segment in &self.segments {
+			match segment {
+				FormatSegment::Literal(text) => rendered.push_str(text),
+				FormatSegment::Local => rendered
+					.push_str(&entry.local.to_string_lossy()),
+				FormatSegment::Remote => rendered
+					.push_str(&entry.remote.to_string_lossy()),
+				FormatSegment::Path => rendered
+					.push_str(&stall_dir.join(entry.local).to_string_lossy()),
+				FormatSegment::Name => if let Some(name) = entry.local
+					.file_name()
+				{
+					rendered.push_str(&name.to_string_lossy());
+				},
+				FormatSegment::Index => rendered.push_str(&index.to_string()),
+				FormatSegment::Flags => rendered
+					.push_str(&entry.flags.description()),
+			}
+		}
+		rendered
+	}
+}
+
+impl Default for FormatTemplate {
+	fn default() -> Self {
+		Self::DEFAULT.parse().expect("parse default format template")
+	}
+}
+
+impl std::str::FromStr for FormatTemplate {
+	type Err = FormatTemplateParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut segments = Vec::new();
+		let mut literal = String::new();
+		let mut chars = s.chars().peekable();
+
+		while let Some(c) = chars.next() {
+			match c {
+				'\\' => match chars.next() {
+					Some('t')  => literal.push('\t'),
+					Some('n')  => literal.push('\n'),
+					Some('\\') => literal.push('\\'),
+					Some(c)    => return Err(FormatTemplateParseError(
+						format!("unrecognized escape '\\{c}'"))),
+					None       => return Err(FormatTemplateParseError(
+						"trailing '\\'".to_owned())),
+				},
+				'{' if chars.peek() == Some(&'{') => {
+					let _ = chars.next();
+					literal.push('{');
+				},
+				'}' if chars.peek() == Some(&'}') => {
+					let _ = chars.next();
+					literal.push('}');
+				},
+				'{' => {
+					let mut token = String::new();
+					loop {
+						match chars.next() {
+							Some('}') => break,
+							Some(c)   => token.push(c),
+							None      => return Err(FormatTemplateParseError(
+								format!("unclosed token '{{{token}'"))),
+						}
+					}
+					let segment = match token.as_str() {
+						"local"  => FormatSegment::Local,
+						"remote" => FormatSegment::Remote,
+						"path"   => FormatSegment::Path,
+						"name"   => FormatSegment::Name,
+						"index"  => FormatSegment::Index,
+						"flags"  => FormatSegment::Flags,
+						_ => return Err(FormatTemplateParseError(
+							format!("unrecognized token '{{{token}}}'"))),
+					};
+					if !literal.is_empty() {
+						segments.push(FormatSegment::Literal(
+							std::mem::take(&mut literal)));
+					}
+					segments.push(segment);
+				},
+				'}' => return Err(FormatTemplateParseError(
+					"unmatched '}'".to_owned())),
+				_ => literal.push(c),
+			}
+		}
+		if !literal.is_empty() {
+			segments.push(FormatSegment::Literal(literal));
+		}
+
+		Ok(Self { segments })
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FormatSegment
+////////////////////////////////////////////////////////////////////////////////
+/// A parsed section of a `FormatTemplate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FormatSegment {
+	/// Literal text.
+	Literal(String),
+	/// The `{local}` token.
+	Local,
+	/// The `{remote}` token.
+	Remote,
+	/// The `{path}` token.
+	Path,
+	/// The `{name}` token.
+	Name,
+	/// The `{index}` token.
+	Index,
+	/// The `{flags}` token.
+	Flags,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// RenamePolicy
+////////////////////////////////////////////////////////////////////////////////
+/// The policy for deriving the stall path of an added file from its remote
+/// path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[derive(clap::ArgEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RenamePolicy {
+	/// Use the file name of the remote path.
+	#[default]
+	KeepName,
+	/// Use the file name of the remote path without any leading `.`, so
+	/// `.bashrc` is stalled as `bashrc`.
+	FlattenDotfiles,
+	/// Prefix the file name with the name of its parent directory, without any
+	/// leading `.`, so `~/.config/nvim/init.vim` is stalled as
+	/// `nvim-init.vim`. Files in the home directory are not prefixed.
+	PrefixByApp,
+	/// Use the file name of the remote path, adding a suffix derived from a
+	/// hash of the remote path if the name is already used by another file.
+	HashSuffixOnCollision,
+}
+
+impl RenamePolicy {
+	/// Returns `true` if the policy is the default policy.
+	#[must_use]
+	pub fn is_default(&self) -> bool {
+		*self == Self::default()
+	}
+
+	/// Returns the stall file name for the given remote path, or `None` if
+	/// the path has no file name. The `in_use` function returns whether a
+	/// name is already used by a different file in the stall.
+	#[must_use]
+	pub fn local_name<F>(self, remote: &Path, in_use: F) -> Option<PathBuf>
+		where F: Fn(&Path) -> bool
+	{
+		let name = remote.file_name()?;
+		match self {
+			Self::KeepName => Some(name.into()),
+
+			Self::FlattenDotfiles => Some(strip_dot(name).into()),
+
+			Self::PrefixByApp => {
+				let home = platform::home_dir();
+				let app = remote.parent()
+					.filter(|parent| home.as_deref() != Some(*parent))
+					.and_then(Path::file_name)
+					.map(strip_dot)
+					.filter(|app| !app.is_empty());
+				Some(app.map_or_else(|| name.into(), |mut prefixed| {
+					prefixed.push("-");
+					prefixed.push(name);
+					prefixed.into()
+				}))
+			},
+
+			Self::HashSuffixOnCollision => {
+				let local = PathBuf::from(name);
+				if !in_use(&local) { return Some(local); }
+
+				let digest = Sha256::digest(
+					remote.to_string_lossy().as_bytes());
+				let hex = digest[..4]
+					.iter()
+					.fold(String::new(), |mut hex, b| {
+						let _ = write!(hex, "{b:02x}");
+						hex
+					});
+				Some(with_suffix(&local, &hex))
+			},
+		}
+	}
+}
+
+impl std::fmt::Display for RenamePolicy {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::KeepName              => write!(f, "keep-name"),
+			Self::FlattenDotfiles       => write!(f, "flatten-dotfiles"),
+			Self::PrefixByApp           => write!(f, "prefix-by-app"),
+			Self::HashSuffixOnCollision => write!(f, "hash-suffix-on-collision"),
+		}
+	}
+}
+
+/// Returns the given path with `-` and a suffix appended to its file stem.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+	let mut name = path
+		.file_stem()
+		.unwrap_or_default()
+		.to_os_string();
+	name.push("-");
+	name.push(suffix);
+	if let Some(ext) = path.extension() {
+		name.push(".");
+		name.push(ext);
+	}
+	path.with_file_name(name)
+}
+
+/// Returns the given file name without any leading `.`. Names which are not
+/// valid unicode are returned unchanged.
+fn strip_dot(name: &OsStr) -> OsString {
+	match name.to_str().and_then(|text| text.strip_prefix('.')) {
+		Some(stripped) if !stripped.is_empty() => stripped.into(),
+		_ => name.to_os_string(),
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Disambiguation
+////////////////////////////////////////////////////////////////////////////////
+/// A method for choosing a new stall path for a file whose stall path is
+/// already used by a different file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(clap::ArgEnum)]
+pub enum Disambiguation {
+	/// Prefix the file name with the name of the remote file's parent
+	/// directory, without any leading `.`, so `~/.config/a/config` is stalled
+	/// as `a-config`. Numbered suffixes are used if the prefixed name is also
+	/// in use.
+	Parent,
+	/// Append the lowest unused number to the file stem, so `config` is
+	/// stalled as `config-2`.
+	Number,
+}
+
+impl Disambiguation {
+	/// Returns an unused stall path for the file with the given remote path,
+	/// whose stall path `local` is already in use. The `in_use` function
+	/// returns whether a stall path is used by a different file in the stall.
+	#[must_use]
+	pub fn resolve<F>(self, local: &Path, remote: &Path, in_use: F) -> PathBuf
+		where F: Fn(&Path) -> bool
+	{
+		let local = match self {
+			Self::Parent => {
+				let parent = remote.parent()
+					.and_then(Path::file_name)
+					.map(strip_dot)
+					.filter(|parent| !parent.is_empty());
+				match (parent, local.file_name()) {
+					(Some(mut prefixed), Some(name)) => {
+						prefixed.push("-");
+						prefixed.push(name);
+						local.with_file_name(prefixed)
+					},
+					_ => local.to_path_buf(),
+				}
+			},
+			Self::Number => local.to_path_buf(),
+		};
+
+		let mut numbered = local.clone();
+		let mut n = 1_usize;
+		while in_use(&numbered) {
+			n += 1;
+			numbered = with_suffix(&local, &n.to_string());
+		}
+		numbered
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// NameTemplateParseError
+////////////////////////////////////////////////////////////////////////////////
+/// An error indicating a failure to parse a [`NameTemplate`].
+///
+/// [`NameTemplate`]: NameTemplate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplateParseError(String);
+
+impl std::error::Error for NameTemplateParseError {}
+
+impl std::fmt::Display for NameTemplateParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "invalid name template: {}", self.0)
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// FormatTemplateParseError
+////////////////////////////////////////////////////////////////////////////////
+/// An error indicating a failure to parse a [`FormatTemplate`].
+///
+/// [`FormatTemplate`]: FormatTemplate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatTemplateParseError(String);
+
+impl std::error::Error for FormatTemplateParseError {}
+
+impl std::fmt::Display for FormatTemplateParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "invalid format template: {}", self.0)
+	}
+}
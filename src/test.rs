@@ -18,6 +18,8 @@ use test_log::test;
 // Standard library imports.
 use std::fs::File;
 use std::path::Path;
+use std::process::Command;
+use std::process::Output;
 
 
 fn file_exists<P>(path: P) -> bool where P: AsRef<Path> {
@@ -37,6 +39,44 @@ fn create_file<P>(path: P) where P: AsRef<Path> {
 }
 
 
+/// Returns a command running the stall executable built for the tests. The
+/// state and cache directories are kept in the target directory.
+fn stall_command() -> Command {
+    let target_dir = std::env::current_dir().unwrap().join("target");
+    let mut command = Command::new(target_dir.join("debug/stall"));
+    let _ = command
+        .env("XDG_STATE_HOME", target_dir.join("test-xdg/state"))
+        .env("XDG_CACHE_HOME", target_dir.join("test-xdg/cache"));
+    command
+}
+
+
+/// Returns the path printed by the `paths` command for the given name, such
+/// as `state` or `journal`, for the stall in the given directory.
+fn stall_file_path(dir: &Path, name: &str) -> std::path::PathBuf {
+    let output = stall(dir, &["paths"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let prefix = format!("{name}:");
+    stdout.lines()
+        .find_map(|line| line.strip_prefix(&prefix))
+        .map(|path| path.trim().into())
+        .expect("find path")
+}
+
+
+/// Runs the stall executable with the subcommand and arguments in `args`,
+/// using the stall in the given directory.
+fn stall(dir: &Path, args: &[&str]) -> Output {
+    stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(dir)
+        .args(&args[1..])
+        .output()
+        .unwrap()
+}
+
+
 #[test]
 #[tracing::instrument]
 pub fn init_plain() {
@@ -109,3 +149,5593 @@ pub fn add_multi_collect() {
     assert!(file_exists(stall_path.join("b")));
 }
 
+
+
+#[test]
+#[tracing::instrument]
+pub fn status_check_exit_codes() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("a"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add file without collecting.
+    let output = stall_command()
+        .args(["add", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Local copy is missing.
+    let output = stall_command()
+        .args(["status", "--check", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("LOCAL"));
+    assert_eq!(output.status.code(), Some(2));
+
+    // Collect the file.
+    let output = stall_command()
+        .args(["collect", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Stall is clean.
+    let output = stall_command()
+        .args(["status", "--check", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("LOCAL"));
+    assert_eq!(output.status.code(), Some(0));
+
+    // Errors exit with the same code as missing files.
+    std::fs::write(stall_path.join(".stall"), "(entries: {").unwrap();
+    let output = stall(stall_path, &["status", "--check"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn add_rename_template_and_bulk_move() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    std::fs::create_dir(remote_path.join("x")).unwrap();
+    std::fs::create_dir(remote_path.join("y")).unwrap();
+    create_file(remote_path.join("x/config.toml"));
+    create_file(remote_path.join("y/config.toml"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add files with a naming template.
+    let output = stall_command()
+        .args(["add", "--collect", "--rename-template", "{parent}-{stem}{ext}"])
+        .arg("--stall")
+        .arg(stall_path)
+        .args([
+            remote_path.join("x/config.toml"),
+            remote_path.join("y/config.toml"),
+        ])
+        .output()
+        .unwrap();
+    println!("{}", String::from_utf8(output.stderr).unwrap());
+    assert!(output.status.success());
+    assert!(file_exists(stall_path.join("x-config.toml")));
+    assert!(file_exists(stall_path.join("y-config.toml")));
+
+    // Rename both files using a pattern.
+    let output = stall_command()
+        .args(["mv", "--move", "--stall"])
+        .arg(stall_path)
+        .args(["*-config.toml", "{n}.{stem}"])
+        .output()
+        .unwrap();
+    println!("{}", String::from_utf8(output.stderr).unwrap());
+    assert!(output.status.success());
+    assert!(file_exists(stall_path.join("1.x-config")));
+    assert!(file_exists(stall_path.join("2.y-config")));
+    assert!(!file_exists(stall_path.join("x-config.toml")));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn add_append_distribute_multiple_remotes() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("a"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add and collect the first remote.
+    let output = stall_command()
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add a second remote for the same stall file.
+    let output = stall_command()
+        .args(["add", "--append", "--rename", "a", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("b"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Distribute to both remotes.
+    let output = stall_command()
+        .args(["distribute", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    println!("{}", String::from_utf8(output.stdout).unwrap());
+    assert!(output.status.success());
+    assert!(file_exists(remote_path.join("b")));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn distribute_durable_replaces_file() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    std::fs::write(remote_path.join("a"), "old").expect("write file");
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .args(["add", "--collect", "--durable", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Update the stall copy and distribute it over the remote.
+    std::fs::write(stall_path.join("a"), "new").expect("write file");
+    let output = stall_command()
+        .args(["distribute", "--force", "--durable", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    println!("{}", String::from_utf8(output.stdout).unwrap());
+    assert!(output.status.success());
+
+    let content = std::fs::read_to_string(remote_path.join("a"))
+        .expect("read file");
+    assert_eq!(content, "new");
+
+    // No temporary files should be left behind.
+    let names: Vec<_> = std::fs::read_dir(remote_path)
+        .expect("read dir")
+        .map(|e| e.expect("dir entry").file_name())
+        .collect();
+    assert_eq!(names, vec![std::ffi::OsString::from("a")]);
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn grep_local_and_remote() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    std::fs::write(remote_path.join("zshrc"), "export EDITOR=vim\n")
+        .expect("write file");
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add and collect file.
+    let output = stall_command()
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("zshrc"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Change the remote file.
+    std::fs::write(remote_path.join("zshrc"), "export EDITOR=nano\n")
+        .expect("write file");
+
+    // Search the local file.
+    let output = stall_command()
+        .args(["grep", "-i", "editor=VIM", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("zshrc [local]"));
+    assert!(stdout.contains("1: export EDITOR=vim"));
+    assert_eq!(output.status.code(), Some(0));
+
+    // Search the remote file.
+    let output = stall_command()
+        .args(["grep", "--in", "remote", "EDITOR=vim", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("[remote]"));
+    assert_eq!(output.status.code(), Some(1));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn completions_list_entries() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("a"));
+    create_file(remote_path.join("b"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add files.
+    let output = stall_command()
+        .args(["add", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .arg(remote_path.join("b"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Generate completion script.
+    let output = stall_command()
+        .args(["completions", "bash"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap()
+        .contains("complete-entries"));
+
+    // List entries for completion.
+    let output = stall_command()
+        .args(["complete-entries", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\n");
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn gen_manpage_out_dir() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let out_dir = temp_dir.path();
+
+    let output = stall_command()
+        .args(["gen-manpage", "--out-dir"])
+        .arg(out_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    assert!(file_exists(out_dir.join("stall.1")));
+    assert!(file_exists(out_dir.join("stall-add.1")));
+    assert!(file_exists(out_dir.join("stall-status.1")));
+    assert!(!file_exists(out_dir.join("stall-complete-entries.1")));
+
+    // Print help for all subcommands.
+    let output = stall_command()
+        .args(["help", "--all"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("stall-add"));
+    assert!(stdout.contains("stall-distribute"));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn stall_discovery() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("a"));
+    std::fs::create_dir(stall_path.join("sub")).expect("create dir");
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add file from a subdirectory of the stall.
+    let output = stall_command()
+        .current_dir(stall_path.join("sub"))
+        .env_remove("STALL_FILE")
+        .env_remove("STALL_DIR")
+        .args(["add", "--collect"])
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(file_exists(stall_path.join("a")));
+
+    // Use the stall directory from the environment.
+    let output = stall_command()
+        .current_dir(remote_path)
+        .env_remove("STALL_FILE")
+        .env("STALL_DIR", stall_path)
+        .args(["status", "--check"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn named_stalls() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let work_path = temp_dir_b.path();
+
+    create_file(work_path.join("a"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Register the stall.
+    let output = stall_command()
+        .current_dir(work_path)
+        .env("XDG_CONFIG_HOME", work_path)
+        .env("APPDATA", work_path)
+        .args(["stalls", "add", "home"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(file_exists(work_path.join("stall").join("prefs")));
+
+    // Add a file using the stall name.
+    let output = stall_command()
+        .current_dir(work_path)
+        .env("XDG_CONFIG_HOME", work_path)
+        .env("APPDATA", work_path)
+        .args(["add", "--collect", "--stall", "home"])
+        .arg(work_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(file_exists(stall_path.join("a")));
+
+    // Unregister the stall.
+    let output = stall_command()
+        .current_dir(work_path)
+        .env("XDG_CONFIG_HOME", work_path)
+        .env("APPDATA", work_path)
+        .args(["stalls", "rm", "home"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .current_dir(work_path)
+        .env("XDG_CONFIG_HOME", work_path)
+        .env("APPDATA", work_path)
+        .args(["stalls", "rm", "home"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+
+#[test]
+#[tracing::instrument]
+#[cfg(not(target_os = "windows"))]
+pub fn foreach_sets_entry_env() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("a"));
+    create_file(remote_path.join("b"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add files.
+    let output = stall_command()
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .arg(remote_path.join("b"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Copy each remote file to a new name next to it.
+    let output = stall_command()
+        .args(["foreach", "--jobs", "2", "--stall"])
+        .arg(stall_path)
+        .args(["--", "cp \"$STALL_REMOTE\" \"$STALL_REMOTE.$STALL_NAME\""])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(file_exists(remote_path.join("a.a")));
+    assert!(file_exists(remote_path.join("b.b")));
+
+    // Failing commands produce an error.
+    let output = stall_command()
+        .args(["foreach", "--filter", "a", "--stall"])
+        .arg(stall_path)
+        .args(["--", "false"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn remove_delete_trash_restore() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("a"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add and collect file.
+    let output = stall_command()
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(file_exists(stall_path.join("a")));
+
+    // Remove and delete the stalled file.
+    let output = stall_command()
+        .args(["rm", "--delete", "--stall"])
+        .arg(stall_path)
+        .arg("a")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!file_exists(stall_path.join("a")));
+
+    // Restore it from the trash.
+    let output = stall_command()
+        .args(["trash", "--stall"])
+        .arg(stall_path)
+        .args(["restore", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(file_exists(stall_path.join("a")));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn pinned_entry_collect() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    std::fs::write(remote_path.join("a"), "old").expect("write file");
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add and collect file.
+    let output = stall_command()
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Pin the entry.
+    let output = stall_command()
+        .args(["flag", "--pin", "--stall"])
+        .arg(stall_path)
+        .arg("a")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Forced collect keeps the pinned file.
+    std::fs::write(remote_path.join("a"), "new").expect("write file");
+    let output = stall_command()
+        .args(["collect", "--force", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(stall_path.join("a")).unwrap(), "old");
+
+    // Forced collect with unpin overwrites it.
+    let output = stall_command()
+        .args(["collect", "--force", "--unpin", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(stall_path.join("a")).unwrap(), "new");
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn locked_stall_rejects_modification() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("a"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let lock = crate::lock::StallLock::acquire(stall_path, false)
+        .expect("acquire lock");
+
+    // Modifying commands fail while another process holds the lock.
+    let output = stall_command()
+        .args(["add", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    // Read-only commands don't need the lock.
+    let output = stall_command()
+        .args(["status", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .args(["add", "--no-lock", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // The lock file is removed when the lock is released on unix systems.
+    let lock_path = lock.path().to_owned();
+    assert!(lock_path.exists());
+    drop(lock);
+    assert_eq!(lock_path.exists(), cfg!(not(unix)));
+
+    let output = stall_command()
+        .args(["rm", "--stall"])
+        .arg(stall_path)
+        .arg("a")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(lock_path.exists(), cfg!(not(unix)));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn distribute_create_dirs() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let nested = remote_path.join("x/y/a");
+
+    create_file(remote_path.join("a"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .args(["add", "--append", "--rename", "a", "--stall"])
+        .arg(stall_path)
+        .arg(&nested)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Missing directories are not created by default.
+    let output = stall_command()
+        .args(["distribute", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!remote_path.join("x").exists());
+
+    // Dry runs print the directories to create.
+    let output = stall_command()
+        .args(["distribute", "--create-dirs", "--dry-run", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    println!("{stdout}");
+    assert!(output.status.success());
+    assert!(stdout.contains("mkdir"));
+    assert!(!remote_path.join("x").exists());
+
+    let output = stall_command()
+        .args(["distribute", "--create-dirs", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(file_exists(&nested));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn manifest_write_verify() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    std::fs::write(remote_path.join("a"), "a").expect("write file");
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Verifying without a manifest is an error.
+    let output = stall_command()
+        .args(["manifest", "--stall"])
+        .arg(stall_path)
+        .arg("verify")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let output = stall_command()
+        .args(["manifest", "--stall"])
+        .arg(stall_path)
+        .arg("write")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(file_exists(stall_path.join(".stall-manifest")));
+
+    let output = stall_command()
+        .args(["manifest", "--stall"])
+        .arg(stall_path)
+        .arg("verify")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    // Modify the remote file.
+    std::fs::write(remote_path.join("a"), "b").expect("write file");
+    let output = stall_command()
+        .args(["manifest", "--stall"])
+        .arg(stall_path)
+        .arg("verify")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    println!("{stdout}");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout.contains("changed"));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn error_messages() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir.path();
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .args(["flag", "--pin", "--stall"])
+        .arg(stall_path)
+        .arg("missing")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    println!("{stderr}");
+    assert!(!output.status.success());
+    assert!(stderr.contains("unrecognized stall entry: missing"));
+
+    let output = stall_command()
+        .args(["trash", "--stall"])
+        .arg(stall_path)
+        .args(["restore", "7"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    println!("{stderr}");
+    assert!(!output.status.success());
+    assert!(stderr.contains("trash item not found: 7"));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn distribute_continues_after_failure() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("a"));
+    std::fs::write(remote_path.join("b"), "old").expect("write file");
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .arg(remote_path.join("b"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Add a remote which can't be distributed to.
+    let output = stall_command()
+        .args(["add", "--append", "--rename", "a", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("x/a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    std::fs::write(stall_path.join("b"), "new").expect("write file");
+    let output = stall_command()
+        .args(["distribute", "--error", "--force", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    println!("{stderr}");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("1 of 3 entries failed"));
+
+    // Later entries are still distributed.
+    let content = std::fs::read_to_string(remote_path.join("b"))
+        .expect("read file");
+    assert_eq!(content, "new");
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn collect_warnings_allow_and_error() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("a"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    std::fs::remove_file(remote_path.join("a")).expect("remove file");
+
+    // A missing remote is a warning.
+    let output = stall_command()
+        .args(["collect", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Promoted to an error with --error.
+    let output = stall_command()
+        .args(["collect", "--error", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    println!("{stderr}");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("remote file does not exist"));
+
+    // Suppressed with --allow.
+    let output = stall_command()
+        .args(["collect", "--error", "--allow", "missing-remote", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn config_layered_defaults() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let temp_dir_c = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let home_path = temp_dir_c.path();
+    let system_config = home_path.join("system-config");
+
+    let stall = |args: &[&str]| stall_command()
+        .env("HOME", home_path)
+        .env("USERPROFILE", home_path)
+        .env("XDG_CONFIG_HOME", home_path.join(".config"))
+        .env("APPDATA", home_path.join(".config"))
+        .env("STALL_SYSTEM_CONFIG", &system_config)
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    create_file(remote_path.join("a"));
+
+    // Init stall
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Set options at each level.
+    let output = stall(&["config", "set", "--level", "system", "jobs", "3"]);
+    assert!(output.status.success());
+    let output = stall(&["config", "set", "hash_algorithm", "sha512"]);
+    assert!(output.status.success());
+    let output = stall(&["config", "set", "--level", "stall", "jobs", "2"]);
+    assert!(output.status.success());
+    assert!(stall_path.join(".stall-config").exists());
+    assert!(home_path.join(".config/stall/config").exists());
+
+    // Invalid values and keys are rejected.
+    let output = stall(&["config", "set", "jobs", "0"]);
+    assert!(!output.status.success());
+    let output = stall(&["config", "get", "nonexistent"]);
+    assert!(!output.status.success());
+
+    // Later levels take precedence.
+    let output = stall(&["config", "get", "jobs"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().last(), Some("2"));
+
+    let output = stall(&["config", "--color", "never", "list"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    println!("{stdout}");
+    let rows: Vec<Vec<&str>> = stdout
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert!(rows.contains(&vec!["hash_algorithm", "sha512", "user"]));
+    assert!(rows.contains(&vec!["jobs", "2", "stall"]));
+    assert!(rows.contains(&vec!["color", "auto", "default"]));
+
+    // Configured defaults are used by commands, and command line options
+    // take precedence.
+    let output = stall(&["add", "--collect"]);
+    assert!(output.status.success());
+    let output = stall_command()
+        .env("HOME", home_path)
+        .env("USERPROFILE", home_path)
+        .env("STALL_SYSTEM_CONFIG", &system_config)
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall(&["manifest", "write", "--dry-run"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("SHA-512"));
+    let output = stall(&["manifest", "write", "--dry-run", "--hash", "sha256"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("SHA-256"));
+
+    // Unset options fall back to lower levels.
+    let output = stall(&["config", "unset", "--level", "stall", "jobs"]);
+    assert!(output.status.success());
+    let output = stall(&["config", "get", "jobs"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().last(), Some("3"));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn prefs_edit_and_set() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let prefs_path = temp_dir.path().join("prefs");
+
+    let prefs = |args: &[&str]| stall_command()
+        .env_remove("VISUAL")
+        .env("EDITOR", "true")
+        .arg("prefs")
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(args)
+        .output()
+        .unwrap();
+
+    // Editing creates the prefs file from the template.
+    let output = prefs(&["edit"]);
+    assert!(output.status.success());
+    let content = std::fs::read_to_string(&prefs_path).expect("read file");
+    assert!(content.contains("create_dirs: false"));
+
+    // A failing editor is an error.
+    let output = stall_command()
+        .env_remove("VISUAL")
+        .env("EDITOR", "false")
+        .args(["prefs", "--prefs"])
+        .arg(&prefs_path)
+        .arg("edit")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    // Values are validated.
+    let output = prefs(&["set", "create_dirs", "maybe"]);
+    assert!(!output.status.success());
+    let output = prefs(&["set", "stalls.x", "relative/path"]);
+    assert!(!output.status.success());
+    let output = prefs(&["set", "nonexistent", "true"]);
+    assert!(!output.status.success());
+
+    let output = prefs(&["set", "create_dirs", "true"]);
+    assert!(output.status.success());
+    let stall_path = temp_dir.path().to_str().unwrap();
+    let output = prefs(&["set", "stalls.x", stall_path]);
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .args(["stalls", "--prefs"])
+        .arg(&prefs_path)
+        .arg("list")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(stall_path));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn trace_log_show() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir.path().join("stall");
+    let prefs_path = temp_dir.path().join("prefs");
+    std::fs::create_dir(&stall_path).expect("create stall dir");
+
+    let stall = |args: &[&str]| stall_command()
+        .env("XDG_STATE_HOME", temp_dir.path().join("state"))
+        .arg(args[0])
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .arg("--stall")
+        .arg(&stall_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    // The log is written into the stall directory when the preference is set.
+    let output = stall_command()
+        .env_remove("VISUAL")
+        .env("EDITOR", "true")
+        .args(["prefs", "--prefs"])
+        .arg(&prefs_path)
+        .arg("edit")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall_command()
+        .args(["prefs", "--prefs"])
+        .arg(&prefs_path)
+        .args(["set", "trace_log", "stall"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall_command()
+        .env("XDG_STATE_HOME", temp_dir.path().join("state"))
+        .args(["init", "--prefs"])
+        .arg(&prefs_path)
+        .arg(&stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall(&["status"]);
+    assert!(output.status.success());
+    assert!(stall_path.join(".stall-log").exists());
+    assert!(!temp_dir.path().join("state").exists());
+
+    let output = stall(&["log", "show"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    println!("{stdout}");
+    assert!(stdout.contains("status"));
+
+    let output = stall(&["log", "show", "--since", "1d"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("status"));
+
+    let output = stall(&["log", "show", "--since", "2999-01-01"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = stall(&["log", "show", "--since", "yesterday"]);
+    assert!(!output.status.success());
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn history_entry_filter() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("a"));
+    create_file(remote_path.join("b"));
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--color=never")
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = stall(&["history"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("No recorded operations."));
+
+    let output = stall_command()
+        .args(["add", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .arg(remote_path.join("b"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall(&["collect"]);
+    assert!(output.status.success());
+    let output = stall(&["mv", "a", "c"]);
+    assert!(output.status.success());
+    let output = stall(&["rm", "--dry-run", "b"]);
+    assert!(output.status.success());
+    let output = stall(&["mv", "missing", "d"]);
+    assert!(!output.status.success());
+
+    let output = stall(&["history"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    println!("{stdout}");
+    let rows: Vec<Vec<&str>> = stdout
+        .lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().skip(2).collect())
+        .collect();
+    assert_eq!(rows, vec![
+        vec!["add", "ok", "a,", "b"],
+        vec!["collect", "ok", "a,", "b"],
+        vec!["mv", "ok", "a,", "c"],
+        vec!["mv", "error:", "unrecognized", "stall", "entry:", "missing"],
+    ]);
+
+    let output = stall(&["history", "--entry", "b"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let operations: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(2))
+        .collect();
+    assert_eq!(operations, vec!["add", "collect"]);
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn move_onto_existing_swap_and_force() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    for name in ["a", "b", "c"] {
+        std::fs::write(remote_path.join(name), name).expect("write file");
+    }
+
+    let read = |name: &str| std::fs::read_to_string(stall_path.join(name))
+        .expect("read file");
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall_command()
+        .args(["add", "--collect", "--stall"])
+        .arg(stall_path)
+        .arg(remote_path.join("a"))
+        .arg(remote_path.join("b"))
+        .arg(remote_path.join("c"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Renaming onto an existing entry is refused by default.
+    let output = stall(stall_path, &["mv", "--move", "a", "b"]);
+    assert!(!output.status.success());
+    assert_eq!(read("a"), "a");
+
+    let output = stall(stall_path, &["mv", "--force", "--swap", "a", "b"]);
+    assert!(!output.status.success());
+
+    // Swapped entries exchange their names and files.
+    let output = stall(stall_path, &["mv", "--move", "--swap", "a", "b"]);
+    assert!(output.status.success());
+    assert_eq!(read("a"), "b");
+    assert_eq!(read("b"), "a");
+    let output = stall(stall_path, &["collect", "--force", "a"]);
+    assert!(output.status.success());
+    assert_eq!(read("a"), "b");
+
+    // Forced renames replace the existing entry, trashing its file.
+    let output = stall(stall_path, &["mv", "--move", "--force", "a", "c"]);
+    assert!(output.status.success());
+    assert!(!file_exists(stall_path.join("a")));
+    assert_eq!(read("c"), "b");
+    let output = stall(stall_path, &["status", "--columns", "remote"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains(remote_path.join("c").to_str().unwrap()));
+    let output = stall(stall_path, &["trash", "list"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(stall_path.join("c").to_str().unwrap()));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn init_rename_policy() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let home_path = temp_dir_b.path();
+
+    std::fs::create_dir_all(home_path.join(".config/nvim")).unwrap();
+    std::fs::create_dir_all(home_path.join(".config/git")).unwrap();
+    create_file(home_path.join(".bashrc"));
+    create_file(home_path.join(".config/nvim/init.vim"));
+    create_file(home_path.join(".config/git/init.vim"));
+
+    let stall = |args: &[&str]| stall_command()
+        .env("HOME", home_path)
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    let entries = || {
+        let output = stall(&["status", "--columns", "local"]);
+        String::from_utf8(output.stdout).unwrap()
+            .lines()
+            .skip(2)
+            .map(|line| line.trim().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    // The policy is stored in the stall file.
+    let output = stall_command()
+        .args(["init", "--rename-policy", "prefix-by-app"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let content = std::fs::read_to_string(stall_path.join(".stall")).unwrap();
+    assert!(content.contains("prefix-by-app"));
+
+    let output = stall(&["add",
+        home_path.join(".bashrc").to_str().unwrap(),
+        home_path.join(".config/nvim/init.vim").to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(entries(), vec![".bashrc", "nvim-init.vim"]);
+
+    // The hash suffix is only added when the names collide.
+    std::fs::remove_file(stall_path.join(".stall")).unwrap();
+    let output = stall_command()
+        .args(["init", "--rename-policy", "hash-suffix-on-collision"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall(&["add",
+        home_path.join(".config/nvim/init.vim").to_str().unwrap(),
+        home_path.join(".config/git/init.vim").to_str().unwrap(),
+        home_path.join(".config/nvim/init.vim").to_str().unwrap()]);
+    assert!(output.status.success());
+    let names = entries();
+    println!("{names:?}");
+    assert_eq!(names.len(), 2);
+    assert!(names.contains(&"init.vim".to_owned()));
+    assert!(names.iter().any(|name| name.starts_with("init-")
+        && Path::new(name).extension() == Some("vim".as_ref())));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn add_name_collision() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let config_path = temp_dir_b.path();
+
+    for dir in ["a", "b", "c", "d"] {
+        std::fs::create_dir_all(config_path.join(dir)).unwrap();
+        create_file(config_path.join(dir).join("config"));
+    }
+    let file = |dir: &str| config_path.join(dir).join("config");
+
+    let entries = || {
+        let output = stall(stall_path,
+            &["status", "--columns", "local,remote"]);
+        String::from_utf8(output.stdout).unwrap()
+            .lines()
+            .skip(2)
+            .map(|line| line.split_whitespace()
+                .map(str::to_owned)
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Re-adding the same file is not a collision.
+    let output = stall(stall_path, &["add", file("a").to_str().unwrap()]);
+    assert!(output.status.success());
+    let output = stall(stall_path, &["add", file("a").to_str().unwrap()]);
+    assert!(output.status.success());
+
+    // Adding a different file with the same name is refused.
+    let output = stall(stall_path, &["add", file("b").to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert_eq!(entries().len(), 1);
+
+    let output = stall(stall_path, &["add", "--disambiguate", "parent",
+        file("b").to_str().unwrap()]);
+    assert!(output.status.success());
+    let output = stall(stall_path, &["add", "--disambiguate", "number",
+        file("c").to_str().unwrap()]);
+    assert!(output.status.success());
+    let names = entries()
+        .into_iter()
+        .map(|entry| entry[0].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["b-config", "config", "config-2"]);
+
+    // Forcing replaces the existing entry.
+    let output = stall(stall_path,
+        &["add", "--force", file("d").to_str().unwrap()]);
+    assert!(output.status.success());
+    let entries = entries();
+    println!("{entries:?}");
+    assert_eq!(entries.len(), 3);
+    assert!(entries.iter().any(|entry| entry[0] == "config"
+        && entry[1].ends_with("config")
+        && Path::new(&entry[1]).parent().unwrap().ends_with("d")));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn add_reports_displaced_entry() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let config_path = temp_dir_b.path();
+
+    create_file(config_path.join("a"));
+    create_file(config_path.join("b"));
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Invalid stall paths are reported as errors.
+    let output = stall(stall_path, &["add", "--rename", "..",
+        config_path.join("a").to_str().unwrap()]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    println!("{stderr}");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr.contains("stall path has no file name"));
+
+    let output = stall(stall_path, &["add", "--rename", "config",
+        config_path.join("a").to_str().unwrap()]);
+    assert!(output.status.success());
+    let output = stall(stall_path, &["add", "--rename", "config", "--force",
+        config_path.join("b").to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Replaced stall entry config from"));
+
+    // A remote file can't be taken from another entry.
+    let output = stall(stall_path, &["add", "--rename", "other",
+        config_path.join("b").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap()
+        .contains("remote file is already stalled as: config"));
+    let output = stall(stall_path, &["status", "--columns", "local"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("config"));
+    assert!(!stdout.contains("other"));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn remote_path_normalization() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let home_path = temp_dir_b.path();
+
+    std::fs::create_dir_all(home_path.join("sub")).unwrap();
+    create_file(home_path.join(".bashrc"));
+
+    let stall = |args: &[&str]| stall_command()
+        .env("HOME", home_path)
+        .current_dir(home_path.join("sub"))
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    let remotes = || {
+        let output = stall(&["status", "--columns", "remote"]);
+        String::from_utf8(output.stdout).unwrap()
+            .lines()
+            .skip(2)
+            .map(|line| line.trim().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Equivalent spellings of the same remote path are a single entry.
+    for remote in [
+        "~/.bashrc".to_owned(),
+        home_path.join("./sub/../.bashrc").display().to_string(),
+        "../.bashrc".to_owned(),
+    ] {
+        let output = stall(&["add", &remote]);
+        assert!(output.status.success());
+    }
+    let remotes = remotes();
+    println!("{remotes:?}");
+    assert_eq!(remotes.len(), 1);
+    assert!(Path::new(&remotes[0]).is_absolute());
+
+    let output = stall(&["rm", "--remote-naming", "./../.bashrc"]);
+    assert!(output.status.success());
+    let content = std::fs::read_to_string(stall_path.join(".stall")).unwrap();
+    assert!(!content.contains(".bashrc"));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn platform_windows_path_text() {
+    use crate::platform::strip_verbatim_prefix;
+    use crate::platform::upper_case_drive_letter;
+
+    assert_eq!(strip_verbatim_prefix(r"\\?\C:\Users\a\.bashrc").as_deref(),
+        Some(r"C:\Users\a\.bashrc"));
+    assert_eq!(strip_verbatim_prefix(r"\\?\UNC\server\share\a").as_deref(),
+        Some(r"\\server\share\a"));
+    assert_eq!(strip_verbatim_prefix(r"\\?\Volume{1234}\a"), None);
+    assert_eq!(strip_verbatim_prefix(r"C:\a"), None);
+
+    assert_eq!(upper_case_drive_letter(r"c:\a").as_deref(), Some(r"C:\a"));
+    assert_eq!(upper_case_drive_letter(r"C:\a"), None);
+    assert_eq!(upper_case_drive_letter(r"\\server\share"), None);
+}
+
+#[cfg(unix)]
+#[test]
+#[tracing::instrument]
+pub fn collect_symlink() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    create_file(remote_path.join("target"));
+    std::os::unix::fs::symlink(remote_path.join("target"),
+        remote_path.join("link")).unwrap();
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall(stall_path,
+        &["add", remote_path.join("link").to_str().unwrap()]);
+    assert!(output.status.success());
+
+    // Links are recreated with the same destination.
+    let output = stall(stall_path, &["collect", "--force"]);
+    println!("{}", String::from_utf8(output.stderr).unwrap());
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_link(stall_path.join("link")).unwrap(),
+        remote_path.join("target"));
+
+    std::fs::remove_file(remote_path.join("link")).unwrap();
+    let output = stall(stall_path, &["distribute", "--force"]);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_link(remote_path.join("link")).unwrap(),
+        remote_path.join("target"));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn overlapping_entries() {
+    use crate::warning::Overlap;
+    use crate::warning::Warning;
+    use crate::Stall;
+    use std::path::PathBuf;
+
+    let mut stall = Stall::new(".stall");
+    for (local, remote) in [
+        ("conf", "/home/conf"),
+        ("x", "/home/conf/x"),
+        ("vim", "/home/.vimrc"),
+        ("vim/colors", "/home/.vim/colors"),
+        ("Bashrc", "/home/.bashrc"),
+        ("bashrc", "/root/.bashrc"),
+    ] {
+        let _ = stall.insert(PathBuf::from(local), Path::new(remote)).unwrap();
+    }
+    let overlap = |local: &str, other: &str, overlap| {
+        Warning::OverlappingEntries {
+            local: PathBuf::from(local),
+            other: PathBuf::from(other),
+            overlap,
+        }
+    };
+
+    assert_eq!(stall.overlaps(|_| true), [
+        overlap("Bashrc", "bashrc", Overlap::AliasedLocal),
+        overlap("x", "conf", Overlap::NestedRemote),
+        overlap("vim/colors", "vim", Overlap::NestedLocal),
+    ]);
+    assert_eq!(stall.overlaps(|local| local == Path::new("vim")), [
+        overlap("vim/colors", "vim", Overlap::NestedLocal),
+    ]);
+    assert!(stall.overlaps(|_| false).is_empty());
+
+    // Overlaps are warnings, which can be allowed.
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::create_dir(remote_path.join("conf")).unwrap();
+    std::fs::write(remote_path.join("conf/x"), "x").unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n{}\n",
+            remote_path.join("conf").display(),
+            remote_path.join("conf/x").display()))
+        .unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    let output = stall(&["collect", "--error"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains(
+        "remote file of x is inside the remote directory of conf"));
+    assert!(!stall_path.join("x").exists());
+    assert!(stall(&["collect", "--error", "--allow", "overlapping"])
+        .status
+        .success());
+    assert!(stall_path.join("x").exists());
+}
+
+#[cfg(unix)]
+#[test]
+#[tracing::instrument]
+pub fn remote_in_stall_dir_refused() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    let remote = remote_path.join("a");
+    let local = stall_path.join("a");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::write(&remote, "a").unwrap();
+    std::fs::write(stall_path.join("inner"), "inner").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    let stderr = |output: Output| String::from_utf8(
+        output.stderr).unwrap();
+
+    assert!(stall(&["init"]).status.success());
+    let output = stall(&["add", stall_path.join("inner").to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(stderr(output).contains("remote file is inside the stall \
+        directory"));
+    assert!(stall(&["add", remote.to_str().unwrap()]).status.success());
+    assert!(stall(&["collect"]).status.success());
+
+    // A remote link to the stalled file is neither collected nor distributed.
+    std::fs::remove_file(&remote).unwrap();
+    std::os::unix::fs::symlink(&local, &remote).unwrap();
+    assert!(!stall(&["distribute", "--force"]).status.success());
+    assert!(!stall(&["collect", "--force"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "a");
+
+    // Nor is a remote hard link to the stalled file.
+    std::fs::remove_file(&remote).unwrap();
+    std::fs::hard_link(&local, &remote).unwrap();
+    let output = stall(&["distribute", "--force"]);
+    assert!(!output.status.success());
+    assert!(stderr(output).contains("remote file is the same file as \
+        stalled file a"));
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "a");
+
+    // Renaming an entry onto the remote file's hard link is refused.
+    std::fs::remove_file(&remote).unwrap();
+    std::fs::write(&remote, "a").unwrap();
+    std::fs::hard_link(&remote, stall_path.join("b")).unwrap();
+    assert!(!stall(&["mv", "a", "b"]).status.success());
+    assert!(stall(&["mv", "a", "c"]).status.success());
+}
+
+#[test]
+#[tracing::instrument]
+pub fn readonly_stall() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    let remote = remote_path.join("a");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::write(&remote, "a").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    assert!(stall(&["init"]).status.success());
+    assert!(stall(&["add", remote.to_str().unwrap()]).status.success());
+    assert!(stall(&["collect"]).status.success());
+    let output = stall(&["rm", "a", "--readonly-stall"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("stall is read-only"));
+
+    // Mark the stall read-only in the stall file, and remove everything else
+    // from the stall directory.
+    let stall_file = stall_path.join(".stall");
+    let text = std::fs::read_to_string(&stall_file)
+        .unwrap()
+        .replacen("version: 1,", "version: 1,\n    readonly: true,", 1);
+    std::fs::write(&stall_file, &text).unwrap();
+    for entry in std::fs::read_dir(stall_path).unwrap() {
+        let path = entry.unwrap().path();
+        if path.file_name().unwrap() != ".stall" && !path.ends_with("a") {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    // Mutating commands are refused.
+    std::fs::write(&remote, "b").unwrap();
+    assert!(!stall(&["collect", "--force"]).status.success());
+    assert!(!stall(&["rm", "a"]).status.success());
+    assert!(!stall(&["edit"]).status.success());
+    assert!(!stall(&["config", "set", "--level", "stall", "jobs", "2"])
+        .status
+        .success());
+    assert!(!file_exists(stall_path.join(".stall-config")));
+    assert_eq!(std::fs::read_to_string(stall_path.join("a")).unwrap(), "a");
+
+    // Distribute and status run without writing to the stall directory.
+    assert!(stall(&["distribute", "--force"]).status.success());
+    assert_eq!(std::fs::read_to_string(&remote).unwrap(), "a");
+    assert!(stall(&["status"]).status.success());
+    let mut names: Vec<_> = std::fs::read_dir(stall_path)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, [".stall", "a"]);
+    assert_eq!(std::fs::read_to_string(&stall_file).unwrap(), text);
+}
+
+#[test]
+#[tracing::instrument]
+pub fn distribute_timeout() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = stall_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    let remotes: Vec<_> = (0..5)
+        .map(|i| remote_path.join(format!("f{i}")))
+        .collect();
+    for remote in &remotes {
+        std::fs::write(remote, vec![b'a'; 300_000]).unwrap();
+    }
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    assert!(stall(&["init"]).status.success());
+    for remote in &remotes {
+        assert!(stall(&["add", remote.to_str().unwrap()]).status.success());
+    }
+    assert!(stall(&["collect"]).status.success());
+    for remote in &remotes {
+        std::fs::remove_file(remote).unwrap();
+    }
+
+    // Each throttled copy takes over half a second, so the distribute times
+    // out before every file is copied.
+    let output = stall(&["distribute", "--throttle", "0.5", "--timeout", "1"]);
+    assert_eq!(output.status.code(), Some(124));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("not processed"));
+    assert!(!remotes.iter().all(|remote| remote.exists()));
+    for entry in std::fs::read_dir(remote_path).unwrap() {
+        let name = entry.unwrap().file_name();
+        assert!(!name.to_string_lossy().contains(".stall-tmp"));
+    }
+
+    assert!(!stall(&["distribute", "--timeout", "0"]).status.success());
+}
+
+#[test]
+#[tracing::instrument]
+pub fn collect_distribute_preserve() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    std::fs::write(remote_path.join("config"), "remote").unwrap();
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall(stall_path,
+        &["add", remote_path.join("config").to_str().unwrap()]);
+    assert!(output.status.success());
+
+    // Attributes are copied after the contents, or a warning is emitted.
+    let output = stall(stall_path, &["collect", "--preserve", "xattr,acl"]);
+    println!("{}", String::from_utf8(output.stderr).unwrap());
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(stall_path.join("config")).unwrap(),
+        "remote");
+
+    std::fs::write(stall_path.join("config"), "local").unwrap();
+    let output = stall(stall_path,
+        &["distribute", "--force", "--preserve", "xattr",
+            "--allow", "preserve-failed"]);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(remote_path.join("config")).unwrap(),
+        "local");
+
+    let output = stall(stall_path, &["collect", "--preserve", "mtime"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+#[tracing::instrument]
+pub fn collect_copy_mode() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    std::fs::write(remote_path.join("config"), "remote").unwrap();
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall(stall_path,
+        &["add", remote_path.join("config").to_str().unwrap()]);
+    assert!(output.status.success());
+
+    // The mode used is reported in verbose output.
+    let output = stall(stall_path,
+        &["collect", "--verbose", "--copy-mode", "standard"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("using standard copy"));
+
+    // Auto mode falls back to a standard copy if reflinks aren't supported.
+    let output = stall(stall_path, &["collect", "--verbose", "--force",
+        "--copy-mode", "auto"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    println!("{stdout}");
+    assert!(stdout.contains("using standard copy")
+        || stdout.contains("using reflink copy"));
+    assert_eq!(std::fs::read_to_string(stall_path.join("config")).unwrap(),
+        "remote");
+}
+
+#[test]
+#[tracing::instrument]
+pub fn collect_throttle() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    let contents = vec![b'x'; 200_000];
+    std::fs::write(remote_path.join("config"), &contents).unwrap();
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall(stall_path,
+        &["add", remote_path.join("config").to_str().unwrap()]);
+    assert!(output.status.success());
+
+    // Invalid rates are rejected.
+    let output = stall(stall_path, &["collect", "--throttle", "0"]);
+    assert!(!output.status.success());
+
+    // Copying 200 KB at 0.5 MB/s takes at least 0.4 seconds.
+    let start = std::time::Instant::now();
+    let output = stall(stall_path, &["collect", "--copy-mode", "standard",
+        "--throttle", "0.5"]);
+    assert!(output.status.success());
+    assert!(start.elapsed() >= std::time::Duration::from_millis(400));
+    assert_eq!(std::fs::read(stall_path.join("config")).unwrap(), contents);
+
+    // The modification time is preserved, so the files compare equal.
+    let modified = |path: &Path| std::fs::metadata(path)
+        .unwrap()
+        .modified()
+        .unwrap();
+    assert_eq!(
+        modified(&stall_path.join("config")),
+        modified(&remote_path.join("config")));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn copy_buffer() {
+    use crate::entry::BufferSize;
+
+    let parse = |text: &str| text.parse::<BufferSize>().map(BufferSize::bytes);
+    assert_eq!(parse("65536"), Ok(64 * 1024));
+    assert_eq!(parse("256K"), Ok(256 * 1024));
+    assert_eq!(parse("4m"), Ok(4 * 1024 * 1024));
+    assert_eq!(parse("1000"), Ok(4 * 1024));
+    assert!(parse("0").is_err());
+    assert!(parse("65M").is_err());
+    assert!(parse("fast").is_err());
+    assert_eq!(BufferSize::DEFAULT.to_string(), "1M");
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+
+    let contents: Vec<u8> = (0..300_000u32)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    std::fs::write(remote_path.join("data"), &contents).unwrap();
+
+    let prefs = |args: &[&str]| stall_command()
+        .arg("prefs")
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(args)
+        .output()
+        .unwrap();
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    assert!(!prefs(&["set", "copy_buffer", "0"]).status.success());
+    assert!(prefs(&["set", "copy_buffer", "4K"]).status.success());
+    assert!(std::fs::read_to_string(&prefs_path)
+        .unwrap()
+        .contains("\"4K\""));
+
+    assert!(stall(&["init"]).status.success());
+    assert!(stall(&["add", remote_path.join("data").to_str().unwrap()])
+        .status
+        .success());
+    let output = stall(&["collect", "--copy-mode", "standard"]);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(stall_path.join("data")).unwrap(), contents);
+
+    // Permissions and modification times are preserved.
+    let metadata = |path: &Path| std::fs::metadata(path).unwrap();
+    assert_eq!(
+        metadata(&stall_path.join("data")).modified().unwrap(),
+        metadata(&remote_path.join("data")).modified().unwrap());
+    assert_eq!(
+        metadata(&stall_path.join("data")).permissions(),
+        metadata(&remote_path.join("data")).permissions());
+
+    // Throttled copies use the buffer in chunks.
+    let changed: Vec<u8> = contents.iter().rev().copied().collect();
+    std::fs::write(stall_path.join("data"), &changed).unwrap();
+    let output = stall(&["distribute", "--force", "--copy-mode", "standard",
+        "--throttle", "100"]);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(remote_path.join("data")).unwrap(), changed);
+}
+
+#[test]
+#[tracing::instrument]
+pub fn retry_policy() {
+    use crate::entry::Retry;
+    use crate::error::StallError;
+    use std::io::ErrorKind;
+    use std::time::Duration;
+
+    let parse = |text: &str| text
+        .parse::<Retry>()
+        .map(|retry| (retry.retries(), retry.delay()));
+    assert_eq!(parse("5"), Ok((5, Duration::from_secs(1))));
+    assert_eq!(parse("5x2s"), Ok((5, Duration::from_secs(2))));
+    assert_eq!(parse("2x500ms"), Ok((2, Duration::from_millis(500))));
+    assert_eq!(parse("0"), Ok((0, Duration::from_secs(1))));
+    assert!(parse("-1").is_err());
+    assert!(parse("3x2m").is_err());
+    assert!(parse("3x90s").is_err());
+    assert_eq!(Retry::DEFAULT.to_string(), "3x1s");
+    assert_eq!(
+        "2x500ms".parse::<Retry>().map(|retry| retry.to_string()),
+        Ok("2x500ms".to_owned()));
+
+    let io_error = |kind: ErrorKind| StallError::Io {
+        context: "failed to copy".to_owned(),
+        path: None,
+        source: std::io::Error::from(kind),
+    };
+    assert!(io_error(ErrorKind::WouldBlock).is_transient());
+    assert!(io_error(ErrorKind::StaleNetworkFileHandle).is_transient());
+    assert!(!io_error(ErrorKind::NotFound).is_transient());
+    assert!(!io_error(ErrorKind::PermissionDenied).is_transient());
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::write(remote_path.join("config"), "remote").unwrap();
+
+    let prefs = |args: &[&str]| stall_command()
+        .arg("prefs")
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(args)
+        .output()
+        .unwrap();
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    assert!(!prefs(&["set", "retry", "often"]).status.success());
+    assert!(prefs(&["set", "retry", "5x2s"]).status.success());
+    assert!(std::fs::read_to_string(&prefs_path)
+        .unwrap()
+        .contains("\"5x2s\""));
+
+    assert!(stall(&["init"]).status.success());
+    assert!(stall(&["add", remote_path.join("config").to_str().unwrap()])
+        .status
+        .success());
+    assert!(!stall(&["collect", "--retry", "often"]).status.success());
+    let output = stall(&["collect", "--retry", "0"]);
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(stall_path.join("config")).unwrap(),
+        "remote");
+}
+
+#[test]
+#[tracing::instrument]
+pub fn verify_copies() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::write(remote_path.join("config"), "remote").unwrap();
+    std::fs::create_dir(remote_path.join("dir")).unwrap();
+    std::fs::write(remote_path.join("dir/file"), "remote").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    assert!(stall(&["init"]).status.success());
+    assert!(stall(&[
+            "add",
+            remote_path.join("config").to_str().unwrap(),
+            remote_path.join("dir").to_str().unwrap()])
+        .status
+        .success());
+    let output = stall(&["collect", "--verify"]);
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(stall_path.join("dir/file")).unwrap(),
+        "remote");
+
+    std::fs::write(stall_path.join("config"), "stalled").unwrap();
+    std::fs::write(stall_path.join("dir/file"), "stalled").unwrap();
+    let output = stall(&["distribute", "--verify", "--force"]);
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr)
+        .contains("failed verification"));
+    assert_eq!(
+        std::fs::read_to_string(remote_path.join("config")).unwrap(),
+        "stalled");
+    assert_eq!(
+        std::fs::read_to_string(remote_path.join("dir/file")).unwrap(),
+        "stalled");
+}
+
+#[test]
+#[tracing::instrument]
+pub fn clean_orphaned_files() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::write(remote_path.join("a"), "a").unwrap();
+    std::fs::write(remote_path.join("b"), "b").unwrap();
+    std::fs::create_dir(remote_path.join("dir")).unwrap();
+    std::fs::write(remote_path.join("dir/file"), "file").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(stall(&["init"]).status.success());
+    assert!(stall(&[
+            "add",
+            "--collect",
+            remote_path.join("a").to_str().unwrap(),
+            remote_path.join("b").to_str().unwrap(),
+            remote_path.join("dir").to_str().unwrap()])
+        .status
+        .success());
+    assert!(stall(&["rm", "b"]).status.success());
+    std::fs::create_dir_all(stall_path.join("old/nested")).unwrap();
+    std::fs::write(stall_path.join("old/nested/file"), "old").unwrap();
+    std::fs::write(stall_path.join(".gitignore"), "").unwrap();
+
+    let output = stall(&["clean", "--dry-run"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "Trash b\nTrash old\n");
+
+    // Files are only removed after confirmation.
+    let output = stall(&["clean"]);
+    assert!(output.status.success());
+    assert!(file_exists(stall_path.join("b")));
+
+    let output = stall(&["clean", "--yes"]);
+    assert!(output.status.success());
+    assert!(!file_exists(stall_path.join("b")));
+    assert!(!file_exists(stall_path.join("old")));
+    assert!(file_exists(stall_path.join("a")));
+    assert!(file_exists(stall_path.join("dir/file")));
+    assert!(file_exists(stall_path.join(".gitignore")));
+    let output = stall(&["trash", "list"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(stall_path.join("b").to_str().unwrap()));
+
+    std::fs::write(stall_path.join("c"), "c").unwrap();
+    let output = stall(&["clean", "--yes", "--no-trash"]);
+    assert!(output.status.success());
+    assert!(!file_exists(stall_path.join("c")));
+    let output = stall(&["clean"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "No orphaned files in stall directory.\n");
+}
+
+#[test]
+#[tracing::instrument]
+pub fn prune_missing_remotes() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::write(remote_path.join("a"), "a").unwrap();
+    std::fs::write(remote_path.join("b"), "b").unwrap();
+    std::fs::write(remote_path.join("c"), "c").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(stall(&["init"]).status.success());
+    assert!(stall(&[
+            "add",
+            "--collect",
+            remote_path.join("a").to_str().unwrap(),
+            remote_path.join("b").to_str().unwrap(),
+            remote_path.join("c").to_str().unwrap()])
+        .status
+        .success());
+    std::fs::remove_file(remote_path.join("b")).unwrap();
+    std::fs::remove_file(remote_path.join("c")).unwrap();
+    assert!(stall(&["flag", "c", "--archive"]).status.success());
+
+    // Archived entries are skipped by default.
+    let output = stall(&["prune", "--dry-run"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, format!("Remove b ({})\n",
+        remote_path.join("b").display()));
+
+    // Entries are only removed after confirmation.
+    let output = stall(&["prune"]);
+    assert!(output.status.success());
+    let output = stall(&["list"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(remote_path.join("b").to_str().unwrap()));
+
+    let output = stall(&["prune", "--yes", "--delete"]);
+    assert!(output.status.success());
+    let output = stall(&["list"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains(remote_path.join("b").to_str().unwrap()));
+    assert!(stdout.contains(remote_path.join("a").to_str().unwrap()));
+    assert!(!file_exists(stall_path.join("b")));
+    let output = stall(&["trash", "list"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(stall_path.join("b").to_str().unwrap()));
+
+    // Stalled files are kept unless --delete is given.
+    let output = stall(&["prune", "--yes", "--include-archived"]);
+    assert!(output.status.success());
+    assert!(file_exists(stall_path.join("c")));
+    let output = stall(&["prune", "--include-archived"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "No entries with missing remote files.\n");
+}
+
+#[test]
+#[tracing::instrument]
+pub fn relocate_moved_remotes() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let home_path = temp_dir_b.path();
+    let prefs_path = home_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::write(home_path.join(".arc"), "a").unwrap();
+    std::fs::write(home_path.join(".brc"), "b").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .env("HOME", home_path)
+        .env("XDG_CONFIG_HOME", home_path.join(".config"))
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(stall(&["init"]).status.success());
+    assert!(stall(&[
+            "add",
+            home_path.join(".arc").to_str().unwrap(),
+            home_path.join(".brc").to_str().unwrap()])
+        .status
+        .success());
+    assert!(stall(&["collect"]).status.success());
+
+    // The moved file is found by its inode, even though it has changed.
+    std::fs::create_dir_all(home_path.join(".config/a")).unwrap();
+    std::fs::rename(home_path.join(".arc"), home_path.join(".config/a/rc"))
+        .unwrap();
+    std::fs::write(home_path.join(".config/a/rc"), "new a").unwrap();
+    // The copied file is found by its contents.
+    std::fs::create_dir(home_path.join("other")).unwrap();
+    std::fs::write(home_path.join("other/brc"), "b").unwrap();
+    std::fs::remove_file(home_path.join(".brc")).unwrap();
+
+    let output = stall(&["relocate", "--dry-run"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, format!(
+        "Relocate .arc from {} to {} (same file)\n\
+        No new location found for .brc ({})\n",
+        home_path.join(".arc").display(),
+        home_path.join(".config/a/rc").display(),
+        home_path.join(".brc").display()));
+
+    // Entries are only updated after confirmation.
+    let output = stall(&["relocate", "--scan", "other"]);
+    assert!(output.status.success());
+    let output = stall(&["list"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(home_path.join(".arc").to_str().unwrap()));
+
+    let output = stall(&["relocate", "--scan", "other", "--yes"]);
+    assert!(output.status.success());
+    let output = stall(&["list"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(home_path.join(".config/a/rc").to_str().unwrap()));
+    assert!(stdout.contains(home_path.join("other/brc").to_str().unwrap()));
+    let output = stall(&["relocate"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "No entries with missing remote files.\n");
+}
+
+#[test]
+#[tracing::instrument]
+pub fn entry_order() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+
+    // The remote paths are ordered in reverse, and the sizes are unordered.
+    let remotes = [("z", "a", 10), ("y", "b", 30), ("x", "c", 1)];
+    for (dir, name, size) in remotes {
+        std::fs::create_dir(remote_path.join(dir)).unwrap();
+        std::fs::write(remote_path.join(dir).join(name), "-".repeat(size))
+            .unwrap();
+    }
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    // Returns the names of the remote files in the order they are printed.
+    let printed = |args: &[&str]| {
+        let output = stall(args);
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let mut names: Vec<_> = remotes
+            .iter()
+            .map(|(dir, name, _)| (stdout
+                .find(&format!("{dir}/{name}"))
+                .expect("find entry"), *name))
+            .collect();
+        names.sort_unstable();
+        names.into_iter().map(|(_, name)| name).collect::<Vec<_>>()
+    };
+
+    assert!(stall(&["init"]).status.success());
+    for (dir, name, _) in remotes {
+        assert!(stall(&["add", remote_path.join(dir).join(name)
+                .to_str()
+                .unwrap()])
+            .status
+            .success());
+    }
+    assert!(stall(&["collect"]).status.success());
+
+    assert_eq!(printed(&["status"]), ["a", "b", "c"]);
+    assert_eq!(printed(&["status", "--order", "remote"]), ["c", "b", "a"]);
+    assert_eq!(printed(&["status", "--order", "size"]), ["c", "a", "b"]);
+
+    // Selected entries are processed in order and only once.
+    assert_eq!(printed(&["collect", "--dry-run", "c", "a", "c", "b"]),
+        ["a", "b", "c"]);
+    assert_eq!(printed(&["collect", "--dry-run", "--order", "remote"]),
+        ["c", "b", "a"]);
+    let output = stall(&["collect", "--dry-run", "c", "c"]);
+    assert_eq!(String::from_utf8(output.stdout)
+        .unwrap()
+        .matches("x/c")
+        .count(), 1);
+}
+
+#[test]
+#[tracing::instrument]
+pub fn archived_entries() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::write(remote_path.join("current"), "current").unwrap();
+    std::fs::write(remote_path.join("obsolete"), "obsolete").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    let stdout = |args: &[&str]| String::from_utf8(stall(args).stdout)
+        .unwrap();
+
+    assert!(stall(&["init"]).status.success());
+    for name in ["current", "obsolete"] {
+        assert!(stall(&["add", remote_path.join(name).to_str().unwrap()])
+            .status
+            .success());
+    }
+    assert!(stall(&["collect"]).status.success());
+    assert!(stall(&["flag", "obsolete", "--archive"]).status.success());
+    assert_eq!(stdout(&["flag", "obsolete"]), "obsolete: archived\n");
+    assert!(std::fs::read_to_string(stall_path.join(".stall"))
+        .unwrap()
+        .contains("archived:true"));
+
+    // Archived entries are skipped unless they are included.
+    std::fs::write(remote_path.join("obsolete"), "changed").unwrap();
+    assert!(!stdout(&["status"]).contains("obsolete"));
+    assert!(stdout(&["status", "--include-archived"]).contains("obsolete"));
+    assert!(!stdout(&["collect"]).contains("obsolete"));
+    assert_eq!(std::fs::read_to_string(stall_path.join("obsolete")).unwrap(),
+        "obsolete");
+    assert!(stall(&["collect", "--force", "--include-archived"])
+        .status
+        .success());
+    assert_eq!(std::fs::read_to_string(stall_path.join("obsolete")).unwrap(),
+        "changed");
+
+    assert!(stall(&["flag", "obsolete", "--unarchive"]).status.success());
+    assert!(stdout(&["status"]).contains("obsolete"));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn entry_groups() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    for name in ["vimrc", "editorconfig", "bashrc"] {
+        std::fs::write(remote_path.join(name), name).unwrap();
+    }
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    let stdout = |args: &[&str]| String::from_utf8(stall(args).stdout)
+        .unwrap();
+
+    assert!(stall(&["init"]).status.success());
+    for name in ["vimrc", "editorconfig", "bashrc"] {
+        assert!(stall(&["add", remote_path.join(name).to_str().unwrap()])
+            .status
+            .success());
+    }
+    assert!(stall(&["group", "create", "editor", "vimrc"]).status.success());
+    assert!(!stall(&["group", "create", "@editor"]).status.success());
+    assert!(stall(&["group", "add", "@editor", "editorconfig"])
+        .status
+        .success());
+    assert_eq!(stdout(&["group", "list", "editor"]),
+        "editorconfig\nvimrc\n");
+
+    // Group arguments select only the group's entries.
+    assert!(stall(&["collect", "@editor"]).status.success());
+    assert!(stall_path.join("vimrc").exists());
+    assert!(stall_path.join("editorconfig").exists());
+    assert!(!stall_path.join("bashrc").exists());
+    assert!(!stall(&["collect", "@missing"]).status.success());
+
+    // Memberships follow renamed entries and are dropped with removed ones.
+    assert!(stall(&["mv", "vimrc", "init.vim"]).status.success());
+    assert_eq!(stdout(&["group", "list", "editor"]),
+        "editorconfig\ninit.vim\n");
+    assert!(stall(&["rm", "editorconfig"]).status.success());
+    assert_eq!(stdout(&["group", "list", "editor"]), "init.vim\n");
+
+    assert!(!stall(&["group", "rm", "editor", "bashrc"]).status.success());
+    assert!(stall(&["group", "rm", "editor", "init.vim"]).status.success());
+    assert_eq!(stdout(&["group", "list", "editor"]), "");
+    assert!(stall(&["group", "rm", "editor"]).status.success());
+    assert!(!stall(&["group", "list", "editor"]).status.success());
+    assert!(!std::fs::read_to_string(stall_path.join(".stall"))
+        .unwrap()
+        .contains("groups"));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn entry_dependencies() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    for name in ["a", "b", "c"] {
+        std::fs::write(remote_path.join(name), name).unwrap();
+    }
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    let stdout = |args: &[&str]| String::from_utf8(stall(args).stdout)
+        .unwrap();
+    let distributed = |args: &[&str]| stdout(args)
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(3).map(str::to_owned))
+        .filter(|name| ["a", "b", "c"].contains(&name.as_str()))
+        .collect::<Vec<_>>();
+
+    assert!(stall(&["init"]).status.success());
+    for name in ["a", "b", "c"] {
+        assert!(stall(&["add", remote_path.join(name).to_str().unwrap()])
+            .status
+            .success());
+    }
+    assert!(stall(&["collect"]).status.success());
+    assert!(stall(&["flag", "a", "--after", "b"]).status.success());
+    assert!(stall(&["flag", "b", "--after", "c"]).status.success());
+    assert_eq!(stdout(&["flag", "a"]), "a: -\n    after: b\n");
+
+    // Dependencies are distributed first, including those of unselected
+    // entries.
+    assert_eq!(distributed(&["distribute", "--force"]), ["c", "b", "a"]);
+    assert_eq!(distributed(&["distribute", "--force", "a", "c"]), ["c", "a"]);
+
+    // Cycles are rejected without modifying the stall.
+    assert!(!stall(&["flag", "c", "--after", "a"]).status.success());
+    assert_eq!(stdout(&["flag", "c"]), "c: -\n");
+
+    // Dependencies follow renamed entries and are dropped with removed ones.
+    assert!(stall(&["mv", "b", "d"]).status.success());
+    assert_eq!(stdout(&["flag", "a"]), "a: -\n    after: d\n");
+    assert!(stall(&["rm", "d"]).status.success());
+    assert_eq!(stdout(&["flag", "a"]), "a: -\n");
+}
+
+#[test]
+#[tracing::instrument]
+pub fn plan_out_apply_plan() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    let plan_path = remote_path.join("plan.json");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::write(remote_path.join("a"), "a").unwrap();
+    std::fs::write(remote_path.join("b"), "b").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    assert!(stall(&["init"]).status.success());
+    for name in ["a", "b"] {
+        assert!(stall(&["add", remote_path.join(name).to_str().unwrap()])
+            .status
+            .success());
+    }
+    assert!(stall(&["collect"]).status.success());
+    std::fs::write(stall_path.join("a"), "changed").unwrap();
+
+    // The plan records only the files the dry run would copy.
+    assert!(!stall(&["distribute", "--plan-out", plan_path.to_str().unwrap()])
+        .status
+        .success());
+    assert!(stall(&[
+            "distribute",
+            "--dry-run",
+            "--plan-out",
+            plan_path.to_str().unwrap()])
+        .status
+        .success());
+    let plan = crate::plan::Plan::read_from_path(&plan_path).unwrap();
+    assert_eq!(plan.command(), crate::plan::PlanCommand::Distribute);
+    assert_eq!(plan.steps().len(), 1);
+    assert_eq!(plan.steps()[0].local, Path::new("a"));
+    assert_eq!(std::fs::read_to_string(remote_path.join("a")).unwrap(), "a");
+
+    // Plans are applied verbatim, unless the planned files have changed.
+    std::fs::write(stall_path.join("b"), "changed").unwrap();
+    assert!(stall(&["apply-plan", plan_path.to_str().unwrap()])
+        .status
+        .success());
+    assert_eq!(std::fs::read_to_string(remote_path.join("a")).unwrap(),
+        "changed");
+    assert_eq!(std::fs::read_to_string(remote_path.join("b")).unwrap(), "b");
+    std::fs::write(stall_path.join("a"), "changed again").unwrap();
+    assert!(!stall(&["apply-plan", plan_path.to_str().unwrap()])
+        .status
+        .success());
+    assert_eq!(std::fs::read_to_string(remote_path.join("a")).unwrap(),
+        "changed");
+    assert!(stall(&["apply-plan", plan_path.to_str().unwrap(), "--force"])
+        .status
+        .success());
+    assert_eq!(std::fs::read_to_string(remote_path.join("a")).unwrap(),
+        "changed again");
+
+    // Shell plans make the same copies.
+    let script_path = remote_path.join("plan.sh");
+    assert!(stall(&[
+            "distribute",
+            "--dry-run",
+            "--plan-out",
+            script_path.to_str().unwrap()])
+        .status
+        .success());
+    let script = std::fs::read_to_string(&script_path).unwrap();
+    assert!(script.starts_with("#!/bin/sh\n"));
+    assert!(script.contains(&format!("copy \"$STALL_DIR\"/'b' '{}'",
+        remote_path.join("b").display())));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn confirm_plan() {
+    use std::io::Write as _;
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let prefs_path = remote_path.join("prefs");
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    for name in ["a", "b", "c"] {
+        std::fs::write(remote_path.join(name), name).unwrap();
+    }
+
+    let stall = |args: &[&str], input: &str| {
+        let mut child = stall_command()
+            .arg(args[0])
+            .arg("--stall")
+            .arg(stall_path)
+            .arg("--prefs")
+            .arg(&prefs_path)
+            .args(&args[1..])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    };
+    let write_later = |path: &Path, contents: &str, secs: u64| {
+        std::fs::write(path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now()
+                + std::time::Duration::from_secs(secs))
+            .unwrap();
+    };
+    let remote = |name: &str| std::fs::read_to_string(remote_path.join(name))
+        .ok();
+
+    assert!(stall(&["init"], "").status.success());
+    for name in ["a", "b", "c"] {
+        assert!(stall(&["add", remote_path.join(name).to_str().unwrap()], "")
+            .status
+            .success());
+    }
+    assert!(stall(&["collect"], "").status.success());
+
+    // One file is overwritten, one is created, and one is in conflict.
+    write_later(&stall_path.join("a"), "local a", 1000);
+    std::fs::remove_file(remote_path.join("b")).unwrap();
+    write_later(&stall_path.join("c"), "local c", 1000);
+    write_later(&remote_path.join("c"), "remote c", 2000);
+
+    // The whole plan is printed before anything is copied.
+    let output = stall(&[
+        "distribute",
+        "--confirm-plan",
+        "--conflict",
+        "fail"], "n\n");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("overwrite {}",
+        remote_path.join("a").display())));
+    assert!(stdout.contains(&format!("create    {}",
+        remote_path.join("b").display())));
+    assert!(stdout.contains("conflict  c: "));
+    assert!(stdout.contains("1 to create, 1 to overwrite, 1 not copied"));
+    assert!(stdout.contains("No files copied."));
+    assert_eq!(remote("a").as_deref(), Some("a"));
+    assert_eq!(remote("b"), None);
+
+    // Once confirmed, only the planned files are copied.
+    let output = stall(&[
+        "distribute",
+        "--confirm-plan",
+        "--conflict",
+        "fail"], "y\n");
+    assert!(!output.status.success());
+    assert_eq!(remote("a").as_deref(), Some("local a"));
+    assert_eq!(remote("b").as_deref(), Some("b"));
+    assert_eq!(remote("c").as_deref(), Some("remote c"));
+
+    assert!(!stall(&["distribute", "--confirm-plan", "--dry-run"], "")
+        .status
+        .success());
+
+    // Recursive collects can't be confirmed as a single plan.
+    let output = stall(&["collect", "--confirm-plan", "--recursive"], "y\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn remote_ssh_path_parse() {
+    use crate::remote::SshPath;
+
+    let parse = |text: &str| SshPath::parse(Path::new(text))
+        .map(|ssh| ssh.to_string());
+
+    assert_eq!(parse("ssh://host/etc/hosts").as_deref(),
+        Some("ssh://host/etc/hosts"));
+    assert_eq!(parse("ssh://user@host:2222/a/../b").as_deref(),
+        Some("ssh://user@host:2222/b"));
+    assert_eq!(parse("host:.bashrc").as_deref(),
+        Some("ssh://host/~/.bashrc"));
+    assert_eq!(parse("host:~/.bashrc").as_deref(),
+        Some("ssh://host/~/.bashrc"));
+    assert_eq!(parse("host:/etc/hosts").as_deref(),
+        Some("ssh://host/etc/hosts"));
+    assert_eq!(parse("/home/a:b"), None);
+    assert_eq!(parse("./a:b"), None);
+    assert_eq!(parse("C:/a"), None);
+    assert_eq!(parse("ssh://host:port/a"), None);
+    assert_eq!(parse("user@10.0.0.1:a").as_deref(),
+        Some("ssh://user@10.0.0.1/~/a"));
+    assert_eq!(parse("@host:a"), None);
+    assert_eq!(parse("my host:a"), None);
+    assert_eq!(parse("-oProxyCommand=x:a"), None);
+
+    assert_eq!(crate::path::clean(Path::new("host:a/./b")),
+        Path::new("ssh://host/~/a/b"));
+}
+
+#[cfg(unix)]
+#[test]
+#[tracing::instrument]
+pub fn collect_distribute_ssh() {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let temp_dir_c = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let bin_path = temp_dir_c.path();
+
+    // Stand-in ssh and scp commands which access the local machine.
+    let scripts = [
+        ("ssh", "#!/bin/sh\n\
+            while [ \"$1\" != \"--\" ]; do shift; done\n\
+            exec sh -c \"$3\"\n"),
+        ("scp", "#!/bin/sh\n\
+            while getopts OprqP:l: opt; do :; done\n\
+            shift $((OPTIND - 1))\n\
+            unquote() { case \"$1\" in \
+                *:*) eval \"printf '%s' ${1#*:}\" ;; \
+                *) printf '%s' \"$1\" ;; esac; }\n\
+            exec cp -R -p \"$(unquote \"$1\")\" \"$(unquote \"$2\")\"\n"),
+    ];
+    for (name, script) in scripts {
+        let path = bin_path.join(name);
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .unwrap();
+    }
+    let search_path = std::env::join_paths(
+        std::iter::once(bin_path.to_path_buf())
+            .chain(std::env::split_paths(&std::env::var_os("PATH").unwrap())))
+        .unwrap();
+
+    // Remote paths are quoted for the remote shell.
+    std::fs::write(remote_path.join("my config"), "remote").unwrap();
+    let remote = format!("ssh://host{}",
+        remote_path.join("my config").display());
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .args(&args[1..])
+        .env("PATH", &search_path)
+        .output()
+        .unwrap();
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall(&["add", &remote]);
+    assert!(output.status.success());
+
+    let output = stall(&["collect"]);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(stall_path.join("my config")).unwrap(),
+        "remote");
+
+    std::fs::write(stall_path.join("my config"), "local").unwrap();
+    let output = stall(&["distribute", "--force"]);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(remote_path.join("my config")).unwrap(),
+        "local");
+}
+
+#[test]
+#[tracing::instrument]
+pub fn remote_backend_selection() {
+    use crate::remote::backend;
+    use crate::remote::RemoteBackend;
+
+    let scheme = |text: &str| backend(Path::new(text))
+        .map(RemoteBackend::scheme);
+
+    assert_eq!(scheme("/etc/hosts").unwrap(), "file");
+    assert_eq!(scheme("file:///etc/hosts").unwrap(), "file");
+    assert_eq!(scheme("ssh://host/etc/hosts").unwrap(), "ssh");
+    assert_eq!(scheme("host:.bashrc").unwrap(), "ssh");
+    assert!(scheme("s3://bucket/config").is_err());
+
+    assert_eq!(crate::path::clean(Path::new("file:///etc/./hosts")),
+        Path::new("/etc/hosts"));
+    assert_eq!(crate::path::clean(Path::new("s3://bucket/./config")),
+        Path::new("s3://bucket/./config"));
+
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir.path();
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Remote paths with unsupported schemes can't be added.
+    let output = stall_command()
+        .args(["add", "--stall"])
+        .arg(stall_path)
+        .arg("s3://bucket/config")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    // Relative remote paths of existing local files are not ssh paths.
+    std::fs::write(stall_path.join(".stall"), "a -> host:a\n").unwrap();
+    let list = || String::from_utf8(stall_command()
+        .arg("list")
+        .current_dir(stall_path)
+        .output()
+        .unwrap()
+        .stdout)
+        .unwrap();
+    assert_eq!(list(), "a\tssh://host/~/a\n");
+    create_file(stall_path.join("host:a"));
+    assert_eq!(list(), "a\thost:a\n");
+}
+
+#[test]
+#[tracing::instrument]
+pub fn archive_unpack_round_trip() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let temp_dir_c = TempDir::new().expect("create temp dir");
+    let gnupg_dir = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let archive_path = temp_dir_c.path();
+
+    std::fs::write(remote_path.join("config"), "config").unwrap();
+    std::fs::write(remote_path.join("rc"), "rc").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .args(args)
+        .current_dir(stall_path)
+        .env("GNUPGHOME", gnupg_dir.path())
+        .env("STALL_PASSPHRASE", "correct horse")
+        .output()
+        .unwrap();
+
+    assert!(stall(&["init", "."]).status.success());
+    assert!(stall(&["add",
+        remote_path.join("config").to_str().unwrap(),
+        remote_path.join("rc").to_str().unwrap()]).status.success());
+    assert!(stall(&["collect"]).status.success());
+
+    for (name, encrypt) in [
+        ("stall.tar.gz", false),
+        ("stall.zip", false),
+        ("stall.tar.gpg", true),
+    ] {
+        let archive = archive_path.join(name);
+        let mut args = vec!["archive", archive.to_str().unwrap()];
+        if encrypt {
+            args.push("--encrypt");
+        }
+        let output = stall(&args);
+        assert!(output.status.success(), "{output:?}");
+        if encrypt {
+            assert!(!std::fs::read(&archive).unwrap().windows(6)
+                .any(|w| w == b"config"));
+        }
+
+        // Existing archives are only overwritten with --force.
+        assert!(!stall(&args).status.success());
+        args.push("--force");
+        assert!(stall(&args).status.success());
+
+        // Unpack into an empty directory.
+        let target_dir = TempDir::new().expect("create temp dir");
+        let target = target_dir.path().to_str().unwrap();
+        let output = stall(&["unpack", archive.to_str().unwrap(), target]);
+        assert!(output.status.success(), "{output:?}");
+        for file in [".stall", "config", "rc"] {
+            assert_eq!(
+                std::fs::read(target_dir.path().join(file)).unwrap(),
+                std::fs::read(stall_path.join(file)).unwrap());
+        }
+
+        // Existing files are only overwritten with --force.
+        std::fs::write(target_dir.path().join("rc"), "changed").unwrap();
+        let output = stall(&["unpack", archive.to_str().unwrap(), target]);
+        assert!(!output.status.success());
+        let output = stall(&["unpack", archive.to_str().unwrap(), target,
+            "--force"]);
+        assert!(output.status.success());
+        assert_eq!(
+            std::fs::read_to_string(target_dir.path().join("rc")).unwrap(),
+            "rc");
+    }
+
+    // Archives with links are not unpacked, as later members could be
+    // written through them.
+    #[cfg(unix)]
+    for (name, create) in [
+        ("links.tar", &["tar", "-cf"][..]),
+        ("links.zip", &["zip", "-q", "-r", "-y"][..]),
+    ] {
+        let source_dir = TempDir::new().expect("create temp dir");
+        let outside_dir = TempDir::new().expect("create temp dir");
+        std::os::unix::fs::symlink(outside_dir.path(),
+            source_dir.path().join("link")).unwrap();
+        let archive = archive_path.join(name);
+        let status = std::process::Command::new(create[0])
+            .args(&create[1..])
+            .arg(&archive)
+            .arg("link")
+            .current_dir(source_dir.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let target_dir = TempDir::new().expect("create temp dir");
+        let output = stall(&["unpack", archive.to_str().unwrap(),
+            target_dir.path().to_str().unwrap()]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains("archive member is a link"), "{output:?}");
+        assert!(!target_dir.path().join("link").exists());
+    }
+}
+
+#[test]
+#[tracing::instrument]
+pub fn bootstrap_confirm_conflicts() {
+    use std::io::Write as _;
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    std::fs::create_dir_all(remote_path.join("sub")).unwrap();
+    std::fs::write(remote_path.join("sub/config"), "config").unwrap();
+    std::fs::write(remote_path.join("rc"), "rc").unwrap();
+
+    let stall = |args: &[&str], input: &str| {
+        let mut child = stall_command()
+            .arg(args[0])
+            .arg("--stall")
+            .arg(stall_path)
+            .args(&args[1..])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(&["add",
+        remote_path.join("sub/config").to_str().unwrap(),
+        remote_path.join("rc").to_str().unwrap()], "").status.success());
+    assert!(stall(&["collect"], "").status.success());
+
+    // Simulate a new machine: the remote directory is missing, and a
+    // different file exists at a remote path.
+    std::fs::remove_dir_all(remote_path.join("sub")).unwrap();
+    std::fs::write(remote_path.join("rc"), "default rc").unwrap();
+    let earlier = std::time::SystemTime::now()
+        - std::time::Duration::from_secs(1000);
+    File::options()
+        .write(true)
+        .open(remote_path.join("rc"))
+        .unwrap()
+        .set_modified(earlier)
+        .unwrap();
+
+    // Declined files are skipped, and missing directories are created.
+    let output = stall(&["bootstrap"], "n\n");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Overwrite"));
+    assert_eq!(
+        std::fs::read_to_string(remote_path.join("sub/config")).unwrap(),
+        "config");
+    assert_eq!(
+        std::fs::read_to_string(remote_path.join("rc")).unwrap(),
+        "default rc");
+
+    // The end of input declines the overwrite.
+    let output = stall(&["bootstrap"], "");
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(remote_path.join("rc")).unwrap(),
+        "default rc");
+
+    // Confirmed files are overwritten.
+    let output = stall(&["bootstrap"], "maybe\ny\n");
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(remote_path.join("rc")).unwrap(),
+        "rc");
+}
+
+#[test]
+#[tracing::instrument]
+pub fn stallignore_directory_and_init_from_dir() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let temp_dir_c = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    let init_path = temp_dir_c.path();
+
+    let app = remote_path.join("app");
+    std::fs::create_dir_all(app.join("cache")).unwrap();
+    std::fs::create_dir_all(app.join("sub")).unwrap();
+    for file in ["a.conf", "b.lock", "keep.lock", "cache/data", "sub/c.conf"] {
+        std::fs::write(app.join(file), file).unwrap();
+    }
+
+    let ignore = "# Generated files.\n*.lock\n!keep.lock\ncache/\n";
+    std::fs::write(stall_path.join(".stallignore"), ignore).unwrap();
+    std::fs::write(init_path.join(".stallignore"), ignore).unwrap();
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(stall_path,
+        &["add", app.to_str().unwrap()]).status.success());
+
+    // Ignored files in directory entries are not collected.
+    let output = stall(stall_path, &["collect"]);
+    assert!(output.status.success());
+    assert!(stall_path.join("app/a.conf").exists());
+    assert!(stall_path.join("app/keep.lock").exists());
+    assert!(stall_path.join("app/sub/c.conf").exists());
+    assert!(!stall_path.join("app/b.lock").exists());
+    assert!(!stall_path.join("app/cache").exists());
+
+    // Ignored files are not distributed.
+    std::fs::remove_dir_all(&app).unwrap();
+    std::fs::write(stall_path.join("app/sub/c.lock"), "c.lock").unwrap();
+    let output = stall(stall_path, &["distribute", "--create-dirs"]);
+    assert!(output.status.success());
+    assert!(app.join("sub/c.conf").exists());
+    assert!(!app.join("sub/c.lock").exists());
+
+    // Ignored files are not added by init --from-dir.
+    std::fs::write(app.join("b.lock"), "b.lock").unwrap();
+    let output = stall_command()
+        .arg("init")
+        .arg(init_path)
+        .arg("--from-dir")
+        .arg(&app)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let content = std::fs::read_to_string(init_path.join(".stall")).unwrap();
+    assert!(content.contains("a.conf"));
+    assert!(content.contains("keep.lock"));
+    assert!(content.contains("c.conf"));
+    assert!(!content.contains("b.lock"));
+}
+
+#[test]
+#[tracing::instrument]
+pub fn collect_directory_gitignore() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let repo_path = temp_dir_b.path();
+
+    let output = Command::new("git")
+        .arg("init")
+        .arg("-q")
+        .arg(repo_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let app = repo_path.join("app");
+    std::fs::create_dir_all(app.join("cache")).unwrap();
+    std::fs::write(repo_path.join(".gitignore"), "cache/\n").unwrap();
+    std::fs::write(app.join(".gitignore"), "*.log\n").unwrap();
+    for file in ["config", "debug.log", "cache/data"] {
+        std::fs::write(app.join(file), file).unwrap();
+    }
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(stall_path,
+        &["add", app.to_str().unwrap()]).status.success());
+
+    // Files ignored by git are not collected.
+    assert!(stall(stall_path, &["collect"]).status.success());
+    assert!(stall_path.join("app/config").exists());
+    assert!(stall_path.join("app/.gitignore").exists());
+    assert!(!stall_path.join("app/debug.log").exists());
+    assert!(!stall_path.join("app/cache").exists());
+
+    // Ignored files are collected for entries with the no-gitignore flag.
+    assert!(stall(stall_path,
+        &["flag", "app", "--no-gitignore"]).status.success());
+    let output = stall(stall_path, &["flag", "app"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "app: no-gitignore\n");
+    std::fs::remove_dir_all(stall_path.join("app")).unwrap();
+    assert!(stall(stall_path, &["collect"]).status.success());
+    assert!(stall_path.join("app/debug.log").exists());
+    assert!(stall_path.join("app/cache/data").exists());
+
+    // The flag may be set when adding the entry.
+    assert!(stall(stall_path, &["rm", "app"]).status.success());
+    assert!(stall(stall_path, &["add", "--no-gitignore", app.to_str().unwrap()])
+        .status.success());
+    let output = stall(stall_path, &["flag", "app"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "app: no-gitignore\n");
+}
+
+
+#[test]
+pub fn status_cached_hashes() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    std::fs::write(&remote, "aaaa").unwrap();
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(stall_path,
+        &["add", remote.to_str().unwrap()]).status.success());
+    assert!(stall(stall_path, &["collect"]).status.success());
+
+    // Files which differ only by modification time are the same.
+    let touch = |contents: &str| {
+        std::fs::write(&remote, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(&remote)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now()
+                + std::time::Duration::from_secs(1000))
+            .unwrap();
+    };
+    touch("aaaa");
+    assert_eq!(stall(stall_path,
+        &["status", "--check"]).status.code(), Some(0));
+    let state_path = stall_file_path(stall_path, "state");
+    assert!(state_path.starts_with(
+        std::env::current_dir().unwrap().join("target/test-xdg/cache")));
+    let state = std::fs::read_to_string(&state_path).unwrap();
+    assert!(state.contains("settings"));
+
+    // Cached hashes are discarded when the file changes.
+    touch("bbbb");
+    assert_eq!(stall(stall_path,
+        &["status", "--check"]).status.code(), Some(1));
+
+    // The cache is not used or written with --no-cache.
+    std::fs::remove_file(&state_path).unwrap();
+    touch("aaaa");
+    assert_eq!(stall(stall_path,
+        &["status", "--check", "--no-cache"]).status.code(),
+        Some(0));
+    assert!(!state_path.exists());
+}
+
+
+#[test]
+pub fn collect_incremental() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let app = temp_dir_b.path().join("app");
+    std::fs::create_dir_all(&app).unwrap();
+    std::fs::write(app.join("a"), "aaaa").unwrap();
+    std::fs::write(app.join("b"), "bbbb").unwrap();
+
+    let set_modified = |path: &Path, modified: std::time::SystemTime| {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    };
+    let later = std::time::SystemTime::now()
+        + std::time::Duration::from_secs(1000);
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(stall_path,
+        &["add", app.to_str().unwrap()]).status.success());
+    assert!(stall(stall_path, &["collect"]).status.success());
+    assert_eq!(std::fs::read_to_string(stall_path.join("app/a")).unwrap(),
+        "aaaa");
+
+    // Files with the same size and modification time are not copied.
+    let local_b = stall_path.join("app/b");
+    std::fs::write(&local_b, "xxxx").unwrap();
+    set_modified(&local_b,
+        std::fs::metadata(app.join("b")).unwrap().modified().unwrap());
+    std::fs::write(app.join("a"), "cccc").unwrap();
+    set_modified(&app.join("a"), later);
+    File::open(&app).unwrap().set_modified(later).unwrap();
+    assert!(stall(stall_path, &["collect"]).status.success());
+    assert_eq!(std::fs::read_to_string(stall_path.join("app/a")).unwrap(),
+        "cccc");
+    assert_eq!(std::fs::read_to_string(&local_b).unwrap(), "xxxx");
+    assert!(!stall_path.join("app/app").exists());
+}
+
+
+#[test]
+pub fn status_verbose_copy_times() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    std::fs::write(&remote, "aaaa").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("--color")
+        .arg("never")
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    let status_row = |args: &[&str]| {
+        String::from_utf8(stall(args).stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|line| line.contains("settings"))
+            .collect::<String>()
+    };
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(&["add", remote.to_str().unwrap()]).status.success());
+
+    // Entries which were never copied have no times.
+    let row = status_row(&["status", "--verbose"]);
+    assert!(row.ends_with("- -"), "{row}");
+
+    // Copy times are recorded in the state file.
+    assert!(stall(&["collect"]).status.success());
+    let row = status_row(&["status", "--verbose"]);
+    assert!(!row.contains(" - "), "{row}");
+    assert!(row.ends_with(" -"), "{row}");
+    assert!(stall(&["distribute", "--force"]).status.success());
+    let row = status_row(&["status", "--columns", "local,distributed"]);
+    assert!(!row.ends_with(" -"), "{row}");
+
+    // Times are not shown without --verbose.
+    let row = status_row(&["status"]);
+    assert!(!row.contains(':'), "{row}");
+}
+
+
+#[test]
+pub fn conflict_policy() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    let local = stall_path.join("settings");
+    std::fs::write(&remote, "base").unwrap();
+
+    let write_later = |path: &Path, contents: &str, secs: u64| {
+        std::fs::write(path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now()
+                + std::time::Duration::from_secs(secs))
+            .unwrap();
+    };
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(stall_path,
+        &["add", remote.to_str().unwrap()]).status.success());
+    assert!(stall(stall_path, &["collect"]).status.success());
+
+    // Both files change after they were copied.
+    write_later(&local, "local", 1000);
+    write_later(&remote, "remote", 2000);
+
+    // The fail policy copies neither file.
+    assert!(!stall(stall_path,
+        &["collect", "--conflict", "fail"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "local");
+
+    // The entry's policy is used instead of comparing modification times.
+    assert!(stall(stall_path,
+        &["flag", "settings", "--conflict", "prefer-local"])
+        .status.success());
+    let output = stall(stall_path, &["flag", "settings"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),
+        "settings: conflict=prefer-local\n");
+    assert!(stall(stall_path, &["collect"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "local");
+    assert!(stall(stall_path, &["distribute"]).status.success());
+    assert_eq!(std::fs::read_to_string(&remote).unwrap(), "local");
+
+    // The policy may be cleared.
+    assert!(stall(stall_path,
+        &["flag", "settings", "--clear-conflict"]).status.success());
+    let output = stall(stall_path, &["flag", "settings"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "settings: -\n");
+}
+
+
+#[test]
+pub fn conflict_without_copy_time() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    let local = stall_path.join("settings");
+    std::fs::write(&remote, "remote").unwrap();
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(stall_path,
+        &["add", remote.to_str().unwrap()]).status.success());
+
+    // Both files exist with different contents, but the entry has never been
+    // collected or distributed.
+    std::fs::write(&local, "local").unwrap();
+    File::options()
+        .write(true)
+        .open(&local)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now()
+            + std::time::Duration::from_secs(1000))
+        .unwrap();
+
+    // The entry is conflicted, so the fail policy copies neither file.
+    assert!(!stall(stall_path,
+        &["collect", "--conflict", "fail"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "local");
+    assert!(!stall(stall_path,
+        &["distribute", "--conflict", "fail"]).status.success());
+    assert_eq!(std::fs::read_to_string(&remote).unwrap(), "remote");
+
+    // The default policy doesn't overwrite the newer stalled file.
+    assert!(stall(stall_path, &["collect"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "local");
+    assert!(stall(stall_path, &["distribute"]).status.success());
+    assert_eq!(std::fs::read_to_string(&remote).unwrap(), "local");
+}
+
+
+#[test]
+pub fn collect_overwrite_newer() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    let local = stall_path.join("settings");
+    std::fs::write(&remote, "remote").unwrap();
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(stall_path,
+        &["add", remote.to_str().unwrap()]).status.success());
+    assert!(stall(stall_path, &["collect"]).status.success());
+
+    // The stalled file is newer than the remote file.
+    std::fs::write(&local, "local").unwrap();
+    File::options()
+        .write(true)
+        .open(&local)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now()
+            + std::time::Duration::from_secs(1000))
+        .unwrap();
+
+    assert!(stall(stall_path, &["collect"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "local");
+
+    let output = stall(stall_path, &["collect", "--overwrite-newer"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("force"));
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "remote");
+}
+
+
+#[test]
+pub fn distribute_confirm() {
+    use std::io::Write as _;
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_a = temp_dir_b.path().join("a");
+    let remote_b = temp_dir_b.path().join("b");
+    std::fs::write(&remote_a, "a").unwrap();
+    std::fs::write(&remote_b, "b").unwrap();
+
+    let stall = |args: &[&str], input: &str| {
+        let mut child = stall_command()
+            .arg(args[0])
+            .arg("--stall")
+            .arg(stall_path)
+            .args(&args[1..])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(&["add",
+        remote_a.to_str().unwrap(),
+        remote_b.to_str().unwrap()], "").status.success());
+    assert!(stall(&["collect"], "").status.success());
+
+    // The remote files have drifted, and are older than the stalled files.
+    let earlier = std::time::SystemTime::now()
+        - std::time::Duration::from_secs(5000);
+    for remote in [&remote_a, &remote_b] {
+        std::fs::write(remote, "drifted").unwrap();
+        File::options()
+            .write(true)
+            .open(remote)
+            .unwrap()
+            .set_modified(earlier)
+            .unwrap();
+    }
+
+    // Quitting skips all remaining files.
+    let output = stall(&["collect", "--force", "--confirm"], "q\n");
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(stall_path.join("a")).unwrap(), "a");
+    assert_eq!(std::fs::read_to_string(stall_path.join("b")).unwrap(), "b");
+
+    // Each overwrite shows the age and size differences.
+    let output = stall(&["distribute", "--confirm"], "n\ny\n");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("h older, 6 bytes larger"), "{stdout}");
+    assert_eq!(std::fs::read_to_string(&remote_a).unwrap(), "drifted");
+    assert_eq!(std::fs::read_to_string(&remote_b).unwrap(), "b");
+}
+
+
+#[test]
+pub fn collect_entry_indices() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    for name in ["a", "b", "c"] {
+        std::fs::write(remote_path.join(name), name).unwrap();
+    }
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    for name in ["a", "b", "c"] {
+        assert!(stall(stall_path,
+            &["add", remote_path.join(name).to_str().unwrap()])
+            .status.success());
+    }
+
+    // Entries are numbered in the status output.
+    let output = stall(stall_path, &["status"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<_> = stdout
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| line.contains(remote_path.to_str().unwrap()))
+        .collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[1].starts_with("2 "), "{}", rows[1]);
+
+    // Entries are selected by index and range.
+    assert!(stall(stall_path, &["collect", "1"]).status.success());
+    assert!(stall_path.join("a").exists());
+    assert!(!stall_path.join("b").exists());
+    assert!(stall(stall_path, &["collect", "2-3"]).status.success());
+    assert!(stall_path.join("b").exists());
+    assert!(stall_path.join("c").exists());
+
+    // Indices outside of the stall are not found.
+    assert!(!stall(stall_path, &["collect", "4"]).status.success());
+}
+
+
+#[test]
+pub fn fuzzy_entry_names() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    for name in [".bashrc", ".bash_profile", "init.vim"] {
+        std::fs::write(remote_path.join(name), name).unwrap();
+    }
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    for name in [".bashrc", ".bash_profile", "init.vim"] {
+        assert!(stall(stall_path,
+            &["add", remote_path.join(name).to_str().unwrap()])
+            .status.success());
+    }
+
+    // A unique partial name selects its entry.
+    assert!(stall(stall_path, &["collect", "bashrc"]).status.success());
+    assert!(stall_path.join(".bashrc").exists());
+    assert!(!stall_path.join(".bash_profile").exists());
+    assert!(stall(stall_path, &["collect", "ivm"]).status.success());
+    assert!(stall_path.join("init.vim").exists());
+
+    // Ambiguous names list the candidates.
+    let output = stall(stall_path, &["collect", "bash"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(".bash_profile"), "{stderr}");
+    assert!(!stall_path.join(".bash_profile").exists());
+
+    // Entries are removed by partial name.
+    assert!(stall(stall_path, &["rm", "profile"]).status.success());
+    assert!(!stall(stall_path, &["collect", "profile"]).status.success());
+}
+
+
+#[test]
+pub fn did_you_mean_suggestions() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join(".bashrc");
+    let prefs_path = temp_dir_b.path().join("prefs.ron");
+    std::fs::write(&remote, "bashrc").unwrap();
+
+    let stderr = |output: Output| String::from_utf8(
+        output.stderr).unwrap();
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(stall_path,
+        &["add", remote.to_str().unwrap()]).status.success());
+
+    // Unknown entries suggest similar entries.
+    let output = stall(stall_path, &["collect", "bashrx"]);
+    assert!(!output.status.success());
+    assert!(stderr(output).contains("Did you mean '.bashrc'?"));
+
+    // Unknown keys and values suggest similar keys and values.
+    let prefs = |args: &[&str]| stall_command()
+        .args(["prefs", "--prefs"])
+        .arg(&prefs_path)
+        .args(args)
+        .output()
+        .unwrap();
+    std::fs::write(&prefs_path, "()").unwrap();
+    let output = prefs(&["set", "conflict_polcy", "fail"]);
+    assert!(!output.status.success());
+    assert!(stderr(output).contains("Did you mean 'conflict_policy'?"));
+    let output = prefs(&["set", "conflict_policy", "newest-win"]);
+    assert!(!output.status.success());
+    assert!(stderr(output).contains("Did you mean 'newest-wins'?"));
+}
+
+
+#[test]
+pub fn show_entry_details() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    std::fs::write(&remote, "remote contents\n").unwrap();
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(stall_path,
+        &["add", remote.to_str().unwrap()]).status.success());
+    assert!(stall(stall_path, &["flag", "settings", "--pin"]).status.success());
+
+    // Details are shown for the entry.
+    let output = stall(stall_path, &["show", "settings"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("remote:      {}", remote.display())),
+        "{stdout}");
+    assert!(stdout.contains("state:       absent exists"), "{stdout}");
+    assert!(stdout.contains("flags:       pinned"), "{stdout}");
+    assert!(stdout.contains("collected:   -"), "{stdout}");
+
+    // File contents are printed on request.
+    let output = stall(stall_path, &["show", "settings", "--cat", "remote"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("\nremote contents\n"), "{stdout}");
+    assert!(!stall(stall_path,
+        &["show", "settings", "--cat", "local"]).status.success());
+
+    assert!(!stall(stall_path, &["show", "missing"]).status.success());
+}
+
+
+#[test]
+pub fn stall_schema_version() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    std::fs::write(&remote, "remote contents\n").unwrap();
+
+
+    // List format stall files are upgraded when written.
+    std::fs::write(stall_path.join(".stall"),
+        format!("{}\n", remote.display())).unwrap();
+    let output = stall(stall_path, &["flag", "settings", "--pin"]);
+    assert!(output.status.success());
+    let content = std::fs::read_to_string(stall_path.join(".stall")).unwrap();
+    assert!(content.contains("version: 1"), "{content}");
+    assert!(String::from_utf8(output.stdout).unwrap()
+        .contains("from schema version 0 to 1"));
+
+    // Newer schema versions are rejected.
+    std::fs::write(stall_path.join(".stall"),
+        content.replace("version: 1", "version: 99")).unwrap();
+    let output = stall(stall_path, &["status"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap()
+        .contains("unsupported schema version 99"));
+}
+
+
+#[test]
+pub fn stall_ron_parse_errors() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir.path();
+
+    // Syntax errors in RON stall files are reported with their location.
+    std::fs::write(stall_path.join(".stall"), "(\n    entries: {\n        \
+        \"a\": \"/remote/a\"\n        \"b\": \"/remote/b\",\n    },\n)\n")
+        .unwrap();
+    let output = stall_command()
+        .arg("status")
+        .arg("--stall")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(".stall:4:9: Expected end of map"), "{stderr}");
+    assert!(stderr.contains("4 |         \"b\": \"/remote/b\","), "{stderr}");
+    assert!(stderr.contains("Check for a missing comma"), "{stderr}");
+}
+
+
+#[test]
+pub fn stall_format_option() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("entries:settings");
+    std::fs::write(&remote, "remote contents\n").unwrap();
+
+    let show = |args: &[&str]| stall_command()
+        .arg("show")
+        .arg("--stall")
+        .arg(stall_path)
+        .arg("1")
+        .args(args)
+        .output()
+        .unwrap();
+
+    // A list file which looks like RON is only read with --stall-format.
+    std::fs::write(stall_path.join(".stall"),
+        format!("{}\n", remote.display())).unwrap();
+    assert!(!show(&[]).status.success());
+    let output = show(&["--stall-format", "list", "--verbose"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("using the list format"), "{stdout}");
+    assert!(stdout.contains("entries:settings"), "{stdout}");
+    assert!(!show(&["--stall-format", "ron"]).status.success());
+}
+
+
+#[test]
+pub fn list_format_mappings() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_a = temp_dir_b.path().join("init.vim");
+    let remote_b = temp_dir_b.path().join("bashrc");
+    let remote_c = temp_dir_b.path().join("gitconfig");
+    for remote in [&remote_a, &remote_b, &remote_c] {
+        std::fs::write(remote, "remote contents\n").unwrap();
+    }
+
+
+    // Lines may map a local name to a remote path.
+    std::fs::write(stall_path.join(".stall"), format!(
+            "nvim.vim -> {}\nbash = {}\n",
+            remote_a.display(),
+            remote_b.display()))
+        .unwrap();
+    let output = stall(stall_path, &["show", "nvim.vim"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap()
+        .contains(&format!("remote:      {}", remote_a.display())));
+    assert!(stall(stall_path, &["show", "bash"]).status.success());
+
+    // List format stall files are written back in the list format.
+    assert!(stall(stall_path,
+        &["add", remote_c.to_str().unwrap()]).status.success());
+    let content = std::fs::read_to_string(stall_path.join(".stall")).unwrap();
+    assert_eq!(content, format!("bash -> {}\n{}\nnvim.vim -> {}\n",
+        remote_b.display(),
+        remote_c.display(),
+        remote_a.display()));
+}
+
+
+#[test]
+#[cfg(unix)]
+pub fn edit_stall_file() {
+    use std::io::Write as _;
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    std::fs::write(&remote, "remote contents\n").unwrap();
+
+    // The editor replaces the stall file with the given contents.
+    let editor = temp_dir_b.path().join("editor");
+    let edit = |contents: &str, answers: &str| {
+        std::fs::write(&editor, format!(
+                "#!/bin/sh\nprintf '%s' '{contents}' > \"$1\"\n"))
+            .unwrap();
+        std::fs::set_permissions(&editor,
+                std::fs::Permissions::from_mode(0o755))
+            .unwrap();
+        let mut child = stall_command()
+            .env_remove("VISUAL")
+            .env("EDITOR", &editor)
+            .arg("edit")
+            .arg("--stall")
+            .arg(stall_path)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(answers.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let original = std::fs::read_to_string(stall_path.join(".stall")).unwrap();
+
+    // Invalid edits are restored.
+    let output = edit("(\n    entries: {\n", "r\n");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Failed parsing RON file"), "{stderr}");
+    assert_eq!(std::fs::read_to_string(stall_path.join(".stall")).unwrap(),
+        original);
+
+    // Duplicate remotes are rejected.
+    let output = edit(&format!("a -> {0}\nb -> {0}\n", remote.display()), "");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("remote file is stalled more than once"),
+        "{stderr}");
+
+    // Valid edits are kept, with warnings for missing remote files.
+    let missing = temp_dir_b.path().join("missing");
+    let contents = format!("{}\n{}\n", remote.display(), missing.display());
+    let output = edit(&contents, "");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("remote file does not exist"), "{stdout}");
+    assert_eq!(std::fs::read_to_string(stall_path.join(".stall")).unwrap(),
+        contents);
+}
+
+
+#[test]
+pub fn stall_ron_entries() {
+    use crate::Stall;
+    use std::sync::Arc;
+
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let stall_file = temp_dir.path().join(".stall");
+    std::fs::write(&stall_file, concat!(
+        "#![enable(implicit_some)]\n",
+        "// A comment with a brace }, and a comma.\n",
+        "(\n",
+        "    version: 1,\n",
+        "    entries: {\n",
+        "        \"a\": (\n",
+        "            remotes: [\"/remote/a\"],\n",
+        "            conflict: Ask,\n",
+        "            after: [\"b, c\"],\n",
+        "        ),\n",
+        "        /* A nested /* block */ comment, } */\n",
+        "        \"b, c\": r#\"/remote/\"b\", }\"#,\n",
+        "        \"d\": [\"/remote/d1\", \"/remote/d2\"],\n",
+        "    },\n",
+        "    groups: { \"g\": [\"b, c\"] },\n",
+        ")\n")).unwrap();
+
+    let stall = Stall::read_from_path(&stall_file).unwrap();
+    let entries: Vec<_> = stall.entries()
+        .map(|entry| (entry.local.to_path_buf(), entry.remote.to_path_buf()))
+        .collect();
+    assert_eq!(entries, [
+        ("a".into(), "/remote/a".into()),
+        ("b, c".into(), "/remote/\"b\", }".into()),
+        ("d".into(), "/remote/d1".into()),
+        ("d".into(), "/remote/d2".into()),
+    ]);
+
+    // Dependencies and group members share the storage of the entries they
+    // name.
+    let dependency = &stall.dependencies(Path::new("a")).unwrap()[0];
+    let member = stall.group("g").unwrap().first().unwrap();
+    assert!(Arc::ptr_eq(dependency, member));
+
+    // Files which aren't valid RON still report their errors.
+    std::fs::write(&stall_file, "(version: 1, entries: { \"a\" \"/a\" })")
+        .unwrap();
+    let e = Stall::read_from_path(&stall_file).unwrap_err();
+    assert!(e.to_string().contains("RON"), "{e}");
+
+    // Entries with the same local path are rejected.
+    std::fs::write(&stall_file, concat!(
+        "(version: 1, entries: {\n",
+        "    \"a\": \"/remote/a\",\n",
+        "    \"b\": \"/remote/b\",\n",
+        "    \"a\": \"/remote/c\",\n",
+        "})\n")).unwrap();
+    let e = Stall::read_from_path(&stall_file).unwrap_err();
+    assert!(format!("{e:?}").contains("duplicate stall entry a"), "{e:?}");
+}
+
+
+#[test]
+pub fn stall_insert_and_remove_remotes() {
+    use crate::InsertError;
+    use crate::Stall;
+
+    let mut stall = Stall::new("/stall/.stall");
+    let _ = stall.insert("a".into(), Path::new("/remote/a")).unwrap();
+    stall.set_modified(false);
+
+    // Remotes of other entries are refused without modifying the stall.
+    assert_eq!(stall.insert("b".into(), Path::new("/remote/a")),
+        Err(InsertError::RemoteInUse {
+            local: "a".into(),
+            remote: "/remote/a".into(),
+        }));
+    assert!(!stall.modified());
+
+    // Removing missing entries doesn't modify the stall.
+    assert_eq!(stall.remove_remote(Path::new("/remote/b")), None);
+    assert_eq!(stall.remove_local(Path::new("b")), None);
+    assert!(!stall.modified());
+
+    assert_eq!(stall.remove_remote(Path::new("/remote/a")),
+        Some(("a".into(), "/remote/a".into())));
+    assert!(stall.modified());
+}
+
+
+#[test]
+pub fn stall_changed_on_disk() {
+    use crate::Stall;
+
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let stall_file = temp_dir.path().join(".stall");
+    std::fs::write(&stall_file, "/remote/a\n").unwrap();
+
+    // Unchanged files are written.
+    let mut stall = Stall::read_from_path(&stall_file).unwrap();
+    let _ = stall.insert_remote("b".into(), Path::new("/remote/b")).unwrap();
+    assert!(stall.write_to_load_path().unwrap());
+
+    // Files changed after loading are not overwritten.
+    let mut stall = Stall::read_from_path(&stall_file).unwrap();
+    let _ = stall.insert_remote("c".into(), Path::new("/remote/c")).unwrap();
+    std::fs::write(&stall_file, "/remote/d\n").unwrap();
+    let e = stall.write_to_load_path().unwrap_err();
+    assert!(e.to_string().contains("changed by another program"));
+    assert_eq!(std::fs::read_to_string(&stall_file).unwrap(), "/remote/d\n");
+}
+
+
+#[test]
+pub fn merge_stalls() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let temp_dir_c = TempDir::new().expect("create temp dir");
+    let ours_path = temp_dir_a.path();
+    let theirs_path = temp_dir_b.path();
+    let remote_path = temp_dir_c.path();
+    for name in ["bashrc", "vimrc", "gitconfig"] {
+        std::fs::write(remote_path.join(name), name).unwrap();
+    }
+    std::fs::create_dir_all(remote_path.join("other")).unwrap();
+    std::fs::write(remote_path.join("other/vimrc"), "other vimrc").unwrap();
+
+    let stall = |dir: &Path, args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(dir)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    let remote = |name: &str| remote_path.join(name).display().to_string();
+
+    for dir in [ours_path, theirs_path] {
+        let output = stall_command()
+            .arg("init")
+            .arg(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+    }
+    assert!(stall(ours_path, &["add", &remote("bashrc"), &remote("vimrc")])
+        .status.success());
+    assert!(stall(theirs_path, &["add", &remote("gitconfig"),
+        &remote("other/vimrc"), "--collect"]).status.success());
+
+    // Conflicting entries are reported without merging anything.
+    let output = stall(ours_path, &["merge", theirs_path.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("entries differ from the other stall: vimrc"),
+        "{stderr}");
+    assert!(!stall(ours_path, &["show", "gitconfig"]).status.success());
+
+    // Conflicts are resolved by the given flag.
+    let output = stall(ours_path, &["merge", theirs_path.to_str().unwrap(),
+        "--theirs"]);
+    assert!(output.status.success());
+    let output = stall(ours_path, &["show", "vimrc"]);
+    assert!(String::from_utf8(output.stdout).unwrap()
+        .contains(&remote("other/vimrc")));
+    assert!(stall(ours_path, &["show", "bashrc"]).status.success());
+    assert_eq!(std::fs::read_to_string(ours_path.join("gitconfig")).unwrap(),
+        "gitconfig");
+}
+
+
+#[test]
+pub fn split_stall() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    std::fs::create_dir_all(remote_path.join(".config/nvim")).unwrap();
+    std::fs::create_dir_all(remote_path.join(".config/git")).unwrap();
+    let remote_a = remote_path.join(".config/nvim/init.vim");
+    let remote_b = remote_path.join(".config/git/config");
+    std::fs::write(&remote_a, "init.vim").unwrap();
+    std::fs::write(&remote_b, "config").unwrap();
+
+    let stall = |dir: &Path, args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(dir)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(stall_path, &["add", remote_a.to_str().unwrap(),
+        remote_b.to_str().unwrap(), "--collect"]).status.success());
+
+    // Each group of entries is moved into a new stall with its files.
+    let output = stall(stall_path, &["split", "--by", "remote-prefix"]);
+    assert!(output.status.success());
+    assert!(stall_path.join("nvim/.stall").exists());
+    assert_eq!(std::fs::read_to_string(stall_path.join("git/config")).unwrap(),
+        "config");
+    assert!(!stall_path.join("init.vim").exists());
+    let output = stall(&stall_path.join("nvim"), &["show", "init.vim"]);
+    assert!(String::from_utf8(output.stdout).unwrap()
+        .contains("state:       same same"));
+    assert!(!stall(stall_path, &["show", "config"]).status.success());
+}
+
+
+#[test]
+pub fn include_stall_file() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_a = temp_dir_b.path().join("bashrc");
+    let remote_b = temp_dir_b.path().join("gitconfig");
+    let remote_c = temp_dir_b.path().join("init.vim");
+    for remote in [&remote_a, &remote_b, &remote_c] {
+        std::fs::write(remote, "remote contents\n").unwrap();
+    }
+
+
+    // Shared entries are included from another stall file, and the
+    // including file's entries take precedence.
+    std::fs::write(stall_path.join("common.stall"), format!("{}\n{}\n",
+            remote_a.display(),
+            remote_b.display()))
+        .unwrap();
+    std::fs::write(stall_path.join(".stall"), format!(
+            "include \"common.stall\"\nbashrc -> {}\n",
+            remote_c.display()))
+        .unwrap();
+    let output = stall(stall_path, &["show", "bashrc"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap()
+        .contains(&format!("remote:      {}", remote_c.display())));
+    assert!(stall(stall_path, &["show", "gitconfig"]).status.success());
+
+    // Removed included entries are removed from the included file, and the
+    // including file is written without them.
+    assert!(stall(stall_path, &["rm", "gitconfig"]).status.success());
+    let common = std::fs::read_to_string(stall_path.join("common.stall"))
+        .unwrap();
+    assert_eq!(common, format!("{}\n", remote_a.display()));
+    let content = std::fs::read_to_string(stall_path.join(".stall")).unwrap();
+    assert_eq!(content, format!("include \"common.stall\"\nbashrc -> {}\n",
+        remote_c.display()));
+
+    // Include cycles are reported.
+    std::fs::write(stall_path.join("common.stall"), "include \".stall\"\n")
+        .unwrap();
+    let output = stall(stall_path, &["show", "bashrc"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap()
+        .contains("stall file includes itself"));
+}
+
+
+#[test]
+pub fn collect_recursive() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_a = temp_dir_b.path().join("bashrc");
+    let remote_b = temp_dir_b.path().join("init.vim");
+    let remote_c = temp_dir_b.path().join("gitconfig");
+    for remote in [&remote_a, &remote_b, &remote_c] {
+        std::fs::write(remote, "remote contents\n").unwrap();
+    }
+
+    // Stall files in subdirectories are nested stalls, except in hidden
+    // directories.
+    for dir in ["nvim/plugins", ".hidden"] {
+        std::fs::create_dir_all(stall_path.join(dir)).unwrap();
+    }
+    std::fs::write(stall_path.join(".stall"),
+        format!("{}\n", remote_a.display())).unwrap();
+    std::fs::write(stall_path.join("nvim/plugins/.stall"),
+        format!("{}\n", remote_b.display())).unwrap();
+    std::fs::write(stall_path.join(".hidden/.stall"),
+        format!("{}\n", remote_c.display())).unwrap();
+
+    let output = stall_command()
+        .args(["collect", "--recursive", "--stall"])
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Collected 2 stalls:"));
+    assert!(stdout.contains(&format!("{}: 1 entries, 1 copied, 0 failed",
+        stall_path.join("nvim/plugins").display())));
+
+    assert!(stall_path.join("bashrc").exists());
+    assert!(stall_path.join("nvim/plugins/init.vim").exists());
+    assert!(!stall_path.join(".hidden/gitconfig").exists());
+}
+
+
+#[test]
+pub fn status_remote_missing() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let home = temp_dir_b.path();
+    for dir in [".config/nvim", ".config/git", "notes"] {
+        std::fs::create_dir_all(home.join(dir)).unwrap();
+    }
+    for file in [".bashrc", ".config/git/config", "notes/todo.txt"] {
+        std::fs::write(home.join(file), "remote contents\n").unwrap();
+    }
+    std::fs::write(stall_path.join(".stall"),
+        format!("{}\n", home.join(".config/git/config").display())).unwrap();
+    std::fs::write(stall_path.join("prefs"),
+        "(scan_paths: [\"notes/*.txt\"])").unwrap();
+
+    let output = stall_command()
+        .args(["status", "--remote-missing", "--stall"])
+        .arg(stall_path)
+        .arg("--prefs")
+        .arg(stall_path.join("prefs"))
+        .current_dir(stall_path)
+        .env("HOME", home)
+        .env_remove("XDG_CONFIG_HOME")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Found 3 config files"));
+    for path in [".bashrc", ".config/nvim", "notes/todo.txt"] {
+        assert!(stdout.contains(&format!("stall add {}",
+            home.join(path).display())));
+    }
+    assert!(!stdout.contains(".config/git"));
+}
+
+
+#[test]
+pub fn du_sizes() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_a = temp_dir_b.path().join("bashrc");
+    let remote_b = temp_dir_b.path().join("gitconfig");
+    std::fs::write(&remote_a, vec![b'a'; 2048]).unwrap();
+    std::fs::write(&remote_b, vec![b'b'; 100]).unwrap();
+    std::fs::write(stall_path.join("bashrc"), vec![b'a'; 2048]).unwrap();
+    std::fs::write(stall_path.join("gitconfig"), vec![b'b'; 10]).unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n{}\n",
+            remote_a.display(),
+            remote_b.display()))
+        .unwrap();
+
+    let du = |args: &[&str]| stall_command()
+        .arg("du")
+        .arg("--stall")
+        .arg(stall_path)
+        .args(args)
+        .output()
+        .unwrap();
+
+    let output = du(&[]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("2.0 KiB"));
+    assert!(stdout
+        .lines()
+        .any(|line| line.contains("gitconfig") && line.ends_with("sizes differ")));
+    assert!(stdout.contains("Total of 2 entries"));
+
+    let stdout = String::from_utf8(du(&["--bytes", "bashrc"]).stdout).unwrap();
+    assert!(stdout.contains("2048 B"));
+    assert!(!stdout.contains("gitconfig"));
+}
+
+
+#[test]
+pub fn diff_binary_files() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_a = temp_dir_b.path().join("bashrc");
+    let remote_b = temp_dir_b.path().join("font.bin");
+    std::fs::write(&remote_a, "one\ntwo\nthree\n").unwrap();
+    std::fs::write(stall_path.join("bashrc"), "one\n2\nthree\n").unwrap();
+    std::fs::write(&remote_b, b"abc\0def").unwrap();
+    std::fs::write(stall_path.join("font.bin"), b"abc\0de").unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n{}\n",
+            remote_a.display(),
+            remote_b.display()))
+        .unwrap();
+
+
+    // Text files are printed as a unified diff, and binary files by size and
+    // hash.
+    let output = stall(stall_path, &["diff"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("@@ -1,3 +1,3 @@\n one\n-2\n+two\n three\n"));
+    assert!(stdout.contains("Binary files"));
+    assert!(stdout.contains("size: 6 -> 7 bytes (+1)"));
+
+    let stdout = String::from_utf8(stall(stall_path,
+        &["diff", "--text", "font.bin"]).stdout)
+        .unwrap();
+    assert!(!stdout.contains("Binary files"));
+
+    // Entries flagged as binary are never printed as text.
+    assert!(stall(stall_path,
+        &["flag", "bashrc", "--binary"]).status.success());
+    let stdout = String::from_utf8(stall(stall_path,
+        &["diff", "bashrc"]).stdout).unwrap();
+    assert!(stdout.contains("Binary files"));
+    assert!(stall(stall_path, &["show", "bashrc"]).status.success());
+
+    std::fs::write(stall_path.join("bashrc"), "one\ntwo\nthree\n").unwrap();
+    assert_eq!(stall(stall_path, &["diff", "bashrc"]).status.code(), Some(0));
+}
+
+
+#[test]
+pub fn diff_words_and_side_by_side() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("gitconfig");
+    std::fs::write(&remote, "[core]\neditor = nvim\npager = less\nnew\n")
+        .unwrap();
+    std::fs::write(stall_path.join("gitconfig"),
+            "[core]\neditor = vim\npager = less\n")
+        .unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n",
+            remote.display()))
+        .unwrap();
+
+
+    // Changed words are marked within each line.
+    let output = stall(stall_path, &["diff", "--word-diff"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("@@ -1,3 +1,4 @@\n\
+        [core]\n\
+        editor = [-vim-]{+nvim+}\n\
+        pager = less\n\
+        {+new+}\n"));
+
+    // Both files are printed in columns fitting the width.
+    let output = stall(stall_path,
+        &["diff", "--side-by-side", "--width", "31"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let row = |left: &str, separator: char, right: &str|
+        format!("{left:14} {separator} {right}\n");
+    assert!(stdout.ends_with(&format!("@@ -1,3 +1,4 @@\n{}{}{}{}",
+        row("[core]", ' ', "[core]"),
+        row("editor = vim", '|', "editor = nvim"),
+        row("pager = less", ' ', "pager = less"),
+        row("", '>', "new"))));
+
+    assert!(!stall(stall_path,
+        &["diff", "--word-diff", "--side-by-side"]).status.success());
+}
+
+
+#[test]
+pub fn line_ending_conversion() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    let local = stall_path.join("settings");
+    std::fs::write(&remote, "one\r\ntwo\r\n").unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n",
+            remote.display()))
+        .unwrap();
+
+
+    assert!(stall(stall_path,
+        &["flag", "settings", "--eol", "crlf"]).status.success());
+    let output = stall(stall_path, &["flag", "settings"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),
+        "settings: eol=crlf\n");
+
+    // Collected files are normalized, and aren't reported as modified.
+    assert!(stall(stall_path, &["collect"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "one\ntwo\n");
+    assert_eq!(stall(stall_path,
+        &["status", "--check"]).status.code(), Some(0));
+
+    // Distributed files are converted to the entry's line ending.
+    std::fs::write(&local, "one\ntwo\nthree\n").unwrap();
+    File::options()
+        .write(true)
+        .open(&local)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now()
+            + std::time::Duration::from_secs(1000))
+        .unwrap();
+    assert!(stall(stall_path, &["distribute"]).status.success());
+    assert_eq!(std::fs::read_to_string(&remote).unwrap(),
+        "one\r\ntwo\r\nthree\r\n");
+
+    // Preserved line endings are copied unchanged.
+    assert!(stall(stall_path,
+        &["flag", "settings", "--eol", "preserve"]).status.success());
+    assert!(stall(stall_path, &["collect", "--force"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(),
+        "one\r\ntwo\r\nthree\r\n");
+}
+
+
+#[test]
+pub fn transform_pipeline() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    let local = stall_path.join("settings");
+    std::fs::write(&remote, "b  \r\na\t\r\n").unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n",
+            remote.display()))
+        .unwrap();
+
+
+    assert!(stall(stall_path, &["flag", "settings",
+            "--eol", "crlf",
+            "--transform", "trim-whitespace",
+            "--transform", "collect-cmd=sort",
+            "--transform", "distribute-cmd=tr a-z A-Z"])
+        .status.success());
+    let output = stall(stall_path, &["flag", "settings"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),
+        "settings: eol=crlf\n    \
+        transform: trim-whitespace\n    \
+        transform: collect-cmd=sort\n    \
+        transform: distribute-cmd=tr a-z A-Z\n");
+    assert!(!stall(stall_path, &["flag", "settings", "--transform", "unknown"])
+        .status.success());
+
+    // Dry runs describe the transforms in the order they are applied.
+    let stdout = String::from_utf8(stall(stall_path,
+        &["collect", "--dry-run"]).stdout)
+        .unwrap();
+    let eol = stdout.find("filter eol=lf").unwrap();
+    let sort = stdout.find("filter run `sort`").unwrap();
+    let trim = stdout.find("filter trim-whitespace").unwrap();
+    assert!(eol < sort && sort < trim);
+    assert!(!local.exists());
+
+    // Line endings are normalized before the collect transforms are applied.
+    assert!(stall(stall_path, &["collect"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "a\nb\n");
+
+    // Line endings are converted after the distribute transforms.
+    assert!(stall(stall_path, &["distribute", "--force"]).status.success());
+    assert_eq!(std::fs::read_to_string(&remote).unwrap(), "A\r\nB\r\n");
+
+    assert!(stall(stall_path,
+        &["flag", "settings", "--clear-transforms"]).status.success());
+    let output = stall(stall_path, &["flag", "settings"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),
+        "settings: eol=crlf\n");
+}
+
+
+#[test]
+pub fn merge_tool_conflict() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let prefs_path = temp_dir_b.path().join("prefs");
+    let remote = temp_dir_b.path().join("settings");
+    let local = stall_path.join("settings");
+    std::fs::write(&remote, "base\n").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .arg("--stall")
+        .arg(stall_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    let write_later = |path: &Path, contents: &str, secs: u64| {
+        std::fs::write(path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now()
+                + std::time::Duration::from_secs(secs))
+            .unwrap();
+    };
+    let prefs = |args: &[&str]| stall_command()
+        .args(["prefs", "--prefs"])
+        .arg(&prefs_path)
+        .args(args)
+        .output()
+        .unwrap();
+
+    let output = stall_command()
+        .env_remove("VISUAL")
+        .env("EDITOR", "true")
+        .args(["prefs", "--prefs"])
+        .arg(&prefs_path)
+        .arg("edit")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(&["add", remote.to_str().unwrap()]).status.success());
+    assert!(stall(&["collect"]).status.success());
+    write_later(&local, "local\n", 1000);
+    write_later(&remote, "remote\n", 2000);
+
+    // The merge policy requires a merge tool.
+    assert!(!stall(&["collect", "--conflict", "merge"]).status.success());
+    assert!(!prefs(&["set", "merge_tool", "merge-files"]).status.success());
+    assert!(prefs(&["set", "merge_tool", "cat {local} {remote} > {merged}"])
+        .status.success());
+
+    // Dry runs don't run the merge tool.
+    let stdout = String::from_utf8(
+            stall(&["collect", "--conflict", "merge", "--dry-run"]).stdout)
+        .unwrap();
+    assert!(stdout.contains("merge"));
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "local\n");
+
+    // The merged result is written to the file being copied over.
+    assert!(stall(&["collect", "--conflict", "merge"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "local\nremote\n");
+    assert_eq!(std::fs::read_to_string(&remote).unwrap(), "remote\n");
+
+    // Once merged, the entry is no longer in conflict.
+    assert!(stall(&["collect", "--conflict", "fail"]).status.success());
+
+    // The most recently saved version, which is the stalled file replaced by
+    // the last collect, is given as the base.
+    assert!(prefs(&["set", "version_retention", "10"]).status.success());
+    write_later(&remote, "copied\n", 3000);
+    assert!(stall(&["collect"]).status.success());
+    write_later(&local, "local\n", 4000);
+    write_later(&remote, "other\n", 5000);
+    assert!(prefs(&["set", "merge_tool",
+        "cat {base} {local} {remote} > {merged}"]).status.success());
+    assert!(stall(&["collect", "--conflict", "merge"]).status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(),
+        "remote\nlocal\nother\n");
+
+    // The version saved before that merge is the base when distributing.
+    write_later(&local, "stall\n", 6000);
+    write_later(&remote, "again\n", 7000);
+    assert!(stall(&["distribute", "--conflict", "merge"]).status.success());
+    assert_eq!(std::fs::read_to_string(&remote).unwrap(),
+        "local\nstall\nagain\n");
+}
+
+#[test]
+pub fn collect_versions() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let prefs_path = temp_dir_b.path().join("prefs");
+    let remote = temp_dir_b.path().join("settings");
+    let local = stall_path.join("settings");
+    std::fs::write(&remote, "v0\n").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .arg("--stall")
+        .arg(stall_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+    let write_later = |path: &Path, contents: &str, secs: u64| {
+        std::fs::write(path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now()
+                + std::time::Duration::from_secs(secs))
+            .unwrap();
+    };
+    let version_names = || String::from_utf8(
+            stall(&["versions", "list", "settings"]).stdout)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|name| name.starts_with(|c: char| c.is_ascii_digit()))
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+
+    let output = stall_command()
+        .env_remove("VISUAL")
+        .env("EDITOR", "true")
+        .args(["prefs", "--prefs"])
+        .arg(&prefs_path)
+        .arg("edit")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(&["add", remote.to_str().unwrap()]).status.success());
+    assert!(stall(&["collect"]).status.success());
+
+    // No versions are kept without a retention policy.
+    write_later(&remote, "v1\n", 100);
+    assert!(stall(&["collect"]).status.success());
+    assert!(version_names().is_empty());
+
+    let output = stall_command()
+        .args(["prefs", "--prefs"])
+        .arg(&prefs_path)
+        .args(["set", "version_retention", "2"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Only the most recent versions are retained.
+    for (i, secs) in [(2, 200), (3, 300), (4, 400)] {
+        write_later(&remote, &format!("v{i}\n"), secs);
+        assert!(stall(&["collect"]).status.success());
+    }
+    let names = version_names();
+    assert_eq!(names.len(), 2);
+    let show = |name: &str| String::from_utf8(
+            stall(&["versions", "show", "settings", name]).stdout)
+        .unwrap();
+    assert_eq!(show(&names[0]), "v2\n");
+    assert_eq!(show(&names[1]), "v3\n");
+    assert!(!stall(&["versions", "show", "settings", "none"]).status.success());
+
+    // Restoring a version saves the replaced file as a new version.
+    assert!(stall(&["versions", "restore", "settings", "--dry-run"])
+        .status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "v4\n");
+    assert!(stall(&["versions", "restore", "settings", &names[0]])
+        .status.success());
+    assert_eq!(std::fs::read_to_string(&local).unwrap(), "v2\n");
+    let names = version_names();
+    assert_eq!(names.len(), 2);
+    assert_eq!(show(&names[1]), "v4\n");
+}
+
+
+#[test]
+pub fn collect_summary_only() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+    let write_later = |name: &str, contents: &str| {
+        let path = remote_path.join(name);
+        std::fs::write(&path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now()
+                + std::time::Duration::from_secs(100))
+            .unwrap();
+    };
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    for name in ["a", "b", "c"] {
+        std::fs::write(remote_path.join(name), "old").unwrap();
+        let remote = remote_path.join(name);
+        assert!(stall(stall_path,
+            &["add", "--collect", remote.to_str().unwrap()])
+            .status.success());
+    }
+    assert!(stall(stall_path, &["flag", "--pin", "c"]).status.success());
+    write_later("a", "new");
+    write_later("c", "new");
+
+    // Only the summary line is printed.
+    let output = stall(stall_path, &["collect", "--summary-only"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),
+        "1 copied, 1 unchanged, 1 skipped, 0 errors\n");
+
+    // The summary line follows the status of each file otherwise.
+    let output = stall(stall_path, &["collect"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Stall directory:"));
+    assert!(stdout.ends_with("0 copied, 2 unchanged, 1 skipped, 0 errors\n"));
+
+    // Nothing is printed in quiet mode.
+    let output = stall(stall_path, &["collect", "--summary-only", "--quiet"]);
+    assert!(output.stdout.is_empty());
+}
+
+
+#[test]
+pub fn list_format() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    for name in ["a", "b"] {
+        std::fs::write(remote_path.join(name), name).unwrap();
+        let remote = remote_path.join(name);
+        assert!(stall(stall_path,
+            &["add", remote.to_str().unwrap()]).status.success());
+    }
+    assert!(stall(stall_path, &["flag", "--pin", "b"]).status.success());
+
+    // Remote files are not accessed.
+    std::fs::remove_file(remote_path.join("a")).unwrap();
+
+    let output = stall(stall_path, &["list"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!(
+        "a\t{}\nb\t{}\n",
+        remote_path.join("a").display(),
+        remote_path.join("b").display()));
+
+    let output = stall(stall_path,
+        &["list", "--format", "{index}:{name}:{flags}", "2"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2:b:pinned\n");
+
+    let output = stall(stall_path,
+        &["list", "--null", "--format", "{{{local}}}"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "{a}\0{b}\0");
+
+    assert!(!stall(stall_path,
+        &["list", "--format", "{size}"]).status.success());
+}
+
+
+#[test]
+pub fn pick_requires_terminal() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("settings");
+    std::fs::write(&remote, "remote contents\n").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .args(&args[1..])
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .output()
+        .unwrap();
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stall(&["add", remote.to_str().unwrap()]).status.success());
+
+    // The picker is not shown without a terminal, so nothing is copied.
+    let output = stall(&["collect", "--pick", "--force"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap()
+        .contains("--pick requires an interactive terminal"));
+    assert!(!stall_path.join("settings").exists());
+
+    // Picked entries can't be combined with named entries.
+    assert!(!stall(&["rm", "--pick", "settings"]).status.success());
+}
+
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+pub fn paths_xdg_dirs() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let xdg_path = temp_dir_b.path();
+
+    let stall = |args: &[&str]| stall_command()
+        .env("XDG_CONFIG_HOME", xdg_path.join("config"))
+        .env("XDG_STATE_HOME", xdg_path.join("state"))
+        .env("XDG_CACHE_HOME", xdg_path.join("cache"))
+        .env("EDITOR", "true")
+        .env_remove("VISUAL")
+        .args(args)
+        .output()
+        .unwrap();
+
+    let output = stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // The prefs file is created in the config directory.
+    assert!(stall(&["prefs", "edit"]).status.success());
+    assert!(xdg_path.join("config/stall/prefs").exists());
+
+    let output = stall(&["paths", "--stall", stall_path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    for (name, path) in [
+        ("config dir:", xdg_path.join("config/stall")),
+        ("state dir:", xdg_path.join("state/stall")),
+        ("cache dir:", xdg_path.join("cache/stall")),
+        ("user config:", xdg_path.join("config/stall/config")),
+        ("prefs:", xdg_path.join("config/stall/prefs")),
+    ] {
+        assert!(stdout.lines().any(|line| line.starts_with(name)
+            && line.ends_with(&path.display().to_string())),
+            "missing {name} {}", path.display());
+    }
+
+    // The journal is kept in the stall's directory in the state directory.
+    let journal_path: std::path::PathBuf = stdout.lines()
+        .find_map(|line| line.strip_prefix("journal:"))
+        .map(|path| path.trim().into())
+        .unwrap();
+    assert!(journal_path.starts_with(xdg_path.join("state/stall/stalls")));
+    let state_path: std::path::PathBuf = stdout.lines()
+        .find_map(|line| line.strip_prefix("state:"))
+        .map(|path| path.trim().into())
+        .unwrap();
+    assert!(state_path.starts_with(xdg_path.join("cache/stall/stalls")));
+
+    // A journal in the stall directory is read, and moved when it is next
+    // written.
+    let remote = xdg_path.join("settings");
+    std::fs::write(&remote, "settings").unwrap();
+    let stall_arg = stall_path.to_str().unwrap();
+    assert!(stall(&["add", "--stall", stall_arg, remote.to_str().unwrap()])
+        .status
+        .success());
+    let legacy_path = stall_path.join(".stall-journal");
+    std::fs::rename(&journal_path, &legacy_path).unwrap();
+    let output = stall(&["history", "--stall", stall_arg]);
+    assert!(String::from_utf8(output.stdout).unwrap().contains("add"));
+    assert!(stall(&["rm", "--stall", stall_arg, "settings"])
+        .status
+        .success());
+    assert!(!legacy_path.exists());
+    let journal = std::fs::read_to_string(&journal_path).unwrap();
+    assert_eq!(journal.lines().count(), 2, "{journal}");
+}
+
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+pub fn distribute_privileged() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let temp_dir_c = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_a = temp_dir_b.path().join("hosts");
+    let remote_b = temp_dir_b.path().join("bashrc");
+    let script = temp_dir_c.path().join("privileged.sh");
+    std::fs::write(&remote_a, "old hosts\n").unwrap();
+    std::fs::write(&remote_b, "old bashrc\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    std::fs::write(stall_path.join("hosts"), "new hosts\n").unwrap();
+    std::fs::write(stall_path.join("bashrc"), "new bashrc\n").unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n{}\n",
+            remote_a.display(),
+            remote_b.display()))
+        .unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .env("XDG_CONFIG_HOME", temp_dir_c.path())
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    let output = stall(&["flag", "hosts", "--privileged"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(stall(&["flag", "hosts"]).stdout).unwrap();
+    assert!(stdout.contains("privileged"));
+
+    // Privileged copies are written into the script instead of being run.
+    let output = stall(&["distribute", "--privileged-script",
+        script.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Wrote privileged commands to"));
+    let contents = std::fs::read_to_string(&script).unwrap();
+    assert!(contents.starts_with("#!/bin/sh\n"));
+    assert!(contents.contains(&format!("cp -R '{}' '{}'",
+        stall_path.join("hosts").display(),
+        remote_a.display())));
+    assert!(contents.contains("touch -r"));
+    assert!(!contents.contains("bashrc"));
+    assert_eq!(std::fs::read_to_string(&remote_a).unwrap(), "old hosts\n");
+    assert_eq!(std::fs::read_to_string(&remote_b).unwrap(), "new bashrc\n");
+
+    // With --system, every entry is copied with the escalation command.
+    let output = stall_command()
+        .env("XDG_CONFIG_HOME", temp_dir_c.path())
+        .args(["prefs", "set", "escalation", "env"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    std::fs::write(stall_path.join("bashrc"), "newer bashrc\n").unwrap();
+    let output = stall(&["distribute", "--system"]);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(&remote_a).unwrap(), "new hosts\n");
+    assert_eq!(std::fs::read_to_string(&remote_b).unwrap(),
+        "newer bashrc\n");
+}
+
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+pub fn export_script_restores_files() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let temp_dir_c = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let home_b = temp_dir_b.path();
+    let home_c = temp_dir_c.path();
+    std::fs::write(stall_path.join(".bashrc"), "bashrc\n").unwrap();
+    std::fs::create_dir(stall_path.join("nvim")).unwrap();
+    std::fs::write(stall_path.join("nvim/init.vim"), "init\n").unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n{}\n",
+            home_b.join(".bashrc").display(),
+            home_b.join(".config/nvim").display()))
+        .unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .env("HOME", home_b)
+        .arg(args[0])
+        .arg("--stall")
+        .arg(stall_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    // Remote paths in the home directory are written relative to $HOME.
+    let output = stall(&["export-script"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("#!/bin/sh\n"));
+    assert!(stdout.contains("restore '.bashrc' \"$HOME\"/'.bashrc'\n"));
+
+    let output = stall(&["export-script", "--shell", "powershell"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(
+        "Restore-StallFile '.bashrc' (Join-Path $HOME '.bashrc')\n"));
+
+    // The script restores the files from its own directory.
+    let script = stall_path.join("restore.sh");
+    let script_arg = script.to_str().unwrap();
+    assert!(stall(&["export-script", "--output", script_arg])
+        .status
+        .success());
+    assert!(!stall(&["export-script", "--output", script_arg])
+        .status
+        .success());
+    let output = Command::new("sh")
+        .env("HOME", home_c)
+        .arg(&script)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(home_c.join(".bashrc")).unwrap(),
+        "bashrc\n");
+    assert_eq!(std::fs::read_to_string(
+            home_c.join(".config/nvim/init.vim")).unwrap(),
+        "init\n");
+}
+
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+pub fn schedule_install_dry_run() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let config_path = temp_dir_b.path();
+    std::fs::write(stall_path.join(".stall"), "").unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .env("XDG_CONFIG_HOME", config_path)
+        .env("HOME", config_path)
+        .arg("schedule")
+        .arg("--stall")
+        .arg(stall_path)
+        .args(args)
+        .output()
+        .unwrap();
+
+    let output = stall(&["install", "--interval", "30m", "--backend",
+        "systemd", "--dry-run"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("Write {}",
+        config_path.join("systemd/user/stall-collect-").display())));
+    assert!(stdout.contains("OnUnitActiveSec=30m\n"));
+    assert!(stdout.contains("\"collect\" \"--stall\""));
+    assert!(!config_path.join("systemd").exists());
+
+    let output = stall(&["install", "--interval", "1d", "--command",
+        "distribute", "--backend", "cron", "--dry-run"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\n0 0 * * * '"));
+    assert!(stdout.contains("'distribute' '--stall'"));
+
+    // A timeout is passed to the scheduled command.
+    let output = stall(&["install", "--interval", "1h", "--timeout", "600",
+        "--backend", "cron", "--dry-run"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("interval=1h timeout=10m stall="));
+    assert!(stdout.contains("'--notify' '--timeout' '10m'"));
+
+    // Intervals which cron can't express and empty intervals are rejected.
+    assert!(!stall(&["install", "--interval", "5h", "--backend", "cron",
+        "--dry-run"]).status.success());
+    assert!(!stall(&["install", "--interval", "0h", "--dry-run"])
+        .status
+        .success());
+
+    let output = stall(&["status", "--backend", "launchd"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("No schedules installed"));
+}
+
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+pub fn notify_on_failure() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let prefs_path = temp_dir_b.path().join("prefs");
+    let log_path = temp_dir_b.path().join("notifications");
+    let remote = temp_dir_b.path().join("bashrc");
+    std::fs::write(&remote, "bashrc\n").unwrap();
+    std::fs::write(&prefs_path, "()\n").unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n",
+            remote.display()))
+        .unwrap();
+
+    let stall = |args: &[&str]| stall_command()
+        .arg(args[0])
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .output()
+        .unwrap();
+
+    let notify = format!("printf '%s|%s' {{title}} {{message}} > '{}'",
+        log_path.display());
+    assert!(stall(&["prefs", "set", "notify", &notify]).status.success());
+
+    // Successful commands and commands without --notify don't notify.
+    let stall_arg = stall_path.to_str().unwrap();
+    assert!(stall(&["collect", "--stall", stall_arg, "--notify"])
+        .status
+        .success());
+    assert!(!stall(&["collect", "--stall", stall_arg, "missing"])
+        .status
+        .success());
+    assert!(!log_path.exists());
+
+    assert!(!stall(&["collect", "--stall", stall_arg, "--notify", "missing"])
+        .status
+        .success());
+    let notification = std::fs::read_to_string(&log_path).unwrap();
+    assert!(notification.starts_with("stall collect failed|"));
+    assert!(notification.contains("missing"));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn stall_file_or_dir_resolution() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote = temp_dir_b.path().join("bashrc");
+    let prefs_path = temp_dir_b.path().join("prefs");
+    std::fs::write(&remote, "bashrc\n").unwrap();
+    std::fs::write(&prefs_path, "()\n").unwrap();
+
+    let stall = |dir: &Path, args: &[&str]| stall_command()
+        .current_dir(dir)
+        .arg(args[0])
+        .arg("--prefs")
+        .arg(&prefs_path)
+        .args(&args[1..])
+        .env_remove("STALL_FILE")
+        .env_remove("STALL_DIR")
+        .output()
+        .unwrap();
+
+    // A nonexistent path with an extension is a stall file.
+    let stall_file = stall_path.join("stall.ron");
+    assert!(stall(stall_path, &["init", "--stall", "stall.ron"])
+        .status
+        .success());
+    assert!(stall_file.exists());
+    assert!(!stall_path.join(".stall").exists());
+
+    // A directory is searched for stall.ron, as is the upward search.
+    let subdir = stall_path.join("sub");
+    std::fs::create_dir(&subdir).unwrap();
+    let stall_arg = stall_path.to_str().unwrap();
+    assert!(stall(&subdir, &["add", "--stall", stall_arg,
+            remote.to_str().unwrap()])
+        .status
+        .success());
+    let output = stall(&subdir, &["list"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("bashrc"));
+
+    // The stall file names can be set in the prefs, and init uses the first.
+    let other_path = temp_dir_b.path().join("other");
+    std::fs::create_dir(&other_path).unwrap();
+    assert!(stall(&other_path, &["prefs", "set", "stall_files",
+            "dotfiles, stall.ron"])
+        .status
+        .success());
+    assert!(stall(&other_path, &["init"]).status.success());
+    assert!(other_path.join("dotfiles").exists());
+    assert!(stall(&subdir, &["list"]).status.success());
+    assert!(!stall(&subdir, &["prefs", "set", "stall_files", "a/b"])
+        .status
+        .success());
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn add_from_remote_list() {
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    std::fs::create_dir(remote_path.join("a")).unwrap();
+    std::fs::create_dir(remote_path.join("b")).unwrap();
+    std::fs::write(remote_path.join("a/config"), "a\n").unwrap();
+    std::fs::write(remote_path.join("b/config"), "b\n").unwrap();
+    std::fs::write(remote_path.join("bashrc"), "bashrc\n").unwrap();
+    std::fs::write(remote_path.join("list"), format!("\
+        # Files to stall.\n\
+        {}\n\
+        \n\
+        b/config\n\
+        shell-rc -> {}\n",
+        remote_path.join("a/config").display(),
+        remote_path.join("bashrc").display()))
+        .unwrap();
+
+    assert!(stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap()
+        .status
+        .success());
+
+    let output = stall_command()
+        .args(["add", "--collect", "--disambiguate", "number", "--stall"])
+        .arg(stall_path)
+        .arg("--from-remote-list")
+        .arg(remote_path.join("list"))
+        .output()
+        .unwrap();
+
+    println!("{}", String::from_utf8(output.stdout).unwrap());
+    println!("{}", String::from_utf8(output.stderr).unwrap());
+
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(stall_path.join("config")).unwrap(),
+        "a\n");
+    assert_eq!(std::fs::read_to_string(stall_path.join("config-2")).unwrap(),
+        "b\n");
+    assert!(file_exists(stall_path.join("shell-rc")));
+}
+
+
+#[test]
+#[tracing::instrument]
+pub fn stdin_paths() {
+    use std::io::Write as _;
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    std::fs::write(remote_path.join("a"), "a\n").unwrap();
+    std::fs::write(remote_path.join("b c"), "b c\n").unwrap();
+    std::fs::write(remote_path.join("d"), "d\n").unwrap();
+
+    let stall = |args: &[&str], input: &str| {
+        let mut child = stall_command()
+            .arg(args[0])
+            .arg("--stall")
+            .arg(stall_path)
+            .args(&args[1..])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    assert!(stall_command()
+        .arg("init")
+        .arg(stall_path)
+        .output()
+        .unwrap()
+        .status
+        .success());
+
+    // NUL-separated paths may contain spaces.
+    let input = format!("{}\0{}\0",
+        remote_path.join("a").display(),
+        remote_path.join("b c").display());
+    assert!(stall(&["add", "--collect", "--stdin", "-0"], &input)
+        .status
+        .success());
+    assert!(file_exists(stall_path.join("a")));
+    assert!(file_exists(stall_path.join("b c")));
+
+    // A `-` argument reads newline-separated paths.
+    let input = format!("{}\n", remote_path.join("d").display());
+    assert!(stall(&["add", "--collect", "-"], &input).status.success());
+    assert!(file_exists(stall_path.join("d")));
+
+    assert!(stall(&["rm", "--stdin"], "a\nd\n").status.success());
+    let output = stall(&["list"], "");
+    let listed = String::from_utf8(output.stdout).unwrap();
+    assert!(!listed.contains(&*remote_path.join("a").to_string_lossy()));
+    assert!(listed.contains("b c"));
+}
+
+
+#[test]
+pub fn command_reports() {
+    use crate::entry::CopyOptions;
+    use crate::entry::EntryOrder;
+    use crate::entry::Outcome;
+    use crate::entry::Overwrite;
+    use crate::CommonOptions;
+    use crate::StallState;
+    use crate::StateFilter;
+    use crate::Stall;
+    use clap::Parser as _;
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    std::fs::write(remote_path.join("a"), "a\n").unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n{}\n",
+            remote_path.join("a").display(),
+            remote_path.join("b").display()))
+        .unwrap();
+
+    let common = CommonOptions::parse_from(["stall"]);
+    let stall = Stall::read_from_path(stall_path.join(".stall")).unwrap();
+
+    // The status of each entry is written into the given writer, and the stall
+    // directory is left to the caller.
+    let mut out = Vec::new();
+    let report = crate::collect(
+            &mut out,
+            stall_path,
+            &stall,
+            std::iter::empty(),
+            Overwrite::default(),
+            false,
+            &CopyOptions::default(),
+            None,
+            None,
+            false,
+            false,
+            &common)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(&remote_path.join("a").display().to_string()));
+    assert!(!out.contains("Stall directory"));
+    assert_eq!(report.total(), 2);
+    assert_eq!(report.copied().collect::<Vec<_>>(), [Path::new("a")]);
+    assert!(report.entries()
+        .iter()
+        .any(|entry| entry.local == Path::new("b")
+            && entry.result.as_ref().ok() != Some(&Outcome::Copied)));
+
+    let report = crate::status(
+            stall_path,
+            &stall,
+            std::iter::empty(),
+            StateFilter::default(),
+            EntryOrder::Local,
+            false)
+        .unwrap();
+    assert_eq!(report.total, 2);
+    assert_eq!(report.entries[0].local, Path::new("a"));
+    assert_eq!(report.entries[0].state, StallState::Clean);
+    assert_eq!(report.state(), StallState::Error);
+}
+
+
+#[cfg(feature = "async")]
+#[test]
+pub fn nonblocking_collect_distribute() {
+    use crate::entry::CopyOptions;
+    use crate::entry::Overwrite;
+    use crate::CommonOptions;
+    use crate::DistributeOptions;
+    use crate::Stall;
+    use clap::Parser as _;
+    use std::sync::Arc;
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path().to_owned();
+    let remote_path = temp_dir_b.path();
+    std::fs::write(remote_path.join("a"), "a\n").unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n",
+            remote_path.join("a").display()))
+        .unwrap();
+
+    let common = CommonOptions::parse_from(["stall", "--quiet"]);
+    let stall = Arc::new(
+        Stall::read_from_path(stall_path.join(".stall")).unwrap());
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let report = runtime.block_on(crate::nonblocking::collect(
+            stall_path.clone(),
+            stall.clone(),
+            Vec::new(),
+            Overwrite::default(),
+            false,
+            CopyOptions::default(),
+            false,
+            common.clone()))
+        .unwrap();
+    assert_eq!(report.copied().collect::<Vec<_>>(), [Path::new("a")]);
+    assert_eq!(std::fs::read_to_string(stall_path.join("a")).unwrap(), "a\n");
+
+    std::fs::write(stall_path.join("a"), "b\n").unwrap();
+    let report = runtime.block_on(crate::nonblocking::distribute(
+            stall_path,
+            stall,
+            Vec::new(),
+            Overwrite::default(),
+            DistributeOptions::default(),
+            CopyOptions::default(),
+            common))
+        .unwrap();
+    assert_eq!(report.copied().collect::<Vec<_>>(), [Path::new("a")]);
+    assert_eq!(std::fs::read_to_string(remote_path.join("a")).unwrap(), "b\n");
+}
+
+
+#[cfg(feature = "ffi")]
+#[test]
+#[allow(unsafe_code)]
+pub fn ffi_collect() {
+    use crate::ffi::*;
+    use std::ffi::c_char;
+    use std::ffi::c_int;
+    use std::ffi::c_void;
+    use std::ffi::CStr;
+    use std::ffi::CString;
+
+    unsafe extern "C" fn on_entry(
+        local: *const c_char,
+        _remote: *const c_char,
+        user_data: *mut c_void)
+    {
+        let names = unsafe { &mut *user_data.cast::<Vec<String>>() };
+        let local = unsafe { CStr::from_ptr(local) };
+        names.push(local.to_string_lossy().into_owned());
+    }
+
+    unsafe extern "C" fn on_progress(
+        local: *const c_char,
+        outcome: c_int,
+        _message: *const c_char,
+        user_data: *mut c_void)
+    {
+        let results = unsafe { &mut *user_data.cast::<Vec<(String, c_int)>>() };
+        let local = unsafe { CStr::from_ptr(local) };
+        results.push((local.to_string_lossy().into_owned(), outcome));
+    }
+
+    let temp_dir_a = TempDir::new().expect("create temp dir");
+    let temp_dir_b = TempDir::new().expect("create temp dir");
+    let stall_path = temp_dir_a.path();
+    let remote_path = temp_dir_b.path();
+    std::fs::write(remote_path.join("a"), "a\n").unwrap();
+    std::fs::write(stall_path.join(".stall"), format!("{}\n{}\n",
+            remote_path.join("a").display(),
+            remote_path.join("b").display()))
+        .unwrap();
+
+    let path = CString::new(stall_path.display().to_string()).unwrap();
+    let handle = unsafe { stall_open(path.as_ptr()) };
+    assert!(!handle.is_null());
+
+    let mut names: Vec<String> = Vec::new();
+    let count = unsafe { stall_entries(
+        handle,
+        Some(on_entry),
+        std::ptr::addr_of_mut!(names).cast()) };
+    assert_eq!(count, 2);
+    assert_eq!(names, ["a", "b"]);
+
+    let mut results: Vec<(String, c_int)> = Vec::new();
+    let code = unsafe { stall_collect(
+        handle,
+        Some(on_progress),
+        std::ptr::addr_of_mut!(results).cast()) };
+    assert_eq!(code, 0);
+    assert!(stall_last_error().is_null());
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0], ("a".to_owned(), STALL_COPIED));
+    assert_eq!(results[1].0, "b");
+    assert_ne!(results[1].1, STALL_COPIED);
+    assert_eq!(std::fs::read_to_string(stall_path.join("a")).unwrap(), "a\n");
+
+    unsafe { stall_close(handle) };
+
+    let missing = CString::new(stall_path.join("missing.ron")
+        .display()
+        .to_string())
+        .unwrap();
+    assert!(unsafe { stall_open(missing.as_ptr()) }.is_null());
+    assert!(!stall_last_error().is_null());
+
+    // The error is cleared by the next call.
+    unsafe { stall_close(std::ptr::null_mut()) };
+    assert!(stall_last_error().is_null());
+}
@@ -0,0 +1,432 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! File content transforms.
+//!
+//! Transforms rewrite the contents of files after they are copied into or out
+//! of a stall. Each entry has a [`Pipeline`] of transforms, which are applied
+//! in order when distributing, and in reverse order when collecting:
+//!
+//! + `trim-whitespace` removes trailing whitespace from each line.
+//! + `collect-cmd=<CMD>` runs a shell command with the file contents as its
+//!   input, replacing them with its output. It is only run when collecting.
+//! + `distribute-cmd=<CMD>` runs a shell command as `collect-cmd` does, but
+//!   only when distributing.
+//!
+//! The line ending of the entry is applied last when distributing, and
+//! collected files have their line endings normalized to `\n` first, so that
+//! stalls can be shared between platforms without spurious differences.
+//! Built-in transforms skip binary files, but commands are run for all files.
+//!
+//! [`Pipeline`]: struct.Pipeline.html
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::entry::EntryFlags;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::platform::shell_command;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+use std::process::Stdio;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The number of bytes at the start of a file which are checked for NUL bytes
+/// to detect binary files.
+const BINARY_CHECK_LEN: usize = 8000;
+
+/// The environment variable holding the path of the file being transformed by
+/// a command transform.
+pub const STALL_PATH_ENV: &str = "STALL_PATH";
+
+
+////////////////////////////////////////////////////////////////////////////////
+// LineEnding
+////////////////////////////////////////////////////////////////////////////////
+/// The line ending to use for an entry's remote files.
+///
+/// Line endings are serialized as strings, as untagged stall file entries
+/// can't hold enum variants.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+#[derive(clap::ArgEnum)]
+pub enum LineEnding {
+	/// Use `\n` line endings.
+	Lf,
+	/// Use `\r\n` line endings.
+	Crlf,
+	/// Use the line endings of the current platform.
+	Native,
+	/// Copy files without changing their line endings.
+	#[default]
+	Preserve,
+}
+
+impl LineEnding {
+	/// Returns `true` if files are copied without changing their line
+	/// endings.
+	#[must_use]
+	pub const fn is_preserve(&self) -> bool {
+		matches!(self, Self::Preserve)
+	}
+
+	/// Returns the line ending with `Native` resolved for the current
+	/// platform.
+	#[must_use]
+	pub const fn resolve(self) -> Self {
+		match self {
+			Self::Native if cfg!(windows) => Self::Crlf,
+			Self::Native                  => Self::Lf,
+			_                             => self,
+		}
+	}
+}
+
+impl std::fmt::Display for LineEnding {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Lf       => write!(f, "lf"),
+			Self::Crlf     => write!(f, "crlf"),
+			Self::Native   => write!(f, "native"),
+			Self::Preserve => write!(f, "preserve"),
+		}
+	}
+}
+
+impl TryFrom<String> for LineEnding {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		<Self as clap::ArgEnum>::from_str(&value, false)
+	}
+}
+
+impl From<LineEnding> for String {
+	fn from(eol: LineEnding) -> Self {
+		eol.to_string()
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Transform
+////////////////////////////////////////////////////////////////////////////////
+/// A transform of file contents in an entry's [`Pipeline`].
+///
+/// Transforms are serialized as strings, as untagged stall file entries can't
+/// hold enum variants.
+///
+/// [`Pipeline`]: struct.Pipeline.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Transform {
+	/// Remove trailing whitespace from each line.
+	TrimWhitespace,
+	/// Replace the file contents with the output of a shell command when
+	/// collecting.
+	CollectCommand(String),
+	/// Replace the file contents with the output of a shell command when
+	/// distributing.
+	DistributeCommand(String),
+}
+
+impl std::str::FromStr for Transform {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let (name, arg) = match text.split_once('=') {
+			Some((name, arg)) => (name.trim(), Some(arg)),
+			None              => (text.trim(), None),
+		};
+		match (name, arg) {
+			("trim-whitespace", None) => Ok(Self::TrimWhitespace),
+			("collect-cmd", Some(command)) if !command.trim().is_empty()
+				=> Ok(Self::CollectCommand(command.to_owned())),
+			("distribute-cmd", Some(command)) if !command.trim().is_empty()
+				=> Ok(Self::DistributeCommand(command.to_owned())),
+			_ => Err(format!("invalid transform {text:?}: expected \
+				trim-whitespace, collect-cmd=<CMD>, or distribute-cmd=<CMD>")),
+		}
+	}
+}
+
+impl std::fmt::Display for Transform {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::TrimWhitespace             => write!(f, "trim-whitespace"),
+			Self::CollectCommand(command)    => write!(f, "collect-cmd={command}"),
+			Self::DistributeCommand(command) => {
+				write!(f, "distribute-cmd={command}")
+			},
+		}
+	}
+}
+
+impl TryFrom<String> for Transform {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		value.parse()
+	}
+}
+
+impl From<Transform> for String {
+	fn from(transform: Transform) -> Self {
+		transform.to_string()
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Direction
+////////////////////////////////////////////////////////////////////////////////
+/// The direction in which an entry's files are copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+	/// Files are copied from their remote locations into the stall.
+	Collect,
+	/// Files are copied from the stall to their remote locations.
+	Distribute,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Pipeline
+////////////////////////////////////////////////////////////////////////////////
+/// The transforms applied to an entry's files when they are copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pipeline<'a> {
+	/// The line ending of the entry's remote files.
+	eol: LineEnding,
+	/// Whether the entry's files are binary.
+	binary: bool,
+	/// The entry's transforms, in distribute order.
+	transforms: &'a [Transform],
+}
+
+/// A single step of a [`Pipeline`] in a particular direction.
+///
+/// [`Pipeline`]: struct.Pipeline.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(variant_size_differences)]
+enum Stage<'a> {
+	/// Convert line endings.
+	Eol(LineEnding),
+	/// Remove trailing whitespace.
+	TrimWhitespace,
+	/// Run a shell command.
+	Command(&'a str),
+}
+
+impl<'a> Pipeline<'a> {
+	/// Constructs a new `Pipeline` for an entry with the given flags and
+	/// transforms.
+	#[must_use]
+	pub const fn new(flags: EntryFlags, transforms: &'a [Transform]) -> Self {
+		Self {
+			eol: flags.eol,
+			binary: flags.binary,
+			transforms,
+		}
+	}
+
+	/// Returns the stages to apply in the given direction, in order.
+	fn stages(&self, direction: Direction) -> Vec<Stage<'a>> {
+		let mut stages: Vec<Stage<'a>> = self.transforms
+			.iter()
+			.filter_map(|transform| match (transform, direction) {
+				(Transform::TrimWhitespace, _) => Some(Stage::TrimWhitespace),
+				(Transform::CollectCommand(command), Direction::Collect) |
+				(Transform::DistributeCommand(command), Direction::Distribute)
+					=> Some(Stage::Command(command)),
+				_ => None,
+			})
+			.collect();
+		if self.binary {
+			stages.retain(|stage| matches!(stage, Stage::Command(_)));
+		}
+		let eol = !self.binary && !self.eol.is_preserve();
+		match direction {
+			Direction::Collect => {
+				stages.reverse();
+				if eol { stages.insert(0, Stage::Eol(LineEnding::Lf)); }
+			},
+			Direction::Distribute => {
+				if eol { stages.push(Stage::Eol(self.eol)); }
+			},
+		}
+		stages
+	}
+
+	/// Returns `true` if no stages are applied in the given direction.
+	#[must_use]
+	pub fn is_empty(&self, direction: Direction) -> bool {
+		self.stages(direction).is_empty()
+	}
+
+	/// Returns descriptions of the stages applied in the given direction, in
+	/// order.
+	#[must_use]
+	pub fn describe(&self, direction: Direction) -> Vec<String> {
+		self.stages(direction)
+			.into_iter()
+			.map(|stage| match stage {
+				Stage::Eol(eol)         => format!("eol={eol}"),
+				Stage::TrimWhitespace   => "trim-whitespace".to_owned(),
+				Stage::Command(command) => format!("run `{command}`"),
+			})
+			.collect()
+	}
+
+	/// Applies the stages for the given direction to the file at `path`, or to
+	/// the files within it if it is a directory. Symbolic links are skipped,
+	/// and transformed files keep their modification times, so that they
+	/// aren't reported as modified.
+	pub fn apply(&self, path: &Path, direction: Direction)
+		-> Result<(), StallError>
+	{
+		let stages = self.stages(direction);
+		if stages.is_empty() { return Ok(()); }
+		apply_stages(path, &stages)
+	}
+}
+
+/// Applies the given stages to the file at `path`, or to the files within it
+/// if it is a directory.
+fn apply_stages(path: &Path, stages: &[Stage<'_>]) -> Result<(), StallError> {
+	let metadata = std::fs::symlink_metadata(path)
+		.with_path("failed to read metadata for", path)?;
+	if metadata.is_dir() {
+		for entry in std::fs::read_dir(path)
+			.with_path("failed to read directory", path)?
+		{
+			let entry = entry.with_path("failed to read directory", path)?;
+			apply_stages(&entry.path(), stages)?;
+		}
+		return Ok(());
+	}
+	if !metadata.is_file() { return Ok(()); }
+
+	let original = std::fs::read(path)
+		.with_path("failed to read", path)?;
+	let mut bytes = original.clone();
+	for stage in stages {
+		bytes = match stage {
+			_ if is_binary(&bytes) && !matches!(stage, Stage::Command(_))
+				=> bytes,
+			Stage::Eol(eol)         => with_line_endings(&bytes, *eol),
+			Stage::TrimWhitespace   => trim_whitespace(&bytes),
+			Stage::Command(command) => run_command(command, path, bytes)?,
+		};
+	}
+	if bytes == original { return Ok(()); }
+
+	event!(Level::DEBUG, "Writing transformed contents of {:?}", path);
+	std::fs::write(path, bytes)
+		.with_path("failed to write", path)?;
+	if let Ok(modified) = metadata.modified() {
+		std::fs::File::options()
+			.write(true)
+			.open(path)
+			.and_then(|file| file.set_modified(modified))
+			.with_path("failed to set modification time for", path)?;
+	}
+	Ok(())
+}
+
+/// Returns the given file contents with every line ending replaced by the
+/// given [`LineEnding`]. `Preserve` leaves the contents unchanged.
+///
+/// [`LineEnding`]: enum.LineEnding.html
+fn with_line_endings(bytes: &[u8], eol: LineEnding) -> Vec<u8> {
+	let eol = eol.resolve();
+	if eol.is_preserve() { return bytes.to_vec(); }
+
+	let mut converted = Vec::with_capacity(bytes.len());
+	let mut rest = bytes;
+	while let Some(idx) = rest.iter().position(|b| *b == b'\n') {
+		let line = &rest[..idx];
+		converted.extend_from_slice(line.strip_suffix(b"\r").unwrap_or(line));
+		if eol == LineEnding::Crlf { converted.push(b'\r'); }
+		converted.push(b'\n');
+		rest = &rest[idx + 1..];
+	}
+	converted.extend_from_slice(rest);
+	converted
+}
+
+/// Returns the given file contents with the spaces and tabs at the end of each
+/// line removed.
+fn trim_whitespace(bytes: &[u8]) -> Vec<u8> {
+	let trim = |line: &[u8]| -> usize {
+		line.iter().rposition(|b| !matches!(b, b' ' | b'\t')).map_or(0, |i| i + 1)
+	};
+	let mut trimmed = Vec::with_capacity(bytes.len());
+	for line in bytes.split_inclusive(|b| *b == b'\n') {
+		let (text, end): (&[u8], &[u8]) = if line.ends_with(b"\r\n") {
+			(&line[..line.len() - 2], b"\r\n")
+		} else if line.ends_with(b"\n") {
+			(&line[..line.len() - 1], b"\n")
+		} else {
+			(line, b"")
+		};
+		trimmed.extend_from_slice(&text[..trim(text)]);
+		trimmed.extend_from_slice(end);
+	}
+	trimmed
+}
+
+/// Returns the output of the given shell command, run with the given file
+/// contents as its input. The path of the file is given in the `STALL_PATH`
+/// environment variable.
+fn run_command(command: &str, path: &Path, input: Vec<u8>)
+	-> Result<Vec<u8>, StallError>
+{
+	event!(Level::DEBUG, "Running transform command for {:?}", path);
+	let mut child = shell_command(command)
+		.env(STALL_PATH_ENV, path)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.spawn()
+		.with_path("failed to run transform command for", path)?;
+
+	// The input is written from another thread so that the command's output
+	// can't fill its pipe and block it from reading.
+	let mut stdin = child.stdin.take().expect("take command stdin");
+	let writer = std::thread::spawn(move || stdin.write_all(&input));
+	let output = child.wait_with_output()
+		.with_path("failed to run transform command for", path)?;
+	let written = writer.join().expect("join command input thread");
+
+	if !output.status.success() {
+		return Err(StallError::CommandFailed {
+			message: format!("transform command failed for {}: {}",
+				path.display(),
+				output.status),
+		});
+	}
+	written.with_path("failed to write transform command input for", path)?;
+	Ok(output.stdout)
+}
+
+/// Returns `true` if the given file contents appear to be binary, because a
+/// NUL byte occurs near the start.
+pub(in crate) fn is_binary(bytes: &[u8]) -> bool {
+	bytes.iter().take(BINARY_CHECK_LEN).any(|b| *b == 0)
+}
@@ -0,0 +1,298 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Recoverable storage for deleted and overwritten files.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::entry::copy;
+use crate::entry::CopyMethod;
+use crate::entry::CopyMode;
+use crate::entry::CopyOptions;
+use crate::remote;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Trash
+////////////////////////////////////////////////////////////////////////////////
+/// A trash directory holding files removed from a stall or overwritten by
+/// stall commands, so they can be restored later.
+///
+/// Each trashed file is stored in a numbered subdirectory of the trash
+/// directory, and an index file records the original location of each file.
+#[derive(Debug, Clone)]
+pub struct Trash {
+	/// The trash directory.
+	dir: PathBuf,
+	/// The trashed items.
+	items: Vec<TrashItem>,
+}
+
+impl Trash {
+	/// The default trash directory, relative to the stall directory.
+	pub const DEFAULT_TRASH_PATH: &'static str = ".stall-trash";
+
+	/// The name of the trash index file within the trash directory.
+	const INDEX_PATH: &'static str = "index.ron";
+
+	/// Opens the trash for the given stall directory. The trash directory is
+	/// `backup_dir` relative to the stall directory, or the default trash
+	/// directory if it is `None`.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the trash index exists but cannot be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn open(stall_dir: &Path, backup_dir: Option<&Path>)
+		-> Result<Self, StallError>
+	{
+		let dir = stall_dir.join(backup_dir
+			.unwrap_or_else(|| Path::new(Self::DEFAULT_TRASH_PATH)));
+		let index_path = dir.join(Self::INDEX_PATH);
+
+		let items = if index_path.exists() {
+			let text = std::fs::read_to_string(&index_path)
+				.with_path("failed to read trash index", &index_path)?;
+			ron::from_str(&text)
+				.map_err(|e| StallError::ron_parse(
+					"failed to parse trash index",
+					Some(&index_path),
+					&e))?
+		} else {
+			Vec::new()
+		};
+
+		Ok(Self { dir, items })
+	}
+
+	/// Returns the trash directory.
+	#[must_use]
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// Returns the trashed items, oldest first.
+	#[must_use]
+	pub fn items(&self) -> &[TrashItem] {
+		&self.items
+	}
+
+	/// Returns `true` if the trash contains no items.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.items.is_empty()
+	}
+
+	/// Moves the file at the given path into the trash, returning the trash
+	/// item id.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the file cannot be moved or the trash index
+	/// cannot be written.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn move_in(&mut self, path: &Path) -> Result<u64, StallError> {
+		let (id, target) = self.prepare_item(path)?;
+		if std::fs::rename(path, &target).is_err() {
+			// The trash may be on a different device, so fall back to copying.
+			copy(path, &target, CopyMethod::Direct)?;
+			remove_path(path)?;
+		}
+		self.push_item(id, path)
+	}
+
+	/// Copies the file at the given remote path into the trash, returning the
+	/// trash item id. The file is copied by the [`RemoteBackend`] for the
+	/// path.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the file cannot be copied or the trash index
+	/// cannot be written.
+	///
+	/// [`RemoteBackend`]: ../remote/trait.RemoteBackend.html
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn copy_in(&mut self, path: &Path) -> Result<u64, StallError> {
+		let (id, target) = self.prepare_item(path)?;
+		let _ = remote::backend(path)?.fetch(path, &target, &CopyOptions {
+			mode: CopyMode::Standard,
+			..CopyOptions::default()
+		})?;
+		self.push_item(id, path)
+	}
+
+	/// Restores the trash item with the given id to its original location,
+	/// returning the restored path.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the item does not exist, if a file exists at
+	/// the original location and `force` is false, or if the file cannot be
+	/// moved.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn restore(&mut self, id: u64, force: bool) -> Result<PathBuf, StallError> {
+		let index = self.items
+			.iter()
+			.position(|item| item.id == id)
+			.ok_or_else(|| StallError::NotFound {
+				kind: "trash item",
+				name: id.to_string(),
+				suggestions: Vec::new(),
+			})?;
+		let item = &self.items[index];
+		let original = item.original.clone();
+		let source = self.item_path(item);
+
+		if original.exists() {
+			if !force {
+				return Err(StallError::Conflict {
+					reason: "file already exists".to_owned(),
+					subject: original.display().to_string(),
+					hint: Some("Use --force option to overwrite it.".to_owned()),
+				});
+			}
+			remove_path(&original)?;
+		}
+		if std::fs::rename(&source, &original).is_err() {
+			copy(&source, &original, CopyMethod::Direct)?;
+		}
+		remove_path(&self.dir.join(id.to_string()))?;
+
+		let _ = self.items.remove(index);
+		self.write_index()?;
+		Ok(original)
+	}
+
+	/// Permanently deletes all trashed items, returning the number of items
+	/// deleted.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the trash directory cannot be removed.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn empty(&mut self) -> Result<usize, StallError> {
+		let count = self.items.len();
+		if self.dir.exists() {
+			std::fs::remove_dir_all(&self.dir)
+				.with_path("failed to remove trash directory", &self.dir)?;
+		}
+		self.items.clear();
+		Ok(count)
+	}
+
+	/// Returns the path of the stored file for the given item.
+	fn item_path(&self, item: &TrashItem) -> PathBuf {
+		let mut path = self.dir.join(item.id.to_string());
+		if let Some(name) = item.original.file_name() {
+			path.push(name);
+		}
+		path
+	}
+
+	/// Creates the storage directory for a new item, returning the item id and
+	/// the path to store the file at.
+	fn prepare_item(&self, path: &Path) -> Result<(u64, PathBuf), StallError> {
+		let id = self.items
+			.iter()
+			.map(|item| item.id + 1)
+			.max()
+			.unwrap_or(1);
+		let item_dir = self.dir.join(id.to_string());
+		std::fs::create_dir_all(&item_dir)
+			.with_path("failed to create trash directory", &item_dir)?;
+
+		let name = path.file_name()
+			.ok_or_else(|| StallError::InvalidPath {
+				path: path.to_path_buf(),
+				reason: "invalid file name".to_owned(),
+			})?;
+		Ok((id, item_dir.join(name)))
+	}
+
+	/// Records a new item in the trash index.
+	fn push_item(&mut self, id: u64, path: &Path) -> Result<u64, StallError> {
+		let original = std::fs::canonicalize(path.parent()
+				.unwrap_or_else(|| Path::new(".")))
+			.map_or_else(
+				|_| path.to_path_buf(),
+				|parent| parent.join(path.file_name().unwrap_or_default()));
+		let deleted = SystemTime::now()
+			.duration_since(SystemTime::UNIX_EPOCH)
+			.map_or(0, |d| d.as_secs());
+
+		event!(Level::INFO, "Moved {:?} to trash item {}", original, id);
+		self.items.push(TrashItem { id, original, deleted });
+		self.write_index()?;
+		Ok(id)
+	}
+
+	/// Writes the trash index into the trash directory.
+	fn write_index(&self) -> Result<(), StallError> {
+		std::fs::create_dir_all(&self.dir)
+			.with_path("failed to create trash directory", &self.dir)?;
+		let index_path = self.dir.join(Self::INDEX_PATH);
+		let pretty = ron::ser::PrettyConfig::new();
+		let text = ron::ser::to_string_pretty(&self.items, pretty)
+			.map_err(|e| StallError::ron_serialize(
+				"failed to serialize trash index",
+				&e))?;
+		std::fs::write(&index_path, text)
+			.with_path("failed to write trash index", &index_path)
+	}
+}
+
+
+/// Removes the file or directory at the given path.
+pub(in crate) fn remove_path(path: &Path) -> Result<(), StallError> {
+	let res = if path.is_dir() {
+		std::fs::remove_dir_all(path)
+	} else {
+		std::fs::remove_file(path)
+	};
+	res.with_path("failed to remove", path)
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TrashItem
+////////////////////////////////////////////////////////////////////////////////
+/// A file stored in the trash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct TrashItem {
+	/// The trash item id.
+	pub id: u64,
+	/// The original path of the file.
+	pub original: PathBuf,
+	/// The time the file was trashed, in seconds since the Unix epoch.
+	pub deleted: u64,
+}
+
+impl TrashItem {
+	/// Returns the time the file was trashed.
+	#[must_use]
+	pub fn deleted_time(&self) -> SystemTime {
+		SystemTime::UNIX_EPOCH + Duration::from_secs(self.deleted)
+	}
+}
@@ -0,0 +1,318 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Previous versions of stalled files.
+//!
+//! When a [`Retention`] policy is configured with the `version_retention`
+//! preference, `collect` saves each stalled file it overwrites under
+//! `.stall-versions/<entry>/<timestamp>` in the stall directory, and then
+//! deletes the versions which are no longer retained.
+//!
+//! [`Retention`]: enum.Retention.html
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::entry::copy;
+use crate::entry::CopyMethod;
+use crate::error::IoResultExt as _;
+use crate::error::StallError;
+use crate::trash::remove_path;
+
+// External library imports.
+use chrono::DateTime;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::event;
+use tracing::Level;
+
+// Standard library imports.
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+/// The format of version names, in UTC.
+const VERSION_NAME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// The number of seconds in a day.
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Retention
+////////////////////////////////////////////////////////////////////////////////
+/// A policy for which previous versions of a stalled file are kept.
+///
+/// Retention policies are serialized as strings: a number of versions, such
+/// as `10`, or a number of days followed by `d`, such as `30d`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Retention {
+	/// Keep the given number of most recent versions.
+	Count(usize),
+	/// Keep the versions saved within the given number of days.
+	Days(u64),
+}
+
+impl Retention {
+	/// Returns `true` if a version saved at the given time is retained, given
+	/// the number of versions saved after it.
+	#[must_use]
+	pub fn retains(self, saved: SystemTime, newer: usize, now: SystemTime)
+		-> bool
+	{
+		match self {
+			Self::Count(count) => newer < count,
+			Self::Days(days) => now
+				.duration_since(saved)
+				.map_or(true, |age| age
+					<= Duration::from_secs(days * SECONDS_PER_DAY)),
+		}
+	}
+}
+
+impl std::str::FromStr for Retention {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let text = text.trim();
+		let retention = text.strip_suffix('d').map_or_else(
+			|| text.parse().map(Self::Count),
+			|days| days.parse().map(Self::Days));
+		retention.map_err(|e| format!("invalid version retention {text:?}: \
+			{e} (expected a number of versions, or a number of days followed \
+			by 'd')"))
+	}
+}
+
+impl std::fmt::Display for Retention {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Count(count) => write!(f, "{count}"),
+			Self::Days(days)   => write!(f, "{days}d"),
+		}
+	}
+}
+
+impl TryFrom<String> for Retention {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		value.parse()
+	}
+}
+
+impl From<Retention> for String {
+	fn from(retention: Retention) -> Self {
+		retention.to_string()
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Versions
+////////////////////////////////////////////////////////////////////////////////
+/// The saved previous versions of a stall entry's file.
+///
+/// Each version is a copy of the stalled file, named by the UTC time it was
+/// saved. Versions saved within the same second are given a numeric suffix.
+#[derive(Debug, Clone)]
+pub struct Versions {
+	/// The directory holding the entry's versions.
+	dir: PathBuf,
+}
+
+impl Versions {
+	/// The versions directory, relative to the stall directory.
+	pub const VERSIONS_PATH: &'static str = ".stall-versions";
+
+	/// Opens the versions of the entry with the given local path in the given
+	/// stall directory.
+	#[must_use]
+	pub fn open(stall_dir: &Path, local: &Path) -> Self {
+		Self {
+			dir: stall_dir.join(Self::VERSIONS_PATH).join(local),
+		}
+	}
+
+	/// Returns the directory holding the entry's versions.
+	#[must_use]
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// Returns the saved versions, oldest first.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the versions directory cannot be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn list(&self) -> Result<Vec<Version>, StallError> {
+		if !self.dir.exists() { return Ok(Vec::new()); }
+
+		let mut versions = Vec::new();
+		let read_dir = std::fs::read_dir(&self.dir)
+			.with_path("failed to read versions directory", &self.dir)?;
+		for dir_entry in read_dir {
+			let dir_entry = dir_entry
+				.with_path("failed to read versions directory", &self.dir)?;
+			let name = dir_entry.file_name().to_string_lossy().into_owned();
+			if let Some(version) = Version::parse(&name, dir_entry.path()) {
+				versions.push(version);
+			}
+		}
+		versions.sort_by_key(|version| (version.saved, version.sequence));
+		Ok(versions)
+	}
+
+	/// Returns the version with the given name, or the most recent version if
+	/// no name is given.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the version does not exist or the versions
+	/// directory cannot be read.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn get(&self, name: Option<&str>) -> Result<Version, StallError> {
+		let mut versions = self.list()?;
+		let index = name.map_or_else(
+			|| versions.len().checked_sub(1),
+			|name| versions.iter().position(|version| version.name == name));
+		index
+			.map(|index| versions.swap_remove(index))
+			.ok_or_else(|| StallError::NotFound {
+				kind: "version",
+				name: name.unwrap_or("latest").to_owned(),
+				suggestions: Vec::new(),
+			})
+	}
+
+	/// Copies the file at the given path into a new version, returning the
+	/// version.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the file cannot be copied.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn save(&self, path: &Path) -> Result<Version, StallError> {
+		std::fs::create_dir_all(&self.dir)
+			.with_path("failed to create versions directory", &self.dir)?;
+
+		let saved: DateTime<Utc> = SystemTime::now().into();
+		let base = saved.format(VERSION_NAME_FORMAT).to_string();
+		// Versions saved in the same second are numbered after the last of
+		// them, so that they remain in order if earlier ones are deleted.
+		let name = self.list()?
+			.iter()
+			.filter(|version| version.name.starts_with(&base))
+			.map(|version| version.sequence + 1)
+			.max()
+			.map_or_else(
+				|| base.clone(),
+				|sequence| format!("{base}-{sequence}"));
+
+		let target = self.dir.join(&name);
+		copy(path, &target, CopyMethod::Direct)?;
+		event!(Level::INFO, "Saved {:?} as version {}", path, name);
+		Version::parse(&name, target)
+			.ok_or_else(|| StallError::InvalidPath {
+				path: self.dir.join(&name),
+				reason: "invalid version name".to_owned(),
+			})
+	}
+
+	/// Replaces the file at the given path with the given version. The
+	/// replaced file is saved as a new version first, so that the restore can
+	/// be undone.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the files cannot be copied.
+	///
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn restore(&self, version: &Version, path: &Path)
+		-> Result<(), StallError>
+	{
+		if path.exists() {
+			let _ = self.save(path)?;
+			remove_path(path)?;
+		}
+		copy(&version.path, path, CopyMethod::Direct)?;
+		event!(Level::INFO, "Restored version {} to {:?}", version.name, path);
+		Ok(())
+	}
+
+	/// Deletes the versions which are not kept by the given [`Retention`]
+	/// policy, returning the number of versions deleted.
+	///
+	/// ### Errors
+	///
+	/// Returns a [`StallError`] if the versions cannot be read or deleted.
+	///
+	/// [`Retention`]: enum.Retention.html
+	/// [`StallError`]: ../error/enum.StallError.html
+	pub fn prune(&self, retention: Retention) -> Result<usize, StallError> {
+		let versions = self.list()?;
+		let now = SystemTime::now();
+		let mut deleted = 0;
+		for (i, version) in versions.iter().enumerate() {
+			let newer = versions.len() - i - 1;
+			if !retention.retains(version.saved, newer, now) {
+				remove_path(&version.path)?;
+				event!(Level::DEBUG, "Deleted version {}", version.name);
+				deleted += 1;
+			}
+		}
+		Ok(deleted)
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Version
+////////////////////////////////////////////////////////////////////////////////
+/// A saved previous version of a stalled file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+	/// The name of the version.
+	pub name: String,
+	/// The path of the saved file.
+	pub path: PathBuf,
+	/// The time the version was saved.
+	pub saved: SystemTime,
+	/// The suffix distinguishing versions saved within the same second.
+	sequence: u32,
+}
+
+impl Version {
+	/// Parses a version from its name, returning `None` if the name is not a
+	/// valid version name.
+	fn parse(name: &str, path: PathBuf) -> Option<Self> {
+		let (time, sequence) = match name.split_once('-') {
+			Some((time, sequence)) => (time, sequence.parse().ok()?),
+			None => (name, 0),
+		};
+		let saved = NaiveDateTime::parse_from_str(time, VERSION_NAME_FORMAT)
+			.ok()?
+			.and_utc();
+		Some(Self {
+			name: name.to_owned(),
+			path,
+			saved: saved.into(),
+			sequence,
+		})
+	}
+}
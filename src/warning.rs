@@ -0,0 +1,203 @@
+////////////////////////////////////////////////////////////////////////////////
+// Stall configuration management utility
+////////////////////////////////////////////////////////////////////////////////
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Command warnings.
+////////////////////////////////////////////////////////////////////////////////
+
+// Internal library imports.
+use crate::entry::Attribute;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+
+// Standard library imports.
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Warning
+////////////////////////////////////////////////////////////////////////////////
+/// A non-fatal problem found while running a command.
+///
+/// Warnings are emitted with [`report::warn`], which suppresses warnings of
+/// allowed kinds and promotes the rest to errors if requested.
+///
+/// [`report::warn`]: ../report/fn.warn.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+	/// A stalled file's remote file does not exist.
+	MissingRemote {
+		/// The path of the remote file.
+		path: PathBuf,
+	},
+	/// A stalled file does not exist in the stall directory.
+	MissingLocal {
+		/// The path of the stalled file.
+		path: PathBuf,
+	},
+	/// A file exists but its metadata could not be read.
+	UnreadableFile {
+		/// The path of the file.
+		path: PathBuf,
+	},
+	/// A remote file's parent directory does not exist.
+	MissingRemoteDir {
+		/// The path of the missing directory.
+		path: PathBuf,
+	},
+	/// A remote path has no file name to use for the stalled file.
+	InvalidRemoteName {
+		/// The remote path.
+		path: PathBuf,
+	},
+	/// A stalled file could not be deleted.
+	RemoveFailed {
+		/// The path of the stalled file.
+		path: PathBuf,
+		/// A description of the failure.
+		reason: String,
+	},
+	/// A registered stall path does not exist.
+	MissingStall {
+		/// The stall path.
+		path: PathBuf,
+	},
+	/// A file attribute could not be copied.
+	PreserveFailed {
+		/// The path of the copied file.
+		path: PathBuf,
+		/// The attribute which was not copied.
+		attribute: Attribute,
+		/// A description of the failure.
+		reason: String,
+	},
+	/// Two entries' files overlap, so that copying one may copy the other.
+	OverlappingEntries {
+		/// The local path of the overlapping entry.
+		local: PathBuf,
+		/// The local path of the entry it overlaps.
+		other: PathBuf,
+		/// How the entries overlap.
+		overlap: Overlap,
+	},
+}
+
+impl Warning {
+	/// Returns the kind of the warning.
+	#[must_use]
+	pub const fn kind(&self) -> WarningKind {
+		match self {
+			Self::MissingRemote { .. }     => WarningKind::MissingRemote,
+			Self::MissingLocal { .. }      => WarningKind::MissingLocal,
+			Self::UnreadableFile { .. }    => WarningKind::Unreadable,
+			Self::MissingRemoteDir { .. }  => WarningKind::MissingDir,
+			Self::InvalidRemoteName { .. } => WarningKind::InvalidName,
+			Self::RemoveFailed { .. }      => WarningKind::RemoveFailed,
+			Self::MissingStall { .. }      => WarningKind::MissingStall,
+			Self::PreserveFailed { .. }    => WarningKind::PreserveFailed,
+			Self::OverlappingEntries { .. } => WarningKind::Overlapping,
+		}
+	}
+}
+
+impl std::fmt::Display for Warning {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::MissingRemote { path } => write!(f,
+				"remote file does not exist: {}",
+				path.display()),
+			Self::MissingLocal { path } => write!(f,
+				"stalled file does not exist: {}",
+				path.display()),
+			Self::UnreadableFile { path } => write!(f,
+				"unable to read file metadata: {}",
+				path.display()),
+			Self::MissingRemoteDir { path } => write!(f,
+				"remote directory does not exist: {} \
+				(use --create-dirs to create it)",
+				path.display()),
+			Self::InvalidRemoteName { path } => write!(f,
+				"invalid remote file name: {}",
+				path.display()),
+			Self::RemoveFailed { path, reason } => write!(f,
+				"failed to remove {}: {}",
+				path.display(),
+				reason),
+			Self::MissingStall { path } => write!(f,
+				"registered stall path does not exist: {}",
+				path.display()),
+			Self::PreserveFailed { path, attribute, reason } => write!(f,
+				"failed to copy {} attributes to {}: {}",
+				attribute,
+				path.display(),
+				reason),
+			Self::OverlappingEntries { local, other, overlap } => match overlap {
+				Overlap::NestedRemote => write!(f,
+					"remote file of {} is inside the remote directory of {}",
+					local.display(),
+					other.display()),
+				Overlap::NestedLocal => write!(f,
+					"stalled file {} is inside the stalled directory {}",
+					local.display(),
+					other.display()),
+				Overlap::AliasedLocal => write!(f,
+					"stalled files {} and {} are the same file on \
+					case-insensitive file systems",
+					local.display(),
+					other.display()),
+			},
+		}
+	}
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// WarningKind
+////////////////////////////////////////////////////////////////////////////////
+/// A category of [`Warning`], used to allow warnings of that kind.
+///
+/// [`Warning`]: Warning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[derive(clap::ArgEnum)]
+pub enum WarningKind {
+	/// A stalled file's remote file does not exist.
+	MissingRemote,
+	/// A stalled file does not exist in the stall directory.
+	MissingLocal,
+	/// A file's metadata could not be read.
+	Unreadable,
+	/// A remote file's parent directory does not exist.
+	MissingDir,
+	/// A remote path has no file name.
+	InvalidName,
+	/// A stalled file could not be deleted.
+	RemoveFailed,
+	/// A registered stall path does not exist.
+	MissingStall,
+	/// A file attribute could not be copied.
+	PreserveFailed,
+	/// Two entries' files overlap.
+	Overlapping,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Overlap
+////////////////////////////////////////////////////////////////////////////////
+/// How the files of two stall entries overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Overlap {
+	/// The remote file of an entry is inside the remote directory of another.
+	NestedRemote,
+	/// The stalled file of an entry is inside the stalled directory of
+	/// another.
+	NestedLocal,
+	/// The local paths of two entries differ only by case, or by `.` and `..`
+	/// components.
+	AliasedLocal,
+}